@@ -3,5 +3,7 @@ from flask import Flask
 app = Flask(__name__)
 app.url_map.strict_slashes = False
 
+import azure_devops  # noqa: E402,F401
+import bitbucket  # noqa: E402,F401
 import github  # noqa: E402,F401
 import gitlab  # noqa: E402,F401
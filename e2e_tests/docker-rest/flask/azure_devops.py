@@ -0,0 +1,120 @@
+import base64
+
+from app import app
+from flask import abort, jsonify, make_response, request
+
+# organization -> project -> (visibility, repositories)
+ORGANIZATIONS = {
+    "myorg1": {
+        "myproject1": ("public", ["myrepo1", "myrepo2"]),
+        "myproject2": ("private", ["myrepo3", "disabled"]),
+    },
+    "myorg2": {
+        "myproject1": ("private", ["myrepo4"]),
+    },
+    "maliciousorg": {
+        "..": ("public", ["myrepo5"]),
+    },
+}
+
+
+def error(message, status):
+    return make_response(jsonify({"$id": "1", "message": message}), status)
+
+
+def check_headers():
+    if request.headers.get("accept") != "application/json":
+        app.logger.error("Invalid accept header")
+        abort(500)
+    if request.args.get("api-version") != "7.1":
+        app.logger.error("Invalid API version")
+        abort(500)
+    # Personal access tokens are the password of basic authentication
+    token = base64.b64encode(b":secret-token:myauthtoken").decode()
+    auth_header = request.headers.get("authorization")
+    if auth_header != f"Basic {token}":
+        app.logger.error("Invalid authorization header: %s", auth_header)
+        abort(error("TF400813: The user is not authorized", 401))
+
+
+def project(org, name):
+    visibility, _ = ORGANIZATIONS[org][name]
+    return {"id": f"{org}-{name}", "name": name, "visibility": visibility}
+
+
+def repository(org, project_name, name):
+    return {
+        "id": f"{org}-{project_name}-{name}",
+        "name": name,
+        "project": project(org, project_name),
+        "remoteUrl": f"https://{org}@example.com/{org}/{project_name}/_git/{name}",
+        "sshUrl": f"ssh://git@example.com/v3/{org}/{project_name}/{name}",
+        "defaultBranch": "refs/heads/master",
+        "isDisabled": name == "disabled",
+    }
+
+
+def repositories(org, projects):
+    # Azure DevOps does not page lists of repositories
+    values = [
+        repository(org, project_name, name)
+        for project_name in projects
+        for name in ORGANIZATIONS[org][project_name][1]
+    ]
+    return jsonify({"count": len(values), "value": values})
+
+
+@app.route("/azure-devops/<string:org>/_apis/git/repositories")
+def azure_devops_org_repos(org):
+    check_headers()
+    if org not in ORGANIZATIONS:
+        abort(error(f"Organization {org} not found", 404))
+    return repositories(org, ORGANIZATIONS[org])
+
+
+@app.route("/azure-devops/<string:org>/<string:project_name>/_apis/git/repositories")
+def azure_devops_project_repos(org, project_name):
+    check_headers()
+    if project_name not in ORGANIZATIONS.get(org, {}):
+        abort(error(f"TF200016: The project {project_name} does not exist", 404))
+    return repositories(org, [project_name])
+
+
+@app.route("/azure-devops/<string:org>/_apis/projects/<string:project_name>")
+def azure_devops_project(org, project_name):
+    check_headers()
+    if project_name not in ORGANIZATIONS.get(org, {}):
+        abort(error(f"TF200016: The project {project_name} does not exist", 404))
+    return jsonify(project(org, project_name))
+
+
+@app.route(
+    "/azure-devops/<string:org>/<string:project_name>/_apis/git/repositories",
+    methods=["POST"],
+)
+def azure_devops_create_repo(org, project_name):
+    check_headers()
+    if request.json["project"]["id"] != f"{org}-{project_name}":
+        abort(500, "wrong arguments")
+    created = repository(org, project_name, request.json["name"])
+    created["id"] = "4242"
+    # Empty repositories do not have a default branch
+    del created["defaultBranch"]
+    response = jsonify(created)
+    response.status_code = 201
+    return response
+
+
+@app.route("/azure-devops-profile/_apis/profile/profiles/me")
+def azure_devops_profile():
+    check_headers()
+    return jsonify({"id": "1", "displayName": "My User", "publicAlias": "1"})
+
+
+@app.route("/azure-devops-profile/_apis/accounts")
+def azure_devops_accounts():
+    check_headers()
+    if request.args.get("memberId") != "1":
+        abort(500, "wrong arguments")
+    accounts = [{"accountId": org, "accountName": org} for org in ("myorg1", "myorg2")]
+    return jsonify({"count": len(accounts), "value": accounts})
@@ -0,0 +1,133 @@
+from app import app
+from flask import abort, jsonify, make_response, request
+
+# Bitbucket pages in the response body instead of the "link" header
+PAGES = [[1, 2], [3], [4], [5]]
+
+
+def error(message, status):
+    return make_response(
+        jsonify({"type": "error", "error": {"message": message}}), status
+    )
+
+
+def check_headers():
+    if request.headers.get("accept") != "application/json":
+        app.logger.error("Invalid accept header")
+        abort(500)
+    auth_header = request.headers.get("authorization")
+    if auth_header != "Bearer secret-token:myauthtoken":
+        app.logger.error("Invalid authorization header: %s", auth_header)
+        abort(error("Bad credentials", 401))
+
+
+def project(id, name, namespace, private=False, mainbranch="master"):
+    return {
+        "type": "repository",
+        "uuid": f"{{{id}}}",
+        "name": name,
+        "slug": name,
+        "full_name": f"{namespace}/{name}",
+        "is_private": private,
+        "mainbranch": (
+            None if mainbranch is None else {"type": "branch", "name": mainbranch}
+        ),
+        "links": {
+            "clone": [
+                {
+                    "name": "https",
+                    "href": f"https://myuser1@example.com/{namespace}/{name}.git",
+                },
+                {
+                    "name": "ssh",
+                    "href": f"ssh://git@example.com/{namespace}/{name}.git",
+                },
+            ],
+        },
+    }
+
+
+def paged_projects(namespaces):
+    page = int(request.args.get("page", "1"))
+    if page > len(PAGES):
+        abort(error("Invalid page", 404))
+
+    values = [
+        project(i, f"myproject{i}", namespaces[page - 1], private=(i == 3))
+        for i in PAGES[page - 1]
+    ]
+    response = {
+        "size": sum(len(ids) for ids in PAGES),
+        "page": page,
+        "pagelen": int(request.args.get("pagelen", "10")),
+        "values": values,
+    }
+    if page < len(PAGES):
+        args = request.args.copy()
+        args["page"] = page + 1
+        response["next"] = (
+            f"{request.base_url}?" + "&".join(f"{k}={v}" for k, v in args.items())
+        )
+    return jsonify(response)
+
+
+@app.route("/bitbucket/repositories/<string:workspace>")
+def bitbucket_workspace_repos(workspace):
+    check_headers()
+    if workspace in ("myuser1", "mygroup1"):
+        return paged_projects([workspace] * len(PAGES))
+    if workspace == "malicioususer":
+        return paged_projects(["../../malicioususer"] * len(PAGES))
+    abort(error(f"Workspace {workspace} not found", 404))
+
+
+@app.route("/bitbucket/repositories/")
+def bitbucket_accessible_repos():
+    check_headers()
+    if request.args.get("role") != "member":
+        abort(500, "wrong arguments")
+    return paged_projects(["myuser1", "myuser2", "mygroup1", "mygroup2"])
+
+
+@app.route("/bitbucket/user/")
+def bitbucket_user():
+    check_headers()
+    return jsonify({"type": "user", "username": "myuser1", "uuid": "{1}"})
+
+
+@app.route(
+    "/bitbucket/repositories/<string:workspace>/<string:name>", methods=["POST"]
+)
+def bitbucket_create_repo(workspace, name):
+    check_headers()
+    if request.json.get("scm") != "git":
+        abort(500, "wrong arguments")
+    # Pushing via HTTPS is not supported, so pushing fails
+    return jsonify(
+        project(
+            4242,
+            name,
+            workspace,
+            private=request.json["is_private"],
+            mainbranch=None,
+        )
+    )
+
+
+@app.route(
+    "/bitbucket/repositories/<string:workspace>/<string:name>/forks",
+    methods=["POST"],
+)
+def bitbucket_fork_repo(workspace, name):
+    check_headers()
+    if workspace != "upstreamowner":
+        abort(error("Repository not found", 404))
+    namespace = request.json.get("workspace", {}).get("slug", "myuser1")
+    fork = project(4343, name, namespace)
+    # The tests create a local repository that stands in for the fork
+    fork["links"]["clone"][0]["href"] = (
+        f"file:///tmp/grm-e2e-forks/{namespace}/{name}.git"
+    )
+    response = jsonify(fork)
+    response.status_code = 201
+    return response
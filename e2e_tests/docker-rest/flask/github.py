@@ -1,3 +1,5 @@
+import base64
+import json
 import os.path
 
 import jinja2
@@ -74,6 +76,8 @@ def github_user_repos(user):
     check_headers()
     if user == "myuser1":
         return single_namespaced_projects("myuser1")
+    if user == "malicioususer":
+        return single_namespaced_projects("../../malicioususer")
     return jsonify([])
 
 
@@ -99,3 +103,108 @@ def github_user():
     response = make_response(open("./github_api_user.json").read())
     response.headers["content-type"] = "application/json"
     return response
+
+
+def created_project(namespace):
+    name = request.json["name"]
+    response = jsonify(
+        {
+            "id": 4242,
+            "name": name,
+            "full_name": f"{namespace}/{name}",
+            # Pushing via HTTPS is not supported, so pushing fails
+            "clone_url": f"https://example.com/{namespace}/{name}.git",
+            "ssh_url": f"ssh://git@example.com/{namespace}/{name}.git",
+            "private": request.json["private"],
+        }
+    )
+    response.status_code = 201
+    return response
+
+
+@app.route("/github/user/repos/", methods=["POST"])
+def github_create_own_repo():
+    check_headers()
+    return created_project("myuser1")
+
+
+@app.route("/github/orgs/<string:group>/repos/", methods=["POST"])
+def github_create_group_repo(group):
+    check_headers()
+    if group != "mygroup1":
+        abort(make_response(jsonify({"message": "Not Found"}), 404))
+    return created_project(group)
+
+
+def forked_project(namespace, name):
+    response = jsonify(
+        {
+            "id": 4343,
+            "name": name,
+            "full_name": f"{namespace}/{name}",
+            # The tests create a local repository that stands in for the fork
+            "clone_url": f"file:///tmp/grm-e2e-forks/{namespace}/{name}.git",
+            "ssh_url": f"ssh://git@example.com/{namespace}/{name}.git",
+            "private": False,
+            "default_branch": "master",
+        }
+    )
+    # Forks are created asynchronously
+    response.status_code = 202
+    return response
+
+
+@app.route("/github/repos/<string:owner>/<string:repo>/forks/", methods=["POST"])
+def github_fork_repo(owner, repo):
+    check_headers()
+    if owner != "upstreamowner":
+        abort(make_response(jsonify({"message": "Not Found"}), 404))
+    namespace = request.json.get("organization", "myuser1")
+    return forked_project(namespace, repo)
+
+
+def app_installations(app_id):
+    # App 1 is installed once, app 2 twice
+    installations = [{"id": 42, "account": {"login": "mygroup1"}}]
+    if app_id == 2:
+        installations.append({"id": 43, "account": {"login": "mygroup2"}})
+    return installations
+
+
+def check_app_jwt():
+    if request.headers.get("accept") != "application/vnd.github.v3+json":
+        app.logger.error("Invalid accept header")
+        abort(500)
+    auth_header = request.headers.get("authorization", "")
+    if not auth_header.startswith("Bearer "):
+        abort(make_response(jsonify({"message": "Bad credentials"}), 401))
+
+    def decode(part):
+        return json.loads(base64.urlsafe_b64decode(part + "=" * (-len(part) % 4)))
+
+    header, claims, signature = auth_header.removeprefix("Bearer ").split(".")
+    if decode(header)["alg"] != "RS256" or not signature:
+        abort(make_response(jsonify({"message": "Bad credentials"}), 401))
+    claims = decode(claims)
+    if claims["exp"] - claims["iat"] > 600:
+        abort(make_response(jsonify({"message": "Expiration too far"}), 401))
+    return claims["iss"]
+
+
+@app.route("/github/app/installations/")
+def github_app_installations():
+    app_id = check_app_jwt()
+    return jsonify(app_installations(app_id))
+
+
+@app.route(
+    "/github/app/installations/<int:installation_id>/access_tokens/",
+    methods=["POST"],
+)
+def github_app_installation_token(installation_id):
+    app_id = check_app_jwt()
+    if installation_id not in [i["id"] for i in app_installations(app_id)]:
+        abort(make_response(jsonify({"message": "Not Found"}), 404))
+    response = jsonify({"token": "secret-token:myauthtoken"})
+    response.status_code = 201
+    return response
@@ -74,6 +74,8 @@ def gitlab_user_repos(user):
     check_headers()
     if user == "myuser1":
         return single_namespaced_projects("myuser1")
+    if user == "malicioususer":
+        return single_namespaced_projects("../../malicioususer")
     return jsonify([])
 
 
@@ -85,14 +87,39 @@ def gitlab_group_repos(group):
         and request.args.get("archived") == "false"
     ):
         abort(500, "wrong arguments")
+    if check_access_level():
+        return jsonify([])
     if group == "mygroup1":
         return single_namespaced_projects("mygroup1")
+    if group == "mygroup3":
+        # A group with nested subgroups
+        return mixed_projects(
+            ["mygroup3", "mygroup3/sub1", "mygroup3/sub1/deep", "mygroup3/sub2"]
+        )
     return jsonify([])
 
 
+def check_access_level():
+    if "min_access_level" not in request.args:
+        return False
+    if not (
+        request.args.get("membership") == "true"
+        and request.args["min_access_level"] == "30"
+    ):
+        abort(500, "wrong arguments")
+    return True
+
+
 @app.route("/gitlab/api/v4/projects/")
 def gitlab_own_repos():
     check_headers()
+    developer = check_access_level()
+    if request.args.get("starred") == "true":
+        if developer:
+            return jsonify([])
+        return single_namespaced_projects("mygroup2")
+    if developer:
+        return single_namespaced_projects("myuser2")
     return mixed_projects(["myuser1", "myuser2", "mygroup1", "mygroup2"])
 
 
@@ -102,3 +129,54 @@ def gitlab_user():
     response = make_response(open("./gitlab_api_user.json").read())
     response.headers["content-type"] = "application/json"
     return response
+
+
+@app.route("/gitlab/api/v4/namespaces/<path:namespace>")
+def gitlab_namespace(namespace):
+    check_headers()
+    if namespace != "mygroup1":
+        abort(make_response(jsonify({"message": "404 Namespace Not Found"}), 404))
+    return jsonify({"id": 3, "full_path": namespace})
+
+
+@app.route("/gitlab/api/v4/projects/", methods=["POST"])
+def gitlab_create_repo():
+    check_headers()
+    name = request.json["path"]
+    namespace = "mygroup1" if request.json.get("namespace_id") == 3 else "myuser1"
+    response = jsonify(
+        {
+            "id": 4242,
+            "path": name,
+            "path_with_namespace": f"{namespace}/{name}",
+            # Pushing via HTTPS is not supported, so pushing fails
+            "http_url_to_repo": f"https://example.com/{namespace}/{name}.git",
+            "ssh_url_to_repo": f"ssh://git@example.com/{namespace}/{name}.git",
+            "visibility": request.json["visibility"],
+        }
+    )
+    response.status_code = 201
+    return response
+
+
+@app.route("/gitlab/api/v4/projects/<path:project>/fork", methods=["POST"])
+def gitlab_fork_repo(project):
+    check_headers()
+    owner, _, name = project.rpartition("/")
+    if owner != "upstreamowner":
+        abort(make_response(jsonify({"message": "404 Project Not Found"}), 404))
+    namespace = request.json.get("namespace_path", "myuser1")
+    response = jsonify(
+        {
+            "id": 4343,
+            "path": name,
+            "path_with_namespace": f"{namespace}/{name}",
+            # The tests create a local repository that stands in for the fork
+            "http_url_to_repo": f"file:///tmp/grm-e2e-forks/{namespace}/{name}.git",
+            "ssh_url_to_repo": f"ssh://git@example.com/{namespace}/{name}.git",
+            "visibility": "public",
+            "default_branch": "master",
+        }
+    )
+    response.status_code = 201
+    return response
@@ -25,8 +25,10 @@ def get_temporary_directory(dir=None):
     return tempfile.TemporaryDirectory(dir=dir)
 
 
-def grm(args, cwd=None, is_invalid=False):
-    cmd = subprocess.run([binary] + args, cwd=cwd, capture_output=True, text=True)
+def grm(args, cwd=None, is_invalid=False, input=None):
+    cmd = subprocess.run(
+        [binary] + args, cwd=cwd, capture_output=True, text=True, input=input
+    )
     if not is_invalid:
         assert "usage" not in cmd.stderr.lower()
     print(f"grmcmd: {args}")
@@ -47,6 +49,47 @@ def shell(script):
     cmd.check_returncode()
 
 
+def toml_value(value):
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if isinstance(value, str):
+        return f'"{value}"'
+    return str(value)
+
+
+def write_config(
+    config, root, remote, name="test", remote_name="origin", tree_options="", **options
+):
+    """
+    Writes a configuration with a single tree that contains the repository `name`,
+    cloned from the local repository `remote`
+
+    `options` are set on the repository, e.g. `worktree_setup=True`. `tree_options`
+    is added to the tree as it is.
+    """
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+            {tree_options}
+
+            [[trees.repos]]
+            name = "{name}"
+            """
+        )
+        for key, value in options.items():
+            f.write(f"{key} = {toml_value(value)}\n")
+        f.write(
+            f"""
+            [[trees.repos.remotes]]
+            name = "{remote_name}"
+            url = "file://{remote}"
+            type = "file"
+            """
+        )
+
+
 def checksum_directory(path):
     """
     Gives a "checksum" of a directory that includes all files & directories
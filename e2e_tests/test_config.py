@@ -0,0 +1,110 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import tempfile
+
+import toml
+from helpers import grm
+
+
+def write_config(path, root):
+    with open(path, "w") as f:
+        f.write(
+            f"""
+            [hosts]
+            "gh:" = "https://github.com/"
+
+            [[trees]]
+            name = "projects"
+            root = "{root}/default"
+
+            [[trees.repos]]
+            name = "test"
+
+            [[trees.repos.remotes]]
+            name = "origin"
+            url = "gh:hakoerber/git-repo-manager.git"
+            type = "https"
+
+            [profiles.server]
+            [[profiles.server.trees]]
+            name = "projects"
+            root = "{root}/server"
+            """
+        )
+
+
+def test_config_show():
+    with tempfile.TemporaryDirectory() as root:
+        config = os.path.join(root, "config.toml")
+        write_config(config, root)
+
+        cmd = grm(["config", "show", "--config", config])
+        assert cmd.returncode == 0
+        shown = toml.loads(cmd.stdout)
+        assert "hosts" not in shown
+        assert "profiles" not in shown
+        assert shown["trees"][0]["root"] == f"{root}/default"
+        assert (
+            shown["trees"][0]["repos"][0]["remotes"][0]["url"]
+            == "https://github.com/hakoerber/git-repo-manager.git"
+        )
+
+        cmd = grm(["config", "show", "--config", config, "--profile", "server"])
+        assert cmd.returncode == 0
+        assert toml.loads(cmd.stdout)["trees"][0]["root"] == f"{root}/server"
+
+        cmd = grm(["config", "show", "--config", config, "--format", "yaml"])
+        assert cmd.returncode == 0
+        assert "url: https://github.com/hakoerber/git-repo-manager.git" in cmd.stdout
+
+        cmd = grm(["config", "show", "--config", config, "--format", "json"])
+        assert cmd.returncode == 0
+        assert json.loads(cmd.stdout)["trees"][0]["root"] == f"{root}/default"
+
+
+def test_config_path():
+    with tempfile.TemporaryDirectory() as root:
+        config = os.path.join(root, "config.toml")
+        write_config(config, root)
+
+        cmd = grm(["config", "path"], cwd=root)
+        assert cmd.returncode == 0
+        assert cmd.stdout.strip() == os.path.realpath(config)
+
+        cmd = grm(["config", "path", "--config", "missing.toml"], cwd=root)
+        assert cmd.returncode != 0
+        assert "not found" in cmd.stderr
+
+
+def test_config_edit():
+    with tempfile.TemporaryDirectory() as root:
+        config = os.path.join(root, "config.toml")
+        write_config(config, root)
+
+        editor = os.environ.get("EDITOR")
+        try:
+            os.environ["EDITOR"] = "sed -i s/default/changed/"
+            cmd = grm(["config", "edit", "--config", config])
+            assert cmd.returncode == 0
+            with open(config) as f:
+                assert f"{root}/changed" in f.read()
+
+            # The result is checked, but kept, so it can be fixed
+            os.environ["EDITOR"] = "sed -i s/repos/broken/"
+            cmd = grm(["config", "edit", "--config", config])
+            assert cmd.returncode != 0
+            assert "not valid anymore" in cmd.stderr
+            with open(config) as f:
+                assert "broken" in f.read()
+
+            os.environ["EDITOR"] = "false"
+            cmd = grm(["config", "edit", "--config", config])
+            assert cmd.returncode != 0
+            assert "failed with exit code 1" in cmd.stderr
+        finally:
+            if editor is None:
+                del os.environ["EDITOR"]
+            else:
+                os.environ["EDITOR"] = editor
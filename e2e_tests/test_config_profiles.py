@@ -0,0 +1,81 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+from helpers import grm
+
+
+def write_config(path, root):
+    with open(path, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            name = "projects"
+            root = "{root}/default"
+
+            [[trees.repos]]
+            name = "test"
+
+            [profiles.grm-test-laptop]
+            [[profiles.grm-test-laptop.trees]]
+            name = "projects"
+            root = "{root}/laptop"
+
+            [profiles.server]
+            [[profiles.server.trees]]
+            name = "projects"
+            root = "{root}/server"
+            """
+        )
+
+
+def test_config_profiles_by_hostname():
+    with tempfile.TemporaryDirectory() as root:
+        config = os.path.join(root, "config.toml")
+        write_config(config, root)
+
+        hostname = os.environ.get("HOSTNAME")
+        try:
+            os.environ["HOSTNAME"] = "grm-test-laptop.example.com"
+            cmd = grm(["repos", "sync", "config", "--config", config])
+            assert cmd.returncode == 0
+            assert os.path.isdir(os.path.join(root, "laptop", "test", ".git"))
+            assert not os.path.exists(os.path.join(root, "default"))
+
+            # Without a profile for the host, the configuration is used as it is
+            os.environ["HOSTNAME"] = "grm-test-desktop"
+            cmd = grm(["repos", "sync", "config", "--config", config])
+            assert cmd.returncode == 0
+            assert os.path.isdir(os.path.join(root, "default", "test", ".git"))
+        finally:
+            if hostname is None:
+                del os.environ["HOSTNAME"]
+            else:
+                os.environ["HOSTNAME"] = hostname
+
+
+def test_config_profiles_selected():
+    with tempfile.TemporaryDirectory() as root:
+        config = os.path.join(root, "config.toml")
+        write_config(config, root)
+
+        cmd = grm(
+            ["repos", "sync", "config", "--config", config, "--profile", "server"]
+        )
+        assert cmd.returncode == 0
+        assert os.path.isdir(os.path.join(root, "server", "test", ".git"))
+
+        cmd = grm(["--profile", "missing", "repos", "status", "--config", config])
+        assert cmd.returncode != 0
+        assert 'Profile "missing" not found' in cmd.stderr
+
+        # Writing the configuration back would lose the profiles
+        cmd = grm(
+            ["repos", "new", "--config", config, "other", "--profile", "server"]
+            + ["--template", os.path.join(root, "server", "test")]
+            + ["--author", "me"]
+        )
+        assert cmd.returncode != 0
+        assert "uses profiles" in cmd.stderr
+        assert not os.path.exists(os.path.join(root, "server", "other"))
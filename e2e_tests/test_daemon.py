@@ -0,0 +1,99 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import subprocess
+import tempfile
+import time
+
+import pytest
+from helpers import TempGitFileRemote, binary, grm, write_config
+
+
+def test_daemon_once_with_report():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                cmd = grm(["repos", "sync", "config", "--config", config])
+                assert cmd.returncode == 0
+
+                report_file = os.path.join(tmpdir, "report.json")
+                lock_file = os.path.join(tmpdir, "daemon.lock")
+                cmd = grm(
+                    [
+                        "daemon",
+                        "--config",
+                        config,
+                        "--once",
+                        "--lock-file",
+                        lock_file,
+                        "--report-file",
+                        report_file,
+                    ]
+                )
+                assert cmd.returncode == 0
+
+                with open(report_file) as f:
+                    report = json.load(f)
+
+                assert report["operation"] == "fetch"
+                assert report["success"] is True
+                assert report["repos"] == [{"name": "test", "error": None}]
+                assert report["check_failures"] == []
+                assert report["errors"] == []
+
+
+def test_daemon_single_instance():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                lock_file = os.path.join(tmpdir, "daemon.lock")
+                args = ["daemon", "--config", config, "--lock-file", lock_file]
+
+                first = subprocess.Popen(
+                    [binary] + args + ["--interval", "1h"],
+                    stdout=subprocess.DEVNULL,
+                    stderr=subprocess.DEVNULL,
+                )
+                try:
+                    for _ in range(100):
+                        if os.path.exists(lock_file):
+                            break
+                        time.sleep(0.1)
+                    assert os.path.exists(lock_file)
+
+                    cmd = grm(args + ["--once"])
+                    assert cmd.returncode != 0
+                    assert "already running" in cmd.stderr.lower()
+                finally:
+                    first.kill()
+                    first.wait()
+
+                # The lock of the killed daemon was released with it
+                assert os.path.exists(lock_file)
+                cmd = grm(args + ["--once"])
+                assert cmd.returncode == 0
+
+
+@pytest.mark.parametrize("interval", ["0", "10x", "m", ""])
+def test_daemon_invalid_interval(interval):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        cmd = grm(
+            [
+                "daemon",
+                "--config",
+                os.path.join(tmpdir, "config.toml"),
+                "--interval",
+                interval,
+                "--lock-file",
+                os.path.join(tmpdir, "daemon.lock"),
+            ]
+        )
+        assert cmd.returncode != 0
+        assert "interval" in cmd.stderr.lower()
@@ -0,0 +1,115 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+import pytest
+import toml
+from helpers import grm
+
+PROVIDERS = ["github", "gitlab"]
+
+
+def answers(provider, token="myauthtoken", write_anyway=None):
+    lines = [
+        provider,
+        "",
+        f"echo secret-token:{token}",
+        "/myroot",
+        "",
+        "y",
+        "myuser1, myuser2",
+        "mygroup1",
+        "y",
+    ]
+    if write_anyway is not None:
+        lines.append(write_anyway)
+    return "\n".join(lines) + "\n"
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_init(provider):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        config = os.path.join(tmpdir, "config.toml")
+        cmd = grm(["init", "--config", config], input=answers(provider))
+        assert cmd.returncode == 0
+        assert "Logged in as" in cmd.stdout
+
+        with open(config) as f:
+            output = toml.loads(f.read())
+
+        groups_key = "orgs" if provider == "github" else "groups"
+        assert output["provider"].lower() == provider
+        assert output["token_command"] == "echo secret-token:myauthtoken"
+        assert output["root"] == "/myroot"
+        assert output["worktree"] is True
+        assert "api_url" not in output
+        assert output["filters"]["owner"] is True
+        assert output["filters"]["access"] is True
+        assert output["filters"]["users"] == ["myuser1", "myuser2"]
+        assert output["filters"][groups_key] == ["mygroup1"]
+
+        # The written configuration works as it is
+        cmd = grm(["repos", "find", "config", "--config", config])
+        assert cmd.returncode == 0
+        assert len(toml.loads(cmd.stdout)["trees"]) > 0
+
+
+def test_init_azure_devops():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        config = os.path.join(tmpdir, "config.toml")
+        lines = [
+            "azure-devops",
+            "",
+            "echo secret-token:myauthtoken",
+            "/myroot",
+            "y",
+            "myorg1",
+            "myorg2/myproject1",
+            "n",
+        ]
+        cmd = grm(["init", "--config", config], input="\n".join(lines) + "\n")
+        assert cmd.returncode == 0
+        assert 'Logged in as "My User"' in cmd.stdout
+
+        with open(config) as f:
+            output = toml.loads(f.read())
+
+        # Users do not have repositories of their own on Azure DevOps
+        assert output["filters"]["owner"] is False
+        assert output["filters"]["access"] is True
+        assert output["filters"]["orgs"] == ["myorg1"]
+        assert output["filters"]["groups"] == ["myorg2/myproject1"]
+
+
+@pytest.mark.parametrize("write_anyway", ["y", "n"])
+def test_init_no_access(write_anyway):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        config = os.path.join(tmpdir, "config.toml")
+        cmd = grm(
+            ["init", "--config", config],
+            input=answers("github", token="wrongtoken", write_anyway=write_anyway),
+        )
+        assert "Accessing the forge failed" in cmd.stderr
+        if write_anyway == "y":
+            assert cmd.returncode == 0
+            assert os.path.exists(config)
+        else:
+            assert cmd.returncode != 0
+            assert not os.path.exists(config)
+
+
+def test_init_existing_config():
+    with tempfile.NamedTemporaryFile() as config:
+        cmd = grm(["init", "--config", config.name], input=answers("github"))
+        assert cmd.returncode != 0
+        assert "already exists" in cmd.stderr
+        assert os.path.getsize(config.name) == 0
+
+
+def test_init_no_answers():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        config = os.path.join(tmpdir, "config.toml")
+        cmd = grm(["init", "--config", config], input="")
+        assert cmd.returncode != 0
+        assert not os.path.exists(config)
@@ -0,0 +1,168 @@
+#!/usr/bin/env python3
+
+import contextlib
+import fcntl
+import os
+import tempfile
+import threading
+import time
+
+from helpers import (
+    TempGitFileRemote,
+    TempGitRepositoryWorktree,
+    funcname,
+    grm,
+    write_config,
+)
+
+
+def lock_file(kind, path):
+    runtime_dir = os.environ.get("XDG_RUNTIME_DIR")
+    if runtime_dir:
+        lock_dir = os.path.join(runtime_dir, "grm-locks")
+    else:
+        lock_dir = os.path.join(tempfile.gettempdir(), f"grm-locks-{os.getuid()}")
+    os.makedirs(lock_dir, exist_ok=True)
+
+    # FNV-1a, the same as grm uses
+    hash = 0xCBF29CE484222325
+    for byte in os.path.realpath(path).encode():
+        hash ^= byte
+        hash = (hash * 0x100000001B3) % 2**64
+
+    return os.path.join(lock_dir, f"{kind}-{hash:016x}.lock")
+
+
+@contextlib.contextmanager
+def hold_lock(path):
+    with open(path, "w") as f:
+        fcntl.flock(f, fcntl.LOCK_EX)
+        yield f
+
+
+def is_locked(path):
+    with open(path, "w") as f:
+        try:
+            fcntl.flock(f, fcntl.LOCK_EX | fcntl.LOCK_NB)
+        except BlockingIOError:
+            return True
+        fcntl.flock(f, fcntl.LOCK_UN)
+        return False
+
+
+def test_sync_releases_locks():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                for _ in range(2):
+                    cmd = grm(["repos", "sync", "config", "--config", config])
+                    assert cmd.returncode == 0
+                    assert not is_locked(lock_file("tree", root))
+                    assert not is_locked(lock_file("repo", os.path.join(root, "test")))
+
+
+def test_sync_tree_locked_no_wait():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                tree_lock = lock_file("tree", root)
+                with hold_lock(tree_lock):
+                    cmd = grm(
+                        ["--no-wait", "repos", "sync", "config", "--config", config]
+                    )
+                    assert cmd.returncode != 0
+                    assert "locked by another grm process" in cmd.stderr
+                    assert not os.path.exists(os.path.join(root, "test"))
+
+
+def test_sync_tree_left_over_lock_file():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                # A lock file without a process holding the lock, e.g. from a
+                # killed process
+                tree_lock = lock_file("tree", root)
+                with open(tree_lock, "w") as f:
+                    f.write("1")
+
+                cmd = grm(["repos", "sync", "config", "--config", config, "--no-wait"])
+                assert cmd.returncode == 0
+                assert os.path.exists(os.path.join(root, "test"))
+
+
+def test_sync_tree_locked_wait():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                lock = open(lock_file("tree", root), "w")
+                fcntl.flock(lock, fcntl.LOCK_EX)
+
+                def release():
+                    time.sleep(1)
+                    lock.close()
+
+                thread = threading.Thread(target=release)
+                thread.start()
+                cmd = grm(["repos", "sync", "config", "--config", config, "--wait"])
+                thread.join()
+
+                assert cmd.returncode == 0
+                assert "waiting" in cmd.stdout
+                assert os.path.exists(os.path.join(root, "test"))
+
+
+def test_wait_conflicts_with_no_wait():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        config = os.path.join(tmpdir, "config.toml")
+        cmd = grm(
+            ["repos", "sync", "config", "--config", config, "--wait", "--no-wait"],
+            is_invalid=True,
+        )
+        assert cmd.returncode != 0
+
+
+def test_fetch_repo_locked_no_wait():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                write_config(config, root, remote)
+
+                cmd = grm(["repos", "sync", "config", "--config", config])
+                assert cmd.returncode == 0
+
+                with hold_lock(lock_file("repo", os.path.join(root, "test"))):
+                    cmd = grm(["repos", "fetch", "--config", config, "--no-wait"])
+                    assert cmd.returncode != 0
+                    assert "locked by another grm process" in cmd.stderr
+
+                    cmd = grm(
+                        ["repos", "sync", "config", "--config", config, "--no-wait"]
+                    )
+                    assert cmd.returncode != 0
+                    assert "locked by another grm process" in cmd.stderr
+
+
+def test_worktree_locked_no_wait():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with hold_lock(lock_file("repo", base_dir)):
+            cmd = grm(["wt", "add", "test", "--no-wait"], cwd=base_dir)
+            assert cmd.returncode != 0
+            assert "locked by another grm process" in cmd.stderr
+            assert not os.path.exists(os.path.join(base_dir, "test"))
+
+            # Status does not modify anything, so it does not need the lock
+            cmd = grm(["wt", "status", "--no-wait"], cwd=base_dir)
+            assert cmd.returncode == 0
@@ -0,0 +1,81 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import tempfile
+
+from helpers import TempGitFileRemote, TempGitRepositoryWorktree, funcname, grm
+
+
+def events(output):
+    return [json.loads(line) for line in output.splitlines()]
+
+
+def test_log_json_sync():
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{target}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+
+                        [[trees.repos]]
+                        name = "broken"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file:///does/not/exist"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(
+                    ["--log-json", "repos", "sync", "config", "--config", config.name]
+                )
+                assert cmd.returncode != 0
+                assert len(cmd.stdout) == 0
+
+                log = events(cmd.stderr)
+                assert len(log) > 0
+                for event in log:
+                    assert set(event.keys()) <= {"level", "repo", "message"}
+                    assert event["level"] in ("action", "success", "warning", "error")
+
+                assert {"level": "success", "repo": "test", "message": "OK"} in log
+                assert any(
+                    event["level"] == "error" and event.get("repo") == "broken"
+                    for event in log
+                )
+                assert any(
+                    event["level"] == "action" and "repo" not in event for event in log
+                )
+
+
+def test_log_json_worktree():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["--log-json", "wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stdout) == 0
+
+        log = events(cmd.stderr)
+        assert len(log) > 0
+        assert all(event["level"] == "success" for event in log)
+        assert os.path.exists(os.path.join(base_dir, "test"))
+
+
+def test_log_json_keeps_output():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["--log-json", "wt", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stdout) > 0
+        assert len(cmd.stderr) == 0
@@ -0,0 +1,114 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+import pytest
+from helpers import EmptyDir, grm, shell, write_config
+
+
+def create_remote(remote, project_config):
+    with EmptyDir() as tmp:
+        shell(
+            f"""
+            git -c init.defaultBranch=master init --quiet --bare {remote}
+            cd {tmp}
+            git -c init.defaultBranch=master init --quiet
+            mkdir src docs
+            touch src/main docs/index
+            cat > .grm.toml <<'EOT'
+{project_config}
+EOT
+            git add .
+            git commit --quiet -m "initial"
+            git branch develop
+            git push --quiet {remote} master develop
+            """
+        )
+
+
+@pytest.mark.parametrize("run_hooks", [True, False])
+def test_project_config_sync(run_hooks):
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as remote:
+            create_remote(
+                remote,
+                'default_branch = "develop"\npost_clone = ["touch hooked"]',
+            )
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, worktree_setup=True)
+
+                args = ["repos", "sync", "config", "--config", config.name]
+                if run_hooks:
+                    args += ["--run-hooks"]
+                cmd = grm(args)
+                assert cmd.returncode == 0
+
+                # The default branch of the project is checked out first
+                repo_path = os.path.join(root, "test")
+                assert "develop" in os.listdir(repo_path)
+                assert "master" not in os.listdir(repo_path)
+
+                hooked = os.path.join(repo_path, "develop", "hooked")
+                assert os.path.exists(hooked) == run_hooks
+                assert ("use --run-hooks" in cmd.stderr) != run_hooks
+
+                # Commands only run after cloning
+                cmd = grm(args)
+                assert cmd.returncode == 0
+                assert "use --run-hooks" not in cmd.stderr
+
+
+def test_project_config_worktree():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as remote:
+            create_remote(
+                remote,
+                'persistent_branches = ["develop"]\nsparse = ["src"]',
+            )
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, worktree_setup=True)
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                repo_path = os.path.join(root, "test")
+                cmd = grm(
+                    ["wt", "add", "develop", "--track", "origin/develop"], cwd=repo_path
+                )
+                assert cmd.returncode == 0
+                cmd = grm(["wt", "add", "feature"], cwd=repo_path)
+                assert cmd.returncode == 0
+
+                files = os.listdir(os.path.join(repo_path, "feature"))
+                assert "src" in files
+                assert "docs" not in files
+
+                # The persistent branch of the project is never cleaned up
+                cmd = grm(["wt", "clean"], cwd=repo_path)
+                assert cmd.returncode == 0
+                assert "develop" in os.listdir(repo_path)
+
+                # The configuration of the user wins
+                with open(os.path.join(repo_path, "grm.toml"), "w") as f:
+                    f.write("sparse = []\n")
+                cmd = grm(["wt", "add", "other"], cwd=repo_path)
+                assert cmd.returncode == 0
+                assert "docs" in os.listdir(os.path.join(repo_path, "other"))
+
+
+def test_project_config_invalid():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as remote:
+            create_remote(remote, 'unknown_setting = "x"')
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, worktree_setup=True)
+                # A broken project configuration does not stop anything
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "Error parsing project configuration" in cmd.stderr
+
+                repo_path = os.path.join(root, "test")
+                assert "master" in os.listdir(repo_path)
+                cmd = grm(["wt", "add", "feature"], cwd=repo_path)
+                assert cmd.returncode == 0
+                assert "Ignoring the project configuration" in cmd.stderr
@@ -0,0 +1,104 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+from helpers import (
+    NonGitDir,
+    TempGitRepository,
+    TempGitRepositoryWorktree,
+    funcname,
+    grm,
+    shell,
+)
+
+
+class TempCacheDir:
+    """Points grm to an empty cache directory"""
+
+    def __enter__(self):
+        self.tmpdir = tempfile.TemporaryDirectory()
+        self.previous = os.environ.get("XDG_CACHE_HOME")
+        os.environ["XDG_CACHE_HOME"] = self.tmpdir.name
+        return self.tmpdir.name
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        if self.previous is None:
+            del os.environ["XDG_CACHE_HOME"]
+        else:
+            os.environ["XDG_CACHE_HOME"] = self.previous
+        self.tmpdir.cleanup()
+
+
+def prompt_status(cwd, refresh=False):
+    cmd = grm(["prompt-status"] + (["--refresh"] if refresh else []), cwd=cwd)
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+    return cmd.stdout
+
+
+def test_prompt_status_cached():
+    with TempCacheDir():
+        with TempGitRepository() as git_dir:
+            shell(f"mkdir {git_dir}/subdir")
+
+            # Nothing cached yet
+            assert prompt_status(git_dir) == ""
+
+            shell(f"echo change > {git_dir}/root-commit")
+            cmd = grm(["repos", "status"], cwd=git_dir)
+            assert cmd.returncode == 0
+
+            assert prompt_status(git_dir) == "*\n"
+            assert prompt_status(os.path.join(git_dir, "subdir")) == "*\n"
+
+            # The cache is not updated by the prompt itself
+            shell(f"cd {git_dir} && git checkout --quiet root-commit")
+            assert prompt_status(git_dir) == "*\n"
+
+            assert prompt_status(git_dir, refresh=True) == ""
+            assert prompt_status(git_dir) == ""
+
+
+def test_prompt_status_refresh():
+    with TempCacheDir():
+        with TempGitRepository() as git_dir:
+            shell(f"touch {git_dir}/new-file")
+            assert prompt_status(git_dir, refresh=True) == "*\n"
+            assert prompt_status(git_dir) == "*\n"
+
+
+def test_prompt_status_worktree():
+    with TempCacheDir():
+        with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+            cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+            worktree = os.path.join(base_dir, "test")
+            shell(
+                f"""
+                cd {worktree}
+                echo change > new-file
+                git add new-file
+                git commit --quiet -m "new commit"
+                echo change > uncommitted
+                """
+            )
+
+            cmd = grm(["wt", "status"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+            assert prompt_status(worktree) == "test * ↑1\n"
+
+            # The root of the worktree setup is not a worktree itself
+            assert prompt_status(base_dir) == ""
+
+            shell(f"rm {worktree}/uncommitted")
+            assert prompt_status(worktree, refresh=True) == "test ↑1\n"
+
+
+def test_prompt_status_no_repository():
+    with TempCacheDir():
+        with NonGitDir() as nongit_dir:
+            assert prompt_status(nongit_dir) == ""
+            assert prompt_status(nongit_dir, refresh=True) == ""
@@ -0,0 +1,83 @@
+#!/usr/bin/env python3
+
+import contextlib
+import os
+import subprocess
+import tempfile
+
+import pytest
+from helpers import TempGitFileRemote, grm
+
+
+@contextlib.contextmanager
+def read_only(path):
+    """
+    Makes `path` read-only by bind-mounting it onto itself, which requires root
+    """
+    cmd = subprocess.run(["mount", "--bind", path, path], capture_output=True)
+    if cmd.returncode != 0:
+        pytest.skip("Cannot create bind mounts")
+    try:
+        subprocess.run(["mount", "-o", "remount,bind,ro", path], check=True)
+        yield
+    finally:
+        subprocess.run(["umount", path], check=True)
+
+
+def write_config(config, root, remote, repos):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+            """
+        )
+        for name, worktree_setup in repos:
+            f.write(
+                f"""
+                [[trees.repos]]
+                name = "{name}"
+                worktree_setup = {str(worktree_setup).lower()}
+
+                [[trees.repos.remotes]]
+                name = "origin"
+                url = "file://{remote}"
+                type = "file"
+                """
+            )
+
+
+def test_read_only_filesystem():
+    with TempGitFileRemote() as (remote, _head_commit_sha):
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.NamedTemporaryFile() as config:
+                repos = [("plain", False), ("setup", True)]
+                write_config(config.name, root, remote, repos)
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                setup = os.path.join(root, "setup")
+                with read_only(root):
+                    cmd = grm(["repos", "status", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "plain" in cmd.stdout
+
+                    cmd = grm(["wt", "status"], cwd=setup)
+                    assert cmd.returncode == 0
+
+                    write_config(
+                        config.name, root, remote, repos + [("new", False)]
+                    )
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode != 0
+                    assert f'filesystem of "{root}" is read-only' in cmd.stderr
+                    assert "Cloning" not in cmd.stdout
+                    assert not os.path.exists(os.path.join(root, "new"))
+
+                    cmd = grm(["repos", "fetch", "--config", config.name])
+                    assert cmd.returncode != 0
+                    assert "is read-only" in cmd.stderr
+
+                    cmd = grm(["wt", "add", "test"], cwd=setup)
+                    assert cmd.returncode != 0
+                    assert f'filesystem of "{setup}" is read-only' in cmd.stderr
@@ -0,0 +1,204 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+import git
+import pytest
+from helpers import TempGitFileRemote, grm, shell
+
+
+def write_config(config, root, repos, worktree_setup=False):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+            """
+        )
+        for name, remotes in repos:
+            f.write(
+                f"""
+                [[trees.repos]]
+                name = "{name}"
+                worktree_setup = {"true" if worktree_setup else "false"}
+                """
+            )
+            for remote_name, url in remotes:
+                f.write(
+                    f"""
+                    [[trees.repos.remotes]]
+                    name = "{remote_name}"
+                    url = "{url}"
+                    type = "file"
+                    """
+                )
+
+
+def git_output(repo_path, *args):
+    return subprocess.run(
+        ["git", "-C", repo_path] + list(args),
+        capture_output=True,
+        text=True,
+    ).stdout.strip()
+
+
+def manual_clone(remote, path, origin="origin"):
+    shell(f"git clone --quiet --origin {origin} file://{remote} {path}")
+
+
+@pytest.mark.parametrize("url_suffix", ["", ".git", "/"])
+def test_repos_adopt(url_suffix):
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as elsewhere:
+            with TempGitFileRemote() as (remote, head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    url = f"file://{remote}"
+                    write_config(
+                        config.name,
+                        root,
+                        [("namespace/test", [("origin", url + url_suffix)])],
+                    )
+
+                    source = os.path.join(elsewhere, "my-clone")
+                    manual_clone(remote, source, origin="upstream")
+
+                    cmd = grm(["repos", "adopt", "--config", config.name, source])
+                    assert cmd.returncode == 0
+                    assert "Adopted repository" in cmd.stdout
+                    assert 'Renaming remote "upstream" to "origin"' in cmd.stdout
+
+                    target = os.path.join(root, "namespace", "test")
+                    assert not os.path.exists(source)
+                    assert os.path.exists(target)
+
+                    with git.Repo(target) as repo:
+                        assert repo.head.commit.hexsha == head_commit_sha
+                        assert [r.name for r in repo.remotes] == ["origin"]
+                        assert repo.remotes.origin.url == url + url_suffix
+                    # The upstream configuration is kept
+                    assert git_output(target, "config", "branch.master.remote") == (
+                        "origin"
+                    )
+
+                    # Afterwards, it is a normal managed repository
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "unmanaged" not in cmd.stderr
+
+
+def test_repos_adopt_in_place():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                url = f"file://{remote}"
+                write_config(
+                    config.name,
+                    root,
+                    [("test", [("origin", url), ("fork", url + "-fork")])],
+                )
+
+                target = os.path.join(root, "test")
+                manual_clone(remote, target)
+
+                cmd = grm(["repos", "adopt", "--config", config.name, target])
+                assert cmd.returncode == 0
+                assert "Moving" not in cmd.stdout
+
+                with git.Repo(target) as repo:
+                    assert {r.name for r in repo.remotes} == {"origin", "fork"}
+
+
+def test_repos_adopt_no_match():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as elsewhere:
+            with TempGitFileRemote() as (remote, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    write_config(
+                        config.name,
+                        root,
+                        [("test", [("origin", "https://example.com/other.git")])],
+                    )
+
+                    source = os.path.join(elsewhere, "my-clone")
+                    manual_clone(remote, source)
+
+                    cmd = grm(["repos", "adopt", "--config", config.name, source])
+                    assert cmd.returncode != 0
+                    assert "No repository in the configuration" in cmd.stderr
+                    assert os.path.exists(source)
+                    assert os.listdir(root) == []
+
+
+def test_repos_adopt_ambiguous():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as elsewhere:
+            with TempGitFileRemote() as (remote, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    url = f"file://{remote}"
+                    write_config(
+                        config.name,
+                        root,
+                        [("test1", [("origin", url)]), ("test2", [("origin", url)])],
+                    )
+
+                    source = os.path.join(elsewhere, "my-clone")
+                    manual_clone(remote, source)
+
+                    cmd = grm(["repos", "adopt", "--config", config.name, source])
+                    assert cmd.returncode != 0
+                    assert "Multiple repositories" in cmd.stderr
+                    assert "test1, test2" in cmd.stderr
+                    assert os.path.exists(source)
+
+
+def test_repos_adopt_target_exists():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as elsewhere:
+            with TempGitFileRemote() as (remote, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    url = f"file://{remote}"
+                    write_config(config.name, root, [("test", [("origin", url)])])
+
+                    manual_clone(remote, os.path.join(root, "test"))
+                    source = os.path.join(elsewhere, "my-clone")
+                    manual_clone(remote, source)
+
+                    cmd = grm(["repos", "adopt", "--config", config.name, source])
+                    assert cmd.returncode != 0
+                    assert "already exists" in cmd.stderr
+                    assert os.path.exists(source)
+
+
+def test_repos_adopt_worktree_mismatch():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as elsewhere:
+            with TempGitFileRemote() as (remote, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    url = f"file://{remote}"
+                    write_config(
+                        config.name,
+                        root,
+                        [("test", [("origin", url)])],
+                        worktree_setup=True,
+                    )
+
+                    source = os.path.join(elsewhere, "my-clone")
+                    manual_clone(remote, source)
+
+                    cmd = grm(["repos", "adopt", "--config", config.name, source])
+                    assert cmd.returncode != 0
+                    assert "wt convert" in cmd.stderr
+                    assert os.path.exists(source)
+
+
+def test_repos_adopt_not_a_repository():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as elsewhere:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, [])
+
+                cmd = grm(["repos", "adopt", "--config", config.name, elsewhere])
+                assert cmd.returncode != 0
+                assert "Opening repository failed" in cmd.stderr
@@ -0,0 +1,132 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+from helpers import grm
+
+
+def write_config(config, root):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[web_urls]]
+            host = "ssh.git.example.com"
+            url = "https://git.example.com/{{path}}"
+
+            [[trees]]
+            root = "{root}"
+
+            [[trees.repos]]
+            name = "hakoerber/git-repo-manager"
+
+            [[trees.repos.remotes]]
+            name = "origin"
+            url = "git@github.com:hakoerber/git-repo-manager.git"
+            type = "ssh"
+
+            [[trees.repos.remotes]]
+            name = "mirror"
+            url = "ssh://git@ssh.git.example.com/me/git-repo-manager.git"
+            type = "ssh"
+
+            [[trees.repos]]
+            name = "local"
+
+            [[trees.repos]]
+            name = "unknown"
+
+            [[trees.repos.remotes]]
+            name = "origin"
+            url = "https://code.example.com/unknown.git"
+            type = "https"
+            """
+        )
+
+
+def browse(config, *args, cwd=None):
+    return grm(["repos", "browse", "--config", config, "--print", *args], cwd=cwd)
+
+
+def test_repos_browse():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, root)
+
+            url = "https://github.com/hakoerber/git-repo-manager\n"
+            cmd = browse(config.name, "hakoerber/git-repo-manager")
+            assert cmd.returncode == 0
+            assert cmd.stdout == url
+
+            cmd = browse(
+                config.name, "hakoerber/git-repo-manager", "--remote", "mirror"
+            )
+            assert cmd.returncode == 0
+            assert cmd.stdout == "https://git.example.com/me/git-repo-manager\n"
+
+            # By path, defaulting to the current directory
+            subdir = os.path.join(root, "hakoerber", "git-repo-manager", "src")
+            os.makedirs(subdir)
+            cmd = browse(config.name, subdir)
+            assert cmd.returncode == 0
+            assert cmd.stdout == url
+
+            cmd = browse(config.name, cwd=subdir)
+            assert cmd.returncode == 0
+            assert cmd.stdout == url
+
+
+def test_repos_browse_opens_browser():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, root)
+            output = os.path.join(root, "output")
+            browser = os.path.join(root, "browser")
+            with open(browser, "w") as f:
+                f.write(f'#!/bin/sh\necho "$1" > {output}\n')
+            os.chmod(browser, 0o755)
+
+            os.environ["BROWSER"] = browser
+            try:
+                cmd = grm(
+                    [
+                        "repos",
+                        "browse",
+                        "--config",
+                        config.name,
+                        "hakoerber/git-repo-manager",
+                    ]
+                )
+            finally:
+                del os.environ["BROWSER"]
+            assert cmd.returncode == 0
+            assert len(cmd.stdout) == 0
+            with open(output) as f:
+                assert f.read() == "https://github.com/hakoerber/git-repo-manager\n"
+
+
+def test_repos_browse_invalid():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, root)
+
+            cmd = browse(config.name, "doesnotexist")
+            assert cmd.returncode != 0
+            assert "not part of the configuration" in cmd.stderr
+
+            cmd = browse(config.name, "local")
+            assert cmd.returncode != 0
+            assert "does not have any remotes" in cmd.stderr
+
+            cmd = browse(config.name, "hakoerber/git-repo-manager", "--remote", "gone")
+            assert cmd.returncode != 0
+            assert 'does not have a remote "gone"' in cmd.stderr
+
+            cmd = browse(config.name, "unknown")
+            assert cmd.returncode != 0
+            assert '"code.example.com" is unknown' in cmd.stderr
+            assert "web_urls" in cmd.stderr
+
+            cmd = browse(config.name, cwd=root)
+            assert cmd.returncode != 0
+            assert "not part of any configured repository" in cmd.stderr
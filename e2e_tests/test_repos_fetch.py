@@ -0,0 +1,368 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import tempfile
+
+import git
+import pytest
+import toml
+from helpers import TempGitFileRemote, grm, shell
+
+
+def test_repos_fetch():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                with tempfile.TemporaryDirectory() as clone:
+                    shell(
+                        f"""
+                        git clone file://{remote} {clone}/repo
+                        cd {clone}/repo
+                        echo change > newfile
+                        git add newfile
+                        git commit -m "newfile"
+                        git push origin master
+                    """
+                    )
+
+                cmd = grm(["repos", "fetch", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "test" in cmd.stdout
+
+                with git.Repo(os.path.join(root, "test")) as repo:
+                    assert str(repo.head.commit) == head_commit_sha
+                    assert repo.remotes.origin.refs.master.commit.message.strip() == "newfile"
+
+                # Fetching counts as an update for "repos status --stale-after"
+                with open(os.path.join(root, ".grm-state.toml")) as f:
+                    state = toml.loads(f.read())
+                assert state["repos"][0]["last_fetch"] > 0
+
+
+@pytest.mark.parametrize("restrict_via", ["config", "argument"])
+def test_repos_fetch_only_configured(restrict_via):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with TempGitFileRemote() as (adhoc_remote, _adhoc_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    with open(config.name, "w") as f:
+                        if restrict_via == "config":
+                            f.write(
+                                """
+                                [fetch]
+                                only_configured = true
+                                """
+                            )
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}"
+
+                            [[trees.repos]]
+                            name = "test"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+
+                    repo_path = os.path.join(root, "test")
+                    shell(f"git -C {repo_path} remote add adhoc file://{adhoc_remote}")
+
+                    args = ["repos", "fetch", "--config", config.name]
+                    if restrict_via == "argument":
+                        args += ["--remote", "origin"]
+                    cmd = grm(args)
+                    assert cmd.returncode == 0
+                    assert "Fetched from origin" in cmd.stdout
+
+                    with git.Repo(repo_path) as repo:
+                        assert len(repo.remotes.adhoc.refs) == 0
+
+                    cmd = grm(["repos", "fetch", "--config", config.name])
+                    assert cmd.returncode == 0
+                    with git.Repo(repo_path) as repo:
+                        if restrict_via == "config":
+                            assert len(repo.remotes.adhoc.refs) == 0
+                        else:
+                            assert len(repo.remotes.adhoc.refs) > 0
+
+
+
+@pytest.mark.parametrize("prune_via", [None, "config", "git"])
+def test_repos_fetch_prune(prune_via):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            shell(f"git -C {remote} branch feature master")
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    if prune_via == "config":
+                        f.write(
+                            """
+                            [fetch]
+                            prune = true
+                            """
+                        )
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                repo_path = os.path.join(root, "test")
+                with git.Repo(repo_path) as repo:
+                    assert "origin/feature" in [ref.name for ref in repo.remote().refs]
+
+                if prune_via == "git":
+                    shell(f"git -C {repo_path} config fetch.prune true")
+                shell(f"git -C {remote} branch -D feature")
+
+                cmd = grm(["repos", "fetch", "--config", config.name])
+                assert cmd.returncode == 0
+
+                with git.Repo(repo_path) as repo:
+                    refs = [ref.name for ref in repo.remote().refs]
+                    assert "origin/master" in refs
+                    if prune_via is None:
+                        assert "origin/feature" in refs
+                    else:
+                        assert "origin/feature" not in refs
+
+
+def test_repos_fetch_unknown_remote():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                cmd = grm(
+                    ["repos", "fetch", "--config", config.name, "--remote", "upstream"]
+                )
+                assert cmd.returncode != 0
+                assert 'Remote "upstream" does not exist' in cmd.stderr
+
+
+def test_repos_fetch_retry():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [retry]
+                        retries = 2
+                        backoff = 0
+
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                # Nothing listens on port 1, so connecting fails every time
+                repo = os.path.join(root, "test")
+                shell(f"git -C {repo} remote set-url origin http://127.0.0.1:1/test")
+                cmd = grm(["repos", "fetch", "--config", config.name])
+                assert cmd.returncode != 0
+                assert cmd.stderr.count("Retrying in 0s") == 2
+                assert "(2/2)" in cmd.stderr
+
+                # A missing repository does not appear by retrying
+                shell(f"git -C {repo} remote set-url origin file:///doesnotexist")
+                cmd = grm(["repos", "fetch", "--config", config.name])
+                assert cmd.returncode != 0
+                assert "Retrying" not in cmd.stderr
+
+
+def test_repos_fetch_missing_repo():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{root}"
+
+                    [[trees.repos]]
+                    name = "test"
+                    """
+                )
+
+            cmd = grm(["repos", "fetch", "--config", config.name])
+            assert cmd.returncode != 0
+            assert "does not exist" in cmd.stderr.lower()
+
+
+def test_repos_fetch_notify_command():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            report_file = os.path.join(tmpdir, "report.json")
+            config = os.path.join(tmpdir, "config.toml")
+            with open(config, "w") as f:
+                f.write(
+                    f"""
+                    [notify]
+                    command = "cat > {report_file}"
+
+                    [[trees]]
+                    root = "{root}"
+
+                    [[trees.repos]]
+                    name = "test"
+                    """
+                )
+
+            cmd = grm(["repos", "fetch", "--config", config])
+            assert cmd.returncode != 0
+
+            with open(report_file) as f:
+                report = json.load(f)
+
+            assert report["operation"] == "fetch"
+            assert report["success"] is False
+            assert len(report["repos"]) == 1
+            assert report["repos"][0]["name"] == "test"
+            assert "does not exist" in report["repos"][0]["error"].lower()
+
+
+def test_repos_fetch_changes():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                report_file = os.path.join(tmpdir, "report.json")
+                config = os.path.join(tmpdir, "config.toml")
+                with open(config, "w") as f:
+                    f.write(
+                        f"""
+                        [notify]
+                        command = "cat > {report_file}"
+
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config])
+                assert cmd.returncode == 0
+
+                shell(
+                    f"""
+                    git clone file://{remote} {tmpdir}/repo
+                    cd {tmpdir}/repo
+                    echo one > one
+                    git add one
+                    git commit --author "Alice <alice@example.com>" -m "one"
+                    echo two > two
+                    git add two
+                    git commit --author "Bob <bob@example.com>" -m "two"
+                    echo three > three
+                    git add three
+                    git commit --author "Alice <alice@example.com>" -m "three"
+                    git push origin master
+                    git checkout -b feature
+                    echo four > four
+                    git add four
+                    git commit --author "Bob <bob@example.com>" -m "four"
+                    git push origin feature
+                """
+                )
+
+                cmd = grm(["repos", "fetch", "--config", config])
+                assert cmd.returncode == 0
+                assert "origin/master: 3 new commits by Alice, Bob" in cmd.stdout
+                assert "origin/feature: 1 new commit by Bob" in cmd.stdout
+
+                with open(report_file) as f:
+                    report = json.load(f)
+
+                assert report["success"] is True
+                assert report["repos"][0]["changes"] == [
+                    {"branch": "origin/feature", "commits": 1, "authors": ["Bob"]},
+                    {
+                        "branch": "origin/master",
+                        "commits": 3,
+                        "authors": ["Alice", "Bob"],
+                    },
+                ]
+
+                # Nothing changed since the last fetch
+                cmd = grm(["repos", "fetch", "--config", config])
+                assert cmd.returncode == 0
+                assert "new commit" not in cmd.stdout
+
+                with open(report_file) as f:
+                    report = json.load(f)
+
+                assert "changes" not in report["repos"][0]
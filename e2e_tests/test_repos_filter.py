@@ -0,0 +1,120 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+import git
+from helpers import grm, shell, write_config
+
+# A partial clone in a namespace, from a remote that is not called "origin"
+filter_repo = {"name": "ns/test", "remote_name": "upstream", "filter": "blob:none"}
+
+
+class FilterableRemote:
+    """A bare remote with a file that only exists in its history"""
+
+    def __enter__(self):
+        self.tmpdir = tempfile.TemporaryDirectory()
+        shell(
+            f"""
+            cd {self.tmpdir.name}
+            git -c init.defaultBranch=master init
+            echo old > old-file
+            git add old-file
+            git commit -m "add old-file"
+            git rm old-file
+            echo new > new-file
+            git add new-file
+            git commit -m "replace old-file"
+            git ls-files | xargs rm -rf
+            mv .git/* .
+            git config core.bare true
+            git config uploadpack.allowFilter true
+            """
+        )
+        return self.tmpdir.name
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self.tmpdir.cleanup()
+
+
+def missing_objects(repo_path):
+    cmd = subprocess.run(
+        ["git", "-C", repo_path, "rev-list", "--objects", "--all", "--missing=print"],
+        capture_output=True,
+        text=True,
+    )
+    assert cmd.returncode == 0
+    return [line for line in cmd.stdout.splitlines() if line.startswith("?")]
+
+
+def test_repos_filter():
+    with tempfile.TemporaryDirectory() as root:
+        with FilterableRemote() as remote:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, **filter_repo)
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "failed" not in cmd.stdout.lower()
+
+                repo_path = os.path.join(root, "ns", "test")
+                repo = git.Repo(repo_path)
+                assert [remote.name for remote in repo.remotes] == ["upstream"]
+                assert repo.active_branch.name == "master"
+                assert repo.active_branch.tracking_branch().name == "upstream/master"
+                assert os.path.exists(os.path.join(repo_path, "new-file"))
+
+                # The blob of the deleted file was left out
+                assert len(missing_objects(repo_path)) == 1
+
+                # Syncing again leaves the partial clone alone
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert len(missing_objects(repo_path)) == 1
+
+                cmd = grm(["repos", "status", "--config", config.name])
+                assert cmd.returncode == 0
+
+                cmd = grm(["repos", "unfilter", "--config", config.name, "ns/test"])
+                assert cmd.returncode == 0
+                assert missing_objects(repo_path) == []
+                cmd = subprocess.run(
+                    ["git", "-C", repo_path, "config", "remote.upstream.promisor"],
+                    capture_output=True,
+                )
+                assert cmd.returncode == 1
+
+                cmd = grm(["repos", "unfilter", "--config", config.name, "ns/test"])
+                assert cmd.returncode != 0
+                assert "is not a partial clone" in cmd.stderr
+
+
+def test_repos_unfilter_unknown():
+    with tempfile.TemporaryDirectory() as root:
+        with FilterableRemote() as remote:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, **filter_repo)
+
+                cmd = grm(["repos", "unfilter", "--config", config.name, "missing"])
+                assert cmd.returncode != 0
+                assert "not part of the configuration" in cmd.stderr
+
+                cmd = grm(["repos", "unfilter", "--config", config.name, "ns/test"])
+                assert cmd.returncode != 0
+                assert "does not exist" in cmd.stderr
+
+
+def test_repos_filter_worktree():
+    with tempfile.TemporaryDirectory() as root:
+        with FilterableRemote() as remote:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(
+                    config.name, root, remote, worktree_setup=True, **filter_repo
+                )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode != 0
+                assert "cannot be used with a worktree setup" in cmd.stderr
+                assert not os.path.exists(os.path.join(root, "ns", "test"))
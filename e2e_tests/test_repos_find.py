@@ -1,7 +1,9 @@
 #!/usr/bin/env python3
 
+import json
 import os
 import re
+import subprocess
 import tempfile
 
 import pytest
@@ -65,7 +67,7 @@ def test_repos_find_non_git_repos():
 
 
 @pytest.mark.parametrize("default_format", [True, False])
-@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("configtype", ["toml", "yaml", "json"])
 @pytest.mark.parametrize("exclude", [None, "^.*/repo2$", "^not_matching$"])
 def test_repos_find(configtype, exclude, default_format):
     with tempfile.TemporaryDirectory() as tmpdir:
@@ -117,6 +119,8 @@ def test_repos_find(configtype, exclude, default_format):
             output = toml.loads(cmd.stdout)
         elif configtype == "yaml":
             output = yaml.safe_load(cmd.stdout)
+        elif configtype == "json":
+            output = json.loads(cmd.stdout)
         else:
             raise NotImplementedError()
 
@@ -130,7 +134,17 @@ def test_repos_find(configtype, exclude, default_format):
             assert isinstance(tree["repos"], list)
             assert len(tree["repos"]) == (1 if exclude == "^.*/repo2$" else 2)
 
-            repo1 = [r for r in tree["repos"] if r["name"] == "repo1"][0]
+            # Repositories are found by directory, as the name may come from the
+            # remote
+            def directory(repo):
+                return repo.get("dir", repo["name"])
+
+            repo1 = [r for r in tree["repos"] if directory(r) == "repo1"][0]
+            if exclude == "^.*/repo2$":
+                # Without the actual "repo2", the name of the remote is free
+                assert repo1["name"] == "repo2"
+            else:
+                assert repo1["name"] == "repo1"
             assert repo1["worktree_setup"] is False
             assert isinstance(repo1["remotes"], list)
             assert len(repo1["remotes"]) == 2
@@ -146,9 +160,9 @@ def test_repos_find(configtype, exclude, default_format):
             assert someremote["url"] == "ssh://example.com/repo2.git"
 
             if exclude == "^.*/repo2$":
-                assert [r for r in tree["repos"] if r["name"] == "repo2"] == []
+                assert [r for r in tree["repos"] if directory(r) == "repo2"] == []
             else:
-                repo2 = [r for r in tree["repos"] if r["name"] == "repo2"][0]
+                repo2 = [r for r in tree["repos"] if directory(r) == "repo2"][0]
                 assert repo2["worktree_setup"] is False
                 assert isinstance(repo1["remotes"], list)
                 assert len(repo2["remotes"]) == 1
@@ -159,8 +173,108 @@ def test_repos_find(configtype, exclude, default_format):
                 assert origin["url"] == "https://example.com/repo2.git"
 
 
+def test_repos_find_dir_differs_from_remote():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        with tempfile.TemporaryDirectory() as remotes:
+            shell(
+                f"""
+                cd {remotes}
+                git -c init.defaultBranch=master init --quiet --bare my-dotfiles-2024.git
+                cd {tmpdir}
+                git clone --quiet file://{remotes}/my-dotfiles-2024.git dotfiles
+                mkdir work
+                (
+                    cd ./work
+                    git -c init.defaultBranch=master init --quiet grm
+                    cd grm
+                    git remote add origin https://github.com/me/git-repo-manager.git
+                )
+                git -c init.defaultBranch=master init --quiet same
+                (
+                    cd ./same
+                    git remote add origin https://github.com/me/same.git
+                )
+            """
+            )
+
+            cmd = grm(["repos", "find", "local", tmpdir])
+            assert cmd.returncode == 0
+            assert len(cmd.stderr) == 0
+
+            repos = toml.loads(cmd.stdout)["trees"][0]["repos"]
+            dirs = {repo["name"]: repo.get("dir") for repo in repos}
+            assert dirs == {
+                "my-dotfiles-2024": "dotfiles",
+                "me/git-repo-manager": "work/grm",
+                "same": None,
+            }
+
+            # The generated configuration points to the existing directories
+            with tempfile.NamedTemporaryFile("w") as config:
+                config.write(cmd.stdout)
+                config.flush()
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "unmanaged" not in cmd.stderr.lower()
+                assert not os.path.exists(os.path.join(tmpdir, "my-dotfiles-2024"))
+                assert not os.path.exists(os.path.join(tmpdir, "me"))
+
+
+@pytest.mark.parametrize("skip_jj", [True, False])
+def test_repos_find_jj_colocated(skip_jj):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        shell(
+            f"""
+            git -c init.defaultBranch=master init {tmpdir}/git
+            git -c init.defaultBranch=master init {tmpdir}/jj
+            mkdir {tmpdir}/jj/.jj
+        """
+        )
+
+        args = ["repos", "find", "local", tmpdir]
+        if skip_jj:
+            args += ["--skip-jj"]
+        cmd = grm(args)
+        assert cmd.returncode == 0
+
+        output = toml.loads(cmd.stdout)
+        names = sorted(repo["name"] for repo in output["trees"][0]["repos"])
+        if skip_jj:
+            assert names == ["git"]
+            assert "jj: colocated with jj" in cmd.stderr.lower()
+        else:
+            assert names == ["git", "jj"]
+            assert len(cmd.stderr) == 0
+
+
+@pytest.mark.parametrize("verbose", [True, False])
+def test_repos_find_exclude_summary(verbose):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        for i in range(5):
+            shell(f"git -c init.defaultBranch=master init {tmpdir}/excluded{i}")
+        shell(f"git -c init.defaultBranch=master init {tmpdir}/included")
+
+        args = ["repos", "find", "local", tmpdir, "--exclude", "/excluded[0-9]$"]
+        if verbose:
+            args += ["--verbose"]
+        cmd = grm(args)
+        assert cmd.returncode == 0
+
+        lines = cmd.stderr.strip().splitlines()
+        if verbose:
+            assert len(lines) == 5
+            assert all("[skipped]" in line for line in lines)
+        else:
+            assert len(lines) == 1
+            assert '5 paths matching "/excluded[0-9]$"' in lines[0]
+
+        output = toml.loads(cmd.stdout)
+        assert [repo["name"] for repo in output["trees"][0]["repos"]] == ["included"]
+
+
 @pytest.mark.parametrize("default_format", [True, False])
-@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("configtype", ["toml", "yaml", "json"])
 def test_repos_find_in_root(configtype, default_format):
     with TempGitRepository() as repo_dir:
         args = ["repos", "find", "local", repo_dir]
@@ -174,6 +288,8 @@ def test_repos_find_in_root(configtype, default_format):
             output = toml.loads(cmd.stdout)
         elif configtype == "yaml":
             output = yaml.safe_load(cmd.stdout)
+        elif configtype == "json":
+            output = json.loads(cmd.stdout)
         else:
             raise NotImplementedError()
 
@@ -203,7 +319,7 @@ def test_repos_find_in_root(configtype, default_format):
             assert someremote["type"] == "file"
 
 
-@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("configtype", ["toml", "yaml", "json"])
 @pytest.mark.parametrize("default_format", [True, False])
 def test_repos_find_with_invalid_repo(configtype, default_format):
     with tempfile.TemporaryDirectory() as tmpdir:
@@ -245,10 +361,21 @@ def test_repos_find_with_invalid_repo(configtype, default_format):
         assert cmd.returncode == 0
         assert "broken" in cmd.stderr
 
+        # The broken repository is part of the output as a commented-out stub
+        if default_format or configtype == "toml":
+            assert '# name = "broken_repo"' in cmd.stdout.splitlines()
+        elif configtype == "yaml":
+            assert "  # - name: broken_repo" in cmd.stdout.splitlines()
+        else:
+            # JSON has no comments, so it is only reported
+            assert "Skipped broken_repo: " in cmd.stderr
+
         if default_format or configtype == "toml":
             output = toml.loads(cmd.stdout)
         elif configtype == "yaml":
             output = yaml.safe_load(cmd.stdout)
+        elif configtype == "json":
+            output = json.loads(cmd.stdout)
         else:
             raise NotImplementedError()
 
@@ -286,3 +413,123 @@ def test_repos_find_with_invalid_repo(configtype, default_format):
             assert set(origin.keys()) == {"name", "type", "url"}
             assert origin["type"] == "https"
             assert origin["url"] == "https://example.com/repo2.git"
+
+
+@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+def test_repos_find_with_invalid_remote_url(configtype):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        shell(
+            f"""
+            cd {tmpdir}
+            git -c init.defaultBranch=master init repo1
+            git -C repo1 remote add origin https://example.com/repo1.git
+            git -C repo1 remote add weird "not a url"
+        """
+        )
+
+        cmd = grm(["repos", "find", "local", tmpdir, "--format", configtype])
+        assert cmd.returncode == 0
+        assert "not a url" in cmd.stderr
+
+        if configtype == "toml":
+            output = toml.loads(cmd.stdout)
+            stub = '# url = "not a url"'
+        else:
+            output = yaml.safe_load(cmd.stdout)
+            stub = "  #     url: not a url"
+        assert stub in cmd.stdout.splitlines()
+
+        repo1 = output["trees"][0]["repos"][0]
+        assert [remote["name"] for remote in repo1["remotes"]] == ["origin"]
+
+
+def test_repos_find_non_utf8_path():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        shell(f"cd {tmpdir} && git -c init.defaultBranch=master init repo1")
+        subprocess.run(
+            ["git", "init", "--quiet", os.path.join(tmpdir.encode(), b"invalid\xff")],
+            check=True,
+        )
+
+        cmd = grm(["repos", "find", "local", tmpdir])
+        assert cmd.returncode == 0
+        assert "is not valid UTF-8" in cmd.stderr
+        assert "# Skipped: " in cmd.stdout
+
+        output = toml.loads(cmd.stdout)
+        assert [repo["name"] for repo in output["trees"][0]["repos"]] == ["repo1"]
+
+
+@pytest.mark.parametrize("follow_symlinks", [True, False])
+def test_repos_find_symlinks(follow_symlinks):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        with tempfile.TemporaryDirectory() as outside:
+            shell(
+                f"""
+                cd {outside}
+                mkdir -p linked deeper/deep
+                (
+                    cd ./linked
+                    git -c init.defaultBranch=master init
+                )
+                (
+                    cd ./deeper/deep
+                    git -c init.defaultBranch=master init
+                )
+
+                cd {tmpdir}
+                mkdir repo1
+                (
+                    cd ./repo1
+                    git -c init.defaultBranch=master init
+                )
+                ln -s {outside}/linked ./link
+                mkdir nested
+                ln -s {outside}/deeper ./nested/inner
+                ln -s {tmpdir} ./loop
+                ln -s {tmpdir}/repo1 ./alias
+            """
+            )
+
+            args = ["repos", "find", "local", tmpdir]
+            if follow_symlinks:
+                args += ["--follow-symlinks"]
+            cmd = grm(args)
+            assert cmd.returncode == 0
+            assert len(cmd.stderr) == 0
+
+            output = toml.loads(cmd.stdout)
+            assert len(output["trees"]) == 1
+            tree = output["trees"][0]
+            names = sorted([r["name"] for r in tree["repos"]])
+            if follow_symlinks:
+                assert names == ["link", "nested/inner/deep", "repo1"]
+            else:
+                assert names == ["repo1"]
+
+
+def test_repos_find_symlinked_root():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        with tempfile.TemporaryDirectory() as linkdir:
+            shell(
+                f"""
+                cd {tmpdir}
+                mkdir repo1
+                (
+                    cd ./repo1
+                    git -c init.defaultBranch=master init
+                )
+            """
+            )
+            root_link = os.path.join(linkdir, "root")
+            os.symlink(tmpdir, root_link)
+
+            cmd = grm(["repos", "find", "local", root_link])
+            assert cmd.returncode == 0
+            assert len(cmd.stderr) == 0
+
+            output = toml.loads(cmd.stdout)
+            assert len(output["trees"]) == 1
+            tree = output["trees"][0]
+            assert tree["root"] == os.path.realpath(tmpdir)
+            assert [r["name"] for r in tree["repos"]] == ["repo1"]
@@ -1,7 +1,9 @@
 #!/usr/bin/env python3
 
+import json
 import os
 import re
+import subprocess
 import tempfile
 
 import pytest
@@ -133,7 +135,7 @@ def test_repos_find_remote_wrong_token(provider, use_config):
 
 @pytest.mark.parametrize("provider", PROVIDERS)
 @pytest.mark.parametrize("default", [True, False])
-@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("configtype", ["toml", "yaml", "json"])
 @pytest.mark.parametrize("use_config", [True, False])
 def test_repos_find_remote_no_filter(provider, configtype, default, use_config):
     if use_config:
@@ -173,6 +175,8 @@ def test_repos_find_remote_no_filter(provider, configtype, default, use_config):
         output = toml.loads(cmd.stdout)
     elif configtype == "yaml":
         output = yaml.safe_load(cmd.stdout)
+    elif configtype == "json":
+        output = json.loads(cmd.stdout)
     else:
         raise NotImplementedError()
 
@@ -182,9 +186,22 @@ def test_repos_find_remote_no_filter(provider, configtype, default, use_config):
     assert len(output["trees"]) == 0
 
 
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_repos_find_remote_config_from_stdin(provider):
+    config = f"""
+        provider = "{provider}"
+        token_command = "echo secret-token:myauthtoken"
+        root = "/myroot"
+        """
+    cmd = grm(["repos", "find", "config", "--config", "-"], input=config)
+    assert cmd.returncode == 0
+    assert "did not specify any filters" in cmd.stderr.lower()
+    assert toml.loads(cmd.stdout) == {"trees": []}
+
+
 @pytest.mark.parametrize("provider", PROVIDERS)
 @pytest.mark.parametrize("configtype_default", [True, False])
-@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("configtype", ["toml", "yaml", "json"])
 @pytest.mark.parametrize("use_config", [True, False])
 def test_repos_find_remote_user_empty(
     provider, configtype, configtype_default, use_config
@@ -231,6 +248,8 @@ def test_repos_find_remote_user_empty(
         output = toml.loads(cmd.stdout)
     elif configtype == "yaml":
         output = yaml.safe_load(cmd.stdout)
+    elif configtype == "json":
+        output = json.loads(cmd.stdout)
     else:
         raise NotImplementedError()
 
@@ -355,6 +374,7 @@ def test_repos_find_remote_user(
             0
         ]
         assert repo["worktree_setup"] is (not worktree_default and worktree)
+        assert repo["default_branch"] == ("main" if i == 2 else "master")
         assert isinstance(repo["remotes"], list)
         assert len(repo["remotes"]) == 1
         if override_remote_name:
@@ -955,3 +975,752 @@ def test_repos_find_remote_owner(
             repo["remotes"][0]["url"] == "https://example.com/mygroup2/myproject5.git"
         )
         assert repo["remotes"][0]["type"] == "https"
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_repos_find_remote_multiple_accounts(provider):
+    with tempfile.NamedTemporaryFile() as config:
+        with open(config.name, "w") as f:
+            f.write(
+                f"""
+            provider = "{provider}"
+            root = "/myroot"
+
+            [filters]
+            users = ["myuser1"]
+
+            [[accounts]]
+            token_command = "echo secret-token:myauthtoken"
+
+            [[accounts]]
+            token_command = "echo secret-token:myauthtoken"
+            [accounts.filters]
+            groups = ["mygroup1"]
+
+            # Sees the same repositories as the first account
+            [[accounts]]
+            token_command = "echo secret-token:myauthtoken"
+            [accounts.filters]
+            users = ["myuser1"]
+            """
+            )
+        cmd = grm(["repos", "find", "config", "--config", config.name])
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    assert len(output["trees"]) == 2
+
+    trees = {tree["root"]: tree["repos"] for tree in output["trees"]}
+    assert set(trees.keys()) == {"/myroot/myuser1", "/myroot/mygroup1"}
+    for repos in trees.values():
+        assert sorted(repo["name"] for repo in repos) == [
+            f"myproject{i}" for i in range(1, 6)
+        ]
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_repos_find_remote_multiple_accounts_wrong_token(provider):
+    with tempfile.NamedTemporaryFile() as config:
+        with open(config.name, "w") as f:
+            f.write(
+                f"""
+            provider = "{provider}"
+            root = "/myroot"
+
+            [filters]
+            access = true
+
+            [[accounts]]
+            token_command = "echo secret-token:myauthtoken"
+
+            [[accounts]]
+            token_command = "echo wrongtoken"
+            """
+            )
+        cmd = grm(["repos", "find", "config", "--config", config.name])
+
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "bad credentials" in cmd.stderr.lower()
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_repos_find_remote_accounts_and_token_command(provider):
+    with tempfile.NamedTemporaryFile() as config:
+        with open(config.name, "w") as f:
+            f.write(
+                f"""
+            provider = "{provider}"
+            token_command = "echo secret-token:myauthtoken"
+            root = "/myroot"
+
+            [filters]
+            access = true
+
+            [[accounts]]
+            token_command = "echo secret-token:myauthtoken"
+            """
+            )
+        cmd = grm(["repos", "find", "config", "--config", config.name])
+
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "token_command" in cmd.stderr
+
+
+def find_remote_with_github_app(app, filters, provider="github"):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        key = os.path.join(tmpdir, "app.pem")
+        subprocess.run(
+            ["openssl", "genrsa", "-out", key, "2048"],
+            check=True,
+            capture_output=True,
+        )
+        config = os.path.join(tmpdir, "config.toml")
+        with open(config, "w") as f:
+            f.write(
+                f"""
+            provider = "{provider}"
+            root = "/myroot"
+
+            [github_app]
+            private_key = "{key}"
+            {app}
+
+            [filters]
+            {filters}
+            """
+            )
+        return grm(["repos", "find", "config", "--config", config])
+
+
+@pytest.mark.parametrize("installation_id", [None, 42])
+def test_repos_find_remote_github_app(installation_id):
+    app = "app_id = 1"
+    if installation_id is not None:
+        app += f"\ninstallation_id = {installation_id}"
+    cmd = find_remote_with_github_app(app, 'orgs = ["mygroup1"]')
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    assert len(output["trees"]) == 1
+    assert output["trees"][0]["root"] == "/myroot/mygroup1"
+    assert sorted(repo["name"] for repo in output["trees"][0]["repos"]) == [
+        f"myproject{i}" for i in range(1, 6)
+    ]
+
+
+def test_repos_find_remote_github_app_multiple_installations():
+    # App 2 is installed twice
+    cmd = find_remote_with_github_app("app_id = 2", 'orgs = ["mygroup1"]')
+
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "installation_id" in cmd.stderr
+    assert "42 (mygroup1), 43 (mygroup2)" in cmd.stderr
+
+    cmd = find_remote_with_github_app(
+        "app_id = 2\ninstallation_id = 43", 'orgs = ["mygroup1"]'
+    )
+    assert cmd.returncode == 0
+
+
+@pytest.mark.parametrize("filters", ["owner = true", "access = true"])
+def test_repos_find_remote_github_app_user_filters(filters):
+    cmd = find_remote_with_github_app("app_id = 1", filters)
+
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "cannot be used with a GitHub App" in cmd.stderr
+
+
+def test_repos_find_remote_github_app_gitlab():
+    cmd = find_remote_with_github_app(
+        "app_id = 1", 'groups = ["mygroup1"]', provider="gitlab"
+    )
+
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "can only be used with GitHub" in cmd.stderr
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_repos_find_remote_path_traversal(provider):
+    # The mock API returns projects in the namespace "../../malicioususer"
+    cmd = grm(
+        [
+            "repos",
+            "find",
+            "remote",
+            "--provider",
+            provider,
+            "--token-command",
+            "echo secret-token:myauthtoken",
+            "--root",
+            "/myroot",
+            "--user",
+            "malicioususer",
+        ]
+    )
+    assert cmd.returncode == 0
+    assert "invalid name" in cmd.stderr
+    assert "../../malicioususer" in cmd.stderr
+
+    output = toml.loads(cmd.stdout)
+    assert len(output["trees"]) == 0
+
+
+def find_remote_with_pagination(provider, use_config, per_page, max_requests):
+    if use_config:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    provider = "{provider}"
+                    token_command = "echo secret-token:myauthtoken"
+                    root = "/myroot"
+                    per_page = {per_page}
+                    max_requests = {max_requests}
+
+                    [filters]
+                    users = ["myuser1"]
+                    """
+                )
+            return grm(["repos", "find", "config", "--config", config.name])
+    return grm(
+        [
+            "repos",
+            "find",
+            "remote",
+            "--provider",
+            provider,
+            "--token-command",
+            "echo secret-token:myauthtoken",
+            "--root",
+            "/myroot",
+            "--user",
+            "myuser1",
+            "--per-page",
+            str(per_page),
+            "--max-requests",
+            str(max_requests),
+        ]
+    )
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_pagination(provider, use_config):
+    # The mock API returns the projects of a user on four pages
+    cmd = find_remote_with_pagination(provider, use_config, 50, 4)
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    assert len(output["trees"]) == 1
+    assert sorted(repo["name"] for repo in output["trees"][0]["repos"]) == [
+        f"myproject{i}" for i in range(1, 6)
+    ]
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_max_requests_exceeded(provider, use_config):
+    cmd = find_remote_with_pagination(provider, use_config, 50, 3)
+
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "limit of 3 API requests" in cmd.stderr
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+@pytest.mark.parametrize("force_ssh", [True, False])
+def test_repos_find_remote_snapshot(provider, force_ssh):
+    with tempfile.TemporaryDirectory() as tmpdir:
+        snapshot = os.path.join(tmpdir, "snapshot.json")
+        args = ["repos", "find", "remote"]
+        if force_ssh:
+            args += ["--force-ssh"]
+
+        cmd = grm(
+            args
+            + ["--root", "/myroot", "--provider", provider, "--user", "myuser1"]
+            + ["--token-command", "echo secret-token:myauthtoken"]
+            + ["--snapshot", snapshot]
+        )
+        assert cmd.returncode == 0
+        expected = toml.loads(cmd.stdout)
+
+        with open(snapshot) as f:
+            projects = json.load(f)["projects"]
+        assert len(projects) == 5
+        assert {project["namespace"] for project in projects} == {"myuser1"}
+
+        # Neither provider nor token are required anymore
+        cmd = grm(args + ["--root", "/myroot", "--from-snapshot", snapshot])
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+        assert toml.loads(cmd.stdout) == expected
+
+        # The tree is generated from the snapshot, with the options of this run
+        cmd = grm(args + ["--from-snapshot", snapshot, "--worktree", "--root", "/x"])
+        assert cmd.returncode == 0
+        output = toml.loads(cmd.stdout)
+        assert output["trees"][0]["root"] == "/x/myuser1"
+        assert all(repo["worktree_setup"] for repo in output["trees"][0]["repos"])
+
+
+def test_repos_find_remote_invalid_snapshot():
+    with tempfile.NamedTemporaryFile() as snapshot:
+        with open(snapshot.name, "w") as f:
+            f.write('{"repos": []}')
+        args = ["repos", "find", "remote", "--root", "/x"]
+        cmd = grm(args + ["--from-snapshot", snapshot.name])
+        assert cmd.returncode != 0
+        assert "Failed parsing snapshot" in cmd.stderr
+
+    cmd = grm(["repos", "find", "remote", "--root", "/x"], is_invalid=True)
+    assert cmd.returncode != 0
+    assert "--provider" in cmd.stderr
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_org(provider, use_config):
+    with tempfile.NamedTemporaryFile() as config:
+        if use_config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    provider = "{provider}"
+                    token_command = "echo secret-token:myauthtoken"
+                    root = "/myroot"
+
+                    [filters]
+                    orgs = ["mygroup1"]
+                    """
+                )
+            cmd = grm(["repos", "find", "config", "--config", config.name])
+        else:
+            args = ["repos", "find", "remote", "--provider", provider]
+            args += ["--token-command", "echo secret-token:myauthtoken"]
+            args += ["--root", "/myroot", "--org", "mygroup1"]
+            cmd = grm(args)
+
+    if provider == "gitlab":
+        assert cmd.returncode != 0
+        assert 'organization "mygroup1"' in cmd.stderr
+        assert "only supported for GitHub" in cmd.stderr
+        return
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    assert len(output["trees"]) == 1
+    assert output["trees"][0]["root"] == "/myroot/mygroup1"
+    assert len(output["trees"][0]["repos"]) == 5
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+def test_repos_find_remote_namespace_roots(provider):
+    with tempfile.NamedTemporaryFile() as config:
+        with open(config.name, "w") as f:
+            f.write(
+                f"""
+                provider = "{provider}"
+                token_command = "echo secret-token:myauthtoken"
+                root = "/myroot"
+
+                [namespace_roots]
+                "mygroup1" = "/work/group"
+                "*" = "/misc"
+
+                [filters]
+                users = ["myuser1"]
+                groups = ["mygroup1"]
+                """
+            )
+        cmd = grm(["repos", "find", "config", "--config", config.name])
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    roots = {tree["root"]: len(tree["repos"]) for tree in output["trees"]}
+    assert roots == {"/work/group": 5, "/misc/myuser1": 5}
+
+
+@pytest.mark.parametrize("tree_per_filter", [True, False])
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_tree_per_filter(tree_per_filter, use_config):
+    with tempfile.NamedTemporaryFile() as config:
+        if use_config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    provider = "gitlab"
+                    token_command = "echo secret-token:myauthtoken"
+                    root = "/myroot"
+                    tree_per_filter = {str(tree_per_filter).lower()}
+
+                    [filters]
+                    users = ["myuser1"]
+                    groups = ["mygroup3"]
+                    """
+                )
+            cmd = grm(["repos", "find", "config", "--config", config.name])
+        else:
+            args = ["repos", "find", "remote", "--provider", "gitlab"]
+            args += ["--token-command", "echo secret-token:myauthtoken"]
+            args += ["--root", "/myroot", "--user", "myuser1", "--group", "mygroup3"]
+            if tree_per_filter:
+                args += ["--tree-per-filter"]
+            cmd = grm(args)
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    trees = {
+        tree["root"]: sorted(repo["name"] for repo in tree["repos"])
+        for tree in output["trees"]
+    }
+    user_projects = [f"myproject{i}" for i in range(1, 6)]
+    if tree_per_filter:
+        # The subgroups end up in the tree of their group
+        assert trees == {
+            "/myroot/myuser1": user_projects,
+            "/myroot/mygroup3": [
+                "myproject1",
+                "myproject2",
+                "sub1/deep/myproject4",
+                "sub1/myproject3",
+                "sub2/myproject5",
+            ],
+        }
+    else:
+        assert trees == {
+            "/myroot/myuser1": user_projects,
+            "/myroot/mygroup3": ["myproject1", "myproject2"],
+            "/myroot/mygroup3/sub1": ["myproject3"],
+            "/myroot/mygroup3/sub1/deep": ["myproject4"],
+            "/myroot/mygroup3/sub2": ["myproject5"],
+        }
+
+
+@pytest.mark.parametrize("starred", [True, False])
+@pytest.mark.parametrize("min_access_level", [None, "developer"])
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_gitlab_membership(starred, min_access_level, use_config):
+    with tempfile.NamedTemporaryFile() as config:
+        if use_config:
+            cfg = """
+                provider = "gitlab"
+                token_command = "echo secret-token:myauthtoken"
+                root = "/myroot"
+
+                [filters]
+                groups = ["mygroup1"]
+            """
+            if starred:
+                cfg += "starred = true\n"
+            if min_access_level is not None:
+                cfg += f'min_access_level = "{min_access_level}"\n'
+            with open(config.name, "w") as f:
+                f.write(cfg)
+            cmd = grm(["repos", "find", "config", "--config", config.name])
+        else:
+            args = ["repos", "find", "remote", "--provider", "gitlab"]
+            args += ["--token-command", "echo secret-token:myauthtoken"]
+            args += ["--root", "/myroot", "--group", "mygroup1"]
+            if starred:
+                args += ["--starred"]
+            if min_access_level is not None:
+                args += ["--min-access-level", min_access_level]
+            cmd = grm(args)
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    # Neither the group nor the starred projects contain any project with
+    # developer access
+    roots = {tree["root"] for tree in toml.loads(cmd.stdout).get("trees", [])}
+    if min_access_level is not None:
+        assert roots == set()
+    elif starred:
+        assert roots == {"/myroot/mygroup1", "/myroot/mygroup2"}
+    else:
+        assert roots == {"/myroot/mygroup1"}
+
+
+def test_repos_find_remote_github_membership():
+    args = ["repos", "find", "remote", "--provider", "github"]
+    args += ["--token-command", "echo secret-token:myauthtoken"]
+    args += ["--root", "/myroot"]
+
+    cmd = grm(args + ["--access", "--min-access-level", "developer"])
+    assert cmd.returncode != 0
+    assert "only supported for GitLab" in cmd.stderr
+
+    cmd = grm(args + ["--starred"])
+    assert cmd.returncode != 0
+    assert "Starred repositories are only supported for GitLab" in cmd.stderr
+
+
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_bitbucket(use_config):
+    with tempfile.NamedTemporaryFile() as config:
+        if use_config:
+            with open(config.name, "w") as f:
+                f.write(
+                    """
+                    provider = "bitbucket"
+                    token_command = "echo secret-token:myauthtoken"
+                    root = "/myroot"
+                    per_page = 2
+
+                    [filters]
+                    owner = true
+                    groups = ["mygroup1"]
+                    """
+                )
+            cmd = grm(["repos", "find", "config", "--config", config.name])
+        else:
+            args = ["repos", "find", "remote", "--provider", "bitbucket"]
+            args += ["--token-command", "echo secret-token:myauthtoken"]
+            args += ["--root", "/myroot", "--owner", "--group", "mygroup1"]
+            args += ["--per-page", "2"]
+            cmd = grm(args)
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    trees = {tree["root"]: tree["repos"] for tree in output["trees"]}
+    assert set(trees) == {"/myroot/myuser1", "/myroot/mygroup1"}
+
+    # All four pages are read, by following the "next" URL of each page
+    repos = sorted(trees["/myroot/mygroup1"], key=lambda repo: repo["name"])
+    assert [repo["name"] for repo in repos] == [f"myproject{i}" for i in range(1, 6)]
+
+    assert repos[0]["provider_id"] == "bitbucket:1"
+    assert repos[0]["default_branch"] == "master"
+    # The name of the requesting user is not part of the URL
+    assert repos[0]["remotes"][0]["url"] == (
+        "https://example.com/mygroup1/myproject1.git"
+    )
+    assert repos[0]["remotes"][0]["type"] == "https"
+    assert repos[2]["remotes"][0]["url"] == (
+        "ssh://git@example.com/mygroup1/myproject3.git"
+    )
+    assert repos[2]["remotes"][0]["type"] == "ssh"
+
+
+def test_repos_find_remote_bitbucket_access():
+    args = ["repos", "find", "remote", "--provider", "bitbucket"]
+    args += ["--token-command", "echo secret-token:myauthtoken"]
+    args += ["--root", "/myroot"]
+
+    cmd = grm(args + ["--access"])
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+    roots = {tree["root"] for tree in toml.loads(cmd.stdout)["trees"]}
+    assert roots == {
+        "/myroot/myuser1",
+        "/myroot/myuser2",
+        "/myroot/mygroup1",
+        "/myroot/mygroup2",
+    }
+
+    cmd = grm(args + ["--access", "--max-requests", "3"])
+    assert cmd.returncode != 0
+    assert "limit of 3 API requests" in cmd.stderr
+
+    cmd = grm(args + ["--user", "nobody"])
+    assert cmd.returncode != 0
+    assert "Workspace nobody not found" in cmd.stderr
+
+    cmd = grm(
+        ["repos", "find", "remote", "--provider", "bitbucket"]
+        + ["--token-command", "echo wrongtoken", "--root", "/myroot", "--owner"]
+    )
+    assert cmd.returncode != 0
+    assert "Bad credentials" in cmd.stderr
+
+    for unsupported in (
+        ["--starred"],
+        ["--org", "mygroup1"],
+        ["--owner", "--min-access-level", "developer"],
+        ["--owner", "--api-url", "https://bitbucket.example.com"],
+    ):
+        cmd = grm(args + unsupported)
+        assert cmd.returncode != 0
+        assert "supported" in cmd.stderr
+
+
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_azure_devops(use_config):
+    with tempfile.NamedTemporaryFile() as config:
+        if use_config:
+            with open(config.name, "w") as f:
+                f.write(
+                    """
+                    provider = "azure-devops"
+                    token_command = "echo secret-token:myauthtoken"
+                    root = "/myroot"
+
+                    [filters]
+                    orgs = ["myorg1"]
+                    groups = ["myorg2/myproject1"]
+                    """
+                )
+            cmd = grm(["repos", "find", "config", "--config", config.name])
+        else:
+            args = ["repos", "find", "remote", "--provider", "azure-devops"]
+            args += ["--token-command", "echo secret-token:myauthtoken"]
+            args += ["--root", "/myroot", "--org", "myorg1"]
+            args += ["--group", "myorg2/myproject1"]
+            cmd = grm(args)
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    trees = {tree["root"]: tree["repos"] for tree in output["trees"]}
+    # Repositories are grouped by organization and project
+    assert set(trees) == {
+        "/myroot/myorg1/myproject1",
+        "/myroot/myorg1/myproject2",
+        "/myroot/myorg2/myproject1",
+    }
+
+    repos = sorted(trees["/myroot/myorg1/myproject1"], key=lambda repo: repo["name"])
+    assert [repo["name"] for repo in repos] == ["myrepo1", "myrepo2"]
+    assert repos[0]["provider_id"] == "azure-devops:myorg1-myproject1-myrepo1"
+    assert repos[0]["default_branch"] == "master"
+    # The name of the requesting organization is not part of the URL
+    assert repos[0]["remotes"][0]["url"] == (
+        "https://example.com/myorg1/myproject1/_git/myrepo1"
+    )
+    assert repos[0]["remotes"][0]["type"] == "https"
+
+    # Disabled repositories cannot be cloned, so they are left out
+    repos = trees["/myroot/myorg1/myproject2"]
+    assert [repo["name"] for repo in repos] == ["myrepo3"]
+    assert repos[0]["remotes"][0]["url"] == (
+        "ssh://git@example.com/v3/myorg1/myproject2/myrepo3"
+    )
+    assert repos[0]["remotes"][0]["type"] == "ssh"
+
+
+def test_repos_find_remote_azure_devops_access():
+    args = ["repos", "find", "remote", "--provider", "azure-devops"]
+    args += ["--token-command", "echo secret-token:myauthtoken"]
+    args += ["--root", "/myroot"]
+
+    # All organizations of the requesting user
+    cmd = grm(args + ["--access"])
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+    roots = {tree["root"] for tree in toml.loads(cmd.stdout)["trees"]}
+    assert roots == {
+        "/myroot/myorg1/myproject1",
+        "/myroot/myorg1/myproject2",
+        "/myroot/myorg2/myproject1",
+    }
+
+    cmd = grm(args + ["--access", "--max-requests", "1"])
+    assert cmd.returncode != 0
+    assert "limit of 1 API requests" in cmd.stderr
+
+    cmd = grm(args + ["--org", "maliciousorg"])
+    assert cmd.returncode == 0
+    assert "Skipping repository" in cmd.stderr
+
+    for group, message in (
+        ("myorg1", "has to be an organization and a project"),
+        ("myorg1/missing", "The project missing does not exist"),
+    ):
+        cmd = grm(args + ["--group", group])
+        assert cmd.returncode != 0
+        assert message in cmd.stderr
+
+    cmd = grm(
+        ["repos", "find", "remote", "--provider", "azure-devops"]
+        + ["--token-command", "echo wrongtoken", "--root", "/myroot"]
+        + ["--org", "myorg1"]
+    )
+    assert cmd.returncode != 0
+    assert "The user is not authorized" in cmd.stderr
+
+    for unsupported in (
+        ["--owner"],
+        ["--user", "myuser1"],
+        ["--starred"],
+        ["--org", "myorg1", "--min-access-level", "developer"],
+        ["--org", "myorg1", "--api-url", "https://azure.example.com"],
+    ):
+        cmd = grm(args + unsupported)
+        assert cmd.returncode != 0
+        assert "supported" in cmd.stderr or "Users do not have" in cmd.stderr
+
+
+@pytest.mark.parametrize("provider", PROVIDERS)
+@pytest.mark.parametrize("use_config", [True, False])
+def test_repos_find_remote_url_template(provider, use_config):
+    template = "ssh://git@example.com:2222/{namespace}/{name}.git"
+    with tempfile.NamedTemporaryFile() as config:
+        if use_config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    provider = "{provider}"
+                    token_command = "echo secret-token:myauthtoken"
+                    root = "/myroot"
+                    url_template = "{template}"
+
+                    [filters]
+                    users = ["myuser1"]
+                    """
+                )
+            cmd = grm(["repos", "find", "config", "--config", config.name])
+        else:
+            args = ["repos", "find", "remote", "--provider", provider]
+            args += ["--token-command", "echo secret-token:myauthtoken"]
+            args += ["--root", "/myroot", "--user", "myuser1"]
+            args += ["--url-template", template]
+            cmd = grm(args)
+
+    assert cmd.returncode == 0
+    assert len(cmd.stderr) == 0
+
+    output = toml.loads(cmd.stdout)
+    repos = output["trees"][0]["repos"]
+    assert len(repos) == 5
+    for repo in repos:
+        assert repo["remotes"][0]["url"] == (
+            f"ssh://git@example.com:2222/myuser1/{repo['name']}.git"
+        )
+        assert repo["remotes"][0]["type"] == "ssh"
+
+
+@pytest.mark.parametrize("template", ["http://example.com/{path}", "ssh://x/y.git"])
+def test_repos_find_remote_invalid_url_template(template):
+    args = ["repos", "find", "remote", "--provider", "github"]
+    args += ["--token-command", "echo secret-token:myauthtoken"]
+    args += ["--root", "/myroot", "--user", "myuser1"]
+
+    cmd = grm(args + ["--url-template", template])
+    assert cmd.returncode != 0
+    assert len(cmd.stdout) == 0
+    assert "URL template" in cmd.stderr
+
+    cmd = grm(args + ["--url-template", template, "--force-ssh"], is_invalid=True)
+    assert cmd.returncode != 0
+    assert "cannot be used with" in cmd.stderr
@@ -0,0 +1,210 @@
+#!/usr/bin/env python3
+
+import os
+import shutil
+import subprocess
+import tempfile
+import threading
+import time
+
+import pytest
+import toml
+from helpers import grm, shell
+
+# The REST mock returns forks below this directory
+FORKS_DIR = "/tmp/grm-e2e-forks"
+
+UPSTREAM_URL = "https://example.com/upstreamowner/myproject.git"
+
+
+def git_output(repo_path, *args):
+    return subprocess.run(
+        ["git", "-C", repo_path] + list(args),
+        capture_output=True,
+        text=True,
+    ).stdout.strip()
+
+
+def make_fork(namespace):
+    fork = os.path.join(FORKS_DIR, namespace, "myproject.git")
+    shutil.rmtree(fork, ignore_errors=True)
+    shell(
+        f"""
+        mkdir -p {fork}
+        cd {fork}
+        git -c init.defaultBranch=master init --quiet --bare
+        cd $(mktemp -d)
+        git -c init.defaultBranch=master init --quiet
+        git commit --quiet --allow-empty -m "commit-1"
+        git push --quiet {fork} master
+        """
+    )
+    return f"file://{fork}"
+
+
+def write_config(config, roots, hosts=None):
+    with open(config, "w") as f:
+        if hosts is not None:
+            f.write("[hosts]\n")
+            for alias, url in hosts.items():
+                f.write(f'"{alias}" = "{url}"\n')
+        for root in roots:
+            f.write(
+                f"""
+                [[trees]]
+                root = "{root}"
+                """
+            )
+
+
+def fork_args(config, provider):
+    return ["repos", "fork", "--config", config, UPSTREAM_URL] + [
+        "--provider",
+        provider,
+        "--token-command",
+        "echo secret-token:myauthtoken",
+    ]
+
+
+@pytest.mark.parametrize("provider", ["github", "gitlab", "bitbucket"])
+@pytest.mark.parametrize("group", [None, "mygroup1"])
+def test_repos_fork(provider, group):
+    namespace = group or "myuser1"
+    fork_url = make_fork(namespace)
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, [root])
+
+            args = fork_args(config.name, provider)
+            if group is not None:
+                args += ["--group", group]
+
+            cmd = grm(args)
+            assert cmd.returncode == 0
+            assert f'Forked "{UPSTREAM_URL}" to "{fork_url}"' in cmd.stdout
+            # The upstream is not reachable, but the fork is kept
+            assert "Fetching upstream failed" in cmd.stderr
+
+            repo_path = os.path.join(root, "myproject")
+            assert git_output(repo_path, "remote", "get-url", "origin") == fork_url
+            assert git_output(repo_path, "remote", "get-url", "upstream") == (
+                UPSTREAM_URL
+            )
+            assert git_output(repo_path, "config", "remote.pushDefault") == ""
+
+            with open(config.name) as f:
+                repo = toml.loads(f.read())["trees"][0]["repos"][0]
+            assert repo["name"] == "myproject"
+            assert repo["provider_id"] == f"{provider}:4343"
+            assert repo["default_branch"] == "master"
+            assert [remote["name"] for remote in repo["remotes"]] == [
+                "origin",
+                "upstream",
+            ]
+            assert repo["remotes"][0]["url"] == fork_url
+            assert repo["remotes"][1]["url"] == UPSTREAM_URL
+            assert repo["remotes"][1]["type"] == "https"
+
+
+def test_repos_fork_not_ready():
+    fork = os.path.join(FORKS_DIR, "myuser1", "myproject.git")
+    shutil.rmtree(fork, ignore_errors=True)
+
+    # The forge finishes creating the fork while grm is already trying to clone
+    def create_fork_later():
+        time.sleep(2)
+        make_fork("myuser1")
+
+    creator = threading.Thread(target=create_fork_later)
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, [root])
+
+            creator.start()
+            try:
+                cmd = grm(fork_args(config.name, "github"))
+            finally:
+                creator.join()
+            assert cmd.returncode == 0
+            assert "Cloning the fork failed" in cmd.stderr
+            assert "Retrying in 1s (1/6)" in cmd.stderr
+
+            repo_path = os.path.join(root, "myproject")
+            assert git_output(repo_path, "log", "--format=%s") == "commit-1"
+
+            with open(config.name) as f:
+                repos = toml.loads(f.read())["trees"][0]["repos"]
+            assert [repo["name"] for repo in repos] == ["myproject"]
+
+
+def test_repos_fork_track_upstream():
+    make_fork("myuser1")
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, [root])
+
+            cmd = grm(
+                fork_args(config.name, "github") + ["--track-upstream", "--name", "x"]
+            )
+            assert cmd.returncode == 0
+
+            repo_path = os.path.join(root, "x")
+            assert git_output(repo_path, "config", "remote.pushDefault") == "origin"
+
+            with open(config.name) as f:
+                repo = toml.loads(f.read())["trees"][0]["repos"][0]
+            assert repo["name"] == "x"
+
+
+def test_repos_fork_host_alias():
+    make_fork("myuser1")
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            hosts = {
+                "forks:": f"file://{FORKS_DIR}/",
+                "example:": "https://example.com/",
+            }
+            write_config(config.name, [root], hosts)
+
+            cmd = grm(fork_args(config.name, "github"))
+            assert cmd.returncode == 0
+
+            with open(config.name) as f:
+                repo = toml.loads(f.read())["trees"][0]["repos"][0]
+            assert repo["remotes"][0]["url"] == "forks:myuser1/myproject.git"
+            assert repo["remotes"][1]["url"] == "example:upstreamowner/myproject.git"
+
+
+def test_repos_fork_existing_repo():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, [root])
+            os.mkdir(os.path.join(root, "myproject"))
+
+            cmd = grm(fork_args(config.name, "github"))
+            assert cmd.returncode != 0
+            assert "already exists" in cmd.stderr
+            assert "Forked" not in cmd.stdout
+
+
+def test_repos_fork_unknown_project():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, [root])
+
+            args = fork_args(config.name, "github")
+            args[4] = "https://example.com/someoneelse/myproject.git"
+            cmd = grm(args)
+            assert cmd.returncode != 0
+            assert "Forking the project failed: Not Found" in cmd.stderr
+            assert not os.path.exists(os.path.join(root, "myproject"))
+
+
+def test_repos_fork_invalid_url():
+    with tempfile.NamedTemporaryFile() as config:
+        cmd = grm(
+            ["repos", "fork", "--config", config.name, "https://example.com/x"]
+            + ["--provider", "github", "--token-command", "true"]
+        )
+        assert cmd.returncode != 0
+        assert "Could not determine the project" in cmd.stderr
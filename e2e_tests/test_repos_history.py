@@ -0,0 +1,203 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+import pytest
+from helpers import grm, shell, write_config
+
+
+def make_remote(path):
+    """
+    A remote with a few megabytes of old history, which are deleted by the
+    latest commit
+    """
+    shell(
+        f"""
+        cd {path}
+        git -c init.defaultBranch=master init --quiet
+        for i in 1 2 3; do
+            head -c 700000 /dev/urandom > "old$i"
+            git add "old$i"
+            GIT_AUTHOR_DATE="2020-01-0${{i}}T00:00:00" \\
+            GIT_COMMITTER_DATE="2020-01-0${{i}}T00:00:00" \\
+                git commit --quiet -m "old $i"
+        done
+        git rm --quiet old1 old2 old3
+        echo new > new
+        git add new
+        git commit --quiet -m "new"
+        """
+    )
+
+
+def git_dir(root, worktree_setup=False):
+    name = ".git-main-working-tree" if worktree_setup else ".git"
+    return os.path.join(root, "test", name)
+
+
+def commits(root, worktree_setup=False):
+    return subprocess.run(
+        ["git", "--git-dir", git_dir(root, worktree_setup), "log", "--format=%s"]
+        + ["--all"],
+        capture_output=True,
+        text=True,
+        check=True,
+    ).stdout.splitlines()
+
+
+@pytest.mark.parametrize("worktree_setup", [True, False])
+def test_repos_sync_history_days(worktree_setup):
+    with tempfile.TemporaryDirectory() as remote:
+        make_remote(remote)
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(
+                    config.name,
+                    root,
+                    remote,
+                    tree_options="history_days = 365",
+                    worktree_setup=worktree_setup,
+                )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                assert os.path.isfile(
+                    os.path.join(git_dir(root, worktree_setup), "shallow")
+                )
+                assert commits(root, worktree_setup) == ["new"]
+
+                # Fetching new commits keeps working
+                shell(f"cd {remote} && git commit --quiet --allow-empty -m newer")
+                cmd = grm(["repos", "fetch", "--config", config.name])
+                assert cmd.returncode == 0
+                assert sorted(commits(root, worktree_setup)) == ["new", "newer"]
+
+
+def test_repos_sync_max_size_trim():
+    with tempfile.TemporaryDirectory() as remote:
+        make_remote(remote)
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert len(commits(root)) == 4
+
+                write_config(
+                    config.name,
+                    root,
+                    remote,
+                    tree_options="history_days = 365\nmax_size_mb = 1",
+                )
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "trimming its history to the last 365 days" in cmd.stdout
+                assert "budget" not in cmd.stdout + cmd.stderr
+                assert commits(root) == ["new"]
+
+
+def test_repos_sync_max_size_warning():
+    with tempfile.TemporaryDirectory() as remote:
+        make_remote(remote)
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, tree_options="max_size_mb = 1")
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "more than the budget of 1 MiB" in cmd.stdout + cmd.stderr
+                assert len(commits(root)) == 4
+
+
+def test_repos_sync_max_size_keeps_stashes():
+    with tempfile.TemporaryDirectory() as remote:
+        make_remote(remote)
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                shell(
+                    f"""
+                    cd {root}/test
+                    echo first > new
+                    git stash --quiet
+                    echo second > new
+                    git stash --quiet
+                    """
+                )
+
+                write_config(
+                    config.name,
+                    root,
+                    remote,
+                    tree_options="history_days = 365\nmax_size_mb = 1",
+                )
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "trimming" not in cmd.stdout
+                assert "the repository has stashes" in cmd.stdout + cmd.stderr
+                assert "more than the budget of 1 MiB" in cmd.stdout + cmd.stderr
+
+                stashes = subprocess.run(
+                    ["git", "-C", os.path.join(root, "test"), "stash", "list"],
+                    capture_output=True,
+                    text=True,
+                    check=True,
+                ).stdout.splitlines()
+                assert len(stashes) == 2
+
+
+def test_repos_sync_max_size_keeps_lent_objects():
+    with tempfile.TemporaryDirectory() as remote:
+        make_remote(remote)
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.TemporaryDirectory() as other_root:
+                with tempfile.NamedTemporaryFile() as config:
+
+                    def write(roots, options=""):
+                        with open(config.name, "w") as f:
+                            f.write(
+                                """
+                                [clone]
+                                share_objects = true
+                                """
+                            )
+                            for tree_root in roots:
+                                f.write(
+                                    f"""
+                                    [[trees]]
+                                    root = "{tree_root}"
+                                    {options if tree_root == root else ""}
+
+                                    [[trees.repos]]
+                                    name = "test"
+
+                                    [[trees.repos.remotes]]
+                                    name = "origin"
+                                    url = "file://{remote}"
+                                    type = "file"
+                                    """
+                                )
+
+                    # The second clone borrows the objects of the first one
+                    for roots in [[root], [root, other_root]]:
+                        write(roots)
+                        cmd = grm(["repos", "sync", "config", "--config", config.name])
+                        assert cmd.returncode == 0
+
+                    write([root, other_root], "history_days = 365\nmax_size_mb = 1")
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "trimming" not in cmd.stdout
+                    assert "other clones borrow objects" in cmd.stdout + cmd.stderr
+                    assert len(commits(root)) == 4
+
+                    # The borrowing clone is still intact
+                    subprocess.run(
+                        ["git", "-C", os.path.join(other_root, "test"), "fsck"],
+                        check=True,
+                    )
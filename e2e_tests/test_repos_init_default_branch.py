@@ -0,0 +1,76 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+import pytest
+from helpers import grm, shell, write_config
+
+
+def git(git_dir, *args):
+    cmd = subprocess.run(
+        ["git", "--git-dir", git_dir, *args], capture_output=True, text=True
+    )
+    assert cmd.returncode == 0, cmd.stderr
+    return cmd.stdout.strip()
+
+
+@pytest.mark.parametrize("worktree_setup", [True, False])
+@pytest.mark.parametrize("branch", [None, "trunk"])
+def test_repos_init_default_branch(worktree_setup, branch):
+    git_dir_name = ".git-main-working-tree" if worktree_setup else ".git"
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as remote:
+            shell(f"git -c init.defaultBranch=master init --quiet --bare {remote}")
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, worktree_setup=worktree_setup)
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert ("Repository is empty" in cmd.stderr) == worktree_setup
+                assert "Could not determine default branch" not in cmd.stderr
+
+                repo_path = os.path.join(root, "test")
+                if worktree_setup:
+                    cmd = grm(["wt", "add", "mybranch"], cwd=repo_path)
+                    assert cmd.returncode != 0
+                    assert "does not contain any commits yet" in cmd.stderr
+
+                args = ["repos", "init-default-branch", "--config", config.name]
+                args += ["test"]
+                if branch is not None:
+                    args += ["--branch", branch]
+                cmd = grm(args)
+                assert cmd.returncode == 0
+
+                branch = branch or "master"
+                assert f"Initialized {branch}" in cmd.stdout
+
+                git_dir = os.path.join(repo_path, git_dir_name)
+                assert git(remote, "log", "--format=%s", branch) == "Initial commit"
+                assert git(git_dir, "rev-parse", branch) == git(
+                    remote, "rev-parse", branch
+                )
+                assert git(git_dir, "rev-parse", "--abbrev-ref", f"{branch}@{{u}}") == (
+                    f"origin/{branch}"
+                )
+                assert os.path.isdir(os.path.join(repo_path, branch)) == worktree_setup
+
+                # There is nothing to initialize anymore
+                cmd = grm(args)
+                assert cmd.returncode != 0
+                assert "already contains commits" in cmd.stderr
+
+                if worktree_setup:
+                    cmd = grm(["wt", "add", "mybranch"], cwd=repo_path)
+                    assert cmd.returncode == 0
+
+
+def test_repos_init_default_branch_unknown_repo():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, root, "/tmp/nonexistent")
+            cmd = grm(["repos", "init-default-branch", "--config", config.name, "x"])
+            assert cmd.returncode != 0
+            assert 'Repository "x" is not part of the configuration' in cmd.stderr
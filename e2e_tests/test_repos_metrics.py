@@ -0,0 +1,93 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+from helpers import RepoTree, TempGitFileRemote, grm, shell
+
+
+def parse_metrics(output):
+    samples = {}
+    for line in output.splitlines():
+        if line.startswith("#"):
+            continue
+        sample, value = line.rsplit(" ", 1)
+        samples[sample] = int(value)
+    return samples
+
+
+def test_repos_metrics():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+
+                        [[trees.repos]]
+                        name = "missing"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                repo_dir = os.path.join(root, "test")
+                shell(
+                    f"""
+                    cd {repo_dir}
+                    git commit --allow-empty -m "unpushed"
+                    echo change > newfile
+                    """
+                )
+                cmd = grm(["repos", "fetch", "--config", config.name])
+                assert cmd.returncode == 0
+                shell(f"rm -rf {root}/missing")
+
+                cmd = grm(["repos", "metrics", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "# TYPE grm_repo_dirty gauge" in cmd.stdout
+                samples = parse_metrics(cmd.stdout)
+
+                test = f'tree="{root}",repo="test"'
+                missing = f'tree="{root}",repo="missing"'
+                assert samples[f"grm_repo_present{{{test}}}"] == 1
+                assert samples[f"grm_repo_present{{{missing}}}"] == 0
+                assert samples[f"grm_repo_dirty{{{test}}}"] == 1
+                assert samples[f"grm_repo_check_failures{{{test}}}"] == 2
+                branch = f'{test},branch="master"'
+                assert samples[f"grm_branch_ahead_commits{{{branch}}}"] == 1
+                assert samples[f"grm_branch_behind_commits{{{branch}}}"] == 0
+                assert samples[f"grm_repo_last_fetch_timestamp_seconds{{{test}}}"] > 0
+                for labels in [test, missing]:
+                    name = f"grm_repo_last_sync_timestamp_seconds{{{labels}}}"
+                    assert samples[name] > 0
+
+                with tempfile.TemporaryDirectory() as tmpdir:
+                    output = os.path.join(tmpdir, "grm.prom")
+                    cmd = grm(
+                        ["repos", "metrics", "--config", config.name, "-o", output]
+                    )
+                    assert cmd.returncode == 0
+                    assert len(cmd.stdout) == 0
+                    assert os.listdir(tmpdir) == ["grm.prom"]
+                    with open(output) as f:
+                        assert parse_metrics(f.read()) == samples
+
+
+def test_repos_metrics_never_fetched():
+    with RepoTree() as (root, config, _repos):
+        cmd = grm(["repos", "metrics", "--config", config])
+        assert cmd.returncode == 0
+        assert "grm_repo_last_fetch_timestamp_seconds{" not in cmd.stdout
+        assert "grm_branch_ahead_commits{" not in cmd.stdout
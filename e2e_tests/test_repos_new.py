@@ -0,0 +1,251 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+import pytest
+import toml
+from helpers import grm, shell
+
+
+def git_output(repo_path, *args):
+    return subprocess.run(
+        ["git", "-C", repo_path] + list(args),
+        capture_output=True,
+        text=True,
+    ).stdout.strip()
+
+
+def make_template(path):
+    shell(
+        f"""
+        mkdir -p {path}/src
+        cd {path}
+        git -c init.defaultBranch=master init --quiet
+        echo "# {{{{project_name}}}} by {{{{author}}}}" > README.md
+        echo "name = '{{{{project_name}}}}'" > src/config.py
+        printf '\\x00\\xff{{{{project_name}}}}' > binary
+        git add .
+        git commit --quiet -m "template-commit-1"
+        echo change >> README.md
+        git commit --quiet -am "template-commit-2"
+        """
+    )
+
+
+def write_config(config, roots):
+    with open(config, "w") as f:
+        for root in roots:
+            f.write(
+                f"""
+                [[trees]]
+                root = "{root}"
+                """
+            )
+
+
+def test_repos_new():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as template:
+            with tempfile.NamedTemporaryFile() as config:
+                make_template(template)
+                write_config(config.name, [root])
+
+                cmd = grm(
+                    ["repos", "new", "--config", config.name, "myproject"]
+                    + ["--template", template, "--author", "Jane Doe"]
+                )
+                assert cmd.returncode == 0
+                assert "Created repository" in cmd.stdout
+
+                repo_path = os.path.join(root, "myproject")
+                with open(os.path.join(repo_path, "README.md")) as f:
+                    assert f.read().startswith("# myproject by Jane Doe")
+                with open(os.path.join(repo_path, "src", "config.py")) as f:
+                    assert f.read() == "name = 'myproject'\n"
+                # Binary files are left alone
+                with open(os.path.join(repo_path, "binary"), "rb") as f:
+                    assert f.read() == b"\x00\xff{{project_name}}"
+
+                # The history of the template is gone
+                log = git_output(repo_path, "log", "--format=%s")
+                assert log == "Initial commit"
+                assert git_output(repo_path, "status", "--porcelain") == ""
+
+                with open(config.name) as f:
+                    repos = toml.loads(f.read())["trees"][0]["repos"]
+                assert [repo["name"] for repo in repos] == ["myproject"]
+
+                # It is a normal repository now
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                # Adding it again does not work
+                cmd = grm(
+                    ["repos", "new", "--config", config.name, "myproject"]
+                    + ["--template", template, "--author", "Jane Doe"]
+                )
+                assert cmd.returncode != 0
+                assert "already exists" in cmd.stderr
+
+
+def test_repos_new_multiple_trees():
+    with tempfile.TemporaryDirectory() as root1:
+        with tempfile.TemporaryDirectory() as root2:
+            with tempfile.TemporaryDirectory() as template:
+                with tempfile.NamedTemporaryFile() as config:
+                    make_template(template)
+                    write_config(config.name, [root1, root2])
+
+                    args = ["repos", "new", "--config", config.name, "ns/myproject"]
+                    # Without --author, the git identity is used
+                    args += ["--template", template]
+
+                    cmd = grm(args)
+                    assert cmd.returncode != 0
+                    assert "--tree" in cmd.stderr
+
+                    cmd = grm(args + ["--tree", root2])
+                    assert cmd.returncode == 0
+                    assert not os.listdir(root1)
+
+                    repo_path = os.path.join(root2, "ns", "myproject")
+                    with open(os.path.join(repo_path, "README.md")) as f:
+                        assert f.read().startswith("# myproject by Example user")
+
+                    with open(config.name) as f:
+                        trees = toml.loads(f.read())["trees"]
+                    assert "repos" not in trees[0] or not trees[0]["repos"]
+                    assert trees[1]["repos"][0]["name"] == "ns/myproject"
+
+
+def test_repos_new_named_tree():
+    with tempfile.TemporaryDirectory() as root1:
+        with tempfile.TemporaryDirectory() as root2:
+            with tempfile.TemporaryDirectory() as template:
+                with tempfile.NamedTemporaryFile() as config:
+                    make_template(template)
+                    with open(config.name, "w") as f:
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root1}"
+                            name = "work"
+
+                            [[trees]]
+                            root = "{root2}"
+                            name = "private"
+                            """
+                        )
+
+                    cmd = grm(
+                        ["repos", "new", "--config", config.name, "myproject"]
+                        + ["--template", template, "--tree", "private"]
+                    )
+                    assert cmd.returncode == 0
+                    assert not os.listdir(root1)
+                    assert os.path.exists(os.path.join(root2, "myproject"))
+
+                    with open(config.name) as f:
+                        trees = toml.loads(f.read())["trees"]
+                    assert trees[1]["name"] == "private"
+                    assert trees[1]["repos"][0]["name"] == "myproject"
+
+                    cmd = grm(
+                        ["repos", "new", "--config", config.name, "other"]
+                        + ["--template", template, "--tree", "home"]
+                    )
+                    assert cmd.returncode != 0
+                    assert 'There is no tree with name or root "home"' in cmd.stderr
+
+
+def test_repos_new_invalid_template():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            write_config(config.name, [root])
+
+            cmd = grm(
+                ["repos", "new", "--config", config.name, "myproject"]
+                + ["--template", "file:///does/not/exist", "--author", "Jane Doe"]
+            )
+            assert cmd.returncode != 0
+            assert "Cloning the template failed" in cmd.stderr
+            assert not os.path.exists(os.path.join(root, "myproject"))
+
+            with open(config.name) as f:
+                assert "repos" not in toml.loads(f.read())["trees"][0]
+
+
+@pytest.mark.parametrize("provider", ["github", "gitlab", "bitbucket"])
+@pytest.mark.parametrize("group", [None, "mygroup1"])
+def test_repos_new_provider(provider, group):
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as template:
+            with tempfile.NamedTemporaryFile() as config:
+                make_template(template)
+                write_config(config.name, [root])
+
+                args = ["repos", "new", "--config", config.name, "myproject"]
+                args += ["--template", template, "--author", "Jane Doe"]
+                args += ["--provider", provider]
+                args += ["--token-command", "echo secret-token:myauthtoken"]
+                if group is not None:
+                    args += ["--group", group]
+
+                cmd = grm(args)
+                assert cmd.returncode == 0
+                assert "Created project at" in cmd.stdout
+                # The project is kept even though the push fails
+                assert "Pushing master to origin failed" in cmd.stderr
+
+                namespace = group or "myuser1"
+                repo_path = os.path.join(root, "myproject")
+                url = git_output(repo_path, "remote", "get-url", "origin")
+                assert url == f"https://example.com/{namespace}/myproject.git"
+
+                with open(config.name) as f:
+                    repo = toml.loads(f.read())["trees"][0]["repos"][0]
+                assert repo["name"] == "myproject"
+                assert repo["provider_id"] == f"{provider}:4242"
+                assert repo["remotes"][0]["name"] == "origin"
+                assert repo["remotes"][0]["url"] == url
+
+
+def test_repos_new_azure_devops():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as template:
+            with tempfile.NamedTemporaryFile() as config:
+                make_template(template)
+                write_config(config.name, [root])
+
+                args = ["repos", "new", "--config", config.name, "myproject"]
+                args += ["--template", template, "--author", "Jane Doe"]
+                args += ["--provider", "azure-devops"]
+                args += ["--token-command", "echo secret-token:myauthtoken"]
+
+                cmd = grm(args + ["--group", "myorg1/myproject1"])
+                assert cmd.returncode == 0
+                assert "Created project at" in cmd.stdout
+
+                repo_path = os.path.join(root, "myproject")
+                url = git_output(repo_path, "remote", "get-url", "origin")
+                assert url == "https://example.com/myorg1/myproject1/_git/myproject"
+
+                with open(config.name) as f:
+                    repo = toml.loads(f.read())["trees"][0]["repos"][0]
+                assert repo["provider_id"] == "azure-devops:4242"
+
+                # Repositories always belong to a project
+                args[4] = "otherproject"
+                cmd = grm(args)
+                assert cmd.returncode != 0
+                assert "requires a group" in cmd.stderr
+
+
+def test_repos_new_provider_requires_token_command():
+    cmd = grm(
+        ["repos", "new", "myproject", "--template", "/tmp", "--provider", "github"],
+        is_invalid=True,
+    )
+    assert cmd.returncode != 0
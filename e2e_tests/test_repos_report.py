@@ -0,0 +1,74 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+import toml
+from helpers import RepoTree, grm, shell
+
+
+def test_repos_report():
+    with RepoTree() as (root, config, repos):
+        # Sync records when each repository was synced
+        with open(os.path.join(root, ".grm-state.toml")) as f:
+            state = toml.loads(f.read())
+        assert sorted(repo["name"] for repo in state["repos"]) == repos
+        assert all(repo["last_sync"] > 0 for repo in state["repos"])
+
+        shell(f"cd {root}/test && echo '<b>' > newfile")
+
+        with tempfile.TemporaryDirectory() as tmpdir:
+            output = os.path.join(tmpdir, "report")
+            cmd = grm(["repos", "report", "--config", config, "--html", output])
+            assert cmd.returncode == 0
+            assert "Report written" in cmd.stdout
+
+            with open(os.path.join(output, "index.html")) as f:
+                html = f.read()
+
+            assert html.startswith("<!DOCTYPE html>")
+            assert "2 repositories, 2 with warnings" in html
+            for repo in repos:
+                assert f"<td>{repo}</td>" in html
+            assert "Uncommitted changes" in html
+            assert "No remotes configured" in html
+            assert "Never" not in html
+
+            # A missing repository is an error, but does not stop the report
+            shell(f"rm -rf {root}/test")
+            cmd = grm(["repos", "report", "--config", config, "--html", output])
+            assert cmd.returncode == 0
+            with open(os.path.join(output, "index.html")) as f:
+                html = f.read()
+            assert '<tr class="error"><td>test</td><td>Missing</td>' in html
+
+
+def test_repos_report_invalid_directory():
+    with RepoTree() as (root, config, _repos):
+        with tempfile.NamedTemporaryFile() as output:
+            cmd = grm(["repos", "report", "--config", config, "--html", output.name])
+            assert cmd.returncode != 0
+            assert "Error writing report" in cmd.stderr
+
+
+def test_repos_report_named_tree():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            with open(config, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{root}"
+                    name = "work"
+                    """
+                )
+
+            output = os.path.join(tmpdir, "report")
+            cmd = grm(["repos", "report", "--config", config, "--html", output])
+            assert cmd.returncode == 0
+
+            with open(os.path.join(output, "index.html")) as f:
+                html = f.read()
+            assert "<h2>work</h2>" in html
+            assert f'<p class="root">{root}</p>' in html
@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+
+import os
+import shutil
+import subprocess
+import tempfile
+
+import pytest
+from helpers import TempGitFileRemote, grm
+
+
+def write_config(config, roots, remote, worktree_setup=False, share_objects=True):
+    with open(config, "w") as f:
+        if share_objects:
+            f.write(
+                """
+                [clone]
+                share_objects = true
+                """
+            )
+        for root in roots:
+            f.write(
+                f"""
+                [[trees]]
+                root = "{root}"
+
+                [[trees.repos]]
+                name = "test"
+                worktree_setup = {"true" if worktree_setup else "false"}
+
+                [[trees.repos.remotes]]
+                name = "origin"
+                url = "file://{remote}"
+                type = "file"
+                """
+            )
+
+
+def git(git_dir, *args):
+    cmd = subprocess.run(
+        ["git", "--git-dir", git_dir, *args], capture_output=True, text=True
+    )
+    assert cmd.returncode == 0, cmd.stderr
+    return cmd.stdout.strip()
+
+
+def sync(config):
+    cmd = grm(["repos", "sync", "config", "--config", config])
+    assert cmd.returncode == 0
+    return cmd
+
+
+@pytest.mark.parametrize("worktree_setup", [True, False])
+def test_repos_share_objects(worktree_setup):
+    git_dir_name = ".git-main-working-tree" if worktree_setup else ".git"
+    with tempfile.TemporaryDirectory() as work:
+        with tempfile.TemporaryDirectory() as experiments:
+            with TempGitFileRemote() as (remote, head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    write_config(config.name, [work], remote, worktree_setup)
+                    cmd = sync(config.name)
+                    assert "Sharing objects" not in cmd.stdout
+
+                    write_config(
+                        config.name, [work, experiments], remote, worktree_setup
+                    )
+                    cmd = sync(config.name)
+                    assert "Sharing objects with" in cmd.stdout
+
+                    source = os.path.join(work, "test", git_dir_name, "objects")
+                    git_dir = os.path.join(experiments, "test", git_dir_name)
+                    alternates = os.path.join(git_dir, "objects/info/alternates")
+                    with open(alternates) as f:
+                        borrowed = f.read().strip()
+                    assert os.path.realpath(borrowed) == os.path.realpath(source)
+
+                    assert git(git_dir, "rev-parse", "master") == head_commit_sha
+                    upstream = git(git_dir, "rev-parse", "--abbrev-ref", "master@{u}")
+                    assert upstream == "origin/master"
+
+                    write_config(config.name, [experiments], remote, worktree_setup)
+                    cmd = grm(["repos", "dissociate", "--config", config.name, "test"])
+                    assert cmd.returncode == 0
+                    assert not os.path.exists(alternates)
+
+                    shutil.rmtree(os.path.join(work, "test"))
+                    git(git_dir, "fsck")
+                    assert git(git_dir, "rev-parse", "master") == head_commit_sha
+
+                    cmd = grm(["repos", "dissociate", "--config", config.name, "test"])
+                    assert cmd.returncode != 0
+                    assert "does not share objects" in cmd.stderr
+
+
+def test_repos_share_objects_disabled():
+    with tempfile.TemporaryDirectory() as work:
+        with tempfile.TemporaryDirectory() as experiments:
+            with TempGitFileRemote() as (remote, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    write_config(config.name, [work], remote, share_objects=False)
+                    sync(config.name)
+
+                    write_config(
+                        config.name, [work, experiments], remote, share_objects=False
+                    )
+                    cmd = sync(config.name)
+                    assert "Sharing objects" not in cmd.stdout
+                    alternates = os.path.join(
+                        experiments, "test", ".git", "objects", "info", "alternates"
+                    )
+                    assert not os.path.exists(alternates)
+
+
+def test_repos_dissociate_unknown():
+    with tempfile.TemporaryDirectory() as work:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, [work], remote)
+
+                cmd = grm(["repos", "dissociate", "--config", config.name, "missing"])
+                assert cmd.returncode != 0
+                assert "not part of the configuration" in cmd.stderr
+
+                cmd = grm(["repos", "dissociate", "--config", config.name, "test"])
+                assert cmd.returncode != 0
+                assert "does not exist" in cmd.stderr
@@ -0,0 +1,150 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import tempfile
+
+import pytest
+from helpers import TempGitFileRemote, grm, shell
+
+
+def create_tree(root, config):
+    shell(
+        f"""
+        cd {root}
+        mkdir repo1
+        (
+            cd ./repo1
+            git -c init.defaultBranch=master init
+            head -c 204800 /dev/zero > bigfile
+        )
+        mkdir -p ns/repo2
+        (
+            cd ./ns/repo2
+            git -c init.defaultBranch=master init
+        )
+    """
+    )
+
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+
+            [[trees.repos]]
+            name = "repo1"
+
+            [[trees.repos.remotes]]
+            name = "origin"
+            url = "https://github.com/user/repo1.git"
+            type = "https"
+
+            [[trees.repos.remotes]]
+            name = "ssh"
+            url = "git@github.com:user/repo1.git"
+            type = "ssh"
+
+            [[trees.repos]]
+            name = "ns/repo2"
+
+            [[trees.repos.remotes]]
+            name = "origin"
+            url = "https://gitlab.com/user/repo2.git"
+            type = "https"
+
+            [[trees.repos]]
+            name = "missing"
+            """
+        )
+
+
+def test_repos_stats_json():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            create_tree(root, config)
+
+            cmd = grm(["repos", "stats", "--config", config, "--format", "json"])
+            assert cmd.returncode == 0
+            assert "missing: repository does not exist" in cmd.stderr.lower()
+
+            stats = json.loads(cmd.stdout)
+            assert stats["repo_count"] == 2
+            assert stats["worktree_count"] == 0
+
+            assert [r["name"] for r in stats["repos"]] == ["repo1", "ns/repo2"]
+            repo1 = stats["repos"][0]
+            assert repo1["size"] > 204800
+            assert repo1["hosts"] == ["github.com"]
+            assert repo1["namespace"] is None
+            assert stats["repos"][1]["namespace"] == "ns"
+            assert stats["total_size"] == sum([r["size"] for r in stats["repos"]])
+
+            assert {n["namespace"]: n["repos"] for n in stats["namespaces"]} == {
+                None: 1,
+                "ns": 1,
+            }
+            assert {h["host"]: h["repos"] for h in stats["hosts"]} == {
+                "github.com": 1,
+                "gitlab.com": 1,
+            }
+
+
+@pytest.mark.parametrize("top", [None, 1])
+def test_repos_stats_table(top):
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            create_tree(root, config)
+
+            args = ["repos", "stats", "--config", config]
+            if top is not None:
+                args += ["--top", str(top)]
+            cmd = grm(args)
+            assert cmd.returncode == 0
+            assert "github.com" in cmd.stdout
+            assert "gitlab.com" in cmd.stdout
+            assert "repo1" in cmd.stdout
+            if top == 1:
+                assert "ns/repo2" not in cmd.stdout
+            else:
+                assert "ns/repo2" in cmd.stdout
+
+
+def test_repos_stats_worktrees():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                config = os.path.join(tmpdir, "config.toml")
+                with open(config, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+                        worktree_setup = true
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                # This already creates a worktree for the default branch
+                cmd = grm(["repos", "sync", "config", "--config", config])
+                assert cmd.returncode == 0
+
+                cmd = grm(["wt", "add", "mybranch"], cwd=os.path.join(root, "test"))
+                assert cmd.returncode == 0
+
+                cmd = grm(["repos", "stats", "--config", config, "--format", "json"])
+                assert cmd.returncode == 0
+
+                stats = json.loads(cmd.stdout)
+                assert stats["worktree_count"] == 2
+                assert stats["repos"][0]["worktrees"] == 2
+                assert stats["hosts"] == []
@@ -1,6 +1,19 @@
 #!/usr/bin/env python3
 
-from helpers import RepoTree, grm
+import json
+import os
+import tempfile
+import time
+
+import toml
+from helpers import (
+    RepoTree,
+    TempGitFileRemote,
+    TempGitRepositoryWorktree,
+    funcname,
+    grm,
+    shell,
+)
 
 
 def test_repos_sync_worktree_clone():
@@ -9,3 +22,628 @@ def test_repos_sync_worktree_clone():
         assert cmd.returncode == 0
         for repo in repos:
             assert repo in cmd.stdout
+
+
+def test_repos_status_check_without_remotes():
+    with RepoTree() as (root, config, repos):
+        cmd = grm(["repos", "status", "--config", config, "--check"])
+        assert cmd.returncode != 0
+        for repo in repos:
+            assert repo in cmd.stdout
+        assert "no remotes configured" in cmd.stderr.lower()
+
+
+def test_repos_status_check():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                repo_dir = os.path.join(root, "test")
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode == 0
+                assert len(cmd.stderr) == 0
+
+                cmd = grm(["repos", "status", "--check"], cwd=repo_dir)
+                assert cmd.returncode == 0
+                assert len(cmd.stderr) == 0
+
+                shell(f"cd {repo_dir} && echo change > newfile")
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode != 0
+                assert "uncommitted changes" in cmd.stderr.lower()
+
+                cmd = grm(["repos", "status", "--check"], cwd=repo_dir)
+                assert cmd.returncode != 0
+                assert "uncommitted changes" in cmd.stderr.lower()
+
+                # Without --check, the status is informational only
+                cmd = grm(["repos", "status", "--config", config.name])
+                assert cmd.returncode == 0
+
+                shell(
+                    f"""
+                    cd {repo_dir}
+                    git add newfile
+                    git commit -m "newfile"
+                """
+                )
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode != 0
+                assert "ahead" in cmd.stderr.lower()
+
+                shell(f"cd {repo_dir} && git push origin master")
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode == 0
+
+                shell(f"cd {repo_dir} && git branch local-only")
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode != 0
+                assert "local-only has no remote branch" in cmd.stderr.lower()
+
+
+def test_repos_status_check_missing_repo():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{root}"
+
+                    [[trees.repos]]
+                    name = "test"
+                    """
+                )
+
+            cmd = grm(["repos", "status", "--config", config.name, "--check"])
+            assert cmd.returncode != 0
+            assert "does not exist" in cmd.stderr.lower()
+
+
+def setup_sort_and_group(root, config):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+
+            [[trees.repos]]
+            name = "alpha"
+            tags = ["backend"]
+
+            [[trees.repos]]
+            name = "beta"
+            tags = ["backend", "frontend"]
+
+            [[trees.repos]]
+            name = "ns/gamma"
+            """
+        )
+
+    cmd = grm(["repos", "sync", "config", "--config", config])
+    assert cmd.returncode == 0
+
+    for name, date, dirty_files in [
+        ("alpha", "2020-01-01T00:00:00", 1),
+        ("beta", "2010-01-01T00:00:00", 2),
+        ("ns/gamma", "2015-01-01T00:00:00", 0),
+    ]:
+        shell(
+            f"""
+            cd {root}/{name}
+            echo test > committed
+            git add committed
+            GIT_COMMITTER_DATE={date} git commit --date={date} -m "commit"
+            """
+        )
+        for i in range(dirty_files):
+            shell(f"cd {root}/{name} && echo change > dirty{i}")
+
+
+def assert_order(output, *names):
+    positions = [output.index(name) for name in names]
+    assert positions == sorted(positions)
+
+
+def test_repos_status_sort():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            setup_sort_and_group(root, config)
+
+            cmd = grm(["repos", "status", "--config", config, "--sort", "name"])
+            assert cmd.returncode == 0
+            assert_order(cmd.stdout, "alpha", "beta", "ns/gamma")
+
+            cmd = grm(["repos", "status", "--config", config, "--sort", "dirty"])
+            assert cmd.returncode == 0
+            assert_order(cmd.stdout, "beta", "alpha", "ns/gamma")
+
+            cmd = grm(["repos", "status", "--config", config, "--sort", "age"])
+            assert cmd.returncode == 0
+            assert_order(cmd.stdout, "beta", "ns/gamma", "alpha")
+
+
+def test_repos_status_group_by():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            setup_sort_and_group(root, config)
+
+            cmd = grm(["repos", "status", "--config", config, "--group-by", "tag"])
+            assert cmd.returncode == 0
+            assert_order(cmd.stdout, "Tag: backend", "Tag: frontend", "No tags")
+            # beta has two tags, so it is shown twice
+            assert cmd.stdout.count("beta") == 2
+            assert cmd.stdout.count("ns/gamma") == 1
+            assert cmd.stdout.index("ns/gamma") > cmd.stdout.index("No tags")
+
+            cmd = grm(
+                [
+                    "repos",
+                    "status",
+                    "--config",
+                    config,
+                    "--group-by",
+                    "namespace",
+                    "--sort",
+                    "dirty",
+                ]
+            )
+            assert cmd.returncode == 0
+            assert_order(
+                cmd.stdout, "Namespace: ns", "ns/gamma", "No namespace", "beta", "alpha"
+            )
+
+
+def test_repos_status_json():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            setup_sort_and_group(root, config)
+
+            cmd = grm(
+                ["repos", "status", "--config", config, "--json", "--sort", "dirty"]
+            )
+            assert cmd.returncode == 0
+            repos = json.loads(cmd.stdout)
+            assert [repo["name"] for repo in repos] == ["beta", "alpha", "ns/gamma"]
+            beta = repos[0]
+            assert beta["tree"] == root
+            assert beta["path"] == os.path.join(root, "beta")
+            assert beta["worktree_setup"] is False
+            assert isinstance(beta["last_update"], int)
+            assert beta["status"]["operation"] is None
+            assert beta["status"]["changes"]["files_new"] == 2
+            assert repos[2]["status"]["changes"] is None
+
+            cmd = grm(["repos", "status", "--json"], cwd=os.path.join(root, "beta"))
+            assert cmd.returncode == 0
+            [repo] = json.loads(cmd.stdout)
+            assert repo["tree"] is None
+            assert repo["name"] == "beta"
+            assert repo["status"]["changes"]["files_new"] == 2
+
+            cmd = grm(
+                ["repos", "status", "--config", config, "--json", "--group-by", "tag"],
+                is_invalid=True,
+            )
+            assert cmd.returncode != 0
+
+
+def test_repos_status_sort_requires_config():
+    cmd = grm(["repos", "status", "--sort", "name"], is_invalid=True)
+    assert cmd.returncode != 0
+
+
+def test_repos_status_single_tree():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            with open(config, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{root}/work"
+                    name = "work"
+
+                    [[trees.repos]]
+                    name = "work-repo"
+
+                    [[trees]]
+                    root = "{root}/private"
+
+                    [[trees.repos]]
+                    name = "private-repo"
+                    """
+                )
+
+            # Only the selected tree is synced, so the other one is missing
+            cmd = grm(["repos", "sync", "config", "--config", config, "--tree", "work"])
+            assert cmd.returncode == 0
+
+            cmd = grm(["repos", "status", "--config", config, "--tree", "work"])
+            assert cmd.returncode == 0
+            assert "work-repo" in cmd.stdout
+            assert "private-repo" not in cmd.stdout + cmd.stderr
+
+            cmd = grm(["repos", "status", "--config", config])
+            assert "private-repo: Repository does not exist" in cmd.stderr
+            # The tables are told apart by the names of the trees
+            assert "Tree: work" in cmd.stdout
+            assert f"Tree: {root}/private" in cmd.stdout
+
+            cmd = grm(
+                ["repos", "status", "--config", config, "--tree", f"{root}/other"]
+            )
+            assert cmd.returncode != 0
+            assert "There is no tree with name or root" in cmd.stderr
+
+
+def test_repos_status_tree_requires_config():
+    cmd = grm(["repos", "status", "--tree", "work"], is_invalid=True)
+    assert cmd.returncode != 0
+
+
+def test_repos_status_operation_in_progress():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            setup_sort_and_group(root, config)
+
+            cmd = grm(["repos", "status", "--config", config, "--only-in-progress"])
+            assert cmd.returncode == 0
+            assert "No repository has an operation in progress" in cmd.stdout
+            assert "alpha" not in cmd.stdout
+
+            shell(
+                f"""
+                cd {root}/alpha
+                git checkout --quiet -b other
+                echo other > committed
+                git commit --quiet -am "other"
+                git checkout --quiet -
+                echo mine > committed
+                git commit --quiet -am "mine"
+                git merge other || true
+                """
+            )
+
+            cmd = grm(["repos", "status", "--config", config])
+            assert cmd.returncode == 0
+            assert "Merge in progress" in cmd.stdout
+
+            cmd = grm(["repos", "status", "--config", config, "--only-in-progress"])
+            assert cmd.returncode == 0
+            assert "alpha" in cmd.stdout
+            assert "beta" not in cmd.stdout
+            assert "ns/gamma" not in cmd.stdout
+
+            cmd = grm(["repos", "status", "--config", config, "--check"])
+            assert cmd.returncode != 0
+            assert "alpha: Merge in progress" in cmd.stderr
+
+            cmd = grm(
+                ["repos", "status", "--config", config, "--only-in-progress", "--json"]
+            )
+            assert cmd.returncode == 0
+            [repo] = json.loads(cmd.stdout)
+            assert repo["name"] == "alpha"
+            assert repo["status"]["operation"] == "merge"
+
+            cmd = grm(["repos", "status"], cwd=os.path.join(root, "alpha"))
+            assert cmd.returncode == 0
+            assert "Merge in progress" in cmd.stdout
+
+
+def test_repos_status_only_in_progress_requires_config():
+    cmd = grm(["repos", "status", "--only-in-progress"], is_invalid=True)
+    assert cmd.returncode != 0
+
+
+def test_repos_status_stale_after():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            config = os.path.join(tmpdir, "config.toml")
+            setup_sort_and_group(root, config)
+
+            cmd = grm(["repos", "status", "--config", config])
+            assert cmd.returncode == 0
+            assert "Last update" in cmd.stdout
+            assert "today" in cmd.stdout
+
+            cmd = grm(["repos", "status", "--config", config, "--stale-after", "7d"])
+            assert cmd.returncode == 0
+            assert "No repository is stale" in cmd.stdout
+            assert "alpha" not in cmd.stdout
+
+            state_file = os.path.join(root, ".grm-state.toml")
+            with open(state_file) as f:
+                state = toml.loads(f.read())
+            for repo in state["repos"]:
+                if repo["name"] == "alpha":
+                    repo["last_sync"] = int(time.time()) - 10 * 24 * 60 * 60
+            state["repos"] = [
+                repo for repo in state["repos"] if repo["name"] != "ns/gamma"
+            ]
+            with open(state_file, "w") as f:
+                f.write(toml.dumps(state))
+
+            cmd = grm(["repos", "status", "--config", config, "--stale-after", "7d"])
+            assert cmd.returncode == 0
+            assert "alpha" in cmd.stdout
+            assert "10 days ago" in cmd.stdout
+            assert "beta" not in cmd.stdout
+            # Never synced at all is stale as well
+            assert "ns/gamma" in cmd.stdout
+            assert "never" in cmd.stdout
+
+            cmd = grm(["repos", "status", "--config", config, "--stale-after", "2w"])
+            assert cmd.returncode != 0
+            assert "Invalid duration" in cmd.stderr
+
+
+def test_repos_status_stale_after_requires_config():
+    cmd = grm(["repos", "status", "--stale-after", "7d"], is_invalid=True)
+    assert cmd.returncode != 0
+
+
+def test_repos_status_in_linked_worktree():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        os.mkdir(os.path.join(base_dir, "test", "subdir"))
+        for cwd in ["test", "test/subdir"]:
+            cmd = grm(["repos", "status"], cwd=os.path.join(base_dir, cwd))
+            assert cmd.returncode == 0
+            assert len(cmd.stderr) == 0
+            # The main repository, not the worktree, followed by the worktrees
+            assert os.path.basename(base_dir) in cmd.stdout
+            assert "test (current)" in cmd.stdout
+
+        cmd = grm(["repos", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "test" in cmd.stdout
+        assert "(current)" not in cmd.stdout
+
+
+def test_repos_status_in_plain_linked_worktree():
+    with RepoTree() as (root, _config, _repos):
+        shell(
+            f"""
+            cd {root}/test
+            git commit --allow-empty -m "initial"
+            git worktree add --quiet -b feature ../linked
+            """
+        )
+        cmd = grm(["repos", "status"], cwd=os.path.join(root, "linked"))
+        assert cmd.returncode == 0
+        assert "test" in cmd.stdout
+        assert "linked" not in cmd.stdout
+
+
+def test_repos_status_unpushed_submodule():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with TempGitFileRemote() as (submodule_remote, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    with open(config.name, "w") as f:
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}"
+
+                            [[trees.repos]]
+                            name = "test"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+
+                    repo_dir = os.path.join(root, "test")
+                    shell(
+                        f"""
+                        cd {repo_dir}
+                        git -c protocol.file.allow=always submodule add \\
+                            file://{submodule_remote} sub
+                        git commit -m "add submodule"
+                        git push origin master
+                        """
+                    )
+
+                    status = ["repos", "status", "--check", "--submodules"]
+                    cmd = grm(status + ["--config", config.name])
+                    assert cmd.returncode == 0
+
+                    shell(
+                        f"""
+                        cd {repo_dir}/sub
+                        git commit --allow-empty -m "not pushed"
+                        cd {repo_dir}
+                        git commit -am "bump submodule"
+                        git push origin master
+                        """
+                    )
+
+                    for args in [["--config", config.name], []]:
+                        cmd = grm(status + args, cwd=repo_dir)
+                        assert cmd.returncode != 0
+                        assert "Unpushed submodule: sub" in cmd.stdout
+                        assert "Submodule sub points to commit" in cmd.stderr
+
+                    # The check is opt-in
+                    cmd = grm(["repos", "status", "--check"], cwd=repo_dir)
+                    assert cmd.returncode == 0
+
+                    shell(f"cd {repo_dir}/sub && git push origin HEAD:master")
+
+                    cmd = grm(status + ["--config", config.name])
+                    assert cmd.returncode == 0
+
+
+def test_repos_status_verify_remotes():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                cfg = f"""
+                    [[trees]]
+                    root = "{root}"
+
+                    [[trees.repos]]
+                    name = "test"
+
+                    [[trees.repos.remotes]]
+                    name = "origin"
+                    url = "file://{remote}"
+                    type = "file"
+                    """
+                with open(config.name, "w") as f:
+                    f.write(cfg)
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                args = ["repos", "status", "--config", config.name]
+                cmd = grm(args + ["--verify-remotes", "--check"])
+                assert cmd.returncode == 0
+                assert "Verified 1 remotes, all are reachable" in cmd.stdout
+
+                with open(config.name, "w") as f:
+                    f.write(
+                        cfg
+                        + f"""
+                        [[trees.repos.remotes]]
+                        name = "gone"
+                        url = "file://{remote}-gone"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(args + ["--verify-remotes"])
+                assert cmd.returncode == 0
+                assert 'test: Remote "gone"' in cmd.stderr
+                assert "does not appear to be a git repository" in cmd.stderr
+                assert 'Remote "origin"' not in cmd.stderr
+
+                cmd = grm(args + ["--verify-remotes", "--check"])
+                assert cmd.returncode != 0
+                assert 'test: Remote "gone"' in cmd.stderr
+
+                # Verifying is never done without asking for it
+                cmd = grm(args)
+                assert cmd.returncode == 0
+                assert "gone" not in cmd.stderr
+
+
+def test_repos_status_detached_head():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                shell(f"cd {os.path.join(root, 'test')} && git checkout -q --detach")
+
+                cmd = grm(["repos", "status", "--config", config.name])
+                assert cmd.returncode == 0
+                assert len(cmd.stderr) == 0
+                assert "Detached at" in cmd.stdout
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode != 0
+                assert "test: HEAD is detached at" in cmd.stdout + cmd.stderr
+                assert "git switch" in cmd.stdout + cmd.stderr
+
+
+def test_repos_status_jj_colocated():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                # What "jj git init --colocate" leaves behind: jj ignores its own
+                # directory, detaches HEAD and keeps all changes in its working copy
+                # commit
+                repo_dir = os.path.join(root, "test")
+                shell(
+                    f"""
+                    cd {repo_dir}
+                    mkdir .jj
+                    echo '/*' > .jj/.gitignore
+                    git checkout --quiet --detach
+                    echo change > newfile
+                    """
+                )
+
+                cmd = grm(["repos", "status", "--config", config.name, "--check"])
+                assert cmd.returncode == 0
+                assert len(cmd.stderr) == 0
+                assert "jj" in cmd.stdout
+                assert "Detached" in cmd.stdout
+
+                cmd = grm(["repos", "status", "--config", config.name, "--skip-jj"])
+                assert cmd.returncode == 0
+                assert "test" not in cmd.stdout
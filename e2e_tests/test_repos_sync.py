@@ -1,7 +1,9 @@
 #!/usr/bin/env python3
 
+import json
 import os
 import re
+import shutil
 import subprocess
 import tempfile
 import textwrap
@@ -15,6 +17,7 @@ from helpers import (
     checksum_directory,
     grm,
     shell,
+    write_config,
 )
 
 templates = {
@@ -303,6 +306,87 @@ def test_repos_sync_unmanaged_repos(configtype):
                 )
 
 
+def test_repos_sync_unmanaged_non_utf8_repo():
+    with tempfile.TemporaryDirectory() as root:
+        subprocess.run(
+            ["git", "init", "--quiet", os.path.join(root.encode(), b"invalid\xff")],
+            check=True,
+        )
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(templates["repo_simple"]["toml"].format(root=root))
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode == 0
+            assert "Found unmanaged repository" in cmd.stderr
+            assert "invalid\ufffd" in cmd.stderr
+
+
+@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+def test_repos_sync_symlinked_repo(configtype):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.TemporaryDirectory() as outside:
+                target = os.path.join(outside, "test")
+                git.Repo.clone_from(f"file://{remote}", target)
+
+                link = os.path.join(root, "test")
+                os.symlink(target, link)
+
+                with tempfile.NamedTemporaryFile() as config:
+                    with open(config.name, "w") as f:
+                        f.write(
+                            templates["repo_with_remote"][configtype].format(
+                                root=root, remote=remote, remotename="origin"
+                            )
+                        )
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "cloning" not in cmd.stdout.lower()
+                    assert "unmanaged" not in cmd.stderr.lower()
+
+                    assert os.path.islink(link)
+                    assert os.readlink(link) == target
+                    with git.Repo(target) as repo:
+                        assert str(repo.head.commit) == head_commit_sha
+                        urls = list(repo.remote("origin").urls)
+                        assert urls == [f"file://{remote}"]
+
+
+@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("target_exists", [True, False])
+def test_repos_sync_symlinked_repo_missing_target(configtype, target_exists):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.TemporaryDirectory() as outside:
+                target = os.path.join(outside, "test")
+                if target_exists:
+                    os.mkdir(target)
+
+                link = os.path.join(root, "test")
+                os.symlink(target, link)
+
+                with tempfile.NamedTemporaryFile() as config:
+                    with open(config.name, "w") as f:
+                        f.write(
+                            templates["repo_with_remote"][configtype].format(
+                                root=root, remote=remote, remotename="origin"
+                            )
+                        )
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode != 0
+                    assert "symlink" in cmd.stderr.lower()
+
+                    assert os.path.islink(link)
+                    assert os.readlink(link) == target
+                    if target_exists:
+                        assert os.listdir(target) == []
+                    else:
+                        assert not os.path.exists(target)
+
+
 @pytest.mark.parametrize("configtype", ["toml", "yaml"])
 def test_repos_sync_root_is_file(configtype):
     with tempfile.NamedTemporaryFile() as target:
@@ -315,6 +399,107 @@ def test_repos_sync_root_is_file(configtype):
             assert "notadirectory" in cmd.stderr.lower()
 
 
+@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+def test_repos_sync_unrelated_root(configtype):
+    with tempfile.TemporaryDirectory() as target:
+        os.mkdir(os.path.join(target, "Photos"))
+        with open(os.path.join(target, "notes.txt"), "w") as f:
+            f.write("important\n")
+        # Hidden entries are ignored
+        with open(os.path.join(target, ".directory"), "w") as f:
+            f.write("")
+
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(templates["repo_simple"][configtype].format(root=target))
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode != 0
+            assert '"Photos", "notes.txt"' in cmd.stderr
+            assert "--allow-existing-root" in cmd.stderr
+            assert not os.path.exists(os.path.join(target, "test"))
+
+            cmd = grm(
+                ["repos", "sync", "config", "--config", config.name]
+                + ["--allow-existing-root"]
+            )
+            assert cmd.returncode == 0
+            assert os.path.exists(os.path.join(target, "test"))
+
+            # The root was synced before, so there is nothing to confirm anymore
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode == 0
+
+
+@pytest.mark.parametrize("dir_mode", ["0750", "2770"])
+def test_repos_sync_dir_mode(dir_mode):
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                root = os.path.join(target, "root")
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+                        dir_mode = "{dir_mode}"
+
+                        [[trees.repos]]
+                        name = "namespace/test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                for directory in ["", "namespace", "namespace/test"]:
+                    mode = os.stat(os.path.join(root, directory)).st_mode
+                    assert oct(mode & 0o7777) == oct(int(dir_mode, 8))
+
+                # Directories that already exist are left alone
+                os.chmod(root, 0o700)
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert os.stat(root).st_mode & 0o7777 == 0o700
+
+
+def test_repos_sync_invalid_dir_mode():
+    with tempfile.TemporaryDirectory() as target:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{target}"
+                    dir_mode = "rwxr-x---"
+                    """
+                )
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode != 0
+            assert "Invalid dir_mode" in cmd.stderr
+
+
+def test_repos_sync_existing_root_with_repos():
+    with tempfile.TemporaryDirectory() as target:
+        # Configured repositories and other git repositories are no reason to refuse
+        os.makedirs(os.path.join(target, "test"))
+        subprocess.run(["git", "init", "-q", os.path.join(target, "unmanaged")])
+
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(templates["repo_simple"]["toml"].format(root=target))
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode == 0
+            assert "Found unmanaged repository" in cmd.stderr
+
+
 @pytest.mark.parametrize("configtype", ["toml", "yaml"])
 def test_repos_sync_normal_clone(configtype):
     with tempfile.TemporaryDirectory() as target:
@@ -353,6 +538,65 @@ def test_repos_sync_normal_clone(configtype):
                         assert urls[0] == f"file://{remote2}"
 
 
+def test_repos_sync_repo_with_dir():
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, remote_head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{target}"
+
+                        [[trees.repos]]
+                        name = "me/my-dotfiles-2024"
+                        dir = "dotfiles"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "my-dotfiles-2024" in cmd.stdout
+
+                assert not os.path.exists(os.path.join(target, "me"))
+                with git.Repo(os.path.join(target, "dotfiles")) as repo:
+                    assert str(repo.head.commit) == remote_head_commit_sha
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "found unmanaged repository" not in cmd.stderr.lower()
+
+                cmd = grm(["repos", "status", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "does not exist" not in cmd.stdout.lower()
+
+
+def test_repos_sync_repo_with_invalid_dir():
+    with tempfile.TemporaryDirectory() as target:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{target}"
+
+                    [[trees.repos]]
+                    name = "test"
+                    dir = "../outside"
+                    """
+                )
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode != 0
+            assert "Invalid repository dir" in cmd.stdout + cmd.stderr
+            assert not os.path.exists(os.path.join(target, "..", "outside"))
+
+
 @pytest.mark.parametrize("configtype", ["toml", "yaml"])
 def test_repos_sync_repo_in_subdirectory(configtype):
     with tempfile.TemporaryDirectory() as target:
@@ -431,6 +675,42 @@ def test_repos_sync_nested_clone(configtype):
                     assert "found unmanaged repository" not in cmd.stderr.lower()
 
 
+def test_repos_sync_jobs():
+    # Nested repositories are still cloned one after the other
+    names = ["one", "two", "three", "four", "outer", "outer/inner", "five"]
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, remote_head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(f'[[trees]]\nroot = "{target}"\n')
+                    for name in names:
+                        f.write(
+                            f"""
+                            [[trees.repos]]
+                            name = "{name}"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                args = ["repos", "sync", "config", "--config", config.name]
+                cmd = grm(args + ["--jobs", "4"])
+                assert cmd.returncode == 0
+                for name in names:
+                    with git.Repo(os.path.join(target, name)) as repo:
+                        assert str(repo.head.commit) == remote_head_commit_sha
+
+                cmd = grm(args + ["--jobs", "4"])
+                assert cmd.returncode == 0
+                assert "found unmanaged repository" not in cmd.stderr.lower()
+
+                cmd = grm(args + ["--jobs", "0"])
+                assert cmd.returncode != 0
+
+
 @pytest.mark.parametrize("configtype", ["toml", "yaml"])
 def test_repos_sync_normal_init(configtype):
     with tempfile.TemporaryDirectory() as target:
@@ -684,6 +964,7 @@ def test_repos_sync_worktree_clone(configtype, init_worktree):
                     if init_worktree is True or init_worktree == "default":
                         assert set(os.listdir(worktree_dir)) == {
                             ".git-main-working-tree",
+                            ".grm-state.json",
                             "master",
                         }
                     else:
@@ -828,3 +1109,1255 @@ def test_repos_sync_worktree_change_to_normal(configtype):
                     assert cmd.returncode != 0
                     assert "already exists" in cmd.stderr
                     assert "using a worktree setup" in cmd.stderr
+
+
+@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+@pytest.mark.parametrize("remote_exists", [True, False])
+def test_repos_sync_notify_command(configtype, remote_exists):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                report_file = os.path.join(tmpdir, "report.json")
+                command = f"cat > {report_file}"
+                if not remote_exists:
+                    remote = os.path.join(tmpdir, "doesnotexist")
+
+                config = os.path.join(tmpdir, "config")
+                with open(config, "w") as f:
+                    if configtype == "toml":
+                        f.write(f"[notify]\ncommand = '{command}'\n")
+                    else:
+                        f.write(f"notify:\n  command: '{command}'\n")
+                    f.write(
+                        templates["repo_with_remote"][configtype].format(
+                            root=root, remote=remote, remotename="origin"
+                        )
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config])
+                if remote_exists:
+                    assert cmd.returncode == 0
+                else:
+                    assert cmd.returncode != 0
+
+                with open(report_file) as f:
+                    report = json.load(f)
+
+                assert report["operation"] == "sync"
+                assert report["success"] is remote_exists
+                assert len(report["repos"]) == 1
+                assert report["repos"][0]["name"] == "test"
+                if remote_exists:
+                    assert report["repos"][0]["error"] is None
+                else:
+                    assert "clone" in report["repos"][0]["error"]
+
+
+@pytest.mark.parametrize("configtype", ["toml", "yaml"])
+def test_repos_sync_notify_command_fails(configtype):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    if configtype == "toml":
+                        f.write("[notify]\ncommand = 'echo broken >&2; exit 1'\n")
+                    else:
+                        f.write("notify:\n  command: 'echo broken >&2; exit 1'\n")
+                    f.write(
+                        templates["repo_with_remote"][configtype].format(
+                            root=root, remote=remote, remotename="origin"
+                        )
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "notify command failed: broken" in cmd.stderr.lower()
+
+
+def test_repos_sync_renamed_provider_repo():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+
+                def write_config(name):
+                    with open(config.name, "w") as f:
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}"
+
+                            [[trees.repos]]
+                            name = "{name}"
+                            provider_id = "github:1"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                write_config("oldname")
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                old_path = os.path.join(root, "oldname")
+                new_path = os.path.join(root, "newnamespace", "newname")
+                provider_id = subprocess.run(
+                    ["git", "-C", old_path, "config", "grm.providerid"],
+                    capture_output=True,
+                    text=True,
+                ).stdout.strip()
+                assert provider_id == "github:1"
+
+                # The repo is renamed and transferred on the forge
+                write_config("newnamespace/newname")
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode != 0
+                assert "--move-renamed" in cmd.stderr
+                assert old_path in cmd.stderr
+                assert os.path.exists(old_path)
+                assert not os.path.exists(new_path)
+
+                cmd = grm(
+                    [
+                        "repos",
+                        "sync",
+                        "config",
+                        "--config",
+                        config.name,
+                        "--move-renamed",
+                    ]
+                )
+                assert cmd.returncode == 0
+                assert "renamed" in cmd.stdout.lower()
+                assert not os.path.exists(old_path)
+                assert "unmanaged" not in cmd.stderr.lower()
+                with git.Repo(new_path) as repo:
+                    assert str(repo.head.commit) == head_commit_sha
+
+                cmd = grm(["repos", "find", "local", root])
+                assert cmd.returncode == 0
+                assert 'provider_id = "github:1"' in cmd.stdout
+
+
+def write_canonical_remote_config(
+    config, root, remotes, canonical_remote_name=None, repo_remote_name=None
+):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+            """
+        )
+        if canonical_remote_name is not None:
+            f.write(
+                f"""
+                canonical_remote_name = "{canonical_remote_name}"
+                """
+            )
+        f.write(
+            """
+            [[trees.repos]]
+            name = "test"
+            """
+        )
+        if repo_remote_name is not None:
+            f.write(
+                f"""
+                remote_name = "{repo_remote_name}"
+                """
+            )
+        for name, url in remotes:
+            f.write(
+                f"""
+                [[trees.repos.remotes]]
+                name = "{name}"
+                url = "file://{url}"
+                type = "file"
+                """
+            )
+
+
+def test_repos_sync_canonical_remote_name_clone():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_canonical_remote_config(
+                    config.name, root, [("upstream", remote)], "origin"
+                )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                with git.Repo(os.path.join(root, "test")) as repo:
+                    assert [r.name for r in repo.remotes] == ["origin"]
+                    assert repo.remotes.origin.url == f"file://{remote}"
+
+
+def test_repos_sync_canonical_remote_name_rename():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote1, _remote1_head_commit_sha):
+            with TempGitFileRemote() as (remote2, _remote2_head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    remotes = [("upstream", remote1), ("fork", remote2)]
+                    write_canonical_remote_config(config.name, root, remotes)
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+
+                    repo_path = os.path.join(root, "test")
+
+                    def git_output(*args):
+                        return subprocess.run(
+                            ["git", "-C", repo_path] + list(args),
+                            capture_output=True,
+                            text=True,
+                        ).stdout.strip()
+
+                    with git.Repo(repo_path) as repo:
+                        assert {r.name for r in repo.remotes} == {"upstream", "fork"}
+                    assert git_output("config", "branch.master.remote") == "upstream"
+
+                    write_canonical_remote_config(config.name, root, remotes, "origin")
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "Renaming remote" in cmd.stdout
+
+                    with git.Repo(repo_path) as repo:
+                        assert {r.name for r in repo.remotes} == {"origin", "fork"}
+                        assert repo.remotes.origin.url == f"file://{remote1}"
+                    # Tracking branches are renamed together with the remote
+                    assert git_output("config", "branch.master.remote") == "origin"
+                    assert "origin/master" in git_output("branch", "--remotes")
+
+                    # Nothing to do anymore
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "Renaming remote" not in cmd.stdout
+
+
+def test_repos_sync_canonical_remote_name_conflict():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote1, _remote1_head_commit_sha):
+            with TempGitFileRemote() as (remote2, _remote2_head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+                    write_canonical_remote_config(
+                        config.name,
+                        root,
+                        [("upstream", remote1), ("origin", remote2)],
+                        "origin",
+                    )
+
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode != 0
+                    assert "not the first remote" in cmd.stderr
+                    assert not os.path.exists(os.path.join(root, "test"))
+
+
+def test_repos_sync_repo_remote_name():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                # The name of the repository wins over the one of the tree
+                write_canonical_remote_config(
+                    config.name, root, [("upstream", remote)], "origin", "github"
+                )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                repo_path = os.path.join(root, "test")
+                with git.Repo(repo_path) as repo:
+                    assert [r.name for r in repo.remotes] == ["github"]
+
+                write_canonical_remote_config(
+                    config.name, root, [("upstream", remote)], "origin"
+                )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert 'Renaming remote "github" to "origin"' in cmd.stdout
+
+                with git.Repo(repo_path) as repo:
+                    assert [r.name for r in repo.remotes] == ["origin"]
+                    assert repo.remotes.origin.url == f"file://{remote}"
+
+
+def test_repos_sync_tree_remote_name():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+                        remote_name = "origin"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "upstream"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                with git.Repo(os.path.join(root, "test")) as repo:
+                    assert [r.name for r in repo.remotes] == ["origin"]
+
+
+def test_repos_sync_diverged_default_branch():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_canonical_remote_config(config.name, root, [("origin", remote)])
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "diverged" not in cmd.stderr
+
+                repo_path = os.path.join(root, "test")
+                with tempfile.TemporaryDirectory() as other_clone:
+                    # Rewrite the history of the remote, and commit something
+                    # else locally
+                    shell(
+                        f"""
+                        git clone --quiet file://{remote} {other_clone}
+                        cd {other_clone}
+                        git reset --hard HEAD~1
+                        echo test > force-pushed
+                        git add force-pushed
+                        git commit -m force-pushed
+                        git push --force origin master
+
+                        cd {repo_path}
+                        echo test > local
+                        git add local
+                        git commit -m local
+                        git fetch origin
+                    """
+                    )
+
+                with git.Repo(repo_path) as repo:
+                    local_commit = repo.head.commit.hexsha
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert (
+                    'Default branch "master" has diverged from "origin/master"'
+                    in cmd.stderr
+                )
+                assert "2 local and 1 remote commits" in cmd.stderr
+
+                # Nothing is touched
+                with git.Repo(repo_path) as repo:
+                    assert repo.head.commit.hexsha == local_commit
+
+
+def test_repos_sync_default_branch_ahead():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_canonical_remote_config(config.name, root, [("origin", remote)])
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                shell(
+                    f"""
+                    cd {os.path.join(root, "test")}
+                    echo test > local
+                    git add local
+                    git commit -m local
+                """
+                )
+
+                # Only being ahead is perfectly normal
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "diverged" not in cmd.stderr
+
+
+@pytest.mark.parametrize(
+    "name",
+    [
+        "..",
+        ".",
+        "../escaped",
+        "../../escaped",
+        "namespace/../../escaped",
+        "namespace/..",
+        "./repo",
+        "namespace//repo",
+        "/tmp/absolute",
+    ],
+)
+def test_repos_sync_path_traversal(name):
+    with tempfile.TemporaryDirectory() as parent:
+        root = os.path.join(parent, "a", "root")
+        os.makedirs(root)
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "{name}"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+
+                        [[trees.repos]]
+                        name = "valid"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                for subcommand in (["sync", "config"], ["fetch"]):
+                    cmd = grm(["repos"] + subcommand + ["--config", config.name])
+                    assert cmd.returncode != 0
+                    assert "Invalid repository name" in cmd.stderr
+
+                # Only the valid repository was cloned, nothing else was touched
+                assert sorted(os.listdir(root)) == [".grm-state.toml", "valid"]
+                assert sorted(os.listdir(parent)) == ["a"]
+                assert os.listdir(os.path.join(parent, "a")) == ["root"]
+                assert not os.path.exists("/tmp/absolute")
+
+
+def create_conflict(repo_path, kind):
+    if kind == "directory":
+        os.makedirs(repo_path)
+        with open(os.path.join(repo_path, "file"), "w") as f:
+            f.write("important")
+    elif kind == "repository":
+        shell(
+            f"""
+            git init --quiet {repo_path}
+            git -C {repo_path} remote add origin https://example.com/other.git
+            echo important > {repo_path}/file
+            """
+        )
+    else:
+        raise NotImplementedError()
+
+
+@pytest.mark.parametrize("kind", ["directory", "repository"])
+def test_repos_sync_conflict_default(kind):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                repo_path = os.path.join(root, "test")
+                create_conflict(repo_path, kind)
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                if kind == "directory":
+                    assert cmd.returncode != 0
+                    assert "not a git repository" in cmd.stderr
+                    assert "--on-conflict" in cmd.stderr
+                    assert os.listdir(repo_path) == ["file"]
+                else:
+                    # The existing repository is adopted
+                    assert cmd.returncode == 0
+                    assert "none of the configured remotes" in cmd.stdout
+                    assert "adopting it" in cmd.stdout
+                    with git.Repo(repo_path) as repo:
+                        assert repo.remotes.origin.url == f"file://{remote}"
+
+
+@pytest.mark.parametrize("kind", ["directory", "repository"])
+def test_repos_sync_conflict_skip(kind):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                repo_path = os.path.join(root, "test")
+                create_conflict(repo_path, kind)
+                before = checksum_directory(repo_path)
+
+                cmd = grm(
+                    [
+                        "repos",
+                        "sync",
+                        "config",
+                        "--config",
+                        config.name,
+                        "--on-conflict",
+                        "skip",
+                    ]
+                )
+                assert cmd.returncode == 0
+                assert "skipped it" in cmd.stderr
+                assert checksum_directory(repo_path) == before
+
+
+@pytest.mark.parametrize("kind", ["directory", "repository"])
+@pytest.mark.parametrize("interactive", [True, False])
+def test_repos_sync_conflict_move_aside(kind, interactive):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                repo_path = os.path.join(root, "test")
+                create_conflict(repo_path, kind)
+
+                args = ["repos", "sync", "config", "--config", config.name]
+                if interactive:
+                    # An invalid answer is asked again
+                    cmd = grm(args + ["--on-conflict", "ask"], input="x\nm\n")
+                    assert "Invalid answer" in cmd.stderr
+                else:
+                    cmd = grm(args + ["--on-conflict", "move-aside"])
+                assert cmd.returncode == 0
+                assert "test.bak" in cmd.stdout
+
+                with open(os.path.join(root, "test.bak", "file")) as f:
+                    assert f.read().strip() == "important"
+                with git.Repo(repo_path) as repo:
+                    assert repo.head.commit.hexsha == head_commit_sha
+
+                # Does not overwrite an existing backup
+                shutil.rmtree(repo_path)
+                create_conflict(repo_path, kind)
+                cmd = grm(args + ["--on-conflict", "move-aside"])
+                assert cmd.returncode != 0
+                assert "already exists" in cmd.stderr
+
+
+def test_repos_sync_conflict_ask_without_answer():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                repo_path = os.path.join(root, "test")
+                create_conflict(repo_path, "directory")
+
+                cmd = grm(
+                    [
+                        "repos",
+                        "sync",
+                        "config",
+                        "--config",
+                        config.name,
+                        "--on-conflict",
+                        "ask",
+                    ],
+                    input="",
+                )
+                assert cmd.returncode != 0
+                assert "No answer given" in cmd.stderr
+                assert os.listdir(repo_path) == ["file"]
+
+
+def test_repos_sync_no_conflict_without_remotes():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                repo_path = os.path.join(root, "test")
+                shell(f"git init --quiet {repo_path}")
+
+                cmd = grm(
+                    [
+                        "repos",
+                        "sync",
+                        "config",
+                        "--config",
+                        config.name,
+                        "--on-conflict",
+                        "skip",
+                    ]
+                )
+                assert cmd.returncode == 0
+                assert "skipped" not in cmd.stderr
+                with git.Repo(repo_path) as repo:
+                    assert repo.remotes.origin.url == f"file://{remote}"
+
+
+@pytest.mark.parametrize(
+    "configured_url,protocol,expected_url",
+    [
+        (
+            "https://github.com/user/repo.git",
+            "ssh",
+            "git@github.com:user/repo.git",
+        ),
+        (
+            "git@gitlab.com:group/repo.git",
+            "https",
+            "https://gitlab.com/group/repo.git",
+        ),
+        (
+            "https://git.example.com/repo.git",
+            "ssh",
+            "git@ssh.example.com:repo.git",
+        ),
+        # Unknown hosts are left alone
+        (
+            "https://example.org/repo.git",
+            "ssh",
+            "https://example.org/repo.git",
+        ),
+    ],
+)
+def test_repos_sync_rewrite_protocol(configured_url, protocol, expected_url):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                remote_type = "ssh" if configured_url.startswith("git@") else "https"
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[host_mappings]]
+                        https = "git.example.com"
+                        ssh = "git@ssh.example.com"
+
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+
+                        [[trees.repos.remotes]]
+                        name = "forge"
+                        url = "{configured_url}"
+                        type = "{remote_type}"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                repo_path = os.path.join(root, "test")
+                with git.Repo(repo_path) as repo:
+                    assert repo.remotes.forge.url == configured_url
+
+                cmd = grm(
+                    [
+                        "repos",
+                        "sync",
+                        "config",
+                        "--config",
+                        config.name,
+                        "--rewrite-protocol",
+                        protocol,
+                    ]
+                )
+                assert cmd.returncode == 0
+                with git.Repo(repo_path) as repo:
+                    assert repo.remotes.forge.url == expected_url
+                    assert repo.remotes.origin.url == f"file://{remote}"
+
+                # Without the option, the configuration is authoritative again
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                with git.Repo(repo_path) as repo:
+                    assert repo.remotes.forge.url == configured_url
+
+
+def test_repos_sync_keeps_local_changes():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote1, _head_commit_sha):
+            with TempGitFileRemote() as (remote2, _head_commit_sha):
+                with tempfile.NamedTemporaryFile() as config:
+
+                    def write_config(remotes):
+                        with open(config.name, "w") as f:
+                            f.write(
+                                f"""
+                                [[trees]]
+                                root = "{root}"
+
+                                [[trees.repos]]
+                                name = "test"
+                                """
+                            )
+                            for name, remote in remotes:
+                                f.write(
+                                    f"""
+                                    [[trees.repos.remotes]]
+                                    name = "{name}"
+                                    url = "file://{remote}"
+                                    type = "file"
+                                    """
+                                )
+
+                    def sync():
+                        cmd = grm(["repos", "sync", "config", "--config", config.name])
+                        assert cmd.returncode == 0
+                        return cmd
+
+                    def remotes():
+                        with git.Repo(repo_path) as repo:
+                            return {r.name: list(r.urls)[0] for r in repo.remotes}
+
+                    write_config([("origin", remote1), ("mirror", remote2)])
+                    sync()
+                    assert os.path.exists(os.path.join(root, ".grm-state.toml"))
+
+                    # Changes made by hand are left alone
+                    repo_path = os.path.join(root, "test")
+                    shell(
+                        f"""
+                        git -C {repo_path} remote add local file://{remote2}
+                        git -C {repo_path} remote set-url origin file://{remote2}
+                        """
+                    )
+                    cmd = sync()
+                    assert "remote" not in cmd.stdout
+                    assert remotes() == {
+                        "origin": f"file://{remote2}",
+                        "mirror": f"file://{remote2}",
+                        "local": f"file://{remote2}",
+                    }
+
+                    # Changes in the configuration are applied
+                    write_config([("origin", remote2)])
+                    cmd = sync()
+                    assert 'Deleting remote "mirror"' in cmd.stdout
+                    assert remotes() == {
+                        "origin": f"file://{remote2}",
+                        "local": f"file://{remote2}",
+                    }
+
+                    write_config([("origin", remote1)])
+                    sync()
+                    assert remotes() == {
+                        "origin": f"file://{remote1}",
+                        "local": f"file://{remote2}",
+                    }
+
+
+def test_repos_sync_output():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+
+                def write_config(extra_remote):
+                    with open(config.name, "w") as f:
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}"
+
+                            [[trees.repos]]
+                            name = "cloned-repo"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            {extra_remote}
+
+                            [[trees.repos]]
+                            name = "broken-repo"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{root}/does-not-exist"
+                            type = "file"
+                            """
+                        )
+
+                write_config("")
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode != 0
+                assert "         cloned  cloned-repo\n" in cmd.stdout
+                assert "Cloning into" not in cmd.stdout
+                assert "         failed  broken-repo\n" in cmd.stderr
+                assert "failed during clone" in cmd.stderr
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert "     up-to-date  cloned-repo\n" in cmd.stdout
+
+                write_config(
+                    f"""
+                    [[trees.repos.remotes]]
+                    name = "mirror"
+                    url = "file://{remote}"
+                    type = "file"
+                    """
+                )
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert "remotes-updated  cloned-repo\n" in cmd.stdout
+                assert '                 Setting up new remote "mirror"' in cmd.stdout
+
+                cmd = grm(
+                    ["repos", "sync", "config", "--config", config.name, "--plain"]
+                )
+                assert "[✔] cloned-repo: OK" in cmd.stdout
+                assert "[✘] broken-repo: Repository failed during clone" in cmd.stderr
+                assert "Cloning into" in cmd.stdout
+
+
+def test_repos_sync_tree_order():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    for tree, options in [
+                        ("super", 'depends_on = ["sources"]'),
+                        ("sources", 'name = "sources"\nserial = true'),
+                        ("local", ""),
+                    ]:
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}/{tree}"
+                            {options}
+
+                            [[trees.repos]]
+                            name = "{tree}-repo"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                for tree in ["super", "sources", "local"]:
+                    assert os.path.exists(os.path.join(root, tree, f"{tree}-repo"))
+                assert cmd.stdout.index("cloned  sources-repo") < cmd.stdout.index(
+                    "cloned  super-repo"
+                )
+
+
+@pytest.mark.parametrize(
+    "depends_on,error",
+    [
+        ('["unknown"]', 'depends on unknown tree "unknown"'),
+        ('["b"]', "Cyclic dependencies between trees: a, b"),
+    ],
+)
+def test_repos_sync_tree_order_invalid(depends_on, error):
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    [[trees]]
+                    root = "{root}/a"
+                    name = "a"
+                    depends_on = {depends_on}
+
+                    [[trees]]
+                    root = "{root}/b"
+                    name = "b"
+                    depends_on = ["a"]
+                    """
+                )
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode != 0
+            assert error in cmd.stderr
+            assert os.listdir(root) == []
+
+
+def test_repos_sync_single_tree():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    for tree, options in [
+                        ("work", 'name = "work"\ndepends_on = ["sources"]'),
+                        ("sources", 'name = "sources"'),
+                        ("private", ""),
+                        ("private", 'name = "shared"'),
+                    ]:
+                        repo = f"{tree}-repo" if options else "other-repo"
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}/{tree}"
+                            {options}
+
+                            [[trees.repos]]
+                            name = "{repo}"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                args = ["repos", "sync", "config", "--config", config.name]
+
+                cmd = grm(args + ["--tree", "missing"])
+                assert cmd.returncode != 0
+                assert 'There is no tree with name or root "missing"' in cmd.stderr
+                assert os.listdir(root) == []
+
+                # Trees that the selected one depends on are not synced
+                cmd = grm(args + ["--tree", "work"])
+                assert cmd.returncode == 0
+                assert os.listdir(root) == ["work"]
+
+                cmd = grm(args + ["--tree", "shared"])
+                assert cmd.returncode == 0
+                assert os.path.exists(os.path.join(root, "private", "private-repo"))
+                assert not os.path.exists(os.path.join(root, "private", "other-repo"))
+
+                # The repositories of the other tree with the same root are known
+                cmd = grm(args + ["--tree", f"{root}/private"])
+                assert cmd.returncode == 0
+                assert "unmanaged" not in cmd.stderr
+                assert os.path.exists(os.path.join(root, "private", "other-repo"))
+
+                cmd = grm(args + ["--tree", "shared"])
+                assert cmd.returncode == 0
+                assert "unmanaged" not in cmd.stderr
+
+                cmd = grm(args + ["--tree", "work", "--check"])
+                assert cmd.returncode == 0
+
+
+def test_repos_sync_shared_root():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    for team in ["a", "b"]:
+                        f.write(
+                            f"""
+                            [[trees]]
+                            root = "{root}"
+                            name = "team-{team}"
+
+                            [[trees.repos]]
+                            name = "{team}-repo"
+
+                            [[trees.repos.remotes]]
+                            name = "origin"
+                            url = "file://{remote}"
+                            type = "file"
+                            """
+                        )
+
+                for _ in range(2):
+                    cmd = grm(["repos", "sync", "config", "--config", config.name])
+                    assert cmd.returncode == 0
+                    assert "unmanaged" not in cmd.stderr.lower()
+                    assert sorted(os.listdir(root)) == [
+                        ".grm-state.toml",
+                        "a-repo",
+                        "b-repo",
+                    ]
+
+                with open(os.path.join(root, ".grm-state.toml")) as f:
+                    state = f.read()
+                assert "a-repo" in state
+                assert "b-repo" in state
+
+
+def test_repos_sync_shared_root_overlapping():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                for team in ["a", "b"]:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+                        name = "team-{team}"
+
+                        [[trees.repos]]
+                        name = "shared"
+                        """
+                    )
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode != 0
+            assert 'is part of both tree "team-a" and tree "team-b"' in cmd.stderr
+            assert os.listdir(root) == []
+
+
+def test_repos_sync_clone_retry():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.NamedTemporaryFile() as config:
+            with open(config.name, "w") as f:
+                f.write(
+                    f"""
+                    [retry]
+                    retries = 1
+                    backoff = 0
+
+                    [[trees]]
+                    root = "{root}"
+
+                    [[trees.repos]]
+                    name = "test"
+
+                    [[trees.repos.remotes]]
+                    name = "origin"
+                    url = "http://127.0.0.1:1/test"
+                    type = "https"
+                    """
+                )
+
+            cmd = grm(["repos", "sync", "config", "--config", config.name])
+            assert cmd.returncode != 0
+            assert "Cloning failed" in cmd.stderr
+            assert "Retrying in 0s (1/1)" in cmd.stderr
+            assert not os.path.exists(os.path.join(root, "test"))
+
+
+@pytest.mark.parametrize("default_branch", [None, "develop", "missing"])
+def test_repos_sync_worktree_default_branch(default_branch):
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            shell(f"git -C {remote} branch develop")
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+                        worktree_setup = true
+                        """
+                    )
+                    if default_branch is not None:
+                        f.write(f'default_branch = "{default_branch}"\n')
+                    f.write(
+                        f"""
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                # A default branch that does not exist falls back to guessing
+                expected = "develop" if default_branch == "develop" else "master"
+                worktrees = set(os.listdir(os.path.join(root, "test")))
+                assert worktrees == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    expected,
+                }
+
+
+def test_repos_sync_host_alias():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [hosts]
+                        "local:" = "file://{os.path.dirname(remote)}/"
+
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test"
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "local:{os.path.basename(remote)}"
+                        type = "file"
+                        """
+                    )
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                with git.Repo(os.path.join(root, "test")) as repo:
+                    assert repo.remotes.origin.url == f"file://{remote}"
+
+                # The expanded URL matches, so there is nothing to update
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "Updating remote" not in cmd.stdout
+
+
+def test_repos_sync_check():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                args = ["repos", "sync", "config", "--config", config.name]
+
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode != 0
+                assert "Repository does not exist" in cmd.stderr
+                assert os.listdir(root) == []
+
+                cmd = grm(args)
+                assert cmd.returncode == 0
+
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode == 0
+                assert "All repositories match the configuration" in cmd.stdout
+
+                repo_path = os.path.join(root, "test")
+                shell(f"git -C {repo_path} remote rename origin upstream")
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode != 0
+                assert 'Remote "origin" is missing' in cmd.stderr
+                with git.Repo(repo_path) as repo:
+                    assert [r.name for r in repo.remotes] == ["upstream"]
+
+                shell(f"git -C {repo_path} remote add origin file:///other")
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode != 0
+                assert f'Remote "origin" does not point to "file://{remote}"' in (
+                    cmd.stderr
+                )
+                shell(f"git -C {repo_path} remote remove origin")
+
+                shell(f"git -C {repo_path} remote set-url upstream file:///other")
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode != 0
+                assert "none of the configured remotes" in cmd.stderr
+
+
+def test_repos_sync_check_worktree():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote, worktree_setup=True)
+                args = ["repos", "sync", "config", "--config", config.name]
+
+                cmd = grm(args)
+                assert cmd.returncode == 0
+
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode == 0
+
+                shutil.rmtree(os.path.join(root, "test", "master"))
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode != 0
+                assert 'Worktree "master" does not exist anymore' in cmd.stderr
+
+                write_config(config.name, root, remote, worktree_setup=False)
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode != 0
+                assert "Repository is using a worktree setup" in cmd.stderr
+
+
+@pytest.mark.parametrize("worktree_setup", [True, False])
+def test_repos_sync_standalone(worktree_setup):
+    with tempfile.TemporaryDirectory() as parent:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                repo_path = os.path.join(parent, "dotfiles")
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        trees = []
+
+                        [[standalone]]
+                        path = "{repo_path}"
+                        worktree_setup = {str(worktree_setup).lower()}
+
+                        [[standalone.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+                        """
+                    )
+
+                # Other repositories next to it are none of its business
+                shell(f"git init --quiet {os.path.join(parent, 'other')}")
+
+                args = ["repos", "sync", "config", "--config", config.name]
+                cmd = grm(args)
+                assert cmd.returncode == 0
+                assert "unmanaged" not in cmd.stderr
+
+                git_dir = ".git-main-working-tree" if worktree_setup else ".git"
+                git_dir = os.path.join(repo_path, git_dir)
+                assert os.path.isfile(os.path.join(git_dir, ".grm-state.toml"))
+                assert not os.path.exists(os.path.join(parent, ".grm-state.toml"))
+
+                cmd = grm(args + ["--check"])
+                assert cmd.returncode == 0
+                assert "unmanaged" not in cmd.stderr
+
+                cmd = grm(["repos", "status", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "dotfiles" in cmd.stdout
+
+
+def test_repos_sync_standalone_relative():
+    with tempfile.NamedTemporaryFile() as config:
+        with open(config.name, "w") as f:
+            f.write('trees = []\n[[standalone]]\npath = "dotfiles"\n')
+        cmd = grm(["repos", "sync", "config", "--config", config.name])
+        assert cmd.returncode != 0
+        assert 'Path of standalone repository "dotfiles" must be absolute' in cmd.stderr
+
+
+def test_repos_sync_config_from_stdin():
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            config = f"""
+                [[trees]]
+                root = "{target}"
+
+                [[trees.repos]]
+                name = "test"
+
+                [[trees.repos.remotes]]
+                name = "origin"
+                url = "file://{remote}"
+                type = "file"
+                """
+            cmd = grm(["repos", "sync", "config", "--config", "-"], input=config)
+            assert cmd.returncode == 0
+            assert os.path.isdir(os.path.join(target, "test", ".git"))
+
+            # YAML works just as well
+            config = f"""
+                trees:
+                - root: "{target}"
+                  repos:
+                  - name: test
+                    remotes:
+                    - name: origin
+                      url: "file://{remote}"
+                      type: file
+                """
+            args = ["repos", "sync", "config", "--config", "-", "--check"]
+            cmd = grm(args, input=config)
+            assert cmd.returncode == 0
+
+
+def test_repos_sync_config_from_stdin_invalid():
+    cmd = grm(["repos", "sync", "config", "--config", "-"], input="invalid")
+    assert cmd.returncode != 0
+    assert "Error parsing configuration" in cmd.stderr
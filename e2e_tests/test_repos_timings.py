@@ -0,0 +1,76 @@
+#!/usr/bin/env python3
+
+import tempfile
+
+import pytest
+from helpers import TempGitFileRemote, grm, write_config
+
+
+def test_repos_timings():
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, target, remote)
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+                assert "Duration" not in cmd.stdout
+
+                for args, phase in [
+                    (["sync", "config"], "update"),
+                    (["fetch"], "fetch"),
+                    (["status"], "status"),
+                ]:
+                    cmd = grm(
+                        ["repos", *args, "--config", config.name, "--timings"]
+                    )
+                    assert cmd.returncode == 0
+
+                    lines = cmd.stdout.splitlines()
+                    assert any("Phase" in line and "Duration" in line for line in lines)
+                    assert any(
+                        line.split()[1:3] == ["total", "┆"] for line in lines
+                    ), cmd.stdout
+                    assert any(
+                        line.split()[1:5] == ["test", "┆", phase, "┆"]
+                        for line in lines
+                    ), cmd.stdout
+
+
+def test_repos_timings_clone():
+    with tempfile.TemporaryDirectory() as target:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, target, remote)
+
+                cmd = grm(
+                    ["repos", "sync", "config", "--config", config.name, "--timings"]
+                )
+                assert cmd.returncode == 0
+                assert any(
+                    line.split()[1:5] == ["test", "┆", "clone", "┆"]
+                    for line in cmd.stdout.splitlines()
+                ), cmd.stdout
+
+
+def test_repos_timings_requires_config():
+    cmd = grm(["repos", "status", "--timings"], is_invalid=True)
+    assert cmd.returncode != 0
+
+
+@pytest.mark.parametrize("provider", ["github", "gitlab"])
+def test_repos_timings_provider(provider):
+    with tempfile.NamedTemporaryFile() as config:
+        with open(config.name, "w") as f:
+            f.write(
+                f"""
+                provider = "{provider}"
+                token_command = "echo secret-token:myauthtoken"
+                root = "/myroot"
+                [filters]
+                owner = true
+                """
+            )
+
+        cmd = grm(["repos", "status", "--config", config.name, "--timings"])
+        assert "provider enumeration" in cmd.stdout
@@ -0,0 +1,99 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import tempfile
+
+from helpers import TempGitFileRemote, grm
+
+
+def write_config(config, root, remote):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+            name = "projects"
+
+            [[trees.repos]]
+            name = "plain"
+
+            [[trees.repos]]
+            name = "namespace/setup"
+            worktree_setup = true
+
+            [[trees.repos.remotes]]
+            name = "origin"
+            url = "file://{remote}"
+            type = "file"
+            """
+        )
+
+
+def which(config, path=None, cwd=None):
+    args = ["repos", "which", "--config", config, "--format", "json"]
+    if path is not None:
+        args.append(path)
+    cmd = grm(args, cwd=cwd)
+    assert cmd.returncode == 0
+    return json.loads(cmd.stdout)
+
+
+def test_repos_which():
+    with TempGitFileRemote() as (remote, _head_commit_sha):
+        with tempfile.TemporaryDirectory() as root:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                setup = os.path.join(root, "namespace", "setup")
+                cmd = grm(["wt", "add", "feature/test"], cwd=setup)
+                assert cmd.returncode == 0
+
+                subdir = os.path.join(root, "plain", "subdir")
+                os.mkdir(subdir)
+                location = which(config.name, subdir)
+                assert location == {
+                    "config": config.name,
+                    "tree": root,
+                    "tree_name": "projects",
+                    "repo": "plain",
+                    "namespace": None,
+                    "repo_path": os.path.join(root, "plain"),
+                    "worktree": None,
+                }
+
+                location = which(config.name, cwd=os.path.join(setup, "feature/test"))
+                assert location["repo"] == "namespace/setup"
+                assert location["namespace"] == "namespace"
+                assert location["repo_path"] == setup
+                assert location["worktree"] == "feature/test"
+
+                location = which(config.name, setup)
+                assert location["repo"] == "namespace/setup"
+                assert location["worktree"] is None
+
+                location = which(config.name, root)
+                assert location["tree"] == root
+                assert location["repo"] is None
+
+                cmd = grm(["repos", "which", "--config", config.name, subdir])
+                assert cmd.returncode == 0
+                assert "plain" in cmd.stdout
+                assert "projects" in cmd.stdout
+
+
+def test_repos_which_outside():
+    with tempfile.TemporaryDirectory() as root:
+        with tempfile.TemporaryDirectory() as other:
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, "/nonexistent")
+
+                cmd = grm(["repos", "which", "--config", config.name, other])
+                assert cmd.returncode == 1
+                assert "is not part of any configured tree" in cmd.stderr
+
+                cmd = grm(["repos", "which", "--config", config.name, "/nonexistent"])
+                assert cmd.returncode == 1
+                assert "Cannot access" in cmd.stderr
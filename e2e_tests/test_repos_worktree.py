@@ -0,0 +1,117 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+from helpers import TempGitFileRemote, grm
+
+
+def write_config(config, root, remote):
+    with open(config, "w") as f:
+        f.write(
+            f"""
+            [[trees]]
+            root = "{root}"
+            """
+        )
+        for name, worktree_setup, tag in [
+            ("api", True, "backend"),
+            ("worker", True, "backend"),
+            ("web", True, "frontend"),
+            ("plain", False, "backend"),
+        ]:
+            f.write(
+                f"""
+                [[trees.repos]]
+                name = "{name}"
+                worktree_setup = {"true" if worktree_setup else "false"}
+                tags = ["{tag}"]
+
+                [[trees.repos.remotes]]
+                name = "origin"
+                url = "file://{remote}"
+                type = "file"
+                """
+            )
+
+
+def test_repos_worktree():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                args = ["repos", "worktree", "add", "--config", config.name]
+                cmd = grm(args + ["bump-api", "--tag", "backend"])
+                assert cmd.returncode == 0
+                assert "api: Worktree bump-api created" in cmd.stdout
+                assert "worker: Worktree bump-api created" in cmd.stdout
+                assert "web" not in cmd.stdout
+                assert "plain" not in cmd.stdout
+
+                assert os.path.exists(os.path.join(root, "api", "bump-api"))
+                assert os.path.exists(os.path.join(root, "worker", "bump-api"))
+                assert not os.path.exists(os.path.join(root, "web", "bump-api"))
+                assert not os.path.exists(os.path.join(root, "plain", "bump-api"))
+
+                # Running it again does not hurt
+                cmd = grm(args + ["bump-api", "--tag", "backend"])
+                assert cmd.returncode == 0
+                assert "api: Worktree bump-api already exists" in cmd.stdout
+
+                # Without a tag, all repositories with a worktree setup are used
+                cmd = grm(args + ["everywhere"])
+                assert cmd.returncode == 0
+                for name in ["api", "worker", "web"]:
+                    assert os.path.exists(os.path.join(root, name, "everywhere"))
+
+                args = ["repos", "worktree", "delete", "--config", config.name]
+
+                # There is no remote branch, so the worktrees are kept
+                cmd = grm(args + ["bump-api", "--tag", "backend"])
+                assert cmd.returncode != 0
+                assert "api: No remote tracking branch" in cmd.stderr
+                assert os.path.exists(os.path.join(root, "api", "bump-api"))
+
+                cmd = grm(args + ["bump-api", "--tag", "backend", "--force"])
+                assert cmd.returncode == 0
+                assert "api: Worktree bump-api deleted" in cmd.stdout
+                assert "worker: Worktree bump-api deleted" in cmd.stdout
+                assert not os.path.exists(os.path.join(root, "api", "bump-api"))
+                assert not os.path.exists(os.path.join(root, "worker", "bump-api"))
+
+                cmd = grm(args + ["everywhere", "--ignore-upstream-divergence"])
+                assert cmd.returncode == 0
+                for name in ["api", "worker", "web"]:
+                    assert not os.path.exists(os.path.join(root, name, "everywhere"))
+
+
+def test_repos_worktree_no_match():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+
+                cmd = grm(
+                    ["repos", "worktree", "add", "--config", config.name]
+                    + ["test", "--tag", "unknown"]
+                )
+                assert cmd.returncode != 0
+                assert "No repository with a worktree setup matches" in cmd.stderr
+
+
+def test_repos_worktree_missing_repo():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                write_config(config.name, root, remote)
+
+                cmd = grm(
+                    ["repos", "worktree", "add", "--config", config.name]
+                    + ["test", "--tag", "frontend"]
+                )
+                assert cmd.returncode != 0
+                assert "web: Repository does not exist" in cmd.stderr
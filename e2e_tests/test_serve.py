@@ -0,0 +1,195 @@
+#!/usr/bin/env python3
+
+import json
+import os
+import socket
+import subprocess
+import tempfile
+import time
+
+from helpers import RepoTree, TempGitFileRemote, binary, grm, shell
+
+
+def wait_for_socket(socket_path):
+    for _ in range(100):
+        try:
+            with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as s:
+                s.connect(socket_path)
+            return
+        except (FileNotFoundError, ConnectionRefusedError):
+            time.sleep(0.05)
+    assert False, f"{socket_path} does not accept connections"
+
+
+class Server:
+    def __init__(self, config):
+        self.config = config
+
+    def __enter__(self):
+        self.tmpdir = tempfile.TemporaryDirectory()
+        self.socket_path = os.path.join(self.tmpdir.name, "grm.sock")
+        self.process = subprocess.Popen(
+            [binary, "serve", "--config", self.config, "--socket", self.socket_path],
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+            text=True,
+        )
+        wait_for_socket(self.socket_path)
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self.process.kill()
+        self.process.wait()
+        self.tmpdir.cleanup()
+
+    def call(self, method, params=None, id=1):
+        request = {"jsonrpc": "2.0", "id": id, "method": method}
+        if params is not None:
+            request["params"] = params
+        with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as s:
+            s.connect(self.socket_path)
+            s.sendall((json.dumps(request) + "\n").encode())
+            response = json.loads(s.makefile().readline())
+        print(f"request: {request}")
+        print(f"response: {response}")
+        assert response["jsonrpc"] == "2.0"
+        assert response["id"] == id
+        return response
+
+
+def test_serve_queries():
+    with RepoTree() as (root, config, repos):
+        with Server(config) as server:
+            response = server.call("repos.list")
+            assert os.stat(server.socket_path).st_mode & 0o777 == 0o600
+            # The private directory the socket was created in is gone
+            socket_dir = os.path.dirname(server.socket_path)
+            assert not [
+                entry
+                for entry in os.listdir(socket_dir)
+                if entry.startswith(".grm-serve-")
+            ]
+            assert [repo["name"] for repo in response["result"]] == repos
+            assert response["result"][0]["path"] == os.path.join(root, "test")
+            assert response["result"][1]["worktree_setup"] is True
+
+            shell(f"echo change > {root}/test/newfile")
+            response = server.call("repos.status", {"repo": "test"})
+            [status] = response["result"]
+            assert status["status"]["dirty"] is True
+            assert "Uncommitted changes" in status["status"]["check_failures"]
+
+            response = server.call("repos.status", {"repo": "missing"})
+            assert response["error"]["code"] == -32000
+            assert "not part of the configuration" in response["error"]["message"]
+
+            response = server.call("repos.status", {"unknown": "x"})
+            assert response["error"]["code"] == -32602
+
+            response = server.call("unknown.method")
+            assert response["error"]["code"] == -32601
+
+            # Several requests on the same connection
+            with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as s:
+                s.connect(server.socket_path)
+                f = s.makefile()
+                for id in [1, 2]:
+                    request = {"jsonrpc": "2.0", "id": id, "method": "repos.list"}
+                    s.sendall((json.dumps(request) + "\n").encode())
+                    assert json.loads(f.readline())["id"] == id
+
+
+def test_serve_worktrees():
+    with tempfile.TemporaryDirectory() as root:
+        with TempGitFileRemote() as (remote, _head_commit_sha):
+            with tempfile.NamedTemporaryFile() as config:
+                with open(config.name, "w") as f:
+                    f.write(
+                        f"""
+                        [[trees]]
+                        root = "{root}"
+
+                        [[trees.repos]]
+                        name = "test_worktree"
+                        worktree_setup = true
+
+                        [[trees.repos.remotes]]
+                        name = "origin"
+                        url = "file://{remote}"
+                        type = "file"
+
+                        [[trees.repos]]
+                        name = "test"
+                        """
+                    )
+                cmd = grm(["repos", "sync", "config", "--config", config.name])
+                assert cmd.returncode == 0
+
+                check_worktrees(root, config.name)
+
+
+def worktree_names(server):
+    response = server.call("worktrees.list", {"repo": "test_worktree"})
+    return {worktree["name"] for worktree in response["result"]}
+
+
+def check_worktrees(root, config):
+    with Server(config) as server:
+        # The default worktree is created by the sync
+        before = worktree_names(server)
+        assert "feature" not in before
+
+        response = server.call(
+            "worktrees.add", {"repo": "test_worktree", "name": "feature"}
+        )
+        assert response["result"]["name"] == "feature"
+        assert os.path.exists(os.path.join(root, "test_worktree", "feature"))
+
+        assert worktree_names(server) == before | {"feature"}
+
+        response = server.call(
+            "worktrees.add", {"repo": "test_worktree", "name": "feature"}
+        )
+        assert "already exists" in response["error"]["message"]
+
+        response = server.call("worktrees.add", {"repo": "test", "name": "x"})
+        assert "does not use a worktree setup" in response["error"]["message"]
+
+
+def test_serve_sync():
+    with RepoTree() as (root, config, _repos):
+        with Server(config) as server:
+            shell(f"rm -rf {root}/test")
+            response = server.call("repos.sync")
+            assert response["result"]["success"] is True
+            assert response["result"]["operation"] == "sync"
+            assert os.path.exists(os.path.join(root, "test", ".git"))
+
+
+def test_serve_socket_in_use():
+    with RepoTree() as (_root, config, _repos):
+        with Server(config) as server:
+            cmd = subprocess.run(
+                [binary, "serve", "--config", config, "--socket", server.socket_path],
+                capture_output=True,
+                text=True,
+            )
+            assert cmd.returncode != 0
+            assert "already listening" in cmd.stderr
+
+        # A socket left behind by a killed server is replaced
+        with tempfile.TemporaryDirectory() as tmpdir:
+            socket_path = os.path.join(tmpdir, "grm.sock")
+            with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as s:
+                s.bind(socket_path)
+            server = Server(config)
+            server.socket_path = socket_path
+            process = subprocess.Popen(
+                [binary, "serve", "--config", config, "--socket", socket_path]
+            )
+            try:
+                wait_for_socket(socket_path)
+                assert server.call("repos.list")["result"]
+            finally:
+                process.kill()
+                process.wait()
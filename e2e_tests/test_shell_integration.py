@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+import pytest
+from helpers import TempGitRepositoryWorktree, binary, funcname, grm
+
+
+def run_in_shell(script):
+    """
+    Runs the script in bash with the shell integration loaded. `grm` is put into
+    $PATH, just like for a real installation.
+    """
+    with tempfile.TemporaryDirectory() as bindir:
+        os.symlink(binary, os.path.join(bindir, "grm"))
+        env = dict(os.environ)
+        env["PATH"] = f"{bindir}:{env['PATH']}"
+        env.pop("GRM_CD_FILE", None)
+        cmd = subprocess.run(
+            ["bash", "-c", 'eval "$(grm shell-init bash)"\n' + script],
+            env=env,
+            capture_output=True,
+            text=True,
+        )
+        print(f"stdout:\n{cmd.stdout}")
+        print(f"stderr:\n{cmd.stderr}")
+        return cmd
+
+
+@pytest.mark.parametrize("shell", ["bash", "zsh", "fish"])
+def test_shell_init(shell):
+    cmd = grm(["shell-init", shell])
+    assert cmd.returncode == 0
+    assert "GRM_CD_FILE" in cmd.stdout
+    assert "command grm" in cmd.stdout
+
+
+def test_shell_init_invalid_shell():
+    cmd = grm(["shell-init", "powershell"], is_invalid=True)
+    assert cmd.returncode != 0
+
+
+def test_worktree_add_and_cd():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = run_in_shell(
+            f"""
+            cd {base_dir}
+            grm wt add test --and-cd
+            pwd
+            """
+        )
+        assert cmd.returncode == 0
+        assert cmd.stdout.strip().split("\n")[-1] == os.path.join(base_dir, "test")
+
+
+def test_worktree_add_without_and_cd():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = run_in_shell(
+            f"""
+            cd {base_dir}
+            grm wt add test
+            pwd
+            """
+        )
+        assert cmd.returncode == 0
+        assert cmd.stdout.strip().split("\n")[-1] == base_dir
+
+
+def test_worktree_add_and_cd_without_integration():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--and-cd"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "test" in os.listdir(base_dir)
+        assert "shell integration is not set up" in cmd.stderr.lower()
+
+
+def test_worktree_delete_from_worktree():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = run_in_shell(
+            f"""
+            cd {base_dir}/test
+            grm wt delete test
+            pwd
+            """
+        )
+        assert cmd.returncode == 0
+        assert cmd.stdout.strip().split("\n")[-1] == base_dir
+        assert "test" not in os.listdir(base_dir)
+
+
+def test_worktree_delete_from_worktree_without_integration():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "delete", "test"], cwd=os.path.join(base_dir, "test"))
+        assert cmd.returncode == 0
+        assert "test" not in os.listdir(base_dir)
+
+
+def test_failing_command_does_not_cd():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = run_in_shell(
+            f"""
+            cd {base_dir}
+            grm wt delete doesnotexist
+            echo "status: $?"
+            pwd
+            """
+        )
+        lines = cmd.stdout.strip().split("\n")
+        assert lines[-2] == "status: 1"
+        assert lines[-1] == base_dir
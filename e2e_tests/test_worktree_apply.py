@@ -0,0 +1,78 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+import tempfile
+
+from helpers import TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def make_wip(base_dir):
+    cmd = grm(["wt", "add", "wip"], cwd=base_dir)
+    assert cmd.returncode == 0
+    wip = os.path.join(base_dir, "wip")
+    with open(os.path.join(wip, "root-commit-in-worktree-1"), "w") as f:
+        f.write("changed\n")
+    return wip
+
+
+def test_worktree_add_apply_patch():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        wip = make_wip(base_dir)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            patch = os.path.join(tmpdir, "wip.patch")
+            shell(f"cd {wip} && git diff > {patch}")
+
+            cmd = grm(["wt", "add", "test", "--apply", patch], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "Applied the patch" in cmd.stdout
+
+        with open(os.path.join(base_dir, "test", "root-commit-in-worktree-1")) as f:
+            assert f.read() == "changed\n"
+
+
+def test_worktree_add_apply_stash():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        wip = make_wip(base_dir)
+        shell(f"cd {wip} && git stash")
+
+        cmd = grm(["wt", "add", "test", "--apply", "stash@{0}"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Applied the stash stash@{0}" in cmd.stdout
+
+        with open(os.path.join(base_dir, "test", "root-commit-in-worktree-1")) as f:
+            assert f.read() == "changed\n"
+
+        # The stash is kept
+        stashes = subprocess.run(
+            ["git", "stash", "list"], cwd=wip, capture_output=True, text=True
+        ).stdout
+        assert stashes.strip() != ""
+
+
+def test_worktree_add_apply_invalid():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--apply", "stash@{0}"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "neither a patch file nor a stash" in cmd.stderr
+        assert "test" not in os.listdir(base_dir)
+
+        cmd = grm(["wt", "add", "test", "--apply", "master"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "test" not in os.listdir(base_dir)
+
+
+def test_worktree_add_apply_conflict():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            patch = os.path.join(tmpdir, "broken.patch")
+            with open(patch, "w") as f:
+                f.write(
+                    "--- a/nonexistent\n+++ b/nonexistent\n@@ -1 +1 @@\n-old\n+new\n"
+                )
+
+            cmd = grm(["wt", "add", "test", "--apply", patch], cwd=base_dir)
+            assert cmd.returncode != 0
+            assert "Applying the patch" in cmd.stderr
+            # The worktree itself is kept
+            assert "test" in os.listdir(base_dir)
@@ -0,0 +1,89 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+
+from helpers import TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def push_branches(base_dir, remote, branches):
+    refspecs = " ".join(f"HEAD:refs/heads/{branch}" for branch in branches)
+    shell(
+        f"""
+        cd {base_dir}
+        git --git-dir .git-main-working-tree push --quiet {remote} {refspecs}
+        git --git-dir .git-main-working-tree fetch --quiet {remote}
+        """
+    )
+
+
+def listed_branches(base_dir):
+    cmd = grm(["wt", "branches"], cwd=base_dir)
+    assert cmd.returncode == 0
+    return [
+        [cell.strip() for cell in line.strip("│").split("┆")]
+        for line in cmd.stdout.splitlines()
+        if "┆" in line and "Branch" not in line
+    ]
+
+
+def upstream(worktree):
+    return subprocess.run(
+        ["git", "-C", worktree, "rev-parse", "--abbrev-ref", "@{upstream}"],
+        capture_output=True,
+        text=True,
+    ).stdout.strip()
+
+
+def test_worktree_branches():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        push_branches(
+            base_dir,
+            "origin",
+            ["branches-feature/login", "branches-feature/signup", "branches-fix"],
+        )
+        push_branches(base_dir, "otherremote", ["branches-fix"])
+
+        cmd = grm(["wt", "add", "branches-feature/signup"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "branches"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert cmd.stdout.index("origin") < cmd.stdout.index("otherremote")
+
+        rows = listed_branches(base_dir)
+        assert ["branches-feature", "branches-feature/login", "today"] in rows
+        assert ["", "branches-fix", "today"] in rows
+        branches = [branch for (_prefix, branch, _age) in rows]
+        assert "branches-feature/signup" not in branches
+        assert "HEAD" not in branches
+
+        # The branch is on two remotes, so the remote has to be given
+        cmd = grm(["wt", "branches", "--create", "branches-fix"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "origin, otherremote" in cmd.stderr
+        assert not os.path.exists(os.path.join(base_dir, "branches-fix"))
+
+        cmd = grm(
+            ["wt", "branches", "--create", "otherremote/branches-fix"], cwd=base_dir
+        )
+        assert cmd.returncode == 0
+        worktree = os.path.join(base_dir, "branches-fix")
+        assert upstream(worktree) == "otherremote/branches-fix"
+
+        cmd = grm(
+            ["wt", "branches", "--create", "branches-feature/login"], cwd=base_dir
+        )
+        assert cmd.returncode == 0
+        worktree = os.path.join(base_dir, "branches-feature", "login")
+        assert upstream(worktree) == "origin/branches-feature/login"
+
+        branches = [branch for (_prefix, branch, _age) in listed_branches(base_dir)]
+        assert not [branch for branch in branches if branch.startswith("branches-")]
+
+
+def test_worktree_branches_create_unknown():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "branches", "--create", "does-not-exist"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "No remote branch" in cmd.stderr
@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+
+import os
+
+from helpers import TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def test_worktree_browse():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(
+            ["wt", "add", "feature/login", "--track", "origin/login"], cwd=base_dir
+        )
+        assert cmd.returncode == 0
+        cmd = grm(["wt", "add", "local"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}
+            git --git-dir .git-main-working-tree remote set-url origin \\
+                git@gitlab.com:group/project.git
+            """
+        )
+
+        cmd = grm(["wt", "browse", "--print"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert cmd.stdout == "https://gitlab.com/group/project\n"
+
+        # The branch on the remote, not the local one
+        cmd = grm(["wt", "browse", "feature/login", "--print"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert cmd.stdout == "https://gitlab.com/group/project/-/tree/login\n"
+
+        cmd = grm(["wt", "browse", "local", "--print"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert cmd.stdout == "https://gitlab.com/group/project/-/tree/local\n"
+
+
+def test_worktree_browse_configured():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write(
+                """
+                [[web_urls]]
+                host = "git.example.com"
+                url = "https://git.example.com/ui/{path}"
+                branch_url = "https://git.example.com/ui/{path}/branch/{branch}"
+                """
+            )
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}
+            git --git-dir .git-main-working-tree remote set-url origin \\
+                https://git.example.com/team/project.git
+            """
+        )
+
+        cmd = grm(["wt", "browse", "test", "--print"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert cmd.stdout == "https://git.example.com/ui/team/project/branch/test\n"
+
+
+def test_worktree_browse_invalid():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "browse", "doesnotexist", "--print"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "does not exist" in cmd.stderr
+
+        # The remotes still point to the local filesystem
+        cmd = grm(["wt", "browse", "--print"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "does not point to a forge" in cmd.stderr
@@ -2,9 +2,11 @@
 
 import os
 
+import git
 import pytest
 from helpers import (
     NonGitDir,
+    TempGitFileRemote,
     TempGitRepository,
     TempGitRepositoryWorktree,
     checksum_directory,
@@ -25,6 +27,82 @@ def test_worktree_clean():
         assert "test" not in os.listdir(base_dir)
 
 
+def test_worktree_clean_archive():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('[cleanup]\narchive = "refs/archive/{branch}"\n')
+
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "clean"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Worktree test deleted, archived to refs/archive/test" in cmd.stdout
+
+        repo = git.Repo(os.path.join(base_dir, ".git-main-working-tree"))
+        assert repo.commit("refs/archive/test").hexsha == repo.commit(
+            "origin/test"
+        ).hexsha
+
+
+def test_worktree_clean_skip():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('skip = ["spike-*"]')
+
+        for name in ["test", "spike-test"]:
+            cmd = grm(["wt", "add", name, "--track", f"origin/{name}"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+        cmd = grm(["wt", "clean"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "test" not in os.listdir(base_dir)
+        assert "spike-test" in os.listdir(base_dir)
+
+        cmd = grm(["wt", "clean", "--suggest"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "spike-test" not in cmd.stdout
+
+
+def test_worktree_clean_suggest():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        cmd = grm(["wt", "add", "changed", "--track", "origin/changed"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(f"cd {base_dir}/changed && touch changed_file")
+
+        before = checksum_directory(base_dir)
+        cmd = grm(["wt", "clean", "--suggest"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Disk usage" in cmd.stdout
+        # Removable worktrees are listed first
+        assert cmd.stdout.index("test") < cmd.stdout.index("changed")
+        assert "Changes found in worktree" in cmd.stdout
+
+        # Nothing is removed
+        after = checksum_directory(base_dir)
+        assert before == after
+
+
+def test_worktree_clean_suggest_no_tracking_branch():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "clean", "--suggest"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "No remote tracking branch" in cmd.stdout
+        assert "test" in os.listdir(base_dir)
+
+
+def test_worktree_clean_suggest_nothing():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "clean", "--suggest"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "No worktrees to clean up" in cmd.stdout
+
+
 def test_worktree_clean_refusal_no_tracking_branch():
     with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
         cmd = grm(["wt", "add", "test"], cwd=base_dir)
@@ -125,6 +203,62 @@ def test_worktree_clean_refusal_tracking_branch_mismatch():
         assert before == after
 
 
+def test_worktree_clean_refusal_submodule():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with TempGitFileRemote() as (submodule_remote, _head_commit_sha):
+            cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+            shell(
+                f"""
+                cd {base_dir}/test
+                git -c protocol.file.allow=always submodule add \\
+                    file://{submodule_remote} sub
+                git commit -m "add submodule"
+                git push origin test
+                cd sub
+                echo change > newfile
+                """
+            )
+
+            cmd = grm(["wt", "clean", "--suggest"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "Submodule sub has uncommitted changes" in cmd.stdout
+
+            cmd = grm(["wt", "clean"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "test" in os.listdir(base_dir)
+            assert "Submodule sub has uncommitted changes" in cmd.stderr
+
+            cmd = grm(["wt", "delete", "test"], cwd=base_dir)
+            assert cmd.returncode != 0
+            assert "test" in os.listdir(base_dir)
+            assert "Submodules would lose work" in cmd.stderr
+
+            # Committed in the submodule, but never pushed
+            shell(
+                f"""
+                cd {base_dir}/test/sub
+                git add newfile
+                git commit -m "not pushed"
+                cd ..
+                git commit -am "bump submodule"
+                git push origin test
+                """
+            )
+
+            cmd = grm(["wt", "clean"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "test" in os.listdir(base_dir)
+            assert "Submodule sub points to commit" in cmd.stderr
+
+            shell(f"cd {base_dir}/test/sub && git push origin HEAD:master")
+
+            cmd = grm(["wt", "clean"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "test" not in os.listdir(base_dir)
+
+
 def test_worktree_clean_fail_from_subdir():
     with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
         cmd = grm(["wt", "add", "test"], cwd=base_dir)
@@ -142,3 +142,124 @@ def test_worktree_delete_branch_merged_into_persistent():
         assert cmd.returncode == 0
 
         assert "test" not in os.listdir(base_dir)
+
+
+def test_worktree_clean_delete_remote():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write(
+                """
+            persistent_branches = [
+                "master",
+            ]
+
+            [cleanup]
+            delete_remote = true
+            """
+            )
+
+        for branch in ["test", "unmerged"]:
+            cmd = grm(
+                ["wt", "add", branch, "--track", f"origin/{branch}"], cwd=base_dir
+            )
+            assert cmd.returncode == 0
+            shell(
+                f"""
+                cd {base_dir}/{branch}
+                touch {branch}
+                git add {branch}
+                git commit -m "{branch}"
+                git push origin {branch}
+                """
+            )
+
+        cmd = grm(["wt", "add", "master"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        shell(
+            f"""
+            cd {base_dir}/master
+            git merge --no-ff test
+            """
+        )
+
+        cmd = grm(["wt", "clean"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "test" not in os.listdir(base_dir)
+        assert "unmerged" in os.listdir(base_dir)
+        assert "Remote branch origin/test deleted" in cmd.stdout
+
+        repo = git.Repo(os.path.join(base_dir, ".git-main-working-tree"))
+        remote = git.Repo(repo.remotes.origin.url.removeprefix("file://"))
+        heads = [head.name for head in remote.heads]
+        assert "test" not in heads
+        assert "unmerged" in heads
+        # The persistent branch is never deleted, even though it is merged
+        assert "master" in heads
+        assert "origin/test" not in [ref.name for ref in repo.remotes.origin.refs]
+
+
+def test_worktree_clean_delete_remote_updated_since_fetch():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write(
+                """
+            persistent_branches = [
+                "master",
+            ]
+
+            [cleanup]
+            delete_remote = true
+            """
+            )
+
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/test
+            touch test
+            git add test
+            git commit -m "test"
+            git push origin test
+            """
+        )
+
+        cmd = grm(["wt", "add", "master"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        shell(
+            f"""
+            cd {base_dir}/master
+            git merge --no-ff test
+            """
+        )
+
+        repo = git.Repo(os.path.join(base_dir, ".git-main-working-tree"))
+        remote_path = repo.remotes.origin.url.removeprefix("file://")
+        tip = repo.remotes.origin.refs.test.commit.hexsha
+
+        # Someone else pushes to the branch, the local repository does not know
+        shell(
+            f"""
+            cd {base_dir}
+            git clone --branch test {remote_path} other
+            cd other
+            touch other
+            git add other
+            git commit -m "other"
+            git push origin test
+            cd {base_dir}
+            rm -rf other
+            """
+        )
+
+        cmd = grm(["wt", "clean"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "test" not in os.listdir(base_dir)
+        assert "Remote branch origin/test deleted" not in cmd.stdout
+        assert "updated on the remote since the last fetch" in cmd.stderr
+
+        remote = git.Repo(remote_path)
+        assert "test" in [head.name for head in remote.heads]
+        assert remote.commit("test").hexsha != tip
@@ -19,15 +19,15 @@ def test_convert():
         assert cmd.returncode == 0
 
         files = os.listdir(git_dir)
-        assert len(files) == 1
-        assert files[0] == ".git-main-working-tree"
+        assert len(files) == 2
+        assert set(files) == {".git-main-working-tree", ".grm-state.json"}
 
         cmd = grm(["wt", "add", "test"], cwd=git_dir)
         assert cmd.returncode == 0
 
         files = os.listdir(git_dir)
-        assert len(files) == 2
-        assert set(files) == {".git-main-working-tree", "test"}
+        assert len(files) == 3
+        assert set(files) == {".git-main-working-tree", ".grm-state.json", "test"}
 
 
 def test_convert_already_worktree():
@@ -57,6 +57,91 @@ def test_worktree_fetch():
             assert repo.commit("upstream/master").hexsha == remote_commit
 
 
+@pytest.mark.parametrize("restrict_via", ["config", "argument"])
+def test_worktree_fetch_only_configured(restrict_via):
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        with TempGitFileRemote() as (remote_path, _remote_sha):
+            shell(
+                f"""
+                cd {base_dir}
+                git --git-dir .git-main-working-tree remote add upstream file://{remote_path}
+            """
+            )
+            args = ["wt", "fetch"]
+            if restrict_via == "config":
+                with open(f"{base_dir}/grm.toml", "w") as f:
+                    f.write(
+                        """
+                        [track]
+                        default = true
+                        default_remote = "origin"
+
+                        [fetch]
+                        only_configured = true
+                        """
+                    )
+            else:
+                args += ["--remote", "origin"]
+
+            cmd = grm(args, cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "Fetched from origin" in cmd.stdout
+
+            repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+            assert len(repo.remotes.upstream.refs) == 0
+
+
+def test_worktree_fetch_only_configured_without_tracking():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        with open(f"{base_dir}/grm.toml", "w") as f:
+            f.write(
+                """
+                [fetch]
+                only_configured = true
+                """
+            )
+
+        cmd = grm(["wt", "fetch"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "track.default_remote" in cmd.stderr
+
+
+
+def test_worktree_fetch_prune():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        with TempGitFileRemote() as (remote_path, _remote_sha):
+            shell(
+                f"""
+                cd {base_dir}
+                git --git-dir .git-main-working-tree remote add upstream file://{remote_path}
+                git -C {remote_path} branch feature master
+            """
+            )
+            with open(f"{base_dir}/grm.toml", "w") as f:
+                f.write(
+                    """
+                    [fetch]
+                    prune = true
+                    """
+                )
+
+            cmd = grm(["wt", "fetch"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+            repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+            refs = [ref.name for ref in repo.remotes.upstream.refs]
+            assert "upstream/feature" in refs
+
+            shell(f"git -C {remote_path} branch -D feature")
+
+            cmd = grm(["wt", "fetch"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+            refs = [ref.name for ref in repo.remotes.upstream.refs]
+            assert "upstream/master" in refs
+            assert "upstream/feature" not in refs
+
+
 @pytest.mark.parametrize("rebase", [True, False])
 @pytest.mark.parametrize("ffable", [True, False])
 @pytest.mark.parametrize("has_changes", [True, False])
@@ -0,0 +1,98 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+
+from helpers import TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def stashes(path):
+    return subprocess.run(
+        ["git", "stash", "list"], cwd=path, capture_output=True, text=True
+    ).stdout.strip()
+
+
+def setup_worktrees(base_dir):
+    for name in ["main", "feature"]:
+        cmd = grm(["wt", "add", name], cwd=base_dir)
+        assert cmd.returncode == 0
+    main = os.path.join(base_dir, "main")
+    with open(os.path.join(main, "root-commit-in-worktree-1"), "w") as f:
+        f.write("changed\n")
+    with open(os.path.join(main, "newfile"), "w") as f:
+        f.write("new\n")
+    return main, os.path.join(base_dir, "feature")
+
+
+def test_worktree_move_changes():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        main, feature = setup_worktrees(base_dir)
+
+        cmd = grm(["wt", "move-changes", "main", "feature"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Moved changes from main to feature" in cmd.stdout
+
+        with open(os.path.join(feature, "root-commit-in-worktree-1")) as f:
+            assert f.read() == "changed\n"
+        assert os.path.exists(os.path.join(feature, "newfile"))
+
+        with open(os.path.join(main, "root-commit-in-worktree-1")) as f:
+            assert f.read() == "test\n"
+        assert not os.path.exists(os.path.join(main, "newfile"))
+        assert stashes(main) == ""
+
+
+def test_worktree_move_changes_conflict():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        main, feature = setup_worktrees(base_dir)
+        shell(
+            f"""
+            cd {feature}
+            echo other > root-commit-in-worktree-1
+            git commit --quiet -am "conflicting change"
+            """
+        )
+
+        cmd = grm(["wt", "move-changes", "main", "feature"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "with conflicts" in cmd.stderr
+
+        with open(os.path.join(feature, "root-commit-in-worktree-1")) as f:
+            assert "<<<<<<<" in f.read()
+        assert "grm: moving changes to feature" in stashes(main)
+
+
+def test_worktree_move_changes_restore():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        main, feature = setup_worktrees(base_dir)
+        # The untracked file in the target blocks applying the stash
+        with open(os.path.join(feature, "newfile"), "w") as f:
+            f.write("other\n")
+
+        cmd = grm(["wt", "move-changes", "main", "feature"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "restored in worktree main" in cmd.stderr
+
+        with open(os.path.join(main, "root-commit-in-worktree-1")) as f:
+            assert f.read() == "changed\n"
+        assert os.path.exists(os.path.join(main, "newfile"))
+        assert stashes(main) == ""
+
+
+def test_worktree_move_changes_invalid():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        for name in ["main", "feature"]:
+            cmd = grm(["wt", "add", name], cwd=base_dir)
+            assert cmd.returncode == 0
+
+        cmd = grm(["wt", "move-changes", "main", "feature"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "no changes to move" in cmd.stderr
+
+        cmd = grm(["wt", "move-changes", "main", "main"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "are the same" in cmd.stderr
+
+        cmd = grm(["wt", "move-changes", "main", "nonexistent"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "does not exist" in cmd.stderr
@@ -0,0 +1,102 @@
+#!/usr/bin/env python3
+
+import os
+import tempfile
+
+from helpers import TempGitRepositoryWorktree, funcname, grm
+
+
+def write_open_command(base_dir, command):
+    with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+        f.write(
+            f"""
+            open_command = "{command}"
+            """
+        )
+
+
+def test_worktree_open():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            output = os.path.join(tmpdir, "output")
+            write_open_command(base_dir, f"echo {{path}} > {output}")
+
+            cmd = grm(["wt", "add", "test"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert not os.path.exists(output)
+
+            cmd = grm(["wt", "open", "test"], cwd=base_dir)
+            assert cmd.returncode == 0
+            with open(output) as f:
+                assert f.read().strip() == os.path.join(base_dir, "test")
+
+
+def test_worktree_add_open():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            output = os.path.join(tmpdir, "output")
+            # Without {path}, the path is appended
+            write_open_command(base_dir, f"touch {output} && echo")
+
+            cmd = grm(["wt", "add", "test", "--open"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "test" in os.listdir(base_dir)
+            assert os.path.exists(output)
+            assert os.path.join(base_dir, "test") in cmd.stdout
+
+
+def test_worktree_open_editor_fallback():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            output = os.path.join(tmpdir, "output")
+            cmd = grm(["wt", "add", "test"], cwd=base_dir)
+            assert cmd.returncode == 0
+
+            old_environ = dict(os.environ)
+            try:
+                os.environ.pop("VISUAL", None)
+                os.environ["EDITOR"] = f"echo > {output}"
+                cmd = grm(["wt", "open", "test"], cwd=base_dir)
+            finally:
+                os.environ.clear()
+                os.environ.update(old_environ)
+
+            assert cmd.returncode == 0
+            assert os.path.exists(output)
+
+
+def test_worktree_open_no_command():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        old_environ = dict(os.environ)
+        try:
+            os.environ.pop("VISUAL", None)
+            os.environ.pop("EDITOR", None)
+            cmd = grm(["wt", "open", "test"], cwd=base_dir)
+        finally:
+            os.environ.clear()
+            os.environ.update(old_environ)
+
+        assert cmd.returncode != 0
+        assert "open_command" in cmd.stderr
+
+
+def test_worktree_open_nonexistent():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        write_open_command(base_dir, "true")
+
+        cmd = grm(["wt", "open", "test"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "does not exist" in cmd.stderr
+
+
+def test_worktree_open_command_fails():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        write_open_command(base_dir, "exit 3")
+
+        cmd = grm(["wt", "add", "test", "--open"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "test" in os.listdir(base_dir)
+        assert "exit code 3" in cmd.stderr
@@ -0,0 +1,92 @@
+#!/usr/bin/env python3
+
+import os
+import subprocess
+
+import git
+from helpers import TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def upstream(base_dir, branch):
+    return subprocess.run(
+        ["git", "-C", os.path.join(base_dir, branch)]
+        + ["rev-parse", "--abbrev-ref", f"{branch}@{{upstream}}"],
+        capture_output=True,
+        text=True,
+    ).stdout.strip()
+
+
+def test_worktree_profile():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "feature", "--track", "origin/feature"], cwd=base_dir)
+        assert cmd.returncode == 0
+        cmd = grm(["wt", "add", "local", "--no-track"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/feature
+            git push --quiet origin feature
+            cd {base_dir}/local
+            echo change > mychange
+            git add mychange
+            git commit -m "commit-in-local"
+        """
+        )
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        local_commit = repo.commit("local").hexsha
+
+        cmd = grm(["wt", "save-profile", "work"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Profile work saved" in cmd.stdout
+        assert os.path.exists(os.path.join(base_dir, "grm-profiles.toml"))
+
+        # Start over. This removes the branches as well
+        for name in ["feature", "local"]:
+            cmd = grm(["wt", "delete", "--force", name], cwd=base_dir)
+            assert cmd.returncode == 0
+
+        cmd = grm(["wt", "restore-profile", "work"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Worktree feature created" in cmd.stdout
+        assert "Worktree local created" in cmd.stdout
+        assert set(os.listdir(base_dir)) >= {"feature", "local"}
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        assert repo.commit("local").hexsha == local_commit
+        assert upstream(base_dir, "feature") == "origin/feature"
+        assert upstream(base_dir, "local") == ""
+
+        # Existing worktrees are left alone
+        cmd = grm(["wt", "restore-profile", "work"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "feature: Worktree already exists" in cmd.stdout
+        assert "created" not in cmd.stdout
+
+
+def test_worktree_profile_update():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "save-profile", "work"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Profile work saved" in cmd.stdout
+
+        cmd = grm(["wt", "save-profile", "work"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Profile work updated" in cmd.stdout
+
+        with open(os.path.join(base_dir, "grm-profiles.toml")) as f:
+            assert f.read().count('name = "work"') == 1
+
+        # The profiles file is not mistaken for a worktree
+        cmd = grm(["wt", "clean"], cwd=base_dir)
+        assert "grm-profiles.toml" not in cmd.stderr
+
+
+def test_worktree_profile_not_found():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "restore-profile", "work"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert 'Profile "work" not found' in cmd.stderr
@@ -268,3 +268,129 @@ def test_worktree_rebase(pull, rebase, ffable, has_changes, stash):
                         repo.commit("HEAD~3").message.strip() == "commit-in-base-local"
                     )
                     assert repo.commit("HEAD~4").message.strip() == "commit-root"
+
+
+@pytest.mark.parametrize("answer", ["y", "n", "", "--yes"])
+def test_worktree_rebase_interactive_list(answer):
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('persistent_branches = ["mybasebranch"]')
+
+        cmd = grm(["wt", "add", "mybasebranch"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/mybasebranch
+            echo change > mychange-base
+            git add mychange-base
+            git commit -m "commit-in-base"
+        """
+        )
+
+        cmd = grm(["wt", "add", "myfeatbranch"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/myfeatbranch
+            git reset --hard mybasebranch^
+            echo change > mychange-feat
+            git add mychange-feat
+            git commit -m "commit-in-feat"
+        """
+        )
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        base_commit = repo.commit("mybasebranch").hexsha
+        old_base_commit = repo.commit("myfeatbranch~1").hexsha
+
+        args = ["wt", "rebase", "--interactive-list"]
+        if answer == "--yes":
+            args.append("--yes")
+            cmd = grm(args, cwd=base_dir)
+        else:
+            cmd = grm(args, cwd=base_dir, input=answer)
+
+        plan = [line for line in cmd.stdout.splitlines() if "┆" in line]
+        columns = [
+            [column.strip(" │") for column in line.split("┆")] for line in plan
+        ]
+        feat = [row for row in columns if row[0] == "myfeatbranch"]
+        assert len(feat) == 1
+        assert feat[0][1:6] == [
+            "mybasebranch",
+            old_base_commit[:7],
+            base_commit[:7],
+            "1",
+            "1",
+        ]
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        if answer in ["y", "--yes"]:
+            assert cmd.returncode == 0
+            assert repo.commit("myfeatbranch~1").hexsha == base_commit
+        else:
+            assert cmd.returncode != 0
+            assert "nothing was rebased" in cmd.stderr
+            assert repo.commit("myfeatbranch~1").hexsha == old_base_commit
+
+
+def test_worktree_rebase_yes_requires_interactive_list():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        cmd = grm(["wt", "rebase", "--yes"], cwd=base_dir, is_invalid=True)
+        assert cmd.returncode != 0
+
+
+@pytest.mark.parametrize("action", ["pull", "rebase"])
+def test_worktree_rebase_skip(action):
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write(
+                """
+                persistent_branches = ["mybasebranch"]
+                skip = ["wip/*", "spike-*"]
+                """
+            )
+
+        cmd = grm(["wt", "add", "mybasebranch"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/mybasebranch
+            echo change > mychange-base
+            git add mychange-base
+            git commit -m "commit-in-base"
+        """
+        )
+
+        for name in ["spike-myfeatbranch", "myfeatbranch"]:
+            cmd = grm(["wt", "add", name], cwd=base_dir)
+            assert cmd.returncode == 0
+            shell(
+                f"""
+                cd {base_dir}/{name}
+                git reset --hard mybasebranch^
+                echo change > mychange-feat
+                git add mychange-feat
+                git commit -m "commit-in-feat"
+            """
+            )
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        skipped_before = repo.commit("spike-myfeatbranch").hexsha
+
+        cmd = grm(["wt", action], cwd=base_dir)
+        assert 'spike-myfeatbranch: Skipped, matches "spike-*"' in cmd.stdout
+        assert "spike-myfeatbranch:" not in cmd.stderr
+        # There are no remote branches to pull from
+        if action == "rebase":
+            assert cmd.returncode == 0
+            assert "spike-myfeatbranch: Done" not in cmd.stdout
+            assert "myfeatbranch: Done" in cmd.stdout
+        else:
+            assert "myfeatbranch: No remote branch" in cmd.stderr
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        assert repo.commit("spike-myfeatbranch").hexsha == skipped_before
+        if action == "rebase":
+            base_commit = repo.commit("mybasebranch").hexsha
+            assert repo.commit("myfeatbranch~1").hexsha == base_commit
@@ -0,0 +1,72 @@
+#!/usr/bin/env python3
+
+import os
+import shutil
+import tempfile
+
+import git
+from helpers import TempGitRepositoryWorktree, funcname, grm
+
+
+def test_worktree_repair_after_move():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        for name in ["test", "fix/b"]:
+            cmd = grm(["wt", "add", name], cwd=base_dir)
+            assert cmd.returncode == 0
+
+        with tempfile.TemporaryDirectory() as target:
+            moved = os.path.join(target, "moved")
+            shutil.move(base_dir, moved)
+            try:
+                cmd = grm(["wt", "status"], cwd=moved)
+                assert "grm wt repair" in cmd.stderr
+
+                cmd = grm(["wt", "repair"], cwd=moved)
+                assert cmd.returncode == 0
+                assert "Worktree test repaired" in cmd.stdout
+                assert "Worktree fix/b repaired" in cmd.stdout
+
+                for name in ["test", "fix/b"]:
+                    with git.Repo(os.path.join(moved, name)) as repo:
+                        assert str(repo.active_branch) == name
+                        assert not repo.is_dirty()
+
+                cmd = grm(["wt", "status"], cwd=moved)
+                assert cmd.returncode == 0
+                assert "does not exist" not in cmd.stderr
+
+                cmd = grm(["wt", "repair"], cwd=moved)
+                assert cmd.returncode == 0
+                assert "All worktrees are linked correctly" in cmd.stdout
+                assert "repaired" not in cmd.stdout
+            finally:
+                shutil.move(moved, base_dir)
+
+
+def test_worktree_repair_nothing_to_do():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "repair"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "All worktrees are linked correctly" in cmd.stdout
+        assert len(cmd.stderr) == 0
+
+
+def test_worktree_repair_missing_worktree():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shutil.rmtree(os.path.join(base_dir, "test"))
+
+        cmd = grm(["wt", "repair"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert 'No worktree found for "test"' in cmd.stderr
+
+
+def test_worktree_repair_no_worktree_setup():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        cmd = grm(["wt", "repair"], cwd=tmpdir)
+        assert cmd.returncode != 0
+        assert "does not contain a worktree setup" in cmd.stderr
@@ -0,0 +1,70 @@
+#!/usr/bin/env python3
+
+import os
+
+import git
+from helpers import TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def upstream(base_dir, name):
+    repo = git.Repo(os.path.join(base_dir, name))
+    return repo.active_branch.tracking_branch().name
+
+
+def test_worktree_retrack():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        # The branch was renamed on the remote
+        shell(
+            f"""
+            cd {base_dir}/test
+            git push origin test:renamed
+            git push origin :test
+            git fetch --prune origin
+            """
+        )
+
+        cmd = grm(["wt", "retrack", "test", "origin/renamed"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+        assert upstream(base_dir, "test") == "origin/renamed"
+
+        cmd = grm(["wt", "retrack", "test", "otherremote/renamed"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "does not exist" in cmd.stderr
+        assert upstream(base_dir, "test") == "origin/renamed"
+
+
+def test_worktree_retrack_push():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "retrack", "test", "origin/new"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "--push" in cmd.stderr
+        assert upstream(base_dir, "test") == "origin/test"
+
+        cmd = grm(["wt", "retrack", "test", "origin/new", "--push"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert upstream(base_dir, "test") == "origin/new"
+
+
+def test_worktree_retrack_invalid():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "retrack", "missing", "origin/test"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "does not exist" in cmd.stderr
+
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "retrack", "test", "origin"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "<remote>/<branch_name>" in cmd.stderr
+
+        cmd = grm(["wt", "retrack", "test", "missing/test"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "not found" in cmd.stderr
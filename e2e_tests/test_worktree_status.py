@@ -1,5 +1,6 @@
 #!/usr/bin/env python3
 
+import json
 import os
 import re
 
@@ -30,6 +31,42 @@ def test_worktree_status(has_config):
         assert "test" in stdout
 
 
+def test_worktree_status_disk_usage():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Disk usage" not in cmd.stdout
+
+        cmd = grm(["wt", "status", "--disk-usage"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+        assert "Disk usage" in cmd.stdout
+
+
+def test_worktree_status_json():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(f"cd {base_dir}/test && echo test > new")
+
+        cmd = grm(["wt", "status", "--json"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+        [worktree] = json.loads(cmd.stdout)
+        assert worktree["name"] == "test"
+        assert "disk_usage" not in worktree
+        assert worktree["status"]["head"] == "test"
+        assert worktree["status"]["changes"]["files_new"] == 1
+
+        cmd = grm(["wt", "status", "--json", "--disk-usage"], cwd=base_dir)
+        assert cmd.returncode == 0
+        [worktree] = json.loads(cmd.stdout)
+        assert worktree["disk_usage"] > 0
+
+
 def test_worktree_status_fail_from_subdir():
     with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
         cmd = grm(["wt", "add", "test"], cwd=base_dir)
@@ -82,3 +119,82 @@ def test_worktree_status_warn_with_non_worktree_dir():
             )
             is not None
         )
+
+
+def test_worktree_status_gone_upstream():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(f"cd {base_dir}/test && git push origin test")
+
+        cmd = grm(["wt", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "origin/test" in cmd.stdout
+        assert "[gone]" not in cmd.stdout
+
+        shell(
+            f"""
+            cd {base_dir}/test
+            git push origin --delete test
+            git fetch --prune origin
+            """
+        )
+
+        cmd = grm(["wt", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+        assert "origin/test [gone]" in cmd.stdout
+
+
+def test_worktree_status_detached_head():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(f"cd {base_dir}/test && git checkout --quiet --detach")
+
+        cmd = grm(["wt", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+        assert re.search(r"Detached at [0-9a-f]+", cmd.stdout)
+
+        cmd = grm(["wt", "pull"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "HEAD is detached at" in cmd.stdout + cmd.stderr
+        assert "git switch" in cmd.stdout + cmd.stderr
+
+        cmd = grm(["wt", "delete", "test"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "HEAD is detached at" in cmd.stdout + cmd.stderr
+        assert os.path.exists(f"{base_dir}/test")
+
+
+def test_worktree_state_file():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        state_file = os.path.join(base_dir, ".grm-state.json")
+
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(f"cd {base_dir}/test && git push --quiet origin test")
+        cmd = grm(["wt", "add", "other", "--no-track"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        with open(state_file) as f:
+            state = json.load(f)
+        assert state["updated"] > 0
+        worktrees = {worktree["name"]: worktree for worktree in state["worktrees"]}
+        assert set(worktrees) == {"test", "other"}
+        assert worktrees["test"]["branch"] == "test"
+        assert worktrees["test"]["upstream"] == "origin/test"
+        assert worktrees["test"]["commit_time"] > 0
+        assert worktrees["other"]["upstream"] is None
+
+        # The file is not mistaken for a worktree
+        cmd = grm(["wt", "status"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert len(cmd.stderr) == 0
+
+        cmd = grm(["wt", "delete", "other", "--force"], cwd=base_dir)
+        assert cmd.returncode == 0
+        with open(state_file) as f:
+            state = json.load(f)
+        assert [worktree["name"] for worktree in state["worktrees"]] == ["test"]
@@ -0,0 +1,95 @@
+#!/usr/bin/env python3
+
+import os
+
+import git
+import pytest
+from helpers import EmptyDir, TempGitRepositoryWorktree, funcname, grm, shell
+
+
+def setup_worktrees(base_dir):
+    for name in ["master", "myfeatbranch", "mergedbranch"]:
+        cmd = grm(["wt", "add", name, "--track", f"origin/{name}"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+    shell(
+        f"""
+        cd {base_dir}/myfeatbranch
+        echo change > mychange-feat
+        git add mychange-feat
+        git commit -m "commit-in-feat"
+        git push --force origin HEAD:myfeatbranch
+    """
+    )
+
+    # Push from a separate clone, so only fetching picks up the change
+    repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+    with EmptyDir() as tmp:
+        shell(
+            f"""
+            cd {tmp}
+            git clone --quiet {repo.remote("origin").url} tmp
+            cd tmp
+            git reset --hard {repo.commit("master").hexsha}
+            echo change > mychange-master-remote
+            git add mychange-master-remote
+            git commit -m "commit-in-master-remote"
+            git push --force origin HEAD:master
+        """
+        )
+
+
+@pytest.mark.parametrize("disabled", [None, "fetch", "pull", "rebase", "clean"])
+def test_worktree_sync(disabled):
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, root_commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('persistent_branches = ["master"]\n')
+            if disabled is not None:
+                f.write(f"[sync]\n{disabled} = false\n")
+
+        setup_worktrees(base_dir)
+
+        cmd = grm(["wt", "sync"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert ("Fetched from all remotes" in cmd.stdout) == (disabled != "fetch")
+        assert "master: Done" in cmd.stdout
+        assert "myfeatbranch: Done" in cmd.stdout
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        master = repo.commit("master")
+        if disabled in ["fetch", "pull"]:
+            assert master.hexsha == root_commit
+        else:
+            assert master.message.strip() == "commit-in-master-remote"
+
+        feat = repo.commit("myfeatbranch")
+        assert feat.message.strip() == "commit-in-feat"
+        if disabled == "rebase":
+            assert repo.commit("myfeatbranch~1").hexsha == root_commit
+        else:
+            assert repo.commit("myfeatbranch~1").hexsha == master.hexsha
+
+        # Unchanged branches are merged already and cleaned up
+        assert ("mergedbranch" in os.listdir(base_dir)) == (disabled == "clean")
+
+
+def test_worktree_sync_stops_on_conflict():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _root_commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('persistent_branches = ["master"]\n')
+
+        setup_worktrees(base_dir)
+        shell(f"cd {base_dir}/myfeatbranch && echo change > uncommittedchange")
+
+        cmd = grm(["wt", "sync"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "myfeatbranch: " in cmd.stderr
+        assert "master: Done" in cmd.stdout
+
+        repo = git.Repo(f"{base_dir}/.git-main-working-tree")
+        assert repo.commit("myfeatbranch~1").hexsha != repo.commit("master").hexsha
+
+        cmd = grm(["wt", "sync", "--stash"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert repo.commit("myfeatbranch~1").hexsha == repo.commit("master").hexsha
+        assert "uncommittedchange" in os.listdir(f"{base_dir}/myfeatbranch")
@@ -308,24 +308,52 @@ def test_worktree_add(
         files = os.listdir(base_dir)
         if config_enabled is True:
             if worktree_with_slash:
-                assert set(files) == {".git-main-working-tree", "grm.toml", "dir"}
+                assert set(files) == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    "grm.toml",
+                    "dir",
+                }
             else:
-                assert set(files) == {".git-main-working-tree", "grm.toml", "test"}
-            assert len(files) == 3
+                assert set(files) == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    "grm.toml",
+                    "test",
+                }
+            assert len(files) == 4
             if worktree_with_slash:
-                assert set(files) == {".git-main-working-tree", "grm.toml", "dir"}
+                assert set(files) == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    "grm.toml",
+                    "dir",
+                }
                 assert set(os.listdir(os.path.join(base_dir, "dir"))) == {"nested"}
                 assert set(os.listdir(os.path.join(base_dir, "dir/nested"))) == {"test"}
             else:
-                assert set(files) == {".git-main-working-tree", "grm.toml", "test"}
+                assert set(files) == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    "grm.toml",
+                    "test",
+                }
         else:
-            assert len(files) == 2
+            assert len(files) == 3
             if worktree_with_slash:
-                assert set(files) == {".git-main-working-tree", "dir"}
+                assert set(files) == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    "dir",
+                }
                 assert set(os.listdir(os.path.join(base_dir, "dir"))) == {"nested"}
                 assert set(os.listdir(os.path.join(base_dir, "dir/nested"))) == {"test"}
             else:
-                assert set(files) == {".git-main-working-tree", "test"}
+                assert set(files) == {
+                    ".git-main-working-tree",
+                    ".grm-state.json",
+                    "test",
+                }
 
         repo = git.Repo(os.path.join(base_dir, worktree_name))
         assert not repo.bare
@@ -698,6 +726,113 @@ def test_worktree_delete_force_refusal():
         assert "test" not in os.listdir(base_dir)
 
 
+def test_worktree_delete_archive():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('[cleanup]\narchive = "refs/archive/{branch}"\n')
+
+        repo = git.Repo(os.path.join(base_dir, ".git-main-working-tree"))
+        commits = []
+        for i in range(2):
+            cmd = grm(["wt", "add", "test"], cwd=base_dir)
+            assert cmd.returncode == 0
+            shell(f"cd {base_dir}/test && git commit --allow-empty -m commit-{i}")
+            commits.append(repo.commit("test").hexsha)
+
+            cmd = grm(["wt", "delete", "test", "--force"], cwd=base_dir)
+            assert cmd.returncode == 0
+            assert "test" not in os.listdir(base_dir)
+            assert "test" not in [str(b) for b in repo.branches]
+
+        # An existing archive is never overwritten
+        assert "archived to refs/archive/test-2" in cmd.stdout
+        assert repo.commit("refs/archive/test").hexsha == commits[0]
+        assert repo.commit("refs/archive/test-2").hexsha == commits[1]
+
+
+def test_worktree_delete_archive_invalid():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('[cleanup]\narchive = "archive/{branch}"\n')
+
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(["wt", "delete", "test", "--force"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "not a valid reference name" in cmd.stderr
+        assert "test" in os.listdir(base_dir)
+
+
+def test_worktree_delete_ignore_upstream_divergence():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/test
+            touch committed_file
+            git add committed_file
+            git commit -m "commitmsg"
+            touch changed_file
+            """
+        )
+
+        # Uncommitted changes are still checked
+        cmd = grm(
+            ["wt", "delete", "test", "--ignore-upstream-divergence"], cwd=base_dir
+        )
+        assert cmd.returncode != 0
+        assert "changes in worktree" in cmd.stderr.lower()
+        assert "test" in os.listdir(base_dir)
+
+        cmd = grm(["wt", "delete", "test", "--ignore-changes"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "not in line with remote branch" in cmd.stderr
+        assert "test" in os.listdir(base_dir)
+
+        cmd = grm(
+            [
+                "wt",
+                "delete",
+                "test",
+                "--ignore-changes",
+                "--ignore-upstream-divergence",
+            ],
+            cwd=base_dir,
+        )
+        assert cmd.returncode == 0
+        assert "test" not in os.listdir(base_dir)
+
+
+def test_worktree_delete_ignore_unmerged():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        with open(os.path.join(base_dir, "grm.toml"), "w") as f:
+            f.write('persistent_branches = ["master"]\n')
+
+        cmd = grm(["wt", "add", "master"], cwd=base_dir)
+        assert cmd.returncode == 0
+        cmd = grm(["wt", "add", "test"], cwd=base_dir)
+        assert cmd.returncode == 0
+        shell(
+            f"""
+            cd {base_dir}/test
+            touch committed_file
+            git add committed_file
+            git commit -m "commitmsg"
+            """
+        )
+
+        cmd = grm(["wt", "delete", "test"], cwd=base_dir)
+        assert cmd.returncode != 0
+        assert "not merged into any persistent branches" in cmd.stderr
+        assert "test" in os.listdir(base_dir)
+
+        cmd = grm(["wt", "delete", "test", "--ignore-unmerged"], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "test" not in os.listdir(base_dir)
+
+
 def test_worktree_add_delete_add():
     with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
         cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
@@ -711,3 +846,63 @@ def test_worktree_add_delete_add():
         cmd = grm(["wt", "add", "test", "--track", "origin/test"], cwd=base_dir)
         assert cmd.returncode == 0
         assert "test" in os.listdir(base_dir)
+
+
+@pytest.mark.parametrize("from_stdin", [True, False])
+def test_worktree_add_from_file(from_stdin):
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        names = "feature-a\n\n# comment\nfix/b\nfeature-a\n"
+        if from_stdin:
+            cmd = grm(["wt", "add", "--from-file", "-"], cwd=base_dir, input=names)
+        else:
+            with tempfile.NamedTemporaryFile("w") as f:
+                f.write(names)
+                f.flush()
+                cmd = grm(["wt", "add", "--from-file", f.name], cwd=base_dir)
+        assert cmd.returncode == 0
+        assert "Worktree feature-a created" in cmd.stdout
+        assert "Worktree fix/b created" in cmd.stdout
+
+        assert "feature-a" in os.listdir(base_dir)
+        assert "b" in os.listdir(os.path.join(base_dir, "fix"))
+        with git.Repo(os.path.join(base_dir, "fix", "b")) as repo:
+            assert str(repo.active_branch) == "fix/b"
+
+
+def test_worktree_add_from_file_partial_failure():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "existing"], cwd=base_dir)
+        assert cmd.returncode == 0
+
+        cmd = grm(
+            ["wt", "add", "--from-file", "-"],
+            cwd=base_dir,
+            input="existing\nnew\n",
+        )
+        assert cmd.returncode != 0
+        assert "existing: Error creating worktree" in cmd.stderr
+        assert "Failed creating 1 of 2 worktrees" in cmd.stderr
+        assert "Worktree new created" in cmd.stdout
+        assert "new" in os.listdir(base_dir)
+
+
+def test_worktree_add_from_file_invalid():
+    with TempGitRepositoryWorktree.get(funcname()) as (base_dir, _commit):
+        cmd = grm(["wt", "add", "--from-file", "-"], cwd=base_dir, input="# none\n")
+        assert cmd.returncode != 0
+        assert "No worktree names given" in cmd.stderr
+
+        cmd = grm(
+            ["wt", "add", "--from-file", "-", "test"], cwd=base_dir, is_invalid=True
+        )
+        assert cmd.returncode != 0
+
+        cmd = grm(
+            ["wt", "add", "--from-file", "-", "--track", "origin/test"],
+            cwd=base_dir,
+            is_invalid=True,
+        )
+        assert cmd.returncode != 0
+
+        cmd = grm(["wt", "add"], cwd=base_dir, is_invalid=True)
+        assert cmd.returncode != 0
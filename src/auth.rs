@@ -1,9 +1,19 @@
+use std::io::Write;
+use std::path::Path;
 use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 
 #[derive(Clone)]
 pub struct AuthToken(String);
 
 impl AuthToken {
+    pub fn new(token: String) -> Self {
+        Self(token)
+    }
+
     pub fn access(&self) -> &str {
         &self.0
     }
@@ -43,3 +53,63 @@ pub fn get_token_from_command(command: &str) -> Result<AuthToken, String> {
 
     Ok(AuthToken(token.to_string()))
 }
+
+/// Creates a JSON Web Token that authenticates as the GitHub App `app_id`
+///
+/// The token is signed with the private key of the app. Signing is done by
+/// `openssl`, so it has to be installed.
+pub fn github_app_jwt(app_id: u64, private_key: &Path) -> Result<AuthToken, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|error| error.to_string())?
+        .as_secs();
+
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+    // GitHub accepts tokens that are valid for at most ten minutes. The token is
+    // backdated a bit to allow for clock drift.
+    let claims = URL_SAFE_NO_PAD.encode(
+        serde_json::json!({
+            "iat": now - 60,
+            "exp": now + 540,
+            "iss": app_id,
+        })
+        .to_string(),
+    );
+
+    let message = format!("{header}.{claims}");
+    let signature = URL_SAFE_NO_PAD.encode(sign_rs256(&message, private_key)?);
+
+    Ok(AuthToken(format!("{message}.{signature}")))
+}
+
+fn sign_rs256(message: &str, private_key: &Path) -> Result<Vec<u8>, String> {
+    let mut child = process::Command::new("openssl")
+        .args(["dgst", "-sha256", "-sign"])
+        .arg(private_key)
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Failed to run openssl: {error}"))?;
+
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| String::from("Failed to open stdin of openssl"))?
+        .write_all(message.as_bytes())
+        .map_err(|error| format!("Failed to write to openssl: {error}"))?;
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Failed to run openssl: {error}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Signing with private key \"{}\" failed: {}",
+            private_key.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,187 @@
+//! Opening repositories and branches in the web interface of their forge, see
+//! `grm repos browse` and `grm wt browse`
+//!
+//! The web URL is derived from the URL of a remote. For the big public forges,
+//! the URL schemes are built in. Self-hosted instances are configured with
+//! templates in `web_urls`, where `{path}` is replaced with the path of the
+//! repository on the host (e.g. `hakoerber/git-repo-manager`) and `{branch}`
+//! with the branch.
+
+use serde::{Deserialize, Serialize};
+
+use super::repo;
+
+/// How to get from a remote on `host` to the web interface
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebUrlTemplate {
+    /// The host of the remote URLs, e.g. `git.example.com`
+    pub host: String,
+    /// e.g. `https://git.example.com/{path}`
+    pub url: String,
+    /// e.g. `https://git.example.com/{path}/-/tree/{branch}`. Without it, the
+    /// page of the repository is opened for branches as well.
+    pub branch_url: Option<String>,
+}
+
+fn default_templates() -> Vec<WebUrlTemplate> {
+    [
+        ("github.com", "/tree/{branch}"),
+        ("gitlab.com", "/-/tree/{branch}"),
+        ("bitbucket.org", "/src/{branch}"),
+        ("codeberg.org", "/src/branch/{branch}"),
+        ("gitea.com", "/src/branch/{branch}"),
+    ]
+    .into_iter()
+    .map(|(host, branch_suffix)| WebUrlTemplate {
+        host: host.to_string(),
+        url: format!("https://{host}/{{path}}"),
+        branch_url: Some(format!("https://{host}/{{path}}{branch_suffix}")),
+    })
+    .collect()
+}
+
+/// Returns the path of the repository on its host, without a `.git` suffix
+fn remote_path(remote_url: &str) -> Option<&str> {
+    let path = match remote_url.split_once("://") {
+        Some((_scheme, rest)) => rest.split_once('/')?.1,
+        None => remote_url.split_once(':')?.1,
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Returns the web URL of the repository behind `remote_url`, or of `branch` in
+/// it
+///
+/// The `configured` templates take precedence over the built-in ones.
+pub fn web_url(
+    remote_url: &str,
+    branch: Option<&str>,
+    configured: &[WebUrlTemplate],
+) -> Result<String, String> {
+    let (Some(host), Some(path)) = (repo::remote_host(remote_url), remote_path(remote_url)) else {
+        return Err(format!("Remote \"{remote_url}\" does not point to a forge"));
+    };
+
+    let defaults = default_templates();
+    let template = configured
+        .iter()
+        .chain(&defaults)
+        .find(|template| template.host.eq_ignore_ascii_case(&host))
+        .ok_or_else(|| {
+            format!(
+                "The web URL for host \"{host}\" is unknown, add it to \"web_urls\" in the configuration"
+            )
+        })?;
+
+    let url = match (branch, &template.branch_url) {
+        (Some(branch), Some(branch_url)) => branch_url.replace("{branch}", branch),
+        _ => template.url.clone(),
+    };
+    Ok(url.replace("{path}", path))
+}
+
+/// Opens `url` in the default browser
+///
+/// `$BROWSER` wins if it is set, otherwise `xdg-open` (or `open` on macOS) is
+/// used.
+pub fn open_url(url: &str) -> Result<(), String> {
+    let command = match std::env::var("BROWSER") {
+        Ok(browser) if !browser.is_empty() => browser,
+        _ if cfg!(target_os = "macos") => String::from("open"),
+        _ => String::from("xdg-open"),
+    };
+
+    let status = std::process::Command::new(&command)
+        .arg(url)
+        .status()
+        .map_err(|error| format!("Failed to run \"{command}\": {error}"))?;
+
+    if !status.success() {
+        return Err(match status.code() {
+            Some(code) => format!("\"{command}\" failed with exit code {code}"),
+            None => format!("\"{command}\" was terminated by a signal"),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_web_url() {
+        for remote_url in [
+            "https://github.com/hakoerber/git-repo-manager.git",
+            "https://github.com/hakoerber/git-repo-manager",
+            "git@github.com:hakoerber/git-repo-manager.git",
+            "ssh://git@github.com/hakoerber/git-repo-manager.git",
+            "ssh://git@github.com:22/hakoerber/git-repo-manager",
+        ] {
+            assert_eq!(
+                web_url(remote_url, None, &[]),
+                Ok(String::from(
+                    "https://github.com/hakoerber/git-repo-manager"
+                ))
+            );
+        }
+
+        assert_eq!(
+            web_url("git@gitlab.com:group/sub/project.git", Some("main"), &[]),
+            Ok(String::from(
+                "https://gitlab.com/group/sub/project/-/tree/main"
+            ))
+        );
+        assert_eq!(
+            web_url("git@bitbucket.org:team/repo.git", Some("feature/x"), &[]),
+            Ok(String::from(
+                "https://bitbucket.org/team/repo/src/feature/x"
+            ))
+        );
+        assert_eq!(
+            web_url("https://codeberg.org/user/repo.git", Some("dev"), &[]),
+            Ok(String::from(
+                "https://codeberg.org/user/repo/src/branch/dev"
+            ))
+        );
+    }
+
+    #[test]
+    fn check_web_url_configured() {
+        let configured = vec![
+            WebUrlTemplate {
+                host: String::from("ssh.git.example.com"),
+                url: String::from("https://git.example.com/{path}"),
+                branch_url: None,
+            },
+            WebUrlTemplate {
+                host: String::from("github.com"),
+                url: String::from("https://mirror.example.com/{path}"),
+                branch_url: Some(String::from("https://mirror.example.com/{path}?b={branch}")),
+            },
+        ];
+
+        assert_eq!(
+            web_url(
+                "git@ssh.git.example.com:team/repo.git",
+                Some("main"),
+                &configured
+            ),
+            Ok(String::from("https://git.example.com/team/repo"))
+        );
+        assert_eq!(
+            web_url("git@github.com:user/repo.git", Some("main"), &configured),
+            Ok(String::from("https://mirror.example.com/user/repo?b=main"))
+        );
+        assert!(web_url("git@unknown.example.com:repo.git", None, &configured).is_err());
+        assert!(web_url("file:///tmp/repo", None, &configured).is_err());
+    }
+}
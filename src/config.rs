@@ -1,24 +1,39 @@
 use serde::{Deserialize, Serialize};
-use std::process;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use super::auth;
+use super::browse;
 use super::output::*;
 use super::path;
 use super::provider;
+use super::provider::AccessLevel;
 use super::provider::Filter;
 use super::provider::Provider;
 use super::repo;
 use super::tree;
+use super::worktree;
 
 pub type RemoteProvider = provider::RemoteProvider;
 pub type RemoteType = repo::RemoteType;
+pub type CloneConfig = repo::CloneConfig;
+pub type FetchConfig = repo::FetchConfig;
+pub type RetryConfig = repo::RetryConfig;
+pub type HostMapping = repo::HostMapping;
+
+/// Aliases for the beginning of remote URLs, like `url.<base>.insteadOf` of git,
+/// e.g. `"work:" = "ssh://git@gitlab.example.com:2222/"`
+pub type HostAliases = BTreeMap<String, String>;
 
 fn worktree_setup_default() -> bool {
     false
 }
 
+// Only a single configuration exists at a time, so its size does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Config {
@@ -30,23 +45,117 @@ pub enum Config {
 #[serde(deny_unknown_fields)]
 pub struct ConfigTrees {
     pub trees: Vec<ConfigTree>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<NotifyConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone: Option<CloneConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    /// Additional hosts for `--rewrite-protocol`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_mappings: Option<Vec<HostMapping>>,
+
+    /// Aliases for the beginning of remote URLs, see [`HostAliases`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<HostAliases>,
+
+    /// Web interfaces of self-hosted forges for `grm repos browse`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_urls: Option<Vec<browse::WebUrlTemplate>>,
+
+    /// Repositories outside of any tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standalone: Option<Vec<StandaloneRepoConfig>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigProviderFilter {
     pub access: Option<bool>,
     pub owner: Option<bool>,
     pub users: Option<Vec<String>>,
     pub groups: Option<Vec<String>>,
+    pub orgs: Option<Vec<String>>,
+    pub starred: Option<bool>,
+    pub min_access_level: Option<AccessLevel>,
+}
+
+impl ConfigProviderFilter {
+    fn to_filter(&self) -> Filter {
+        Filter::new(
+            self.users.clone().unwrap_or_default(),
+            self.groups.clone().unwrap_or_default(),
+            self.orgs.clone().unwrap_or_default(),
+            self.owner.unwrap_or(false),
+            self.access.unwrap_or(false),
+            self.starred.unwrap_or(false),
+            self.min_access_level,
+        )
+    }
+}
+
+/// One of several accounts of a forge, e.g. a personal and a bot account
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigProviderAccount {
+    pub token_command: String,
+    /// Overrides the filters of the provider configuration for this account
+    pub filters: Option<ConfigProviderFilter>,
+}
+
+/// A GitHub App that authenticates via one of its installations
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GithubAppConfig {
+    pub app_id: u64,
+    /// Path to the private key of the app (PEM)
+    pub private_key: String,
+    /// Required if the app is installed more than once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installation_id: Option<u64>,
+}
+
+impl GithubAppConfig {
+    fn get_token(&self) -> Result<auth::AuthToken, String> {
+        let jwt = auth::github_app_jwt(
+            self.app_id,
+            &path::expand_path(Path::new(&self.private_key)),
+        )?;
+        provider::github::installation_token(&jwt, self.installation_id)
+            .map_err(|error| format!("Getting token of GitHub App failed: {error}"))
+    }
+}
+
+/// How to get the token of an account
+enum Credentials<'a> {
+    TokenCommand(&'a str),
+    GithubApp(&'a GithubAppConfig),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigProvider {
     pub provider: RemoteProvider,
-    pub token_command: String,
+    pub token_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<ConfigProviderAccount>>,
+    /// Authenticate as a GitHub App instead of a user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_app: Option<GithubAppConfig>,
     pub root: String,
+    /// Different roots for some namespaces, see [`ConfigProvider::namespace_root()`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace_roots: Option<BTreeMap<String, String>>,
+    /// One tree per user, group and organization of the filters instead of one
+    /// per namespace, see [`provider::Filter::group_by_entry()`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree_per_filter: Option<bool>,
     pub filters: Option<ConfigProviderFilter>,
 
     pub force_ssh: Option<bool>,
@@ -56,6 +165,61 @@ pub struct ConfigProvider {
     pub worktree: Option<bool>,
 
     pub remote_name: Option<String>,
+
+    /// Builds the remote URLs from this template instead of using the ones
+    /// reported by the API, see [`provider::render_url_template()`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url_template: Option<String>,
+
+    /// Number of repositories per API request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<u32>,
+
+    /// Maximum number of API requests for listing repositories, per account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_requests: Option<usize>,
+
+    /// Permissions of created directories, see [`ConfigTree::dir_mode`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir_mode: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<NotifyConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone: Option<CloneConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    /// Additional hosts for `--rewrite-protocol`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_mappings: Option<Vec<HostMapping>>,
+
+    /// Aliases for the beginning of remote URLs, see [`HostAliases`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<HostAliases>,
+
+    /// Web interfaces of self-hosted forges for `grm repos browse`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_urls: Option<Vec<browse::WebUrlTemplate>>,
+}
+
+/// Notifications that are sent after a sync or fetch, see [`notify`](super::notify)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// A command that receives the JSON report on stdin
+    pub command: Option<String>,
+
+    /// An URL that the JSON report is sent to via HTTP POST
+    pub webhook_url: Option<String>,
+
+    /// A command that prints the webhook URL, for URLs that contain a secret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_url_command: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -94,6 +258,28 @@ pub struct RepoConfig {
     pub worktree_setup: bool,
 
     pub remotes: Option<Vec<RemoteConfig>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+
+    /// Partial clone filter for the initial clone, e.g. `blob:none`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    /// The branch to create the initial worktree for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+
+    /// The directory below the tree root, if it should differ from the name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+
+    /// Overrides `canonical_remote_name` of the tree for this repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_name: Option<String>,
 }
 
 impl RepoConfig {
@@ -104,6 +290,16 @@ impl RepoConfig {
             remotes: repo
                 .remotes
                 .map(|remotes| remotes.into_iter().map(RemoteConfig::from_remote).collect()),
+            provider_id: repo.provider_id,
+            tags: if repo.tags.is_empty() {
+                None
+            } else {
+                Some(repo.tags)
+            },
+            filter: repo.filter,
+            default_branch: repo.default_branch,
+            dir: repo.dir,
+            remote_name: repo.remote_name,
         }
     }
 
@@ -124,22 +320,229 @@ impl RepoConfig {
                     .map(|remote| remote.into_remote())
                     .collect()
             }),
+            provider_id: self.provider_id,
+            tags: self.tags.unwrap_or_default(),
+            filter: self.filter,
+            default_branch: self.default_branch,
+            dir: self.dir,
+            remote_name: self.remote_name,
         }
     }
 }
 
+impl ConfigProvider {
+    /// The directory that the repositories of `namespace` go to
+    ///
+    /// The longest key of `namespace_roots` that `namespace` starts with wins, and
+    /// the rest of the namespace is appended to its root. `*` matches all other
+    /// namespaces, including repositories without a namespace. Without a match,
+    /// the namespace is appended to `root`.
+    pub fn namespace_root(&self, namespace: Option<&str>) -> String {
+        let roots = self.namespace_roots.as_ref();
+
+        let matching = namespace.and_then(|namespace| {
+            roots?
+                .iter()
+                .filter(|(key, _)| *key != "*")
+                .filter_map(|(key, root)| {
+                    let rest = if namespace == key {
+                        ""
+                    } else {
+                        namespace.strip_prefix(key.as_str())?.strip_prefix('/')?
+                    };
+                    Some((key.len(), root, rest))
+                })
+                .max_by_key(|(length, _, _)| *length)
+                .map(|(_, root, rest)| (root, rest))
+        });
+
+        let (root, rest) = matching.unwrap_or_else(|| {
+            (
+                roots.and_then(|roots| roots.get("*")).unwrap_or(&self.root),
+                namespace.unwrap_or(""),
+            )
+        });
+
+        if rest.is_empty() {
+            path::path_as_string(Path::new(root))
+        } else {
+            path::path_as_string(&Path::new(root).join(rest))
+        }
+    }
+
+    /// Returns all repositories matching the filters as trees, one per root
+    pub fn trees(&self) -> Result<Vec<ConfigTree>, String> {
+        let mut roots: BTreeMap<String, Vec<RepoConfig>> = BTreeMap::new();
+
+        for (namespace, namespace_repos) in self.get_repos()? {
+            roots
+                .entry(self.namespace_root(namespace.as_deref()))
+                .or_default()
+                .extend(namespace_repos.into_iter().map(RepoConfig::from_repo));
+        }
+
+        Ok(roots
+            .into_iter()
+            .map(|(root, repos)| ConfigTree {
+                root,
+                repos: Some(repos),
+                canonical_remote_name: None,
+                name: None,
+                serial: None,
+                depends_on: None,
+                history_days: None,
+                max_size_mb: None,
+                dir_mode: self.dir_mode.clone(),
+                standalone: false,
+            })
+            .collect())
+    }
+
+    /// Returns all repositories matching the filters, grouped by namespace
+    ///
+    /// With multiple `accounts`, the repositories of all accounts are merged.
+    /// Repositories that are visible to more than one account are only returned once.
+    pub fn get_repos(&self) -> Result<HashMap<Option<String>, Vec<repo::Repo>>, String> {
+        let accounts: Vec<(Credentials, Option<&ConfigProviderFilter>)> =
+            match (&self.token_command, &self.accounts, &self.github_app) {
+                (Some(token_command), None, None) => {
+                    vec![(Credentials::TokenCommand(token_command), None)]
+                }
+                (None, Some(accounts), None) if !accounts.is_empty() => accounts
+                    .iter()
+                    .map(|account| {
+                        (
+                            Credentials::TokenCommand(account.token_command.as_str()),
+                            account.filters.as_ref(),
+                        )
+                    })
+                    .collect(),
+                (None, None, Some(github_app)) => {
+                    if !matches!(self.provider, RemoteProvider::Github) {
+                        return Err(String::from("\"github_app\" can only be used with GitHub"));
+                    }
+                    vec![(Credentials::GithubApp(github_app), None)]
+                }
+                (None, None | Some(_), None) => {
+                    return Err(String::from(
+                        "Either \"token_command\", \"accounts\" or \"github_app\" is required",
+                    ))
+                }
+                _ => return Err(String::from(
+                    "Only one of \"token_command\", \"accounts\" and \"github_app\" can be used",
+                )),
+            };
+
+        let mut repos: HashMap<Option<String>, Vec<repo::Repo>> = HashMap::new();
+
+        for (credentials, filters) in accounts {
+            let token = match credentials {
+                Credentials::TokenCommand(token_command) => {
+                    auth::get_token_from_command(token_command)
+                        .map_err(|error| format!("Getting token from command failed: {error}"))?
+                }
+                Credentials::GithubApp(github_app) => {
+                    // An app is no user, so there is nothing it owns or has access to
+                    if let Some(filters) = filters.or(self.filters.as_ref()) {
+                        if filters.owner.unwrap_or(false) || filters.access.unwrap_or(false) {
+                            return Err(String::from(
+                                "The \"owner\" and \"access\" filters cannot be used with a GitHub App, use \"orgs\" or \"users\" instead",
+                            ));
+                        }
+                    }
+                    github_app.get_token()?
+                }
+            };
+
+            let filter = filters
+                .or(self.filters.as_ref())
+                .map(ConfigProviderFilter::to_filter)
+                .unwrap_or_else(|| Filter::new(vec![], vec![], vec![], false, false, false, None));
+
+            if filter.empty() {
+                print_warning("You did not specify any filters, so no repos will match");
+            }
+
+            let worktree = self.worktree.unwrap_or(false);
+            let force_ssh = self.force_ssh.unwrap_or(false);
+            let remote_name = self.remote_name.clone();
+            let url_template = self.url_template.as_deref();
+            let api_url = self.api_url.clone();
+
+            let pagination = provider::Pagination::new(self.per_page, self.max_requests);
+            let grouping = self
+                .tree_per_filter
+                .unwrap_or(false)
+                .then(|| filter.clone());
+
+            let account_repos = match self.provider {
+                RemoteProvider::Github => provider::Github::new(
+                    filter, token, api_url, pagination,
+                )?
+                .get_repos(worktree, force_ssh, remote_name, url_template)?,
+                RemoteProvider::Gitlab => provider::Gitlab::new(
+                    filter, token, api_url, pagination,
+                )?
+                .get_repos(worktree, force_ssh, remote_name, url_template)?,
+                RemoteProvider::Bitbucket => provider::Bitbucket::new(
+                    filter, token, api_url, pagination,
+                )?
+                .get_repos(worktree, force_ssh, remote_name, url_template)?,
+                RemoteProvider::AzureDevops => provider::AzureDevops::new(
+                    filter, token, api_url, pagination,
+                )?
+                .get_repos(worktree, force_ssh, remote_name, url_template)?,
+            };
+
+            let account_repos = match &grouping {
+                Some(filter) => filter.group_by_entry(account_repos),
+                None => account_repos,
+            };
+
+            for (namespace, namespace_repos) in account_repos {
+                let known_repos = repos.entry(namespace).or_default();
+                for repo in namespace_repos {
+                    if !known_repos.iter().any(|known| known.name == repo.name) {
+                        known_repos.push(repo);
+                    }
+                }
+            }
+        }
+
+        Ok(repos)
+    }
+}
+
 impl ConfigTrees {
     pub fn to_config(self) -> Config {
         Config::ConfigTrees(self)
     }
 
     pub fn from_vec(vec: Vec<ConfigTree>) -> Self {
-        Self { trees: vec }
+        Self {
+            trees: vec,
+            notify: None,
+            fetch: None,
+            clone: None,
+            retry: None,
+            host_mappings: None,
+            hosts: None,
+            web_urls: None,
+            standalone: None,
+        }
     }
 
     pub fn from_trees(vec: Vec<tree::Tree>) -> Self {
         Self {
             trees: vec.into_iter().map(ConfigTree::from_tree).collect(),
+            notify: None,
+            fetch: None,
+            clone: None,
+            retry: None,
+            host_mappings: None,
+            hosts: None,
+            web_urls: None,
+            standalone: None,
         }
     }
 
@@ -147,6 +550,22 @@ impl ConfigTrees {
         self.trees
     }
 
+    /// Replaces the beginning of all remote URLs by their alias from `hosts`,
+    /// where there is one
+    pub fn shorten_urls(&mut self) {
+        let Some(hosts) = &self.hosts else {
+            return;
+        };
+        for remote in self
+            .trees
+            .iter_mut()
+            .flat_map(|tree| tree.repos.iter_mut().flatten())
+            .flat_map(|repo| repo.remotes.iter_mut().flatten())
+        {
+            remote.url = shorten_url(&remote.url, hosts);
+        }
+    }
+
     pub fn trees_mut(&mut self) -> &mut Vec<ConfigTree> {
         &mut self.trees
     }
@@ -154,96 +573,116 @@ impl ConfigTrees {
     pub fn trees_ref(&self) -> &Vec<ConfigTree> {
         self.trees.as_ref()
     }
+
+    /// The tree with the name or root `tree`, which can be omitted if there is
+    /// only a single tree
+    pub fn tree_mut(&mut self, tree: Option<&str>) -> Result<&mut ConfigTree, String> {
+        match tree {
+            Some(name_or_root) => self
+                .trees
+                .iter_mut()
+                .find(|tree| tree_is_called(tree.name.as_deref(), &tree.root, name_or_root))
+                .ok_or_else(|| format!("There is no tree with name or root \"{name_or_root}\"")),
+            None => match self.trees.as_mut_slice() {
+                [tree] => Ok(tree),
+                [] => Err(String::from("The configuration does not contain any trees")),
+                _ => Err(String::from(
+                    "The configuration contains multiple trees, use --tree to select one",
+                )),
+            },
+        }
+    }
 }
 
 impl Config {
-    pub fn trees(self) -> Result<Vec<ConfigTree>, String> {
+    pub fn trees(mut self) -> Result<Vec<ConfigTree>, String> {
+        self.expand_hosts();
         match self {
-            Self::ConfigTrees(config) => Ok(config.trees),
-            Self::ConfigProvider(config) => {
-                let token = match auth::get_token_from_command(&config.token_command) {
-                    Ok(token) => token,
-                    Err(error) => {
-                        print_error(&format!("Getting token from command failed: {error}"));
-                        process::exit(1);
-                    }
-                };
-
-                let filters = config.filters.unwrap_or(ConfigProviderFilter {
-                    access: Some(false),
-                    owner: Some(false),
-                    users: Some(vec![]),
-                    groups: Some(vec![]),
-                });
-
-                let filter = Filter::new(
-                    filters.users.unwrap_or_default(),
-                    filters.groups.unwrap_or_default(),
-                    filters.owner.unwrap_or(false),
-                    filters.access.unwrap_or(false),
-                );
-
-                if filter.empty() {
-                    print_warning(
-                        "The configuration does not contain any filters, so no repos will match",
-                    );
-                }
-
-                let repos = match config.provider {
-                    RemoteProvider::Github => {
-                        match provider::Github::new(filter, token, config.api_url) {
-                            Ok(provider) => provider,
-                            Err(error) => {
-                                print_error(&format!("Error: {error}"));
-                                process::exit(1);
-                            }
-                        }
-                        .get_repos(
-                            config.worktree.unwrap_or(false),
-                            config.force_ssh.unwrap_or(false),
-                            config.remote_name,
-                        )?
-                    }
-                    RemoteProvider::Gitlab => {
-                        match provider::Gitlab::new(filter, token, config.api_url) {
-                            Ok(provider) => provider,
-                            Err(error) => {
-                                print_error(&format!("Error: {error}"));
-                                process::exit(1);
-                            }
-                        }
-                        .get_repos(
-                            config.worktree.unwrap_or(false),
-                            config.force_ssh.unwrap_or(false),
-                            config.remote_name,
-                        )?
-                    }
-                };
-
-                let mut trees = vec![];
-
-                for (namespace, namespace_repos) in repos {
-                    let repos = namespace_repos
-                        .into_iter()
-                        .map(RepoConfig::from_repo)
-                        .collect();
-                    let tree = ConfigTree {
-                        root: if let Some(namespace) = namespace {
-                            path::path_as_string(&Path::new(&config.root).join(namespace))
-                        } else {
-                            path::path_as_string(Path::new(&config.root))
-                        },
-                        repos: Some(repos),
-                    };
-                    trees.push(tree);
+            Self::ConfigTrees(config) => {
+                let mut trees = config.trees;
+                for repo in config.standalone.unwrap_or_default() {
+                    trees.push(repo.into_tree()?);
                 }
                 Ok(trees)
             }
+            Self::ConfigProvider(config) => config.trees(),
+        }
+    }
+
+    /// Replaces the aliases in all remote URLs with what they stand for, see
+    /// [`HostAliases`]. The aliases themselves are dropped.
+    pub fn expand_hosts(&mut self) {
+        if let Self::ConfigTrees(config) = self {
+            let hosts = config.hosts.take().unwrap_or_default();
+            for remote in config
+                .trees
+                .iter_mut()
+                .flat_map(|tree| tree.repos.iter_mut().flatten())
+                .flat_map(|repo| repo.remotes.iter_mut().flatten())
+                .chain(
+                    config
+                        .standalone
+                        .iter_mut()
+                        .flatten()
+                        .flat_map(|repo| repo.remotes.iter_mut().flatten()),
+                )
+            {
+                remote.url = expand_url(&remote.url, &hosts);
+            }
         }
     }
 
     pub fn from_trees(trees: Vec<ConfigTree>) -> Self {
-        Self::ConfigTrees(ConfigTrees { trees })
+        Self::ConfigTrees(ConfigTrees::from_vec(trees))
+    }
+
+    pub fn notify(&self) -> Option<&NotifyConfig> {
+        match self {
+            Self::ConfigTrees(config) => config.notify.as_ref(),
+            Self::ConfigProvider(config) => config.notify.as_ref(),
+        }
+    }
+
+    /// The built-in host mappings, extended by the ones from the configuration
+    pub fn host_mappings(&self) -> Vec<HostMapping> {
+        let configured = match self {
+            Self::ConfigTrees(config) => config.host_mappings.as_ref(),
+            Self::ConfigProvider(config) => config.host_mappings.as_ref(),
+        };
+        let mut mappings = repo::default_host_mappings();
+        mappings.extend(configured.into_iter().flatten().cloned());
+        mappings
+    }
+
+    pub fn web_urls(&self) -> &[browse::WebUrlTemplate] {
+        let web_urls = match self {
+            Self::ConfigTrees(config) => config.web_urls.as_ref(),
+            Self::ConfigProvider(config) => config.web_urls.as_ref(),
+        };
+        web_urls.map_or(&[], Vec::as_slice)
+    }
+
+    pub fn fetch(&self) -> Option<&FetchConfig> {
+        match self {
+            Self::ConfigTrees(config) => config.fetch.as_ref(),
+            Self::ConfigProvider(config) => config.fetch.as_ref(),
+        }
+    }
+
+    pub fn clone_config(&self) -> Option<&CloneConfig> {
+        match self {
+            Self::ConfigTrees(config) => config.clone.as_ref(),
+            Self::ConfigProvider(config) => config.clone.as_ref(),
+        }
+    }
+
+    /// The retry settings, without retries if they are not configured
+    pub fn retry(&self) -> RetryConfig {
+        match self {
+            Self::ConfigTrees(config) => config.retry,
+            Self::ConfigProvider(config) => config.retry,
+        }
+        .unwrap_or_default()
     }
 
     pub fn normalize(&mut self) {
@@ -277,6 +716,12 @@ impl Config {
     pub fn as_yaml(&self) -> Result<String, String> {
         serde_yaml::to_string(self).map_err(|e| e.to_string())
     }
+
+    pub fn as_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -284,13 +729,172 @@ impl Config {
 pub struct ConfigTree {
     pub root: String,
     pub repos: Option<Vec<RepoConfig>>,
+
+    /// If set, the first remote of every repository is called like this, regardless
+    /// of its name in `repos`
+    #[serde(alias = "remote_name", skip_serializing_if = "Option::is_none")]
+    pub canonical_remote_name: Option<String>,
+
+    /// Used to refer to the tree in `depends_on` of other trees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Sync this tree only while no other serial tree is synced, e.g. for trees
+    /// on slow network filesystems. All other trees are synced in parallel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<bool>,
+
+    /// Trees (by name or root) that have to be synced before this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+
+    /// Only clone the history of the last this many days, e.g. for mirrors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_days: Option<u32>,
+
+    /// Warn about repositories that take up more disk space than this, after
+    /// trimming their history to `history_days`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+
+    /// Permissions (octal, e.g. `0750`) of the directories that are created for
+    /// namespaces and repositories
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir_mode: Option<String>,
+
+    /// Set for the tree of a standalone repository, which is the only
+    /// repository of the tree that matters
+    #[serde(skip)]
+    pub standalone: bool,
+}
+
+/// A single repository at an arbitrary path, e.g. `/etc/nixos` or `~/dotfiles`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StandaloneRepoConfig {
+    pub path: String,
+
+    #[serde(default = "worktree_setup_default")]
+    pub worktree_setup: bool,
+
+    pub remotes: Option<Vec<RemoteConfig>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+
+    /// The branch to create the initial worktree for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+}
+
+impl StandaloneRepoConfig {
+    /// A tree in the parent directory of the repository, with the repository as
+    /// its only entry
+    pub fn into_tree(self) -> Result<ConfigTree, String> {
+        let path = path::expand_path(Path::new(&self.path));
+        if !path.is_absolute() {
+            return Err(format!(
+                "Path of standalone repository \"{}\" must be absolute",
+                self.path
+            ));
+        }
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(format!(
+                "Invalid path of standalone repository: \"{}\"",
+                self.path
+            ));
+        };
+
+        Ok(ConfigTree {
+            root: path::path_as_utf8(parent)?.to_string(),
+            repos: Some(vec![RepoConfig {
+                name: path::path_as_utf8(Path::new(name))?.to_string(),
+                worktree_setup: self.worktree_setup,
+                remotes: self.remotes,
+                provider_id: None,
+                tags: self.tags,
+                filter: None,
+                default_branch: self.default_branch,
+                dir: None,
+                remote_name: None,
+            }]),
+            canonical_remote_name: None,
+            name: None,
+            serial: None,
+            depends_on: None,
+            history_days: None,
+            max_size_mb: None,
+            dir_mode: None,
+            standalone: true,
+        })
+    }
+}
+
+/// Whether a tree is meant by `name_or_root`, which is either the name of the
+/// tree or its root (as configured or expanded)
+pub fn tree_is_called(name: Option<&str>, root: &str, name_or_root: &str) -> bool {
+    name == Some(name_or_root)
+        || root == name_or_root
+        || path::expand_path(Path::new(root)) == path::expand_path(Path::new(name_or_root))
+}
+
+/// Keeps only the trees meant by `name_or_root`, see [`tree_is_called()`]. Without
+/// `name_or_root`, all trees are kept.
+pub fn select_trees(
+    trees: Vec<ConfigTree>,
+    name_or_root: Option<&str>,
+) -> Result<Vec<ConfigTree>, String> {
+    let Some(name_or_root) = name_or_root else {
+        return Ok(trees);
+    };
+    let trees: Vec<ConfigTree> = trees
+        .into_iter()
+        .filter(|tree| tree_is_called(tree.name.as_deref(), &tree.root, name_or_root))
+        .collect();
+    if trees.is_empty() {
+        return Err(format!(
+            "There is no tree with name or root \"{name_or_root}\""
+        ));
+    }
+    Ok(trees)
 }
 
 impl ConfigTree {
+    /// How the tree is called in output, its name or else its root
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.root)
+    }
+
+    /// The directory that holds the state file of the tree
+    ///
+    /// A standalone repository keeps it in its git directory, as the directory
+    /// around it does not belong to it.
+    pub fn state_root(&self) -> PathBuf {
+        let root_path = path::expand_path(Path::new(&self.root));
+        match self.repos.as_deref() {
+            Some([repo]) if self.standalone => {
+                root_path.join(&repo.name).join(if repo.worktree_setup {
+                    worktree::GIT_MAIN_WORKTREE_DIRECTORY
+                } else {
+                    ".git"
+                })
+            }
+            _ => root_path,
+        }
+    }
+
     pub fn from_repos(root: String, repos: Vec<repo::Repo>) -> Self {
         Self {
             root,
             repos: Some(repos.into_iter().map(RepoConfig::from_repo).collect()),
+            canonical_remote_name: None,
+            name: None,
+            serial: None,
+            depends_on: None,
+            history_days: None,
+            max_size_mb: None,
+            dir_mode: None,
+            standalone: false,
         }
     }
 
@@ -298,27 +902,388 @@ impl ConfigTree {
         Self {
             root: tree.root,
             repos: Some(tree.repos.into_iter().map(RepoConfig::from_repo).collect()),
+            canonical_remote_name: None,
+            name: None,
+            serial: None,
+            depends_on: None,
+            history_days: None,
+            max_size_mb: None,
+            dir_mode: None,
+            standalone: false,
         }
     }
 }
 
-pub fn read_config<'a, T>(path: &str) -> Result<T, String>
-where
-    T: for<'de> serde::Deserialize<'de>,
-{
-    let content = match std::fs::read_to_string(path) {
-        Ok(s) => s,
-        Err(e) => {
+/// Replaces the longest alias from `hosts` that `url` starts with by the URL it
+/// stands for
+pub fn expand_url(url: &str, hosts: &HostAliases) -> String {
+    hosts
+        .iter()
+        .filter(|(alias, _)| !alias.is_empty() && url.starts_with(alias.as_str()))
+        .max_by_key(|(alias, _)| alias.len())
+        .map_or_else(
+            || url.to_string(),
+            |(alias, prefix)| format!("{prefix}{}", &url[alias.len()..]),
+        )
+}
+
+/// The opposite of [`expand_url()`]: Replaces the longest URL from `hosts` that
+/// `url` starts with by its alias
+pub fn shorten_url(url: &str, hosts: &HostAliases) -> String {
+    hosts
+        .iter()
+        .filter(|(_, prefix)| !prefix.is_empty() && url.starts_with(prefix.as_str()))
+        .max_by_key(|(_, prefix)| prefix.len())
+        .map_or_else(
+            || url.to_string(),
+            |(alias, prefix)| format!("{alias}{}", &url[prefix.len()..]),
+        )
+}
+
+/// The environment variable containing the command that decrypts configuration
+/// files encrypted with sops. The path of the file is appended.
+pub const DECRYPT_COMMAND_ENV: &str = "GRM_DECRYPT_COMMAND";
+
+const DEFAULT_DECRYPT_COMMAND: &str = "sops --decrypt";
+
+/// Parses a configuration file without interpreting it
+fn parse_value(content: &str) -> Option<serde_json::Value> {
+    toml::from_str(content)
+        .ok()
+        .or_else(|| serde_yaml::from_str(content).ok())
+}
+
+/// Whether the file contains the metadata that sops adds when encrypting
+fn is_sops_encrypted(content: &str) -> bool {
+    parse_value(content)
+        .as_ref()
+        .and_then(|value| value.get("sops"))
+        .is_some_and(serde_json::Value::is_object)
+}
+
+fn decrypt(path: &str) -> Result<String, String> {
+    let command = match std::env::var(DECRYPT_COMMAND_ENV) {
+        Ok(command) if !command.is_empty() => command,
+        _ => String::from(DEFAULT_DECRYPT_COMMAND),
+    };
+
+    // The path is passed as a positional parameter, so it does not need quoting
+    let output = std::process::Command::new("/usr/bin/env")
+        .arg("sh")
+        .arg("-c")
+        .arg(format!("{command} \"$1\""))
+        .arg("sh")
+        .arg(path)
+        .output()
+        .map_err(|error| format!("Failed to run decrypt command: {error}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "Decrypting configuration file \"{path}\" with \"{command}\" failed: {}",
+            stderr.trim()
+        ));
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|error| format!("Decrypted configuration is not valid UTF-8: {error}"))
+}
+
+/// The configuration path that makes grm read the configuration from stdin
+pub const STDIN_PATH: &str = "-";
+
+/// Reads the configuration from stdin
+///
+/// Stdin can only be read once, so the content is kept for later calls, e.g.
+/// when the daemon reloads the configuration.
+fn read_stdin() -> Result<String, String> {
+    static CONTENT: OnceLock<Result<String, String>> = OnceLock::new();
+    CONTENT
+        .get_or_init(|| {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map(|_| content)
+                .map_err(|error| format!("Error reading configuration from stdin: {error}"))
+        })
+        .clone()
+}
+
+/// Reads the configuration file at `path`, decrypting it first if it is
+/// encrypted with sops. With [`STDIN_PATH`], the configuration is read from
+/// stdin instead.
+///
+/// Returns the content and whether the file was encrypted.
+pub fn read_config_content(path: &str) -> Result<(String, bool), String> {
+    let content = if path == STDIN_PATH {
+        read_stdin()?
+    } else {
+        match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) => {
+                return Err(format!(
+                    "Error reading configuration file \"{path}\": {}",
+                    match e.kind() {
+                        std::io::ErrorKind::NotFound => String::from("not found"),
+                        _ => e.to_string(),
+                    }
+                ));
+            }
+        }
+    };
+
+    if is_sops_encrypted(&content) {
+        if path == STDIN_PATH {
+            return Err(String::from(
+                "Configurations encrypted with sops cannot be read from stdin",
+            ));
+        }
+        Ok((decrypt(path)?, true))
+    } else {
+        Ok((content, false))
+    }
+}
+
+/// Reads the configuration file at `path` as a generic value, e.g. to check it
+/// for secrets
+///
+/// Returns the value and whether the file was encrypted.
+pub fn read_config_value(path: &str) -> Result<(serde_json::Value, bool), String> {
+    let (content, encrypted) = read_config_content(path)?;
+    let value = parse_value(&content)
+        .ok_or_else(|| format!("Error parsing configuration file \"{path}\""))?;
+    Ok((value, encrypted))
+}
+
+/// Fails if grm must not write to the configuration file at `path`, which is the
+/// case for files encrypted with sops and for configurations read from stdin
+pub fn check_writable(path: &str) -> Result<(), String> {
+    if path == STDIN_PATH {
+        return Err(String::from(
+            "The configuration was read from stdin and cannot be changed by grm",
+        ));
+    }
+    match std::fs::read_to_string(path) {
+        Ok(content) if is_sops_encrypted(&content) => Err(format!(
+            "Configuration file \"{path}\" is encrypted with sops and cannot be changed by grm"
+        )),
+        // The profiles would be lost when writing the configuration back
+        Ok(content) if has_profiles(&content) => Err(format!(
+            "Configuration file \"{path}\" uses profiles and cannot be changed by grm"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Writes `config` to `path`, in the format (TOML or YAML) the file is already
+/// written in. Note that comments are not preserved.
+pub fn write_config(config: &Config, path: &str) -> Result<(), String> {
+    check_writable(path)?;
+
+    let is_toml = match std::fs::read_to_string(path) {
+        Ok(content) => toml::from_str::<toml::Table>(&content).is_ok(),
+        Err(_) => true,
+    };
+
+    let content = if is_toml {
+        config.as_toml()?
+    } else {
+        config.as_yaml()?
+    };
+
+    std::fs::write(path, content)
+        .map_err(|error| format!("Error writing configuration file \"{path}\": {error}"))
+}
+
+/// The absolute path of the configuration file at `path`, which has to exist
+pub fn config_path(path: &str) -> Result<PathBuf, String> {
+    if path == STDIN_PATH {
+        return Err(String::from("The configuration is read from stdin"));
+    }
+    std::fs::canonicalize(path).map_err(|error| {
+        format!(
+            "Error reading configuration file \"{path}\": {}",
+            match error.kind() {
+                std::io::ErrorKind::NotFound => String::from("not found"),
+                _ => error.to_string(),
+            }
+        )
+    })
+}
+
+/// Opens the configuration file at `path` in `$VISUAL` or `$EDITOR` (`vi`
+/// without either of them) and checks that it is still valid afterwards
+pub fn edit_config(path: &str) -> Result<(), String> {
+    let path = config_path(path)?;
+    let path_string = path::path_as_string(&path);
+    if let Ok(content) = std::fs::read_to_string(&path) {
+        if is_sops_encrypted(&content) {
             return Err(format!(
-                "Error reading configuration file \"{path}\": {}",
-                match e.kind() {
-                    std::io::ErrorKind::NotFound => String::from("not found"),
-                    _ => e.to_string(),
-                }
+                "Configuration file \"{path_string}\" is encrypted, use \"sops\" to edit it"
             ));
         }
+    }
+
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| String::from("vi"));
+
+    // The path is passed as a positional parameter, so it does not need quoting
+    let status = std::process::Command::new("/usr/bin/env")
+        .arg("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .map_err(|error| format!("Failed to run editor: {error}"))?;
+
+    if !status.success() {
+        return Err(match status.code() {
+            Some(code) => format!("Editor \"{editor}\" failed with exit code {code}"),
+            None => format!("Editor \"{editor}\" was terminated by a signal"),
+        });
+    }
+
+    read_config::<Config>(&path_string)
+        .map(|_config| ())
+        .map_err(|error| format!("The configuration is not valid anymore: {error}"))
+}
+
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Use the profile `name` of the configuration instead of the one named like
+/// the host
+///
+/// This is a process-wide setting, as the configuration is read in many places.
+pub fn set_profile(name: Option<String>) {
+    if let Some(name) = name {
+        let _ = PROFILE.set(name);
+    }
+}
+
+fn hostname() -> Option<String> {
+    let hostname = match std::env::var("HOSTNAME") {
+        Ok(hostname) if !hostname.is_empty() => hostname,
+        _ => {
+            let output = std::process::Command::new("hostname").output().ok()?;
+            String::from_utf8(output.stdout).ok()?
+        }
+    };
+    let hostname = hostname.trim();
+    (!hostname.is_empty()).then(|| hostname.to_string())
+}
+
+fn has_profiles(content: &str) -> bool {
+    parse_value(content).is_some_and(|value| value.get("profiles").is_some())
+}
+
+/// Merges `overrides` into `base`
+///
+/// Tables are merged recursively, everything else is replaced. The exception
+/// are trees: A tree in `overrides` with a `name` is merged into the tree of
+/// `base` with the same name, other trees are added.
+fn merge_value(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(serde_json::Value::Array(base_trees)) if key == "trees" => {
+                        let serde_json::Value::Array(trees) = value else {
+                            base.insert(key, value);
+                            continue;
+                        };
+                        for tree in trees {
+                            let existing = tree.get("name").and_then(|name| {
+                                base_trees
+                                    .iter_mut()
+                                    .find(|base_tree| base_tree.get("name") == Some(name))
+                            });
+                            match existing {
+                                Some(existing) => merge_value(existing, tree),
+                                None => base_trees.push(tree),
+                            }
+                        }
+                    }
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Removes the `profiles` from the configuration and merges the active one into
+/// the rest of it
+///
+/// The active profile is `selected`, or otherwise the one named like the host
+/// (either the full or the short hostname). Without a matching profile, the
+/// configuration is used as it is.
+fn apply_profile(
+    value: &mut serde_json::Value,
+    selected: Option<&str>,
+    hostname: Option<&str>,
+) -> Result<(), String> {
+    let Some(serde_json::Value::Object(mut profiles)) = value
+        .as_object_mut()
+        .and_then(|config| config.remove("profiles"))
+    else {
+        return Err(String::from("\"profiles\" has to be a table"));
+    };
+
+    let profile = match selected {
+        Some(name) => Some(
+            profiles
+                .remove(name)
+                .ok_or_else(|| format!("Profile \"{name}\" not found in the configuration"))?,
+        ),
+        None => hostname.and_then(|hostname| {
+            profiles.remove(hostname).or_else(|| {
+                hostname
+                    .split_once('.')
+                    .and_then(|(short, _domain)| profiles.remove(short))
+            })
+        }),
     };
 
+    if let Some(profile) = profile {
+        if !profile.is_object() {
+            return Err(String::from("A profile has to be a table"));
+        }
+        if profile.get("profiles").is_some() {
+            return Err(String::from("Profiles cannot contain other profiles"));
+        }
+        merge_value(value, profile);
+    }
+
+    Ok(())
+}
+
+/// Reads the configuration at `path`, see [`read_config_content`]
+///
+/// If the configuration contains `profiles`, the active one is applied, see
+/// [`set_profile`].
+pub fn read_config<'a, T>(path: &str) -> Result<T, String>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    let (content, _encrypted) = read_config_content(path)?;
+
+    if let Some(mut value) = parse_value(&content).filter(|value| value.get("profiles").is_some()) {
+        apply_profile(
+            &mut value,
+            PROFILE.get().map(String::as_str),
+            hostname().as_deref(),
+        )
+        .map_err(|error| format!("Error in configuration file \"{path}\": {error}"))?;
+        return serde_json::from_value(value)
+            .map_err(|error| format!("Error parsing configuration file \"{path}\": {error}"));
+    }
+
     let config: T = match toml::from_str(&content) {
         Ok(c) => c,
         Err(_) => match serde_yaml::from_str(&content) {
@@ -329,3 +1294,168 @@ where
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_host_aliases() {
+        let hosts = HostAliases::from([
+            (
+                String::from("work:"),
+                String::from("ssh://git@gitlab.example.com:2222/"),
+            ),
+            (
+                String::from("work:infra/"),
+                String::from("ssh://git@infra.example.com/"),
+            ),
+        ]);
+
+        assert_eq!(
+            expand_url("work:team/repo.git", &hosts),
+            "ssh://git@gitlab.example.com:2222/team/repo.git"
+        );
+        // The longest alias wins
+        assert_eq!(
+            expand_url("work:infra/repo.git", &hosts),
+            "ssh://git@infra.example.com/repo.git"
+        );
+        assert_eq!(
+            expand_url("https://github.com/a/b.git", &hosts),
+            "https://github.com/a/b.git"
+        );
+
+        assert_eq!(
+            shorten_url("ssh://git@gitlab.example.com:2222/team/repo.git", &hosts),
+            "work:team/repo.git"
+        );
+        assert_eq!(
+            shorten_url("ssh://git@infra.example.com/repo.git", &hosts),
+            "work:infra/repo.git"
+        );
+        assert_eq!(
+            shorten_url("ssh://git@gitlab.example.com/team/repo.git", &hosts),
+            "ssh://git@gitlab.example.com/team/repo.git"
+        );
+    }
+
+    #[test]
+    fn check_profiles() {
+        let config = serde_json::json!({
+            "trees": [
+                {"name": "work", "root": "~/work", "repos": [{"name": "a"}]},
+                {"root": "~/private"},
+            ],
+            "fetch": {"prune": true},
+            "profiles": {
+                "work-laptop": {
+                    "trees": [
+                        {"name": "work", "root": "/data/work"},
+                        {"root": "/data/extra"},
+                    ],
+                    "fetch": {"only_configured": true},
+                },
+                "other": {"trees": []},
+            },
+        });
+
+        let mut value = config.clone();
+        apply_profile(&mut value, None, Some("work-laptop.example.com")).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "trees": [
+                    {"name": "work", "root": "/data/work", "repos": [{"name": "a"}]},
+                    {"root": "~/private"},
+                    {"root": "/data/extra"},
+                ],
+                "fetch": {"prune": true, "only_configured": true},
+            })
+        );
+
+        // The explicitly selected profile wins over the hostname
+        let mut value = config.clone();
+        apply_profile(&mut value, Some("other"), Some("work-laptop")).unwrap();
+        assert_eq!(value["trees"].as_array().unwrap().len(), 2);
+        assert!(value.get("profiles").is_none());
+
+        let mut value = config.clone();
+        apply_profile(&mut value, None, Some("desktop")).unwrap();
+        assert_eq!(value["trees"][0]["root"], "~/work");
+
+        let mut value = config;
+        assert!(apply_profile(&mut value, Some("missing"), None).is_err());
+    }
+
+    #[test]
+    fn check_standalone_tree() {
+        let standalone = |path: &str, worktree_setup: bool| StandaloneRepoConfig {
+            path: path.to_string(),
+            worktree_setup,
+            remotes: None,
+            tags: None,
+            default_branch: None,
+        };
+
+        let tree = standalone("/etc/nixos", false).into_tree().unwrap();
+        assert_eq!(tree.root, "/etc");
+        assert_eq!(tree.repos.as_ref().unwrap()[0].name, "nixos");
+        assert_eq!(tree.state_root(), Path::new("/etc/nixos/.git"));
+
+        let tree = standalone("/srv/repo", true).into_tree().unwrap();
+        assert_eq!(
+            tree.state_root(),
+            Path::new("/srv/repo/.git-main-working-tree")
+        );
+
+        assert!(standalone("dotfiles", false).into_tree().is_err());
+        assert!(standalone("/", false).into_tree().is_err());
+    }
+
+    #[test]
+    fn check_namespace_roots() {
+        let config: ConfigProvider = toml::from_str(
+            r#"
+            provider = "gitlab"
+            root = "/work"
+
+            [namespace_roots]
+            "platform" = "/platform"
+            "platform/infra" = "/infra"
+            "*" = "/misc"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.namespace_root(Some("platform")), "/platform");
+        assert_eq!(
+            config.namespace_root(Some("platform/backend")),
+            "/platform/backend"
+        );
+        // The longest namespace wins
+        assert_eq!(config.namespace_root(Some("platform/infra")), "/infra");
+        assert_eq!(
+            config.namespace_root(Some("platform/infra/dns")),
+            "/infra/dns"
+        );
+        // Only whole path components match
+        assert_eq!(
+            config.namespace_root(Some("platform-team")),
+            "/misc/platform-team"
+        );
+        assert_eq!(config.namespace_root(None), "/misc");
+
+        let config: ConfigProvider = toml::from_str(
+            r#"
+            provider = "gitlab"
+            root = "/work"
+            namespace_roots = { "platform" = "/platform" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.namespace_root(Some("other")), "/work/other");
+        assert_eq!(config.namespace_root(None), "/work");
+    }
+}
@@ -0,0 +1,207 @@
+//! Run fetches for a configuration periodically, see `grm daemon`
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::config;
+use super::lock;
+use super::notify;
+use super::output::*;
+use super::path;
+use super::table;
+use super::tree;
+
+pub struct DaemonOptions {
+    pub config_path: String,
+    pub interval: Duration,
+    pub jitter: Duration,
+    pub lock_path: PathBuf,
+    pub report_file: Option<PathBuf>,
+    pub webhook_url: Option<String>,
+    pub once: bool,
+    pub lock_policy: lock::LockPolicy,
+}
+
+/// Parses durations like "30s", "15m", "1h" or "1h30m"
+///
+/// A plain number is interpreted as seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(String::from("Duration is empty"));
+    }
+
+    if let Ok(seconds) = input.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let factor = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 60 * 60 * 24,
+            _ => return Err(format!("Invalid unit \"{c}\" in duration \"{input}\"")),
+        };
+        if number.is_empty() {
+            return Err(format!(
+                "Missing number before \"{c}\" in duration \"{input}\""
+            ));
+        }
+        let value = number
+            .parse::<u64>()
+            .map_err(|error| format!("Invalid duration \"{input}\": {error}"))?;
+        total = value
+            .checked_mul(factor)
+            .and_then(|value| total.checked_add(value))
+            .ok_or(format!("Duration \"{input}\" is too long"))?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(format!("Missing unit at the end of duration \"{input}\""));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Returns a random duration between zero and `max`
+pub fn random_jitter(max: Duration) -> Duration {
+    let max_millis = max.as_millis() as u64;
+    if max_millis == 0 {
+        return Duration::ZERO;
+    }
+    // RandomState is seeded randomly, which is good enough to spread out runs
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % (max_millis + 1))
+}
+
+/// The default lock file, used to make sure that only a single daemon is running
+pub fn default_lock_path() -> PathBuf {
+    let dir = match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::temp_dir(),
+    };
+    dir.join("grm-daemon.lock")
+}
+
+/// Runs a single fetch and, if requested, collects the status of all repositories
+///
+/// Notifications configured in the configuration file are sent as well.
+pub fn run_once(
+    config_path: &str,
+    with_status: bool,
+    lock_policy: lock::LockPolicy,
+) -> Result<notify::Report, String> {
+    let config: config::Config = config::read_config(config_path)?;
+    let notify_config = config.notify().cloned();
+
+    let mut report = match tree::fetch_trees(config, &[], lock_policy) {
+        Ok(result) => notify::Report::new("fetch", result)?,
+        Err(error) => notify::Report::from_error("fetch", error)?,
+    };
+
+    if with_status {
+        // Re-read the configuration, as it is consumed by the fetch
+        let config: config::Config = config::read_config(config_path)?;
+        let (_tables, mut errors, check_failures) =
+            table::get_status_table(config, None, None, None, false, false, false, None)?;
+        report.errors.append(&mut errors);
+        report.check_failures = check_failures;
+    }
+
+    if let Some(notify_config) = notify_config {
+        for error in notify::send(&report, &notify_config) {
+            print_error(&error);
+        }
+    }
+
+    Ok(report)
+}
+
+pub fn run(options: &DaemonOptions) -> Result<(), String> {
+    let _lock = match lock::LockFile::try_acquire(&options.lock_path)? {
+        Some(lock) => lock,
+        None => {
+            return Err(format!(
+                "Another daemon is already running (lock file \"{}\")",
+                path::path_as_string(&options.lock_path)
+            ))
+        }
+    };
+
+    let with_status = options.report_file.is_some() || options.webhook_url.is_some();
+
+    loop {
+        print_action("Starting fetch");
+        match run_once(&options.config_path, with_status, options.lock_policy) {
+            Ok(report) => {
+                if report.success {
+                    print_success("Fetch finished");
+                } else {
+                    print_warning("Fetch finished with errors");
+                }
+                if let Some(report_file) = &options.report_file {
+                    if let Err(error) = notify::write_report_file(&report, report_file) {
+                        print_error(&error);
+                    }
+                }
+                if let Some(webhook_url) = &options.webhook_url {
+                    if let Err(error) = notify::send_webhook(&report, webhook_url) {
+                        print_error(&error);
+                    }
+                }
+            }
+            // A broken configuration may be fixed until the next run, so we keep going
+            Err(error) => print_error(&format!("Fetch failed: {error}")),
+        }
+
+        if options.once {
+            return Ok(());
+        }
+
+        let sleep = options.interval + random_jitter(options.jitter);
+        print_action(&format!("Next fetch in {} seconds", sleep.as_secs()));
+        std::thread::sleep(sleep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse_duration() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(
+            parse_duration("1h30m"),
+            Ok(Duration::from_secs(60 * 60 + 30 * 60))
+        );
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1h30").is_err());
+    }
+
+    #[test]
+    fn check_random_jitter() {
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(random_jitter(Duration::from_secs(10)) <= Duration::from_secs(10));
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Forking projects on a forge, see `grm repos fork`
+//!
+//! The fork is cloned with two remotes: `origin` for the fork and `upstream` for
+//! the forked project. In the end, the new repository is added to the
+//! configuration.
+
+use std::path::{Path, PathBuf};
+
+use super::auth;
+use super::config;
+use super::output::*;
+use super::path;
+use super::provider;
+use super::repo;
+
+pub const UPSTREAM_REMOTE_NAME: &str = "upstream";
+
+/// How often to try cloning the fork before giving up
+const FORK_CLONE_ATTEMPTS: u32 = 7;
+
+/// Seconds to wait before the second attempt, doubled for every further one.
+/// All attempts together wait for about a minute.
+const FORK_CLONE_BACKOFF: u64 = 1;
+
+/// Where and how to fork the project
+pub struct ForkOptions {
+    pub provider: provider::RemoteProvider,
+    pub token_command: String,
+    pub api_url: Option<String>,
+    /// The group (or organization) to fork into, the requesting user if unset
+    pub group: Option<String>,
+    pub force_ssh: bool,
+    /// Let the default branch track `upstream` and push to the fork by default
+    pub track_upstream: bool,
+}
+
+/// Adds the upstream remote to the fresh clone of the fork and fetches it
+///
+/// Failing to fetch or track is not fatal, the fork exists and is cloned already.
+fn add_upstream(
+    repo_handle: &repo::RepoHandle,
+    upstream: &repo::Remote,
+    fork_remote_name: &str,
+    track_upstream: bool,
+    retry: &repo::RetryConfig,
+) -> Result<(), String> {
+    repo_handle.new_remote(&upstream.name, &upstream.url)?;
+
+    if track_upstream {
+        repo_handle.set_push_remote(fork_remote_name)?;
+    }
+
+    if let Err(error) = repo_handle.fetch(&upstream.name, retry, None) {
+        print_warning(&format!("Fetching {} failed: {error}", upstream.name));
+        return Ok(());
+    }
+
+    if track_upstream {
+        let branch_name = repo_handle.head_branch()?.name()?;
+        if let Err(error) = repo_handle
+            .find_local_branch(&branch_name)?
+            .set_upstream(&upstream.name, &branch_name)
+        {
+            print_warning(&format!(
+                "Tracking {}/{branch_name} failed: {error}",
+                upstream.name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Clones the fork, waiting until the forge has finished creating it
+///
+/// Forges like GitHub create forks in the background and answer with "not
+/// found" until they are done, so every failed attempt is retried.
+fn clone_fork(
+    origin: &repo::Remote,
+    repo_path: &Path,
+    retry: &repo::RetryConfig,
+) -> Result<(), String> {
+    let mut delay = std::time::Duration::from_secs(FORK_CLONE_BACKOFF);
+    let mut attempt = 1;
+    loop {
+        match repo::clone_repo(origin, repo_path, false, None, None, None, retry) {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt < FORK_CLONE_ATTEMPTS => {
+                print_warning(&format!(
+                    "Cloning the fork failed: {error}. Retrying in {}s ({attempt}/{})",
+                    delay.as_secs(),
+                    FORK_CLONE_ATTEMPTS - 1
+                ));
+                // A failed clone may leave an empty directory behind
+                if repo_path.exists() {
+                    std::fs::remove_dir_all(repo_path).map_err(|error| {
+                        format!("Failed to remove \"{}\": {error}", repo_path.display())
+                    })?;
+                }
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(error) => return Err(format!("Cloning the fork failed: {error}")),
+        }
+    }
+}
+
+/// Forks the project at `upstream_url`, clones the fork into the tree with the
+/// name or root `tree` (which can be omitted if there is only a single tree) and
+/// adds it to the configuration at `config_path`
+///
+/// The repository is named like the project unless `name` is given. Returns the
+/// path of the new repository.
+pub fn fork_repo(
+    config_path: &str,
+    upstream_url: &str,
+    tree: Option<&str>,
+    name: Option<&str>,
+    options: ForkOptions,
+) -> Result<PathBuf, String> {
+    // Fail before anything is created, on the forge or locally
+    config::check_writable(config_path)?;
+
+    let upstream = repo::Remote {
+        name: String::from(UPSTREAM_REMOTE_NAME),
+        url: upstream_url.to_string(),
+        remote_type: repo::detect_remote_type(upstream_url)?,
+    };
+
+    let project_path = provider::project_path(upstream_url)?;
+    let name = match name {
+        Some(name) => name.to_string(),
+        // The last path component, without any namespace
+        None => project_path
+            .rsplit_once('/')
+            .map_or(project_path.as_str(), |(_, name)| name)
+            .to_string(),
+    };
+    path::check_subpath(&name)?;
+
+    let mut config: config::Config = config::read_config(config_path)?;
+    let retry = config.retry();
+
+    let config::Config::ConfigTrees(ref mut trees) = config else {
+        return Err(String::from(
+            "Forks can only be added to a configuration with a list of trees",
+        ));
+    };
+
+    let hosts = trees.hosts.clone().unwrap_or_default();
+    let tree = trees.tree_mut(tree)?;
+
+    if tree.repos.iter().flatten().any(|repo| repo.name == name) {
+        return Err(format!(
+            "Repository \"{name}\" already exists in the configuration"
+        ));
+    }
+
+    let repo_path = path::expand_path(Path::new(&tree.root)).join(&name);
+    if repo_path.exists() {
+        return Err(format!("\"{}\" already exists", repo_path.display()));
+    }
+    path::check_writable(&repo_path)?;
+
+    let token = auth::get_token_from_command(&options.token_command)
+        .map_err(|error| format!("Getting token from command failed: {error}"))?;
+
+    let fork = provider::fork_repo(
+        &options.provider,
+        token,
+        options.api_url.clone(),
+        upstream_url,
+        options.group.as_deref(),
+        options.force_ssh,
+    )
+    .map_err(|error| format!("Forking the project failed: {error}"))?;
+
+    let origin = fork
+        .remotes
+        .as_ref()
+        .and_then(|remotes| remotes.first())
+        .ok_or_else(|| String::from("The fork does not have a remote"))?
+        .clone();
+
+    print_action(&format!("Forked \"{upstream_url}\" to \"{}\"", origin.url));
+
+    let setup = || -> Result<(), String> {
+        print_action(&repo::clone_message(&origin, &repo_path));
+        clone_fork(&origin, &repo_path, &retry)?;
+
+        let repo_handle = repo::RepoHandle::open(&repo_path, false)
+            .map_err(|error| format!("Opening repository failed: {error}"))?;
+
+        if let Some(provider_id) = &fork.provider_id {
+            repo_handle.set_provider_id(provider_id)?;
+        }
+
+        add_upstream(
+            &repo_handle,
+            &upstream,
+            &origin.name,
+            options.track_upstream,
+            &retry,
+        )
+    };
+
+    setup().map_err(|error| {
+        // Do not leave a half-initialized repository behind. The fork on the
+        // forge stays.
+        if repo_path.exists() {
+            if let Err(cleanup_error) = std::fs::remove_dir_all(&repo_path) {
+                print_warning(&format!(
+                    "Failed to remove \"{}\": {}",
+                    repo_path.display(),
+                    cleanup_error
+                ));
+            }
+        }
+        error
+    })?;
+
+    tree.repos
+        .get_or_insert_with(Vec::new)
+        .push(config::RepoConfig {
+            name,
+            worktree_setup: false,
+            remotes: Some(
+                [origin, upstream]
+                    .into_iter()
+                    .map(|remote| config::RemoteConfig {
+                        url: config::shorten_url(&remote.url, &hosts),
+                        ..config::RemoteConfig::from_remote(remote)
+                    })
+                    .collect(),
+            ),
+            provider_id: fork.provider_id,
+            tags: None,
+            filter: None,
+            default_branch: fork.default_branch,
+            dir: None,
+            remote_name: None,
+        });
+
+    config::write_config(&config, config_path)?;
+
+    Ok(repo_path)
+}
@@ -12,6 +12,41 @@ use clap::Parser;
 pub struct Opts {
     #[clap(subcommand)]
     pub subcmd: SubCommand,
+
+    #[clap(
+        long,
+        global = true,
+        conflicts_with = "no_wait",
+        help = "Wait for other grm processes working on the same trees or repositories [default]"
+    )]
+    pub wait: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Fail instead of waiting for other grm processes working on the same trees or repositories"
+    )]
+    pub no_wait: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Add SSH host keys of unknown hosts to ~/.ssh/known_hosts instead of failing"
+    )]
+    pub accept_new_hostkeys: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Print progress, warnings and errors as JSON lines on stderr"
+    )]
+    pub log_json: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Use this profile of the configuration instead of the one named like the host"
+    )]
+    pub profile: Option<String>,
 }
 
 #[derive(Parser)]
@@ -20,6 +55,18 @@ pub enum SubCommand {
     Repos(Repos),
     #[clap(visible_alias = "wt", about = "Manage worktrees")]
     Worktree(Worktree),
+    #[clap(about = "Fetch configured repositories periodically")]
+    Daemon(DaemonArgs),
+    #[clap(about = "Answer JSON-RPC requests on a unix socket, e.g. for editor plugins")]
+    Serve(ServeArgs),
+    #[clap(about = "Print shell integration code, e.g. eval \"$(grm shell-init bash)\"")]
+    ShellInit(ShellInitArgs),
+    #[clap(about = "Print the cached status of the current repository, for shell prompts")]
+    PromptStatus(PromptStatusArgs),
+    #[clap(name = "config", about = "Inspect configuration files")]
+    ConfigCommand(ConfigCommand),
+    #[clap(about = "Interactively create a configuration for a forge")]
+    Init(InitArgs),
 }
 
 #[derive(Parser)]
@@ -28,6 +75,79 @@ pub struct Repos {
     pub action: ReposAction,
 }
 
+#[derive(Parser)]
+pub struct ConfigCommand {
+    #[clap(subcommand, name = "action")]
+    pub action: ConfigAction,
+}
+
+#[derive(Parser)]
+pub enum ConfigAction {
+    #[clap(about = "Look for tokens and passwords that are written into the configuration")]
+    CheckSecrets(CheckSecretsArgs),
+    #[clap(
+        about = "Show the configuration as grm sees it, with the profile and host aliases applied"
+    )]
+    Show(ConfigShowArgs),
+    #[clap(about = "Print the absolute path of the configuration file")]
+    Path(ConfigPathArgs),
+    #[clap(about = "Open the configuration file in $VISUAL or $EDITOR and check it afterwards")]
+    Edit(ConfigEditArgs),
+}
+
+#[derive(Parser)]
+pub struct ConfigShowArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        help = "Format to produce",
+        default_value_t = ConfigFormat::Toml,
+    )]
+    pub format: ConfigFormat,
+}
+
+#[derive(Parser)]
+pub struct ConfigPathArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+}
+
+#[derive(Parser)]
+pub struct ConfigEditArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+}
+
+#[derive(Parser)]
+pub struct CheckSecretsArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+}
+
 #[derive(Parser)]
 pub enum ReposAction {
     #[clap(subcommand)]
@@ -35,7 +155,214 @@ pub enum ReposAction {
     #[clap(subcommand)]
     Find(FindAction),
     #[clap(about = "Show status of configured repositories")]
-    Status(OptionalConfig),
+    Status(StatusArgs),
+    #[clap(about = "Fetch all remotes of configured repositories")]
+    Fetch(FetchArgs),
+    #[clap(about = "Show statistics about configured repositories")]
+    Stats(StatsArgs),
+    #[clap(about = "Show metrics about configured repositories in the Prometheus format")]
+    Metrics(MetricsArgs),
+    #[clap(about = "Write a report about configured repositories")]
+    Report(ReportArgs),
+    #[clap(about = "Move an existing clone to its configured location and sync it")]
+    Adopt(AdoptArgs),
+    #[clap(about = "Show which configured repository and worktree a path belongs to")]
+    Which(WhichArgs),
+    #[clap(about = "Open a configured repository in the web interface of its forge")]
+    Browse(BrowseArgs),
+    #[clap(about = "Fetch all objects that were left out by a partial clone")]
+    Unfilter(UnfilterArgs),
+    #[clap(about = "Copy the objects shared with another repository and stop sharing them")]
+    Dissociate(DissociateArgs),
+    #[clap(about = "Create and push a first commit in an empty repository")]
+    InitDefaultBranch(InitDefaultBranchArgs),
+    #[clap(subcommand)]
+    Worktree(ReposWorktreeAction),
+    #[clap(about = "Create a new repository from a template and add it to the configuration")]
+    New(NewArgs),
+    #[clap(about = "Fork a project on a forge, clone the fork and add it to the configuration")]
+    Fork(ForkArgs),
+}
+
+#[derive(Parser)]
+pub struct NewArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Name of the new repository")]
+    pub name: String,
+
+    #[clap(long, help = "Path or URL of the template repository")]
+    pub template: String,
+
+    #[clap(
+        long,
+        help = "Name or root of the tree to add the repository to, required if there is more than one"
+    )]
+    pub tree: Option<String>,
+
+    #[clap(
+        long,
+        help = "Replacement for {{author}}, defaults to user.name from git"
+    )]
+    pub author: Option<String>,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        requires = "token_command",
+        help = "Also create the project on this remote provider"
+    )]
+    pub provider: Option<RemoteProvider>,
+
+    #[clap(long, requires = "provider", help = "Command to get API token")]
+    pub token_command: Option<String>,
+
+    #[clap(long, requires = "provider", help = "Base URL for the API")]
+    pub api_url: Option<String>,
+
+    #[clap(
+        long,
+        requires = "provider",
+        help = "Group to create the project in, instead of the own user"
+    )]
+    pub group: Option<String>,
+
+    #[clap(long, requires = "provider", help = "Create a private project")]
+    pub private: bool,
+
+    #[clap(
+        long,
+        requires = "provider",
+        help = "Always use SSH, even for public repositories"
+    )]
+    pub force_ssh: bool,
+
+    #[clap(short, long, requires = "provider", help = "Name of the remote to use")]
+    pub remote_name: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct ForkArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "URL of the project to fork")]
+    pub upstream: String,
+
+    #[clap(
+        long,
+        help = "Name of the repository, defaults to the name of the project"
+    )]
+    pub name: Option<String>,
+
+    #[clap(
+        long,
+        help = "Name or root of the tree to add the repository to, required if there is more than one"
+    )]
+    pub tree: Option<String>,
+
+    #[clap(value_enum, short, long, help = "Remote provider of the project")]
+    pub provider: RemoteProvider,
+
+    #[clap(long, help = "Command to get API token")]
+    pub token_command: String,
+
+    #[clap(long, help = "Base URL for the API")]
+    pub api_url: Option<String>,
+
+    #[clap(long, help = "Group to fork into, instead of the own user")]
+    pub group: Option<String>,
+
+    #[clap(long, help = "Always use SSH for the fork, even if it is public")]
+    pub force_ssh: bool,
+
+    #[clap(
+        long,
+        help = "Let the default branch track upstream and push to the fork by default"
+    )]
+    pub track_upstream: bool,
+}
+
+#[derive(Parser)]
+#[clap(about = "Manage the same worktree in several repositories at once")]
+pub enum ReposWorktreeAction {
+    #[clap(about = "Add a worktree to all matching repositories with a worktree setup")]
+    Add(ReposWorktreeAddArgs),
+    #[clap(about = "Delete a worktree from all matching repositories with a worktree setup")]
+    Delete(ReposWorktreeDeleteArgs),
+}
+
+#[derive(Parser)]
+pub struct ReposWorktreeAddArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Name of the worktree")]
+    pub name: String,
+
+    #[clap(
+        long = "tag",
+        help = "Only use repositories with this tag. Can be given multiple times"
+    )]
+    pub tags: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct ReposWorktreeDeleteArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Name of the worktree")]
+    pub name: String,
+
+    #[clap(
+        long = "tag",
+        help = "Only use repositories with this tag. Can be given multiple times"
+    )]
+    pub tags: Vec<String>,
+
+    #[clap(
+        long = "force",
+        help = "Force deletion, even when there are uncommitted/unpushed changes. Same as all --ignore-* options together"
+    )]
+    pub force: bool,
+
+    #[clap(long, help = "Delete even when there are uncommitted changes")]
+    pub ignore_changes: bool,
+
+    #[clap(
+        long,
+        help = "Delete even when the branch is not merged into any persistent branch"
+    )]
+    pub ignore_unmerged: bool,
+
+    #[clap(
+        long,
+        help = "Delete even when the branch differs from its remote branch or has none"
+    )]
+    pub ignore_upstream_divergence: bool,
 }
 
 #[derive(Parser)]
@@ -44,7 +371,7 @@ pub enum SyncAction {
     #[clap(about = "Synchronize the repositories to the configured values")]
     Config(Config),
     #[clap(about = "Synchronize the repositories from a remote provider")]
-    Remote(SyncRemoteArgs),
+    Remote(Box<SyncRemoteArgs>),
 }
 
 #[derive(Parser)]
@@ -53,7 +380,7 @@ pub enum FindAction {
     #[clap(about = "Find local repositories")]
     Local(FindLocalArgs),
     #[clap(about = "Find repositories on remote provider")]
-    Remote(FindRemoteArgs),
+    Remote(Box<FindRemoteArgs>),
     #[clap(about = "Find repositories as defined in the configuration file")]
     Config(FindConfigArgs),
 }
@@ -71,18 +398,538 @@ pub struct FindLocalArgs {
     )]
     pub exclude: Option<String>,
 
+    #[clap(
+        long,
+        help = "Follow symlinks to directories outside of the search path"
+    )]
+    pub follow_symlinks: bool,
+
+    #[clap(long, help = "List every excluded path instead of a summary")]
+    pub verbose: bool,
+
+    #[clap(long, help = "Skip repositories that are colocated with jujutsu (jj)")]
+    pub skip_jj: bool,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        help = "Format to produce",
+        default_value_t = ConfigFormat::Toml,
+    )]
+    pub format: ConfigFormat,
+}
+
+#[derive(Parser)]
+pub struct FindConfigArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file, or - to read it from stdin"
+    )]
+    pub config: String,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        help = "Format to produce",
+        default_value_t = ConfigFormat::Toml,
+    )]
+    pub format: ConfigFormat,
+}
+
+#[derive(Parser)]
+#[clap()]
+pub struct FindRemoteArgs {
+    #[clap(short, long, help = "Path to the configuration file")]
+    pub config: Option<String>,
+
     #[clap(
         value_enum,
         short,
         long,
-        help = "Format to produce",
-        default_value_t = ConfigFormat::Toml,
+        required_unless_present = "from_snapshot",
+        help = "Remote provider to use"
+    )]
+    pub provider: Option<RemoteProvider>,
+
+    #[clap(short, long, help = "Name of the remote to use")]
+    pub remote_name: Option<String>,
+
+    #[clap(
+        action = clap::ArgAction::Append,
+        name = "user",
+        long,
+        help = "Users to get repositories from"
+    )]
+    pub users: Vec<String>,
+
+    #[clap(
+        action = clap::ArgAction::Append,
+        name = "group",
+        long,
+        help = "Groups to get repositories from (workspaces on Bitbucket, organization/project on Azure DevOps)"
+    )]
+    pub groups: Vec<String>,
+
+    #[clap(
+        action = clap::ArgAction::Append,
+        name = "org",
+        long,
+        help = "GitHub or Azure DevOps organizations to get all visible repositories from"
+    )]
+    pub orgs: Vec<String>,
+
+    #[clap(long, help = "Get repositories that belong to the requesting user")]
+    pub owner: bool,
+
+    #[clap(long, help = "Get repositories that the requesting user has access to")]
+    pub access: bool,
+
+    #[clap(
+        long,
+        help = "Get repositories that the requesting user has starred (GitLab only)"
+    )]
+    pub starred: bool,
+
+    #[clap(
+        value_enum,
+        long,
+        help = "Only get repositories where the requesting user has at least this role (GitLab only)"
+    )]
+    pub min_access_level: Option<AccessLevel>,
+
+    #[clap(long, help = "Always use SSH, even for public repositories")]
+    pub force_ssh: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "force_ssh",
+        help = "Build remote URLs from this template, e.g. \"ssh://git@example.com:2222/{namespace}/{name}.git\""
+    )]
+    pub url_template: Option<String>,
+
+    #[clap(
+        long,
+        required_unless_present = "from_snapshot",
+        help = "Command to get API token"
+    )]
+    pub token_command: Option<String>,
+
+    #[clap(long, help = "Root of the repo tree to produce")]
+    pub root: String,
+
+    #[clap(
+        long,
+        help = "Produce one tree per user, group and organization instead of one per namespace"
+    )]
+    pub tree_per_filter: bool,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        help = "Format to produce",
+        default_value_t = ConfigFormat::Toml,
+    )]
+    pub format: ConfigFormat,
+
+    #[clap(
+        long,
+        help = "Use worktree setup for repositories",
+        value_parser = ["true", "false"],
+        default_value = "false",
+        default_missing_value = "true",
+        num_args = 0..=1,
+    )]
+    pub worktree: String,
+
+    #[clap(long, help = "Base URL for the API")]
+    pub api_url: Option<String>,
+
+    #[clap(long, help = "Number of repositories per API request")]
+    pub per_page: Option<u32>,
+
+    #[clap(long, help = "Maximum number of API requests for listing repositories")]
+    pub max_requests: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Save the list of repositories from the provider to this JSON file"
+    )]
+    pub snapshot: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["provider", "token_command", "snapshot"],
+        help = "Use the list of repositories from this snapshot instead of the provider"
+    )]
+    pub from_snapshot: Option<String>,
+}
+
+#[derive(Parser)]
+#[clap()]
+pub struct Config {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file, or - to read it from stdin"
+    )]
+    pub config: String,
+
+    #[clap(
+        long,
+        value_parser = ["true", "false"],
+        help = "Check out the default worktree after clone",
+        default_value = "true",
+        default_missing_value = "true",
+        num_args = 0..=1,
+    )]
+    pub init_worktree: String,
+
+    #[clap(
+        long,
+        help = "Move repositories that were renamed or transferred on the forge instead of failing"
+    )]
+    pub move_renamed: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value = "adopt",
+        help = "What to do when a directory exists, but does not contain the configured repository"
+    )]
+    pub on_conflict: OnConflict,
+
+    #[clap(
+        long,
+        help = "Sync into roots that were never synced before, even if they contain unrelated files or directories"
+    )]
+    pub allow_existing_root: bool,
+
+    #[clap(
+        long,
+        help = "Print every step as it happens instead of one aligned line per repository"
+    )]
+    pub plain: bool,
+
+    #[clap(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of repositories of a tree to clone or update at the same time"
+    )]
+    pub jobs: u16,
+
+    #[clap(
+        long,
+        value_enum,
+        help = "Switch remotes of known hosts to this protocol, including the ones of existing repositories"
+    )]
+    pub rewrite_protocol: Option<Protocol>,
+
+    #[clap(
+        long,
+        help = "Run the post_clone commands of projects (from .grm.toml in the repository) after cloning"
+    )]
+    pub run_hooks: bool,
+
+    #[clap(
+        long,
+        help = "Print how long the phases and the single repositories took at the end"
+    )]
+    pub timings: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "move_renamed",
+        help = "Only check whether the repositories match the configuration, without changing anything. Exit with an error if they do not"
+    )]
+    pub check: bool,
+
+    #[clap(long, help = "Only sync the tree with this name or root")]
+    pub tree: Option<String>,
+}
+
+#[derive(Parser)]
+#[clap()]
+pub struct FetchArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        long = "remote",
+        help = "Only fetch this remote. Can be given multiple times"
+    )]
+    pub remotes: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Print how long the phases and the single repositories took at the end"
+    )]
+    pub timings: bool,
+}
+
+#[derive(Parser)]
+#[clap()]
+pub struct AdoptArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Path of the repository to adopt")]
+    pub path: String,
+}
+
+#[derive(Parser)]
+pub struct WhichArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Path to look up, defaults to the current directory")]
+    pub path: Option<String>,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        help = "Format to produce",
+        default_value_t = OutputFormat::Table,
+    )]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct BrowseArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        help = "Name of the repository (including its namespace) or a path inside of it, defaults to the current directory"
+    )]
+    pub target: Option<String>,
+
+    #[clap(long, help = "Remote to use, defaults to the first remote")]
+    pub remote: Option<String>,
+
+    #[clap(long, help = "Only print the URL instead of opening it")]
+    pub print: bool,
+}
+
+#[derive(Parser)]
+pub struct UnfilterArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Name of the repository, including its namespace")]
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct DissociateArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Name of the repository, including its namespace")]
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct InitDefaultBranchArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(help = "Name of the repository, including its namespace")]
+    pub name: String,
+
+    #[clap(
+        long,
+        help = "Name of the branch, defaults to the configured default branch or the one HEAD points to"
+    )]
+    pub branch: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct MetricsArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        short,
+        long,
+        help = "Write the metrics to this file instead of stdout, replacing it atomically"
+    )]
+    pub output: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct ReportArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        long,
+        value_name = "DIRECTORY",
+        help = "Write a static HTML report to this directory"
+    )]
+    pub html: String,
+}
+
+#[derive(Parser)]
+#[clap()]
+pub struct StatsArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        long,
+        default_value_t = 10,
+        help = "Number of largest repositories to show"
+    )]
+    pub top: usize,
+
+    #[clap(
+        value_enum,
+        short,
+        long,
+        help = "Format to produce",
+        default_value_t = OutputFormat::Table,
+    )]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct ShellInitArgs {
+    #[clap(value_enum, help = "The shell to print the integration code for")]
+    pub shell: Shell,
+}
+
+#[derive(Parser)]
+pub struct InitArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path of the configuration file to create"
+    )]
+    pub config: String,
+}
+
+#[derive(Parser)]
+pub struct PromptStatusArgs {
+    #[clap(default_value = ".", help = "A path inside the repository or worktree")]
+    pub path: String,
+
+    #[clap(
+        long,
+        help = "Compute the status instead of using the cache, and update the cache"
+    )]
+    pub refresh: bool,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Parser)]
+#[clap()]
+pub struct DaemonArgs {
+    #[clap(
+        short,
+        long,
+        default_value = "./config.toml",
+        help = "Path to the configuration file"
+    )]
+    pub config: String,
+
+    #[clap(
+        short,
+        long,
+        default_value = "30m",
+        help = "Time between two fetches (e.g. \"90s\", \"30m\", \"1h30m\")"
+    )]
+    pub interval: String,
+
+    #[clap(
+        long,
+        help = "Maximum random delay added to each interval [default: a tenth of the interval]"
+    )]
+    pub jitter: Option<String>,
+
+    #[clap(
+        long,
+        help = "Lock file that ensures that only a single daemon is running"
     )]
-    pub format: ConfigFormat,
+    pub lock_file: Option<String>,
+
+    #[clap(long, help = "Write a JSON report to this file after each fetch")]
+    pub report_file: Option<String>,
+
+    #[clap(long, help = "Send a JSON report to this URL after each fetch")]
+    pub webhook_url: Option<String>,
+
+    #[clap(long, help = "Only fetch once and exit")]
+    pub once: bool,
 }
 
 #[derive(Parser)]
-pub struct FindConfigArgs {
+#[clap()]
+pub struct ServeArgs {
     #[clap(
         short,
         long,
@@ -92,21 +939,18 @@ pub struct FindConfigArgs {
     pub config: String,
 
     #[clap(
-        value_enum,
-        short,
         long,
-        help = "Format to produce",
-        default_value_t = ConfigFormat::Toml,
+        help = "Unix socket to listen on [default: $XDG_RUNTIME_DIR/grm.sock]"
     )]
-    pub format: ConfigFormat,
+    pub socket: Option<String>,
 }
 
+pub type RemoteProvider = super::provider::RemoteProvider;
+pub type AccessLevel = super::provider::AccessLevel;
+
 #[derive(Parser)]
 #[clap()]
-pub struct FindRemoteArgs {
-    #[clap(short, long, help = "Path to the configuration file")]
-    pub config: Option<String>,
-
+pub struct SyncRemoteArgs {
     #[clap(value_enum, short, long, help = "Remote provider to use")]
     pub provider: RemoteProvider,
 
@@ -125,19 +969,47 @@ pub struct FindRemoteArgs {
         action = clap::ArgAction::Append,
         name = "group",
         long,
-        help = "Groups to get repositories from"
+        help = "Groups to get repositories from (workspaces on Bitbucket, organization/project on Azure DevOps)"
     )]
     pub groups: Vec<String>,
 
+    #[clap(
+        action = clap::ArgAction::Append,
+        name = "org",
+        long,
+        help = "GitHub or Azure DevOps organizations to get all visible repositories from"
+    )]
+    pub orgs: Vec<String>,
+
     #[clap(long, help = "Get repositories that belong to the requesting user")]
     pub owner: bool,
 
     #[clap(long, help = "Get repositories that the requesting user has access to")]
     pub access: bool,
 
+    #[clap(
+        long,
+        help = "Get repositories that the requesting user has starred (GitLab only)"
+    )]
+    pub starred: bool,
+
+    #[clap(
+        value_enum,
+        long,
+        help = "Only get repositories where the requesting user has at least this role (GitLab only)"
+    )]
+    pub min_access_level: Option<AccessLevel>,
+
     #[clap(long, help = "Always use SSH, even for public repositories")]
     pub force_ssh: bool,
 
+    #[clap(
+        long,
+        conflicts_with = "force_ssh",
+        help = "Build remote URLs from this template, e.g. \"ssh://git@example.com:2222/{namespace}/{name}.git\""
+    )]
+    pub url_template: Option<String>,
+
     #[clap(long, help = "Command to get API token")]
     pub token_command: String,
 
@@ -145,13 +1017,10 @@ pub struct FindRemoteArgs {
     pub root: String,
 
     #[clap(
-        value_enum,
-        short,
         long,
-        help = "Format to produce",
-        default_value_t = ConfigFormat::Toml,
+        help = "Produce one tree per user, group and organization instead of one per namespace"
     )]
-    pub format: ConfigFormat,
+    pub tree_per_filter: bool,
 
     #[clap(
         long,
@@ -165,107 +1034,213 @@ pub struct FindRemoteArgs {
 
     #[clap(long, help = "Base URL for the API")]
     pub api_url: Option<String>,
-}
 
-#[derive(Parser)]
-#[clap()]
-pub struct Config {
-    #[clap(
-        short,
-        long,
-        default_value = "./config.toml",
-        help = "Path to the configuration file"
-    )]
-    pub config: String,
+    #[clap(long, help = "Number of repositories per API request")]
+    pub per_page: Option<u32>,
+
+    #[clap(long, help = "Maximum number of API requests for listing repositories")]
+    pub max_requests: Option<usize>,
 
     #[clap(
         long,
-        value_parser = ["true", "false"],
         help = "Check out the default worktree after clone",
+        value_parser = ["true", "false"],
         default_value = "true",
         default_missing_value = "true",
         num_args = 0..=1,
     )]
     pub init_worktree: String,
-}
 
-pub type RemoteProvider = super::provider::RemoteProvider;
+    #[clap(
+        long,
+        help = "Move repositories that were renamed or transferred on the forge instead of failing"
+    )]
+    pub move_renamed: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value = "adopt",
+        help = "What to do when a directory exists, but does not contain the configured repository"
+    )]
+    pub on_conflict: OnConflict,
+
+    #[clap(
+        long,
+        help = "Sync into roots that were never synced before, even if they contain unrelated files or directories"
+    )]
+    pub allow_existing_root: bool,
+
+    #[clap(
+        long,
+        help = "Print every step as it happens instead of one aligned line per repository"
+    )]
+    pub plain: bool,
+
+    #[clap(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of repositories of a tree to clone or update at the same time"
+    )]
+    pub jobs: u16,
+
+    #[clap(
+        long,
+        value_enum,
+        help = "Switch remotes of known hosts to this protocol, including the ones of existing repositories"
+    )]
+    pub rewrite_protocol: Option<Protocol>,
+
+    #[clap(
+        long,
+        help = "Run the post_clone commands of projects (from .grm.toml in the repository) after cloning"
+    )]
+    pub run_hooks: bool,
+
+    #[clap(
+        long,
+        help = "Print how long the phases and the single repositories took at the end"
+    )]
+    pub timings: bool,
+}
 
 #[derive(Parser)]
 #[clap()]
-pub struct SyncRemoteArgs {
-    #[clap(value_enum, short, long, help = "Remote provider to use")]
-    pub provider: RemoteProvider,
+pub struct StatusArgs {
+    #[clap(short, long, help = "Path to the configuration file")]
+    pub config: Option<String>,
 
-    #[clap(short, long, help = "Name of the remote to use")]
-    pub remote_name: Option<String>,
+    #[clap(
+        long,
+        requires = "config",
+        help = "Print how long the phases and the single repositories took at the end"
+    )]
+    pub timings: bool,
 
     #[clap(
-        action = clap::ArgAction::Append,
-        name = "user",
         long,
-        help = "Users to get repositories from"
+        help = "Exit with an error if any repository has uncommitted changes, unpushed commits, missing remotes or an operation in progress"
     )]
-    pub users: Vec<String>,
+    pub check: bool,
 
     #[clap(
-        action = clap::ArgAction::Append,
-        name = "group",
         long,
-        help = "Groups to get repositories from"
+        value_enum,
+        requires = "config",
+        help = "Sort repositories by name, changed files, unpushed commits or age of the last commit (oldest first)"
     )]
-    pub groups: Vec<String>,
+    pub sort: Option<StatusSort>,
 
-    #[clap(long, help = "Get repositories that belong to the requesting user")]
-    pub owner: bool,
+    #[clap(
+        long,
+        value_enum,
+        requires = "config",
+        help = "Show a separate table for each namespace, remote host or tag"
+    )]
+    pub group_by: Option<StatusGroup>,
 
-    #[clap(long, help = "Get repositories that the requesting user has access to")]
-    pub access: bool,
+    #[clap(
+        long,
+        requires = "config",
+        help = "Only show repositories with a merge, rebase, cherry-pick etc. in progress"
+    )]
+    pub only_in_progress: bool,
 
-    #[clap(long, help = "Always use SSH, even for public repositories")]
-    pub force_ssh: bool,
+    #[clap(
+        long,
+        help = "Report submodules (recursively) whose commit is not on any of their remotes"
+    )]
+    pub submodules: bool,
 
-    #[clap(long, help = "Command to get API token")]
-    pub token_command: String,
+    #[clap(
+        long,
+        requires = "config",
+        help = "Leave out repositories that are colocated with jujutsu (jj)"
+    )]
+    pub skip_jj: bool,
 
-    #[clap(long, help = "Root of the repo tree to produce")]
-    pub root: String,
+    #[clap(
+        long,
+        requires = "config",
+        help = "Only show repositories that were not synced or fetched for this long (e.g. \"7d\", \"12h\")"
+    )]
+    pub stale_after: Option<String>,
 
     #[clap(
         long,
-        help = "Use worktree setup for repositories",
-        value_parser = ["true", "false"],
-        default_value = "false",
-        default_missing_value = "true",
-        num_args = 0..=1,
+        requires = "config",
+        help = "Check that all configured remotes still exist and can be read (needs network access)"
     )]
-    pub worktree: String,
+    pub verify_remotes: bool,
 
-    #[clap(long, help = "Base URL for the API")]
-    pub api_url: Option<String>,
+    #[clap(
+        long,
+        default_value_t = 10,
+        requires = "verify_remotes",
+        help = "Seconds to wait for each remote with --verify-remotes"
+    )]
+    pub verify_timeout: u64,
 
     #[clap(
         long,
-        help = "Check out the default worktree after clone",
-        value_parser = ["true", "false"],
-        default_value = "true",
-        default_missing_value = "true",
-        num_args = 0..=1,
+        requires = "config",
+        help = "Only show the tree with this name or root"
     )]
-    pub init_worktree: String,
+    pub tree: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["group_by", "timings", "verify_remotes"],
+        help = "Print the status as JSON instead of tables"
+    )]
+    pub json: bool,
 }
 
-#[derive(Parser)]
-#[clap()]
-pub struct OptionalConfig {
-    #[clap(short, long, help = "Path to the configuration file")]
-    pub config: Option<String>,
+#[derive(clap::ValueEnum, Clone)]
+pub enum OnConflict {
+    /// Use the existing repository and rewrite its remotes
+    Adopt,
+    /// Move the directory to <name>.bak and clone the repository
+    MoveAside,
+    /// Leave the directory alone
+    Skip,
+    /// Ask for every conflict
+    Ask,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+pub enum Protocol {
+    Ssh,
+    Https,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+pub enum StatusSort {
+    Name,
+    Dirty,
+    Ahead,
+    Age,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+pub enum StatusGroup {
+    Namespace,
+    Host,
+    Tag,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+pub enum OutputFormat {
+    Table,
+    Json,
 }
 
 #[derive(clap::ValueEnum, Clone)]
 pub enum ConfigFormat {
     Yaml,
     Toml,
+    Json,
 }
 
 #[derive(Parser)]
@@ -292,18 +1267,77 @@ pub enum WorktreeAction {
     Pull(WorktreePullArgs),
     #[clap(about = "Rebase worktree onto default branch")]
     Rebase(WorktreeRebaseArgs),
+    #[clap(about = "Fetch, pull, rebase and clean up all worktrees in one go")]
+    Sync(WorktreeSyncArgs),
+    #[clap(about = "Change the remote branch that a worktree tracks")]
+    Retrack(WorktreeRetrackArgs),
+    #[clap(about = "Move uncommitted changes from one worktree to another")]
+    MoveChanges(WorktreeMoveChangesArgs),
+    #[clap(about = "Open a worktree with the configured open_command")]
+    Open(WorktreeOpenArgs),
+    #[clap(about = "Open the branch of a worktree in the web interface of the forge")]
+    Browse(WorktreeBrowseArgs),
+    #[clap(about = "List remote branches that do not have a worktree yet")]
+    Branches(WorktreeBranchesArgs),
+    #[clap(about = "Save the current set of worktrees as a profile")]
+    SaveProfile(WorktreeProfileArgs),
+    #[clap(about = "Create the worktrees of a saved profile")]
+    RestoreProfile(WorktreeProfileArgs),
+    #[clap(about = "Repair the links between the worktrees after moving the worktree setup")]
+    Repair,
+}
+
+#[derive(Parser)]
+pub struct WorktreeBranchesArgs {
+    #[clap(
+        long = "create",
+        value_name = "BRANCH",
+        help = "Create a worktree tracking the remote branch, given as <branch> or <remote>/<branch>"
+    )]
+    pub create: Option<String>,
 }
 
 #[derive(Parser)]
 pub struct WorktreeAddArgs {
-    #[clap(help = "Name of the worktree")]
-    pub name: String,
+    #[clap(
+        required_unless_present = "from_file",
+        conflicts_with = "from_file",
+        help = "Name of the worktree"
+    )]
+    pub name: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["track", "and_cd", "open", "apply"],
+        help = "Create a worktree for each name in this file (one per line, \"-\" for stdin)"
+    )]
+    pub from_file: Option<String>,
 
     #[clap(short = 't', long = "track", help = "Remote branch to track")]
     pub track: Option<String>,
 
     #[clap(long = "no-track", help = "Disable tracking")]
     pub no_track: bool,
+
+    #[clap(
+        long = "and-cd",
+        help = "Change into the new worktree afterwards (requires shell integration, see grm shell-init)"
+    )]
+    pub and_cd: bool,
+
+    #[clap(
+        long = "open",
+        help = "Open the new worktree with the open_command from grm.toml afterwards"
+    )]
+    pub open: bool,
+
+    #[clap(
+        long,
+        value_name = "PATCH|STASH",
+        help = "Apply a patch file or a stash (e.g. stash@{0}) to the new worktree"
+    )]
+    pub apply: Option<String>,
 }
 #[derive(Parser)]
 pub struct WorktreeDeleteArgs {
@@ -312,22 +1346,103 @@ pub struct WorktreeDeleteArgs {
 
     #[clap(
         long = "force",
-        help = "Force deletion, even when there are uncommitted/unpushed changes"
+        help = "Force deletion, even when there are uncommitted/unpushed changes. Same as all --ignore-* options together"
     )]
     pub force: bool,
+
+    #[clap(long, help = "Delete even when there are uncommitted changes")]
+    pub ignore_changes: bool,
+
+    #[clap(
+        long,
+        help = "Delete even when the branch is not merged into any persistent branch"
+    )]
+    pub ignore_unmerged: bool,
+
+    #[clap(
+        long,
+        help = "Delete even when the branch differs from its remote branch or has none"
+    )]
+    pub ignore_upstream_divergence: bool,
+}
+
+#[derive(Parser)]
+pub struct WorktreeStatusArgs {
+    #[clap(long, help = "Show the disk usage of each worktree")]
+    pub disk_usage: bool,
+
+    #[clap(long, help = "Print the status as JSON instead of a table")]
+    pub json: bool,
+}
+
+#[derive(Parser)]
+pub struct WorktreeRetrackArgs {
+    #[clap(help = "Name of the worktree")]
+    pub name: String,
+
+    #[clap(help = "Remote branch to track, as <remote>/<branch_name>")]
+    pub track: String,
+
+    #[clap(long = "push", help = "Create the remote branch if it does not exist")]
+    pub push: bool,
+}
+
+#[derive(Parser)]
+pub struct WorktreeMoveChangesArgs {
+    #[clap(help = "Worktree to take the changes from")]
+    pub from: String,
+
+    #[clap(help = "Worktree to move the changes to")]
+    pub to: String,
+}
+
+#[derive(Parser)]
+pub struct WorktreeOpenArgs {
+    #[clap(help = "Name of the worktree")]
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct WorktreeBrowseArgs {
+    #[clap(help = "Name of the worktree, defaults to the repository itself")]
+    pub name: Option<String>,
+
+    #[clap(
+        long,
+        help = "Remote to use, defaults to the upstream of the worktree or track.default_remote"
+    )]
+    pub remote: Option<String>,
+
+    #[clap(long, help = "Only print the URL instead of opening it")]
+    pub print: bool,
 }
 
 #[derive(Parser)]
-pub struct WorktreeStatusArgs {}
+pub struct WorktreeProfileArgs {
+    #[clap(help = "Name of the profile")]
+    pub name: String,
+}
 
 #[derive(Parser)]
 pub struct WorktreeConvertArgs {}
 
 #[derive(Parser)]
-pub struct WorktreeCleanArgs {}
+pub struct WorktreeCleanArgs {
+    #[clap(
+        long,
+        help = "Do not remove anything, but rank worktrees by how worthwhile removing them is"
+    )]
+    pub suggest: bool,
+}
 
 #[derive(Parser)]
-pub struct WorktreeFetchArgs {}
+pub struct WorktreeFetchArgs {
+    #[clap(
+        long = "remote",
+        help = "Only fetch this remote. Can be given multiple times"
+    )]
+    pub remotes: Vec<String>,
+}
 
 #[derive(Parser)]
 pub struct WorktreePullArgs {
@@ -335,6 +1450,11 @@ pub struct WorktreePullArgs {
     pub rebase: bool,
     #[clap(long = "stash", help = "Stash & unstash changes before & after pull")]
     pub stash: bool,
+    #[clap(
+        long = "remote",
+        help = "Only fetch this remote. Can be given multiple times"
+    )]
+    pub remotes: Vec<String>,
 }
 
 #[derive(Parser)]
@@ -345,6 +1465,26 @@ pub struct WorktreeRebaseArgs {
     pub rebase: bool,
     #[clap(long = "stash", help = "Stash & unstash changes before & after rebase")]
     pub stash: bool,
+    #[clap(
+        long = "interactive-list",
+        help = "Show what will be rebased and ask for confirmation first"
+    )]
+    pub interactive_list: bool,
+    #[clap(
+        long = "yes",
+        requires = "interactive_list",
+        help = "Rebase without asking for confirmation"
+    )]
+    pub yes: bool,
+}
+
+#[derive(Parser)]
+pub struct WorktreeSyncArgs {
+    #[clap(
+        long = "stash",
+        help = "Stash & unstash changes before & after pull and rebase"
+    )]
+    pub stash: bool,
 }
 
 pub fn parse() -> Opts {
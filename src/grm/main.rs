@@ -1,69 +1,162 @@
 #![forbid(unsafe_code)]
 
-use std::path::Path;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::{Duration, Instant};
 
 mod cmd;
 
 use grm::auth;
+use grm::browse;
 use grm::config;
+use grm::daemon;
 use grm::find_in_tree;
+use grm::fork;
+use grm::init;
+use grm::known_hosts;
+use grm::lock;
+use grm::metrics;
+use grm::notify;
 use grm::output::*;
 use grm::path;
+use grm::profile;
+use grm::prompt;
 use grm::provider;
 use grm::provider::Provider;
 use grm::repo;
+use grm::report;
+use grm::secrets;
+use grm::serve;
+use grm::shell;
+use grm::skipped_as_toml;
+use grm::skipped_as_yaml;
+use grm::skipped_message;
+use grm::stats;
 use grm::table;
+use grm::template;
+use grm::timings;
 use grm::tree;
 use grm::worktree;
 
 fn main() {
     let opts = cmd::parse();
 
+    let lock_policy = if opts.no_wait {
+        lock::LockPolicy::NoWait
+    } else {
+        lock::LockPolicy::Wait
+    };
+
+    known_hosts::set_accept_new_host_keys(opts.accept_new_hostkeys);
+    set_log_json(opts.log_json);
+    config::set_profile(opts.profile);
+
     match opts.subcmd {
-        cmd::SubCommand::Repos(repos) => match repos.action {
-            cmd::ReposAction::Sync(sync) => match sync {
-                cmd::SyncAction::Config(args) => {
-                    let config = match config::read_config(&args.config) {
-                        Ok(config) => config,
-                        Err(error) => {
-                            print_error(&error);
-                            process::exit(1);
+        cmd::SubCommand::Repos(repos) => {
+            match repos.action {
+                cmd::ReposAction::Sync(sync) => match sync {
+                    cmd::SyncAction::Config(args) => {
+                        let start = Instant::now();
+                        if args.timings {
+                            timings::enable();
                         }
-                    };
-                    match tree::sync_trees(config, args.init_worktree == "true") {
-                        Ok(success) => {
+                        let config: config::Config = match config::read_config(&args.config) {
+                            Ok(config) => config,
+                            Err(error) => {
+                                print_error(&error);
+                                process::exit(1);
+                            }
+                        };
+                        if args.check {
+                            let success = match tree::check_trees(
+                                config,
+                                args.tree.as_deref(),
+                                args.rewrite_protocol.as_ref().map(protocol),
+                            ) {
+                                Ok(result) => result.success(),
+                                Err(error) => {
+                                    print_error(&format!("Check error: {error}"));
+                                    false
+                                }
+                            };
                             if !success {
-                                process::exit(1)
+                                print_error("Repositories do not match the configuration");
+                                process::exit(1);
                             }
+                            print_success("All repositories match the configuration");
+                            return;
                         }
-                        Err(error) => {
-                            print_error(&format!("Sync error: {error}"));
+                        let notify_config = config.notify().cloned();
+                        let result = tree::sync_trees(
+                            config,
+                            &tree::SyncOptions {
+                                tree: args.tree.clone(),
+                                init_worktree: args.init_worktree == "true",
+                                move_renamed: args.move_renamed,
+                                on_conflict: on_conflict(&args.on_conflict),
+                                rewrite_protocol: args.rewrite_protocol.as_ref().map(protocol),
+                                lock_policy,
+                                run_hooks: args.run_hooks,
+                                existing_root: existing_root(args.allow_existing_root),
+                                plain: args.plain,
+                                jobs: usize::from(args.jobs),
+                            },
+                        );
+                        let success = match &result {
+                            Ok(result) => result.success(),
+                            Err(error) => {
+                                print_error(&format!("Sync error: {error}"));
+                                false
+                            }
+                        };
+                        send_notifications(notify_config.as_ref(), "sync", result);
+                        if args.timings {
+                            print_timings(start);
+                        }
+                        if !success {
                             process::exit(1);
                         }
                     }
-                }
-                cmd::SyncAction::Remote(args) => {
-                    let token = match auth::get_token_from_command(&args.token_command) {
-                        Ok(token) => token,
-                        Err(error) => {
-                            print_error(&format!("Getting token from command failed: {error}"));
-                            process::exit(1);
+                    cmd::SyncAction::Remote(args) => {
+                        let start = Instant::now();
+                        if args.timings {
+                            timings::enable();
                         }
-                    };
+                        let token = match auth::get_token_from_command(&args.token_command) {
+                            Ok(token) => token,
+                            Err(error) => {
+                                print_error(&format!("Getting token from command failed: {error}"));
+                                process::exit(1);
+                            }
+                        };
 
-                    let filter =
-                        provider::Filter::new(args.users, args.groups, args.owner, args.access);
+                        let filter = provider::Filter::new(
+                            args.users,
+                            args.groups,
+                            args.orgs,
+                            args.owner,
+                            args.access,
+                            args.starred,
+                            args.min_access_level,
+                        );
 
-                    if filter.empty() {
-                        print_warning("You did not specify any filters, so no repos will match");
-                    }
+                        if filter.empty() {
+                            print_warning(
+                                "You did not specify any filters, so no repos will match",
+                            );
+                        }
 
-                    let worktree = args.worktree == "true";
+                        let worktree = args.worktree == "true";
+                        let grouping = args.tree_per_filter.then(|| filter.clone());
 
-                    let repos = match args.provider {
-                        cmd::RemoteProvider::Github => {
-                            match provider::Github::new(filter, token, args.api_url) {
+                        let repos = match args.provider {
+                            cmd::RemoteProvider::Github => match provider::Github::new(
+                                filter,
+                                token,
+                                args.api_url,
+                                provider::Pagination::new(args.per_page, args.max_requests),
+                            ) {
                                 Ok(provider) => provider,
                                 Err(error) => {
                                     print_error(&format!("Sync error: {error}"));
@@ -74,10 +167,14 @@ fn main() {
                                 worktree,
                                 args.force_ssh,
                                 args.remote_name,
-                            )
-                        }
-                        cmd::RemoteProvider::Gitlab => {
-                            match provider::Gitlab::new(filter, token, args.api_url) {
+                                args.url_template.as_deref(),
+                            ),
+                            cmd::RemoteProvider::Gitlab => match provider::Gitlab::new(
+                                filter,
+                                token,
+                                args.api_url,
+                                provider::Pagination::new(args.per_page, args.max_requests),
+                            ) {
                                 Ok(provider) => provider,
                                 Err(error) => {
                                     print_error(&format!("Sync error: {error}"));
@@ -88,526 +185,1474 @@ fn main() {
                                 worktree,
                                 args.force_ssh,
                                 args.remote_name,
-                            )
-                        }
-                    };
-
-                    match repos {
-                        Ok(repos) => {
-                            let mut trees: Vec<config::ConfigTree> = vec![];
+                                args.url_template.as_deref(),
+                            ),
+                            cmd::RemoteProvider::Bitbucket => match provider::Bitbucket::new(
+                                filter,
+                                token,
+                                args.api_url,
+                                provider::Pagination::new(args.per_page, args.max_requests),
+                            ) {
+                                Ok(provider) => provider,
+                                Err(error) => {
+                                    print_error(&format!("Sync error: {error}"));
+                                    process::exit(1);
+                                }
+                            }
+                            .get_repos(
+                                worktree,
+                                args.force_ssh,
+                                args.remote_name,
+                                args.url_template.as_deref(),
+                            ),
+                            cmd::RemoteProvider::AzureDevops => match provider::AzureDevops::new(
+                                filter,
+                                token,
+                                args.api_url,
+                                provider::Pagination::new(args.per_page, args.max_requests),
+                            ) {
+                                Ok(provider) => provider,
+                                Err(error) => {
+                                    print_error(&format!("Sync error: {error}"));
+                                    process::exit(1);
+                                }
+                            }
+                            .get_repos(
+                                worktree,
+                                args.force_ssh,
+                                args.remote_name,
+                                args.url_template.as_deref(),
+                            ),
+                        };
 
-                            for (namespace, repolist) in repos {
-                                let root = if let Some(namespace) = namespace {
-                                    path::path_as_string(&Path::new(&args.root).join(namespace))
-                                } else {
-                                    path::path_as_string(Path::new(&args.root))
+                        match repos {
+                            Ok(repos) => {
+                                let repos = match &grouping {
+                                    Some(filter) => filter.group_by_entry(repos),
+                                    None => repos,
                                 };
+                                let mut trees: Vec<config::ConfigTree> = vec![];
 
-                                let tree = config::ConfigTree::from_repos(root, repolist);
-                                trees.push(tree);
-                            }
+                                for (namespace, repolist) in repos {
+                                    let root = if let Some(namespace) = namespace {
+                                        path::path_as_string(&Path::new(&args.root).join(namespace))
+                                    } else {
+                                        path::path_as_string(Path::new(&args.root))
+                                    };
+
+                                    let tree = config::ConfigTree::from_repos(root, repolist);
+                                    trees.push(tree);
+                                }
 
-                            let config = config::Config::from_trees(trees);
+                                let config = config::Config::from_trees(trees);
 
-                            match tree::sync_trees(config, args.init_worktree == "true") {
-                                Ok(success) => {
-                                    if !success {
-                                        process::exit(1)
+                                let result = tree::sync_trees(
+                                    config,
+                                    &tree::SyncOptions {
+                                        tree: None,
+                                        init_worktree: args.init_worktree == "true",
+                                        move_renamed: args.move_renamed,
+                                        on_conflict: on_conflict(&args.on_conflict),
+                                        rewrite_protocol: args
+                                            .rewrite_protocol
+                                            .as_ref()
+                                            .map(protocol),
+                                        lock_policy,
+                                        run_hooks: args.run_hooks,
+                                        existing_root: existing_root(args.allow_existing_root),
+                                        plain: args.plain,
+                                        jobs: usize::from(args.jobs),
+                                    },
+                                );
+                                if args.timings {
+                                    print_timings(start);
+                                }
+                                match result {
+                                    Ok(result) => {
+                                        if !result.success() {
+                                            process::exit(1)
+                                        }
+                                    }
+                                    Err(error) => {
+                                        print_error(&format!("Sync error: {error}"));
+                                        process::exit(1);
+                                    }
+                                }
+                            }
+                            Err(error) => {
+                                print_error(&format!("Sync error: {error}"));
+                                process::exit(1);
+                            }
+                        }
+                    }
+                },
+                cmd::ReposAction::Status(args) => match &args.config {
+                    Some(config_path) => {
+                        let start = Instant::now();
+                        if args.timings {
+                            timings::enable();
+                        }
+                        let config = match config::read_config(config_path) {
+                            Ok(config) => config,
+                            Err(error) => {
+                                print_error(&error);
+                                process::exit(1);
+                            }
+                        };
+                        let stale_after = args.stale_after.as_ref().map(|stale_after| {
+                            daemon::parse_duration(stale_after).unwrap_or_else(|error| {
+                                print_error(&format!("Invalid duration: {error}"));
+                                process::exit(1);
+                            })
+                        });
+                        let sort = args.sort.as_ref().map(|sort| match sort {
+                            cmd::StatusSort::Name => table::StatusSort::Name,
+                            cmd::StatusSort::Dirty => table::StatusSort::Dirty,
+                            cmd::StatusSort::Ahead => table::StatusSort::Ahead,
+                            cmd::StatusSort::Age => table::StatusSort::Age,
+                        });
+                        let group_by = args.group_by.as_ref().map(|group_by| match group_by {
+                            cmd::StatusGroup::Namespace => table::StatusGroup::Namespace,
+                            cmd::StatusGroup::Host => table::StatusGroup::Host,
+                            cmd::StatusGroup::Tag => table::StatusGroup::Tag,
+                        });
+                        if args.json {
+                            match table::get_status_json(
+                                config,
+                                args.tree.as_deref(),
+                                sort,
+                                args.only_in_progress,
+                                args.submodules,
+                                args.skip_jj,
+                                stale_after,
+                            ) {
+                                Ok((json, errors, check_failures)) => {
+                                    println!("{json}");
+                                    for error in &errors {
+                                        print_error(&format!("Error: {error}"));
+                                    }
+                                    if args.check
+                                        && (!errors.is_empty() || !check_failures.is_empty())
+                                    {
+                                        process::exit(1);
                                     }
                                 }
                                 Err(error) => {
-                                    print_error(&format!("Sync error: {error}"));
+                                    print_error(&format!("Error getting status: {error}"));
+                                    process::exit(1);
+                                }
+                            }
+                            return;
+                        }
+                        match table::get_status_table(
+                            config,
+                            args.tree.as_deref(),
+                            sort,
+                            group_by,
+                            args.only_in_progress,
+                            args.submodules,
+                            args.skip_jj,
+                            stale_after,
+                        ) {
+                            Ok((tables, errors, check_failures)) => {
+                                if args.only_in_progress && tables.is_empty() {
+                                    print_success("No repository has an operation in progress");
+                                } else if stale_after.is_some() && tables.is_empty() {
+                                    print_success("No repository is stale");
+                                }
+                                for (title, table) in tables {
+                                    if let Some(title) = title {
+                                        println!("{title}");
+                                    }
+                                    println!("{table}");
+                                }
+                                for error in &errors {
+                                    print_error(&format!("Error: {error}"));
+                                }
+                                if args.timings {
+                                    print_timings(start);
+                                }
+                                let mut dead_remotes = false;
+                                if args.verify_remotes {
+                                    dead_remotes = !verify_remotes(
+                                        config_path,
+                                        args.tree.as_deref(),
+                                        Duration::from_secs(args.verify_timeout),
+                                    );
+                                }
+                                if args.check {
+                                    for failure in &check_failures {
+                                        print_warning(failure);
+                                    }
+                                    if !errors.is_empty()
+                                        || !check_failures.is_empty()
+                                        || dead_remotes
+                                    {
+                                        process::exit(1);
+                                    }
+                                }
+                            }
+                            Err(error) => {
+                                print_error(&format!("Error getting status: {error}"));
+                                process::exit(1);
+                            }
+                        }
+                    }
+                    None => {
+                        let dir = match std::env::current_dir() {
+                            Ok(dir) => dir,
+                            Err(error) => {
+                                print_error(&format!("Could not open current directory: {error}"));
+                                process::exit(1);
+                            }
+                        };
+
+                        if args.json {
+                            match table::get_single_repo_status_json(&dir, args.submodules) {
+                                Ok((json, warnings, check_failures)) => {
+                                    println!("{json}");
+                                    for warning in warnings {
+                                        print_warning(&warning);
+                                    }
+                                    if args.check && !check_failures.is_empty() {
+                                        process::exit(1);
+                                    }
+                                }
+                                Err(error) => {
+                                    print_error(&format!("Error getting status: {error}"));
                                     process::exit(1);
                                 }
                             }
+                            return;
+                        }
+
+                        match table::show_single_repo_status(&dir, args.submodules) {
+                            Ok((tables, warnings, check_failures)) => {
+                                for table in tables {
+                                    println!("{table}");
+                                }
+                                for warning in warnings {
+                                    print_warning(&warning);
+                                }
+                                if args.check {
+                                    for failure in &check_failures {
+                                        print_warning(failure);
+                                    }
+                                    if !check_failures.is_empty() {
+                                        process::exit(1);
+                                    }
+                                }
+                            }
+                            Err(error) => {
+                                print_error(&format!("Error getting status: {error}"));
+                                process::exit(1);
+                            }
                         }
+                    }
+                },
+                cmd::ReposAction::Fetch(args) => {
+                    let start = Instant::now();
+                    if args.timings {
+                        timings::enable();
+                    }
+                    let config: config::Config = match config::read_config(&args.config) {
+                        Ok(config) => config,
                         Err(error) => {
-                            print_error(&format!("Sync error: {error}"));
+                            print_error(&error);
                             process::exit(1);
                         }
+                    };
+                    let notify_config = config.notify().cloned();
+                    let result = tree::fetch_trees(config, &args.remotes, lock_policy);
+                    let success = match &result {
+                        Ok(result) => result.success(),
+                        Err(error) => {
+                            print_error(&format!("Fetch error: {error}"));
+                            false
+                        }
+                    };
+                    send_notifications(notify_config.as_ref(), "fetch", result);
+                    if args.timings {
+                        print_timings(start);
+                    }
+                    if !success {
+                        process::exit(1);
                     }
                 }
-            },
-            cmd::ReposAction::Status(args) => match &args.config {
-                Some(config_path) => {
-                    let config = match config::read_config(config_path) {
+                cmd::ReposAction::Worktree(action) => {
+                    let config_path = match &action {
+                        cmd::ReposWorktreeAction::Add(args) => &args.config,
+                        cmd::ReposWorktreeAction::Delete(args) => &args.config,
+                    };
+                    let config: config::Config = match config::read_config(config_path) {
                         Ok(config) => config,
                         Err(error) => {
                             print_error(&error);
                             process::exit(1);
                         }
                     };
-                    match table::get_status_table(config) {
-                        Ok((tables, errors)) => {
-                            for table in tables {
-                                println!("{table}");
-                            }
-                            for error in errors {
-                                print_error(&format!("Error: {error}"));
+                    let result = match &action {
+                        cmd::ReposWorktreeAction::Add(args) => {
+                            tree::add_worktrees(config, &args.name, &args.tags, lock_policy)
+                        }
+                        cmd::ReposWorktreeAction::Delete(args) => tree::delete_worktrees(
+                            config,
+                            &args.name,
+                            &args.tags,
+                            remove_overrides(
+                                args.force,
+                                args.ignore_changes,
+                                args.ignore_unmerged,
+                                args.ignore_upstream_divergence,
+                            ),
+                            lock_policy,
+                        ),
+                    };
+                    match result {
+                        Ok(result) => {
+                            if !result.success() {
+                                process::exit(1);
                             }
                         }
                         Err(error) => {
-                            print_error(&format!("Error getting status: {error}"));
+                            print_error(&format!("Error: {error}"));
                             process::exit(1);
                         }
                     }
                 }
-                None => {
-                    let dir = match std::env::current_dir() {
-                        Ok(dir) => dir,
+                cmd::ReposAction::New(args) => {
+                    let forge = args.provider.map(|provider| template::ForgeProject {
+                        provider,
+                        // clap makes sure that the token command is given
+                        token_command: args.token_command.clone().unwrap_or_default(),
+                        api_url: args.api_url.clone(),
+                        group: args.group.clone(),
+                        private: args.private,
+                        force_ssh: args.force_ssh,
+                        remote_name: args.remote_name.clone(),
+                    });
+                    match template::new_repo(
+                        &args.config,
+                        &args.name,
+                        &args.template,
+                        args.tree.as_deref(),
+                        args.author,
+                        forge,
+                    ) {
+                        Ok(path) => print_success(&format!(
+                            "Created repository at \"{}\"",
+                            path::path_as_string(&path)
+                        )),
                         Err(error) => {
-                            print_error(&format!("Could not open current directory: {error}"));
+                            print_error(&format!("Error creating repository: {error}"));
                             process::exit(1);
                         }
+                    }
+                }
+                cmd::ReposAction::Fork(args) => {
+                    let options = fork::ForkOptions {
+                        provider: args.provider,
+                        token_command: args.token_command,
+                        api_url: args.api_url,
+                        group: args.group,
+                        force_ssh: args.force_ssh,
+                        track_upstream: args.track_upstream,
                     };
-
-                    match table::show_single_repo_status(&dir) {
-                        Ok((table, warnings)) => {
-                            println!("{table}");
-                            for warning in warnings {
-                                print_warning(&warning);
-                            }
-                        }
+                    match fork::fork_repo(
+                        &args.config,
+                        &args.upstream,
+                        args.tree.as_deref(),
+                        args.name.as_deref(),
+                        options,
+                    ) {
+                        Ok(path) => print_success(&format!(
+                            "Cloned fork to \"{}\"",
+                            path::path_as_string(&path)
+                        )),
                         Err(error) => {
-                            print_error(&format!("Error getting status: {error}"));
+                            print_error(&format!("Error forking repository: {error}"));
                             process::exit(1);
                         }
                     }
                 }
-            },
-            cmd::ReposAction::Find(find) => match find {
-                cmd::FindAction::Local(args) => {
-                    let path = Path::new(&args.path);
-                    if !path.exists() {
-                        print_error(&format!("Path \"{}\" does not exist", path.display()));
-                        process::exit(1);
-                    }
-                    if !path.is_dir() {
-                        print_error(&format!("Path \"{}\" is not a directory", path.display()));
-                        process::exit(1);
-                    }
-
-                    let path = match path.canonicalize() {
-                        Ok(path) => path,
+                cmd::ReposAction::Adopt(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
                         Err(error) => {
-                            print_error(&format!(
-                                    "Failed to canonicalize path \"{}\". This is a bug. Error message: {}",
-                                    &path.display(),
-                                    error
-                                ));
+                            print_error(&error);
                             process::exit(1);
                         }
                     };
-
-                    let (found_repos, warnings) = match find_in_tree(&path, args.exclude.as_deref())
-                    {
-                        Ok((repos, warnings)) => (repos, warnings),
+                    match tree::adopt_repo(config, Path::new(&args.path), lock_policy) {
+                        Ok(path) => print_success(&format!(
+                            "Adopted repository at \"{}\"",
+                            path::path_as_string(&path)
+                        )),
+                        Err(error) => {
+                            print_error(&format!("Error adopting repository: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::ReposAction::Which(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
                         Err(error) => {
                             print_error(&error);
                             process::exit(1);
                         }
                     };
-
-                    let trees = config::ConfigTrees::from_trees(vec![found_repos]);
-                    if trees.trees_ref().iter().all(|t| match &t.repos {
-                        None => false,
-                        Some(r) => r.is_empty(),
-                    }) {
-                        print_warning("No repositories found");
-                    } else {
-                        let mut config = trees.to_config();
-
-                        config.normalize();
-
-                        match args.format {
-                            cmd::ConfigFormat::Toml => {
-                                let toml = match config.as_toml() {
-                                    Ok(toml) => toml,
-                                    Err(error) => {
-                                        print_error(&format!(
-                                            "Failed converting config to TOML: {}",
-                                            &error
-                                        ));
-                                        process::exit(1);
-                                    }
-                                };
-                                print!("{toml}");
+                    let path = match &args.path {
+                        Some(path) => PathBuf::from(path),
+                        None => match std::env::current_dir() {
+                            Ok(cwd) => cwd,
+                            Err(error) => {
+                                print_error(&format!("Could not open current directory: {error}"));
+                                process::exit(1);
                             }
-                            cmd::ConfigFormat::Yaml => {
-                                let yaml = match config.as_yaml() {
-                                    Ok(yaml) => yaml,
+                        },
+                    };
+                    match tree::find_location(config, &args.config, &path) {
+                        Ok(Some(location)) => match args.format {
+                            cmd::OutputFormat::Table => {
+                                println!("{}", table::get_location_table(&location));
+                            }
+                            cmd::OutputFormat::Json => {
+                                match serde_json::to_string_pretty(&location) {
+                                    Ok(json) => println!("{json}"),
                                     Err(error) => {
                                         print_error(&format!(
-                                            "Failed converting config to YAML: {}",
-                                            &error
+                                            "Failed converting location to JSON: {error}"
                                         ));
                                         process::exit(1);
                                     }
-                                };
-                                print!("{yaml}");
+                                }
                             }
+                        },
+                        Ok(None) => {
+                            print_error(&format!(
+                                "\"{}\" is not part of any configured tree",
+                                path.display()
+                            ));
+                            process::exit(1);
+                        }
+                        Err(error) => {
+                            print_error(&format!("Error looking up path: {error}"));
+                            process::exit(1);
                         }
                     }
-                    for warning in warnings {
-                        print_warning(&warning);
+                }
+                cmd::ReposAction::Browse(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&error);
+                            process::exit(1);
+                        }
+                    };
+                    match tree::browse_url(config, args.target.as_deref(), args.remote.as_deref()) {
+                        Ok(url) => browse(&url, args.print),
+                        Err(error) => {
+                            print_error(&format!("Error browsing repository: {error}"));
+                            process::exit(1);
+                        }
                     }
                 }
-                cmd::FindAction::Config(args) => {
-                    let config: config::ConfigProvider = match config::read_config(&args.config) {
+                cmd::ReposAction::Unfilter(args) => {
+                    let config = match config::read_config(&args.config) {
                         Ok(config) => config,
                         Err(error) => {
                             print_error(&error);
                             process::exit(1);
                         }
                     };
-
-                    let token = match auth::get_token_from_command(&config.token_command) {
-                        Ok(token) => token,
+                    match tree::unfilter_repo(config, &args.name, lock_policy) {
+                        Ok(path) => print_success(&format!(
+                            "Fetched all objects of \"{}\"",
+                            path::path_as_string(&path)
+                        )),
+                        Err(error) => {
+                            print_error(&format!("Error unfiltering repository: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::ReposAction::Dissociate(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
                         Err(error) => {
-                            print_error(&format!("Getting token from command failed: {error}"));
+                            print_error(&error);
                             process::exit(1);
                         }
                     };
-
-                    let filters = config.filters.unwrap_or(config::ConfigProviderFilter {
-                        access: Some(false),
-                        owner: Some(false),
-                        users: Some(vec![]),
-                        groups: Some(vec![]),
-                    });
-
-                    let filter = provider::Filter::new(
-                        filters.users.unwrap_or_default(),
-                        filters.groups.unwrap_or_default(),
-                        filters.owner.unwrap_or(false),
-                        filters.access.unwrap_or(false),
-                    );
-
-                    if filter.empty() {
-                        print_warning("You did not specify any filters, so no repos will match");
+                    match tree::dissociate_repo(config, &args.name, lock_policy) {
+                        Ok(path) => print_success(&format!(
+                            "\"{}\" does not share objects anymore",
+                            path::path_as_string(&path)
+                        )),
+                        Err(error) => {
+                            print_error(&format!("Error dissociating repository: {error}"));
+                            process::exit(1);
+                        }
                     }
-
-                    let repos = match config.provider {
-                        provider::RemoteProvider::Github => {
-                            match match provider::Github::new(filter, token, config.api_url) {
-                                Ok(provider) => provider,
+                }
+                cmd::ReposAction::InitDefaultBranch(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&error);
+                            process::exit(1);
+                        }
+                    };
+                    match tree::init_default_branch(
+                        config,
+                        &args.name,
+                        args.branch.as_deref(),
+                        lock_policy,
+                    ) {
+                        Ok((path, branch)) => print_success(&format!(
+                            "Initialized {branch} in \"{}\"",
+                            path::path_as_string(&path)
+                        )),
+                        Err(error) => {
+                            print_error(&format!("Error initializing default branch: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::ReposAction::Stats(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&error);
+                            process::exit(1);
+                        }
+                    };
+                    let (stats, warnings) = match stats::get_stats(config) {
+                        Ok(result) => result,
+                        Err(error) => {
+                            print_error(&format!("Error getting stats: {error}"));
+                            process::exit(1);
+                        }
+                    };
+                    match args.format {
+                        cmd::OutputFormat::Table => {
+                            for table in stats::get_stats_tables(&stats, args.top) {
+                                println!("{table}");
+                            }
+                        }
+                        cmd::OutputFormat::Json => match serde_json::to_string_pretty(&stats) {
+                            Ok(json) => println!("{json}"),
+                            Err(error) => {
+                                print_error(&format!("Failed converting stats to JSON: {error}"));
+                                process::exit(1);
+                            }
+                        },
+                    }
+                    for warning in warnings {
+                        print_warning(&warning);
+                    }
+                }
+                cmd::ReposAction::Metrics(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&error);
+                            process::exit(1);
+                        }
+                    };
+                    let (metrics, warnings) = match metrics::get_metrics(config) {
+                        Ok(result) => result,
+                        Err(error) => {
+                            print_error(&format!("Error getting metrics: {error}"));
+                            process::exit(1);
+                        }
+                    };
+                    match &args.output {
+                        Some(output) => {
+                            if let Err(error) = metrics::write_metrics(&metrics, Path::new(output))
+                            {
+                                print_error(&error);
+                                process::exit(1);
+                            }
+                        }
+                        None => print!("{metrics}"),
+                    }
+                    for warning in warnings {
+                        print_warning(&warning);
+                    }
+                }
+                cmd::ReposAction::Report(args) => {
+                    let config = match config::read_config(&args.config) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&error);
+                            process::exit(1);
+                        }
+                    };
+                    match report::write_html_report(config, Path::new(&args.html)) {
+                        Ok(path) => {
+                            print_success(&format!("Report written to {}", path.display()));
+                        }
+                        Err(error) => {
+                            print_error(&format!("Error writing report: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::ReposAction::Find(find) => match find {
+                    cmd::FindAction::Local(args) => {
+                        let path = Path::new(&args.path);
+                        if !path.exists() {
+                            print_error(&format!("Path \"{}\" does not exist", path.display()));
+                            process::exit(1);
+                        }
+                        if !path.is_dir() {
+                            print_error(&format!("Path \"{}\" is not a directory", path.display()));
+                            process::exit(1);
+                        }
+
+                        let path = match path.canonicalize() {
+                            Ok(path) => path,
+                            Err(error) => {
+                                print_error(&format!(
+                                    "Failed to canonicalize path \"{}\". This is a bug. Error message: {}",
+                                    &path.display(),
+                                    error
+                                ));
+                                process::exit(1);
+                            }
+                        };
+
+                        let (found_repos, warnings, skipped) = match find_in_tree(
+                            &path,
+                            args.exclude.as_deref(),
+                            args.follow_symlinks,
+                            args.verbose,
+                            args.skip_jj,
+                        ) {
+                            Ok((repos, warnings, skipped)) => (repos, warnings, skipped),
+                            Err(error) => {
+                                print_error(&error);
+                                process::exit(1);
+                            }
+                        };
+
+                        let trees = config::ConfigTrees::from_trees(vec![found_repos]);
+                        if skipped.is_empty()
+                            && trees.trees_ref().iter().all(|t| match &t.repos {
+                                None => false,
+                                Some(r) => r.is_empty(),
+                            })
+                        {
+                            print_warning("No repositories found");
+                        } else {
+                            let mut config = trees.to_config();
+
+                            config.normalize();
+
+                            match args.format {
+                                cmd::ConfigFormat::Toml => {
+                                    let toml = match config.as_toml() {
+                                        Ok(toml) => toml,
+                                        Err(error) => {
+                                            print_error(&format!(
+                                                "Failed converting config to TOML: {}",
+                                                &error
+                                            ));
+                                            process::exit(1);
+                                        }
+                                    };
+                                    print!("{toml}");
+                                    print!("{}", skipped_as_toml(&skipped));
+                                }
+                                cmd::ConfigFormat::Yaml => {
+                                    let yaml = match config.as_yaml() {
+                                        Ok(yaml) => yaml,
+                                        Err(error) => {
+                                            print_error(&format!(
+                                                "Failed converting config to YAML: {}",
+                                                &error
+                                            ));
+                                            process::exit(1);
+                                        }
+                                    };
+                                    print!("{yaml}");
+                                    match skipped_as_yaml(&skipped) {
+                                        Ok(comments) => print!("{comments}"),
+                                        Err(error) => {
+                                            print_error(&format!(
+                                                "Failed converting skipped repositories to YAML: {}",
+                                                error
+                                            ));
+                                            process::exit(1);
+                                        }
+                                    }
+                                }
+                                cmd::ConfigFormat::Json => {
+                                    let json = match config.as_json() {
+                                        Ok(json) => json,
+                                        Err(error) => {
+                                            print_error(&format!(
+                                                "Failed converting config to JSON: {}",
+                                                error
+                                            ));
+                                            process::exit(1);
+                                        }
+                                    };
+                                    print!("{json}");
+                                    // JSON cannot contain the skipped entries as comments
+                                    for entry in &skipped {
+                                        print_warning(&skipped_message(entry));
+                                    }
+                                }
+                            }
+                        }
+                        for warning in warnings {
+                            print_warning(&warning);
+                        }
+                    }
+                    cmd::FindAction::Config(args) => {
+                        let config: config::ConfigProvider = match config::read_config(&args.config)
+                        {
+                            Ok(config) => config,
+                            Err(error) => {
+                                print_error(&error);
+                                process::exit(1);
+                            }
+                        };
+
+                        let trees = match config.trees() {
+                            Ok(trees) => trees,
+                            Err(error) => {
+                                print_error(&format!("Error: {error}"));
+                                process::exit(1);
+                            }
+                        };
+
+                        let mut trees = config::ConfigTrees::from_vec(trees);
+                        trees.hosts = config.hosts.clone();
+                        trees.shorten_urls();
+                        let config = trees.to_config();
+
+                        match args.format {
+                            cmd::ConfigFormat::Toml => {
+                                let toml = match config.as_toml() {
+                                    Ok(toml) => toml,
+                                    Err(error) => {
+                                        print_error(&format!(
+                                            "Failed converting config to TOML: {}",
+                                            &error
+                                        ));
+                                        process::exit(1);
+                                    }
+                                };
+                                print!("{toml}");
+                            }
+                            cmd::ConfigFormat::Yaml => {
+                                let yaml = match config.as_yaml() {
+                                    Ok(yaml) => yaml,
+                                    Err(error) => {
+                                        print_error(&format!(
+                                            "Failed converting config to YAML: {}",
+                                            &error
+                                        ));
+                                        process::exit(1);
+                                    }
+                                };
+                                print!("{yaml}");
+                            }
+                            cmd::ConfigFormat::Json => {
+                                let json = match config.as_json() {
+                                    Ok(json) => json,
+                                    Err(error) => {
+                                        print_error(&format!(
+                                            "Failed converting config to JSON: {}",
+                                            error
+                                        ));
+                                        process::exit(1);
+                                    }
+                                };
+                                print!("{json}");
+                            }
+                        }
+                    }
+                    cmd::FindAction::Remote(args) => {
+                        if let Some(template) = &args.url_template {
+                            if let Err(error) = provider::check_url_template(template) {
+                                print_error(&format!("Error: {error}"));
+                                process::exit(1);
+                            }
+                        }
+
+                        let projects = match &args.from_snapshot {
+                            Some(snapshot) => provider::read_snapshot(Path::new(snapshot)),
+                            None => find_remote_projects(&args),
+                        }
+                        .unwrap_or_else(|error| {
+                            print_error(&format!("Error: {error}"));
+                            process::exit(1);
+                        });
+
+                        if let Some(snapshot) = &args.snapshot {
+                            if let Err(error) =
+                                provider::write_snapshot(Path::new(snapshot), projects.clone())
+                            {
+                                print_error(&error);
+                                process::exit(1);
+                            }
+                        }
+
+                        let grouping = args.tree_per_filter.then(|| find_remote_filter(&args));
+                        let repos = provider::repos_from_projects(
+                            projects,
+                            args.worktree == "true",
+                            args.force_ssh,
+                            args.remote_name,
+                            args.url_template.as_deref(),
+                        );
+                        let repos = match &grouping {
+                            Some(filter) => filter.group_by_entry(repos),
+                            None => repos,
+                        };
+
+                        let mut trees: Vec<config::ConfigTree> = vec![];
+
+                        for (namespace, repolist) in repos {
+                            let tree = config::ConfigTree {
+                                root: if let Some(namespace) = namespace {
+                                    path::path_as_string(&Path::new(&args.root).join(namespace))
+                                } else {
+                                    path::path_as_string(Path::new(&args.root))
+                                },
+                                repos: Some(
+                                    repolist
+                                        .into_iter()
+                                        .map(config::RepoConfig::from_repo)
+                                        .collect(),
+                                ),
+                                canonical_remote_name: None,
+                                name: None,
+                                serial: None,
+                                depends_on: None,
+                                history_days: None,
+                                max_size_mb: None,
+                                dir_mode: None,
+                                standalone: false,
+                            };
+                            trees.push(tree);
+                        }
+
+                        let mut config = config::Config::from_trees(trees);
+
+                        config.normalize();
+
+                        match args.format {
+                            cmd::ConfigFormat::Toml => {
+                                let toml = match config.as_toml() {
+                                    Ok(toml) => toml,
+                                    Err(error) => {
+                                        print_error(&format!(
+                                            "Failed converting config to TOML: {}",
+                                            &error
+                                        ));
+                                        process::exit(1);
+                                    }
+                                };
+                                print!("{toml}");
+                            }
+                            cmd::ConfigFormat::Yaml => {
+                                let yaml = match config.as_yaml() {
+                                    Ok(yaml) => yaml,
+                                    Err(error) => {
+                                        print_error(&format!(
+                                            "Failed converting config to YAML: {}",
+                                            &error
+                                        ));
+                                        process::exit(1);
+                                    }
+                                };
+                                print!("{yaml}");
+                            }
+                            cmd::ConfigFormat::Json => {
+                                let json = match config.as_json() {
+                                    Ok(json) => json,
+                                    Err(error) => {
+                                        print_error(&format!(
+                                            "Failed converting config to JSON: {}",
+                                            error
+                                        ));
+                                        process::exit(1);
+                                    }
+                                };
+                                print!("{json}");
+                            }
+                        }
+                    }
+                },
+            }
+        }
+        cmd::SubCommand::Worktree(args) => {
+            let cwd = std::env::current_dir().unwrap_or_else(|error| {
+                print_error(&format!("Could not open current directory: {error}"));
+                process::exit(1);
+            });
+
+            // Deleting also works from within the worktree that is to be deleted,
+            // so we have to look for the root of the worktree setup
+            let root = match args.action {
+                cmd::WorktreeAction::Delete(_) => {
+                    worktree::find_root(&cwd).unwrap_or_else(|| cwd.clone())
+                }
+                _ => cwd.clone(),
+            };
+
+            // Status, open and listing are read-only, everything else modifies the
+            // repository
+            let repo_lock = if matches!(
+                args.action,
+                cmd::WorktreeAction::Status(_)
+                    | cmd::WorktreeAction::Open(_)
+                    | cmd::WorktreeAction::Browse(_)
+                    | cmd::WorktreeAction::Branches(cmd::WorktreeBranchesArgs { create: None })
+                    | cmd::WorktreeAction::Clean(cmd::WorktreeCleanArgs { suggest: true })
+            ) {
+                None
+            } else {
+                match path::check_writable(&root).and_then(|()| lock::lock_repo(&root, lock_policy))
+                {
+                    Ok(lock) => Some(lock),
+                    Err(error) => {
+                        print_error(&error);
+                        process::exit(1);
+                    }
+                }
+            };
+
+            let changes_worktrees = repo_lock.is_some();
+
+            match args.action {
+                cmd::WorktreeAction::Add(action_args) => {
+                    if action_args.track.is_some() && action_args.no_track {
+                        print_warning("You are using --track and --no-track at the same time. --track will be ignored");
+                    }
+                    let track = match &action_args.track {
+                        Some(branch) => {
+                            let split = branch.split_once('/');
+
+                            if split.is_none()
+                                || split.unwrap().0.is_empty()
+                                || split.unwrap().1.is_empty()
+                            {
+                                print_error("Tracking branch needs to match the pattern <remote>/<branch_name>");
+                                process::exit(1);
+                            };
+
+                            // unwrap() here is safe because we checked for
+                            // is_none() explictily before
+                            let (remote_name, remote_branch_name) = split.unwrap();
+
+                            Some((remote_name, remote_branch_name))
+                        }
+                        None => None,
+                    };
+
+                    if let Some(path) = &action_args.from_file {
+                        let names = worktree::read_worktree_names(path).unwrap_or_else(|error| {
+                            print_error(&error);
+                            process::exit(1);
+                        });
+                        let mut failures = 0;
+                        for name in &names {
+                            match worktree::add_worktree(&cwd, name, None, action_args.no_track) {
+                                Ok(warnings) => {
+                                    for warning in warnings.unwrap_or_default() {
+                                        print_repo_warning(name, &warning);
+                                    }
+                                    print_success(&format!("Worktree {name} created"));
+                                }
                                 Err(error) => {
-                                    print_error(&format!("Error: {error}"));
+                                    print_repo_error(
+                                        name,
+                                        &format!("Error creating worktree: {error}"),
+                                    );
+                                    failures += 1;
+                                }
+                            }
+                        }
+                        if failures > 0 {
+                            print_error(&format!(
+                                "Failed creating {failures} of {} worktrees",
+                                names.len()
+                            ));
+                            process::exit(1);
+                        }
+                        return;
+                    }
+                    // clap makes sure that the name is given without --from-file
+                    let name = action_args.name.clone().unwrap_or_default();
+
+                    // Checked first, so a typo does not leave a worktree behind
+                    let changes = action_args.apply.as_ref().map(|value| {
+                        worktree::WorktreeChanges::find(&cwd, value).unwrap_or_else(|error| {
+                            print_error(&format!("Error creating worktree: {error}"));
+                            process::exit(1);
+                        })
+                    });
+
+                    match worktree::add_worktree(&cwd, &name, track, action_args.no_track) {
+                        Ok(warnings) => {
+                            if let Some(warnings) = warnings {
+                                for warning in warnings {
+                                    print_warning(&warning);
+                                }
+                            }
+                            print_success(&format!("Worktree {name} created"));
+                            if let Some(changes) = changes {
+                                if let Err(error) = changes.apply(&cwd, &name) {
+                                    print_error(&format!("Applying the {changes} failed: {error}"));
                                     process::exit(1);
                                 }
+                                print_success(&format!("Applied the {changes}"));
                             }
-                            .get_repos(
-                                config.worktree.unwrap_or(false),
-                                config.force_ssh.unwrap_or(false),
-                                config.remote_name,
-                            ) {
-                                Ok(provider) => provider,
-                                Err(error) => {
-                                    print_error(&format!("Error: {error}"));
+                            if action_args.and_cd {
+                                change_directory(&cwd.join(&name));
+                            }
+                            if action_args.open {
+                                // The editor may run for a long time, so we must not
+                                // block other grm processes in the meantime
+                                write_worktree_state(&cwd);
+                                drop(repo_lock);
+                                open_worktree(&cwd, &name);
+                            }
+                        }
+                        Err(error) => {
+                            print_error(&format!("Error creating worktree: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::WorktreeAction::Delete(action_args) => {
+                    let worktree_config = match repo::read_worktree_root_config(&root) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&format!("Error getting worktree configuration: {error}"));
+                            process::exit(1);
+                        }
+                    };
+
+                    let repo = repo::RepoHandle::open(&root, true).unwrap_or_else(|error| {
+                        print_error(&format!("Error opening repository: {error}"));
+                        process::exit(1);
+                    });
+
+                    match repo.remove_worktree(
+                        &root,
+                        &action_args.name,
+                        Path::new(&action_args.name),
+                        remove_overrides(
+                            action_args.force,
+                            action_args.ignore_changes,
+                            action_args.ignore_unmerged,
+                            action_args.ignore_upstream_divergence,
+                        ),
+                        &worktree_config,
+                    ) {
+                        Ok(archive) => {
+                            print_success(&format!(
+                                "Worktree {} deleted{}",
+                                &action_args.name,
+                                repo::archived_to(archive.as_deref())
+                            ));
+                            // Do not leave the shell in a directory that is gone
+                            if cwd.starts_with(root.join(&action_args.name)) {
+                                change_directory(&root);
+                            }
+                        }
+                        Err(error) => {
+                            match error {
+                                repo::WorktreeRemoveFailureReason::Error(msg) => {
+                                    print_error(&msg);
                                     process::exit(1);
                                 }
+                                repo::WorktreeRemoveFailureReason::Changes(changes) => {
+                                    print_warning(&format!(
+                                        "Changes in worktree: {changes}. Refusing to delete"
+                                    ));
+                                }
+                                repo::WorktreeRemoveFailureReason::Submodules(problems) => {
+                                    print_warning(&format!(
+                                        "Submodules would lose work: {problems}. Refusing to delete"
+                                    ));
+                                }
+                                repo::WorktreeRemoveFailureReason::NotMerged(message) => {
+                                    print_warning(&message);
+                                }
+                            }
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::WorktreeAction::Status(args) => {
+                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
+                        print_error(&format!("Error opening repository: {error}"));
+                        process::exit(1);
+                    });
+
+                    if args.json {
+                        match table::get_worktree_status_json(&repo, &cwd, args.disk_usage) {
+                            Ok((json, errors)) => {
+                                println!("{json}");
+                                for error in errors {
+                                    print_error(&format!("Error: {error}"));
+                                }
+                            }
+                            Err(error) => {
+                                print_error(&format!("Error getting status: {error}"));
+                                process::exit(1);
+                            }
+                        }
+                        return;
+                    }
+
+                    match table::get_worktree_status_table(&repo, &cwd, args.disk_usage, None) {
+                        Ok((table, errors)) => {
+                            println!("{table}");
+                            for error in errors {
+                                print_error(&format!("Error: {error}"));
+                            }
+                        }
+                        Err(error) => {
+                            print_error(&format!("Error getting status: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::WorktreeAction::Convert(_args) => {
+                    // Converting works like this:
+                    // * Check whether there are uncommitted/unpushed changes
+                    // * Move the contents of .git dir to the worktree directory
+                    // * Remove all files
+                    // * Set `core.bare` to `true`
+
+                    let repo = repo::RepoHandle::open(&cwd, false).unwrap_or_else(|error| {
+                        if error.kind == repo::RepoErrorKind::NotFound {
+                            print_error("Directory does not contain a git repository");
+                        } else {
+                            print_error(&format!("Opening repository failed: {error}"));
+                        }
+                        process::exit(1);
+                    });
+
+                    match repo.convert_to_worktree(&cwd) {
+                        Ok(()) => print_success("Conversion done"),
+                        Err(reason) => {
+                            match reason {
+                                repo::WorktreeConversionFailureReason::Changes => {
+                                    print_error("Changes found in repository, refusing to convert");
+                                }
+                                repo::WorktreeConversionFailureReason::Ignored => {
+                                    print_error("Ignored files found in repository, refusing to convert. Run git clean -f -d -X to remove them manually.");
+                                }
+                                repo::WorktreeConversionFailureReason::Error(error) => {
+                                    print_error(&format!("Error during conversion: {error}"));
+                                }
                             }
+                            process::exit(1);
                         }
-                        provider::RemoteProvider::Gitlab => {
-                            match match provider::Gitlab::new(filter, token, config.api_url) {
-                                Ok(provider) => provider,
-                                Err(error) => {
-                                    print_error(&format!("Error: {error}"));
-                                    process::exit(1);
+                    }
+                }
+                cmd::WorktreeAction::Clean(args) => {
+                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
+                        if error.kind == repo::RepoErrorKind::NotFound {
+                            print_error("Directory does not contain a git repository");
+                        } else {
+                            print_error(&format!("Opening repository failed: {error}"));
+                        }
+                        process::exit(1);
+                    });
+
+                    if args.suggest {
+                        match worktree::cleanup_suggestions(&repo, &cwd) {
+                            Ok((suggestions, warnings)) => {
+                                if suggestions.is_empty() {
+                                    print_success("No worktrees to clean up");
+                                } else {
+                                    println!(
+                                        "{}",
+                                        table::get_cleanup_suggestion_table(&suggestions)
+                                    );
                                 }
-                            }
-                            .get_repos(
-                                config.worktree.unwrap_or(false),
-                                config.force_ssh.unwrap_or(false),
-                                config.remote_name,
-                            ) {
-                                Ok(provider) => provider,
-                                Err(error) => {
-                                    print_error(&format!("Error: {error}"));
-                                    process::exit(1);
+                                for warning in warnings {
+                                    print_warning(&warning);
                                 }
                             }
+                            Err(error) => {
+                                print_error(&format!(
+                                    "Getting cleanup suggestions failed: {error}"
+                                ));
+                                process::exit(1);
+                            }
                         }
-                    };
-
-                    let mut trees = vec![];
-
-                    for (namespace, namespace_repos) in repos {
-                        let tree = config::ConfigTree {
-                            root: if let Some(namespace) = namespace {
-                                path::path_as_string(&Path::new(&config.root).join(namespace))
-                            } else {
-                                path::path_as_string(Path::new(&config.root))
-                            },
-                            repos: Some(
-                                namespace_repos
-                                    .into_iter()
-                                    .map(config::RepoConfig::from_repo)
-                                    .collect(),
-                            ),
-                        };
-                        trees.push(tree);
+                        return;
                     }
 
-                    let config = config::Config::from_trees(trees);
-
-                    match args.format {
-                        cmd::ConfigFormat::Toml => {
-                            let toml = match config.as_toml() {
-                                Ok(toml) => toml,
-                                Err(error) => {
-                                    print_error(&format!(
-                                        "Failed converting config to TOML: {}",
-                                        &error
-                                    ));
-                                    process::exit(1);
-                                }
-                            };
-                            print!("{toml}");
+                    match repo.cleanup_worktrees(&cwd) {
+                        Ok(warnings) => {
+                            for warning in warnings {
+                                print_warning(&warning);
+                            }
                         }
-                        cmd::ConfigFormat::Yaml => {
-                            let yaml = match config.as_yaml() {
-                                Ok(yaml) => yaml,
-                                Err(error) => {
-                                    print_error(&format!(
-                                        "Failed converting config to YAML: {}",
-                                        &error
-                                    ));
-                                    process::exit(1);
-                                }
-                            };
-                            print!("{yaml}");
+                        Err(error) => {
+                            print_error(&format!("Worktree cleanup failed: {error}"));
+                            process::exit(1);
                         }
                     }
+
+                    for unmanaged_worktree in
+                        repo.find_unmanaged_worktrees(&cwd).unwrap_or_else(|error| {
+                            print_error(&format!("Failed finding unmanaged worktrees: {error}"));
+                            process::exit(1);
+                        })
+                    {
+                        print_warning(&format!(
+                            "Found {}, which is not a valid worktree directory!",
+                            &unmanaged_worktree
+                        ));
+                    }
                 }
-                cmd::FindAction::Remote(args) => {
-                    let token = match auth::get_token_from_command(&args.token_command) {
-                        Ok(token) => token,
-                        Err(error) => {
-                            print_error(&format!("Getting token from command failed: {error}"));
+                cmd::WorktreeAction::Fetch(args) => {
+                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
+                        if error.kind == repo::RepoErrorKind::NotFound {
+                            print_error("Directory does not contain a git repository");
+                        } else {
+                            print_error(&format!("Opening repository failed: {error}"));
+                        }
+                        process::exit(1);
+                    });
+
+                    let message = worktree::fetch_remotes(&repo, &cwd, &args.remotes)
+                        .unwrap_or_else(|error| {
+                            print_error(&format!("Error fetching remotes: {error}"));
                             process::exit(1);
+                        });
+                    print_success(&message);
+                }
+                cmd::WorktreeAction::Pull(args) => {
+                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
+                        if error.kind == repo::RepoErrorKind::NotFound {
+                            print_error("Directory does not contain a git repository");
+                        } else {
+                            print_error(&format!("Opening repository failed: {error}"));
                         }
-                    };
+                        process::exit(1);
+                    });
 
-                    let filter =
-                        provider::Filter::new(args.users, args.groups, args.owner, args.access);
+                    worktree::fetch_remotes(&repo, &cwd, &args.remotes).unwrap_or_else(|error| {
+                        print_error(&format!("Error fetching remotes: {error}"));
+                        process::exit(1);
+                    });
 
-                    if filter.empty() {
-                        print_warning("You did not specify any filters, so no repos will match");
-                    }
+                    let config = repo::read_worktree_root_config(&cwd).unwrap_or_else(|error| {
+                        print_error(&format!("Failed to read worktree configuration: {error}"));
+                        process::exit(1);
+                    });
 
-                    let worktree = args.worktree == "true";
+                    let worktrees = without_skipped(
+                        repo.get_worktrees().unwrap_or_else(|error| {
+                            print_error(&format!("Error getting worktrees: {error}"));
+                            process::exit(1);
+                        }),
+                        config.as_ref(),
+                    );
 
-                    let repos = match args.provider {
-                        cmd::RemoteProvider::Github => {
-                            match provider::Github::new(filter, token, args.api_url) {
-                                Ok(provider) => provider,
-                                Err(error) => {
-                                    print_error(&format!("Error: {error}"));
-                                    process::exit(1);
-                                }
-                            }
-                            .get_repos(
-                                worktree,
-                                args.force_ssh,
-                                args.remote_name,
-                            )
+                    let mut failures = false;
+                    for worktree in worktrees {
+                        if let Some(warning) = worktree
+                            .forward_branch(args.rebase, args.stash)
+                            .unwrap_or_else(|error| {
+                                print_error(&format!("Error updating worktree branch: {error}"));
+                                process::exit(1);
+                            })
+                        {
+                            print_warning(&format!("{}: {}", worktree.name(), warning));
+                            failures = true;
+                        } else {
+                            print_success(&format!("{}: Done", worktree.name()));
                         }
-                        cmd::RemoteProvider::Gitlab => {
-                            match provider::Gitlab::new(filter, token, args.api_url) {
-                                Ok(provider) => provider,
-                                Err(error) => {
-                                    print_error(&format!("Error: {error}"));
-                                    process::exit(1);
-                                }
-                            }
-                            .get_repos(
-                                worktree,
-                                args.force_ssh,
-                                args.remote_name,
-                            )
+                    }
+                    if failures {
+                        process::exit(1);
+                    }
+                }
+                cmd::WorktreeAction::Open(action_args) => {
+                    open_worktree(&cwd, &action_args.name);
+                }
+                cmd::WorktreeAction::Browse(action_args) => {
+                    let worktree_config = match repo::read_worktree_root_config(&cwd) {
+                        Ok(config) => config,
+                        Err(error) => {
+                            print_error(&format!("Error getting worktree configuration: {error}"));
+                            process::exit(1);
                         }
                     };
-
-                    let repos = repos.unwrap_or_else(|error| {
-                        print_error(&format!("Error: {error}"));
+                    match worktree::browse_url(
+                        &cwd,
+                        action_args.name.as_deref(),
+                        action_args.remote.as_deref(),
+                        &worktree_config,
+                    ) {
+                        Ok(url) => browse(&url, action_args.print),
+                        Err(error) => {
+                            print_error(&format!("Error browsing worktree: {error}"));
+                            process::exit(1);
+                        }
+                    }
+                }
+                cmd::WorktreeAction::Sync(args) => {
+                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
+                        if error.kind == repo::RepoErrorKind::NotFound {
+                            print_error("Directory does not contain a git repository");
+                        } else {
+                            print_error(&format!("Opening repository failed: {error}"));
+                        }
                         process::exit(1);
                     });
 
-                    let mut trees: Vec<config::ConfigTree> = vec![];
+                    let config = repo::read_worktree_root_config(&cwd).unwrap_or_else(|error| {
+                        print_error(&format!("Failed to read worktree configuration: {error}"));
+                        process::exit(1);
+                    });
+                    let sync_config = config.as_ref().and_then(|config| config.sync.as_ref());
+                    let stash = args.stash || repo::WorktreeSyncConfig::stash(sync_config);
 
-                    for (namespace, repolist) in repos {
-                        let tree = config::ConfigTree {
-                            root: if let Some(namespace) = namespace {
-                                path::path_as_string(&Path::new(&args.root).join(namespace))
-                            } else {
-                                path::path_as_string(Path::new(&args.root))
-                            },
-                            repos: Some(
-                                repolist
-                                    .into_iter()
-                                    .map(config::RepoConfig::from_repo)
-                                    .collect(),
-                            ),
-                        };
-                        trees.push(tree);
+                    if repo::WorktreeSyncConfig::fetch(sync_config) {
+                        let message =
+                            worktree::fetch_remotes(&repo, &cwd, &[]).unwrap_or_else(|error| {
+                                print_error(&format!("Error fetching remotes: {error}"));
+                                process::exit(1);
+                            });
+                        print_success(&message);
                     }
 
-                    let mut config = config::Config::from_trees(trees);
-
-                    config.normalize();
+                    let worktrees = without_skipped(
+                        repo.get_worktrees().unwrap_or_else(|error| {
+                            print_error(&format!("Error getting worktrees: {error}"));
+                            process::exit(1);
+                        }),
+                        config.as_ref(),
+                    );
 
-                    match args.format {
-                        cmd::ConfigFormat::Toml => {
-                            let toml = match config.as_toml() {
-                                Ok(toml) => toml,
-                                Err(error) => {
-                                    print_error(&format!(
-                                        "Failed converting config to TOML: {}",
-                                        &error
-                                    ));
-                                    process::exit(1);
-                                }
-                            };
-                            print!("{toml}");
+                    let mut failures = false;
+                    for worktree in &worktrees {
+                        let mut warning = None;
+                        if repo::WorktreeSyncConfig::pull(sync_config) {
+                            warning =
+                                worktree
+                                    .forward_branch(false, stash)
+                                    .unwrap_or_else(|error| {
+                                        print_error(&format!(
+                                            "Error updating worktree branch: {error}"
+                                        ));
+                                        process::exit(1);
+                                    });
                         }
-                        cmd::ConfigFormat::Yaml => {
-                            let yaml = match config.as_yaml() {
-                                Ok(yaml) => yaml,
-                                Err(error) => {
+                        // Rebasing a worktree that could not be updated would
+                        // only pile up more work
+                        if warning.is_none() && repo::WorktreeSyncConfig::rebase(sync_config) {
+                            warning = worktree.rebase_onto_default(&config, stash).unwrap_or_else(
+                                |error| {
                                     print_error(&format!(
-                                        "Failed converting config to YAML: {}",
-                                        &error
+                                        "Error rebasing worktree branch: {error}"
                                     ));
                                     process::exit(1);
-                                }
-                            };
-                            print!("{yaml}");
+                                },
+                            );
                         }
-                    }
-                }
-            },
-        },
-        cmd::SubCommand::Worktree(args) => {
-            let cwd = std::env::current_dir().unwrap_or_else(|error| {
-                print_error(&format!("Could not open current directory: {error}"));
-                process::exit(1);
-            });
-
-            match args.action {
-                cmd::WorktreeAction::Add(action_args) => {
-                    if action_args.track.is_some() && action_args.no_track {
-                        print_warning("You are using --track and --no-track at the same time. --track will be ignored");
-                    }
-                    let track = match &action_args.track {
-                        Some(branch) => {
-                            let split = branch.split_once('/');
-
-                            if split.is_none()
-                                || split.unwrap().0.is_empty()
-                                || split.unwrap().1.is_empty()
-                            {
-                                print_error("Tracking branch needs to match the pattern <remote>/<branch_name>");
-                                process::exit(1);
-                            };
-
-                            // unwrap() here is safe because we checked for
-                            // is_none() explictily before
-                            let (remote_name, remote_branch_name) = split.unwrap();
-
-                            Some((remote_name, remote_branch_name))
+                        match warning {
+                            Some(warning) => {
+                                failures = true;
+                                print_warning(&format!("{}: {}", worktree.name(), warning));
+                            }
+                            None => print_success(&format!("{}: Done", worktree.name())),
                         }
-                        None => None,
-                    };
+                    }
 
-                    match worktree::add_worktree(
-                        &cwd,
-                        &action_args.name,
-                        track,
-                        action_args.no_track,
-                    ) {
-                        Ok(warnings) => {
-                            if let Some(warnings) = warnings {
+                    if repo::WorktreeSyncConfig::clean(sync_config) {
+                        match repo.cleanup_worktrees(&cwd) {
+                            Ok(warnings) => {
                                 for warning in warnings {
                                     print_warning(&warning);
                                 }
                             }
-                            print_success(&format!("Worktree {} created", &action_args.name));
-                        }
-                        Err(error) => {
-                            print_error(&format!("Error creating worktree: {error}"));
-                            process::exit(1);
+                            Err(error) => {
+                                print_error(&format!("Worktree cleanup failed: {error}"));
+                                process::exit(1);
+                            }
                         }
                     }
-                }
-                cmd::WorktreeAction::Delete(action_args) => {
-                    let worktree_config = match repo::read_worktree_root_config(&cwd) {
-                        Ok(config) => config,
-                        Err(error) => {
-                            print_error(&format!("Error getting worktree configuration: {error}"));
-                            process::exit(1);
-                        }
-                    };
-
-                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
-                        print_error(&format!("Error opening repository: {error}"));
+
+                    if failures {
                         process::exit(1);
-                    });
+                    }
+                }
+                cmd::WorktreeAction::Retrack(action_args) => {
+                    let Some((remote_name, remote_branch_name)) = action_args
+                        .track
+                        .split_once('/')
+                        .filter(|(remote, branch)| !remote.is_empty() && !branch.is_empty())
+                    else {
+                        print_error(
+                            "Tracking branch needs to match the pattern <remote>/<branch_name>",
+                        );
+                        process::exit(1);
+                    };
 
-                    match repo.remove_worktree(
+                    match worktree::retrack_worktree(
                         &cwd,
                         &action_args.name,
-                        Path::new(&action_args.name),
-                        action_args.force,
-                        &worktree_config,
+                        remote_name,
+                        remote_branch_name,
+                        action_args.push,
                     ) {
-                        Ok(()) => print_success(&format!("Worktree {} deleted", &action_args.name)),
-                        Err(error) => {
-                            match error {
-                                repo::WorktreeRemoveFailureReason::Error(msg) => {
-                                    print_error(&msg);
-                                    process::exit(1);
-                                }
-                                repo::WorktreeRemoveFailureReason::Changes(changes) => {
-                                    print_warning(&format!(
-                                        "Changes in worktree: {changes}. Refusing to delete"
-                                    ));
-                                }
-                                repo::WorktreeRemoveFailureReason::NotMerged(message) => {
-                                    print_warning(&message);
-                                }
+                        Ok(warnings) => {
+                            for warning in warnings {
+                                print_warning(&warning);
                             }
+                            print_success(&format!(
+                                "Worktree {} now tracks {}",
+                                action_args.name, action_args.track
+                            ));
+                        }
+                        Err(error) => {
+                            print_error(&format!("Error changing tracking branch: {error}"));
                             process::exit(1);
                         }
                     }
                 }
-                cmd::WorktreeAction::Status(_args) => {
-                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
-                        print_error(&format!("Error opening repository: {error}"));
-                        process::exit(1);
-                    });
-
-                    match table::get_worktree_status_table(&repo, &cwd) {
-                        Ok((table, errors)) => {
-                            println!("{table}");
-                            for error in errors {
-                                print_error(&format!("Error: {error}"));
-                            }
+                cmd::WorktreeAction::MoveChanges(action_args) => {
+                    match worktree::move_changes(&cwd, &action_args.from, &action_args.to) {
+                        Ok(worktree::MovedChanges::Clean) => {
+                            print_success(&format!(
+                                "Moved changes from {} to {}",
+                                action_args.from, action_args.to
+                            ));
+                        }
+                        Ok(worktree::MovedChanges::Conflicts(stash)) => {
+                            print_warning(&format!(
+                                "Moved changes to {} with conflicts. Resolve them there, then drop the changes with \"git stash drop {}\"",
+                                action_args.to, stash
+                            ));
+                            process::exit(1);
                         }
                         Err(error) => {
-                            print_error(&format!("Error getting status: {error}"));
+                            print_error(&format!("Error moving changes: {error}"));
                             process::exit(1);
                         }
                     }
                 }
-                cmd::WorktreeAction::Convert(_args) => {
-                    // Converting works like this:
-                    // * Check whether there are uncommitted/unpushed changes
-                    // * Move the contents of .git dir to the worktree directory
-                    // * Remove all files
-                    // * Set `core.bare` to `true`
-
-                    let repo = repo::RepoHandle::open(&cwd, false).unwrap_or_else(|error| {
+                cmd::WorktreeAction::Branches(action_args) => {
+                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
                         if error.kind == repo::RepoErrorKind::NotFound {
                             print_error("Directory does not contain a git repository");
                         } else {
@@ -616,25 +1661,53 @@ fn main() {
                         process::exit(1);
                     });
 
-                    match repo.convert_to_worktree(&cwd) {
-                        Ok(()) => print_success("Conversion done"),
-                        Err(reason) => {
-                            match reason {
-                                repo::WorktreeConversionFailureReason::Changes => {
-                                    print_error("Changes found in repository, refusing to convert");
-                                }
-                                repo::WorktreeConversionFailureReason::Ignored => {
-                                    print_error("Ignored files found in repository, refusing to convert. Run git clean -f -d -X to remove them manually.");
+                    let candidates = worktree::remote_branch_candidates(&repo, &cwd)
+                        .unwrap_or_else(|error| {
+                            print_error(&format!("Error listing remote branches: {error}"));
+                            process::exit(1);
+                        });
+
+                    match action_args.create {
+                        Some(name) => {
+                            let candidate =
+                                worktree::find_remote_branch_candidate(&candidates, &name)
+                                    .unwrap_or_else(|error| {
+                                        print_error(&error);
+                                        process::exit(1);
+                                    });
+                            match worktree::add_worktree(
+                                &cwd,
+                                &candidate.branch,
+                                Some((&candidate.remote, &candidate.branch)),
+                                false,
+                            ) {
+                                Ok(warnings) => {
+                                    for warning in warnings.unwrap_or_default() {
+                                        print_warning(&warning);
+                                    }
+                                    print_success(&format!(
+                                        "Worktree {} created",
+                                        candidate.branch
+                                    ));
                                 }
-                                repo::WorktreeConversionFailureReason::Error(error) => {
-                                    print_error(&format!("Error during conversion: {error}"));
+                                Err(error) => {
+                                    print_error(&format!("Error creating worktree: {error}"));
+                                    process::exit(1);
                                 }
                             }
-                            process::exit(1);
+                        }
+                        None => {
+                            if candidates.is_empty() {
+                                print_success("All remote branches have a worktree");
+                            }
+                            for (remote, table) in table::get_remote_branch_tables(&candidates) {
+                                println!("{remote}");
+                                println!("{table}");
+                            }
                         }
                     }
                 }
-                cmd::WorktreeAction::Clean(_args) => {
+                cmd::WorktreeAction::SaveProfile(action_args) => {
                     let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
                         if error.kind == repo::RepoErrorKind::NotFound {
                             print_error("Directory does not contain a git repository");
@@ -644,31 +1717,24 @@ fn main() {
                         process::exit(1);
                     });
 
-                    match repo.cleanup_worktrees(&cwd) {
-                        Ok(warnings) => {
+                    match profile::save_profile(&repo, &cwd, &action_args.name) {
+                        Ok((replaced, warnings)) => {
                             for warning in warnings {
                                 print_warning(&warning);
                             }
+                            if replaced {
+                                print_success(&format!("Profile {} updated", action_args.name));
+                            } else {
+                                print_success(&format!("Profile {} saved", action_args.name));
+                            }
                         }
                         Err(error) => {
-                            print_error(&format!("Worktree cleanup failed: {error}"));
+                            print_error(&format!("Error saving profile: {error}"));
                             process::exit(1);
                         }
                     }
-
-                    for unmanaged_worktree in
-                        repo.find_unmanaged_worktrees(&cwd).unwrap_or_else(|error| {
-                            print_error(&format!("Failed finding unmanaged worktrees: {error}"));
-                            process::exit(1);
-                        })
-                    {
-                        print_warning(&format!(
-                            "Found {}, which is not a valid worktree directory!",
-                            &unmanaged_worktree
-                        ));
-                    }
                 }
-                cmd::WorktreeAction::Fetch(_args) => {
+                cmd::WorktreeAction::RestoreProfile(action_args) => {
                     let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
                         if error.kind == repo::RepoErrorKind::NotFound {
                             print_error("Directory does not contain a git repository");
@@ -678,49 +1744,35 @@ fn main() {
                         process::exit(1);
                     });
 
-                    repo.fetchall().unwrap_or_else(|error| {
-                        print_error(&format!("Error fetching remotes: {error}"));
-                        process::exit(1);
-                    });
-                    print_success("Fetched from all remotes");
+                    match profile::restore_profile(&repo, &cwd, &action_args.name) {
+                        Ok(warnings) => {
+                            for warning in warnings {
+                                print_warning(&warning);
+                            }
+                        }
+                        Err(error) => {
+                            print_error(&format!("Error restoring profile: {error}"));
+                            process::exit(1);
+                        }
+                    }
                 }
-                cmd::WorktreeAction::Pull(args) => {
-                    let repo = repo::RepoHandle::open(&cwd, true).unwrap_or_else(|error| {
-                        if error.kind == repo::RepoErrorKind::NotFound {
-                            print_error("Directory does not contain a git repository");
-                        } else {
-                            print_error(&format!("Opening repository failed: {error}"));
+                cmd::WorktreeAction::Repair => match worktree::repair_worktrees(&cwd) {
+                    Ok((repaired, warnings)) => {
+                        for warning in warnings {
+                            print_warning(&warning);
                         }
-                        process::exit(1);
-                    });
-
-                    repo.fetchall().unwrap_or_else(|error| {
-                        print_error(&format!("Error fetching remotes: {error}"));
-                        process::exit(1);
-                    });
-
-                    let mut failures = false;
-                    for worktree in repo.get_worktrees().unwrap_or_else(|error| {
-                        print_error(&format!("Error getting worktrees: {error}"));
-                        process::exit(1);
-                    }) {
-                        if let Some(warning) = worktree
-                            .forward_branch(args.rebase, args.stash)
-                            .unwrap_or_else(|error| {
-                                print_error(&format!("Error updating worktree branch: {error}"));
-                                process::exit(1);
-                            })
-                        {
-                            print_warning(&format!("{}: {}", worktree.name(), warning));
-                            failures = true;
-                        } else {
-                            print_success(&format!("{}: Done", worktree.name()));
+                        if repaired.is_empty() {
+                            print_success("All worktrees are linked correctly");
+                        }
+                        for name in repaired {
+                            print_success(&format!("Worktree {name} repaired"));
                         }
                     }
-                    if failures {
+                    Err(error) => {
+                        print_error(&format!("Error repairing worktrees: {error}"));
                         process::exit(1);
                     }
-                }
+                },
                 cmd::WorktreeAction::Rebase(args) => {
                     if args.rebase && !args.pull {
                         print_error("There is no point in using --rebase without --pull");
@@ -736,7 +1788,7 @@ fn main() {
                     });
 
                     if args.pull {
-                        repo.fetchall().unwrap_or_else(|error| {
+                        worktree::fetch_remotes(&repo, &cwd, &[]).unwrap_or_else(|error| {
                             print_error(&format!("Error fetching remotes: {error}"));
                             process::exit(1);
                         });
@@ -747,10 +1799,13 @@ fn main() {
                         process::exit(1);
                     });
 
-                    let worktrees = repo.get_worktrees().unwrap_or_else(|error| {
-                        print_error(&format!("Error getting worktrees: {error}"));
-                        process::exit(1);
-                    });
+                    let worktrees = without_skipped(
+                        repo.get_worktrees().unwrap_or_else(|error| {
+                            print_error(&format!("Error getting worktrees: {error}"));
+                            process::exit(1);
+                        }),
+                        config.as_ref(),
+                    );
 
                     let mut failures = false;
 
@@ -771,6 +1826,34 @@ fn main() {
                         }
                     }
 
+                    if args.interactive_list {
+                        let mut plans = Vec::new();
+                        for worktree in &worktrees {
+                            match worktree.rebase_plan(&config) {
+                                Ok(plan) => plans.push((worktree.name().to_string(), plan)),
+                                Err(error) => {
+                                    print_warning(&format!("{}: {}", worktree.name(), error));
+                                }
+                            }
+                        }
+                        println!("{}", table::get_rebase_plan_table(&plans, args.stash));
+
+                        if !args.yes {
+                            match prompt("Rebase these worktrees? [y/N]") {
+                                Ok(answer)
+                                    if matches!(answer.to_lowercase().as_str(), "y" | "yes") => {}
+                                Ok(_) => {
+                                    print_error("Aborted, nothing was rebased");
+                                    process::exit(1);
+                                }
+                                Err(error) => {
+                                    print_error(&format!("Aborted, nothing was rebased: {error}"));
+                                    process::exit(1);
+                                }
+                            }
+                        }
+                    }
+
                     for worktree in &worktrees {
                         if let Some(warning) = worktree
                             .rebase_onto_default(&config, args.stash)
@@ -790,6 +1873,406 @@ fn main() {
                     }
                 }
             }
+
+            // Only after commands that may have changed the worktrees
+            if changes_worktrees {
+                write_worktree_state(&root);
+            }
+        }
+        cmd::SubCommand::Daemon(args) => {
+            let interval = daemon::parse_duration(&args.interval).unwrap_or_else(|error| {
+                print_error(&format!("Invalid interval: {error}"));
+                process::exit(1);
+            });
+            if interval.is_zero() {
+                print_error("Interval must not be zero");
+                process::exit(1);
+            }
+
+            let jitter = match args.jitter {
+                Some(jitter) => daemon::parse_duration(&jitter).unwrap_or_else(|error| {
+                    print_error(&format!("Invalid jitter: {error}"));
+                    process::exit(1);
+                }),
+                None => interval / 10,
+            };
+
+            let options = daemon::DaemonOptions {
+                config_path: args.config,
+                interval,
+                jitter,
+                lock_path: match args.lock_file {
+                    Some(lock_file) => path::expand_path(Path::new(&lock_file)),
+                    None => daemon::default_lock_path(),
+                },
+                report_file: args
+                    .report_file
+                    .map(|report_file| path::expand_path(Path::new(&report_file))),
+                webhook_url: args.webhook_url,
+                once: args.once,
+                lock_policy,
+            };
+
+            if let Err(error) = daemon::run(&options) {
+                print_error(&error);
+                process::exit(1);
+            }
+        }
+        cmd::SubCommand::Serve(args) => {
+            let options = serve::ServeOptions {
+                config_path: args.config,
+                socket_path: match args.socket {
+                    Some(socket) => path::expand_path(Path::new(&socket)),
+                    None => serve::default_socket_path(),
+                },
+                lock_policy,
+            };
+
+            if let Err(error) = serve::run(&options) {
+                print_error(&error);
+                process::exit(1);
+            }
+        }
+        cmd::SubCommand::ShellInit(args) => {
+            let shell = match args.shell {
+                cmd::Shell::Bash => shell::Shell::Bash,
+                cmd::Shell::Zsh => shell::Shell::Zsh,
+                cmd::Shell::Fish => shell::Shell::Fish,
+            };
+            print!("{}", shell::init_script(shell));
+        }
+        cmd::SubCommand::ConfigCommand(args) => match args.action {
+            cmd::ConfigAction::CheckSecrets(args) => {
+                let (value, encrypted) = match config::read_config_value(&args.config) {
+                    Ok(result) => result,
+                    Err(error) => {
+                        print_error(&error);
+                        process::exit(1);
+                    }
+                };
+                if encrypted {
+                    print_success("Configuration is encrypted with sops");
+                    return;
+                }
+                let findings = secrets::find_secrets(&value);
+                if findings.is_empty() {
+                    print_success("No secrets found");
+                } else {
+                    for finding in &findings {
+                        print_warning(&format!("{}: {}", finding.location, finding.reason));
+                    }
+                    print_error(&format!(
+                        "Found {} value(s) that look like secrets. Use a command like token_command or encrypt the file with sops",
+                        findings.len()
+                    ));
+                    process::exit(1);
+                }
+            }
+            cmd::ConfigAction::Show(args) => {
+                let mut config: config::Config = match config::read_config(&args.config) {
+                    Ok(config) => config,
+                    Err(error) => {
+                        print_error(&error);
+                        process::exit(1);
+                    }
+                };
+                config.expand_hosts();
+                config.normalize();
+
+                let output = match args.format {
+                    cmd::ConfigFormat::Toml => config
+                        .as_toml()
+                        .map_err(|error| format!("Failed converting config to TOML: {error}")),
+                    cmd::ConfigFormat::Yaml => config
+                        .as_yaml()
+                        .map_err(|error| format!("Failed converting config to YAML: {error}")),
+                    cmd::ConfigFormat::Json => config
+                        .as_json()
+                        .map_err(|error| format!("Failed converting config to JSON: {error}")),
+                };
+                match output {
+                    Ok(output) => print!("{output}"),
+                    Err(error) => {
+                        print_error(&error);
+                        process::exit(1);
+                    }
+                }
+            }
+            cmd::ConfigAction::Path(args) => match config::config_path(&args.config) {
+                Ok(path) => println!("{}", path.display()),
+                Err(error) => {
+                    print_error(&error);
+                    process::exit(1);
+                }
+            },
+            cmd::ConfigAction::Edit(args) => {
+                if let Err(error) = config::edit_config(&args.config) {
+                    print_error(&error);
+                    process::exit(1);
+                }
+            }
+        },
+        cmd::SubCommand::PromptStatus(args) => {
+            let path = Path::new(&args.path);
+            let status = if args.refresh {
+                match prompt::refresh_status(path) {
+                    Ok(status) => status,
+                    Err(error) => {
+                        print_error(&format!("Error getting status: {error}"));
+                        process::exit(1);
+                    }
+                }
+            } else {
+                prompt::cached_status(path)
+            };
+            // Nothing known about the repository is not an error, the prompt
+            // just stays empty
+            let line = status.map(|status| status.line()).unwrap_or_default();
+            if !line.is_empty() {
+                println!("{line}");
+            }
+        }
+        cmd::SubCommand::Init(args) => {
+            if let Err(error) = init::run(&args.config) {
+                print_error(&error);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// The filter of `grm repos find remote`
+fn find_remote_filter(args: &cmd::FindRemoteArgs) -> provider::Filter {
+    provider::Filter::new(
+        args.users.clone(),
+        args.groups.clone(),
+        args.orgs.clone(),
+        args.owner,
+        args.access,
+        args.starred,
+        args.min_access_level,
+    )
+}
+
+/// Lists the projects of the provider for `grm repos find remote`
+fn find_remote_projects(
+    args: &cmd::FindRemoteArgs,
+) -> Result<Vec<provider::SnapshotProject>, String> {
+    let (Some(remote_provider), Some(token_command)) = (&args.provider, &args.token_command) else {
+        return Err(String::from("Provider and token command are required"));
+    };
+
+    let token = auth::get_token_from_command(token_command)
+        .map_err(|error| format!("Getting token from command failed: {error}"))?;
+
+    let filter = find_remote_filter(args);
+
+    if filter.empty() {
+        print_warning("You did not specify any filters, so no repos will match");
+    }
+
+    let pagination = provider::Pagination::new(args.per_page, args.max_requests);
+    Ok(match remote_provider {
+        cmd::RemoteProvider::Github => {
+            provider::Github::new(filter, token, args.api_url.clone(), pagination)?
+                .get_projects()?
+                .iter()
+                .map(provider::SnapshotProject::from_project)
+                .collect()
+        }
+        cmd::RemoteProvider::Gitlab => {
+            provider::Gitlab::new(filter, token, args.api_url.clone(), pagination)?
+                .get_projects()?
+                .iter()
+                .map(provider::SnapshotProject::from_project)
+                .collect()
+        }
+        cmd::RemoteProvider::Bitbucket => {
+            provider::Bitbucket::new(filter, token, args.api_url.clone(), pagination)?
+                .get_projects()?
+                .iter()
+                .map(provider::SnapshotProject::from_project)
+                .collect()
+        }
+        cmd::RemoteProvider::AzureDevops => {
+            provider::AzureDevops::new(filter, token, args.api_url.clone(), pagination)?
+                .get_projects()?
+                .iter()
+                .map(provider::SnapshotProject::from_project)
+                .collect()
+        }
+    })
+}
+
+/// Opens `url` in the browser, or only prints it
+fn browse(url: &str, print: bool) {
+    if print {
+        println!("{url}");
+    } else if let Err(error) = browse::open_url(url) {
+        print_error(&format!("Error opening browser: {error}"));
+        process::exit(1);
+    }
+}
+
+/// Updates the state file of the worktree setup at `root` for other tools, see
+/// [`worktree::write_state()`]
+fn write_worktree_state(root: &Path) {
+    if repo::RepoHandle::detect_worktree(root) {
+        if let Err(error) = worktree::write_state(root) {
+            print_warning(&format!("Failed writing worktree state: {error}"));
+        }
+    }
+}
+
+fn open_worktree(directory: &Path, name: &str) {
+    let worktree_config = match repo::read_worktree_root_config(directory) {
+        Ok(config) => config,
+        Err(error) => {
+            print_error(&format!("Error getting worktree configuration: {error}"));
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = worktree::open_worktree(directory, name, &worktree_config) {
+        print_error(&format!("Error opening worktree: {error}"));
+        process::exit(1);
+    }
+}
+
+/// Removes all worktrees that match one of the `skip` patterns of the worktree
+/// configuration
+fn without_skipped(
+    worktrees: Vec<repo::Worktree>,
+    config: Option<&repo::WorktreeRootConfig>,
+) -> Vec<repo::Worktree> {
+    worktrees
+        .into_iter()
+        .filter(
+            |worktree| match repo::WorktreeRootConfig::skip_pattern(config, worktree.name()) {
+                Some(pattern) => {
+                    print_action(&format!(
+                        "{}: Skipped, matches \"{}\"",
+                        worktree.name(),
+                        pattern
+                    ));
+                    false
+                }
+                None => true,
+            },
+        )
+        .collect()
+}
+
+fn on_conflict(on_conflict: &cmd::OnConflict) -> tree::ConflictResolution {
+    match on_conflict {
+        cmd::OnConflict::Adopt => tree::ConflictResolution::Adopt,
+        cmd::OnConflict::MoveAside => tree::ConflictResolution::MoveAside,
+        cmd::OnConflict::Skip => tree::ConflictResolution::Skip,
+        cmd::OnConflict::Ask => tree::ConflictResolution::Ask,
+    }
+}
+
+/// Roots with unrelated content are only synced into after confirmation, or not
+/// at all if nobody can be asked
+fn existing_root(allow_existing_root: bool) -> tree::ExistingRoot {
+    if allow_existing_root {
+        tree::ExistingRoot::Allow
+    } else if std::io::stdin().is_terminal() {
+        tree::ExistingRoot::Ask
+    } else {
+        tree::ExistingRoot::Fail
+    }
+}
+
+/// `--force` skips all checks, the `--ignore-*` options single ones
+fn remove_overrides(
+    force: bool,
+    ignore_changes: bool,
+    ignore_unmerged: bool,
+    ignore_upstream_divergence: bool,
+) -> repo::WorktreeRemoveOverrides {
+    if force {
+        return repo::WorktreeRemoveOverrides::all();
+    }
+    repo::WorktreeRemoveOverrides {
+        ignore_changes,
+        ignore_unmerged,
+        ignore_upstream_divergence,
+    }
+}
+
+fn protocol(protocol: &cmd::Protocol) -> repo::RemoteType {
+    match protocol {
+        cmd::Protocol::Ssh => repo::RemoteType::Ssh,
+        cmd::Protocol::Https => repo::RemoteType::Https,
+    }
+}
+
+/// Makes the shell change into `directory`, if the shell integration is set up
+fn change_directory(directory: &Path) {
+    match shell::request_cd(directory) {
+        Ok(true) => (),
+        Ok(false) => print_warning(&format!(
+            "Shell integration is not set up, cannot change into \"{}\". See grm shell-init",
+            path::path_as_string(directory)
+        )),
+        Err(error) => print_error(&error),
+    }
+}
+
+/// Sends the notifications from the configuration file, if there are any
+fn send_notifications(
+    notify_config: Option<&config::NotifyConfig>,
+    operation: &str,
+    result: Result<tree::RunResult, String>,
+) {
+    if let Some(notify_config) = notify_config {
+        let report = match result {
+            Ok(result) => notify::Report::new(operation, result),
+            Err(error) => notify::Report::from_error(operation, error),
+        };
+        match report {
+            Ok(report) => {
+                for error in notify::send(&report, notify_config) {
+                    print_error(&error);
+                }
+            }
+            Err(error) => print_error(&error),
+        }
+    }
+}
+
+/// Prints the durations recorded for `--timings`, with the whole run measured
+/// from `start`
+fn print_timings(start: Instant) {
+    timings::record("total", None, start.elapsed());
+    for table in table::get_timings_tables(&timings::summary()) {
+        println!("{table}");
+    }
+}
+
+/// Reports all configured remotes that cannot be read, returns whether all of
+/// them can be read
+fn verify_remotes(config_path: &str, tree: Option<&str>, timeout: Duration) -> bool {
+    let result = config::read_config(config_path)
+        .and_then(|config| tree::verify_remotes(config, tree, timeout));
+    match result {
+        Ok((count, dead_remotes)) => {
+            if dead_remotes.is_empty() {
+                print_success(&format!("Verified {count} remotes, all are reachable"));
+                return true;
+            }
+            for dead_remote in &dead_remotes {
+                print_warning(&format!(
+                    "{}: Remote \"{}\" ({}) is not reachable: {}",
+                    dead_remote.repo, dead_remote.remote, dead_remote.url, dead_remote.reason
+                ));
+            }
+            false
+        }
+        Err(error) => {
+            print_error(&format!("Error verifying remotes: {error}"));
+            false
         }
     }
 }
@@ -0,0 +1,226 @@
+//! An interactive wizard that writes a starter configuration for a forge, see
+//! `grm init`
+//!
+//! The result is a configuration like the ones described in "Define options in
+//! a file" of the forge integration documentation. Access to the forge is
+//! checked before anything is written.
+
+use std::path::Path;
+
+use super::auth;
+use super::config;
+use super::output::*;
+use super::provider;
+
+/// Asks `question`, falling back to `default` for an empty answer
+fn ask(question: &str, default: Option<&str>) -> Result<String, String> {
+    let answer = match default {
+        Some(default) => prompt(&format!("{question} [{default}]"))?,
+        None => prompt(question)?,
+    };
+    Ok(match (answer.is_empty(), default) {
+        (true, Some(default)) => default.to_string(),
+        _ => answer,
+    })
+}
+
+fn ask_yes_no(question: &str, default: bool) -> Result<bool, String> {
+    loop {
+        let answer = prompt(&format!(
+            "{} {}",
+            question,
+            if default { "[Y/n]" } else { "[y/N]" }
+        ))?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => print_warning(&format!("Invalid answer \"{answer}\"")),
+        }
+    }
+}
+
+/// Asks for a comma-separated list, which may be empty
+fn ask_list(question: &str) -> Result<Vec<String>, String> {
+    Ok(
+        prompt(&format!("{question} (comma-separated, empty for none)"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(ToString::to_string)
+            .collect(),
+    )
+}
+
+fn ask_provider() -> Result<provider::RemoteProvider, String> {
+    loop {
+        let answer = ask(
+            "Forge (github, gitlab, bitbucket or azure-devops)",
+            Some("github"),
+        )?;
+        match answer.to_lowercase().as_str() {
+            "github" => return Ok(provider::RemoteProvider::Github),
+            "gitlab" => return Ok(provider::RemoteProvider::Gitlab),
+            "bitbucket" => return Ok(provider::RemoteProvider::Bitbucket),
+            "azure-devops" => return Ok(provider::RemoteProvider::AzureDevops),
+            _ => print_warning(&format!("Unknown forge \"{answer}\"")),
+        }
+    }
+}
+
+fn ask_token_command(provider: &provider::RemoteProvider) -> Result<String, String> {
+    let example = match provider {
+        provider::RemoteProvider::Github => "pass show github_token",
+        provider::RemoteProvider::Gitlab => "pass show gitlab_token",
+        provider::RemoteProvider::Bitbucket => "pass show bitbucket_token",
+        provider::RemoteProvider::AzureDevops => "pass show azure_devops_token",
+    };
+    loop {
+        let answer = ask(
+            &format!("Command that prints the API token (e.g. \"{example}\")"),
+            None,
+        )?;
+        if !answer.is_empty() {
+            return Ok(answer);
+        }
+        print_warning("The token command is required");
+    }
+}
+
+/// Checks that the forge can be reached with the token, returning the user name
+fn check_access(
+    provider: &provider::RemoteProvider,
+    token_command: &str,
+    api_url: Option<String>,
+) -> Result<String, String> {
+    let token = auth::get_token_from_command(token_command)
+        .map_err(|error| format!("Getting token from command failed: {error}"))?;
+    provider::current_user(provider, token, api_url)
+}
+
+/// Asks for all settings and writes the configuration to `config_path`
+pub fn run(config_path: &str) -> Result<(), String> {
+    if Path::new(config_path).exists() {
+        return Err(format!(
+            "Configuration file \"{config_path}\" already exists"
+        ));
+    }
+
+    let provider = ask_provider()?;
+    let api_url = Some(ask(
+        &format!(
+            "API URL (empty for {})",
+            match provider {
+                provider::RemoteProvider::Github => "github.com",
+                provider::RemoteProvider::Gitlab => "gitlab.com",
+                provider::RemoteProvider::Bitbucket => "bitbucket.org",
+                provider::RemoteProvider::AzureDevops => "dev.azure.com",
+            }
+        ),
+        None,
+    )?)
+    .filter(|api_url| !api_url.is_empty());
+    let token_command = ask_token_command(&provider)?;
+    let root = ask(
+        "Directory to clone the repositories into",
+        Some("~/projects"),
+    )?;
+
+    // Users do not have repositories of their own on Azure DevOps
+    let (owner, access, users) = match provider {
+        provider::RemoteProvider::AzureDevops => (
+            false,
+            ask_yes_no(
+                "Clone all repositories of the organizations you are a member of?",
+                false,
+            )?,
+            Vec::new(),
+        ),
+        _ => (
+            ask_yes_no("Clone all repositories that you own?", true)?,
+            ask_yes_no(
+                "Clone all other repositories that you have access to?",
+                false,
+            )?,
+            ask_list("Clone the repositories of these users")?,
+        ),
+    };
+    let (groups, orgs) = match provider {
+        provider::RemoteProvider::Github => (
+            Vec::new(),
+            ask_list("Clone the repositories of these organizations")?,
+        ),
+        provider::RemoteProvider::Gitlab => (
+            ask_list("Clone the repositories of these groups")?,
+            Vec::new(),
+        ),
+        provider::RemoteProvider::Bitbucket => (
+            ask_list("Clone the repositories of these workspaces")?,
+            Vec::new(),
+        ),
+        provider::RemoteProvider::AzureDevops => {
+            let orgs = ask_list("Clone the repositories of these organizations")?;
+            (
+                ask_list("Clone the repositories of these projects (organization/project)")?,
+                orgs,
+            )
+        }
+    };
+    if !owner && !access && users.is_empty() && groups.is_empty() && orgs.is_empty() {
+        print_warning("You did not specify any filters, so no repos will match");
+    }
+
+    let worktree = ask_yes_no("Use worktree setups for the repositories?", false)?;
+
+    print_action("Checking access to the forge");
+    match check_access(&provider, &token_command, api_url.clone()) {
+        Ok(user) => print_success(&format!("Logged in as \"{user}\"")),
+        Err(error) => {
+            print_error(&format!("Accessing the forge failed: {error}"));
+            if !ask_yes_no("Write the configuration anyway?", false)? {
+                return Err(String::from("No configuration written"));
+            }
+        }
+    }
+
+    let config = config::Config::ConfigProvider(config::ConfigProvider {
+        provider,
+        token_command: Some(token_command),
+        accounts: None,
+        github_app: None,
+        root,
+        namespace_roots: None,
+        tree_per_filter: None,
+        filters: Some(config::ConfigProviderFilter {
+            access: Some(access),
+            owner: Some(owner),
+            users: Some(users),
+            groups: Some(groups),
+            orgs: Some(orgs),
+            starred: None,
+            min_access_level: None,
+        }),
+        force_ssh: None,
+        api_url,
+        worktree: Some(worktree),
+        remote_name: None,
+        url_template: None,
+        per_page: None,
+        max_requests: None,
+        dir_mode: None,
+        notify: None,
+        fetch: None,
+        clone: None,
+        retry: None,
+        host_mappings: None,
+        hosts: None,
+        web_urls: None,
+    });
+
+    config::write_config(&config, config_path)?;
+
+    print_success(&format!(
+        "Configuration written to \"{config_path}\". Run \"grm repos sync config --config {config_path}\" to clone the repositories"
+    ));
+    Ok(())
+}
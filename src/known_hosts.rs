@@ -0,0 +1,339 @@
+//! Verification of SSH host keys against the `known_hosts` files of OpenSSH
+//!
+//! Instead of leaving this to libgit2, grm checks host keys itself, so the policy
+//! is explicit:
+//!
+//! * A key that matches an entry for the host is accepted.
+//! * A key that differs from a known key of the same type is always refused, as
+//!   this is what a man-in-the-middle attack looks like.
+//! * A host without a matching entry is refused, unless new host keys are
+//!   accepted (`--accept-new-hostkeys`). In that case, the key is added to
+//!   `~/.ssh/known_hosts` and a message with its fingerprint is printed.
+//!
+//! The lookup is done via `ssh-keygen -F`, so hashed entries work as well. Like
+//! OpenSSH, hosts on a port other than 22 are looked up and added as
+//! `[host]:port`.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use base64::Engine;
+
+use super::output::*;
+use super::path;
+
+const GLOBAL_KNOWN_HOSTS: &str = "/etc/ssh/ssh_known_hosts";
+
+static ACCEPT_NEW_HOST_KEYS: AtomicBool = AtomicBool::new(false);
+
+/// Accept (and remember) host keys of hosts that are not known yet
+///
+/// This is a process-wide setting, as the check happens deep inside every
+/// network operation.
+pub fn set_accept_new_host_keys(accept: bool) {
+    ACCEPT_NEW_HOST_KEYS.store(accept, Ordering::Relaxed);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Lookup {
+    Match,
+    Mismatch,
+    Unknown,
+}
+
+/// The SSH port in `url`, if it names one
+///
+/// Only `ssh://` URLs can contain a port, the scp-like syntax (`user@host:path`)
+/// always uses the default port.
+pub fn ssh_port(url: &str) -> Option<u16> {
+    let rest = url
+        .strip_prefix("ssh://")
+        .or_else(|| url.strip_prefix("git+ssh://"))?;
+    let authority = rest.split('/').next()?;
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    // IPv6 addresses are in brackets, so their colons are not mistaken for the port
+    let port = match host_port.rsplit_once(']') {
+        Some((_, after_address)) => after_address.strip_prefix(':')?,
+        None => host_port.rsplit_once(':')?.1,
+    };
+    port.parse().ok()
+}
+
+/// The name of `host` in known_hosts files, which includes the port unless it
+/// is the default one
+fn known_hosts_name(host: &str, port: Option<u16>) -> String {
+    match port {
+        None | Some(22) => host.to_string(),
+        Some(port) => format!("[{host}]:{port}"),
+    }
+}
+
+fn user_known_hosts() -> PathBuf {
+    Path::new(&path::env_home())
+        .join(".ssh")
+        .join("known_hosts")
+}
+
+/// Checks `key` against the entries for `host` in a single known_hosts file
+fn lookup(known_hosts: &Path, host: &str, key_type: &str, key: &str) -> Result<Lookup, String> {
+    if !known_hosts.exists() {
+        return Ok(Lookup::Unknown);
+    }
+
+    let output = process::Command::new("ssh-keygen")
+        .arg("-F")
+        .arg(host)
+        .arg("-f")
+        .arg(known_hosts)
+        .output()
+        .map_err(|error| format!("Failed to run ssh-keygen: {error}"))?;
+
+    // ssh-keygen exits with 1 if the host is not found
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(format!(
+            "ssh-keygen failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(parse_lookup(
+        &String::from_utf8_lossy(&output.stdout),
+        key_type,
+        key,
+    ))
+}
+
+fn parse_lookup(entries: &str, key_type: &str, key: &str) -> Lookup {
+    let mut result = Lookup::Unknown;
+
+    for line in entries.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let marker = if line.starts_with('@') {
+            fields.next()
+        } else {
+            None
+        };
+        let (Some(_hosts), Some(entry_key_type), Some(entry_key)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+
+        match marker {
+            None => (),
+            Some("@revoked") if entry_key == key => return Lookup::Mismatch,
+            // Certificate authorities are not supported
+            Some(_) => continue,
+        }
+
+        if entry_key_type != key_type {
+            continue;
+        }
+        if entry_key == key {
+            result = Lookup::Match;
+        } else if result == Lookup::Unknown {
+            result = Lookup::Mismatch;
+        }
+    }
+
+    result
+}
+
+fn add_host_key(known_hosts: &Path, host: &str, key_type: &str, key: &str) -> Result<(), String> {
+    if let Some(parent) = known_hosts.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|error| format!("Failed to create \"{}\": {}", parent.display(), error))?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(known_hosts)
+        .map_err(|error| format!("Failed to open \"{}\": {}", known_hosts.display(), error))?;
+
+    writeln!(file, "{host} {key_type} {key}")
+        .map_err(|error| format!("Failed to write \"{}\": {}", known_hosts.display(), error))
+}
+
+fn verify(
+    known_hosts: &[PathBuf],
+    host: &str,
+    key_type: &str,
+    key: &str,
+    fingerprint: &str,
+    accept_new: bool,
+) -> Result<(), String> {
+    let mut result = Lookup::Unknown;
+    for file in known_hosts {
+        match lookup(file, host, key_type, key)? {
+            Lookup::Match => return Ok(()),
+            Lookup::Mismatch => result = Lookup::Mismatch,
+            Lookup::Unknown => (),
+        }
+    }
+
+    match result {
+        Lookup::Match => Ok(()),
+        Lookup::Mismatch => Err(format!(
+            "The {key_type} host key of \"{host}\" does not match the one in known_hosts. \
+             Someone could be eavesdropping on you! Fingerprint of the offered key: {fingerprint}"
+        )),
+        Lookup::Unknown => {
+            if !accept_new {
+                return Err(format!(
+                    "Host \"{host}\" is not known. Verify that the fingerprint of its {key_type} key is \
+                     {fingerprint} and use --accept-new-hostkeys to add it to known_hosts"
+                ));
+            }
+            let user_known_hosts = &known_hosts[0];
+            add_host_key(user_known_hosts, host, key_type, key)?;
+            print_action(&format!(
+                "Added {key_type} host key of \"{host}\" ({fingerprint}) to \"{}\"",
+                user_known_hosts.display()
+            ));
+            Ok(())
+        }
+    }
+}
+
+/// Callback for [`git2::RemoteCallbacks::certificate_check`]
+///
+/// libgit2 only passes the host name, so the `port` has to come from the URL of
+/// the remote, see [`ssh_port`]. TLS certificates are left to libgit2.
+pub fn certificate_check(
+    cert: &git2::cert::Cert<'_>,
+    host: &str,
+    port: Option<u16>,
+) -> Result<git2::CertificateCheckStatus, git2::Error> {
+    let Some(hostkey) = cert.as_hostkey() else {
+        return Ok(git2::CertificateCheckStatus::CertificatePassthrough);
+    };
+
+    let (Some(key), Some(key_type), Some(hash)) = (
+        hostkey.hostkey(),
+        hostkey.hostkey_type(),
+        hostkey.hash_sha256(),
+    ) else {
+        return Err(git2::Error::from_str(&format!(
+            "Could not get the host key of \"{host}\""
+        )));
+    };
+
+    let key = base64::engine::general_purpose::STANDARD.encode(key);
+    let fingerprint = format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(hash)
+    );
+
+    verify(
+        &[user_known_hosts(), PathBuf::from(GLOBAL_KNOWN_HOSTS)],
+        &known_hosts_name(host, port),
+        key_type.name(),
+        &key,
+        &fingerprint,
+        ACCEPT_NEW_HOST_KEYS.load(Ordering::Relaxed),
+    )
+    .map_err(|error| git2::Error::from_str(&error))?;
+
+    Ok(git2::CertificateCheckStatus::CertificateOk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    #[test]
+    fn check_parse_lookup() {
+        assert_eq!(parse_lookup("", "ssh-ed25519", KEY), Lookup::Unknown);
+        assert_eq!(
+            parse_lookup(
+                &format!("# Host github.com found: line 1\ngithub.com ssh-ed25519 {KEY}\n"),
+                "ssh-ed25519",
+                KEY
+            ),
+            Lookup::Match
+        );
+        assert_eq!(
+            parse_lookup(
+                &format!("|1|c2FsdA==|aGFzaA== ssh-ed25519 {KEY} comment"),
+                "ssh-ed25519",
+                KEY
+            ),
+            Lookup::Match
+        );
+        assert_eq!(
+            parse_lookup("github.com ssh-ed25519 AAAAother", "ssh-ed25519", KEY),
+            Lookup::Mismatch
+        );
+        // One of several keys matching is enough
+        assert_eq!(
+            parse_lookup(
+                &format!("github.com ssh-ed25519 AAAAother\ngithub.com ssh-ed25519 {KEY}"),
+                "ssh-ed25519",
+                KEY
+            ),
+            Lookup::Match
+        );
+        // A key of another type does not say anything about this one
+        assert_eq!(
+            parse_lookup("github.com ssh-rsa AAAAother", "ssh-ed25519", KEY),
+            Lookup::Unknown
+        );
+        assert_eq!(
+            parse_lookup(
+                &format!("@revoked github.com ssh-ed25519 {KEY}"),
+                "ssh-ed25519",
+                KEY
+            ),
+            Lookup::Mismatch
+        );
+        assert_eq!(
+            parse_lookup(
+                &format!("@cert-authority *.example.com ssh-ed25519 {KEY}"),
+                "ssh-ed25519",
+                KEY
+            ),
+            Lookup::Unknown
+        );
+    }
+
+    #[test]
+    fn check_ssh_port() {
+        assert_eq!(
+            ssh_port("ssh://git@example.com:2222/me/repo.git"),
+            Some(2222)
+        );
+        assert_eq!(ssh_port("ssh://example.com:2222/repo"), Some(2222));
+        assert_eq!(ssh_port("ssh://git@example.com/me/repo.git"), None);
+        assert_eq!(ssh_port("ssh://git@[::1]:2222/repo"), Some(2222));
+        assert_eq!(ssh_port("ssh://git@[::1]/repo"), None);
+        // The scp-like syntax has no port, the part after the colon is the path
+        assert_eq!(ssh_port("git@example.com:2222/repo.git"), None);
+        assert_eq!(ssh_port("https://example.com:8443/repo.git"), None);
+    }
+
+    #[test]
+    fn check_known_hosts_name() {
+        assert_eq!(known_hosts_name("example.com", None), "example.com");
+        assert_eq!(known_hosts_name("example.com", Some(22)), "example.com");
+        assert_eq!(
+            known_hosts_name("example.com", Some(2222)),
+            "[example.com]:2222"
+        );
+        assert_eq!(
+            known_hosts_name("example.com", ssh_port("ssh://git@example.com:2222/repo")),
+            "[example.com]:2222"
+        );
+    }
+}
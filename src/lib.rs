@@ -1,17 +1,168 @@
 #![forbid(unsafe_code)]
 
+use std::collections::HashSet;
 use std::path::Path;
 
 pub mod auth;
+pub mod browse;
 pub mod config;
+pub mod daemon;
+pub mod fork;
+pub mod init;
+pub mod known_hosts;
+pub mod lock;
+pub mod metrics;
+pub mod notify;
 pub mod output;
 pub mod path;
+pub mod profile;
+pub mod prompt;
 pub mod provider;
 pub mod repo;
+pub mod report;
+pub mod secrets;
+pub mod serve;
+pub mod shell;
+pub mod state;
+pub mod stats;
 pub mod table;
+pub mod template;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timings;
 pub mod tree;
 pub mod worktree;
 
+/// A repository or remote that was found but could not be added to the
+/// configuration
+pub struct Skipped {
+    /// The name of the repository, including the namespace
+    pub name: String,
+    pub worktree_setup: bool,
+    /// Name and URL of the remote, if only the remote was skipped
+    pub remote: Option<(String, String)>,
+    pub reason: String,
+}
+
+fn push_comment(out: &mut String, indent: &str, text: &str) {
+    for line in text.lines() {
+        out.push_str(indent);
+        out.push_str("# ");
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Describes a skipped repository or remote in a single line, for formats that
+/// cannot contain comments (i.e. JSON)
+pub fn skipped_message(entry: &Skipped) -> String {
+    match &entry.remote {
+        None => format!("Skipped {}: {}", entry.name, entry.reason),
+        Some((name, _url)) => format!(
+            "Skipped remote {} of {}: {}",
+            name, entry.name, entry.reason
+        ),
+    }
+}
+
+/// Commented-out configuration entries for skipped repositories and remotes,
+/// to be appended to the TOML configuration of a single tree
+pub fn skipped_as_toml(skipped: &[Skipped]) -> String {
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let mut out = String::new();
+    for entry in skipped {
+        out.push('\n');
+        push_comment(&mut out, "", &format!("Skipped: {}", entry.reason));
+        let stub = match &entry.remote {
+            None => format!(
+                "[[trees.repos]]\nname = {}\nworktree_setup = {}",
+                quote(&entry.name),
+                entry.worktree_setup
+            ),
+            Some((name, url)) => format!(
+                "Remote of repository {}:\n[[trees.repos.remotes]]\nname = {}\nurl = {}",
+                quote(&entry.name),
+                quote(name),
+                quote(url)
+            ),
+        };
+        push_comment(&mut out, "", &stub);
+    }
+    out
+}
+
+/// Commented-out configuration entries for skipped repositories and remotes,
+/// to be appended to the YAML configuration of a single tree
+pub fn skipped_as_yaml(skipped: &[Skipped]) -> Result<String, String> {
+    let quote = |value: &str| {
+        serde_yaml::to_string(value)
+            .map(|value| value.trim_end().to_string())
+            .map_err(|error| error.to_string())
+    };
+    let mut out = String::new();
+    for entry in skipped {
+        push_comment(&mut out, "  ", &format!("Skipped: {}", entry.reason));
+        let stub = match &entry.remote {
+            None => format!(
+                "- name: {}\n  worktree_setup: {}",
+                quote(&entry.name)?,
+                entry.worktree_setup
+            ),
+            Some((name, url)) => format!(
+                "Remote of repository {}:\n  - name: {}\n    url: {}",
+                quote(&entry.name)?,
+                quote(name)?,
+                quote(url)?
+            ),
+        };
+        push_comment(&mut out, "  ", &stub);
+    }
+    Ok(out)
+}
+
+/// The name of the repository behind `remotes`, taken from the URL of `origin`
+/// or the first remote, e.g. `me/dotfiles` for `git@github.com:me/dotfiles.git`
+///
+/// URLs without a project path, like `file://` URLs, only give the last path
+/// component.
+fn remote_repo_name(remotes: &[repo::Remote]) -> Option<String> {
+    let remote = remotes
+        .iter()
+        .find(|remote| remote.name == "origin")
+        .or_else(|| remotes.first())?;
+    let name = provider::project_path(&remote.url).ok().or_else(|| {
+        let url = remote.url.trim_end_matches('/');
+        let name = url.rsplit(['/', ':']).next()?;
+        Some(name.strip_suffix(".git").unwrap_or(name).to_string())
+    })?;
+    path::check_subpath(&name).ok().map(|()| name)
+}
+
+/// Names the repositories in directories that are named differently than their
+/// remote repository like the remote repository, with the directory as `dir`
+///
+/// A repository keeps the name of its directory if the name of the remote
+/// repository is already taken by another one.
+fn apply_remote_names(repos: &mut [repo::Repo]) {
+    // Like everywhere in the search, the name is the path relative to the root
+    let mut taken: HashSet<String> = repos.iter().map(|repo| repo.name.clone()).collect();
+    for repo in repos.iter_mut() {
+        let Some(remote_name) = repo.remotes.as_deref().and_then(remote_repo_name) else {
+            continue;
+        };
+        if remote_name.rsplit('/').next() == repo.name.rsplit('/').next()
+            || taken.contains(&remote_name)
+        {
+            continue;
+        }
+        repo.namespace = remote_name
+            .rsplit_once('/')
+            .map(|(namespace, _)| namespace.to_string());
+        repo.dir = Some(std::mem::replace(&mut repo.name, remote_name.clone()));
+        taken.insert(remote_name);
+    }
+}
+
 /// Find all git repositories under root, recursively
 ///
 /// The bool in the return value specifies whether there is a repository
@@ -20,16 +171,28 @@ pub mod worktree;
 fn find_repos(
     root: &Path,
     exclusion_pattern: Option<&str>,
-) -> Result<Option<(Vec<repo::Repo>, Vec<String>, bool)>, String> {
+    follow_symlinks: bool,
+    verbose: bool,
+    skip_jj: bool,
+) -> Result<Option<(Vec<repo::Repo>, Vec<String>, Vec<Skipped>, bool)>, String> {
     let mut repos: Vec<repo::Repo> = Vec::new();
     let mut repo_in_root = false;
     let mut warnings = Vec::new();
+    let mut skipped = Vec::new();
+    let mut excluded = Vec::new();
 
     let exlusion_regex: regex::Regex = regex::Regex::new(exclusion_pattern.unwrap_or(r"^$"))
         .map_err(|e| format!("invalid regex: {e}"))?;
-    for path in tree::find_repo_paths(root)? {
+    for path in tree::find_repo_paths(root, follow_symlinks)? {
         if exclusion_pattern.is_some() && exlusion_regex.is_match(&path::path_as_string(&path)) {
-            warnings.push(format!("[skipped] {}", &path::path_as_string(&path)));
+            excluded.push(path::path_as_string(&path));
+            continue;
+        }
+        if skip_jj && repo::RepoHandle::detect_jj(&path) {
+            warnings.push(format!(
+                "[skipped] {}: Colocated with jj",
+                path::path_as_string(&path)
+            ));
             continue;
         }
 
@@ -38,25 +201,77 @@ fn find_repos(
             repo_in_root = true;
         }
 
+        let (namespace, name) = if path == root {
+            (
+                None,
+                match &root.parent() {
+                    Some(parent) => path::path_as_string(path.strip_prefix(parent).unwrap()),
+                    None => {
+                        warnings.push(String::from("Getting name of the search root failed. Do you have a git repository in \"/\"?"));
+                        continue;
+                    }
+                },
+            )
+        } else {
+            let name = path.strip_prefix(root).unwrap();
+            let namespace = name.parent().unwrap();
+            (
+                if namespace == Path::new("") {
+                    None
+                } else {
+                    Some(path::path_as_string(namespace).to_string())
+                },
+                path::path_as_string(name),
+            )
+        };
+        let repo_name = name.clone();
+
+        // The name of the repository and its path relative to the root are the
+        // same, so it cannot be expressed in the configuration
+        if let Err(error) = path::path_as_utf8(&path) {
+            warnings.push(format!("[skipped] {error}"));
+            skipped.push(Skipped {
+                name: repo_name,
+                worktree_setup: is_worktree,
+                remote: None,
+                reason: error,
+            });
+            continue;
+        }
+
         match repo::RepoHandle::open(&path, is_worktree) {
             Err(error) => {
-                warnings.push(format!(
+                let warning = format!(
                     "Error opening repo {}{}: {}",
                     path.display(),
                     if is_worktree { " as worktree" } else { "" },
                     error
-                ));
+                );
+                skipped.push(Skipped {
+                    name: repo_name,
+                    worktree_setup: is_worktree,
+                    remote: None,
+                    reason: warning.clone(),
+                });
+                warnings.push(warning);
                 continue;
             }
             Ok(repo) => {
                 let remotes = match repo.remotes() {
                     Ok(remote) => remote,
                     Err(error) => {
-                        warnings.push(format!(
+                        let warning = format!(
                             "{}: Error getting remotes: {}",
                             &path::path_as_string(&path),
                             error
-                        ));
+                        );
+                        skipped.push(Skipped {
+                            name: repo_name,
+                            worktree_setup: is_worktree,
+                            remote: None,
+                            reason: warning.clone(),
+                        });
+                        warnings.push(warning);
                         continue;
                     }
                 };
@@ -70,12 +285,19 @@ fn find_repos(
                             let remote_type = match repo::detect_remote_type(&url) {
                                 Ok(t) => t,
                                 Err(e) => {
-                                    warnings.push(format!(
+                                    let warning = format!(
                                         "{}: Could not handle URL {}. Reason: {}",
                                         &path::path_as_string(&path),
                                         &url,
                                         e
-                                    ));
+                                    );
+                                    skipped.push(Skipped {
+                                        name: repo_name.clone(),
+                                        worktree_setup: is_worktree,
+                                        remote: Some((name, url)),
+                                        reason: warning.clone(),
+                                    });
+                                    warnings.push(warning);
                                     continue;
                                 }
                             };
@@ -98,58 +320,71 @@ fn find_repos(
                 }
                 let remotes = results;
 
-                let (namespace, name) = if path == root {
-                    (
-                        None,
-                        match &root.parent() {
-                            Some(parent) => {
-                                path::path_as_string(path.strip_prefix(parent).unwrap())
-                            }
-                            None => {
-                                warnings.push(String::from("Getting name of the search root failed. Do you have a git repository in \"/\"?"));
-                                continue;
-                            }
-                        },
-                    )
-                } else {
-                    let name = path.strip_prefix(root).unwrap();
-                    let namespace = name.parent().unwrap();
-                    (
-                        if namespace == Path::new("") {
-                            None
-                        } else {
-                            Some(path::path_as_string(namespace).to_string())
-                        },
-                        path::path_as_string(name),
-                    )
-                };
-
                 repos.push(repo::Repo {
                     name,
                     namespace,
                     remotes: Some(remotes),
                     worktree_setup: is_worktree,
+                    provider_id: repo.provider_id().unwrap_or(None),
+                    tags: Vec::new(),
+                    filter: None,
+                    default_branch: None,
+                    dir: None,
+                    remote_name: None,
                 });
             }
         }
     }
-    Ok(Some((repos, warnings, repo_in_root)))
+    if let Some(pattern) = exclusion_pattern {
+        warnings.extend(exclusion_warnings(excluded, pattern, verbose));
+    }
+    // With a repository in the root, the tree is rooted at its parent, so the
+    // directories of the repositories are not relative to the tree root
+    if !repo_in_root {
+        apply_remote_names(&mut repos);
+    }
+    Ok(Some((repos, warnings, skipped, repo_in_root)))
 }
 
+/// Warnings about the paths that were skipped because they match the exclusion
+/// pattern. Unless `verbose` is set, multiple paths are summarized in a single
+/// warning.
+fn exclusion_warnings(excluded: Vec<String>, pattern: &str, verbose: bool) -> Vec<String> {
+    if verbose || excluded.len() <= 1 {
+        excluded
+            .into_iter()
+            .map(|path| format!("[skipped] {path}"))
+            .collect()
+    } else {
+        vec![format!(
+            "[skipped] {} paths matching \"{pattern}\", use --verbose to list them",
+            excluded.len()
+        )]
+    }
+}
+
+/// Finds all repositories under `path`
+///
+/// Besides the tree, this returns warnings and the repositories and remotes
+/// that were skipped because of errors.
+#[allow(clippy::type_complexity)]
 pub fn find_in_tree(
     path: &Path,
     exclusion_pattern: Option<&str>,
-) -> Result<(tree::Tree, Vec<String>), String> {
+    follow_symlinks: bool,
+    verbose: bool,
+    skip_jj: bool,
+) -> Result<(tree::Tree, Vec<String>, Vec<Skipped>), String> {
     let mut warnings = Vec::new();
 
-    let (repos, repo_in_root): (Vec<repo::Repo>, bool) = match find_repos(path, exclusion_pattern)?
-    {
-        Some((vec, mut repo_warnings, repo_in_root)) => {
-            warnings.append(&mut repo_warnings);
-            (vec, repo_in_root)
-        }
-        None => (Vec::new(), false),
-    };
+    let (repos, skipped, repo_in_root): (Vec<repo::Repo>, Vec<Skipped>, bool) =
+        match find_repos(path, exclusion_pattern, follow_symlinks, verbose, skip_jj)? {
+            Some((vec, mut repo_warnings, skipped, repo_in_root)) => {
+                warnings.append(&mut repo_warnings);
+                (vec, skipped, repo_in_root)
+            }
+            None => (Vec::new(), Vec::new(), false),
+        };
 
     let mut root = path.to_path_buf();
     if repo_in_root {
@@ -165,9 +400,170 @@ pub fn find_in_tree(
 
     Ok((
         tree::Tree {
-            root: root.into_os_string().into_string().unwrap(),
+            root: path::path_as_utf8(&root)?.to_string(),
             repos,
         },
         warnings,
+        skipped,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_exclusion_warnings() {
+        let paths = || vec![String::from("/a/one"), String::from("/a/two")];
+
+        assert_eq!(
+            exclusion_warnings(paths(), "^/a/", false),
+            vec!["[skipped] 2 paths matching \"^/a/\", use --verbose to list them"]
+        );
+        assert_eq!(
+            exclusion_warnings(paths(), "^/a/", true),
+            vec!["[skipped] /a/one", "[skipped] /a/two"]
+        );
+        assert_eq!(
+            exclusion_warnings(vec![String::from("/a/one")], "^/a/", false),
+            vec!["[skipped] /a/one"]
+        );
+        assert!(exclusion_warnings(Vec::new(), "^/a/", false).is_empty());
+    }
+
+    #[test]
+    fn check_skipped_as_toml() {
+        let skipped = [
+            Skipped {
+                name: String::from("ns/broken"),
+                worktree_setup: true,
+                remote: None,
+                reason: String::from("Error opening repo"),
+            },
+            Skipped {
+                name: String::from("a"),
+                worktree_setup: false,
+                remote: Some((String::from("weird"), String::from("foo \"bar\""))),
+                reason: String::from("Could not handle URL"),
+            },
+        ];
+
+        assert_eq!(
+            skipped_as_toml(&skipped),
+            "\n\
+             # Skipped: Error opening repo\n\
+             # [[trees.repos]]\n\
+             # name = \"ns/broken\"\n\
+             # worktree_setup = true\n\
+             \n\
+             # Skipped: Could not handle URL\n\
+             # Remote of repository \"a\":\n\
+             # [[trees.repos.remotes]]\n\
+             # name = \"weird\"\n\
+             # url = 'foo \"bar\"'\n"
+        );
+        assert_eq!(
+            skipped_as_yaml(&skipped[..1]).unwrap(),
+            "  # Skipped: Error opening repo\n  \
+             # - name: ns/broken\n  \
+             #   worktree_setup: true\n"
+        );
+    }
+
+    fn found_repo(dir: &str, remotes: &[(&str, &str)]) -> repo::Repo {
+        repo::Repo {
+            name: dir.to_string(),
+            namespace: dir
+                .rsplit_once('/')
+                .map(|(namespace, _)| namespace.to_string()),
+            remotes: Some(
+                remotes
+                    .iter()
+                    .map(|(name, url)| repo::Remote {
+                        name: name.to_string(),
+                        url: url.to_string(),
+                        remote_type: repo::detect_remote_type(url).unwrap(),
+                    })
+                    .collect(),
+            ),
+            worktree_setup: false,
+            provider_id: None,
+            tags: Vec::new(),
+            filter: None,
+            default_branch: None,
+            dir: None,
+            remote_name: None,
+        }
+    }
+
+    #[test]
+    fn check_remote_repo_name() {
+        let name = |remotes: &[(&str, &str)]| {
+            remote_repo_name(found_repo("repo", remotes).remotes.as_deref().unwrap())
+        };
+
+        assert_eq!(
+            name(&[("origin", "git@github.com:me/dotfiles.git")]),
+            Some(String::from("me/dotfiles"))
+        );
+        assert_eq!(
+            name(&[
+                ("fork", "https://github.com/me/fork.git"),
+                ("origin", "https://gitlab.com/group/subgroup/repo.git"),
+            ]),
+            Some(String::from("group/subgroup/repo"))
+        );
+        assert_eq!(
+            name(&[("upstream", "https://github.com/other/project")]),
+            Some(String::from("other/project"))
+        );
+        assert_eq!(
+            name(&[("origin", "file:///srv/git/project.git")]),
+            Some(String::from("project"))
+        );
+        assert_eq!(
+            name(&[("origin", "https://example.com/repo.git")]),
+            Some(String::from("repo"))
+        );
+        assert_eq!(name(&[]), None);
+    }
+
+    #[test]
+    fn check_apply_remote_names() {
+        let mut repos = vec![
+            found_repo(
+                "dotfiles",
+                &[("origin", "git@github.com:me/my-dotfiles-2024.git")],
+            ),
+            found_repo(
+                "work/grm",
+                &[("origin", "file:///srv/git-repo-manager.git")],
+            ),
+            found_repo("same", &[("origin", "https://github.com/me/same.git")]),
+            found_repo("work/same", &[("origin", "https://github.com/me/same.git")]),
+            found_repo("nothing", &[]),
+            // Would clash with the repository in "taken"
+            found_repo("other", &[("origin", "https://example.com/taken.git")]),
+            found_repo("taken", &[("origin", "https://example.com/taken.git")]),
+        ];
+        apply_remote_names(&mut repos);
+
+        let names: Vec<(String, Option<&str>)> = repos
+            .iter()
+            .map(|repo| (repo.name.clone(), repo.dir.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (String::from("me/my-dotfiles-2024"), Some("dotfiles")),
+                (String::from("git-repo-manager"), Some("work/grm")),
+                (String::from("same"), None),
+                (String::from("work/same"), None),
+                (String::from("nothing"), None),
+                (String::from("other"), None),
+                (String::from("taken"), None),
+            ]
+        );
+        assert_eq!(repos[0].namespace.as_deref(), Some("me"));
+    }
+}
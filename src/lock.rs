@@ -0,0 +1,215 @@
+//! Simple lock files to make sure that only a single process works on something
+//! at a time.
+//!
+//! The lock itself is an advisory lock (`flock`) on the lock file, not the file
+//! itself. The file stays in place, and the lock is released by the kernel when
+//! the process holding it exits, no matter how. So there are no stale locks to
+//! clean up.
+//!
+//! Apart from the daemon lock, there are two kinds of locks that keep concurrent
+//! grm invocations from stepping on each other's toes:
+//!
+//! * A lock per tree root, held while syncing the tree. This makes sure that two
+//!   syncs do not clone or move the same repository at the same time.
+//! * A lock per repository, held while modifying it (sync, fetch, worktree
+//!   operations).
+//!
+//! These lock files are kept in a separate directory (see [`lock_dir`]) instead of
+//! the trees themselves, so taking a lock does not modify the repositories.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::output::*;
+use super::path;
+
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do when a lock is held by another process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// Wait until the other process releases the lock
+    Wait,
+    /// Fail immediately
+    NoWait,
+}
+
+pub struct LockFile {
+    path: PathBuf,
+    // The lock is held as long as the file is open
+    _file: fs::File,
+}
+
+impl LockFile {
+    /// Tries to acquire the lock at `path`
+    ///
+    /// Returns `Ok(None)` if the lock is held by another process.
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>, String> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|error| {
+                format!(
+                    "Failed opening lock file \"{}\": {}",
+                    path::path_as_string(path),
+                    error
+                )
+            })?;
+
+        if !try_lock(&file).map_err(|error| {
+            format!(
+                "Failed locking \"{}\": {}",
+                path::path_as_string(path),
+                error
+            )
+        })? {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            _file: file,
+        }))
+    }
+
+    /// Acquires the lock at `path`, waiting for other processes if `policy` says so
+    ///
+    /// `description` names the locked thing in messages, e.g. `Repository "foo"`.
+    pub fn acquire(path: &Path, policy: LockPolicy, description: &str) -> Result<Self, String> {
+        let mut waiting = false;
+        loop {
+            if let Some(lock) = Self::try_acquire(path)? {
+                return Ok(lock);
+            }
+            match policy {
+                LockPolicy::NoWait => {
+                    return Err(format!("{description} is locked by another grm process"))
+                }
+                LockPolicy::Wait => {
+                    if !waiting {
+                        print_action(&format!(
+                            "{description} is locked by another grm process, waiting"
+                        ));
+                        waiting = true;
+                    }
+                    std::thread::sleep(WAIT_INTERVAL);
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Takes an exclusive lock on `file` without blocking, returning whether that
+/// worked
+#[cfg(unix)]
+fn try_lock(file: &fs::File) -> std::io::Result<bool> {
+    use rustix::fs::{flock, FlockOperation};
+
+    match flock(file, FlockOperation::NonBlockingLockExclusive) {
+        Ok(()) => Ok(true),
+        Err(rustix::io::Errno::WOULDBLOCK) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Advisory locks are only available on unix, so there is no locking elsewhere
+#[cfg(not(unix))]
+fn try_lock(_file: &fs::File) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+/// The directory containing the lock files for trees and repositories
+///
+/// This is `$XDG_RUNTIME_DIR/grm-locks`, falling back to a directory per user in
+/// the temporary directory if `$XDG_RUNTIME_DIR` is not set.
+pub fn lock_dir() -> PathBuf {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("grm-locks"),
+        _ => fallback_lock_dir(),
+    }
+}
+
+/// The temporary directory is shared between users, so the directory is named
+/// after the user ID, which unlike `$USER` is always known
+#[cfg(unix)]
+fn fallback_lock_dir() -> PathBuf {
+    env::temp_dir().join(format!("grm-locks-{}", rustix::process::getuid().as_raw()))
+}
+
+#[cfg(not(unix))]
+fn fallback_lock_dir() -> PathBuf {
+    env::temp_dir().join("grm-locks")
+}
+
+/// FNV-1a, which is stable across builds, unlike the hashers of the standard library
+pub fn hash(input: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in input {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Returns the lock file for `path`, which does not have to exist
+fn lock_path(kind: &str, path: &Path) -> Result<PathBuf, String> {
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(_) if path.is_absolute() => path.to_path_buf(),
+        Err(_) => env::current_dir()
+            .map_err(|error| format!("Could not get current directory: {error}"))?
+            .join(path),
+    };
+
+    let dir = lock_dir();
+    fs::create_dir_all(&dir).map_err(|error| {
+        format!(
+            "Failed creating lock directory \"{}\": {}",
+            path::path_as_string(&dir),
+            error
+        )
+    })?;
+
+    Ok(dir.join(format!(
+        "{kind}-{:016x}.lock",
+        hash(path.as_os_str().as_encoded_bytes())
+    )))
+}
+
+/// Locks the tree at `root_path` against concurrent syncs
+pub fn lock_tree(root_path: &Path, policy: LockPolicy) -> Result<LockFile, String> {
+    LockFile::acquire(
+        &lock_path("tree", root_path)?,
+        policy,
+        &format!("Tree \"{}\"", path::path_as_string(root_path)),
+    )
+}
+
+/// Locks the repository at `repo_path` against concurrent modifications
+pub fn lock_repo(repo_path: &Path, policy: LockPolicy) -> Result<LockFile, String> {
+    LockFile::acquire(
+        &lock_path("repo", repo_path)?,
+        policy,
+        &format!("Repository \"{}\"", path::path_as_string(repo_path)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_hash() {
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(hash(b"/tmp/a"), hash(b"/tmp/b"));
+    }
+}
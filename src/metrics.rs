@@ -0,0 +1,237 @@
+//! Metrics about all repositories of a configuration in the Prometheus text
+//! format, see `grm repos metrics`
+//!
+//! The output is meant for the textfile collector of the node exporter. All
+//! metrics are gauges.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use super::config;
+use super::path;
+use super::repo;
+use super::state;
+
+type Labels = Vec<(&'static str, String)>;
+
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    samples: Vec<(Labels, u64)>,
+}
+
+impl Metric {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            samples: Vec::new(),
+        }
+    }
+}
+
+/// Escapes a label value, see
+/// <https://prometheus.io/docs/instrumenting/exposition_formats/#text-format-details>
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render(metrics: &[Metric]) -> String {
+    let mut out = String::new();
+    for metric in metrics {
+        writeln!(out, "# HELP {} {}", metric.name, metric.help).unwrap();
+        writeln!(out, "# TYPE {} gauge", metric.name).unwrap();
+        for (labels, value) in &metric.samples {
+            let labels = labels
+                .iter()
+                .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+                .collect::<Vec<String>>()
+                .join(",");
+            writeln!(out, "{}{{{}}} {}", metric.name, labels, value).unwrap();
+        }
+    }
+    out
+}
+
+/// Returns the metrics of all repositories, together with warnings about
+/// repositories that could not be inspected
+pub fn get_metrics(config: config::Config) -> Result<(String, Vec<String>), String> {
+    let mut warnings = Vec::new();
+
+    let mut present = Metric::new(
+        "grm_repo_present",
+        "Whether the repository exists on disk (1) or not (0)",
+    );
+    let mut dirty = Metric::new(
+        "grm_repo_dirty",
+        "Whether the repository has uncommitted changes (1) or not (0)",
+    );
+    let mut check_failures = Metric::new(
+        "grm_repo_check_failures",
+        "Number of problems reported by grm repos status --check",
+    );
+    let mut ahead = Metric::new(
+        "grm_branch_ahead_commits",
+        "Number of commits of a local branch that are not on its remote branch",
+    );
+    let mut behind = Metric::new(
+        "grm_branch_behind_commits",
+        "Number of commits of the remote branch that are not on the local branch",
+    );
+    let mut last_fetch = Metric::new(
+        "grm_repo_last_fetch_timestamp_seconds",
+        "Unix timestamp of the last fetch of the repository",
+    );
+    let mut last_sync = Metric::new(
+        "grm_repo_last_sync_timestamp_seconds",
+        "Unix timestamp of the last successful grm repos sync of the repository",
+    );
+
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        let tree_state = state::read_state(&tree.state_root()).unwrap_or_else(|error| {
+            warnings.push(error);
+            state::TreeState::default()
+        });
+
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            let labels: Labels = vec![("tree", tree.root.clone()), ("repo", repo.fullname())];
+
+            if let Some(timestamp) = tree_state.last_sync(&repo.fullname()) {
+                last_sync.samples.push((labels.clone(), timestamp));
+            }
+
+            let repo_path = root_path.join(repo.directory());
+            if !repo_path.exists() {
+                present.samples.push((labels, 0));
+                continue;
+            }
+            present.samples.push((labels.clone(), 1));
+
+            let repo_handle = match repo::RepoHandle::open(&repo_path, repo.worktree_setup) {
+                Ok(repo_handle) => repo_handle,
+                Err(error) => {
+                    warnings.push(format!(
+                        "{}: Opening repository failed: {}",
+                        repo.fullname(),
+                        error
+                    ));
+                    continue;
+                }
+            };
+
+            if let Some(timestamp) = repo_handle.last_fetch_time() {
+                last_fetch.samples.push((labels.clone(), timestamp));
+            }
+
+            let status = match repo_handle.status(repo.worktree_setup) {
+                Ok(status) => status,
+                Err(error) => {
+                    warnings.push(format!(
+                        "{}: Couldn't get repo status: {}",
+                        repo.fullname(),
+                        error
+                    ));
+                    continue;
+                }
+            };
+
+            dirty
+                .samples
+                .push((labels.clone(), u64::from(status.dirty())));
+            check_failures
+                .samples
+                .push((labels.clone(), status.check_failures().len() as u64));
+
+            for (branch_name, remote_branch) in &status.branches {
+                let Some((_, tracking_status)) = remote_branch else {
+                    continue;
+                };
+                let (ahead_count, behind_count) = match tracking_status {
+                    repo::RemoteTrackingStatus::UpToDate => (0, 0),
+                    repo::RemoteTrackingStatus::Ahead(d) => (*d, 0),
+                    repo::RemoteTrackingStatus::Behind(d) => (0, *d),
+                    repo::RemoteTrackingStatus::Diverged(d1, d2) => (*d1, *d2),
+                };
+                let mut branch_labels = labels.clone();
+                branch_labels.push(("branch", branch_name.clone()));
+                ahead
+                    .samples
+                    .push((branch_labels.clone(), ahead_count as u64));
+                behind.samples.push((branch_labels, behind_count as u64));
+            }
+        }
+    }
+
+    Ok((
+        render(&[
+            present,
+            dirty,
+            check_failures,
+            ahead,
+            behind,
+            last_fetch,
+            last_sync,
+        ]),
+        warnings,
+    ))
+}
+
+/// Writes the metrics to `path`
+///
+/// The file is replaced atomically, so a collector never reads a half-written
+/// file.
+pub fn write_metrics(metrics: &str, path: &Path) -> Result<(), String> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let temporary_path = Path::new(&temporary_path);
+
+    std::fs::write(temporary_path, metrics)
+        .map_err(|error| format!("Error writing \"{}\": {}", temporary_path.display(), error))?;
+    std::fs::rename(temporary_path, path).map_err(|error| {
+        format!(
+            "Error moving \"{}\" to \"{}\": {}",
+            temporary_path.display(),
+            path.display(),
+            error
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_render() {
+        let mut metric = Metric::new("grm_repo_dirty", "Whether the repository is dirty");
+        metric
+            .samples
+            .push((vec![("repo", String::from("ns/a"))], 1));
+        metric.samples.push((
+            vec![
+                ("repo", String::from("b")),
+                ("branch", String::from("say \"hi\"\\\n")),
+            ],
+            0,
+        ));
+
+        assert_eq!(
+            render(&[metric, Metric::new("grm_empty", "Nothing")]),
+            "# HELP grm_repo_dirty Whether the repository is dirty\n\
+             # TYPE grm_repo_dirty gauge\n\
+             grm_repo_dirty{repo=\"ns/a\"} 1\n\
+             grm_repo_dirty{repo=\"b\",branch=\"say \\\"hi\\\"\\\\\\n\"} 0\n\
+             # HELP grm_empty Nothing\n\
+             # TYPE grm_empty gauge\n"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Reports about syncs and fetches, and notifications that send them somewhere
+//!
+//! Notifications are configured in the `notify` section of the configuration.
+//! The report is a JSON document that is either passed to a command via stdin,
+//! or sent via HTTP POST to a webhook URL.
+
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use super::auth;
+use super::config;
+use super::path;
+use super::repo;
+use super::tree;
+
+#[derive(Debug, Serialize)]
+pub struct RepoReport {
+    pub name: String,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// New commits on the remote tracking branches, only set for fetches
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<repo::BranchChanges>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// The operation that was run, e.g. "sync" or "fetch"
+    pub operation: String,
+    /// Unix timestamp of the end of the run
+    pub timestamp: u64,
+    pub success: bool,
+    pub repos: Vec<RepoReport>,
+    /// Errors that do not belong to a single repository
+    pub errors: Vec<String>,
+    /// Check failures of all repositories, see `grm repos status --check`.
+    /// Only filled by the daemon.
+    pub check_failures: Vec<String>,
+}
+
+/// The current time as a unix timestamp
+pub fn now() -> Result<u64, String> {
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|error| format!("System time is before the unix epoch: {error}"))?
+        .as_secs())
+}
+
+impl Report {
+    pub fn new(operation: &str, result: tree::RunResult) -> Result<Self, String> {
+        Ok(Self {
+            operation: operation.to_string(),
+            timestamp: now()?,
+            success: result.success(),
+            repos: result
+                .repos
+                .into_iter()
+                .map(|repo| RepoReport {
+                    name: repo.name,
+                    error: repo.error,
+                    warnings: repo.warnings,
+                    changes: repo.changes,
+                })
+                .collect(),
+            errors: result.errors,
+            check_failures: Vec::new(),
+        })
+    }
+
+    /// A report for a run that failed as a whole, e.g. because the forge
+    /// could not be reached
+    pub fn from_error(operation: &str, error: String) -> Result<Self, String> {
+        Ok(Self {
+            operation: operation.to_string(),
+            timestamp: now()?,
+            success: false,
+            repos: Vec::new(),
+            errors: vec![error],
+            check_failures: Vec::new(),
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|error| format!("Failed serializing report: {error}"))
+    }
+}
+
+pub fn write_report_file(report: &Report, report_file: &Path) -> Result<(), String> {
+    std::fs::write(report_file, report.to_json()?).map_err(|error| {
+        format!(
+            "Failed writing report to \"{}\": {}",
+            path::path_as_string(report_file),
+            error
+        )
+    })
+}
+
+pub fn run_command(report: &Report, command: &str) -> Result<(), String> {
+    let mut child = process::Command::new("/usr/bin/env")
+        .arg("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Failed to run notify command: {error}"))?;
+
+    // The command may not be interested in the report at all and exit without
+    // reading stdin, so write errors are ignored here
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(report.to_json()?.as_bytes());
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Failed to run notify command: {error}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return if stderr.trim().is_empty() {
+            Err(String::from("Notify command failed"))
+        } else {
+            Err(format!("Notify command failed: {}", stderr.trim()))
+        };
+    }
+
+    Ok(())
+}
+
+pub fn send_webhook(report: &Report, url: &str) -> Result<(), String> {
+    ureq::post(url)
+        .send_json(report)
+        .map_err(|error| format!("Failed sending report to webhook: {error}"))?;
+    Ok(())
+}
+
+/// Sends the report to all configured notification targets
+///
+/// All targets are tried, even if one of them fails.
+pub fn send(report: &Report, notify: &config::NotifyConfig) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if let Some(command) = &notify.command {
+        if let Err(error) = run_command(report, command) {
+            errors.push(error);
+        }
+    }
+
+    if let Some(url) = &notify.webhook_url {
+        if let Err(error) = send_webhook(report, url) {
+            errors.push(error);
+        }
+    }
+
+    if let Some(url_command) = &notify.webhook_url_command {
+        let result = auth::get_token_from_command(url_command)
+            .map_err(|error| format!("Getting webhook URL from command failed: {error}"))
+            .and_then(|url| {
+                // The URL is secret, so keep it out of error messages
+                send_webhook(report, url.access())
+                    .map_err(|error| error.replace(url.access(), "<webhook_url_command>"))
+            });
+        if let Err(error) = result {
+            errors.push(error);
+        }
+    }
+
+    errors
+}
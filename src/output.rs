@@ -1,10 +1,64 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use console::{Style, Term};
+use serde::Serialize;
+
+static LOG_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Print all messages as JSON lines on stderr instead of human-readable text
+///
+/// This is a process-wide setting, as messages are printed from everywhere.
+/// Only the messages are affected, not the regular output of commands (tables,
+/// generated configuration and so on).
+pub fn set_log_json(enabled: bool) {
+    LOG_JSON.store(enabled, Ordering::Relaxed);
+}
+
+pub fn log_json() -> bool {
+    LOG_JSON.load(Ordering::Relaxed)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    Error,
+    Action,
+    Warning,
+    Success,
+    Progress,
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    level: Level,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repo: Option<&'a str>,
+    message: &'a str,
+}
+
+fn print_event(level: Level, repo: Option<&str>, message: &str) {
+    let event = Event {
+        level,
+        repo,
+        message,
+    };
+    Term::stderr()
+        .write_line(&serde_json::to_string(&event).unwrap())
+        .unwrap();
+}
 
 pub fn print_repo_error(repo: &str, message: &str) {
-    print_error(&format!("{repo}: {message}"));
+    if log_json() {
+        print_event(Level::Error, Some(repo), message);
+    } else {
+        print_error(&format!("{repo}: {message}"));
+    }
 }
 
 pub fn print_error(message: &str) {
+    if log_json() {
+        return print_event(Level::Error, None, message);
+    }
     let stderr = Term::stderr();
     let mut style = Style::new().red();
     if stderr.is_term() {
@@ -16,10 +70,17 @@ pub fn print_error(message: &str) {
 }
 
 pub fn print_repo_action(repo: &str, message: &str) {
-    print_action(&format!("{repo}: {message}"));
+    if log_json() {
+        print_event(Level::Action, Some(repo), message);
+    } else {
+        print_action(&format!("{repo}: {message}"));
+    }
 }
 
 pub fn print_action(message: &str) {
+    if log_json() {
+        return print_event(Level::Action, None, message);
+    }
     let stdout = Term::stdout();
     let mut style = Style::new().yellow();
     if stdout.is_term() {
@@ -30,7 +91,18 @@ pub fn print_action(message: &str) {
         .unwrap();
 }
 
+pub fn print_repo_warning(repo: &str, message: &str) {
+    if log_json() {
+        print_event(Level::Warning, Some(repo), message);
+    } else {
+        print_warning(&format!("{repo}: {message}"));
+    }
+}
+
 pub fn print_warning(message: &str) {
+    if log_json() {
+        return print_event(Level::Warning, None, message);
+    }
     let stderr = Term::stderr();
     let mut style = Style::new().yellow();
     if stderr.is_term() {
@@ -42,10 +114,17 @@ pub fn print_warning(message: &str) {
 }
 
 pub fn print_repo_success(repo: &str, message: &str) {
-    print_success(&format!("{repo}: {message}"));
+    if log_json() {
+        print_event(Level::Success, Some(repo), message);
+    } else {
+        print_success(&format!("{repo}: {message}"));
+    }
 }
 
 pub fn print_success(message: &str) {
+    if log_json() {
+        return print_event(Level::Success, None, message);
+    }
     let stdout = Term::stdout();
     let mut style = Style::new().green();
     if stdout.is_term() {
@@ -56,3 +135,124 @@ pub fn print_success(message: &str) {
         .write_line(&format!("[{}] {}", style.apply_to('\u{2714}'), &message))
         .unwrap();
 }
+
+/// The width of the action column of [`print_repo_summary`], enough for the
+/// longest action ("remotes-updated")
+const ACTION_WIDTH: usize = 15;
+
+/// How the action of [`print_repo_summary`] is colored
+pub enum SummaryKind {
+    Unchanged,
+    Changed,
+    Failed,
+}
+
+/// Prints what happened to a repository as a single line with a right-aligned,
+/// colored action column, followed by the details indented below it
+///
+/// Failures and warnings go to stderr, everything else to stdout. Each stream gets
+/// one write only, so the output of repositories handled in parallel does not
+/// get mixed up.
+pub fn print_repo_summary(
+    kind: &SummaryKind,
+    action: &str,
+    repo: &str,
+    changes: &[String],
+    problems: &[String],
+) {
+    let indent = " ".repeat(ACTION_WIDTH);
+    let (term, style) = match kind {
+        SummaryKind::Unchanged => (Term::stdout(), Style::new().green()),
+        SummaryKind::Changed => (Term::stdout(), Style::new().yellow()),
+        SummaryKind::Failed => (Term::stderr(), Style::new().red()),
+    };
+    let mut style = style.bold();
+    if term.is_term() {
+        style = style.force_styling(true);
+    }
+
+    let mut lines = vec![format!(
+        "{}  {}",
+        style.apply_to(format!("{action:>ACTION_WIDTH$}")),
+        repo
+    )];
+    lines.extend(changes.iter().map(|change| format!("{indent}  {change}")));
+
+    let problems: Vec<String> = match kind {
+        SummaryKind::Failed => {
+            lines.extend(problems.iter().map(|error| format!("{indent}  {error}")));
+            Vec::new()
+        }
+        SummaryKind::Unchanged | SummaryKind::Changed => {
+            let mut style = Style::new().yellow();
+            if Term::stderr().is_term() {
+                style = style.force_styling(true);
+            }
+            problems
+                .iter()
+                .map(|warning| format!("{indent}  {} {warning}", style.apply_to("warning:")))
+                .collect()
+        }
+    };
+
+    term.write_line(&lines.join("\n")).unwrap();
+    if !problems.is_empty() {
+        Term::stderr().write_line(&problems.join("\n")).unwrap();
+    }
+}
+
+/// Shows the progress of a long-running operation on a single line that is
+/// updated in place
+///
+/// Only shown when stderr is a terminal, so logs and captured output stay clean.
+/// With [`set_log_json`], every update is printed as a separate event instead.
+pub fn print_progress(message: &str) {
+    if log_json() {
+        return print_event(Level::Progress, None, message);
+    }
+    let stderr = Term::stderr();
+    if stderr.is_term() {
+        stderr
+            .clear_line()
+            .and_then(|()| stderr.write_str(message))
+            .unwrap();
+    }
+}
+
+/// Removes the line written by [`print_progress`]
+pub fn clear_progress() {
+    let stderr = Term::stderr();
+    if stderr.is_term() && !log_json() {
+        stderr.clear_line().unwrap();
+    }
+}
+
+/// Calls [`clear_progress`] when dropped, so the progress line is not left
+/// behind when returning early, e.g. on errors
+pub struct ProgressGuard;
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        clear_progress();
+    }
+}
+
+/// Asks a question and returns the answer, read from stdin
+pub fn prompt(message: &str) -> Result<String, String> {
+    let stdout = Term::stdout();
+    let mut style = Style::new().blue();
+    if stdout.is_term() {
+        style = style.force_styling(true);
+    }
+    stdout
+        .write_str(&format!("[{}] {} ", style.apply_to('?'), message))
+        .and_then(|()| stdout.flush())
+        .map_err(|error| format!("Failed writing to stdout: {error}"))?;
+
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(0) => Err(String::from("No answer given")),
+        Ok(_) => Ok(answer.trim().to_string()),
+        Err(error) => Err(format!("Failed reading answer: {error}")),
+    }
+}
@@ -1,10 +1,51 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process;
 
 use super::output::*;
 
+/// Converts a path to a string for messages. Invalid UTF-8 is replaced, so the
+/// result must not be used to get back to the path.
 pub fn path_as_string(path: &Path) -> String {
-    path.to_path_buf().into_os_string().into_string().unwrap()
+    path.to_string_lossy().into_owned()
+}
+
+/// Converts a path to a string for anything that ends up as data, e.g. the name
+/// of a repository in a generated configuration
+pub fn path_as_utf8(path: &Path) -> Result<&str, String> {
+    path.to_str()
+        .ok_or_else(|| format!("Path \"{}\" is not valid UTF-8", path_as_string(path)))
+}
+
+/// Fails if `path` is on a read-only filesystem, e.g. a mounted snapshot. If the
+/// path does not exist yet, its closest existing parent is checked.
+///
+/// This is meant to fail early with a clear message instead of running into I/O
+/// errors halfway through an operation. Other reasons why writing may fail (e.g.
+/// missing permissions) are left to the operation itself. Only the mount flags
+/// are checked, nothing is written.
+pub fn check_writable(path: &Path) -> Result<(), String> {
+    let Some(directory) = path.ancestors().find(|ancestor| ancestor.is_dir()) else {
+        return Ok(());
+    };
+
+    if is_read_only(directory) {
+        return Err(format!(
+            "The filesystem of \"{}\" is read-only",
+            path_as_string(path)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_read_only(directory: &Path) -> bool {
+    rustix::fs::statvfs(directory)
+        .is_ok_and(|stat| stat.f_flag.contains(rustix::fs::StatVfsMountFlags::RDONLY))
+}
+
+#[cfg(not(unix))]
+fn is_read_only(_directory: &Path) -> bool {
+    false
 }
 
 pub fn env_home() -> String {
@@ -39,6 +80,41 @@ pub fn expand_path(path: &Path) -> PathBuf {
     Path::new(&expanded_path).to_path_buf()
 }
 
+/// Makes sure that a relative path, e.g. the name of a repository, stays below
+/// the directory it is joined to
+///
+/// Names and namespaces may come from a forge API, so they cannot be trusted to not
+/// contain something like `../`.
+pub fn check_subpath(path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        return Err(String::from("Path is empty"));
+    }
+
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(_) => {}
+            Component::CurDir | Component::ParentDir => {
+                return Err(format!(
+                    "\"{path}\" must not contain \".\" or \"..\" components"
+                ))
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("\"{path}\" must be a relative path"))
+            }
+        }
+    }
+
+    // `components()` silently drops a trailing "." and empty components
+    if path
+        .split('/')
+        .any(|component| component.is_empty() || component == ".")
+    {
+        return Err(format!("\"{path}\" contains empty or \".\" components"));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,4 +153,43 @@ mod tests {
             Path::new("/home/test/file")
         );
     }
+
+    #[test]
+    fn check_check_subpath() {
+        assert!(check_subpath("repo").is_ok());
+        assert!(check_subpath("namespace/repo").is_ok());
+        assert!(check_subpath("group/subgroup/repo").is_ok());
+        assert!(check_subpath("repo.with.dots").is_ok());
+        assert!(check_subpath("..repo").is_ok());
+        assert!(check_subpath("repo..").is_ok());
+
+        assert!(check_subpath("").is_err());
+        assert!(check_subpath(".").is_err());
+        assert!(check_subpath("..").is_err());
+        assert!(check_subpath("../repo").is_err());
+        assert!(check_subpath("namespace/../repo").is_err());
+        assert!(check_subpath("namespace/../../repo").is_err());
+        assert!(check_subpath("namespace/..").is_err());
+        assert!(check_subpath("./repo").is_err());
+        assert!(check_subpath("repo/.").is_err());
+        assert!(check_subpath("/repo").is_err());
+        assert!(check_subpath("/etc/passwd").is_err());
+        assert!(check_subpath("namespace//repo").is_err());
+        assert!(check_subpath("repo/").is_err());
+    }
+
+    #[test]
+    fn check_path_as_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(path_as_utf8(Path::new("/tmp/repo")), Ok("/tmp/repo"));
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/bad\xff"));
+        assert_eq!(
+            path_as_utf8(path),
+            Err(String::from("Path \"/tmp/bad\u{FFFD}\" is not valid UTF-8"))
+        );
+        assert_eq!(path_as_string(path), "/tmp/bad\u{FFFD}");
+    }
 }
@@ -0,0 +1,210 @@
+//! Profiles are snapshots of the set of worktrees of a repository
+//!
+//! They are stored in `grm-profiles.toml` in the root of the worktree setup, so
+//! the file can be copied (or committed somewhere) to recreate the same
+//! worktrees on another machine.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::output::*;
+use super::repo;
+use super::worktree;
+
+pub const PROFILES_FILE_NAME: &str = "grm-profiles.toml";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileWorktree {
+    pub name: String,
+
+    /// The commit the branch pointed to when the profile was saved. If neither
+    /// the branch nor its remote tracking branch exist on restore, the branch
+    /// is created from this commit.
+    pub base: String,
+
+    /// The remote tracking branch, as `<remote>/<branch>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    pub worktrees: Vec<ProfileWorktree>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Profiles {
+    #[serde(default)]
+    profiles: Vec<Profile>,
+}
+
+fn read_profiles(directory: &Path) -> Result<Profiles, String> {
+    let path = directory.join(PROFILES_FILE_NAME);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => return Ok(Profiles::default()),
+            _ => {
+                return Err(format!(
+                    "Error reading profiles file \"{}\": {}",
+                    path.display(),
+                    e
+                ))
+            }
+        },
+    };
+
+    toml::from_str(&content)
+        .map_err(|e| format!("Error parsing profiles file \"{}\": {}", path.display(), e))
+}
+
+fn write_profiles(directory: &Path, profiles: &Profiles) -> Result<(), String> {
+    let path = directory.join(PROFILES_FILE_NAME);
+    let content =
+        toml::to_string(profiles).map_err(|e| format!("Error serializing profiles: {e}"))?;
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Error writing profiles file \"{}\": {}", path.display(), e))
+}
+
+fn profile_worktree(directory: &Path, name: &str) -> Result<ProfileWorktree, String> {
+    let repo = repo::RepoHandle::open(&directory.join(name), false)
+        .map_err(|error| format!("Error opening worktree: {error}"))?;
+
+    let branch = repo
+        .head_branch()
+        .map_err(|error| format!("Error getting branch: {error}"))?;
+    let branch_name = branch.name()?;
+    if branch_name != name {
+        return Err(format!(
+            "Branch \"{branch_name}\" is checked out, which does not match the worktree name"
+        ));
+    }
+
+    let base = branch.commit()?.id().hex_string();
+    let track = match branch.upstream() {
+        Ok(upstream) => Some(upstream.name()?),
+        Err(_) => None,
+    };
+
+    Ok(ProfileWorktree {
+        name: name.to_string(),
+        base,
+        track,
+    })
+}
+
+/// Saves the current worktrees as profile `name`, replacing an existing profile
+/// of the same name
+///
+/// Returns whether an existing profile was replaced, and warnings about
+/// worktrees that could not be saved.
+pub fn save_profile(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+    name: &str,
+) -> Result<(bool, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let mut worktrees = Vec::new();
+
+    for worktree in repo.get_worktrees()? {
+        match profile_worktree(directory, worktree.name()) {
+            Ok(profile_worktree) => worktrees.push(profile_worktree),
+            Err(error) => warnings.push(format!("{}: {}, skipping", worktree.name(), error)),
+        }
+    }
+
+    worktrees.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut profiles = read_profiles(directory)?;
+    let profile = Profile {
+        name: name.to_string(),
+        worktrees,
+    };
+
+    let replaced = match profiles.profiles.iter_mut().find(|p| p.name == name) {
+        Some(existing) => {
+            *existing = profile;
+            true
+        }
+        None => {
+            profiles.profiles.push(profile);
+            false
+        }
+    };
+
+    write_profiles(directory, &profiles)?;
+
+    Ok((replaced, warnings))
+}
+
+/// Creates all worktrees of profile `name` that do not exist yet
+///
+/// Returns warnings about worktrees that could not be restored exactly.
+pub fn restore_profile(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    let profiles = read_profiles(directory)?;
+    let profile = profiles
+        .profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("Profile \"{name}\" not found"))?;
+
+    let mut warnings = Vec::new();
+
+    for worktree in &profile.worktrees {
+        if repo.find_worktree(&worktree.name).is_ok() {
+            print_action(&format!("{}: Worktree already exists", worktree.name));
+            continue;
+        }
+
+        let track = match &worktree.track {
+            Some(track) => Some(track.split_once('/').ok_or_else(|| {
+                format!(
+                    "Tracking branch \"{track}\" of {} needs to match the pattern <remote>/<branch_name>",
+                    worktree.name
+                )
+            })?),
+            None => None,
+        };
+
+        let remote_branch_exists = track.is_some_and(|(remote_name, remote_branch_name)| {
+            repo.find_remote_branch(remote_name, remote_branch_name)
+                .is_ok()
+        });
+
+        if repo.find_local_branch(&worktree.name).is_err() && !remote_branch_exists {
+            match repo.find_commit(&worktree.base) {
+                Ok(commit) => {
+                    repo.create_branch(&worktree.name, &commit)?;
+                }
+                Err(_) => warnings.push(format!(
+                    "{}: Commit {} not found, creating the branch from the default branch",
+                    worktree.name, worktree.base
+                )),
+            }
+        }
+
+        if let Some(add_warnings) =
+            worktree::add_worktree(directory, &worktree.name, track, track.is_none())
+                .map_err(|error| format!("Error creating worktree {}: {}", worktree.name, error))?
+        {
+            warnings.extend(
+                add_warnings
+                    .into_iter()
+                    .map(|warning| format!("{}: {}", worktree.name, warning)),
+            );
+        }
+
+        print_success(&format!("Worktree {} created", worktree.name));
+    }
+
+    Ok(warnings)
+}
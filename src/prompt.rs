@@ -0,0 +1,203 @@
+//! A cached one-line status for shell prompts, see `grm prompt-status`
+//!
+//! Computing the status of a repository means going over all files in it, which
+//! is too slow to do for every prompt. So commands that compute the status anyway
+//! (`grm repos status`, `grm wt status`) leave a small cache entry per repository
+//! or worktree behind, and `grm prompt-status` only reads these entries without
+//! opening the repository at all.
+//!
+//! The cache is kept in `$XDG_CACHE_HOME/grm/prompt` (`~/.cache/grm/prompt` if
+//! unset), one file per repository.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::lock;
+use super::path;
+use super::repo;
+use super::worktree;
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptStatus {
+    /// The repository or worktree the status belongs to
+    pub path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worktree: Option<String>,
+
+    pub dirty: bool,
+
+    pub ahead: usize,
+
+    pub behind: usize,
+
+    /// When the status was computed, as a unix timestamp
+    pub updated: u64,
+}
+
+impl PromptStatus {
+    /// Builds the entry for the repository (or worktree) at `path` from its full
+    /// status. Ahead and behind refer to the remote tracking branch of HEAD.
+    pub fn new(path: &Path, status: &repo::RepoStatus, worktree: Option<&str>) -> Self {
+        let (ahead, behind) = status.head_ahead_behind();
+
+        Self {
+            path: path::path_as_string(path),
+            worktree: worktree.map(str::to_string),
+            dirty: status.dirty(),
+            ahead,
+            behind,
+            updated: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    /// The line printed for the prompt, e.g. `myfeature * ↑2 ↓1`
+    ///
+    /// Empty if there is nothing to report.
+    pub fn line(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(worktree) = &self.worktree {
+            parts.push(worktree.clone());
+        }
+        if self.dirty {
+            parts.push(String::from("*"));
+        }
+        if self.ahead > 0 {
+            parts.push(format!("\u{2191}{}", self.ahead));
+        }
+        if self.behind > 0 {
+            parts.push(format!("\u{2193}{}", self.behind));
+        }
+        parts.join(" ")
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    match std::env::var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var("HOME")
+            .ok()
+            .map(|home| Path::new(&home).join(".cache")),
+    }
+    .map(|dir| dir.join("grm").join("prompt"))
+}
+
+fn cache_file(cache_dir: &Path, path: &Path) -> PathBuf {
+    cache_dir.join(format!(
+        "{:016x}.toml",
+        lock::hash(path.as_os_str().as_encoded_bytes())
+    ))
+}
+
+fn write_entry(entry: &PromptStatus) -> Result<(), String> {
+    let Some(dir) = cache_dir() else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir)
+        .map_err(|error| format!("Failed creating \"{}\": {}", dir.display(), error))?;
+
+    let content = toml::to_string(entry).map_err(|error| error.to_string())?;
+
+    // Write to a temporary file first, so readers never see half an entry
+    let file = cache_file(&dir, Path::new(&entry.path));
+    let temporary = file.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&temporary, content)
+        .map_err(|error| format!("Failed writing \"{}\": {}", temporary.display(), error))?;
+    std::fs::rename(&temporary, &file)
+        .map_err(|error| format!("Failed writing \"{}\": {}", file.display(), error))
+}
+
+/// Updates the cache entry for the repository (or worktree) at `path`
+///
+/// The cache is best effort, so failures are ignored.
+pub fn record(path: &Path, status: &repo::RepoStatus, worktree: Option<&str>) {
+    if let Ok(path) = path.canonicalize() {
+        let _ = write_entry(&PromptStatus::new(&path, status, worktree));
+    }
+}
+
+fn read_entry(cache_dir: &Path, path: &Path) -> Option<PromptStatus> {
+    let content = std::fs::read_to_string(cache_file(cache_dir, path)).ok()?;
+    let entry: PromptStatus = toml::from_str(&content).ok()?;
+    // Guard against hash collisions
+    (entry.path == path::path_as_string(path)).then_some(entry)
+}
+
+/// Returns the cached status of the repository containing `path`, without
+/// touching the repository itself
+pub fn cached_status(path: &Path) -> Option<PromptStatus> {
+    let cache_dir = cache_dir()?;
+    let path = path.canonicalize().ok()?;
+    path.ancestors()
+        .find_map(|ancestor| read_entry(&cache_dir, ancestor))
+}
+
+/// Computes the status of the repository containing `path` and updates the cache
+///
+/// Returns `Ok(None)` if `path` is not inside a repository.
+pub fn refresh_status(path: &Path) -> Result<Option<PromptStatus>, String> {
+    let path = path
+        .canonicalize()
+        .map_err(|error| format!("Failed to resolve \"{}\": {}", path.display(), error))?;
+
+    let Some(repo_path) = path
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+    else {
+        return Ok(None);
+    };
+
+    // A worktree of a worktree setup is named after its path below the root of
+    // the setup, which can contain slashes
+    let worktree = repo_path.ancestors().skip(1).find_map(|ancestor| {
+        ancestor
+            .join(worktree::GIT_MAIN_WORKTREE_DIRECTORY)
+            .exists()
+            .then(|| repo_path.strip_prefix(ancestor).ok())
+            .flatten()
+            .map(path::path_as_string)
+    });
+
+    let repo_handle = repo::RepoHandle::open(repo_path, false)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+    let status = repo_handle.status(false)?;
+
+    let entry = PromptStatus::new(repo_path, &status, worktree.as_deref());
+    // Still print the status if the cache cannot be written
+    let _ = write_entry(&entry);
+
+    Ok(Some(entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(worktree: Option<&str>, dirty: bool, ahead: usize, behind: usize) -> PromptStatus {
+        PromptStatus {
+            path: String::from("/tmp/repo"),
+            worktree: worktree.map(str::to_string),
+            dirty,
+            ahead,
+            behind,
+            updated: 0,
+        }
+    }
+
+    #[test]
+    fn check_prompt_line() {
+        assert_eq!(entry(None, false, 0, 0).line(), "");
+        assert_eq!(entry(None, true, 0, 0).line(), "*");
+        assert_eq!(
+            entry(Some("feature/x"), true, 2, 1).line(),
+            "feature/x * \u{2191}2 \u{2193}1"
+        );
+        assert_eq!(entry(Some("main"), false, 0, 3).line(), "main \u{2193}3");
+    }
+}
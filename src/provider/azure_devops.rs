@@ -0,0 +1,365 @@
+use base64::Engine;
+use serde::Deserialize;
+
+use super::auth;
+use super::escape;
+use super::strip_user;
+use super::ApiErrorResponse;
+use super::Filter;
+use super::JsonError;
+use super::Pagination;
+use super::Project;
+use super::Provider;
+
+const ACCEPT_HEADER_JSON: &str = "application/json";
+const API_VERSION: &str = "7.1";
+const AZURE_DEVOPS_API_BASEURL: &str = match option_env!("AZURE_DEVOPS_API_BASEURL") {
+    Some(url) => url,
+    None => "https://dev.azure.com",
+};
+/// Profiles and the organizations of a user are served from a different host
+const AZURE_DEVOPS_PROFILE_API_BASEURL: &str = match option_env!("AZURE_DEVOPS_PROFILE_API_BASEURL")
+{
+    Some(url) => url,
+    None => "https://app.vssps.visualstudio.com",
+};
+
+/// The project that contains a repository. This is what other forges call a
+/// group
+#[derive(Deserialize)]
+pub struct AzureDevopsProject {
+    pub id: String,
+    pub name: String,
+    /// `private` or `public`
+    pub visibility: Option<String>,
+}
+
+/// A repository. Azure DevOps uses "project" for the level above
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureDevopsRepository {
+    pub id: String,
+    pub name: String,
+    pub project: AzureDevopsProject,
+    pub remote_url: String,
+    pub ssh_url: String,
+    /// Unset for repositories without any commits, e.g. `refs/heads/main`
+    pub default_branch: Option<String>,
+    #[serde(default)]
+    pub is_disabled: bool,
+    /// Not part of the response, set from the request instead
+    #[serde(skip)]
+    pub organization: String,
+}
+
+#[derive(Deserialize)]
+struct AzureDevopsList<T> {
+    value: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureDevopsProfile {
+    id: String,
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureDevopsAccount {
+    account_name: String,
+}
+
+impl Project for AzureDevopsRepository {
+    fn id(&self) -> String {
+        format!("azure-devops:{}", self.id)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn namespace(&self) -> Option<String> {
+        Some(format!("{}/{}", self.organization, self.project.name))
+    }
+
+    fn ssh_url(&self) -> String {
+        self.ssh_url.clone()
+    }
+
+    fn http_url(&self) -> String {
+        strip_user(&self.remote_url)
+    }
+
+    fn private(&self) -> bool {
+        self.project.visibility.as_deref() != Some("public")
+    }
+
+    fn default_branch(&self) -> Option<String> {
+        self.default_branch.as_ref().map(|branch| {
+            branch
+                .strip_prefix("refs/heads/")
+                .unwrap_or(branch)
+                .to_string()
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AzureDevopsApiErrorResponse {
+    pub message: String,
+}
+
+impl JsonError for AzureDevopsApiErrorResponse {
+    fn to_string(self) -> String {
+        self.message
+    }
+}
+
+/// Splits a group into organization and project, e.g. `myorg/myproject`
+fn split_group(group: &str) -> Result<(&str, &str), String> {
+    match group.split_once('/') {
+        Some((organization, project))
+            if !organization.is_empty() && !project.is_empty() && !project.contains('/') =>
+        {
+            Ok((organization, project))
+        }
+        _ => Err(format!(
+            "Group \"{group}\" has to be an organization and a project, like \"organization/project\""
+        )),
+    }
+}
+
+pub struct AzureDevops {
+    filter: Filter,
+    secret_token: auth::AuthToken,
+    pagination: Pagination,
+}
+
+impl AzureDevops {
+    fn get_profile(
+        &self,
+    ) -> Result<AzureDevopsProfile, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        super::call::<AzureDevopsProfile, AzureDevopsApiErrorResponse>(
+            &format!(
+                "{AZURE_DEVOPS_PROFILE_API_BASEURL}/_apis/profile/profiles/me?api-version={API_VERSION}"
+            ),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+        )
+    }
+
+    /// Lists repositories below `uri`, remembering their organization
+    fn list_repositories(
+        &self,
+        uri: &str,
+        organization: &str,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        Ok(self
+            .call_list(uri, Some(ACCEPT_HEADER_JSON))?
+            .into_iter()
+            // Disabled repositories cannot be cloned
+            .filter(|repository| !repository.is_disabled)
+            .map(|mut repository| {
+                repository.organization = organization.to_string();
+                repository
+            })
+            .collect())
+    }
+}
+
+impl Provider for AzureDevops {
+    type Project = AzureDevopsRepository;
+    type Error = AzureDevopsApiErrorResponse;
+
+    fn new(
+        filter: Filter,
+        secret_token: auth::AuthToken,
+        api_url_override: Option<String>,
+        pagination: Pagination,
+    ) -> Result<Self, String> {
+        if api_url_override.is_some() {
+            return Err("API URL overriding is not supported for Azure DevOps".to_string());
+        }
+        if filter.min_access_level.is_some() {
+            return Err("Filtering by access level is only supported for GitLab".to_string());
+        }
+        // Personal access tokens are sent as the password, without a user
+        let secret_token = auth::AuthToken::new(
+            base64::engine::general_purpose::STANDARD.encode(format!(":{}", secret_token.access())),
+        );
+        Ok(Self {
+            filter,
+            secret_token,
+            pagination,
+        })
+    }
+
+    fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn secret_token(&self) -> &auth::AuthToken {
+        &self.secret_token
+    }
+
+    fn auth_header_key() -> &'static str {
+        "Basic"
+    }
+
+    fn get_user_projects(
+        &self,
+        _user: &str,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        Err(ApiErrorResponse::String(String::from(
+            "Users do not have repositories on Azure DevOps, use organizations or groups instead",
+        )))
+    }
+
+    fn get_own_projects(
+        &self,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        self.get_user_projects("")
+    }
+
+    fn get_group_projects(
+        &self,
+        group: &str,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        let (organization, project) = split_group(group)?;
+        self.list_repositories(
+            &format!(
+                "{AZURE_DEVOPS_API_BASEURL}/{}/{}/_apis/git/repositories?api-version={API_VERSION}",
+                escape(organization),
+                escape(project)
+            ),
+            organization,
+        )
+    }
+
+    fn get_org_projects(
+        &self,
+        org: &str,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        self.list_repositories(
+            &format!(
+                "{AZURE_DEVOPS_API_BASEURL}/{}/_apis/git/repositories?api-version={API_VERSION}",
+                escape(org)
+            ),
+            org,
+        )
+    }
+
+    /// Lists the repositories of all organizations the requesting user is a
+    /// member of
+    fn get_accessible_projects(
+        &self,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        let profile = self.get_profile()?;
+        let accounts = super::call::<
+            AzureDevopsList<AzureDevopsAccount>,
+            AzureDevopsApiErrorResponse,
+        >(
+            &format!(
+                "{AZURE_DEVOPS_PROFILE_API_BASEURL}/_apis/accounts?memberId={}&api-version={API_VERSION}",
+                escape(&profile.id)
+            ),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+        )?;
+
+        let mut repositories = vec![];
+        for account in accounts.value {
+            repositories.extend(self.get_org_projects(&account.account_name)?);
+        }
+        Ok(repositories)
+    }
+
+    fn get_current_user(&self) -> Result<String, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        Ok(self.get_profile()?.display_name)
+    }
+
+    fn create_project(
+        &self,
+        name: &str,
+        group: Option<&str>,
+        private: bool,
+    ) -> Result<AzureDevopsRepository, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        let (organization, project) = split_group(group.ok_or_else(|| {
+            String::from(
+                "Creating a repository on Azure DevOps requires a group (organization/project)",
+            )
+        })?)?;
+
+        let project = super::call::<AzureDevopsProject, AzureDevopsApiErrorResponse>(
+            &format!(
+                "{AZURE_DEVOPS_API_BASEURL}/{}/_apis/projects/{}?api-version={API_VERSION}",
+                escape(organization),
+                escape(project)
+            ),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+        )?;
+
+        // Repositories do not have a visibility of their own
+        if private && project.visibility.as_deref() == Some("public") {
+            return Err(ApiErrorResponse::String(format!(
+                "Project \"{}\" is public, so its repositories cannot be private",
+                project.name
+            )));
+        }
+
+        let mut repository = super::call_post::<AzureDevopsRepository, AzureDevopsApiErrorResponse>(
+            &format!(
+                "{AZURE_DEVOPS_API_BASEURL}/{}/{}/_apis/git/repositories?api-version={API_VERSION}",
+                escape(organization),
+                escape(&project.name)
+            ),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            serde_json::json!({
+                "name": name,
+                "project": { "id": project.id },
+            }),
+        )?;
+        repository.organization = organization.to_string();
+        Ok(repository)
+    }
+
+    fn fork_project(
+        &self,
+        _path: &str,
+        _group: Option<&str>,
+    ) -> Result<AzureDevopsRepository, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        Err(ApiErrorResponse::String(String::from(
+            "Forking is not supported for Azure DevOps",
+        )))
+    }
+
+    /// Azure DevOps returns all repositories at once, in a `value` field
+    fn call_list(
+        &self,
+        uri: &str,
+        accept_header: Option<&str>,
+    ) -> Result<Vec<AzureDevopsRepository>, ApiErrorResponse<AzureDevopsApiErrorResponse>> {
+        self.pagination().count_request()?;
+
+        Ok(
+            super::call::<AzureDevopsList<AzureDevopsRepository>, AzureDevopsApiErrorResponse>(
+                uri,
+                Self::auth_header_key(),
+                self.secret_token(),
+                accept_header,
+            )?
+            .value,
+        )
+    }
+}
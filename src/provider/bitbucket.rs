@@ -0,0 +1,306 @@
+use serde::Deserialize;
+
+use super::auth;
+use super::escape;
+use super::print_progress;
+use super::strip_user;
+use super::ApiErrorResponse;
+use super::Filter;
+use super::JsonError;
+use super::Pagination;
+use super::ProgressGuard;
+use super::Project;
+use super::Provider;
+
+const ACCEPT_HEADER_JSON: &str = "application/json";
+const BITBUCKET_API_BASEURL: &str = match option_env!("BITBUCKET_API_BASEURL") {
+    Some(url) => url,
+    None => "https://api.bitbucket.org/2.0",
+};
+
+#[derive(Deserialize)]
+pub struct BitbucketLink {
+    pub name: String,
+    pub href: String,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketLinks {
+    #[serde(default)]
+    pub clone: Vec<BitbucketLink>,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketBranch {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketProject {
+    pub uuid: String,
+    pub slug: String,
+    pub full_name: String,
+    pub is_private: bool,
+    pub links: BitbucketLinks,
+    /// Unset for repositories without any commits
+    pub mainbranch: Option<BitbucketBranch>,
+}
+
+impl BitbucketProject {
+    fn clone_url(&self, name: &str) -> String {
+        self.links
+            .clone
+            .iter()
+            .find(|link| link.name == name)
+            .map(|link| link.href.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// A page of a list, Bitbucket does not use "link" headers for paging
+#[derive(Deserialize)]
+struct BitbucketPage {
+    values: Vec<BitbucketProject>,
+    next: Option<String>,
+    /// The total number of results, only returned for some lists
+    size: Option<usize>,
+    pagelen: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct BitbucketUser {
+    pub username: String,
+}
+
+impl Project for BitbucketProject {
+    fn id(&self) -> String {
+        format!("bitbucket:{}", self.uuid.trim_matches(['{', '}']))
+    }
+
+    fn name(&self) -> String {
+        self.slug.clone()
+    }
+
+    fn namespace(&self) -> Option<String> {
+        if let Some((namespace, _name)) = self.full_name.rsplit_once('/') {
+            Some(namespace.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn ssh_url(&self) -> String {
+        self.clone_url("ssh")
+    }
+
+    fn http_url(&self) -> String {
+        strip_user(&self.clone_url("https"))
+    }
+
+    fn private(&self) -> bool {
+        self.is_private
+    }
+
+    fn default_branch(&self) -> Option<String> {
+        self.mainbranch.as_ref().map(|branch| branch.name.clone())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketError {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketApiErrorResponse {
+    pub error: BitbucketError,
+}
+
+impl JsonError for BitbucketApiErrorResponse {
+    fn to_string(self) -> String {
+        self.error.message
+    }
+}
+
+pub struct Bitbucket {
+    filter: Filter,
+    secret_token: auth::AuthToken,
+    pagination: Pagination,
+}
+
+impl Provider for Bitbucket {
+    type Project = BitbucketProject;
+    type Error = BitbucketApiErrorResponse;
+
+    fn new(
+        filter: Filter,
+        secret_token: auth::AuthToken,
+        api_url_override: Option<String>,
+        pagination: Pagination,
+    ) -> Result<Self, String> {
+        if api_url_override.is_some() {
+            return Err("API URL overriding is not supported for Bitbucket".to_string());
+        }
+        if filter.min_access_level.is_some() {
+            return Err("Filtering by access level is only supported for GitLab".to_string());
+        }
+        Ok(Self {
+            filter,
+            secret_token,
+            pagination,
+        })
+    }
+
+    fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn secret_token(&self) -> &auth::AuthToken {
+        &self.secret_token
+    }
+
+    fn auth_header_key() -> &'static str {
+        "Bearer"
+    }
+
+    fn get_user_projects(
+        &self,
+        user: &str,
+    ) -> Result<Vec<BitbucketProject>, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        // Every user has a personal workspace of the same name
+        self.get_group_projects(user)
+    }
+
+    fn get_group_projects(
+        &self,
+        group: &str,
+    ) -> Result<Vec<BitbucketProject>, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        self.call_list(
+            &format!("{BITBUCKET_API_BASEURL}/repositories/{}", escape(group)),
+            Some(ACCEPT_HEADER_JSON),
+        )
+    }
+
+    fn get_accessible_projects(
+        &self,
+    ) -> Result<Vec<BitbucketProject>, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        self.call_list(
+            &format!("{BITBUCKET_API_BASEURL}/repositories?role=member"),
+            Some(ACCEPT_HEADER_JSON),
+        )
+    }
+
+    fn get_current_user(&self) -> Result<String, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        Ok(super::call::<BitbucketUser, BitbucketApiErrorResponse>(
+            &format!("{BITBUCKET_API_BASEURL}/user"),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+        )?
+        .username)
+    }
+
+    fn create_project(
+        &self,
+        name: &str,
+        group: Option<&str>,
+        private: bool,
+    ) -> Result<BitbucketProject, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        // Without a workspace, the project is created in the personal workspace
+        // of the requesting user
+        let workspace = match group {
+            Some(group) => group.to_string(),
+            None => self.get_current_user()?,
+        };
+        super::call_post::<BitbucketProject, BitbucketApiErrorResponse>(
+            &format!(
+                "{BITBUCKET_API_BASEURL}/repositories/{}/{}",
+                escape(&workspace),
+                escape(name)
+            ),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            serde_json::json!({
+                "scm": "git",
+                "is_private": private,
+            }),
+        )
+    }
+
+    fn fork_project(
+        &self,
+        path: &str,
+        group: Option<&str>,
+    ) -> Result<BitbucketProject, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        let path = path.split('/').map(escape).collect::<Vec<_>>().join("/");
+        // Without a workspace, the fork is created for the requesting user
+        let body = match group {
+            Some(group) => serde_json::json!({ "workspace": { "slug": group } }),
+            None => serde_json::json!({}),
+        };
+        super::call_post::<BitbucketProject, BitbucketApiErrorResponse>(
+            &format!("{BITBUCKET_API_BASEURL}/repositories/{path}/forks"),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            body,
+        )
+    }
+
+    /// Bitbucket returns the results in a `values` field, next to the URL of
+    /// the next page
+    fn call_list(
+        &self,
+        uri: &str,
+        accept_header: Option<&str>,
+    ) -> Result<Vec<BitbucketProject>, ApiErrorResponse<BitbucketApiErrorResponse>> {
+        let mut results = vec![];
+
+        let mut uri = match self.pagination().per_page {
+            Some(per_page) => format!(
+                "{uri}{}pagelen={per_page}",
+                if uri.contains('?') { '&' } else { '?' }
+            ),
+            None => uri.to_string(),
+        };
+        let mut page = 1;
+
+        let _progress = ProgressGuard;
+        loop {
+            self.pagination().count_request()?;
+
+            let response = super::call::<BitbucketPage, BitbucketApiErrorResponse>(
+                &uri,
+                Self::auth_header_key(),
+                self.secret_token(),
+                accept_header,
+            )?;
+
+            if let (Some(size), Some(pagelen)) = (response.size, response.pagelen) {
+                if pagelen > 0 {
+                    print_progress(&format!(
+                        "Listing repositories: page {page}/{}",
+                        size.div_ceil(pagelen)
+                    ));
+                }
+            }
+
+            results.extend(response.values);
+
+            match response.next {
+                Some(next_page) => {
+                    uri = next_page;
+                    page += 1;
+                }
+                None => break,
+            }
+        }
+
+        Ok(results)
+    }
+}
@@ -5,6 +5,7 @@ use super::escape;
 use super::ApiErrorResponse;
 use super::Filter;
 use super::JsonError;
+use super::Pagination;
 use super::Project;
 use super::Provider;
 
@@ -16,11 +17,15 @@ const GITHUB_API_BASEURL: &str = match option_env!("GITHUB_API_BASEURL") {
 
 #[derive(Deserialize)]
 pub struct GithubProject {
+    pub id: u64,
     pub name: String,
     pub full_name: String,
     pub clone_url: String,
     pub ssh_url: String,
     pub private: bool,
+    /// `public`, `private` or `internal`, missing in older API versions
+    pub visibility: Option<String>,
+    pub default_branch: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -29,7 +34,22 @@ struct GithubUser {
     pub username: String,
 }
 
+#[derive(Deserialize)]
+struct GithubInstallation {
+    id: u64,
+    account: GithubUser,
+}
+
+#[derive(Deserialize)]
+struct GithubInstallationToken {
+    token: String,
+}
+
 impl Project for GithubProject {
+    fn id(&self) -> String {
+        format!("github:{}", self.id)
+    }
+
     fn name(&self) -> String {
         self.name.clone()
     }
@@ -51,7 +71,16 @@ impl Project for GithubProject {
     }
 
     fn private(&self) -> bool {
+        // Internal repositories are only visible to members of the enterprise
         self.private
+            || self
+                .visibility
+                .as_deref()
+                .is_some_and(|visibility| visibility != "public")
+    }
+
+    fn default_branch(&self) -> Option<String> {
+        self.default_branch.clone()
     }
 }
 
@@ -69,6 +98,7 @@ impl JsonError for GithubApiErrorResponse {
 pub struct Github {
     filter: Filter,
     secret_token: auth::AuthToken,
+    pagination: Pagination,
 }
 
 impl Provider for Github {
@@ -79,13 +109,18 @@ impl Provider for Github {
         filter: Filter,
         secret_token: auth::AuthToken,
         api_url_override: Option<String>,
+        pagination: Pagination,
     ) -> Result<Self, String> {
         if api_url_override.is_some() {
             return Err("API URL overriding is not supported for Github".to_string());
         }
+        if filter.min_access_level.is_some() {
+            return Err("Filtering by access level is only supported for GitLab".to_string());
+        }
         Ok(Self {
             filter,
             secret_token,
+            pagination,
         })
     }
 
@@ -93,6 +128,10 @@ impl Provider for Github {
         &self.filter
     }
 
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
     fn secret_token(&self) -> &auth::AuthToken {
         &self.secret_token
     }
@@ -115,8 +154,17 @@ impl Provider for Github {
         &self,
         group: &str,
     ) -> Result<Vec<GithubProject>, ApiErrorResponse<GithubApiErrorResponse>> {
+        self.get_org_projects(group)
+    }
+
+    fn get_org_projects(
+        &self,
+        org: &str,
+    ) -> Result<Vec<GithubProject>, ApiErrorResponse<GithubApiErrorResponse>> {
+        // `all` includes internal repositories and the ones only accessible
+        // through a team
         self.call_list(
-            &format!("{GITHUB_API_BASEURL}/orgs/{}/repos?type=all", escape(group)),
+            &format!("{GITHUB_API_BASEURL}/orgs/{}/repos?type=all", escape(org)),
             Some(ACCEPT_HEADER_JSON),
         )
     }
@@ -139,4 +187,102 @@ impl Provider for Github {
         )?
         .username)
     }
+
+    fn create_project(
+        &self,
+        name: &str,
+        group: Option<&str>,
+        private: bool,
+    ) -> Result<GithubProject, ApiErrorResponse<GithubApiErrorResponse>> {
+        let uri = match group {
+            Some(group) => format!("{GITHUB_API_BASEURL}/orgs/{}/repos", escape(group)),
+            None => format!("{GITHUB_API_BASEURL}/user/repos"),
+        };
+        super::call_post::<GithubProject, GithubApiErrorResponse>(
+            &uri,
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            serde_json::json!({
+                "name": name,
+                "private": private,
+            }),
+        )
+    }
+
+    fn fork_project(
+        &self,
+        path: &str,
+        group: Option<&str>,
+    ) -> Result<GithubProject, ApiErrorResponse<GithubApiErrorResponse>> {
+        let path = path.split('/').map(escape).collect::<Vec<_>>().join("/");
+        // Without an organization, the fork is created for the requesting user
+        let body = match group {
+            Some(group) => serde_json::json!({ "organization": group }),
+            None => serde_json::json!({}),
+        };
+        super::call_post::<GithubProject, GithubApiErrorResponse>(
+            &format!("{GITHUB_API_BASEURL}/repos/{path}/forks"),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            body,
+        )
+    }
+}
+
+/// Exchanges the JSON Web Token of a GitHub App for a token of one of its
+/// installations, see [`auth::github_app_jwt()`]
+///
+/// Without `installation_id`, the app has to be installed exactly once.
+pub fn installation_token(
+    jwt: &auth::AuthToken,
+    installation_id: Option<u64>,
+) -> Result<auth::AuthToken, String> {
+    let to_string = |error| match error {
+        ApiErrorResponse::<GithubApiErrorResponse>::Json(x) => x.to_string(),
+        ApiErrorResponse::String(s) => s,
+    };
+
+    let installation_id = match installation_id {
+        Some(installation_id) => installation_id,
+        None => {
+            let installations = super::call::<Vec<GithubInstallation>, GithubApiErrorResponse>(
+                &format!("{GITHUB_API_BASEURL}/app/installations"),
+                "Bearer",
+                jwt,
+                Some(ACCEPT_HEADER_JSON),
+            )
+            .map_err(to_string)?;
+
+            match installations.as_slice() {
+                [installation] => installation.id,
+                [] => return Err(String::from("The GitHub App is not installed anywhere")),
+                _ => {
+                    return Err(format!(
+                        "The GitHub App is installed more than once, set \"installation_id\" to one of {}",
+                        installations
+                            .iter()
+                            .map(|installation| format!(
+                                "{} ({})",
+                                installation.id, installation.account.username
+                            ))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ))
+                }
+            }
+        }
+    };
+
+    let token = super::call_post::<GithubInstallationToken, GithubApiErrorResponse>(
+        &format!("{GITHUB_API_BASEURL}/app/installations/{installation_id}/access_tokens"),
+        "Bearer",
+        jwt,
+        Some(ACCEPT_HEADER_JSON),
+        serde_json::json!({}),
+    )
+    .map_err(to_string)?;
+
+    Ok(auth::AuthToken::new(token.token))
 }
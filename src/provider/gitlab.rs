@@ -5,6 +5,7 @@ use super::escape;
 use super::ApiErrorResponse;
 use super::Filter;
 use super::JsonError;
+use super::Pagination;
 use super::Project;
 use super::Provider;
 
@@ -24,12 +25,15 @@ pub enum GitlabVisibility {
 
 #[derive(Deserialize)]
 pub struct GitlabProject {
+    pub id: u64,
     #[serde(rename = "path")]
     pub name: String,
     pub path_with_namespace: String,
     pub http_url_to_repo: String,
     pub ssh_url_to_repo: String,
     pub visibility: GitlabVisibility,
+    /// Unset for projects without any commits
+    pub default_branch: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -37,7 +41,16 @@ struct GitlabUser {
     pub username: String,
 }
 
+#[derive(Deserialize)]
+struct GitlabNamespace {
+    pub id: u64,
+}
+
 impl Project for GitlabProject {
+    fn id(&self) -> String {
+        format!("gitlab:{}", self.id)
+    }
+
     fn name(&self) -> String {
         self.name.clone()
     }
@@ -61,6 +74,10 @@ impl Project for GitlabProject {
     fn private(&self) -> bool {
         !matches!(self.visibility, GitlabVisibility::Public)
     }
+
+    fn default_branch(&self) -> Option<String> {
+        self.default_branch.clone()
+    }
 }
 
 #[derive(Deserialize)]
@@ -78,6 +95,7 @@ impl JsonError for GitlabApiErrorResponse {
 pub struct Gitlab {
     filter: Filter,
     secret_token: auth::AuthToken,
+    pagination: Pagination,
     api_url_override: Option<String>,
 }
 
@@ -88,6 +106,18 @@ impl Gitlab {
             None => GITLAB_API_BASEURL.to_string(),
         }
     }
+
+    /// Restricts a project listing to the minimum access level of the filter
+    fn with_access_level(&self, uri: String) -> String {
+        match self.filter.min_access_level {
+            Some(level) => format!(
+                "{uri}{}membership=true&min_access_level={}",
+                if uri.contains('?') { '&' } else { '?' },
+                level.value()
+            ),
+            None => uri,
+        }
+    }
 }
 
 impl Provider for Gitlab {
@@ -98,10 +128,12 @@ impl Provider for Gitlab {
         filter: Filter,
         secret_token: auth::AuthToken,
         api_url_override: Option<String>,
+        pagination: Pagination,
     ) -> Result<Self, String> {
         Ok(Self {
             filter,
             secret_token,
+            pagination,
             api_url_override,
         })
     }
@@ -110,6 +142,10 @@ impl Provider for Gitlab {
         &self.filter
     }
 
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
     fn secret_token(&self) -> &auth::AuthToken {
         &self.secret_token
     }
@@ -123,7 +159,11 @@ impl Provider for Gitlab {
         user: &str,
     ) -> Result<Vec<GitlabProject>, ApiErrorResponse<GitlabApiErrorResponse>> {
         self.call_list(
-            &format!("{}/api/v4/users/{}/projects", self.api_url(), escape(user)),
+            &self.with_access_level(format!(
+                "{}/api/v4/users/{}/projects",
+                self.api_url(),
+                escape(user)
+            )),
             Some(ACCEPT_HEADER_JSON),
         )
     }
@@ -133,11 +173,11 @@ impl Provider for Gitlab {
         group: &str,
     ) -> Result<Vec<GitlabProject>, ApiErrorResponse<GitlabApiErrorResponse>> {
         self.call_list(
-            &format!(
+            &self.with_access_level(format!(
                 "{}/api/v4/groups/{}/projects?include_subgroups=true&archived=false",
                 self.api_url(),
                 escape(group),
-            ),
+            )),
             Some(ACCEPT_HEADER_JSON),
         )
     }
@@ -146,7 +186,16 @@ impl Provider for Gitlab {
         &self,
     ) -> Result<Vec<GitlabProject>, ApiErrorResponse<GitlabApiErrorResponse>> {
         self.call_list(
-            &format!("{}/api/v4/projects", self.api_url(),),
+            &self.with_access_level(format!("{}/api/v4/projects", self.api_url())),
+            Some(ACCEPT_HEADER_JSON),
+        )
+    }
+
+    fn get_starred_projects(
+        &self,
+    ) -> Result<Vec<GitlabProject>, ApiErrorResponse<GitlabApiErrorResponse>> {
+        self.call_list(
+            &self.with_access_level(format!("{}/api/v4/projects?starred=true", self.api_url())),
             Some(ACCEPT_HEADER_JSON),
         )
     }
@@ -160,4 +209,55 @@ impl Provider for Gitlab {
         )?
         .username)
     }
+
+    fn create_project(
+        &self,
+        name: &str,
+        group: Option<&str>,
+        private: bool,
+    ) -> Result<GitlabProject, ApiErrorResponse<GitlabApiErrorResponse>> {
+        let mut body = serde_json::json!({
+            "path": name,
+            "name": name,
+            "visibility": if private { "private" } else { "public" },
+        });
+
+        // Without a namespace, the project is created for the requesting user
+        if let Some(group) = group {
+            let namespace = super::call::<GitlabNamespace, GitlabApiErrorResponse>(
+                &format!("{}/api/v4/namespaces/{}", self.api_url(), escape(group)),
+                Self::auth_header_key(),
+                self.secret_token(),
+                Some(ACCEPT_HEADER_JSON),
+            )?;
+            body["namespace_id"] = namespace.id.into();
+        }
+
+        super::call_post::<GitlabProject, GitlabApiErrorResponse>(
+            &format!("{}/api/v4/projects", self.api_url()),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            body,
+        )
+    }
+
+    fn fork_project(
+        &self,
+        path: &str,
+        group: Option<&str>,
+    ) -> Result<GitlabProject, ApiErrorResponse<GitlabApiErrorResponse>> {
+        // Without a namespace, the fork is created for the requesting user
+        let body = match group {
+            Some(group) => serde_json::json!({ "namespace_path": group }),
+            None => serde_json::json!({}),
+        };
+        super::call_post::<GitlabProject, GitlabApiErrorResponse>(
+            &format!("{}/api/v4/projects/{}/fork", self.api_url(), escape(path)),
+            Self::auth_header_key(),
+            self.secret_token(),
+            Some(ACCEPT_HEADER_JSON),
+            body,
+        )
+    }
 }
@@ -1,15 +1,24 @@
 use serde::{Deserialize, Serialize};
 
+pub mod azure_devops;
+pub mod bitbucket;
 pub mod github;
 pub mod gitlab;
 
+pub use azure_devops::AzureDevops;
+pub use bitbucket::Bitbucket;
 pub use github::Github;
 pub use gitlab::Gitlab;
 
 use super::auth;
+use super::output::*;
+use super::path;
 use super::repo;
+use super::timings;
 
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::path::Path;
 
 const DEFAULT_REMOTE_NAME: &str = "origin";
 
@@ -19,6 +28,10 @@ pub enum RemoteProvider {
     Github,
     #[serde(alias = "gitlab", alias = "GitLab")]
     Gitlab,
+    #[serde(alias = "bitbucket", alias = "BitBucket")]
+    Bitbucket,
+    #[serde(alias = "azure-devops", alias = "azuredevops", alias = "AzureDevOps")]
+    AzureDevops,
 }
 
 #[derive(Deserialize)]
@@ -59,36 +72,338 @@ pub trait Project {
                     repo::RemoteType::Https
                 },
             }]),
+            provider_id: Some(self.id()),
+            tags: Vec::new(),
+            filter: None,
+            default_branch: self.default_branch(),
+            dir: None,
+            remote_name: None,
         }
     }
 
+    /// An ID that does not change when the project is renamed or transferred,
+    /// prefixed with the provider, e.g. `github:1234`
+    fn id(&self) -> String;
     fn name(&self) -> String;
     fn namespace(&self) -> Option<String>;
     fn ssh_url(&self) -> String;
     fn http_url(&self) -> String;
     fn private(&self) -> bool;
+    /// The default branch as reported by the forge, if there is one
+    fn default_branch(&self) -> Option<String>;
+}
+
+/// A project as listed by any provider, to save the list of projects and use it
+/// again later without accessing the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapshotProject {
+    pub id: String,
+    pub name: String,
+    pub namespace: Option<String>,
+    pub ssh_url: String,
+    pub http_url: String,
+    pub private: bool,
+    pub default_branch: Option<String>,
+}
+
+impl SnapshotProject {
+    pub fn from_project(project: &impl Project) -> Self {
+        Self {
+            id: project.id(),
+            name: project.name(),
+            namespace: project.namespace(),
+            ssh_url: project.ssh_url(),
+            http_url: project.http_url(),
+            private: project.private(),
+            default_branch: project.default_branch(),
+        }
+    }
+}
+
+impl Project for SnapshotProject {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn namespace(&self) -> Option<String> {
+        self.namespace.clone()
+    }
+
+    fn ssh_url(&self) -> String {
+        self.ssh_url.clone()
+    }
+
+    fn http_url(&self) -> String {
+        self.http_url.clone()
+    }
+
+    fn private(&self) -> bool {
+        self.private
+    }
+
+    fn default_branch(&self) -> Option<String> {
+        self.default_branch.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Snapshot {
+    projects: Vec<SnapshotProject>,
+}
+
+pub fn write_snapshot(path: &Path, projects: Vec<SnapshotProject>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(&Snapshot { projects })
+        .map_err(|error| format!("Failed serializing snapshot: {error}"))?;
+    std::fs::write(path, content).map_err(|error| {
+        format!(
+            "Failed writing snapshot to \"{}\": {}",
+            path::path_as_string(path),
+            error
+        )
+    })
+}
+
+pub fn read_snapshot(path: &Path) -> Result<Vec<SnapshotProject>, String> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        format!(
+            "Failed reading snapshot \"{}\": {}",
+            path::path_as_string(path),
+            error
+        )
+    })?;
+    let snapshot: Snapshot = serde_json::from_str(&content).map_err(|error| {
+        format!(
+            "Failed parsing snapshot \"{}\": {}",
+            path::path_as_string(path),
+            error
+        )
+    })?;
+    Ok(snapshot.projects)
+}
+
+/// Removes the user from an HTTP URL, e.g. `https://user@host/path`
+///
+/// Some forges put the name of the requesting user into the clone URLs, which
+/// is of no use for anybody else.
+fn strip_user(url: &str) -> String {
+    if let Some((scheme, rest)) = url.split_once("://") {
+        if let Some((authority, path)) = rest.split_once('/') {
+            if let Some((_user, host)) = authority.rsplit_once('@') {
+                return format!("{scheme}://{host}/{path}");
+            }
+        }
+    }
+    url.to_string()
+}
+
+/// Builds the remote URL of a project from a template, e.g.
+/// `ssh://git@example.com:2222/{namespace}/{name}.git`
+///
+/// `{path}` is the namespace and the name together, like `{namespace}/{name}`
+/// for projects with a namespace.
+pub fn render_url_template(template: &str, namespace: Option<&str>, name: &str) -> String {
+    let path = match namespace {
+        Some(namespace) => format!("{namespace}/{name}"),
+        None => name.to_string(),
+    };
+    template
+        .replace("{path}", &path)
+        .replace("{namespace}", namespace.unwrap_or_default())
+        .replace("{name}", name)
+}
+
+/// Makes sure that a URL template results in usable remote URLs, before any
+/// project is listed
+pub fn check_url_template(template: &str) -> Result<(), String> {
+    if !template.contains("{name}") && !template.contains("{path}") {
+        return Err(format!(
+            "URL template \"{template}\" has to contain \"{{name}}\" or \"{{path}}\""
+        ));
+    }
+    repo::detect_remote_type(&render_url_template(template, Some("namespace"), "name"))
+        .map(|_| ())
+        .map_err(|error| format!("Invalid URL template \"{template}\": {error}"))
+}
+
+/// Turns projects into repositories, by their namespace
+///
+/// With `url_template`, the remote URLs are built from the template instead of
+/// using the ones reported by the API, see [`render_url_template()`].
+pub fn repos_from_projects<P: Project>(
+    projects: Vec<P>,
+    worktree_setup: bool,
+    force_ssh: bool,
+    remote_name: Option<String>,
+    url_template: Option<&str>,
+) -> HashMap<Option<String>, Vec<repo::Repo>> {
+    let mut ret: HashMap<Option<String>, Vec<repo::Repo>> = HashMap::new();
+
+    let remote_name = remote_name.unwrap_or_else(|| DEFAULT_REMOTE_NAME.to_string());
+
+    for project in projects {
+        let namespace = project.namespace();
+
+        // Names come from the API, they are used as paths below the root later
+        if let Err(error) = path::check_subpath(&project.name())
+            .and_then(|()| namespace.as_deref().map_or(Ok(()), path::check_subpath))
+        {
+            print_warning(&format!(
+                "Skipping repository \"{}\" with invalid name: {}",
+                project.name(),
+                error
+            ));
+            continue;
+        }
+
+        let name = project.name();
+        let mut repo = project.into_repo_config(&remote_name, worktree_setup, force_ssh);
+
+        if let Some(template) = url_template {
+            let url = render_url_template(template, namespace.as_deref(), &name);
+            let remote_type = match repo::detect_remote_type(&url) {
+                Ok(remote_type) => remote_type,
+                Err(error) => {
+                    print_warning(&format!(
+                        "Skipping repository \"{name}\" with invalid URL \"{url}\": {error}"
+                    ));
+                    continue;
+                }
+            };
+            for remote in repo.remotes.iter_mut().flatten() {
+                remote.url = url.clone();
+                remote.remote_type = remote_type.clone();
+            }
+        }
+
+        // Namespace is already part of the hashmap key. I'm not too happy
+        // about the data exchange format here.
+        repo.remove_namespace();
+
+        ret.entry(namespace).or_default().push(repo);
+    }
+
+    ret
+}
+
+/// The minimum role of the requesting user on a GitLab project
+#[derive(Debug, Deserialize, Serialize, clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLevel {
+    Guest,
+    Reporter,
+    Developer,
+    Maintainer,
+    Owner,
+}
+
+impl AccessLevel {
+    /// The value of the access level in the GitLab API
+    pub fn value(self) -> u8 {
+        match self {
+            Self::Guest => 10,
+            Self::Reporter => 20,
+            Self::Developer => 30,
+            Self::Maintainer => 40,
+            Self::Owner => 50,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Filter {
     users: Vec<String>,
     groups: Vec<String>,
+    orgs: Vec<String>,
     owner: bool,
     access: bool,
+    starred: bool,
+    /// Only include projects where the requesting user has at least this role
+    min_access_level: Option<AccessLevel>,
 }
 
 impl Filter {
-    pub fn new(users: Vec<String>, groups: Vec<String>, owner: bool, access: bool) -> Self {
+    pub fn new(
+        users: Vec<String>,
+        groups: Vec<String>,
+        orgs: Vec<String>,
+        owner: bool,
+        access: bool,
+        starred: bool,
+        min_access_level: Option<AccessLevel>,
+    ) -> Self {
         Self {
             users,
             groups,
+            orgs,
             owner,
             access,
+            starred,
+            min_access_level,
         }
     }
 
     pub fn empty(&self) -> bool {
-        self.users.is_empty() && self.groups.is_empty() && !self.owner && !self.access
+        self.users.is_empty()
+            && self.groups.is_empty()
+            && self.orgs.is_empty()
+            && !self.owner
+            && !self.access
+            && !self.starred
+    }
+
+    /// Regroups repositories from [`repos_from_projects()`] by the user, group or
+    /// organization of the filter that they belong to, so that every entry gets a
+    /// single tree, no matter how deep the namespaces below it nest
+    ///
+    /// The part of the namespace below the entry becomes part of the repository
+    /// names, and the longest matching entry wins. Repositories outside of all
+    /// entries (e.g. found via `owner` or `access`) stay grouped by their
+    /// namespace.
+    pub fn group_by_entry(
+        &self,
+        repos: HashMap<Option<String>, Vec<repo::Repo>>,
+    ) -> HashMap<Option<String>, Vec<repo::Repo>> {
+        let mut ret: HashMap<Option<String>, Vec<repo::Repo>> = HashMap::new();
+
+        for (namespace, namespace_repos) in repos {
+            let entry = namespace.as_deref().and_then(|namespace| {
+                self.users
+                    .iter()
+                    .chain(&self.groups)
+                    .chain(&self.orgs)
+                    .filter_map(|entry| {
+                        let rest = if namespace == entry {
+                            ""
+                        } else {
+                            namespace.strip_prefix(entry.as_str())?.strip_prefix('/')?
+                        };
+                        Some((entry.clone(), rest.to_string()))
+                    })
+                    .max_by_key(|(entry, _)| entry.len())
+            });
+
+            match entry {
+                Some((entry, rest)) => {
+                    ret.entry(Some(entry))
+                        .or_default()
+                        .extend(namespace_repos.into_iter().map(|mut repo| {
+                            if !rest.is_empty() {
+                                repo.name = format!("{}/{}", rest, repo.name);
+                            }
+                            repo
+                        }));
+                }
+                None => ret.entry(namespace).or_default().extend(namespace_repos),
+            }
+        }
+
+        ret
     }
 }
 
@@ -113,6 +428,48 @@ pub trait JsonError {
     fn to_string(self) -> String;
 }
 
+/// How to page through the lists of repositories returned by the API
+#[derive(Debug, Default)]
+pub struct Pagination {
+    /// Number of results per page. Larger pages mean fewer requests
+    per_page: Option<u32>,
+    /// Maximum number of requests for listing repositories
+    max_requests: Option<usize>,
+    requests: Cell<usize>,
+}
+
+impl Pagination {
+    pub fn new(per_page: Option<u32>, max_requests: Option<usize>) -> Self {
+        Self {
+            per_page,
+            max_requests,
+            requests: Cell::new(0),
+        }
+    }
+
+    fn count_request(&self) -> Result<(), String> {
+        let requests = self.requests.get() + 1;
+        if let Some(max_requests) = self.max_requests {
+            if requests > max_requests {
+                return Err(format!(
+                    "Reached the limit of {max_requests} API requests, refusing to continue with an incomplete list of repositories"
+                ));
+            }
+        }
+        self.requests.set(requests);
+        Ok(())
+    }
+}
+
+/// Returns the value of the `page` query parameter of a URL
+fn page_number(uri: &str) -> Option<usize> {
+    let (_, query) = uri.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("page="))
+        .and_then(|page| page.parse().ok())
+}
+
 pub trait Provider {
     type Project: serde::de::DeserializeOwned + Project;
     type Error: serde::de::DeserializeOwned + JsonError;
@@ -121,11 +478,13 @@ pub trait Provider {
         filter: Filter,
         secret_token: auth::AuthToken,
         api_url_override: Option<String>,
+        pagination: Pagination,
     ) -> Result<Self, String>
     where
         Self: Sized;
 
     fn filter(&self) -> &Filter;
+    fn pagination(&self) -> &Pagination;
     fn secret_token(&self) -> &auth::AuthToken;
     fn auth_header_key() -> &'static str;
 
@@ -139,6 +498,25 @@ pub trait Provider {
         group: &str,
     ) -> Result<Vec<Self::Project>, ApiErrorResponse<Self::Error>>;
 
+    /// Lists all projects of an organization that are visible to the requesting
+    /// user, no matter whether access was granted by membership, a team or the
+    /// visibility of the project
+    fn get_org_projects(
+        &self,
+        _org: &str,
+    ) -> Result<Vec<Self::Project>, ApiErrorResponse<Self::Error>> {
+        Err(ApiErrorResponse::String(String::from(
+            "Organizations are only supported for GitHub and Azure DevOps, use groups instead",
+        )))
+    }
+
+    /// Lists all projects the requesting user has starred
+    fn get_starred_projects(&self) -> Result<Vec<Self::Project>, ApiErrorResponse<Self::Error>> {
+        Err(ApiErrorResponse::String(String::from(
+            "Starred repositories are only supported for GitLab",
+        )))
+    }
+
     fn get_own_projects(&self) -> Result<Vec<Self::Project>, ApiErrorResponse<Self::Error>> {
         self.get_user_projects(&self.get_current_user()?)
     }
@@ -147,12 +525,30 @@ pub trait Provider {
 
     fn get_current_user(&self) -> Result<String, ApiErrorResponse<Self::Error>>;
 
+    /// Creates a new, empty project `name` in `group`, or for the requesting user
+    /// without a group
+    fn create_project(
+        &self,
+        name: &str,
+        group: Option<&str>,
+        private: bool,
+    ) -> Result<Self::Project, ApiErrorResponse<Self::Error>>;
+
+    /// Forks the project at `path` (e.g. `owner/name`) into `group`, or for the
+    /// requesting user without a group
+    fn fork_project(
+        &self,
+        path: &str,
+        group: Option<&str>,
+    ) -> Result<Self::Project, ApiErrorResponse<Self::Error>>;
+
     ///
     /// Calls the API at specific uri and expects a successful response of Vec<T> back, or an error
     /// response U
     ///
     /// Handles paging with "link" HTTP headers properly and reads all pages to
-    /// the end.
+    /// the end, within the limits of `pagination()`. Providers that page
+    /// differently have to override this.
     fn call_list(
         &self,
         uri: &str,
@@ -160,43 +556,75 @@ pub trait Provider {
     ) -> Result<Vec<Self::Project>, ApiErrorResponse<Self::Error>> {
         let mut results = vec![];
 
-        match ureq::get(uri)
-            .set("accept", accept_header.unwrap_or("application/json"))
-            .set(
-                "authorization",
-                &format!(
-                    "{} {}",
-                    Self::auth_header_key(),
-                    &self.secret_token().access()
-                ),
-            )
-            .call()
-        {
-            Err(ureq::Error::Transport(error)) => return Err(error.to_string())?,
-            Err(ureq::Error::Status(_code, response)) => {
-                let r: Self::Error = response
-                    .into_json()
-                    .map_err(|error| format!("Failed deserializing error response: {error}"))?;
-                return Err(ApiErrorResponse::Json(r));
-            }
-            Ok(response) => {
-                if let Some(link_header) = response.header("link") {
-                    let link_header =
-                        parse_link_header::parse(link_header).map_err(|error| error.to_string())?;
+        let mut uri = match self.pagination().per_page {
+            Some(per_page) => format!(
+                "{uri}{}per_page={per_page}",
+                if uri.contains('?') { '&' } else { '?' }
+            ),
+            None => uri.to_string(),
+        };
+        let mut page = 1;
+        let mut last_page = None;
 
-                    let next_page = link_header.get(&Some(String::from("next")));
+        let _progress = ProgressGuard;
+        loop {
+            self.pagination().count_request()?;
 
-                    if let Some(page) = next_page {
-                        let following_repos = self.call_list(&page.raw_uri, accept_header)?;
-                        results.extend(following_repos);
-                    }
+            let response = match ureq::get(&uri)
+                .set("accept", accept_header.unwrap_or("application/json"))
+                .set(
+                    "authorization",
+                    &format!(
+                        "{} {}",
+                        Self::auth_header_key(),
+                        &self.secret_token().access()
+                    ),
+                )
+                .call()
+            {
+                Err(ureq::Error::Transport(error)) => {
+                    return Err(error.to_string())?;
+                }
+                Err(ureq::Error::Status(_code, response)) => {
+                    let r: Self::Error = response
+                        .into_json()
+                        .map_err(|error| format!("Failed deserializing error response: {error}"))?;
+                    return Err(ApiErrorResponse::Json(r));
                 }
+                Ok(response) => response,
+            };
+
+            let mut next_page = None;
+            if let Some(link_header) = response.header("link") {
+                let link_header =
+                    parse_link_header::parse(link_header).map_err(|error| error.to_string())?;
 
-                let result: Vec<Self::Project> = response
-                    .into_json()
-                    .map_err(|error| format!("Failed deserializing response: {error}"))?;
+                next_page = link_header
+                    .get(&Some(String::from("next")))
+                    .map(|link| link.raw_uri.clone());
 
-                results.extend(result);
+                // The last page does not link to itself
+                if let Some(last) = link_header.get(&Some(String::from("last"))) {
+                    last_page = page_number(&last.raw_uri);
+                }
+            }
+
+            if let Some(last_page) = last_page {
+                print_progress(&format!("Listing repositories: page {page}/{last_page}"));
+            }
+
+            let result: Vec<Self::Project> = response
+                .into_json()
+                .map_err(|error| format!("Failed deserializing response: {error}"))?;
+
+            results.extend(result);
+
+            match next_page {
+                Some(next_page) => {
+                    uri = next_page;
+                    page += 1;
+                }
+                None => break,
             }
         }
 
@@ -208,7 +636,22 @@ pub trait Provider {
         worktree_setup: bool,
         force_ssh: bool,
         remote_name: Option<String>,
+        url_template: Option<&str>,
     ) -> Result<HashMap<Option<String>, Vec<repo::Repo>>, String> {
+        if let Some(template) = url_template {
+            check_url_template(template)?;
+        }
+        Ok(repos_from_projects(
+            timings::measure("provider enumeration", None, || self.get_projects())?,
+            worktree_setup,
+            force_ssh,
+            remote_name,
+            url_template,
+        ))
+    }
+
+    /// Lists all projects that match the filter, without duplicates
+    fn get_projects(&self) -> Result<Vec<Self::Project>, String> {
         let mut repos = vec![];
 
         if self.filter().owner {
@@ -241,6 +684,27 @@ pub trait Provider {
             }
         }
 
+        if self.filter().starred {
+            let starred_projects = self.get_starred_projects().map_err(|error| match error {
+                ApiErrorResponse::Json(x) => x.to_string(),
+                ApiErrorResponse::String(s) => s,
+            })?;
+
+            for starred_project in starred_projects {
+                let mut already_present = false;
+                for repo in &repos {
+                    if repo.name() == starred_project.name()
+                        && repo.namespace() == starred_project.namespace()
+                    {
+                        already_present = true;
+                    }
+                }
+                if !already_present {
+                    repos.push(starred_project);
+                }
+            }
+        }
+
         for user in &self.filter().users {
             let user_projects = self.get_user_projects(user).map_err(|error| match error {
                 ApiErrorResponse::Json(x) => x.to_string(),
@@ -289,23 +753,34 @@ pub trait Provider {
             }
         }
 
-        let mut ret: HashMap<Option<String>, Vec<repo::Repo>> = HashMap::new();
-
-        let remote_name = remote_name.unwrap_or_else(|| DEFAULT_REMOTE_NAME.to_string());
-
-        for repo in repos {
-            let namespace = repo.namespace();
-
-            let mut repo = repo.into_repo_config(&remote_name, worktree_setup, force_ssh);
-
-            // Namespace is already part of the hashmap key. I'm not too happy
-            // about the data exchange format here.
-            repo.remove_namespace();
+        for org in &self.filter().orgs {
+            let org_projects = self.get_org_projects(org).map_err(|error| {
+                format!(
+                    "organization \"{}\": {}",
+                    org,
+                    match error {
+                        ApiErrorResponse::Json(x) => x.to_string(),
+                        ApiErrorResponse::String(s) => s,
+                    }
+                )
+            })?;
+            for org_project in org_projects {
+                let mut already_present = false;
+                for repo in &repos {
+                    if repo.name() == org_project.name()
+                        && repo.namespace() == org_project.namespace()
+                    {
+                        already_present = true;
+                    }
+                }
 
-            ret.entry(namespace).or_default().push(repo);
+                if !already_present {
+                    repos.push(org_project);
+                }
+            }
         }
 
-        Ok(ret)
+        Ok(repos)
     }
 }
 
@@ -341,3 +816,417 @@ where
 
     Ok(response)
 }
+
+fn call_post<T, U>(
+    uri: &str,
+    auth_header_key: &str,
+    secret_token: &auth::AuthToken,
+    accept_header: Option<&str>,
+    body: serde_json::Value,
+) -> Result<T, ApiErrorResponse<U>>
+where
+    T: serde::de::DeserializeOwned,
+    U: serde::de::DeserializeOwned + JsonError,
+{
+    let response = match ureq::post(uri)
+        .set("accept", accept_header.unwrap_or("application/json"))
+        .set(
+            "authorization",
+            &format!("{} {}", auth_header_key, secret_token.access()),
+        )
+        .send_json(body)
+    {
+        Err(ureq::Error::Transport(error)) => return Err(error.to_string())?,
+        Err(ureq::Error::Status(_code, response)) => {
+            let response: U = response
+                .into_json()
+                .map_err(|error| format!("Failed deserializing error response: {error}"))?;
+            return Err(ApiErrorResponse::Json(response));
+        }
+        Ok(response) => response
+            .into_json()
+            .map_err(|error| format!("Failed deserializing response: {error}"))?,
+    };
+
+    Ok(response)
+}
+
+/// Creates a new project on the forge and returns it as a repository with a
+/// single remote, like the ones returned by [`Provider::get_repos()`]
+#[allow(clippy::too_many_arguments)]
+pub fn create_repo(
+    provider: &RemoteProvider,
+    secret_token: auth::AuthToken,
+    api_url_override: Option<String>,
+    name: &str,
+    group: Option<&str>,
+    private: bool,
+    force_ssh: bool,
+    remote_name: Option<String>,
+) -> Result<repo::Repo, String> {
+    fn create<P: Provider>(
+        provider: P,
+        name: &str,
+        group: Option<&str>,
+        private: bool,
+        force_ssh: bool,
+        remote_name: &str,
+    ) -> Result<repo::Repo, String> {
+        let project =
+            provider
+                .create_project(name, group, private)
+                .map_err(|error| match error {
+                    ApiErrorResponse::Json(x) => x.to_string(),
+                    ApiErrorResponse::String(s) => s,
+                })?;
+        Ok(project.into_repo_config(remote_name, false, force_ssh))
+    }
+
+    let filter = Filter::new(vec![], vec![], vec![], false, false, false, None);
+    let pagination = Pagination::new(None, None);
+    let remote_name = remote_name.unwrap_or_else(|| DEFAULT_REMOTE_NAME.to_string());
+
+    match provider {
+        RemoteProvider::Github => create(
+            Github::new(filter, secret_token, api_url_override, pagination)?,
+            name,
+            group,
+            private,
+            force_ssh,
+            &remote_name,
+        ),
+        RemoteProvider::Gitlab => create(
+            Gitlab::new(filter, secret_token, api_url_override, pagination)?,
+            name,
+            group,
+            private,
+            force_ssh,
+            &remote_name,
+        ),
+        RemoteProvider::Bitbucket => create(
+            Bitbucket::new(filter, secret_token, api_url_override, pagination)?,
+            name,
+            group,
+            private,
+            force_ssh,
+            &remote_name,
+        ),
+        RemoteProvider::AzureDevops => create(
+            AzureDevops::new(filter, secret_token, api_url_override, pagination)?,
+            name,
+            group,
+            private,
+            force_ssh,
+            &remote_name,
+        ),
+    }
+}
+
+/// Returns the name of the user that `secret_token` belongs to, which shows
+/// whether the forge can be reached with it
+pub fn current_user(
+    provider: &RemoteProvider,
+    secret_token: auth::AuthToken,
+    api_url_override: Option<String>,
+) -> Result<String, String> {
+    fn user<P: Provider>(provider: P) -> Result<String, String> {
+        provider.get_current_user().map_err(|error| match error {
+            ApiErrorResponse::Json(x) => x.to_string(),
+            ApiErrorResponse::String(s) => s,
+        })
+    }
+
+    let filter = Filter::new(vec![], vec![], vec![], false, false, false, None);
+    let pagination = Pagination::new(None, None);
+
+    match provider {
+        RemoteProvider::Github => user(Github::new(
+            filter,
+            secret_token,
+            api_url_override,
+            pagination,
+        )?),
+        RemoteProvider::Gitlab => user(Gitlab::new(
+            filter,
+            secret_token,
+            api_url_override,
+            pagination,
+        )?),
+        RemoteProvider::Bitbucket => user(Bitbucket::new(
+            filter,
+            secret_token,
+            api_url_override,
+            pagination,
+        )?),
+        RemoteProvider::AzureDevops => user(AzureDevops::new(
+            filter,
+            secret_token,
+            api_url_override,
+            pagination,
+        )?),
+    }
+}
+
+/// Returns the path of a project on a forge from one of its remote URLs, e.g.
+/// `owner/name` for `https://github.com/owner/name.git`
+pub fn project_path(remote_url: &str) -> Result<String, String> {
+    let path = match remote_url.split_once("://") {
+        Some(("file", _)) => None,
+        Some((_, rest)) => rest.split_once('/').map(|(_, path)| path),
+        None => remote_url.split_once(':').map(|(_, path)| path),
+    };
+
+    path.map(|path| path.trim_end_matches('/'))
+        .map(|path| path.strip_suffix(".git").unwrap_or(path))
+        .map(|path| path.trim_start_matches('/'))
+        .filter(|path| path.contains('/'))
+        .map(ToString::to_string)
+        .ok_or_else(|| format!("Could not determine the project of \"{remote_url}\""))
+}
+
+/// Forks the project at `upstream_url` on the forge and returns the fork as a
+/// repository with a single remote, like the ones returned by
+/// [`Provider::get_repos()`]
+pub fn fork_repo(
+    provider: &RemoteProvider,
+    secret_token: auth::AuthToken,
+    api_url_override: Option<String>,
+    upstream_url: &str,
+    group: Option<&str>,
+    force_ssh: bool,
+) -> Result<repo::Repo, String> {
+    fn fork<P: Provider>(
+        provider: P,
+        path: &str,
+        group: Option<&str>,
+        force_ssh: bool,
+    ) -> Result<repo::Repo, String> {
+        let project = provider
+            .fork_project(path, group)
+            .map_err(|error| match error {
+                ApiErrorResponse::Json(x) => x.to_string(),
+                ApiErrorResponse::String(s) => s,
+            })?;
+        Ok(project.into_repo_config(DEFAULT_REMOTE_NAME, false, force_ssh))
+    }
+
+    let path = project_path(upstream_url)?;
+    let filter = Filter::new(vec![], vec![], vec![], false, false, false, None);
+    let pagination = Pagination::new(None, None);
+
+    match provider {
+        RemoteProvider::Github => fork(
+            Github::new(filter, secret_token, api_url_override, pagination)?,
+            &path,
+            group,
+            force_ssh,
+        ),
+        RemoteProvider::Gitlab => fork(
+            Gitlab::new(filter, secret_token, api_url_override, pagination)?,
+            &path,
+            group,
+            force_ssh,
+        ),
+        RemoteProvider::Bitbucket => fork(
+            Bitbucket::new(filter, secret_token, api_url_override, pagination)?,
+            &path,
+            group,
+            force_ssh,
+        ),
+        RemoteProvider::AzureDevops => fork(
+            AzureDevops::new(filter, secret_token, api_url_override, pagination)?,
+            &path,
+            group,
+            force_ssh,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_project_path() {
+        for url in [
+            "https://github.com/owner/name.git",
+            "https://github.com/owner/name",
+            "https://github.com/owner/name/",
+            "ssh://git@github.com/owner/name.git",
+            "ssh://git@github.com:22/owner/name.git",
+            "git@github.com:owner/name.git",
+        ] {
+            assert_eq!(project_path(url).unwrap(), "owner/name", "{url}");
+        }
+
+        assert_eq!(
+            project_path("https://gitlab.com/group/subgroup/name.git").unwrap(),
+            "group/subgroup/name"
+        );
+
+        assert!(project_path("https://github.com/owner").is_err());
+        assert!(project_path("file:///tmp/owner/name.git").is_err());
+        assert!(project_path("/tmp/owner/name").is_err());
+    }
+
+    #[test]
+    fn check_github_visibility() {
+        let project = |private: bool, visibility: Option<&str>| -> github::GithubProject {
+            serde_json::from_value(serde_json::json!({
+                "id": 1,
+                "name": "name",
+                "full_name": "org/name",
+                "clone_url": "https://github.com/org/name.git",
+                "ssh_url": "git@github.com:org/name.git",
+                "private": private,
+                "visibility": visibility,
+            }))
+            .unwrap()
+        };
+
+        assert!(!project(false, None).private());
+        assert!(!project(false, Some("public")).private());
+        assert!(project(true, Some("private")).private());
+        assert!(project(false, Some("internal")).private());
+    }
+
+    #[test]
+    fn check_bitbucket_project() {
+        let project: bitbucket::BitbucketProject = serde_json::from_value(serde_json::json!({
+            "uuid": "{1234}",
+            "slug": "name",
+            "full_name": "workspace/name",
+            "is_private": false,
+            "mainbranch": { "name": "main" },
+            "links": {
+                "clone": [
+                    { "name": "https", "href": "https://user@bitbucket.org/workspace/name.git" },
+                    { "name": "ssh", "href": "git@bitbucket.org:workspace/name.git" },
+                ],
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(project.id(), "bitbucket:1234");
+        assert_eq!(project.namespace().as_deref(), Some("workspace"));
+        assert_eq!(
+            project.http_url(),
+            "https://bitbucket.org/workspace/name.git"
+        );
+        assert_eq!(project.ssh_url(), "git@bitbucket.org:workspace/name.git");
+        assert_eq!(project.default_branch().as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn check_azure_devops_repository() {
+        let mut repository: azure_devops::AzureDevopsRepository =
+            serde_json::from_value(serde_json::json!({
+                "id": "5febef5a-833d-4e14-b9c0-14cb638f91e6",
+                "name": "name",
+                "project": { "id": "6ce954b1", "name": "project", "visibility": "public" },
+                "remoteUrl": "https://org@dev.azure.com/org/project/_git/name",
+                "sshUrl": "git@ssh.dev.azure.com:v3/org/project/name",
+                "defaultBranch": "refs/heads/main",
+            }))
+            .unwrap();
+        repository.organization = String::from("org");
+
+        assert_eq!(
+            repository.id(),
+            "azure-devops:5febef5a-833d-4e14-b9c0-14cb638f91e6"
+        );
+        assert_eq!(repository.namespace().as_deref(), Some("org/project"));
+        assert_eq!(
+            repository.http_url(),
+            "https://dev.azure.com/org/project/_git/name"
+        );
+        assert!(!repository.private());
+        assert_eq!(repository.default_branch().as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn check_url_templates() {
+        let template = "ssh://git@example.com:2222/{namespace}/{name}.git";
+        assert_eq!(
+            render_url_template(template, Some("group/subgroup"), "name"),
+            "ssh://git@example.com:2222/group/subgroup/name.git"
+        );
+        assert!(check_url_template(template).is_ok());
+
+        let template = "git@example.com:{path}.git";
+        assert_eq!(
+            render_url_template(template, None, "name"),
+            "git@example.com:name.git"
+        );
+        assert_eq!(
+            render_url_template(template, Some("user"), "name"),
+            "git@example.com:user/name.git"
+        );
+        assert!(check_url_template(template).is_ok());
+
+        assert!(check_url_template("ssh://git@example.com/{namespace}.git").is_err());
+        assert!(check_url_template("http://example.com/{path}.git").is_err());
+    }
+
+    #[test]
+    fn check_group_by_entry() {
+        let repo = |name: &str| repo::Repo {
+            name: name.to_string(),
+            namespace: None,
+            worktree_setup: false,
+            remotes: None,
+            provider_id: None,
+            tags: Vec::new(),
+            filter: None,
+            default_branch: None,
+            dir: None,
+            remote_name: None,
+        };
+
+        let filter = Filter::new(
+            vec![String::from("user")],
+            vec![String::from("group"), String::from("group/nested")],
+            vec![],
+            true,
+            false,
+            false,
+            None,
+        );
+        let repos = HashMap::from([
+            (Some(String::from("user")), vec![repo("a")]),
+            (Some(String::from("group")), vec![repo("b")]),
+            (Some(String::from("group/sub/deep")), vec![repo("c")]),
+            (Some(String::from("group/nested/sub")), vec![repo("d")]),
+            (Some(String::from("groupie")), vec![repo("e")]),
+            (None, vec![repo("f")]),
+        ]);
+
+        let mut grouped: Vec<(Option<String>, Vec<String>)> = filter
+            .group_by_entry(repos)
+            .into_iter()
+            .map(|(key, repos)| {
+                let mut names: Vec<String> = repos.into_iter().map(|repo| repo.name).collect();
+                names.sort();
+                (key, names)
+            })
+            .collect();
+        grouped.sort();
+
+        assert_eq!(
+            grouped,
+            vec![
+                (None, vec![String::from("f")]),
+                (
+                    Some(String::from("group")),
+                    vec![String::from("b"), String::from("sub/deep/c")]
+                ),
+                (
+                    Some(String::from("group/nested")),
+                    vec![String::from("sub/d")]
+                ),
+                (Some(String::from("groupie")), vec![String::from("e")]),
+                (Some(String::from("user")), vec![String::from("a")]),
+            ]
+        );
+    }
+}
@@ -1,17 +1,26 @@
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use git2::Repository;
 
+use super::browse;
+use super::known_hosts;
 use super::output::*;
 use super::path;
+use super::profile;
 use super::worktree;
 
 const WORKTREE_CONFIG_FILE_NAME: &str = "grm.toml";
+const PROJECT_CONFIG_FILE_NAME: &str = ".grm.toml";
 const GIT_CONFIG_BARE_KEY: &str = "core.bare";
 const GIT_CONFIG_PUSH_DEFAULT: &str = "push.default";
+const GIT_CONFIG_PUSH_REMOTE: &str = "remote.pushDefault";
+const GIT_CONFIG_PROVIDER_ID_KEY: &str = "grm.providerid";
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum RemoteType {
     Ssh,
@@ -19,8 +28,35 @@ pub enum RemoteType {
     File,
 }
 
+/// The checks before removing a worktree that are skipped
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WorktreeRemoveOverrides {
+    /// Remove the worktree even if it contains uncommitted changes
+    pub ignore_changes: bool,
+    /// Remove the worktree even if its branch is not merged into any
+    /// persistent branch
+    pub ignore_unmerged: bool,
+    /// Remove the worktree even if its branch differs from its remote branch,
+    /// or does not have one
+    pub ignore_upstream_divergence: bool,
+}
+
+impl WorktreeRemoveOverrides {
+    /// Skips all checks
+    pub fn all() -> Self {
+        Self {
+            ignore_changes: true,
+            ignore_unmerged: true,
+            ignore_upstream_divergence: true,
+        }
+    }
+}
+
 pub enum WorktreeRemoveFailureReason {
     Changes(String),
+    /// A submodule contains work that would be lost, see
+    /// [`RepoHandle::check_worktree_removable`]
+    Submodules(String),
     Error(String),
     NotMerged(String),
 }
@@ -59,17 +95,308 @@ pub struct TrackingConfig {
     pub default: bool,
     pub default_remote: String,
     pub default_remote_prefix: Option<String>,
+    /// Set up the worktree without a remote tracking branch if the remote
+    /// refuses the creation of the branch
+    #[serde(default)]
+    pub local_fallback: bool,
+}
+
+/// Restricts which remotes are fetched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FetchConfig {
+    /// Only fetch the remotes that are part of the configuration, and ignore
+    /// any remotes that were added by hand
+    pub only_configured: Option<bool>,
+    /// Remove remote-tracking branches whose branch was deleted on the remote.
+    /// Without this set, the `fetch.prune` setting of git is used.
+    pub prune: Option<bool>,
+}
+
+impl FetchConfig {
+    pub fn only_configured(config: Option<&Self>) -> bool {
+        config
+            .and_then(|config| config.only_configured)
+            .unwrap_or(false)
+    }
+
+    pub fn prune(config: Option<&Self>) -> Option<bool> {
+        config.and_then(|config| config.prune)
+    }
+}
+
+/// How new repositories are cloned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloneConfig {
+    /// Borrow the objects of another configured repository with the same remote
+    /// instead of downloading them again
+    pub share_objects: Option<bool>,
+}
+
+impl CloneConfig {
+    pub fn share_objects(config: Option<&Self>) -> bool {
+        config
+            .and_then(|config| config.share_objects)
+            .unwrap_or(false)
+    }
+}
+
+/// The longest time to wait before a retry, no matter how often it was doubled
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Retries of clones and fetches that failed because of network problems
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfig {
+    /// How often to retry after the first attempt failed
+    pub retries: Option<u32>,
+    /// Seconds to wait before the first retry, doubled for every further retry
+    pub backoff: Option<u64>,
+}
+
+impl RetryConfig {
+    pub fn retries(&self) -> u32 {
+        self.retries.unwrap_or(0)
+    }
+
+    pub fn backoff(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.backoff.unwrap_or(1)).min(MAX_RETRY_DELAY)
+    }
+
+    /// The delay before the retry after the one that waited `delay`
+    fn next_delay(delay: std::time::Duration) -> std::time::Duration {
+        delay.saturating_mul(2).min(MAX_RETRY_DELAY)
+    }
+
+    /// Runs `operation` until it succeeds, fails with an error that retrying
+    /// does not help with, or all retries are used up
+    fn run<T>(
+        &self,
+        description: &str,
+        mut operation: impl FnMut() -> Result<T, git2::Error>,
+    ) -> Result<T, git2::Error> {
+        let mut delay = self.backoff();
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Err(error) if attempt < self.retries() && is_retryable(&error) => {
+                    attempt += 1;
+                    print_warning(&format!(
+                        "{} failed: {}. Retrying in {}s ({}/{})",
+                        description,
+                        error.message(),
+                        delay.as_secs(),
+                        attempt,
+                        self.retries()
+                    ));
+                    std::thread::sleep(delay);
+                    delay = Self::next_delay(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether `error` is a transport error that might go away on its own, e.g. a
+/// connection that timed out. Authentication and permission errors are not.
+fn is_retryable(error: &git2::Error) -> bool {
+    if matches!(
+        error.code(),
+        git2::ErrorCode::Auth | git2::ErrorCode::Certificate | git2::ErrorCode::NotFound
+    ) {
+        return false;
+    }
+    match error.class() {
+        git2::ErrorClass::Net | git2::ErrorClass::Ssh => true,
+        // Failed connections are reported by the operating system
+        git2::ErrorClass::Os => error.message().starts_with("failed to connect"),
+        // Client errors like 403 or 404 do not change by asking again
+        git2::ErrorClass::Http => !error.message().contains("status code: 4"),
+        _ => false,
+    }
+}
+
+/// The steps of `grm wt sync`. All of them run unless disabled here.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorktreeSyncConfig {
+    /// Fetch the remotes, like `grm wt fetch`
+    pub fetch: Option<bool>,
+    /// Fast-forward the worktrees to their remote branches, like `grm wt pull`
+    pub pull: Option<bool>,
+    /// Rebase the worktrees onto the default branch, like `grm wt rebase`
+    pub rebase: Option<bool>,
+    /// Remove worktrees that are merged, like `grm wt clean`
+    pub clean: Option<bool>,
+    /// Stash and unstash changes around pulling and rebasing
+    pub stash: Option<bool>,
+}
+
+impl WorktreeSyncConfig {
+    pub fn fetch(config: Option<&Self>) -> bool {
+        config.and_then(|config| config.fetch).unwrap_or(true)
+    }
+
+    pub fn pull(config: Option<&Self>) -> bool {
+        config.and_then(|config| config.pull).unwrap_or(true)
+    }
+
+    pub fn rebase(config: Option<&Self>) -> bool {
+        config.and_then(|config| config.rebase).unwrap_or(true)
+    }
+
+    pub fn clean(config: Option<&Self>) -> bool {
+        config.and_then(|config| config.clean).unwrap_or(true)
+    }
+
+    pub fn stash(config: Option<&Self>) -> bool {
+        config.and_then(|config| config.stash).unwrap_or(false)
+    }
 }
 
+/// Settings that a project can commit to its repository, in `.grm.toml`
+///
+/// They are defaults for the `grm.toml` of the user, which always wins.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Goes first into `persistent_branches`, so it is the base for rebasing and
+    /// cleaning up worktrees
+    pub default_branch: Option<String>,
+
+    pub persistent_branches: Option<Vec<String>>,
+
+    /// Commands that are run after `grm repos sync` cloned the repository, only
+    /// with `--run-hooks`
+    pub post_clone: Option<Vec<String>>,
+
+    /// Directories to check out in new worktrees, see `git sparse-checkout`
+    pub sparse: Option<Vec<String>>,
+}
+
+/// What happens to the branches of worktrees that are deleted
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
+pub struct WorktreeCleanupConfig {
+    /// Reference to keep the last commit of a deleted branch under, e.g.
+    /// `refs/archive/{branch}`. `{branch}` is replaced with the branch name.
+    pub archive: Option<String>,
+    /// Delete the remote branch of a worktree that `wt clean` removes, if the
+    /// remote branch is merged into one of the persistent branches
+    pub delete_remote: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct WorktreeRootConfig {
     pub persistent_branches: Option<Vec<String>>,
 
     pub track: Option<TrackingConfig>,
+
+    /// Command to open a worktree with, e.g. `code {path}`. `{path}` is replaced
+    /// with the path of the worktree.
+    pub open_command: Option<String>,
+
+    pub fetch: Option<FetchConfig>,
+
+    /// Patterns of worktrees that are left alone by bulk operations like
+    /// `wt pull`, `wt rebase`, `wt clean` and `wt sync`. `*` matches any number of
+    /// characters, `?` a single one.
+    pub skip: Option<Vec<String>>,
+
+    pub sync: Option<WorktreeSyncConfig>,
+
+    pub cleanup: Option<WorktreeCleanupConfig>,
+
+    /// Directories to check out in new worktrees, see `git sparse-checkout`
+    pub sparse: Option<Vec<String>>,
+
+    /// Web interfaces of self-hosted forges for `grm wt browse`
+    pub web_urls: Option<Vec<browse::WebUrlTemplate>>,
 }
 
-pub fn read_worktree_root_config(
+impl WorktreeRootConfig {
+    /// Fills in everything that `config` does not set from the configuration of
+    /// the project
+    pub fn with_project_config(config: Option<Self>, project: ProjectConfig) -> Option<Self> {
+        let mut persistent_branches: Vec<String> = project.default_branch.into_iter().collect();
+        for branch in project.persistent_branches.into_iter().flatten() {
+            if !persistent_branches.contains(&branch) {
+                persistent_branches.push(branch);
+            }
+        }
+        let persistent_branches = Some(persistent_branches).filter(|branches| !branches.is_empty());
+
+        if config.is_none() && persistent_branches.is_none() && project.sparse.is_none() {
+            return None;
+        }
+
+        let mut config = config.unwrap_or_default();
+        if config.persistent_branches.is_none() {
+            config.persistent_branches = persistent_branches;
+        }
+        if config.sparse.is_none() {
+            config.sparse = project.sparse;
+        }
+        Some(config)
+    }
+
+    /// Returns the first `skip` pattern that matches the worktree `name`
+    pub fn skip_pattern<'a>(config: Option<&'a Self>, name: &str) -> Option<&'a str> {
+        config
+            .and_then(|config| config.skip.as_ref())
+            .and_then(|patterns| {
+                patterns
+                    .iter()
+                    .find(|pattern| matches_pattern(pattern, name))
+            })
+            .map(String::as_str)
+    }
+}
+
+/// The part of a message about a deleted worktree that tells where its branch
+/// was archived, if it was
+pub fn archived_to(archive: Option<&str>) -> String {
+    archive.map_or_else(String::new, |archive| format!(", archived to {archive}"))
+}
+
+/// Matches `name` against a shell-like `pattern` that may contain `*` and `?`
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and the position in the name it
+    // was matched against, to backtrack to on a mismatch
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn read_user_worktree_root_config(
     worktree_root: &Path,
 ) -> Result<Option<WorktreeRootConfig>, String> {
     let path = worktree_root.join(WORKTREE_CONFIG_FILE_NAME);
@@ -101,6 +428,29 @@ pub fn read_worktree_root_config(
     Ok(Some(config))
 }
 
+/// Reads `grm.toml` of a worktree setup, together with the `.grm.toml` that the
+/// project committed to its default branch, see [`ProjectConfig`]
+///
+/// A broken `.grm.toml` is out of the hands of the user, so it is only warned
+/// about.
+pub fn read_worktree_root_config(
+    worktree_root: &Path,
+) -> Result<Option<WorktreeRootConfig>, String> {
+    let config = read_user_worktree_root_config(worktree_root)?;
+
+    let Ok(repo) = RepoHandle::open(worktree_root, true) else {
+        return Ok(config);
+    };
+    Ok(match repo.project_config() {
+        Ok(Some(project_config)) => WorktreeRootConfig::with_project_config(config, project_config),
+        Ok(None) => config,
+        Err(error) => {
+            print_warning(&format!("Ignoring the project configuration: {error}"));
+            config
+        }
+    })
+}
+
 impl std::error::Error for RepoError {}
 
 impl std::fmt::Display for RepoError {
@@ -109,19 +459,30 @@ impl std::fmt::Display for RepoError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Remote {
     pub name: String,
     pub url: String,
     pub remote_type: RemoteType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Repo {
     pub name: String,
     pub namespace: Option<String>,
     pub worktree_setup: bool,
     pub remotes: Option<Vec<Remote>>,
+    /// The ID of the project on the forge, stays the same on renames and transfers
+    pub provider_id: Option<String>,
+    pub tags: Vec<String>,
+    /// Partial clone filter, only used for the initial clone
+    pub filter: Option<String>,
+    /// The branch of the initial worktree, guessed if unset
+    pub default_branch: Option<String>,
+    /// The directory below the tree root, if it differs from the full name
+    pub dir: Option<String>,
+    /// The name of the first remote, regardless of its name in `remotes`
+    pub remote_name: Option<String>,
 }
 
 impl Repo {
@@ -132,17 +493,25 @@ impl Repo {
         }
     }
 
+    /// The path of the repository relative to the tree root
+    pub fn directory(&self) -> String {
+        self.dir.clone().unwrap_or_else(|| self.fullname())
+    }
+
     pub fn remove_namespace(&mut self) {
         self.namespace = None;
     }
 }
 
+#[derive(Serialize)]
 pub struct RepoChanges {
     pub files_new: usize,
     pub files_modified: usize,
     pub files_deleted: usize,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SubmoduleStatus {
     Clean,
     Uninitialized,
@@ -157,7 +526,11 @@ pub enum RemoteTrackingStatus {
     Diverged(usize, usize),
 }
 
+/// The status of a repository. Its JSON form (see `grm repos status --json`)
+/// uses objects with named fields instead of tuples.
+#[derive(Serialize)]
 pub struct RepoStatus {
+    #[serde(serialize_with = "serialize_operation")]
     pub operation: Option<git2::RepositoryState>,
 
     pub empty: bool,
@@ -166,19 +539,299 @@ pub struct RepoStatus {
 
     pub head: Option<String>,
 
+    /// The commit (short ID) that HEAD points to if no branch is checked out
+    pub detached_head: Option<String>,
+
     pub changes: Option<RepoChanges>,
 
     pub worktrees: usize,
 
+    #[serde(serialize_with = "serialize_submodules")]
     pub submodules: Option<Vec<(String, SubmoduleStatus)>>,
 
+    #[serde(serialize_with = "serialize_branches")]
     pub branches: Vec<(String, Option<(String, RemoteTrackingStatus)>)>,
+
+    /// Submodules whose commit is not on any of their remotes, with that
+    /// commit. Only filled if requested, see [`RepoHandle::unpushed_submodules`]
+    #[serde(serialize_with = "serialize_unpushed_submodules")]
+    pub unpushed_submodules: Vec<(String, String)>,
+
+    /// The working copy is managed by jj, see [`RepoHandle::detect_jj`]. As jj
+    /// commits all changes automatically, `changes` are not reported as
+    /// uncommitted then.
+    pub jj: bool,
+}
+
+/// The operation in progress as a lowercase name, e.g. "rebase"
+fn serialize_operation<S: serde::Serializer>(
+    operation: &Option<git2::RepositoryState>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let name = operation.and_then(|operation| match operation {
+        git2::RepositoryState::Clean => None,
+        git2::RepositoryState::Merge => Some("merge"),
+        git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => Some("revert"),
+        git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => {
+            Some("cherry_pick")
+        }
+        git2::RepositoryState::Bisect => Some("bisect"),
+        git2::RepositoryState::Rebase
+        | git2::RepositoryState::RebaseInteractive
+        | git2::RepositoryState::RebaseMerge => Some("rebase"),
+        git2::RepositoryState::ApplyMailbox | git2::RepositoryState::ApplyMailboxOrRebase => {
+            Some("apply_mailbox")
+        }
+    });
+    name.serialize(serializer)
+}
+
+fn serialize_submodules<S: serde::Serializer>(
+    submodules: &Option<Vec<(String, SubmoduleStatus)>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Submodule<'a> {
+        name: &'a str,
+        status: &'a SubmoduleStatus,
+    }
+
+    submodules
+        .as_ref()
+        .map(|submodules| {
+            submodules
+                .iter()
+                .map(|(name, status)| Submodule { name, status })
+                .collect::<Vec<_>>()
+        })
+        .serialize(serializer)
+}
+
+/// Every branch with its remote branch, if any, and how far they are apart
+fn serialize_branches<S: serde::Serializer>(
+    branches: &[(String, Option<(String, RemoteTrackingStatus)>)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct BranchStatus<'a> {
+        name: &'a str,
+        upstream: Option<Upstream<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Upstream<'a> {
+        name: &'a str,
+        ahead: usize,
+        behind: usize,
+    }
+
+    serializer.collect_seq(branches.iter().map(|(name, remote_branch)| BranchStatus {
+        name,
+        upstream: remote_branch.as_ref().map(|(remote_branch_name, status)| {
+            let (ahead, behind) = status.ahead_behind();
+            Upstream {
+                name: remote_branch_name,
+                ahead,
+                behind,
+            }
+        }),
+    }))
+}
+
+fn serialize_unpushed_submodules<S: serde::Serializer>(
+    unpushed_submodules: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct UnpushedSubmodule<'a> {
+        name: &'a str,
+        commit: &'a str,
+    }
+
+    serializer.collect_seq(
+        unpushed_submodules
+            .iter()
+            .map(|(name, commit)| UnpushedSubmodule { name, commit }),
+    )
+}
+
+impl RemoteTrackingStatus {
+    /// How many commits the local branch is ahead of and behind the remote branch
+    pub fn ahead_behind(&self) -> (usize, usize) {
+        match self {
+            Self::UpToDate => (0, 0),
+            Self::Ahead(d) => (*d, 0),
+            Self::Behind(d) => (0, *d),
+            Self::Diverged(d1, d2) => (*d1, *d2),
+        }
+    }
+}
+
+impl RepoStatus {
+    /// Whether there are uncommitted changes. In repositories colocated with jj,
+    /// changes are never uncommitted.
+    pub fn dirty(&self) -> bool {
+        self.changes.is_some() && !self.jj
+    }
+
+    /// Describes the operation that is in progress, e.g. "Rebase in progress"
+    pub fn operation_description(&self) -> Option<String> {
+        let operation = match self.operation? {
+            git2::RepositoryState::Clean => return None,
+            git2::RepositoryState::Merge => "Merge",
+            git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => "Revert",
+            git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => {
+                "Cherry-pick"
+            }
+            git2::RepositoryState::Bisect => "Bisect",
+            git2::RepositoryState::Rebase
+            | git2::RepositoryState::RebaseInteractive
+            | git2::RepositoryState::RebaseMerge => "Rebase",
+            git2::RepositoryState::ApplyMailbox | git2::RepositoryState::ApplyMailboxOrRebase => {
+                "git am"
+            }
+        };
+        Some(format!("{operation} in progress"))
+    }
+
+    /// How many commits HEAD is ahead of and behind its remote tracking branch
+    pub fn head_ahead_behind(&self) -> (usize, usize) {
+        self.branches
+            .iter()
+            .find(|(branch_name, _)| Some(branch_name) == self.head.as_ref())
+            .and_then(|(_, remote_branch)| remote_branch.as_ref())
+            .map_or((0, 0), |(_, tracking_status)| {
+                tracking_status.ahead_behind()
+            })
+    }
+
+    /// Returns the reasons why the repository contains work that could get lost,
+    /// i.e. uncommitted changes, unpushed commits, branches without remote or
+    /// an unfinished operation like a rebase.
+    /// An empty list means that everything is safe.
+    pub fn check_failures(&self) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        if let Some(operation) = self.operation_description() {
+            failures.push(operation);
+        }
+
+        if self.dirty() {
+            failures.push(String::from("Uncommitted changes"));
+        }
+
+        if self.remotes.is_empty() {
+            failures.push(String::from("No remotes configured"));
+        }
+
+        // jj leaves HEAD detached at the parent of its working copy commit
+        if let (Some(commit), false) = (&self.detached_head, self.jj) {
+            failures.push(detached_head_message(commit));
+        }
+
+        for (branch_name, remote_branch) in &self.branches {
+            match remote_branch {
+                None => failures.push(format!("Branch {branch_name} has no remote branch")),
+                Some((remote_branch_name, status)) => match status {
+                    RemoteTrackingStatus::Ahead(d) | RemoteTrackingStatus::Diverged(d, _) => {
+                        failures.push(format!(
+                            "Branch {branch_name} is {d} commit(s) ahead of {remote_branch_name}"
+                        ));
+                    }
+                    RemoteTrackingStatus::UpToDate | RemoteTrackingStatus::Behind(_) => {}
+                },
+            }
+        }
+
+        for (name, commit) in &self.unpushed_submodules {
+            failures.push(unpushed_submodule_message(name, commit));
+        }
+
+        failures
+    }
 }
 
 pub struct Worktree {
     name: String,
 }
 
+/// New commits on a remote tracking branch, e.g. after a fetch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BranchChanges {
+    /// The remote tracking branch, e.g. `origin/main`
+    pub branch: String,
+    pub commits: usize,
+    /// The authors of the new commits, each only once
+    pub authors: Vec<String>,
+}
+
+impl std::fmt::Display for BranchChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} new commit{} by {}",
+            self.branch,
+            self.commits,
+            if self.commits == 1 { "" } else { "s" },
+            self.authors.join(", ")
+        )
+    }
+}
+
+/// What rebasing a worktree onto the default branch would do, see
+/// [`Worktree::rebase_plan()`]
+pub struct RebasePlan {
+    /// The branch that the worktree is rebased onto
+    pub base_branch: String,
+    /// The commit the worktree branch currently starts from
+    pub current_base: String,
+    /// The commit the worktree branch would start from afterwards
+    pub target: String,
+    pub ahead: usize,
+    pub behind: usize,
+    pub changes: bool,
+}
+
+/// The default branch, or the first persistent branch if there are any
+fn rebase_base_branch_name(
+    repo: &RepoHandle,
+    config: &Option<WorktreeRootConfig>,
+) -> Result<String, String> {
+    let guess_default_branch = || {
+        repo.default_branch()
+            .map_err(|_| "Could not determine default branch")?
+            .name()
+            .map_err(|error| format!("Failed getting default branch name: {error}"))
+    };
+
+    match &config {
+        None => guess_default_branch(),
+        Some(config) => match &config.persistent_branches {
+            None => guess_default_branch(),
+            Some(persistent_branches) => {
+                if persistent_branches.is_empty() {
+                    guess_default_branch()
+                } else {
+                    Ok(persistent_branches[0].clone())
+                }
+            }
+        },
+    }
+}
+
+fn short_id(oid: git2::Oid) -> String {
+    oid.to_string().chars().take(7).collect()
+}
+
+pub fn unpushed_submodule_message(name: &str, commit: &str) -> String {
+    format!("Submodule {name} points to commit {commit} that is not on any of its remotes")
+}
+
+/// Describes a detached HEAD, with a hint how to get back to a branch
+pub fn detached_head_message(commit: &str) -> String {
+    format!("HEAD is detached at {commit}, use \"git switch <branch>\" to check out a branch")
+}
+
 impl Worktree {
     pub fn new(name: &str) -> Self {
         Self {
@@ -191,107 +844,71 @@ impl Worktree {
     }
 
     pub fn forward_branch(&self, rebase: bool, stash: bool) -> Result<Option<String>, String> {
-        let repo = RepoHandle::open(Path::new(&self.name), false)
+        let mut repo = RepoHandle::open(Path::new(&self.name), false)
             .map_err(|error| format!("Error opening worktree: {error}"))?;
 
-        if let Ok(remote_branch) = repo.find_local_branch(&self.name)?.upstream() {
-            let status = repo.status(false)?;
-            let mut stashed_changes = false;
-
-            if !status.clean() {
-                if stash {
-                    repo.stash()?;
-                    stashed_changes = true;
-                } else {
-                    return Ok(Some(String::from("Worktree contains changes")));
-                }
-            }
+        if let Some(commit) = repo.detached_head()? {
+            return Ok(Some(detached_head_message(&commit)));
+        }
 
-            let unstash = || -> Result<(), String> {
-                if stashed_changes {
-                    repo.stash_pop()?;
-                }
-                Ok(())
-            };
+        // Only the commit is kept, as stashing needs the repository for itself
+        let remote_commit = match repo.find_local_branch(&self.name)?.upstream() {
+            Ok(remote_branch) => remote_branch.commit()?.id().0,
+            Err(_) => return Ok(Some(String::from("No remote branch to rebase onto"))),
+        };
 
-            let remote_annotated_commit = repo
-                .0
-                .find_annotated_commit(remote_branch.commit()?.id().0)
-                .map_err(convert_libgit2_error)?;
+        let status = repo.status(false)?;
+        let mut stashed_changes = false;
 
-            if rebase {
-                let mut rebase = repo
-                    .0
-                    .rebase(
-                        None, // use HEAD
-                        Some(&remote_annotated_commit),
-                        None, // figure out the base yourself, libgit2!
-                        Some(&mut git2::RebaseOptions::new()),
-                    )
-                    .map_err(convert_libgit2_error)?;
+        if !status.clean() {
+            if stash {
+                repo.stash()?;
+                stashed_changes = true;
+            } else {
+                return Ok(Some(String::from("Worktree contains changes")));
+            }
+        }
 
-                while let Some(operation) = rebase.next() {
-                    let operation = operation.map_err(convert_libgit2_error)?;
+        let result = if rebase {
+            rebase_head_onto(&repo, remote_commit)?.map(|()| None)
+        } else {
+            fast_forward_head(&repo, remote_commit)?
+        };
+        if stashed_changes {
+            repo.stash_pop()?;
+        }
+        result
+    }
 
-                    // This is required to preserve the commiter of the rebased
-                    // commits, which is the expected behavior.
-                    let rebased_commit = repo
-                        .0
-                        .find_commit(operation.id())
-                        .map_err(convert_libgit2_error)?;
-                    let committer = rebased_commit.committer();
-
-                    // This is effectively adding all files to the index explicitly.
-                    // Normal files are already staged, but changed submodules are not.
-                    let mut index = repo.0.index().map_err(convert_libgit2_error)?;
-                    index
-                        .add_all(
-                            std::iter::once("."),
-                            git2::IndexAddOption::CHECK_PATHSPEC,
-                            None,
-                        )
-                        .map_err(convert_libgit2_error)?;
+    /// Describes what `rebase_onto_default()` would do, without changing anything
+    pub fn rebase_plan(&self, config: &Option<WorktreeRootConfig>) -> Result<RebasePlan, String> {
+        let repo = RepoHandle::open(Path::new(&self.name), false)
+            .map_err(|error| format!("Error opening worktree: {error}"))?;
 
-                    if let Err(error) = rebase.commit(None, &committer, None) {
-                        if error.code() == git2::ErrorCode::Applied {
-                            continue;
-                        }
-                        rebase.abort().map_err(convert_libgit2_error)?;
-                        unstash()?;
-                        return Err(convert_libgit2_error(error));
-                    }
-                }
+        let base_branch_name = rebase_base_branch_name(&repo, config)?;
 
-                rebase.finish(None).map_err(convert_libgit2_error)?;
-            } else {
-                let (analysis, _preference) = repo
-                    .0
-                    .merge_analysis(&[&remote_annotated_commit])
-                    .map_err(convert_libgit2_error)?;
+        if let Some(commit) = repo.detached_head()? {
+            return Err(detached_head_message(&commit));
+        }
+        let branch = repo.head_branch()?;
+        let base_branch = repo.find_local_branch(&base_branch_name)?;
 
-                if analysis.is_up_to_date() {
-                    unstash()?;
-                    return Ok(None);
-                }
-                if !analysis.is_fast_forward() {
-                    unstash()?;
-                    return Ok(Some(String::from("Worktree cannot be fast forwarded")));
-                }
+        let (ahead, behind) = repo.graph_ahead_behind(&branch, &base_branch)?;
 
-                repo.0
-                    .reset(
-                        remote_branch.commit()?.0.as_object(),
-                        git2::ResetType::Hard,
-                        Some(git2::build::CheckoutBuilder::new().safe()),
-                    )
-                    .map_err(convert_libgit2_error)?;
-            }
-            unstash()?;
-        } else {
-            return Ok(Some(String::from("No remote branch to rebase onto")));
-        };
+        let target = base_branch.commit()?.id().0;
+        let current_base = repo
+            .0
+            .merge_base(branch.commit()?.id().0, target)
+            .map_err(convert_libgit2_error)?;
 
-        Ok(None)
+        Ok(RebasePlan {
+            base_branch: base_branch_name,
+            current_base: short_id(current_base),
+            target: short_id(target),
+            ahead,
+            behind,
+            changes: !repo.status(false)?.clean(),
+        })
     }
 
     pub fn rebase_onto_default(
@@ -299,29 +916,14 @@ impl Worktree {
         config: &Option<WorktreeRootConfig>,
         stash: bool,
     ) -> Result<Option<String>, String> {
-        let repo = RepoHandle::open(Path::new(&self.name), false)
+        let mut repo = RepoHandle::open(Path::new(&self.name), false)
             .map_err(|error| format!("Error opening worktree: {error}"))?;
 
-        let guess_default_branch = || {
-            repo.default_branch()
-                .map_err(|_| "Could not determine default branch")?
-                .name()
-                .map_err(|error| format!("Failed getting default branch name: {error}"))
-        };
+        if let Some(commit) = repo.detached_head()? {
+            return Ok(Some(detached_head_message(&commit)));
+        }
 
-        let default_branch_name = match &config {
-            None => guess_default_branch()?,
-            Some(config) => match &config.persistent_branches {
-                None => guess_default_branch()?,
-                Some(persistent_branches) => {
-                    if persistent_branches.is_empty() {
-                        guess_default_branch()?
-                    } else {
-                        persistent_branches[0].clone()
-                    }
-                }
-            },
-        };
+        let default_branch_name = rebase_base_branch_name(&repo, config)?;
 
         let status = repo.status(false)?;
         let mut stashed_changes = false;
@@ -335,65 +937,111 @@ impl Worktree {
             }
         }
 
-        let unstash = || -> Result<(), String> {
-            if stashed_changes {
-                repo.stash_pop()?;
-            }
-            Ok(())
-        };
+        let base_commit = repo
+            .find_local_branch(&default_branch_name)?
+            .commit()?
+            .id()
+            .0;
+        let result = rebase_head_onto(&repo, base_commit)?;
+        if stashed_changes {
+            repo.stash_pop()?;
+        }
+        result.map(|()| None)
+    }
+}
 
-        let base_branch = repo.find_local_branch(&default_branch_name)?;
-        let base_annotated_commit = repo
+/// Rebases the current branch of `repo` onto `target`
+///
+/// The outer error means that the rebase failed halfway, so stashed changes must
+/// not be applied. Conflicts abort the rebase and are returned as the inner
+/// error.
+fn rebase_head_onto(repo: &RepoHandle, target: git2::Oid) -> Result<Result<(), String>, String> {
+    let annotated_target = repo
+        .0
+        .find_annotated_commit(target)
+        .map_err(convert_libgit2_error)?;
+
+    let mut rebase = repo
+        .0
+        .rebase(
+            None, // use HEAD
+            Some(&annotated_target),
+            None, // figure out the base yourself, libgit2!
+            Some(&mut git2::RebaseOptions::new()),
+        )
+        .map_err(convert_libgit2_error)?;
+
+    while let Some(operation) = rebase.next() {
+        let operation = operation.map_err(convert_libgit2_error)?;
+
+        // This is required to preserve the commiter of the rebased
+        // commits, which is the expected behavior.
+        let rebased_commit = repo
             .0
-            .find_annotated_commit(base_branch.commit()?.id().0)
+            .find_commit(operation.id())
             .map_err(convert_libgit2_error)?;
-
-        let mut rebase = repo
-            .0
-            .rebase(
-                None, // use HEAD
-                Some(&base_annotated_commit),
-                None, // figure out the base yourself, libgit2!
-                Some(&mut git2::RebaseOptions::new()),
+        let committer = rebased_commit.committer();
+
+        // This is effectively adding all files to the index explicitly.
+        // Normal files are already staged, but changed submodules are not.
+        let mut index = repo.0.index().map_err(convert_libgit2_error)?;
+        index
+            .add_all(
+                std::iter::once("."),
+                git2::IndexAddOption::CHECK_PATHSPEC,
+                None,
             )
             .map_err(convert_libgit2_error)?;
 
-        while let Some(operation) = rebase.next() {
-            let operation = operation.map_err(convert_libgit2_error)?;
-
-            // This is required to preserve the commiter of the rebased
-            // commits, which is the expected behavior.
-            let rebased_commit = repo
-                .0
-                .find_commit(operation.id())
-                .map_err(convert_libgit2_error)?;
-            let committer = rebased_commit.committer();
-
-            // This is effectively adding all files to the index explicitly.
-            // Normal files are already staged, but changed submodules are not.
-            let mut index = repo.0.index().map_err(convert_libgit2_error)?;
-            index
-                .add_all(
-                    std::iter::once("."),
-                    git2::IndexAddOption::CHECK_PATHSPEC,
-                    None,
-                )
-                .map_err(convert_libgit2_error)?;
-
-            if let Err(error) = rebase.commit(None, &committer, None) {
-                if error.code() == git2::ErrorCode::Applied {
-                    continue;
-                }
-                rebase.abort().map_err(convert_libgit2_error)?;
-                unstash()?;
-                return Err(convert_libgit2_error(error));
+        if let Err(error) = rebase.commit(None, &committer, None) {
+            if error.code() == git2::ErrorCode::Applied {
+                continue;
             }
+            rebase.abort().map_err(convert_libgit2_error)?;
+            return Ok(Err(convert_libgit2_error(error)));
         }
-
-        rebase.finish(None).map_err(convert_libgit2_error)?;
-        unstash()?;
-        Ok(None)
     }
+
+    rebase.finish(None).map_err(convert_libgit2_error)?;
+    Ok(Ok(()))
+}
+
+/// Fast forwards the current branch of `repo` to `target`, returning a warning
+/// if that is not possible
+///
+/// The outer error means that stashed changes must not be applied, like for
+/// [`rebase_head_onto()`].
+fn fast_forward_head(
+    repo: &RepoHandle,
+    target: git2::Oid,
+) -> Result<Result<Option<String>, String>, String> {
+    let annotated_target = repo
+        .0
+        .find_annotated_commit(target)
+        .map_err(convert_libgit2_error)?;
+    let (analysis, _preference) = repo
+        .0
+        .merge_analysis(&[&annotated_target])
+        .map_err(convert_libgit2_error)?;
+
+    if analysis.is_up_to_date() {
+        return Ok(Ok(None));
+    }
+    if !analysis.is_fast_forward() {
+        return Ok(Ok(Some(String::from("Worktree cannot be fast forwarded"))));
+    }
+
+    repo.0
+        .reset(
+            &repo
+                .0
+                .find_object(target, None)
+                .map_err(convert_libgit2_error)?,
+            git2::ResetType::Hard,
+            Some(git2::build::CheckoutBuilder::new().safe()),
+        )
+        .map_err(convert_libgit2_error)?;
+    Ok(Ok(None))
 }
 
 impl RepoStatus {
@@ -434,6 +1082,94 @@ pub fn detect_remote_type(remote_url: &str) -> Result<RemoteType, String> {
     ))
 }
 
+/// Returns the host of a remote URL, if there is one
+///
+/// Handles both URLs with a scheme (`ssh://git@example.com:22/repo.git`) and the
+/// scp-like syntax (`git@example.com:repo.git`). Local remotes do not have a host.
+pub fn remote_host(remote_url: &str) -> Option<String> {
+    let host = if let Some((scheme, rest)) = remote_url.split_once("://") {
+        if scheme == "file" {
+            return None;
+        }
+        let authority = rest.split('/').next()?;
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        match host.rsplit_once(':') {
+            Some((without_port, port)) if port.chars().all(|c| c.is_ascii_digit()) => without_port,
+            _ => host,
+        }
+    } else {
+        let (user_and_host, _) = remote_url.split_once(':')?;
+        let (_, host) = user_and_host.rsplit_once('@')?;
+        host
+    };
+
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+/// Maps the host of HTTPS remote URLs to the user and host of the equivalent SSH URLs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostMapping {
+    /// e.g. `git.example.com`
+    pub https: String,
+    /// e.g. `git@ssh.git.example.com`
+    pub ssh: String,
+}
+
+pub fn default_host_mappings() -> Vec<HostMapping> {
+    ["github.com", "gitlab.com"]
+        .into_iter()
+        .map(|host| HostMapping {
+            https: host.to_string(),
+            ssh: format!("git@{host}"),
+        })
+        .collect()
+}
+
+/// Rewrites a remote URL to its equivalent using `protocol`
+///
+/// Returns `None` if the URL already uses `protocol` or if its host is not part
+/// of `mappings`.
+pub fn rewrite_remote_url(
+    remote_url: &str,
+    protocol: &RemoteType,
+    mappings: &[HostMapping],
+) -> Option<String> {
+    match protocol {
+        RemoteType::Ssh => {
+            let (host, path) = remote_url.strip_prefix("https://")?.split_once('/')?;
+            let mapping = mappings
+                .iter()
+                .find(|mapping| mapping.https.eq_ignore_ascii_case(host))?;
+            let path = path.trim_end_matches('/');
+            if path.is_empty() {
+                return None;
+            }
+            // Without the suffix, the URL would not be detected as SSH
+            let suffix = if path.ends_with(".git") { "" } else { ".git" };
+            Some(format!("{}:{}{}", mapping.ssh, path, suffix))
+        }
+        RemoteType::Https => {
+            let (user_and_host, path) = match remote_url.strip_prefix("ssh://") {
+                Some(rest) => rest.split_once('/')?,
+                None if !remote_url.contains("://") => remote_url.split_once(':')?,
+                None => return None,
+            };
+            let mapping = mappings
+                .iter()
+                .find(|mapping| mapping.ssh.eq_ignore_ascii_case(user_and_host))?;
+            Some(format!("https://{}/{}", mapping.https, path))
+        }
+        RemoteType::File => None,
+    }
+}
+
 pub struct RepoHandle(git2::Repository);
 pub struct Branch<'a>(git2::Branch<'a>);
 
@@ -464,27 +1200,16 @@ impl RepoHandle {
         }
     }
 
-    pub fn stash(&self) -> Result<(), String> {
-        let head_branch = self.head_branch()?;
-        let head = head_branch.commit()?;
-        let author = head.author();
-
-        // This is honestly quite horrible. The problem is that all stash operations expect a
-        // mutable reference (as they, well, mutate the repo after all). But we are heavily using
-        // immutable references a lot with this struct. I'm really not sure how to best solve this.
-        // Right now, we just open the repo AGAIN. It is safe, as we are only accessing the stash
-        // with the second reference, so there are no cross effects. But it just smells. Also,
-        // using `unwrap()` here as we are already sure that the repo is openable(?).
-        let mut repo = Self::open(self.0.path(), false).unwrap();
-        repo.0
+    pub fn stash(&mut self) -> Result<(), String> {
+        let author = self.head_branch()?.commit()?.author().to_owned();
+        self.0
             .stash_save2(&author, None, Some(git2::StashFlags::INCLUDE_UNTRACKED))
             .map_err(convert_libgit2_error)?;
         Ok(())
     }
 
-    pub fn stash_pop(&self) -> Result<(), String> {
-        let mut repo = Self::open(self.0.path(), false).unwrap();
-        repo.0
+    pub fn stash_pop(&mut self) -> Result<(), String> {
+        self.0
             .stash_pop(
                 0,
                 Some(git2::StashApplyOptions::new().reinstantiate_index()),
@@ -521,6 +1246,42 @@ impl RepoHandle {
             .map_err(convert_libgit2_error)
     }
 
+    /// The configured upstream of `branch`, e.g. `origin/feature`, if that no
+    /// longer exists, e.g. because it was deleted on the remote and pruned
+    pub fn gone_upstream(&self, branch: &Branch) -> Result<Option<String>, String> {
+        let Some(name) = branch.as_reference().name() else {
+            return Ok(None);
+        };
+        // Fails if there is no upstream configured at all
+        let Ok(upstream) = self.0.branch_upstream_name(name) else {
+            return Ok(None);
+        };
+        let upstream = upstream
+            .as_str()
+            .ok_or_else(|| String::from("Upstream branch name is not valid utf-8"))?;
+
+        match self.0.find_reference(upstream) {
+            Ok(_) => Ok(None),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(Some(
+                upstream
+                    .strip_prefix("refs/remotes/")
+                    .or_else(|| upstream.strip_prefix("refs/heads/"))
+                    .unwrap_or(upstream)
+                    .to_string(),
+            )),
+            Err(error) => Err(convert_libgit2_error(error)),
+        }
+    }
+
+    /// The commit (short ID) that HEAD points to if no branch is checked out
+    pub fn detached_head(&self) -> Result<Option<String>, String> {
+        if !self.0.head_detached().map_err(convert_libgit2_error)? {
+            return Ok(None);
+        }
+        let head = self.0.head().map_err(convert_libgit2_error)?;
+        Ok(head.target().map(short_id))
+    }
+
     pub fn head_branch(&self) -> Result<Branch, String> {
         let head = self.0.head().map_err(convert_libgit2_error)?;
         if !head.is_branch() {
@@ -548,6 +1309,95 @@ impl RepoHandle {
         self.0.is_empty().map_err(convert_libgit2_error)
     }
 
+    /// Reads the `.grm.toml` from the commit that HEAD points to, if there is one
+    pub fn project_config(&self) -> Result<Option<ProjectConfig>, String> {
+        let Ok(tree) = self.0.head().and_then(|head| head.peel_to_tree()) else {
+            return Ok(None);
+        };
+        let Ok(entry) = tree.get_path(Path::new(PROJECT_CONFIG_FILE_NAME)) else {
+            return Ok(None);
+        };
+        let blob = entry
+            .to_object(&self.0)
+            .and_then(|object| object.peel_to_blob())
+            .map_err(convert_libgit2_error)?;
+
+        let content = std::str::from_utf8(blob.content()).map_err(|error| {
+            format!("Error reading project configuration \"{PROJECT_CONFIG_FILE_NAME}\": {error}")
+        })?;
+        toml::from_str(content).map(Some).map_err(|error| {
+            format!("Error parsing project configuration \"{PROJECT_CONFIG_FILE_NAME}\": {error}")
+        })
+    }
+
+    /// Restricts the files that are checked out in the worktree at `path` to the
+    /// directories in `patterns`
+    pub fn set_sparse_checkout(path: &Path, patterns: &[String]) -> Result<(), String> {
+        let mut args = vec!["sparse-checkout", "set", "--"];
+        args.extend(patterns.iter().map(String::as_str));
+        run_git(Some(path), &args).map(|_| ())
+    }
+
+    /// Applies the patch file at `patch` to the worktree at `path`
+    pub fn apply_patch(path: &Path, patch: &Path) -> Result<(), String> {
+        run_git(Some(path), &["apply", "--", path::path_as_utf8(patch)?]).map(|_| ())
+    }
+
+    /// Applies the stash `stash` (e.g. `stash@{1}`) to the worktree at `path`,
+    /// without dropping it
+    pub fn apply_stash(path: &Path, stash: &str) -> Result<(), String> {
+        run_git(Some(path), &["stash", "apply", "--quiet", stash]).map(|_| ())
+    }
+
+    /// Stashes all uncommitted changes of the worktree at `path`, including
+    /// untracked files, as `stash@{0}`
+    ///
+    /// Returns `false` if there was nothing to stash.
+    pub fn stash_changes(path: &Path, message: &str) -> Result<bool, String> {
+        if run_git(Some(path), &["status", "--porcelain"])?.is_empty() {
+            return Ok(false);
+        }
+        run_git(
+            Some(path),
+            &[
+                "stash",
+                "push",
+                "--quiet",
+                "--include-untracked",
+                "--message",
+                message,
+            ],
+        )?;
+        Ok(true)
+    }
+
+    /// Applies the stash `stash` to the worktree at `path` and drops it
+    pub fn pop_stash(path: &Path, stash: &str) -> Result<(), String> {
+        run_git(Some(path), &["stash", "pop", "--quiet", stash]).map(|_| ())
+    }
+
+    pub fn drop_stash(path: &Path, stash: &str) -> Result<(), String> {
+        run_git(Some(path), &["stash", "drop", "--quiet", stash]).map(|_| ())
+    }
+
+    /// Whether the worktree at `path` has files with unresolved conflicts
+    pub fn has_conflicts(path: &Path) -> Result<bool, String> {
+        run_git(Some(path), &["diff", "--name-only", "--diff-filter=U"])
+            .map(|output| !output.is_empty())
+    }
+
+    /// Whether `stash` refers to a stash entry, like `stash@{0}`
+    pub fn is_stash(&self, stash: &str) -> bool {
+        // Stash entries are merge commits of the working tree, the commit they
+        // were made on and the index
+        !stash.starts_with('-')
+            && self
+                .0
+                .revparse_single(stash)
+                .and_then(|object| object.peel_to_commit())
+                .is_ok_and(|commit| commit.parent_count() >= 2)
+    }
+
     pub fn is_bare(&self) -> bool {
         self.0.is_bare()
     }
@@ -584,45 +1434,170 @@ impl RepoHandle {
         Ok(())
     }
 
-    pub fn fetchall(&self) -> Result<(), String> {
-        for remote in self.remotes()? {
-            self.fetch(&remote)?;
+    pub fn fetchall(&self, retry: &RetryConfig, prune: Option<bool>) -> Result<(), String> {
+        for remote in self.remotes()? {
+            self.fetch(&remote, retry, prune)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches only the given remotes, all of which have to exist
+    pub fn fetch_only(
+        &self,
+        remotes: &[String],
+        retry: &RetryConfig,
+        prune: Option<bool>,
+    ) -> Result<(), String> {
+        let existing_remotes = self.remotes()?;
+        for remote in remotes {
+            if !existing_remotes.contains(remote) {
+                return Err(format!("Remote \"{remote}\" does not exist"));
+            }
+            self.fetch(remote, retry, prune)?;
+        }
+        Ok(())
+    }
+
+    pub fn local_branches(&self) -> Result<Vec<Branch>, String> {
+        self.0
+            .branches(Some(git2::BranchType::Local))
+            .map_err(convert_libgit2_error)?
+            .map(|branch| Ok(Branch(branch.map_err(convert_libgit2_error)?.0)))
+            .collect::<Result<Vec<Branch>, String>>()
+    }
+
+    pub fn remote_branches(&self) -> Result<Vec<Branch>, String> {
+        self.0
+            .branches(Some(git2::BranchType::Remote))
+            .map_err(convert_libgit2_error)?
+            .map(|branch| Ok(Branch(branch.map_err(convert_libgit2_error)?.0)))
+            .collect::<Result<Vec<Branch>, String>>()
+    }
+
+    /// The commit IDs of all remote tracking branches, by branch name
+    pub fn remote_heads(&self) -> Result<BTreeMap<String, String>, String> {
+        let mut heads = BTreeMap::new();
+        for reference in self
+            .0
+            .references_glob("refs/remotes/*")
+            .map_err(convert_libgit2_error)?
+        {
+            let reference = reference.map_err(convert_libgit2_error)?;
+            // Symbolic references like origin/HEAD point to another branch
+            if let (Some(name), Some(target)) = (reference.shorthand(), reference.target()) {
+                heads.insert(name.to_string(), target.to_string());
+            }
+        }
+        Ok(heads)
+    }
+
+    /// Summarizes the commits that the remote tracking branches gained since
+    /// they pointed to `before` (see [`RepoHandle::remote_heads()`])
+    ///
+    /// For new branches, only the commits that are not on any other branch are
+    /// counted, so commits are not reported twice.
+    pub fn remote_changes(
+        &self,
+        before: &BTreeMap<String, String>,
+    ) -> Result<Vec<BranchChanges>, String> {
+        let mut changes = Vec::new();
+        let heads = self.remote_heads()?;
+
+        for (branch, head) in &heads {
+            let known = match before.get(branch) {
+                Some(old_head) if old_head == head => continue,
+                Some(old_head) => vec![old_head],
+                None => before
+                    .values()
+                    .chain(
+                        heads
+                            .iter()
+                            .filter(|(other, _)| *other != branch)
+                            .map(|(_, other_head)| other_head),
+                    )
+                    .collect(),
+            };
+
+            let mut walk = self.0.revwalk().map_err(convert_libgit2_error)?;
+            walk.push(git2::Oid::from_str(head).map_err(convert_libgit2_error)?)
+                .map_err(convert_libgit2_error)?;
+            for commit in known {
+                // The old commit may be gone already, e.g. after a force push
+                // and garbage collection
+                if let Ok(oid) = git2::Oid::from_str(commit) {
+                    let _ = walk.hide(oid);
+                }
+            }
+
+            let mut commits = 0;
+            let mut authors: Vec<String> = Vec::new();
+            for oid in walk {
+                let commit = self
+                    .0
+                    .find_commit(oid.map_err(convert_libgit2_error)?)
+                    .map_err(convert_libgit2_error)?;
+                commits += 1;
+                let author = commit.author().name().unwrap_or("unknown").to_string();
+                if !authors.contains(&author) {
+                    authors.push(author);
+                }
+            }
+
+            if commits > 0 {
+                changes.push(BranchChanges {
+                    branch: branch.clone(),
+                    commits,
+                    authors,
+                });
+            }
         }
-        Ok(())
-    }
 
-    pub fn local_branches(&self) -> Result<Vec<Branch>, String> {
-        self.0
-            .branches(Some(git2::BranchType::Local))
-            .map_err(convert_libgit2_error)?
-            .map(|branch| Ok(Branch(branch.map_err(convert_libgit2_error)?.0)))
-            .collect::<Result<Vec<Branch>, String>>()
+        Ok(changes)
     }
 
-    pub fn remote_branches(&self) -> Result<Vec<Branch>, String> {
-        self.0
-            .branches(Some(git2::BranchType::Remote))
-            .map_err(convert_libgit2_error)?
-            .map(|branch| Ok(Branch(branch.map_err(convert_libgit2_error)?.0)))
-            .collect::<Result<Vec<Branch>, String>>()
-    }
+    /// Fetches all refspecs of a remote
+    ///
+    /// `prune` removes remote-tracking branches that no longer exist on the
+    /// remote. With `None`, git's `fetch.prune` and `remote.<name>.prune`
+    /// settings decide.
+    pub fn fetch(
+        &self,
+        remote_name: &str,
+        retry: &RetryConfig,
+        prune: Option<bool>,
+    ) -> Result<(), String> {
+        // libgit2 drops the shallow boundary when fetching into a shallow clone,
+        // which leaves the repository broken
+        if self.0.is_shallow() {
+            let mut args = vec!["fetch", "--quiet"];
+            match prune {
+                Some(true) => args.push("--prune"),
+                Some(false) => args.push("--no-prune"),
+                None => (),
+            }
+            args.push(remote_name);
+            return run_git(Some(self.0.path()), &args).map(|_| ());
+        }
 
-    pub fn fetch(&self, remote_name: &str) -> Result<(), String> {
         let mut remote = self
             .0
             .find_remote(remote_name)
             .map_err(convert_libgit2_error)?;
 
-        let mut fetch_options = git2::FetchOptions::new();
-        fetch_options.remote_callbacks(get_remote_callbacks());
-
+        let url = remote.url().unwrap_or_default().to_string();
         for refspec in &remote.fetch_refspecs().map_err(convert_libgit2_error)? {
-            remote
-                .fetch(
-                    &[refspec.ok_or("Remote name is invalid utf-8")?],
-                    Some(&mut fetch_options),
-                    None,
-                )
+            let refspec = refspec.ok_or("Remote name is invalid utf-8")?;
+            retry
+                .run(&format!("Fetching {remote_name}"), || {
+                    let mut fetch_options = git2::FetchOptions::new();
+                    fetch_options.remote_callbacks(get_remote_callbacks(&url));
+                    fetch_options.prune(match prune {
+                        Some(true) => git2::FetchPrune::On,
+                        Some(false) => git2::FetchPrune::Off,
+                        None => git2::FetchPrune::Unspecified,
+                    });
+                    remote.fetch(&[refspec], Some(&mut fetch_options), None)
+                })
                 .map_err(convert_libgit2_error)?;
         }
         Ok(())
@@ -645,6 +1620,107 @@ impl RepoHandle {
         Ok(repo)
     }
 
+    /// Stages all files and commits them on top of `HEAD`, e.g. as the initial
+    /// commit of a new repository
+    /// The signature for new commits. Like git itself, `GIT_<kind>_NAME` and
+    /// `GIT_<kind>_EMAIL` take precedence over the configuration.
+    fn signature(&self, kind: &str) -> Result<git2::Signature<'static>, String> {
+        let configured = self.0.signature().ok();
+        let name = std::env::var(format!("GIT_{kind}_NAME"))
+            .ok()
+            .or_else(|| configured.as_ref().and_then(|s| s.name().map(String::from)));
+        let email = std::env::var(format!("GIT_{kind}_EMAIL")).ok().or_else(|| {
+            configured
+                .as_ref()
+                .and_then(|s| s.email().map(String::from))
+        });
+
+        match (name, email) {
+            (Some(name), Some(email)) => {
+                git2::Signature::now(&name, &email).map_err(convert_libgit2_error)
+            }
+            _ => Err(String::from(
+                "No git identity found, configure user.name and user.email",
+            )),
+        }
+    }
+
+    pub fn commit_all(&self, message: &str) -> Result<(), String> {
+        let mut index = self.0.index().map_err(convert_libgit2_error)?;
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .map_err(convert_libgit2_error)?;
+        index.write().map_err(convert_libgit2_error)?;
+
+        let tree = self
+            .0
+            .find_tree(index.write_tree().map_err(convert_libgit2_error)?)
+            .map_err(convert_libgit2_error)?;
+        let author = self.signature("AUTHOR")?;
+        let committer = self.signature("COMMITTER")?;
+
+        let parent = match self.0.head() {
+            Ok(head) => Some(head.peel_to_commit().map_err(convert_libgit2_error)?),
+            Err(_) => None,
+        };
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+
+        self.0
+            .commit(Some("HEAD"), &author, &committer, message, &tree, &parents)
+            .map_err(convert_libgit2_error)?;
+        Ok(())
+    }
+
+    /// The branch HEAD points to, even if it does not exist yet, like in a
+    /// repository without any commits
+    pub fn head_branch_name(&self) -> Result<String, String> {
+        let head = self
+            .0
+            .find_reference("HEAD")
+            .map_err(convert_libgit2_error)?;
+        head.symbolic_target()
+            .and_then(|target| target.strip_prefix("refs/heads/"))
+            .map(ToString::to_string)
+            .ok_or_else(|| String::from("HEAD does not point to a branch"))
+    }
+
+    /// Creates an empty first commit on `branch` in a repository without any
+    /// commits and points HEAD to it
+    pub fn init_branch(&self, branch: &str, message: &str) -> Result<(), String> {
+        if !self.is_empty()? {
+            return Err(String::from("Repository already contains commits"));
+        }
+
+        let tree_id = self
+            .0
+            .treebuilder(None)
+            .and_then(|builder| builder.write())
+            .map_err(convert_libgit2_error)?;
+        let tree = self.0.find_tree(tree_id).map_err(convert_libgit2_error)?;
+        let author = self.signature("AUTHOR")?;
+        let committer = self.signature("COMMITTER")?;
+
+        let reference = format!("refs/heads/{branch}");
+        self.0
+            .commit(Some(&reference), &author, &committer, message, &tree, &[])
+            .map_err(convert_libgit2_error)?;
+        self.0.set_head(&reference).map_err(convert_libgit2_error)?;
+        Ok(())
+    }
+
+    /// Points `<remote>/HEAD` to `<remote>/<branch>`, like a clone does
+    pub fn set_remote_head(&self, remote_name: &str, branch: &str) -> Result<(), String> {
+        self.0
+            .reference_symbolic(
+                &format!("refs/remotes/{remote_name}/HEAD"),
+                &format!("refs/remotes/{remote_name}/{branch}"),
+                true,
+                "grm: set remote HEAD",
+            )
+            .map_err(convert_libgit2_error)?;
+        Ok(())
+    }
+
     pub fn config(&self) -> Result<git2::Config, String> {
         self.0.config().map_err(convert_libgit2_error)
     }
@@ -654,6 +1730,19 @@ impl RepoHandle {
         Ok(())
     }
 
+    /// The names of all worktrees whose directory does not exist anymore
+    pub fn broken_worktrees(&self) -> Result<Vec<String>, String> {
+        let mut broken_worktrees = Vec::new();
+        for name in self.0.worktrees().map_err(convert_libgit2_error)?.iter() {
+            let name = name.expect("Worktree name is invalid utf-8");
+            let worktree = self.0.find_worktree(name).map_err(convert_libgit2_error)?;
+            if worktree.validate().is_err() {
+                broken_worktrees.push(name.to_string());
+            }
+        }
+        Ok(broken_worktrees)
+    }
+
     pub fn prune_worktree(&self, name: &str) -> Result<(), String> {
         let worktree = self.0.find_worktree(name).map_err(convert_libgit2_error)?;
         worktree.prune(None).map_err(convert_libgit2_error)?;
@@ -683,6 +1772,13 @@ impl RepoHandle {
         ))
     }
 
+    pub fn find_commit(&self, id: &str) -> Result<Commit<'_>, String> {
+        let oid = git2::Oid::from_str(id).map_err(convert_libgit2_error)?;
+        Ok(Commit(
+            self.0.find_commit(oid).map_err(convert_libgit2_error)?,
+        ))
+    }
+
     pub fn create_branch(&self, name: &str, target: &Commit) -> Result<Branch, String> {
         Ok(Branch(
             self.0
@@ -775,6 +1871,26 @@ impl RepoHandle {
         Ok(())
     }
 
+    /// The forge project ID that is stored in the git configuration of the repository
+    pub fn provider_id(&self) -> Result<Option<String>, String> {
+        let config = self.config()?;
+        match config.get_string(GIT_CONFIG_PROVIDER_ID_KEY) {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(error) => Err(format!(
+                "Could not read {GIT_CONFIG_PROVIDER_ID_KEY}: {error}"
+            )),
+        }
+    }
+
+    pub fn set_provider_id(&self, value: &str) -> Result<(), String> {
+        let mut config = self.config()?;
+
+        config
+            .set_str(GIT_CONFIG_PROVIDER_ID_KEY, value)
+            .map_err(|error| format!("Could not set {GIT_CONFIG_PROVIDER_ID_KEY}: {error}"))
+    }
+
     pub fn set_config_push(&self, value: GitPushDefaultSetting) -> Result<(), String> {
         let mut config = self.config()?;
 
@@ -788,6 +1904,15 @@ impl RepoHandle {
             .map_err(|error| format!("Could not set {GIT_CONFIG_PUSH_DEFAULT}: {error}"))
     }
 
+    /// Makes `git push` use `remote_name` unless a branch says otherwise
+    pub fn set_push_remote(&self, remote_name: &str) -> Result<(), String> {
+        let mut config = self.config()?;
+
+        config
+            .set_str(GIT_CONFIG_PUSH_REMOTE, remote_name)
+            .map_err(|error| format!("Could not set {GIT_CONFIG_PUSH_REMOTE}: {error}"))
+    }
+
     pub fn has_untracked_files(&self, is_worktree: bool) -> Result<bool, String> {
         if is_worktree {
             Err(String::from(
@@ -810,6 +1935,30 @@ impl RepoHandle {
         }
     }
 
+    /// Returns the time of the newest commit of all local branches as a unix
+    /// timestamp, or `None` if there are no branches
+    pub fn last_commit_time(&self) -> Result<Option<i64>, String> {
+        let mut newest: Option<i64> = None;
+        for branch in self.local_branches()? {
+            let time = branch.commit()?.time();
+            newest = Some(newest.map_or(time, |newest| newest.max(time)));
+        }
+        Ok(newest)
+    }
+
+    /// The time of the last fetch of any remote as a unix timestamp, or `None`
+    /// if the repository was never fetched
+    pub fn last_fetch_time(&self) -> Option<u64> {
+        let fetch_head = std::fs::metadata(self.0.path().join("FETCH_HEAD")).ok()?;
+        let modified = fetch_head.modified().ok()?;
+        Some(
+            modified
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()?
+                .as_secs(),
+        )
+    }
+
     pub fn status(&self, is_worktree: bool) -> Result<RepoStatus, String> {
         let operation = match self.0.state() {
             git2::RepositoryState::Clean => None,
@@ -827,7 +1976,15 @@ impl RepoHandle {
             .map(|repo_name| repo_name.to_owned())
             .collect::<Vec<String>>();
 
-        let head = if is_worktree || empty {
+        let jj = !is_worktree && self.0.workdir().is_some_and(Self::detect_jj);
+
+        let detached_head = if is_worktree || empty {
+            None
+        } else {
+            self.detached_head()?
+        };
+
+        let head = if is_worktree || empty || detached_head.is_some() {
             None
         } else {
             Some(self.head_branch()?.name()?)
@@ -956,13 +2113,72 @@ impl RepoHandle {
             empty,
             remotes,
             head,
+            detached_head,
             changes,
             worktrees,
             submodules,
             branches,
+            unpushed_submodules: Vec::new(),
+            jj,
         })
     }
 
+    /// Returns the submodules (recursively) whose commit recorded in `HEAD` is
+    /// not on any of the submodule's remote branches, together with that commit
+    ///
+    /// Only the remote branches that were already fetched are taken into
+    /// account. Submodules that are not initialized or do not contain the
+    /// commit at all are skipped.
+    pub fn unpushed_submodules(&self) -> Result<Vec<(String, String)>, String> {
+        let mut unpushed = Vec::new();
+        for submodule in self.0.submodules().map_err(convert_libgit2_error)? {
+            let name = submodule.path().to_string_lossy().to_string();
+            let Some(commit) = submodule.head_id() else {
+                continue;
+            };
+            let Ok(submodule_repo) = submodule.open() else {
+                continue;
+            };
+            let submodule_repo = Self(submodule_repo);
+
+            if submodule_repo.0.find_commit(commit).is_ok()
+                && !submodule_repo.is_on_remote_branch(commit)?
+            {
+                let mut commit = commit.to_string();
+                commit.truncate(7);
+                unpushed.push((name.clone(), commit));
+            }
+
+            for (nested_name, commit) in submodule_repo.unpushed_submodules()? {
+                unpushed.push((format!("{name}/{nested_name}"), commit));
+            }
+        }
+        Ok(unpushed)
+    }
+
+    fn is_on_remote_branch(&self, commit: git2::Oid) -> Result<bool, String> {
+        for branch in self
+            .0
+            .branches(Some(git2::BranchType::Remote))
+            .map_err(convert_libgit2_error)?
+        {
+            let (branch, _) = branch.map_err(convert_libgit2_error)?;
+            // Symbolic references like `origin/HEAD` have no target
+            let Some(target) = branch.get().target() else {
+                continue;
+            };
+            if target == commit
+                || self
+                    .0
+                    .graph_descendant_of(target, commit)
+                    .map_err(convert_libgit2_error)?
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub fn get_remote_default_branch(&self, remote_name: &str) -> Result<Option<Branch>, String> {
         // libgit2's `git_remote_default_branch()` and `Remote::default_branch()`
         // need an actual connection to the remote, so they may fail.
@@ -1072,14 +2288,108 @@ impl RepoHandle {
             .collect())
     }
 
+    /// Checks whether the worktree that has `branch` checked out can be removed
+    /// without losing any work. `self` is the repository of the worktree itself.
+    ///
+    /// Submodules are checked as well: They must not have uncommitted changes,
+    /// and the commits they point to must be on one of their remotes.
+    pub fn check_worktree_removable(
+        &self,
+        branch: &Branch,
+        name: &str,
+        worktree_config: &Option<WorktreeRootConfig>,
+        overrides: WorktreeRemoveOverrides,
+    ) -> Result<(), WorktreeRemoveFailureReason> {
+        let status = self
+            .status(false)
+            .map_err(WorktreeRemoveFailureReason::Error)?;
+
+        // Before the changes of the worktree itself, as a changed submodule
+        // shows up there as well
+        if !overrides.ignore_changes {
+            let mut problems = status
+                .submodules
+                .iter()
+                .flatten()
+                .filter(|(_name, status)| matches!(status, SubmoduleStatus::Changed))
+                .map(|(name, _status)| format!("Submodule {name} has uncommitted changes"))
+                .collect::<Vec<String>>();
+            problems.extend(
+                self.unpushed_submodules()
+                    .map_err(WorktreeRemoveFailureReason::Error)?
+                    .iter()
+                    .map(|(name, commit)| unpushed_submodule_message(name, commit)),
+            );
+            if !problems.is_empty() {
+                return Err(WorktreeRemoveFailureReason::Submodules(problems.join(", ")));
+            }
+        }
+
+        if status.changes.is_some() && !overrides.ignore_changes {
+            return Err(WorktreeRemoveFailureReason::Changes(String::from(
+                "Changes found in worktree",
+            )));
+        }
+
+        let mut is_merged_into_persistent_branch = false;
+        let mut has_persistent_branches = false;
+        if let Some(config) = worktree_config {
+            if let Some(branches) = &config.persistent_branches {
+                has_persistent_branches = true;
+                for persistent_branch in branches {
+                    let persistent_branch = self
+                        .find_local_branch(persistent_branch)
+                        .map_err(WorktreeRemoveFailureReason::Error)?;
+
+                    let (ahead, _behind) =
+                        self.graph_ahead_behind(branch, &persistent_branch).unwrap();
+
+                    if ahead == 0 {
+                        is_merged_into_persistent_branch = true;
+                    }
+                }
+            }
+        }
+
+        if has_persistent_branches
+            && !is_merged_into_persistent_branch
+            && !overrides.ignore_unmerged
+        {
+            return Err(WorktreeRemoveFailureReason::NotMerged(format!(
+                "Branch {name} is not merged into any persistent branches",
+            )));
+        }
+
+        if !has_persistent_branches && !overrides.ignore_upstream_divergence {
+            match branch.upstream() {
+                Ok(remote_branch) => {
+                    let (ahead, behind) = self.graph_ahead_behind(branch, &remote_branch).unwrap();
+
+                    if (ahead, behind) != (0, 0) {
+                        return Err(WorktreeRemoveFailureReason::Changes(format!(
+                            "Branch {name} is not in line with remote branch",
+                        )));
+                    }
+                }
+                Err(_) => {
+                    return Err(WorktreeRemoveFailureReason::Changes(format!(
+                        "No remote tracking branch for branch {name} found",
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn remove_worktree(
         &self,
         base_dir: &Path,
         name: &str,
         worktree_dir: &Path,
-        force: bool,
+        overrides: WorktreeRemoveOverrides,
         worktree_config: &Option<WorktreeRootConfig>,
-    ) -> Result<(), WorktreeRemoveFailureReason> {
+    ) -> Result<Option<String>, WorktreeRemoveFailureReason> {
         let fullpath = base_dir.join(worktree_dir);
 
         if !fullpath.exists() {
@@ -1091,6 +2401,11 @@ impl RepoHandle {
             WorktreeRemoveFailureReason::Error(format!("Error opening repo: {error}"))
         })?;
 
+        if let Ok(Some(commit)) = worktree_repo.detached_head() {
+            return Err(WorktreeRemoveFailureReason::Error(detached_head_message(
+                &commit,
+            )));
+        }
         let local_branch = worktree_repo.head_branch().map_err(|error| {
             WorktreeRemoveFailureReason::Error(format!("Failed getting head branch: {error}"))
         })?;
@@ -1110,64 +2425,21 @@ impl RepoHandle {
             .find_local_branch(&branch_name)
             .map_err(WorktreeRemoveFailureReason::Error)?;
 
-        if !force {
-            let status = worktree_repo
-                .status(false)
-                .map_err(WorktreeRemoveFailureReason::Error)?;
-            if status.changes.is_some() {
-                return Err(WorktreeRemoveFailureReason::Changes(String::from(
-                    "Changes found in worktree",
-                )));
-            }
-
-            let mut is_merged_into_persistent_branch = false;
-            let mut has_persistent_branches = false;
-            if let Some(config) = worktree_config {
-                if let Some(branches) = &config.persistent_branches {
-                    has_persistent_branches = true;
-                    for persistent_branch in branches {
-                        let persistent_branch = worktree_repo
-                            .find_local_branch(persistent_branch)
-                            .map_err(WorktreeRemoveFailureReason::Error)?;
-
-                        let (ahead, _behind) = worktree_repo
-                            .graph_ahead_behind(&branch, &persistent_branch)
-                            .unwrap();
-
-                        if ahead == 0 {
-                            is_merged_into_persistent_branch = true;
-                        }
-                    }
-                }
-            }
-
-            if has_persistent_branches && !is_merged_into_persistent_branch {
-                return Err(WorktreeRemoveFailureReason::NotMerged(format!(
-                    "Branch {name} is not merged into any persistent branches",
-                )));
-            }
-
-            if !has_persistent_branches {
-                match branch.upstream() {
-                    Ok(remote_branch) => {
-                        let (ahead, behind) = worktree_repo
-                            .graph_ahead_behind(&branch, &remote_branch)
-                            .unwrap();
+        worktree_repo.check_worktree_removable(&branch, name, worktree_config, overrides)?;
 
-                        if (ahead, behind) != (0, 0) {
-                            return Err(WorktreeRemoveFailureReason::Changes(format!(
-                                "Branch {name} is not in line with remote branch",
-                            )));
-                        }
-                    }
-                    Err(_) => {
-                        return Err(WorktreeRemoveFailureReason::Changes(format!(
-                            "No remote tracking branch for branch {name} found",
-                        )));
-                    }
-                }
-            }
-        }
+        // Archive before touching anything, so a failure does not leave a half
+        // removed worktree behind
+        let archive = worktree_config
+            .as_ref()
+            .and_then(|config| config.cleanup.as_ref())
+            .and_then(|cleanup| cleanup.archive.as_deref())
+            .map(|pattern| worktree_repo.archive_branch(&branch, pattern))
+            .transpose()
+            .map_err(|error| {
+                WorktreeRemoveFailureReason::Error(format!(
+                    "Failed archiving branch {branch_name}: {error}"
+                ))
+            })?;
 
         // worktree_dir is a relative path, starting from base_dir. We walk it
         // upwards (from subdirectory to parent directories) and remove each
@@ -1215,18 +2487,56 @@ impl RepoHandle {
             .delete()
             .map_err(WorktreeRemoveFailureReason::Error)?;
 
-        Ok(())
+        Ok(archive)
     }
 
-    pub fn cleanup_worktrees(&self, directory: &Path) -> Result<Vec<String>, String> {
-        let mut warnings = Vec::new();
+    /// Keeps the commit of `branch` reachable under the reference `pattern`, see
+    /// [`WorktreeCleanupConfig`], and returns the name of the reference
+    ///
+    /// An existing reference is never overwritten. If it points to a different
+    /// commit, a number is appended to the name instead.
+    pub fn archive_branch(&self, branch: &Branch, pattern: &str) -> Result<String, String> {
+        let name = pattern.replace("{branch}", &branch.name()?);
+        if !name.starts_with("refs/") || !git2::Reference::is_valid_name(&name) {
+            return Err(format!("\"{name}\" is not a valid reference name"));
+        }
+
+        let target = branch.commit()?.id().0;
+        let mut candidate = name.clone();
+        let mut number = 1;
+        loop {
+            match self.0.find_reference(&candidate) {
+                Ok(reference) if reference.target() == Some(target) => return Ok(candidate),
+                Ok(_) => {
+                    number += 1;
+                    candidate = format!("{name}-{number}");
+                }
+                Err(error) if error.code() == git2::ErrorCode::NotFound => break,
+                Err(error) => return Err(convert_libgit2_error(error)),
+            }
+        }
+
+        self.0
+            .reference(
+                &candidate,
+                target,
+                false,
+                &format!("grm: archive branch {}", branch.name()?),
+            )
+            .map_err(convert_libgit2_error)?;
+        Ok(candidate)
+    }
 
+    /// Returns the worktrees that are considered for cleanup, i.e. all except the ones
+    /// of the default branch and of persistent branches
+    pub fn cleanup_candidates(
+        &self,
+        config: &Option<WorktreeRootConfig>,
+    ) -> Result<Vec<Worktree>, String> {
         let worktrees = self
             .get_worktrees()
             .map_err(|error| format!("Getting worktrees failed: {error}"))?;
 
-        let config = read_worktree_root_config(directory)?;
-
         let guess_default_branch = || {
             self.default_branch()
                 .map_err(|_| "Could not determine default branch")?
@@ -1248,8 +2558,8 @@ impl RepoHandle {
             },
         };
 
-        for worktree in worktrees
-            .iter()
+        Ok(worktrees
+            .into_iter()
             .filter(|worktree| worktree.name() != default_branch_name)
             .filter(|worktree| match &config {
                 None => true,
@@ -1258,17 +2568,64 @@ impl RepoHandle {
                     Some(branches) => !branches.iter().any(|branch| branch == worktree.name()),
                 },
             })
-        {
+            .filter(|worktree| {
+                WorktreeRootConfig::skip_pattern(config.as_ref(), worktree.name()).is_none()
+            })
+            .collect())
+    }
+
+    pub fn cleanup_worktrees(&self, directory: &Path) -> Result<Vec<String>, String> {
+        let mut warnings = Vec::new();
+
+        let config = read_worktree_root_config(directory)?;
+
+        let worktrees = self.cleanup_candidates(&config)?;
+
+        let delete_remote = config
+            .as_ref()
+            .and_then(|config| config.cleanup.as_ref())
+            .and_then(|cleanup| cleanup.delete_remote)
+            .unwrap_or(false);
+        // Remote name -> branches, so there is only one push per remote
+        let mut remote_deletions: BTreeMap<String, Vec<(String, Oid)>> = BTreeMap::new();
+
+        for worktree in &worktrees {
             let repo_dir = &directory.join(worktree.name());
             if repo_dir.exists() {
+                // Before removing the worktree, as its branch is deleted with it
+                let remote_branch = if delete_remote {
+                    self.merged_remote_branch(worktree.name(), &config)
+                        .unwrap_or_else(|error| {
+                            warnings.push(format!(
+                                "Keeping the remote branch of {}: {}",
+                                worktree.name(),
+                                error
+                            ));
+                            None
+                        })
+                } else {
+                    None
+                };
                 match self.remove_worktree(
                     directory,
                     worktree.name(),
                     Path::new(worktree.name()),
-                    false,
+                    WorktreeRemoveOverrides::default(),
                     &config,
                 ) {
-                    Ok(()) => print_success(&format!("Worktree {} deleted", &worktree.name())),
+                    Ok(archive) => {
+                        print_success(&format!(
+                            "Worktree {} deleted{}",
+                            &worktree.name(),
+                            archived_to(archive.as_deref())
+                        ));
+                        if let Some((remote_name, branch_name, oid)) = remote_branch {
+                            remote_deletions
+                                .entry(remote_name)
+                                .or_default()
+                                .push((branch_name, oid));
+                        }
+                    }
                     Err(error) => match error {
                         WorktreeRemoveFailureReason::Changes(changes) => {
                             warnings.push(format!(
@@ -1278,6 +2635,14 @@ impl RepoHandle {
                             ));
                             continue;
                         }
+                        WorktreeRemoveFailureReason::Submodules(problems) => {
+                            warnings.push(format!(
+                                "Submodules of {} would lose work: {}, skipping",
+                                worktree.name(),
+                                problems
+                            ));
+                            continue;
+                        }
                         WorktreeRemoveFailureReason::NotMerged(message) => {
                             warnings.push(message);
                             continue;
@@ -1294,7 +2659,144 @@ impl RepoHandle {
                 ));
             }
         }
-        Ok(warnings)
+
+        for (remote_name, branches) in remote_deletions {
+            warnings.extend(self.delete_remote_branches(&remote_name, &branches));
+        }
+
+        Ok(warnings)
+    }
+
+    /// The remote and the name of the branch on that remote that the local branch
+    /// `branch_name` tracks, if it tracks one
+    fn upstream_of(&self, branch_name: &str) -> Result<Option<(String, String)>, String> {
+        let refname = format!("refs/heads/{branch_name}");
+        let (Ok(remote), Ok(upstream)) = (
+            self.0.branch_upstream_remote(&refname),
+            self.0.branch_upstream_name(&refname),
+        ) else {
+            return Ok(None);
+        };
+        let remote = remote
+            .as_str()
+            .ok_or_else(|| String::from("Remote name is not valid utf-8"))?;
+        let upstream = upstream
+            .as_str()
+            .ok_or_else(|| String::from("Remote branch name is not valid utf-8"))?;
+        Ok(upstream
+            .strip_prefix(&format!("refs/remotes/{remote}/"))
+            .map(|branch| (remote.to_string(), branch.to_string())))
+    }
+
+    /// The remote branch of `branch_name` if it can be deleted, i.e. if it is
+    /// merged into one of the persistent branches (as of the last fetch),
+    /// together with the commit it pointed to at the last fetch
+    ///
+    /// Persistent branches and the default branch are never returned, even if a
+    /// worktree tracks them.
+    fn merged_remote_branch(
+        &self,
+        branch_name: &str,
+        config: &Option<WorktreeRootConfig>,
+    ) -> Result<Option<(String, String, Oid)>, String> {
+        let Some(persistent_branches) = config
+            .as_ref()
+            .and_then(|config| config.persistent_branches.as_ref())
+        else {
+            return Ok(None);
+        };
+        let Some((remote_name, remote_branch_name)) = self.upstream_of(branch_name)? else {
+            return Ok(None);
+        };
+
+        let is_default_branch = self
+            .default_branch()
+            .and_then(|branch| branch.name())
+            .is_ok_and(|name| name == remote_branch_name);
+        if is_default_branch || persistent_branches.contains(&remote_branch_name) {
+            return Ok(None);
+        }
+
+        let remote_branch = self.find_remote_branch(&remote_name, &remote_branch_name)?;
+        for persistent_branch in persistent_branches {
+            let persistent_branch = self.find_local_branch(persistent_branch)?;
+            let (ahead, _behind) = self.graph_ahead_behind(&remote_branch, &persistent_branch)?;
+            if ahead == 0 {
+                let oid = remote_branch.commit()?.id();
+                return Ok(Some((remote_name, remote_branch_name, oid)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Deletes `branches` on the remote `remote_name`, returning warnings for the
+    /// ones that could not be deleted
+    ///
+    /// A branch is only deleted if it still points to the given commit on the
+    /// remote. Otherwise, someone pushed to it since the last fetch, and these
+    /// commits were never checked to be merged.
+    fn delete_remote_branches(&self, remote_name: &str, branches: &[(String, Oid)]) -> Vec<String> {
+        let failed = |reason: &str| {
+            branches
+                .iter()
+                .map(|(branch, _oid)| {
+                    format!("Remote branch {remote_name}/{branch} was not deleted: {reason}")
+                })
+                .collect()
+        };
+
+        let mut remote = match self.find_remote(remote_name) {
+            Ok(Some(remote)) => remote,
+            Ok(None) => return failed("Remote not found"),
+            Err(error) => return failed(&error),
+        };
+        match remote.is_pushable() {
+            Ok(true) => (),
+            Ok(false) => return failed("Cannot push to non-pushable remote"),
+            Err(error) => return failed(&error),
+        }
+
+        let tips = match remote.branch_tips() {
+            Ok(tips) => tips,
+            Err(error) => return failed(&error),
+        };
+
+        let mut warnings = Vec::new();
+        let mut branches_to_delete = Vec::new();
+        for (branch, oid) in branches {
+            match tips.get(branch) {
+                Some(tip) if tip == oid => branches_to_delete.push(branch.clone()),
+                Some(_) => warnings.push(format!(
+                    "Remote branch {remote_name}/{branch} was not deleted: It was updated on the remote since the last fetch"
+                )),
+                None => warnings.push(format!(
+                    "Remote branch {remote_name}/{branch} was not deleted: It does not exist on the remote anymore"
+                )),
+            }
+        }
+        if branches_to_delete.is_empty() {
+            return warnings;
+        }
+
+        let rejected = match remote.delete_branches(&branches_to_delete) {
+            Ok(rejected) => rejected,
+            Err(error) => {
+                warnings.extend(branches_to_delete.iter().map(|branch| {
+                    format!("Remote branch {remote_name}/{branch} was not deleted: {error}")
+                }));
+                return warnings;
+            }
+        };
+
+        for branch in &branches_to_delete {
+            match rejected.iter().find(|(name, _message)| name == branch) {
+                Some((_name, message)) => warnings.push(format!(
+                    "Remote {remote_name} refused to delete branch {branch}: {message}"
+                )),
+                None => print_success(&format!("Remote branch {remote_name}/{branch} deleted")),
+            }
+        }
+        warnings
     }
 
     pub fn find_unmanaged_worktrees(&self, directory: &Path) -> Result<Vec<String>, String> {
@@ -1343,6 +2845,12 @@ impl RepoHandle {
             if dirname == WORKTREE_CONFIG_FILE_NAME {
                 continue;
             }
+            if dirname == profile::PROFILES_FILE_NAME {
+                continue;
+            }
+            if dirname == worktree::STATE_FILE_NAME {
+                continue;
+            }
             if let Some(default_branch_name) = default_branch_name {
                 if dirname == default_branch_name {
                     continue;
@@ -1358,11 +2866,164 @@ impl RepoHandle {
     pub fn detect_worktree(path: &Path) -> bool {
         path.join(worktree::GIT_MAIN_WORKTREE_DIRECTORY).exists()
     }
+
+    /// Whether the repository at `path` is colocated with jujutsu (jj), i.e. jj
+    /// and git share the working copy
+    pub fn detect_jj(path: &Path) -> bool {
+        path.join(".jj").is_dir()
+    }
+
+    /// Finds the repository that `path` belongs to, searching the parent
+    /// directories as well
+    ///
+    /// Linked worktrees resolve to their main repository through the common
+    /// git directory, so the whole repository is found from anywhere inside a
+    /// worktree.
+    pub fn discover(path: &Path) -> Result<DiscoveredRepo, RepoError> {
+        if Self::detect_worktree(path) {
+            return Ok(DiscoveredRepo {
+                path: path.to_path_buf(),
+                is_worktree: true,
+                current_worktree: None,
+            });
+        }
+
+        let repo = Repository::discover(path).map_err(|error| match error.code() {
+            git2::ErrorCode::NotFound => RepoError::new(RepoErrorKind::NotFound),
+            _ => RepoError::new(RepoErrorKind::Unknown(convert_libgit2_error(error))),
+        })?;
+
+        if !repo.is_worktree() {
+            return Ok(DiscoveredRepo {
+                path: repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf(),
+                is_worktree: false,
+                current_worktree: None,
+            });
+        }
+
+        // The common directory is the git directory of the main repository,
+        // i.e. `.git` or the main worktree directory of a worktree setup. The
+        // git directory of a linked worktree points to it in its `commondir`
+        // file, relative to itself.
+        let gitdir = repo.path();
+        let commondir = std::fs::read_to_string(gitdir.join("commondir"))
+            .and_then(|commondir| gitdir.join(commondir.trim()).canonicalize())
+            .map_err(|error| {
+                RepoError::new(RepoErrorKind::Unknown(format!(
+                    "Failed to resolve the common directory of \"{}\": {}",
+                    gitdir.display(),
+                    error
+                )))
+            })?;
+        let is_worktree = commondir.file_name()
+            == Some(std::ffi::OsStr::new(worktree::GIT_MAIN_WORKTREE_DIRECTORY));
+        let path = commondir.parent().ok_or_else(|| {
+            RepoError::new(RepoErrorKind::Unknown(format!(
+                "Repository at \"{}\" has no parent directory",
+                commondir.display()
+            )))
+        })?;
+
+        Ok(DiscoveredRepo {
+            path: path.to_path_buf(),
+            is_worktree,
+            current_worktree: repo.workdir().map(Path::to_path_buf),
+        })
+    }
+}
+
+/// A repository found by [`RepoHandle::discover`]
+pub struct DiscoveredRepo {
+    /// The directory of the repository, containing the main worktree directory
+    /// for a worktree setup
+    pub path: PathBuf,
+    pub is_worktree: bool,
+    /// The linked worktree that the search started in, if any
+    pub current_worktree: Option<PathBuf>,
+}
+
+/// A repository that stays open for a whole operation on it (e.g. the sync of a
+/// single repository), so that all steps share the same [`RepoHandle`]
+///
+/// The remotes and the default branch are looked up on first use and then kept.
+/// Changing the remotes through the session forgets them again. Everything else
+/// is passed through to the handle.
+pub struct RepoSession {
+    handle: RepoHandle,
+    remotes: RefCell<Option<Vec<String>>>,
+    default_branch: RefCell<Option<String>>,
+}
+
+impl std::ops::Deref for RepoSession {
+    type Target = RepoHandle;
+
+    fn deref(&self) -> &RepoHandle {
+        &self.handle
+    }
+}
+
+impl RepoSession {
+    pub fn new(handle: RepoHandle) -> Self {
+        Self {
+            handle,
+            remotes: RefCell::new(None),
+            default_branch: RefCell::new(None),
+        }
+    }
+
+    pub fn open(path: &Path, is_worktree: bool) -> Result<Self, RepoError> {
+        Ok(Self::new(RepoHandle::open(path, is_worktree)?))
+    }
+
+    fn forget_remotes(&self) {
+        self.remotes.replace(None);
+        // The default branch is guessed from the remotes
+        self.default_branch.replace(None);
+    }
+
+    pub fn remotes(&self) -> Result<Vec<String>, String> {
+        if let Some(remotes) = self.remotes.borrow().as_ref() {
+            return Ok(remotes.clone());
+        }
+        let remotes = self.handle.remotes()?;
+        self.remotes.replace(Some(remotes.clone()));
+        Ok(remotes)
+    }
+
+    pub fn default_branch(&self) -> Result<Branch<'_>, String> {
+        if let Some(name) = self.default_branch.borrow().as_ref() {
+            return self.handle.find_local_branch(name);
+        }
+        let branch = self.handle.default_branch()?;
+        self.default_branch.replace(Some(branch.name()?));
+        Ok(branch)
+    }
+
+    pub fn new_remote(&self, name: &str, url: &str) -> Result<(), String> {
+        self.forget_remotes();
+        self.handle.new_remote(name, url)
+    }
+
+    pub fn remote_set_url(&self, name: &str, url: &str) -> Result<(), String> {
+        self.forget_remotes();
+        self.handle.remote_set_url(name, url)
+    }
+
+    pub fn remote_delete(&self, name: &str) -> Result<(), String> {
+        self.forget_remotes();
+        self.handle.remote_delete(name)
+    }
+
+    pub fn rename_remote(&self, remote: &RemoteHandle, new_name: &str) -> Result<(), String> {
+        self.forget_remotes();
+        self.handle.rename_remote(remote, new_name)
+    }
 }
 
 pub struct RemoteHandle<'a>(git2::Remote<'a>);
 pub struct Commit<'a>(git2::Commit<'a>);
 pub struct Reference();
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Oid(git2::Oid);
 
 impl Oid {
@@ -1376,6 +3037,11 @@ impl Commit<'_> {
         Oid(self.0.id())
     }
 
+    /// The commit time as a unix timestamp
+    pub fn time(&self) -> i64 {
+        self.0.time().seconds()
+    }
+
     pub(self) fn author(&self) -> git2::Signature {
         self.0.author()
     }
@@ -1449,7 +3115,43 @@ impl<'a> Branch<'a> {
     }
 }
 
-fn get_remote_callbacks() -> git2::RemoteCallbacks<'static> {
+/// The ways pushing a branch can fail
+#[derive(Debug, PartialEq, Eq)]
+pub enum PushError {
+    /// The remote does not allow the branch to be created, e.g. because it
+    /// matches a protected branch pattern or due to missing permissions
+    Refused(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for PushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Refused(message) | Self::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<String> for PushError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<PushError> for String {
+    fn from(error: PushError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Whether a failed push was refused by the remote because of missing
+/// permissions, in contrast to e.g. network problems
+fn is_refusal(error: &git2::Error) -> bool {
+    error.class() == git2::ErrorClass::Http && error.message().contains("status code: 403")
+}
+
+/// The callbacks for network operations with the remote at `url`
+fn get_remote_callbacks(url: &str) -> git2::RemoteCallbacks<'static> {
     let mut callbacks = git2::RemoteCallbacks::new();
     callbacks.push_update_reference(|_, status| {
         if let Some(message) = status {
@@ -1469,6 +3171,9 @@ fn get_remote_callbacks() -> git2::RemoteCallbacks<'static> {
         git2::Cred::ssh_key_from_agent(username)
     });
 
+    let port = known_hosts::ssh_port(url);
+    callbacks.certificate_check(move |cert, host| known_hosts::certificate_check(cert, host, port));
+
     callbacks
 }
 
@@ -1501,46 +3206,399 @@ impl RemoteHandle<'_> {
             .to_string())
     }
 
+    /// The branches on the remote and the commits they point to right now, not
+    /// as of the last fetch
+    pub fn branch_tips(&mut self) -> Result<HashMap<String, Oid>, String> {
+        let (name, url) = (self.name(), self.url());
+        let connection = self
+            .0
+            .connect_auth(
+                git2::Direction::Push,
+                Some(get_remote_callbacks(&url)),
+                None,
+            )
+            .map_err(|error| format!("Connecting to {name} ({url}) failed: {error}"))?;
+        Ok(connection
+            .list()
+            .map_err(convert_libgit2_error)?
+            .iter()
+            .filter_map(|head| {
+                head.name()
+                    .strip_prefix("refs/heads/")
+                    .map(|branch| (branch.to_string(), Oid(head.oid())))
+            })
+            .collect())
+    }
+
     pub fn is_pushable(&self) -> Result<bool, String> {
         let remote_type = detect_remote_type(self.0.url().expect("Remote name is not valid utf-8"))
             .expect("Could not detect remote type");
         Ok(matches!(remote_type, RemoteType::Ssh | RemoteType::File))
     }
 
-    pub fn push(
+    /// Deletes `branches` on the remote, all with a single push
+    ///
+    /// Returns the branches that the remote refused to delete (e.g. protected
+    /// ones), together with the reason.
+    pub fn delete_branches(
         &mut self,
-        local_branch_name: &str,
-        remote_branch_name: &str,
-        _repo: &RepoHandle,
-    ) -> Result<(), String> {
+        branches: &[String],
+    ) -> Result<Vec<(String, String)>, String> {
         if !self.is_pushable()? {
             return Err(String::from("Trying to push to a non-pushable remote"));
         }
 
+        let rejected: Rc<RefCell<Vec<(String, String)>>> = Rc::new(RefCell::new(Vec::new()));
+
+        let mut callbacks = get_remote_callbacks(&self.url());
+        {
+            let rejected = Rc::clone(&rejected);
+            callbacks.push_update_reference(move |refname, status| {
+                if let Some(message) = status {
+                    rejected.borrow_mut().push((
+                        refname
+                            .strip_prefix("refs/heads/")
+                            .unwrap_or(refname)
+                            .to_string(),
+                        message.to_string(),
+                    ));
+                }
+                Ok(())
+            });
+        }
+
         let mut push_options = git2::PushOptions::new();
-        push_options.remote_callbacks(get_remote_callbacks());
+        push_options.remote_callbacks(callbacks);
 
-        let push_refspec =
-            format!("+refs/heads/{local_branch_name}:refs/heads/{remote_branch_name}",);
+        let refspecs: Vec<String> = branches
+            .iter()
+            .map(|branch| format!(":refs/heads/{branch}"))
+            .collect();
         self.0
-            .push(&[push_refspec], Some(&mut push_options))
+            .push(&refspecs, Some(&mut push_options))
             .map_err(|error| {
                 format!(
-                    "Pushing {} to {} ({}) failed: {}",
-                    local_branch_name,
+                    "Deleting branches on {} ({}) failed: {}",
                     self.name(),
                     self.url(),
                     error
                 )
             })?;
-        Ok(())
+
+        let rejected = rejected.borrow_mut().drain(..).collect();
+        Ok(rejected)
+    }
+
+    pub fn push(
+        &mut self,
+        local_branch_name: &str,
+        remote_branch_name: &str,
+        _repo: &RepoHandle,
+    ) -> Result<(), PushError> {
+        if !self.is_pushable()? {
+            return Err(PushError::Failed(String::from(
+                "Trying to push to a non-pushable remote",
+            )));
+        }
+
+        // The remote may accept the push as a whole, but reject the update of
+        // the branch itself, e.g. due to branch protection rules. This is only
+        // reported through the callback.
+        let rejection: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+
+        let mut callbacks = get_remote_callbacks(&self.url());
+        {
+            let rejection = Rc::clone(&rejection);
+            callbacks.push_update_reference(move |_, status| {
+                if let Some(message) = status {
+                    *rejection.borrow_mut() = Some(message.to_string());
+                    return Err(git2::Error::new(
+                        git2::ErrorCode::GenericError,
+                        git2::ErrorClass::None,
+                        message,
+                    ));
+                }
+                Ok(())
+            });
+        }
+
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+
+        let push_refspec =
+            format!("+refs/heads/{local_branch_name}:refs/heads/{remote_branch_name}",);
+        let result = self.0.push(&[push_refspec], Some(&mut push_options));
+
+        let refused = |message: &str| {
+            PushError::Refused(format!(
+                "Remote {} refused branch creation of {} (protected pattern?): {}",
+                self.name(),
+                remote_branch_name,
+                message
+            ))
+        };
+
+        let rejection = rejection.borrow_mut().take();
+        match (result, rejection) {
+            (Ok(()), None) => Ok(()),
+            (_, Some(message)) => Err(refused(&message)),
+            (Err(error), None) if is_refusal(&error) => Err(refused(error.message())),
+            (Err(error), None) => Err(PushError::Failed(format!(
+                "Pushing {} to {} ({}) failed: {}",
+                local_branch_name,
+                self.name(),
+                self.url(),
+                error
+            ))),
+        }
+    }
+}
+
+/// Checks with `git ls-remote` that the repository at `url` exists and can be
+/// read, giving up after `timeout`
+///
+/// Never prompts for credentials or unknown SSH host keys, that counts as a
+/// failure.
+pub fn verify_remote(url: &str, timeout: std::time::Duration) -> Result<(), String> {
+    let mut command = std::process::Command::new("git");
+    command
+        .args(["ls-remote", "--quiet", "--", url, "HEAD"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped());
+    if std::env::var_os("GIT_SSH_COMMAND").is_none() {
+        command.env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes");
+    }
+
+    let mut child = command
+        .spawn()
+        .map_err(|error| format!("Failed to run git: {error}"))?;
+
+    let start = std::time::Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(_)) => break,
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("No response within {} seconds", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(50)),
+            Err(error) => return Err(format!("Failed to run git: {error}")),
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Failed to run git: {error}"))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // The first line has the reason, e.g. that the repository was not found
+    Err(stderr
+        .lines()
+        .map(|line| line.trim_start_matches("fatal: ").trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("git ls-remote failed")
+        .to_string())
+}
+
+/// Runs the git command line client, in `directory` if given, and returns its
+/// standard output
+fn run_git(directory: Option<&Path>, args: &[&str]) -> Result<String, String> {
+    let mut command = std::process::Command::new("git");
+    if let Some(directory) = directory {
+        command.arg("-C").arg(directory);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|error| format!("Failed to run git: {error}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Clones with the git command line client, for partial clones and clones that
+/// borrow the objects of another repository, as libgit2 supports neither
+fn clone_with_git(
+    remote: &Remote,
+    clone_target: &Path,
+    bare: bool,
+    filter: Option<&str>,
+    history_days: Option<u32>,
+    reference: Option<&Path>,
+) -> Result<(), String> {
+    let mut args = vec![String::from("clone"), String::from("--quiet")];
+    if bare {
+        args.push(String::from("--bare"));
+    }
+    if let Some(filter) = filter {
+        args.push(format!("--filter={filter}"));
+    }
+    if let Some(history_days) = history_days {
+        // Shallow clones only get the default branch otherwise
+        args.push(format!("--shallow-since={history_days} days ago"));
+        args.push(String::from("--no-single-branch"));
+    }
+    if let Some(reference) = reference {
+        args.push(String::from("--reference"));
+        args.push(path::path_as_utf8(reference)?.to_string());
+    }
+    args.extend([
+        String::from("--origin"),
+        remote.name.clone(),
+        String::from("--"),
+        remote.url.clone(),
+        path::path_as_utf8(clone_target)?.to_string(),
+    ]);
+    run_git(
+        None,
+        &args.iter().map(String::as_str).collect::<Vec<&str>>(),
+    )?;
+
+    // Unlike libgit2, git does not set up remote tracking branches for bare
+    // clones, but worktree setups rely on them
+    if bare {
+        run_git(
+            Some(clone_target),
+            &[
+                "config",
+                &format!("remote.{}.fetch", remote.name),
+                &format!("+refs/heads/*:refs/remotes/{}/*", remote.name),
+            ],
+        )?;
+        run_git(Some(clone_target), &["fetch", "--quiet", &remote.name])?;
+    }
+
+    Ok(())
+}
+
+/// Copies the objects that a repository borrows from another one (see
+/// `git clone --reference`) into the repository itself and stops borrowing
+///
+/// `git_dir` is the git directory, i.e. the main worktree directory for worktree
+/// setups.
+pub fn dissociate_repo(git_dir: &Path) -> Result<(), String> {
+    let git_dir = PathBuf::from(run_git(
+        Some(git_dir),
+        &["rev-parse", "--absolute-git-dir"],
+    )?);
+    let alternates = git_dir.join("objects").join("info").join("alternates");
+    if !alternates.exists() {
+        return Err(format!(
+            "\"{}\" does not share objects with another repository",
+            git_dir.display()
+        ));
     }
+
+    // Without --local, repack also packs the borrowed objects
+    run_git(Some(&git_dir), &["repack", "-a", "-d", "--quiet"])?;
+    std::fs::remove_file(&alternates)
+        .map_err(|error| format!("Error removing \"{}\": {}", alternates.display(), error))
+}
+
+/// Turns a partial clone into a full one by fetching all missing objects
+///
+/// Returns an error if the repository at `path` is not a partial clone.
+pub fn unfilter_repo(path: &Path) -> Result<(), String> {
+    // The filter is stored per remote, together with the remote being marked as
+    // a "promisor" that can deliver missing objects later
+    let promisors = run_git(
+        Some(path),
+        &["config", "--get-regexp", r"^remote\..*\.promisor$"],
+    )
+    .unwrap_or_default();
+
+    let remotes: Vec<&str> = promisors
+        .lines()
+        .filter_map(|line| line.strip_suffix(" true"))
+        .filter_map(|key| key.strip_prefix("remote."))
+        .filter_map(|key| key.strip_suffix(".promisor"))
+        .collect();
+
+    if remotes.is_empty() {
+        return Err(format!("\"{}\" is not a partial clone", path.display()));
+    }
+
+    for remote in remotes {
+        print_action(&format!("Fetching all objects from {remote}"));
+        let filter_key = format!("remote.{remote}.partialclonefilter");
+        // Exits with 5 if the key does not exist, which is fine
+        let _ = run_git(Some(path), &["config", "--unset", &filter_key]);
+        run_git(Some(path), &["fetch", "--quiet", "--refetch", remote])?;
+        run_git(
+            Some(path),
+            &["config", "--unset", &format!("remote.{remote}.promisor")],
+        )?;
+    }
+
+    // Older versions of git also record the promisor remote as a repository
+    // extension
+    let _ = run_git(
+        Some(path),
+        &["config", "--unset", "extensions.partialclone"],
+    );
+
+    Ok(())
+}
+
+/// Whether the repository with the git directory `path` has any stashes
+pub fn has_stashes(path: &Path) -> Result<bool, String> {
+    let repo = Repository::open(path).map_err(convert_libgit2_error)?;
+    let has_stashes = repo.find_reference("refs/stash").is_ok();
+    Ok(has_stashes)
+}
+
+/// Drops all history older than `days` from the repository with the git
+/// directory `path` and removes the objects that are no longer needed
+///
+/// Branches that have no commits in that time keep their history. The reflogs
+/// are left alone, so commits that are only reachable through them (e.g. stashes
+/// or reset branches) are kept as well.
+pub fn trim_history(path: &Path, days: u32) -> Result<(), String> {
+    run_git(
+        Some(path),
+        &[
+            "fetch",
+            "--quiet",
+            "--all",
+            &format!("--shallow-since={days} days ago"),
+        ],
+    )?;
+    run_git(Some(path), &["gc", "--quiet", "--prune=now"]).map(|_| ())
+}
+
+/// The message about cloning `remote` into `path`, printed by the callers of
+/// [`clone_repo`] as not all of them want to show it
+pub fn clone_message(remote: &Remote, path: &Path) -> String {
+    format!(
+        "Cloning into \"{}\" from \"{}\"",
+        path.display(),
+        remote.url
+    )
 }
 
+/// Clones the repository from `remote` to `path`
+///
+/// With `history_days`, only the history of the last days is cloned. With
+/// `reference`, the objects of the repository at that path are used instead of
+/// downloading them again, see `git clone --reference`.
 pub fn clone_repo(
     remote: &Remote,
     path: &Path,
     is_worktree: bool,
+    filter: Option<&str>,
+    history_days: Option<u32>,
+    reference: Option<&Path>,
+    retry: &RetryConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let clone_target = if is_worktree {
         path.join(worktree::GIT_MAIN_WORKTREE_DIRECTORY)
@@ -1548,31 +3606,45 @@ pub fn clone_repo(
         path.to_path_buf()
     };
 
-    print_action(&format!(
-        "Cloning into \"{}\" from \"{}\"",
-        &clone_target.display(),
-        &remote.url
-    ));
-    match remote.remote_type {
-        RemoteType::Https | RemoteType::File => {
-            let mut builder = git2::build::RepoBuilder::new();
+    if filter.is_some() && is_worktree {
+        // Worktrees are checked out by libgit2, which cannot fetch missing
+        // objects on demand
+        return Err("Partial clones cannot be used with a worktree setup".into());
+    }
 
-            let fetchopts = git2::FetchOptions::new();
+    let with_git = filter.is_some() || history_days.is_some() || reference.is_some();
+    match (with_git, &remote.remote_type) {
+        (true, _) => clone_with_git(
+            remote,
+            &clone_target,
+            is_worktree,
+            filter,
+            history_days,
+            reference,
+        )?,
+        (false, RemoteType::Https | RemoteType::File) => {
+            retry.run("Cloning", || {
+                let mut builder = git2::build::RepoBuilder::new();
 
-            builder.bare(is_worktree);
-            builder.fetch_options(fetchopts);
+                let fetchopts = git2::FetchOptions::new();
 
-            builder.clone(&remote.url, &clone_target)?;
+                builder.bare(is_worktree);
+                builder.fetch_options(fetchopts);
+
+                builder.clone(&remote.url, &clone_target)
+            })?;
         }
-        RemoteType::Ssh => {
-            let mut fo = git2::FetchOptions::new();
-            fo.remote_callbacks(get_remote_callbacks());
+        (false, RemoteType::Ssh) => {
+            retry.run("Cloning", || {
+                let mut fo = git2::FetchOptions::new();
+                fo.remote_callbacks(get_remote_callbacks(&remote.url));
 
-            let mut builder = git2::build::RepoBuilder::new();
-            builder.bare(is_worktree);
-            builder.fetch_options(fo);
+                let mut builder = git2::build::RepoBuilder::new();
+                builder.bare(is_worktree);
+                builder.fetch_options(fo);
 
-            builder.clone(&remote.url, &clone_target)?;
+                builder.clone(&remote.url, &clone_target)
+            })?;
         }
     }
 
@@ -1582,7 +3654,8 @@ pub fn clone_repo(
         repo.set_config_push(GitPushDefaultSetting::Upstream)?;
     }
 
-    if remote.name != "origin" {
+    // Clones by git get the correct remote name right away
+    if remote.name != "origin" && !with_git {
         // unwrap() is safe here as the origin remote will always exist after a successful clone.
         // Note that actual errors are handled in the Results Err variant, not in
         // the Ok variant option
@@ -1595,7 +3668,12 @@ pub fn clone_repo(
     for remote_branch in repo.remote_branches()? {
         let local_branch_name = remote_branch.basename()?;
 
-        if repo.find_local_branch(&local_branch_name).is_ok() {
+        // Bare clones by git already have all local branches, but without
+        // upstream
+        if let Ok(mut local_branch) = repo.find_local_branch(&local_branch_name) {
+            if local_branch.upstream().is_err() {
+                local_branch.set_upstream(&remote.name, &local_branch_name)?;
+            }
             continue;
         }
 
@@ -1621,6 +3699,141 @@ pub fn clone_repo(
 mod tests {
     use super::*;
 
+    #[test]
+    fn check_retry_delay() {
+        use std::time::Duration;
+
+        let retry = RetryConfig {
+            retries: Some(100),
+            backoff: Some(2),
+        };
+        assert_eq!(retry.backoff(), Duration::from_secs(2));
+        assert_eq!(
+            RetryConfig::next_delay(Duration::from_secs(2)),
+            Duration::from_secs(4)
+        );
+
+        let mut delay = retry.backoff();
+        for _ in 0..100 {
+            delay = RetryConfig::next_delay(delay);
+        }
+        assert_eq!(delay, MAX_RETRY_DELAY);
+
+        let retry = RetryConfig {
+            retries: Some(1),
+            backoff: Some(u64::MAX),
+        };
+        assert_eq!(retry.backoff(), MAX_RETRY_DELAY);
+        assert_eq!(RetryConfig::next_delay(Duration::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn check_matches_pattern() {
+        assert!(matches_pattern("wip/*", "wip/foo"));
+        assert!(matches_pattern("wip/*", "wip/"));
+        assert!(!matches_pattern("wip/*", "wip"));
+        assert!(!matches_pattern("wip/*", "feature/wip/foo"));
+        assert!(matches_pattern("spike-*", "spike-parser"));
+        assert!(matches_pattern("*-old", "parser-old"));
+        assert!(matches_pattern("a*b*c", "aXXbYYbc"));
+        assert!(!matches_pattern("a*b*c", "aXXbYYb"));
+        assert!(matches_pattern("v?", "v1"));
+        assert!(!matches_pattern("v?", "v10"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("exact", "exact"));
+        assert!(!matches_pattern("exact", "exactly"));
+    }
+
+    #[test]
+    fn check_with_project_config() {
+        let project = || ProjectConfig {
+            default_branch: Some(String::from("develop")),
+            persistent_branches: Some(vec![String::from("main"), String::from("develop")]),
+            post_clone: None,
+            sparse: Some(vec![String::from("src")]),
+        };
+
+        let config = WorktreeRootConfig::with_project_config(None, project()).unwrap();
+        assert_eq!(
+            config.persistent_branches,
+            Some(vec![String::from("develop"), String::from("main")])
+        );
+        assert_eq!(config.sparse, Some(vec![String::from("src")]));
+
+        // The configuration of the user wins
+        let user = WorktreeRootConfig {
+            persistent_branches: Some(vec![String::from("trunk")]),
+            ..Default::default()
+        };
+        let config = WorktreeRootConfig::with_project_config(Some(user), project()).unwrap();
+        assert_eq!(
+            config.persistent_branches,
+            Some(vec![String::from("trunk")])
+        );
+        assert_eq!(config.sparse, Some(vec![String::from("src")]));
+
+        assert!(WorktreeRootConfig::with_project_config(None, ProjectConfig::default()).is_none());
+    }
+
+    #[test]
+    fn check_is_retryable() {
+        use git2::{ErrorClass, ErrorCode};
+        let error = |code, class, message| git2::Error::new(code, class, message);
+
+        assert!(is_retryable(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Net,
+            "failed to connect to example.com: Connection timed out"
+        )));
+        assert!(is_retryable(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Os,
+            "failed to connect to 127.0.0.1: Connection refused"
+        )));
+        assert!(is_retryable(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 502"
+        )));
+        assert!(!is_retryable(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 404"
+        )));
+        assert!(!is_retryable(&error(
+            ErrorCode::Auth,
+            ErrorClass::Ssh,
+            "Permission denied (publickey)"
+        )));
+        assert!(!is_retryable(&error(
+            ErrorCode::NotFound,
+            ErrorClass::Repository,
+            "could not find repository"
+        )));
+    }
+
+    #[test]
+    fn check_is_refusal() {
+        use git2::{ErrorClass, ErrorCode};
+        let error = |code, class, message| git2::Error::new(code, class, message);
+
+        assert!(is_refusal(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 403"
+        )));
+        assert!(!is_refusal(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 502"
+        )));
+        assert!(!is_refusal(&error(
+            ErrorCode::GenericError,
+            ErrorClass::Net,
+            "failed to connect to example.com: Connection timed out"
+        )));
+    }
+
     #[test]
     fn check_ssh_remote() {
         assert_eq!(
@@ -1699,6 +3912,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn check_remote_host() {
+        assert_eq!(
+            remote_host("https://example.com/repo.git"),
+            Some(String::from("example.com"))
+        );
+        assert_eq!(
+            remote_host("ssh://git@Example.com:2222/repo.git"),
+            Some(String::from("example.com"))
+        );
+        assert_eq!(
+            remote_host("git@example.com:user/repo.git"),
+            Some(String::from("example.com"))
+        );
+        assert_eq!(remote_host("file:///path/to/repo"), None);
+        assert_eq!(remote_host("/path/to/repo"), None);
+        assert_eq!(remote_host("https:///repo.git"), None);
+    }
+
+    #[test]
+    fn check_rewrite_remote_url() {
+        let mut mappings = default_host_mappings();
+        mappings.push(HostMapping {
+            https: "git.example.com".to_string(),
+            ssh: "git@ssh.example.com".to_string(),
+        });
+
+        assert_eq!(
+            rewrite_remote_url(
+                "https://github.com/user/repo.git",
+                &RemoteType::Ssh,
+                &mappings
+            ),
+            Some("git@github.com:user/repo.git".to_string())
+        );
+        assert_eq!(
+            rewrite_remote_url(
+                "https://gitlab.com/group/sub/repo",
+                &RemoteType::Ssh,
+                &mappings
+            ),
+            Some("git@gitlab.com:group/sub/repo.git".to_string())
+        );
+        assert_eq!(
+            rewrite_remote_url(
+                "https://git.example.com/repo.git",
+                &RemoteType::Ssh,
+                &mappings
+            ),
+            Some("git@ssh.example.com:repo.git".to_string())
+        );
+        assert_eq!(
+            rewrite_remote_url(
+                "git@github.com:user/repo.git",
+                &RemoteType::Https,
+                &mappings
+            ),
+            Some("https://github.com/user/repo.git".to_string())
+        );
+        assert_eq!(
+            rewrite_remote_url(
+                "ssh://git@ssh.example.com/repo.git",
+                &RemoteType::Https,
+                &mappings
+            ),
+            Some("https://git.example.com/repo.git".to_string())
+        );
+
+        // Already using the protocol
+        assert_eq!(
+            rewrite_remote_url("git@github.com:user/repo.git", &RemoteType::Ssh, &mappings),
+            None
+        );
+        assert_eq!(
+            rewrite_remote_url(
+                "https://github.com/user/repo.git",
+                &RemoteType::Https,
+                &mappings
+            ),
+            None
+        );
+        // Unknown hosts
+        assert_eq!(
+            rewrite_remote_url("https://example.org/repo.git", &RemoteType::Ssh, &mappings),
+            None
+        );
+        assert_eq!(
+            rewrite_remote_url("git@example.org:repo.git", &RemoteType::Https, &mappings),
+            None
+        );
+        assert_eq!(
+            rewrite_remote_url("file:///path/to/repo", &RemoteType::Https, &mappings),
+            None
+        );
+    }
+
     #[test]
     fn repo_check_fullname() {
         let with_namespace = Repo {
@@ -1706,6 +4015,12 @@ mod tests {
             namespace: Some("namespace".to_string()),
             worktree_setup: false,
             remotes: None,
+            provider_id: None,
+            tags: Vec::new(),
+            filter: None,
+            default_branch: None,
+            dir: None,
+            remote_name: None,
         };
 
         let without_namespace = Repo {
@@ -1713,9 +4028,68 @@ mod tests {
             namespace: None,
             worktree_setup: false,
             remotes: None,
+            provider_id: None,
+            tags: Vec::new(),
+            filter: None,
+            default_branch: None,
+            dir: None,
+            remote_name: None,
         };
 
         assert_eq!(with_namespace.fullname(), "namespace/name");
         assert_eq!(without_namespace.fullname(), "name");
     }
+
+    #[test]
+    fn check_status_json() {
+        let status = RepoStatus {
+            operation: Some(git2::RepositoryState::RebaseMerge),
+            empty: false,
+            remotes: vec![String::from("origin")],
+            head: Some(String::from("main")),
+            detached_head: None,
+            changes: Some(RepoChanges {
+                files_new: 1,
+                files_modified: 0,
+                files_deleted: 2,
+            }),
+            worktrees: 0,
+            submodules: Some(vec![(String::from("lib"), SubmoduleStatus::OutOfDate)]),
+            branches: vec![
+                (
+                    String::from("main"),
+                    Some((
+                        String::from("origin/main"),
+                        RemoteTrackingStatus::Diverged(3, 1),
+                    )),
+                ),
+                (String::from("topic"), None),
+            ],
+            unpushed_submodules: vec![(String::from("lib"), String::from("abc1234"))],
+            jj: false,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "operation": "rebase",
+                "empty": false,
+                "remotes": ["origin"],
+                "head": "main",
+                "detached_head": null,
+                "changes": {"files_new": 1, "files_modified": 0, "files_deleted": 2},
+                "worktrees": 0,
+                "submodules": [{"name": "lib", "status": "out_of_date"}],
+                "branches": [
+                    {
+                        "name": "main",
+                        "upstream": {"name": "origin/main", "ahead": 3, "behind": 1},
+                    },
+                    {"name": "topic", "upstream": null},
+                ],
+                "unpushed_submodules": [{"name": "lib", "commit": "abc1234"}],
+                "jj": false,
+            })
+        );
+    }
 }
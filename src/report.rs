@@ -0,0 +1,254 @@
+//! A static HTML report about all repositories of a configuration, see
+//! `grm repos report`
+//!
+//! The report is a single self-contained `index.html`, so it can be published
+//! by any web server, e.g. from a nightly job.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use super::config;
+use super::notify;
+use super::path;
+use super::repo;
+use super::state;
+use super::table;
+
+pub const REPORT_FILE_NAME: &str = "index.html";
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.4em; text-align: left; vertical-align: top; }
+th { background: #eee; }
+tr.warning td:first-child { border-left: 0.4em solid #e0a000; }
+tr.error td:first-child { border-left: 0.4em solid #d00000; }
+td { white-space: pre-line; }
+p.root { color: #666; font-family: monospace; }
+";
+
+struct ReportRow {
+    name: String,
+    status: String,
+    branches: String,
+    warnings: Vec<String>,
+    error: bool,
+    last_sync: Option<u64>,
+}
+
+/// Escapes text for use in HTML element content and attribute values
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats a unix timestamp as date and time in UTC, e.g. "2024-03-01 12:00 UTC"
+fn format_timestamp(timestamp: u64) -> String {
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+
+    // Converts days since the epoch to a date in the proleptic Gregorian
+    // calendar, see http://howardhinnant.github.io/date_algorithms.html
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+fn get_row(root_path: &Path, repo: &repo::Repo, tree_state: &state::TreeState) -> ReportRow {
+    let mut row = ReportRow {
+        name: repo.fullname(),
+        status: String::new(),
+        branches: String::new(),
+        warnings: Vec::new(),
+        error: false,
+        last_sync: tree_state.last_sync(&repo.fullname()),
+    };
+
+    let repo_path = root_path.join(repo.directory());
+    if !repo_path.exists() {
+        row.status = String::from("Missing");
+        row.warnings
+            .push(String::from("Repository does not exist. Run sync?"));
+        row.error = true;
+        return row;
+    }
+
+    let status = repo::RepoHandle::open(&repo_path, repo.worktree_setup)
+        .map_err(|error| format!("Opening repository failed: {error}"))
+        .and_then(|repo_handle| repo_handle.status(repo.worktree_setup));
+
+    match status {
+        Ok(status) => {
+            row.status = if repo.worktree_setup {
+                String::from("Worktree setup")
+            } else {
+                table::format_changes(&status)
+            };
+            row.branches = table::format_branches(&status);
+            row.warnings = status.check_failures();
+        }
+        Err(error) => {
+            row.status = String::from("Error");
+            row.warnings.push(error);
+            row.error = true;
+        }
+    }
+
+    row
+}
+
+fn write_table(out: &mut String, rows: &[ReportRow]) {
+    out.push_str(
+        "<table>\n<tr><th>Repo</th><th>Status</th><th>Branches</th><th>Warnings</th>\
+         <th>Last sync</th></tr>\n",
+    );
+    for row in rows {
+        let class = if row.error {
+            "error"
+        } else if !row.warnings.is_empty() {
+            "warning"
+        } else {
+            "ok"
+        };
+        writeln!(
+            out,
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            class,
+            escape(&row.name),
+            escape(&row.status),
+            escape(&row.branches),
+            escape(&row.warnings.join("\n")),
+            row.last_sync
+                .map_or_else(|| String::from("Never"), format_timestamp),
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n");
+}
+
+/// Returns the HTML of the report, with one table per tree, headed by the name
+/// (or root) of the tree
+pub fn get_html_report(config: config::Config, generated: u64) -> Result<String, String> {
+    let mut sections = String::new();
+    let mut repo_count = 0;
+    let mut warning_count = 0;
+
+    for mut tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        let mut errors = Vec::new();
+
+        let tree_state = state::read_state(&tree.state_root()).unwrap_or_else(|error| {
+            errors.push(error);
+            state::TreeState::default()
+        });
+
+        let rows: Vec<ReportRow> = tree
+            .repos
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| get_row(&root_path, &repo.into_repo(), &tree_state))
+            .collect();
+
+        repo_count += rows.len();
+        warning_count += rows.iter().filter(|row| !row.warnings.is_empty()).count();
+
+        writeln!(sections, "<h2>{}</h2>", escape(tree.label())).unwrap();
+        if tree.name.is_some() {
+            writeln!(sections, "<p class=\"root\">{}</p>", escape(&tree.root)).unwrap();
+        }
+        for error in errors {
+            writeln!(sections, "<p class=\"error\">{}</p>", escape(&error)).unwrap();
+        }
+        write_table(&mut sections, &rows);
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Repository report</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>Repository report</h1>\n<p>{} repositories, {} with warnings. \
+         Generated {}.</p>",
+        repo_count,
+        warning_count,
+        format_timestamp(generated)
+    )
+    .unwrap();
+    out.push_str(&sections);
+    out.push_str("</body>\n</html>\n");
+
+    Ok(out)
+}
+
+/// Writes the report to `directory`, creating it if necessary, and returns the
+/// path of the report
+pub fn write_html_report(config: config::Config, directory: &Path) -> Result<PathBuf, String> {
+    let html = get_html_report(config, notify::now()?)?;
+
+    std::fs::create_dir_all(directory).map_err(|error| {
+        format!(
+            "Error creating directory \"{}\": {}",
+            directory.display(),
+            error
+        )
+    })?;
+
+    let path = directory.join(REPORT_FILE_NAME);
+    std::fs::write(&path, html)
+        .map_err(|error| format!("Error writing report \"{}\": {}", path.display(), error))?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_escape() {
+        assert_eq!(escape("main"), "main");
+        assert_eq!(
+            escape("<script>\"a\" & 'b'</script>"),
+            "&lt;script&gt;&quot;a&quot; &amp; &#39;b&#39;&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn check_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1_709_294_400), "2024-03-01 12:00 UTC");
+        assert_eq!(format_timestamp(1_735_689_599), "2024-12-31 23:59 UTC");
+    }
+}
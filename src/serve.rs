@@ -0,0 +1,636 @@
+//! A JSON-RPC server on a unix socket, see `grm serve`
+//!
+//! Editor plugins and status bars can query a running server instead of
+//! starting a grm process for every call. Every line sent to the socket is a
+//! JSON-RPC 2.0 request, and every request (except notifications) gets a single
+//! line with the response. The configuration is read for every request, so
+//! changes to it are picked up without restarting the server.
+//!
+//! Methods:
+//!
+//! * `repos.list`: All repositories of the configuration
+//! * `repos.status`: The status of all repositories, or only of `repo`
+//! * `repos.sync`: Syncs the configuration, returns the same report as
+//!   notifications
+//! * `worktrees.list`: The worktrees of `repo`
+//! * `worktrees.add`: Adds the worktree `name` to `repo`, optionally tracking
+//!   `track` (`<remote>/<branch>`)
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::config;
+use super::lock;
+use super::notify;
+use super::output::*;
+use super::path;
+use super::repo;
+use super::tree;
+use super::worktree;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Everything that grm itself reports as an error, e.g. a missing repository
+const SERVER_ERROR: i64 = -32000;
+
+pub struct ServeOptions {
+    pub config_path: String,
+    pub socket_path: PathBuf,
+    pub lock_policy: lock::LockPolicy,
+}
+
+/// The default socket, next to the lock file of the daemon
+pub fn default_socket_path() -> PathBuf {
+    let dir = match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::temp_dir(),
+    };
+    dir.join("grm.sock")
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Requests without an ID are notifications and do not get a response. An
+    /// ID of `null` is still an ID.
+    #[serde(default, deserialize_with = "deserialize_id")]
+    id: Option<serde_json::Value>,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+/// Without this, serde treats `"id": null` the same as a missing ID
+fn deserialize_id<'de, D>(deserializer: D) -> Result<Option<serde_json::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    serde_json::Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: String) -> Self {
+        Self { code, message }
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self::new(SERVER_ERROR, message)
+    }
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatusParams {
+    repo: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RepoParams {
+    repo: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddWorktreeParams {
+    repo: String,
+    name: String,
+    track: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RepoInfo {
+    name: String,
+    path: String,
+    worktree_setup: bool,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct StatusInfo {
+    head: Option<String>,
+    /// e.g. "Rebase in progress"
+    operation: Option<String>,
+    dirty: bool,
+    ahead: usize,
+    behind: usize,
+    worktrees: usize,
+    /// See `grm repos status --check`
+    check_failures: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RepoStatusInfo {
+    name: String,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<StatusInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WorktreeInfo {
+    name: String,
+    path: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+fn parse_params<T>(params: serde_json::Value) -> Result<T, RpcError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    // Omitted params are the same as no params
+    let params = if params.is_null() {
+        serde_json::Value::Object(serde_json::Map::new())
+    } else {
+        params
+    };
+    serde_json::from_value(params)
+        .map_err(|error| RpcError::new(INVALID_PARAMS, format!("Invalid params: {error}")))
+}
+
+fn to_result<T: Serialize>(value: T) -> Result<serde_json::Value, RpcError> {
+    serde_json::to_value(value)
+        .map_err(|error| RpcError::from(format!("Failed serializing result: {error}")))
+}
+
+/// All repositories of the configuration, with their paths
+fn configured_repos(config_path: &str) -> Result<Vec<(PathBuf, repo::Repo)>, String> {
+    let config: config::Config = config::read_config(config_path)?;
+
+    let mut repos = Vec::new();
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            repos.push((root_path.join(repo.directory()), repo));
+        }
+    }
+    Ok(repos)
+}
+
+fn find_repo(config_path: &str, name: &str) -> Result<(PathBuf, repo::Repo), String> {
+    configured_repos(config_path)?
+        .into_iter()
+        .find(|(_, repo)| repo.fullname() == name)
+        .ok_or_else(|| format!("Repository \"{name}\" is not part of the configuration"))
+}
+
+fn repo_status(repo_path: &Path, repo: &repo::Repo) -> Result<StatusInfo, String> {
+    if !repo_path.exists() {
+        return Err(String::from("Repository does not exist. Run sync?"));
+    }
+
+    let repo_handle = repo::RepoHandle::open(repo_path, repo.worktree_setup)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+    let status = repo_handle.status(repo.worktree_setup)?;
+
+    let (ahead, behind) = status.head_ahead_behind();
+    Ok(StatusInfo {
+        head: status.head.clone(),
+        operation: status.operation_description(),
+        dirty: status.dirty(),
+        ahead,
+        behind,
+        worktrees: status.worktrees,
+        check_failures: status.check_failures(),
+    })
+}
+
+fn list_repos(config_path: &str) -> Result<Vec<RepoInfo>, String> {
+    Ok(configured_repos(config_path)?
+        .into_iter()
+        .map(|(repo_path, repo)| RepoInfo {
+            name: repo.fullname(),
+            path: path::path_as_string(&repo_path),
+            worktree_setup: repo.worktree_setup,
+            tags: repo.tags,
+        })
+        .collect())
+}
+
+fn repo_statuses(config_path: &str, params: StatusParams) -> Result<Vec<RepoStatusInfo>, String> {
+    let repos = match params.repo {
+        Some(name) => vec![find_repo(config_path, &name)?],
+        None => configured_repos(config_path)?,
+    };
+
+    Ok(repos
+        .into_iter()
+        .map(|(repo_path, repo)| {
+            let (status, error) = match repo_status(&repo_path, &repo) {
+                Ok(status) => (Some(status), None),
+                Err(error) => (None, Some(error)),
+            };
+            RepoStatusInfo {
+                name: repo.fullname(),
+                path: path::path_as_string(&repo_path),
+                status,
+                error,
+            }
+        })
+        .collect())
+}
+
+fn sync(config_path: &str, lock_policy: lock::LockPolicy) -> Result<notify::Report, String> {
+    let config: config::Config = config::read_config(config_path)?;
+    let notify_config = config.notify().cloned();
+
+    // Nobody can be asked on the server, so roots with unrelated content fail
+    // the sync
+    let report = match tree::sync_trees(
+        config,
+        &tree::SyncOptions {
+            lock_policy,
+            existing_root: tree::ExistingRoot::Fail,
+            ..Default::default()
+        },
+    ) {
+        Ok(result) => notify::Report::new("sync", result)?,
+        Err(error) => notify::Report::from_error("sync", error)?,
+    };
+
+    if let Some(notify_config) = notify_config {
+        for error in notify::send(&report, &notify_config) {
+            print_error(&error);
+        }
+    }
+
+    Ok(report)
+}
+
+fn worktree_repo(config_path: &str, name: &str) -> Result<PathBuf, String> {
+    let (repo_path, repo) = find_repo(config_path, name)?;
+    if !repo.worktree_setup {
+        return Err(format!(
+            "Repository \"{name}\" does not use a worktree setup"
+        ));
+    }
+    if !repo_path.exists() {
+        return Err(format!("Repository \"{name}\" does not exist. Run sync?"));
+    }
+    Ok(repo_path)
+}
+
+fn list_worktrees(config_path: &str, params: RepoParams) -> Result<Vec<WorktreeInfo>, String> {
+    let repo_path = worktree_repo(config_path, &params.repo)?;
+    let repo_handle = repo::RepoHandle::open(&repo_path, true)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+
+    Ok(repo_handle
+        .get_worktrees()?
+        .into_iter()
+        .map(|worktree| WorktreeInfo {
+            name: worktree.name().to_string(),
+            path: path::path_as_string(&repo_path.join(worktree.name())),
+            warnings: Vec::new(),
+        })
+        .collect())
+}
+
+fn add_worktree(
+    config_path: &str,
+    params: AddWorktreeParams,
+    lock_policy: lock::LockPolicy,
+) -> Result<WorktreeInfo, String> {
+    let repo_path = worktree_repo(config_path, &params.repo)?;
+
+    let track = match &params.track {
+        Some(track) => match track.split_once('/') {
+            Some((remote_name, branch_name))
+                if !remote_name.is_empty() && !branch_name.is_empty() =>
+            {
+                Some((remote_name, branch_name))
+            }
+            _ => {
+                return Err(String::from(
+                    "Tracking branch needs to match the pattern <remote>/<branch_name>",
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let _lock = lock::lock_repo(&repo_path, lock_policy)?;
+    let warnings = worktree::add_worktree(&repo_path, &params.name, track, false)?;
+
+    Ok(WorktreeInfo {
+        path: path::path_as_string(&repo_path.join(&params.name)),
+        name: params.name,
+        warnings: warnings.unwrap_or_default(),
+    })
+}
+
+fn call(
+    method: &str,
+    params: serde_json::Value,
+    config_path: &str,
+    lock_policy: lock::LockPolicy,
+) -> Result<serde_json::Value, RpcError> {
+    match method {
+        "repos.list" => to_result(list_repos(config_path)?),
+        "repos.status" => to_result(repo_statuses(config_path, parse_params(params)?)?),
+        "repos.sync" => to_result(sync(config_path, lock_policy)?),
+        "worktrees.list" => to_result(list_worktrees(config_path, parse_params(params)?)?),
+        "worktrees.add" => to_result(add_worktree(
+            config_path,
+            parse_params(params)?,
+            lock_policy,
+        )?),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Method \"{method}\" not found"),
+        )),
+    }
+}
+
+/// Handles a single line of input, returns the response line if there is one
+fn handle_line(line: &str, config_path: &str, lock_policy: lock::LockPolicy) -> Option<String> {
+    let (id, outcome) = match serde_json::from_str::<serde_json::Value>(line) {
+        Err(error) => (
+            serde_json::Value::Null,
+            Err(RpcError::new(PARSE_ERROR, format!("Parse error: {error}"))),
+        ),
+        Ok(value) => {
+            let id = value.get("id").cloned().unwrap_or_default();
+            match serde_json::from_value::<Request>(value) {
+                Err(error) => (
+                    id,
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        format!("Invalid request: {error}"),
+                    )),
+                ),
+                Ok(request) if request.jsonrpc != "2.0" => (
+                    id,
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        String::from("Only JSON-RPC 2.0 is supported"),
+                    )),
+                ),
+                Ok(request) => {
+                    let outcome = call(&request.method, request.params, config_path, lock_policy);
+                    match request.id {
+                        Some(id) => (id, outcome),
+                        None => {
+                            if let Err(error) = outcome {
+                                print_error(&format!("{}: {}", request.method, error.message));
+                            }
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+
+    let response = Response {
+        jsonrpc: "2.0",
+        id,
+        result,
+        error,
+    };
+
+    // Serializing plain values cannot fail
+    Some(serde_json::to_string(&response).expect("Response is valid JSON"))
+}
+
+fn handle_connection<S>(
+    stream: S,
+    config_path: &str,
+    lock_policy: lock::LockPolicy,
+) -> Result<(), String>
+where
+    S: std::io::Read + Write,
+{
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|error| format!("Failed reading request: {error}"))?;
+        if read == 0 {
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle_line(line.trim(), config_path, lock_policy) {
+            let stream = reader.get_mut();
+            writeln!(stream, "{response}")
+                .and_then(|()| stream.flush())
+                .map_err(|error| format!("Failed writing response: {error}"))?;
+        }
+    }
+}
+
+/// Removes a socket that was left behind by a server that is not running anymore
+#[cfg(unix)]
+fn remove_stale_socket(socket_path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    let Ok(metadata) = std::fs::symlink_metadata(socket_path) else {
+        return Ok(());
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(format!(
+            "\"{}\" exists and is not a socket",
+            socket_path.display()
+        ));
+    }
+
+    if UnixStream::connect(socket_path).is_ok() {
+        return Err(format!(
+            "Another server is already listening on \"{}\"",
+            socket_path.display()
+        ));
+    }
+
+    std::fs::remove_file(socket_path)
+        .map_err(|error| format!("Failed removing stale socket: {error}"))
+}
+
+/// Listens on a socket in `private_dir` that only we can connect to, and moves
+/// it to `socket_path`
+#[cfg(unix)]
+fn bind_private(
+    private_dir: &Path,
+    socket_path: &Path,
+) -> Result<std::os::unix::net::UnixListener, String> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+
+    let private_socket = private_dir.join("grm.sock");
+    let listener = UnixListener::bind(&private_socket).map_err(|error| {
+        format!(
+            "Failed to listen on \"{}\": {}",
+            socket_path.display(),
+            error
+        )
+    })?;
+
+    let result = std::fs::set_permissions(&private_socket, std::fs::Permissions::from_mode(0o600))
+        .map_err(|error| format!("Failed setting socket permissions: {error}"))
+        .and_then(|()| {
+            std::fs::rename(&private_socket, socket_path).map_err(|error| {
+                format!(
+                    "Failed to listen on \"{}\": {}",
+                    socket_path.display(),
+                    error
+                )
+            })
+        });
+    if let Err(error) = result {
+        let _ = std::fs::remove_file(&private_socket);
+        return Err(error);
+    }
+    Ok(listener)
+}
+
+#[cfg(unix)]
+pub fn run(options: &ServeOptions) -> Result<(), String> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    remove_stale_socket(&options.socket_path)?;
+
+    // Whoever can connect can modify repositories. The socket is created with
+    // the permissions of the umask, so it is first created in a directory only
+    // we can access, and only moved into place once nobody else can connect.
+    let parent = match options.socket_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let private_dir = parent.join(format!(".grm-serve-{}", std::process::id()));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(|error| format!("Failed creating \"{}\": {}", private_dir.display(), error))?;
+
+    let listener = bind_private(&private_dir, &options.socket_path);
+    if let Err(error) = std::fs::remove_dir(&private_dir) {
+        print_warning(&format!(
+            "Failed removing \"{}\": {}",
+            private_dir.display(),
+            error
+        ));
+    }
+    let listener = listener?;
+
+    print_action(&format!(
+        "Listening on \"{}\"",
+        options.socket_path.display()
+    ));
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let config_path = options.config_path.clone();
+                let lock_policy = options.lock_policy;
+                // A long sync must not block queries, so every connection gets
+                // its own thread. Concurrent changes are serialized by the locks.
+                std::thread::spawn(move || {
+                    if let Err(error) = handle_connection(stream, &config_path, lock_policy) {
+                        print_error(&error);
+                    }
+                });
+            }
+            Err(error) => print_error(&format!("Accepting connection failed: {error}")),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run(_options: &ServeOptions) -> Result<(), String> {
+    Err(String::from("grm serve is only supported on unix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(line: &str) -> serde_json::Value {
+        serde_json::from_str(
+            &handle_line(line, "/nonexistent/config.toml", lock::LockPolicy::NoWait).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn check_handle_line() {
+        assert_eq!(
+            respond("{").pointer("/error/code"),
+            Some(&PARSE_ERROR.into())
+        );
+
+        let response = respond(r#"{"jsonrpc": "2.0", "id": 1, "method": "unknown"}"#);
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert!(response.get("result").is_none());
+
+        let response = respond(r#"{"jsonrpc": "1.0", "id": "a", "method": "repos.list"}"#);
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let response = respond(
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "worktrees.list", "params": {"name": "x"}}"#,
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+        let response = respond(r#"{"jsonrpc": "2.0", "id": 3, "method": "repos.list"}"#);
+        assert_eq!(response["error"]["code"], SERVER_ERROR);
+
+        // A null ID is an ID, unlike a missing one
+        let response = respond(r#"{"jsonrpc": "2.0", "id": null, "method": "unknown"}"#);
+        assert!(response["id"].is_null());
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        // Notifications do not get a response
+        assert_eq!(
+            handle_line(
+                r#"{"jsonrpc": "2.0", "method": "repos.list"}"#,
+                "/nonexistent/config.toml",
+                lock::LockPolicy::NoWait
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Shell integration, see `grm shell-init`
+//!
+//! A process cannot change the working directory of the shell that started it.
+//! So the shell integration wraps `grm` in a shell function that passes the path
+//! of a temporary file in `GRM_CD_FILE`. If `grm` wants the shell to change into
+//! another directory, it writes that directory into the file, and the shell
+//! function does the `cd` after `grm` exits.
+
+use std::path::Path;
+
+use super::path;
+
+/// The environment variable containing the file to write the target directory to
+pub const CD_FILE_ENV: &str = "GRM_CD_FILE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const POSIX_INIT: &str = r#"grm() {
+    local grm_cd_file grm_status
+    grm_cd_file="$(mktemp)" || return
+    GRM_CD_FILE="$grm_cd_file" command grm "$@"
+    grm_status=$?
+    if [ -s "$grm_cd_file" ]; then
+        cd -- "$(cat -- "$grm_cd_file")" || grm_status=$?
+    fi
+    rm -f -- "$grm_cd_file"
+    return $grm_status
+}
+"#;
+
+const FISH_INIT: &str = r#"function grm --wraps grm
+    set -l grm_cd_file (mktemp); or return
+    GRM_CD_FILE=$grm_cd_file command grm $argv
+    set -l grm_status $status
+    if test -s $grm_cd_file
+        cd (cat $grm_cd_file); or set grm_status $status
+    end
+    rm -f $grm_cd_file
+    return $grm_status
+end
+"#;
+
+/// Returns the code that has to be evaluated by the shell to set up the integration
+pub fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash | Shell::Zsh => POSIX_INIT,
+        Shell::Fish => FISH_INIT,
+    }
+}
+
+/// Asks the shell to change into `directory` after grm exits
+///
+/// Returns `Ok(false)` if the shell integration is not set up.
+pub fn request_cd(directory: &Path) -> Result<bool, String> {
+    let cd_file = match std::env::var_os(CD_FILE_ENV) {
+        Some(cd_file) if !cd_file.is_empty() => cd_file,
+        _ => return Ok(false),
+    };
+
+    std::fs::write(&cd_file, path::path_as_utf8(directory)?)
+        .map_err(|error| format!("Failed writing to ${CD_FILE_ENV}: {error}"))?;
+
+    Ok(true)
+}
@@ -0,0 +1,303 @@
+//! The state that sync applied last, to tell intended changes from local ones
+//!
+//! Sync compares three things for every repository: the configuration (what is
+//! wanted), the state that was applied by the last sync, and the repository
+//! itself. Only differences between the configuration and the last-applied
+//! state are changes that are meant to be applied. Differences between the
+//! last-applied state and the repository were made by hand and are kept, e.g. a
+//! remote that was added locally or a remote URL that was changed on purpose.
+//!
+//! The state is kept in `.grm-state.toml` in the root of each tree.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+pub const STATE_FILE_NAME: &str = ".grm-state.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppliedRemote {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppliedRepo {
+    /// The name including the namespace
+    pub name: String,
+    #[serde(default)]
+    pub remotes: Vec<AppliedRemote>,
+    /// When the repository was synced successfully the last time, as a unix
+    /// timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_sync: Option<u64>,
+    /// When the remotes of the repository were fetched successfully the last
+    /// time, as a unix timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fetch: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TreeState {
+    #[serde(default)]
+    pub repos: Vec<AppliedRepo>,
+}
+
+impl TreeState {
+    /// The remotes that were applied to the repository `name`, if it was
+    /// synced before
+    pub fn remotes(&self, name: &str) -> Option<&[AppliedRemote]> {
+        self.repos
+            .iter()
+            .find(|repo| repo.name == name)
+            .map(|repo| repo.remotes.as_slice())
+    }
+
+    pub fn set_remotes(&mut self, name: &str, remotes: Vec<AppliedRemote>) {
+        self.repo_mut(name).remotes = remotes;
+    }
+
+    pub fn last_sync(&self, name: &str) -> Option<u64> {
+        self.repos
+            .iter()
+            .find(|repo| repo.name == name)
+            .and_then(|repo| repo.last_sync)
+    }
+
+    pub fn set_last_sync(&mut self, name: &str, timestamp: u64) {
+        self.repo_mut(name).last_sync = Some(timestamp);
+    }
+
+    pub fn last_fetch(&self, name: &str) -> Option<u64> {
+        self.repos
+            .iter()
+            .find(|repo| repo.name == name)
+            .and_then(|repo| repo.last_fetch)
+    }
+
+    pub fn set_last_fetch(&mut self, name: &str, timestamp: u64) {
+        self.repo_mut(name).last_fetch = Some(timestamp);
+    }
+
+    /// When the repository was last brought up to date, by either sync or fetch
+    pub fn last_update(&self, name: &str) -> Option<u64> {
+        self.last_sync(name).max(self.last_fetch(name))
+    }
+
+    fn repo_mut(&mut self, name: &str) -> &mut AppliedRepo {
+        let index = match self.repos.iter().position(|repo| repo.name == name) {
+            Some(index) => index,
+            None => {
+                self.repos.push(AppliedRepo {
+                    name: name.to_string(),
+                    remotes: Vec::new(),
+                    last_sync: None,
+                    last_fetch: None,
+                });
+                self.repos.len() - 1
+            }
+        };
+        &mut self.repos[index]
+    }
+
+    /// Drops all repositories that are not in `names` anymore
+    pub fn retain(&mut self, names: &[String]) {
+        self.repos.retain(|repo| names.contains(&repo.name));
+        self.repos.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// Reads the state of the tree at `root_path`. A missing file is an empty state.
+pub fn read_state(root_path: &Path) -> Result<TreeState, String> {
+    let path = root_path.join(STATE_FILE_NAME);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(error) => match error.kind() {
+            std::io::ErrorKind::NotFound => return Ok(TreeState::default()),
+            _ => {
+                return Err(format!(
+                    "Error reading state file \"{}\": {}",
+                    path.display(),
+                    error
+                ))
+            }
+        },
+    };
+
+    toml::from_str(&content)
+        .map_err(|error| format!("Error parsing state file \"{}\": {}", path.display(), error))
+}
+
+pub fn write_state(root_path: &Path, state: &TreeState) -> Result<(), String> {
+    let path = root_path.join(STATE_FILE_NAME);
+    let content =
+        toml::to_string(state).map_err(|error| format!("Error serializing state: {error}"))?;
+    std::fs::write(&path, content)
+        .map_err(|error| format!("Error writing state file \"{}\": {}", path.display(), error))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RemoteChange {
+    Add(AppliedRemote),
+    SetUrl(AppliedRemote),
+    Delete(String),
+}
+
+/// Returns the changes that bring the `actual` remotes of a repository in line
+/// with the `desired` ones from the configuration
+///
+/// With `applied` set to `None` (the repository was never synced), the
+/// configuration wins for all configured remotes, but remotes that are not part
+/// of the configuration are kept.
+pub fn plan_remotes(
+    desired: &[AppliedRemote],
+    applied: Option<&[AppliedRemote]>,
+    actual: &[AppliedRemote],
+) -> Vec<RemoteChange> {
+    let applied = applied.unwrap_or_default();
+    let find = |remotes: &[AppliedRemote], name: &str| {
+        remotes
+            .iter()
+            .find(|remote| remote.name == name)
+            .map(|remote| remote.url.clone())
+    };
+
+    let mut changes = Vec::new();
+
+    for remote in desired {
+        // Unchanged in the configuration since the last sync, so any difference
+        // was made locally
+        if find(applied, &remote.name).as_ref() == Some(&remote.url) {
+            continue;
+        }
+        match find(actual, &remote.name) {
+            None => changes.push(RemoteChange::Add(remote.clone())),
+            Some(url) if url != remote.url => changes.push(RemoteChange::SetUrl(remote.clone())),
+            Some(_) => {}
+        }
+    }
+
+    for remote in actual {
+        // Only remotes that were removed from the configuration are deleted
+        if find(desired, &remote.name).is_none() && find(applied, &remote.name).is_some() {
+            changes.push(RemoteChange::Delete(remote.name.clone()));
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(name: &str, url: &str) -> AppliedRemote {
+        AppliedRemote {
+            name: name.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn check_plan_remotes() {
+        let origin = remote("origin", "https://example.com/a.git");
+        let moved = remote("origin", "https://example.com/b.git");
+        let local = remote("local", "/srv/a.git");
+
+        // Never synced: configured remotes are set up, others are kept
+        assert_eq!(
+            plan_remotes(
+                std::slice::from_ref(&origin),
+                None,
+                std::slice::from_ref(&local)
+            ),
+            vec![RemoteChange::Add(origin.clone())]
+        );
+        assert_eq!(
+            plan_remotes(
+                std::slice::from_ref(&origin),
+                None,
+                std::slice::from_ref(&moved)
+            ),
+            vec![RemoteChange::SetUrl(origin.clone())]
+        );
+
+        // Local changes are kept
+        let applied = [origin.clone()];
+        assert_eq!(
+            plan_remotes(
+                std::slice::from_ref(&origin),
+                Some(&applied),
+                std::slice::from_ref(&moved)
+            ),
+            vec![]
+        );
+        assert_eq!(
+            plan_remotes(
+                std::slice::from_ref(&origin),
+                Some(&applied),
+                &[origin.clone(), local]
+            ),
+            vec![]
+        );
+        assert_eq!(
+            plan_remotes(std::slice::from_ref(&origin), Some(&applied), &[]),
+            vec![]
+        );
+
+        // Changes in the configuration are applied
+        assert_eq!(
+            plan_remotes(
+                std::slice::from_ref(&moved),
+                Some(&applied),
+                std::slice::from_ref(&origin)
+            ),
+            vec![RemoteChange::SetUrl(moved)]
+        );
+        assert_eq!(
+            plan_remotes(&[], Some(&applied), &[origin]),
+            vec![RemoteChange::Delete(String::from("origin"))]
+        );
+    }
+
+    #[test]
+    fn check_tree_state() {
+        let mut state = TreeState::default();
+        state.set_remotes("b", vec![remote("origin", "x")]);
+        state.set_remotes("a", Vec::new());
+        state.set_remotes("b", vec![remote("origin", "y")]);
+        assert_eq!(state.remotes("b"), Some([remote("origin", "y")].as_slice()));
+        assert_eq!(state.remotes("c"), None);
+
+        state.set_last_sync("b", 1000);
+        state.set_last_sync("c", 2000);
+        assert_eq!(state.last_sync("a"), None);
+        assert_eq!(state.last_sync("b"), Some(1000));
+        assert_eq!(state.remotes("b"), Some([remote("origin", "y")].as_slice()));
+        assert_eq!(state.remotes("c"), Some([].as_slice()));
+
+        state.set_last_fetch("b", 3000);
+        state.set_last_fetch("d", 500);
+        assert_eq!(state.last_fetch("b"), Some(3000));
+        assert_eq!(state.last_update("a"), None);
+        assert_eq!(state.last_update("b"), Some(3000));
+        assert_eq!(state.last_update("c"), Some(2000));
+        assert_eq!(state.last_update("d"), Some(500));
+        assert_eq!(state.last_sync("d"), None);
+
+        state.retain(&[String::from("a"), String::from("b")]);
+        assert_eq!(
+            state
+                .repos
+                .iter()
+                .map(|repo| repo.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        state.retain(&[String::from("b")]);
+        assert_eq!(state.repos.len(), 1);
+    }
+}
@@ -0,0 +1,258 @@
+//! Statistics about all repositories of a configuration, see `grm repos stats`
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+
+use super::config;
+use super::path;
+use super::repo;
+
+#[derive(Debug, Serialize)]
+pub struct RepoStats {
+    pub name: String,
+    pub namespace: Option<String>,
+    /// Disk usage in bytes
+    pub size: u64,
+    pub worktrees: usize,
+    pub hosts: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamespaceStats {
+    pub namespace: Option<String>,
+    pub repos: usize,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostStats {
+    pub host: String,
+    pub repos: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub repo_count: usize,
+    pub worktree_count: usize,
+    pub total_size: u64,
+    pub namespaces: Vec<NamespaceStats>,
+    pub hosts: Vec<HostStats>,
+    /// All repositories, largest first
+    pub repos: Vec<RepoStats>,
+}
+
+/// Returns the size of all files below `path` in bytes. Symlinks are not followed.
+pub fn disk_usage(path: &Path) -> Result<u64, String> {
+    let metadata = std::fs::symlink_metadata(path)
+        .map_err(|error| format!("Failed reading \"{}\": {}", path.display(), error))?;
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)
+        .map_err(|error| format!("Failed reading \"{}\": {}", path.display(), error))?
+    {
+        let entry = entry.map_err(|error| format!("Error accessing directory: {error}"))?;
+        size += disk_usage(&entry.path())?;
+    }
+    Ok(size)
+}
+
+/// Formats a size in bytes for humans, e.g. "1.5 MiB"
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{value:.1} {unit}")
+}
+
+pub fn get_stats(config: config::Config) -> Result<(Stats, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let mut repos = Vec::new();
+
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            let repo_path = root_path.join(repo.directory());
+
+            if !repo_path.exists() {
+                warnings.push(format!(
+                    "{}: Repository does not exist. Run sync?",
+                    repo.fullname()
+                ));
+                continue;
+            }
+
+            let size = match disk_usage(&repo_path) {
+                Ok(size) => size,
+                Err(error) => {
+                    warnings.push(format!("{}: {}", repo.fullname(), error));
+                    continue;
+                }
+            };
+
+            let worktrees = match repo::RepoHandle::open(&repo_path, repo.worktree_setup) {
+                Ok(repo_handle) => match repo_handle.get_worktrees() {
+                    Ok(worktrees) => worktrees.len(),
+                    Err(error) => {
+                        warnings.push(format!(
+                            "{}: Failed getting worktrees: {}",
+                            repo.fullname(),
+                            error
+                        ));
+                        0
+                    }
+                },
+                Err(error) => {
+                    warnings.push(format!(
+                        "{}: Opening repository failed: {}",
+                        repo.fullname(),
+                        error
+                    ));
+                    0
+                }
+            };
+
+            let mut hosts: Vec<String> = repo
+                .remotes
+                .iter()
+                .flatten()
+                .filter_map(|remote| repo::remote_host(&remote.url))
+                .collect();
+            hosts.sort();
+            hosts.dedup();
+
+            repos.push(RepoStats {
+                name: repo.fullname(),
+                namespace: repo.namespace,
+                size,
+                worktrees,
+                hosts,
+            });
+        }
+    }
+
+    repos.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+    let mut namespaces: BTreeMap<Option<String>, (usize, u64)> = BTreeMap::new();
+    let mut hosts: BTreeMap<String, usize> = BTreeMap::new();
+    for repo in &repos {
+        let entry = namespaces.entry(repo.namespace.clone()).or_default();
+        entry.0 += 1;
+        entry.1 += repo.size;
+
+        for host in &repo.hosts {
+            *hosts.entry(host.clone()).or_default() += 1;
+        }
+    }
+
+    let mut namespaces: Vec<NamespaceStats> = namespaces
+        .into_iter()
+        .map(|(namespace, (repos, size))| NamespaceStats {
+            namespace,
+            repos,
+            size,
+        })
+        .collect();
+    namespaces.sort_by_key(|namespace| std::cmp::Reverse(namespace.size));
+
+    let mut hosts: Vec<HostStats> = hosts
+        .into_iter()
+        .map(|(host, repos)| HostStats { host, repos })
+        .collect();
+    hosts.sort_by_key(|host| std::cmp::Reverse(host.repos));
+
+    Ok((
+        Stats {
+            repo_count: repos.len(),
+            worktree_count: repos.iter().map(|repo| repo.worktrees).sum(),
+            total_size: repos.iter().map(|repo| repo.size).sum(),
+            namespaces,
+            hosts,
+            repos,
+        },
+        warnings,
+    ))
+}
+
+fn new_table(header: &[&str]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(header.iter().map(Cell::new));
+    table
+}
+
+/// Returns the tables to show, only listing the `top` largest repositories
+pub fn get_stats_tables(stats: &Stats, top: usize) -> Vec<Table> {
+    let mut summary = new_table(&["Repositories", "Worktrees", "Disk usage"]);
+    summary.add_row([
+        stats.repo_count.to_string(),
+        stats.worktree_count.to_string(),
+        format_size(stats.total_size),
+    ]);
+
+    let mut namespaces = new_table(&["Namespace", "Repositories", "Disk usage"]);
+    for namespace in &stats.namespaces {
+        namespaces.add_row([
+            namespace.namespace.as_deref().unwrap_or("-").to_string(),
+            namespace.repos.to_string(),
+            format_size(namespace.size),
+        ]);
+    }
+
+    let mut hosts = new_table(&["Remote host", "Repositories"]);
+    for host in &stats.hosts {
+        hosts.add_row([host.host.clone(), host.repos.to_string()]);
+    }
+
+    let mut largest = new_table(&["Repo", "Disk usage", "Worktrees"]);
+    for repo in stats.repos.iter().take(top) {
+        largest.add_row([
+            repo.name.clone(),
+            format_size(repo.size),
+            repo.worktrees.to_string(),
+        ]);
+    }
+
+    vec![summary, namespaces, hosts, largest]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
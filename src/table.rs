@@ -1,93 +1,152 @@
 use super::config;
+use super::notify;
 use super::path;
+use super::prompt;
 use super::repo;
+use super::state;
+use super::stats;
+use super::timings;
+use super::tree;
+use super::worktree;
 
 use comfy_table::{Cell, Table};
+use serde::Serialize;
 
+use std::collections::BTreeMap;
 use std::fmt::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-fn add_table_header(table: &mut Table) {
+/// With `last_update`, there is a column for when the repository was last
+/// synced or fetched
+fn add_table_header(table: &mut Table, last_update: bool) {
+    let mut header = vec![
+        Cell::new("Repo"),
+        Cell::new("Worktree"),
+        Cell::new("Status"),
+        Cell::new("Branches"),
+        Cell::new("HEAD"),
+        Cell::new("Remotes"),
+    ];
+    if last_update {
+        header.push(Cell::new("Last update"));
+    }
     table
         .load_preset(comfy_table::presets::UTF8_FULL)
         .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
-        .set_header([
-            Cell::new("Repo"),
-            Cell::new("Worktree"),
-            Cell::new("Status"),
-            Cell::new("Branches"),
-            Cell::new("HEAD"),
-            Cell::new("Remotes"),
-        ]);
+        .set_header(header);
 }
 
-fn add_repo_status(
-    table: &mut Table,
-    repo_name: &str,
+fn get_repo_status(
+    repo_path: &Path,
     repo_handle: &repo::RepoHandle,
     is_worktree: bool,
-) -> Result<(), String> {
-    let repo_status = repo_handle.status(is_worktree)?;
+    submodules: bool,
+) -> Result<repo::RepoStatus, String> {
+    let mut repo_status = repo_handle.status(is_worktree)?;
+    if submodules && !is_worktree {
+        repo_status.unpushed_submodules = repo_handle.unpushed_submodules()?;
+    }
 
-    table.add_row([
-        repo_name,
-        if is_worktree { "\u{2714}" } else { "" },
-        &if is_worktree {
-            String::new()
-        } else {
-            match repo_status.changes {
-                Some(changes) => {
-                    let mut out = Vec::new();
-                    if changes.files_new > 0 {
-                        out.push(format!("New: {}\n", changes.files_new));
-                    }
-                    if changes.files_modified > 0 {
-                        out.push(format!("Modified: {}\n", changes.files_modified));
-                    }
-                    if changes.files_deleted > 0 {
-                        out.push(format!("Deleted: {}\n", changes.files_deleted));
+    if !is_worktree {
+        prompt::record(repo_path, &repo_status, None);
+    }
+
+    Ok(repo_status)
+}
+
+/// The "Status" column: An operation in progress comes first, as it is easily
+/// forgotten, then the changed files
+pub fn format_changes(repo_status: &repo::RepoStatus) -> String {
+    let mut out = Vec::new();
+    if let Some(operation) = repo_status.operation_description() {
+        out.push(format!("\u{26a0} {operation}\n"));
+    }
+    for (name, _commit) in &repo_status.unpushed_submodules {
+        out.push(format!("\u{26a0} Unpushed submodule: {name}\n"));
+    }
+    match &repo_status.changes {
+        // The changes are part of the working copy commit of jj
+        _ if repo_status.jj => {
+            if out.is_empty() {
+                out.push(String::from("jj"));
+            }
+        }
+        Some(changes) => {
+            if changes.files_new > 0 {
+                out.push(format!("New: {}\n", changes.files_new));
+            }
+            if changes.files_modified > 0 {
+                out.push(format!("Modified: {}\n", changes.files_modified));
+            }
+            if changes.files_deleted > 0 {
+                out.push(format!("Deleted: {}\n", changes.files_deleted));
+            }
+        }
+        None if out.is_empty() => out.push(String::from("\u{2714}")),
+        None => (),
+    }
+    out.into_iter().collect::<String>().trim().to_string()
+}
+
+/// The "Branches" column: Each local branch with its remote branch and how far
+/// they are apart
+pub fn format_branches(repo_status: &repo::RepoStatus) -> String {
+    repo_status
+        .branches
+        .iter()
+        .fold(String::new(), |mut s, (branch_name, remote_branch)| {
+            writeln!(
+                &mut s,
+                "branch: {}{}",
+                &branch_name,
+                &match remote_branch {
+                    None => String::from(" <!local>"),
+                    Some((remote_branch_name, remote_tracking_status)) => {
+                        format!(
+                            " <{}>{}",
+                            remote_branch_name,
+                            &match remote_tracking_status {
+                                repo::RemoteTrackingStatus::UpToDate => String::from(" \u{2714}"),
+                                repo::RemoteTrackingStatus::Ahead(d) => format!(" [+{}]", &d),
+                                repo::RemoteTrackingStatus::Behind(d) => format!(" [-{}]", &d),
+                                repo::RemoteTrackingStatus::Diverged(d1, d2) =>
+                                    format!(" [+{}/-{}]", &d1, &d2),
+                            }
+                        )
                     }
-                    out.into_iter().collect::<String>().trim().to_string()
                 }
-                None => String::from("\u{2714}"),
-            }
+            )
+            .unwrap();
+            s
+        })
+        .trim()
+        .to_string()
+}
+
+fn add_repo_status_row(
+    table: &mut Table,
+    repo_name: &str,
+    repo_status: &repo::RepoStatus,
+    is_worktree: bool,
+    last_update: Option<String>,
+) {
+    let mut row = vec![
+        repo_name.to_string(),
+        String::from(if is_worktree { "\u{2714}" } else { "" }),
+        if is_worktree {
+            String::new()
+        } else {
+            format_changes(repo_status)
         },
-        repo_status
-            .branches
-            .iter()
-            .fold(String::new(), |mut s, (branch_name, remote_branch)| {
-                writeln!(
-                    &mut s,
-                    "branch: {}{}",
-                    &branch_name,
-                    &match remote_branch {
-                        None => String::from(" <!local>"),
-                        Some((remote_branch_name, remote_tracking_status)) => {
-                            format!(
-                                " <{}>{}",
-                                remote_branch_name,
-                                &match remote_tracking_status {
-                                    repo::RemoteTrackingStatus::UpToDate =>
-                                        String::from(" \u{2714}"),
-                                    repo::RemoteTrackingStatus::Ahead(d) => format!(" [+{}]", &d),
-                                    repo::RemoteTrackingStatus::Behind(d) => format!(" [-{}]", &d),
-                                    repo::RemoteTrackingStatus::Diverged(d1, d2) =>
-                                        format!(" [+{}/-{}]", &d1, &d2),
-                                }
-                            )
-                        }
-                    }
-                )
-                .unwrap();
-                s
-            })
-            .trim(),
-        &if is_worktree {
+        format_branches(repo_status),
+        if is_worktree {
             String::new()
         } else {
-            match repo_status.head {
-                Some(head) => head,
-                None => String::from("Empty"),
+            match (&repo_status.head, &repo_status.detached_head) {
+                (Some(head), _) => head.clone(),
+                (None, Some(commit)) => format!("Detached at {commit}"),
+                (None, None) => String::from("Empty"),
             }
         },
         repo_status
@@ -97,39 +156,85 @@ fn add_repo_status(
                 writeln!(&mut s, "{r}").unwrap();
                 s
             })
-            .trim(),
-    ]);
+            .trim()
+            .to_string(),
+    ];
+    if let Some(last_update) = last_update {
+        row.push(last_update);
+    }
+    table.add_row(row);
+}
 
-    Ok(())
+/// The status of a single worktree of a worktree setup
+struct WorktreeStatusEntry {
+    name: String,
+    repo: repo::RepoHandle,
+    status: repo::RepoStatus,
+    /// The disk usage of the worktree, if it is supposed to be shown
+    size: Option<u64>,
+    is_current: bool,
 }
 
-// Don't return table, return a type that implements Display(?)
-pub fn get_worktree_status_table(
+/// Returns the status of all worktrees in `directory`, together with errors
+/// about worktrees that cannot be read
+fn get_worktree_statuses(
     repo: &repo::RepoHandle,
     directory: &Path,
-) -> Result<(impl std::fmt::Display, Vec<String>), String> {
+    disk_usage: bool,
+    current_worktree: Option<&Path>,
+) -> Result<(Vec<WorktreeStatusEntry>, Vec<String>), String> {
     let worktrees = repo.get_worktrees()?;
-    let mut table = Table::new();
 
+    let mut entries = Vec::new();
     let mut errors = Vec::new();
 
-    add_worktree_table_header(&mut table);
     for worktree in &worktrees {
         let worktree_dir = &directory.join(worktree.name());
         if worktree_dir.exists() {
             let repo = match repo::RepoHandle::open(worktree_dir, false) {
                 Ok(repo) => repo,
                 Err(error) => {
-                    errors.push(format!(
-                        "Failed opening repo of worktree {}: {}",
-                        &worktree.name(),
-                        &error
-                    ));
+                    errors.push(if worktree::has_stale_link(worktree_dir) {
+                        format!(
+                            "Worktree {} points to a repository that does not exist. Run \"grm wt repair\" if the worktree setup was moved",
+                            worktree.name()
+                        )
+                    } else {
+                        format!(
+                            "Failed opening repo of worktree {}: {}",
+                            &worktree.name(),
+                            &error
+                        )
+                    });
                     continue;
                 }
             };
-            if let Err(error) = add_worktree_status(&mut table, worktree, &repo) {
-                errors.push(error);
+            let size = if disk_usage {
+                match stats::disk_usage(worktree_dir) {
+                    Ok(size) => Some(size),
+                    Err(error) => {
+                        errors.push(error);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            let is_current = current_worktree.is_some_and(|current_worktree| {
+                worktree_dir.canonicalize().ok() == current_worktree.canonicalize().ok()
+            });
+            match repo.status(false) {
+                Ok(status) => {
+                    prompt::record(worktree_dir, &status, Some(worktree.name()));
+                    entries.push(WorktreeStatusEntry {
+                        name: worktree.name().to_string(),
+                        repo,
+                        status,
+                        size,
+                        is_current,
+                    });
+                }
+                Err(error) => errors.push(error),
             }
         } else {
             errors.push(format!(
@@ -144,31 +249,227 @@ pub fn get_worktree_status_table(
             &worktree
         ));
     }
+    Ok((entries, errors))
+}
+
+// Don't return table, return a type that implements Display(?)
+///
+/// The worktree at `current_worktree` is marked, if given.
+pub fn get_worktree_status_table(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+    disk_usage: bool,
+    current_worktree: Option<&Path>,
+) -> Result<(Table, Vec<String>), String> {
+    let (entries, mut errors) =
+        get_worktree_statuses(repo, directory, disk_usage, current_worktree)?;
+
+    let mut table = Table::new();
+    add_worktree_table_header(&mut table, disk_usage);
+    for entry in &entries {
+        if let Err(error) = add_worktree_status(&mut table, entry) {
+            errors.push(error);
+        }
+    }
     Ok((table, errors))
 }
 
-pub fn get_status_table(config: config::Config) -> Result<(Vec<Table>, Vec<String>), String> {
+/// Like [`get_worktree_status_table()`], but returns the worktrees as a JSON
+/// array, each with its name, status and (with `disk_usage`) disk usage
+pub fn get_worktree_status_json(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+    disk_usage: bool,
+) -> Result<(String, Vec<String>), String> {
+    #[derive(Serialize)]
+    struct WorktreeStatusJson<'a> {
+        name: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        disk_usage: Option<u64>,
+        status: &'a repo::RepoStatus,
+    }
+
+    let (entries, errors) = get_worktree_statuses(repo, directory, disk_usage, None)?;
+    let json = to_json(
+        &entries
+            .iter()
+            .map(|entry| WorktreeStatusJson {
+                name: &entry.name,
+                disk_usage: entry.size,
+                status: &entry.status,
+            })
+            .collect::<Vec<_>>(),
+    )?;
+    Ok((json, errors))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value)
+        .map_err(|error| format!("Error serializing status: {error}"))
+}
+
+/// How to sort the repositories in the status table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusSort {
+    /// Alphabetically
+    Name,
+    /// Most changed files first
+    Dirty,
+    /// Most unpushed commits first
+    Ahead,
+    /// Oldest last commit first
+    Age,
+}
+
+/// How to split the repositories into separate status tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusGroup {
+    Namespace,
+    Host,
+    Tag,
+}
+
+/// The status of a single repository, with everything needed for sorting and grouping
+struct RepoStatusEntry {
+    tree: usize,
+    repo: repo::Repo,
+    path: PathBuf,
+    status: repo::RepoStatus,
+    last_commit_time: Option<i64>,
+    last_update: Option<u64>,
+}
+
+impl RepoStatusEntry {
+    fn dirty(&self) -> usize {
+        match &self.status.changes {
+            Some(changes) if !self.status.jj => {
+                changes.files_new + changes.files_modified + changes.files_deleted
+            }
+            _ => 0,
+        }
+    }
+
+    fn ahead(&self) -> usize {
+        self.status
+            .branches
+            .iter()
+            .map(|(_branch_name, remote_branch)| match remote_branch {
+                Some((_, repo::RemoteTrackingStatus::Ahead(d)))
+                | Some((_, repo::RemoteTrackingStatus::Diverged(d, _))) => *d,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The groups the repository belongs to. A repository can be in multiple
+    /// groups, e.g. if it has multiple tags.
+    fn groups(&self, group_by: StatusGroup) -> Vec<Option<String>> {
+        let groups: Vec<String> = match group_by {
+            StatusGroup::Namespace => self.repo.namespace.iter().cloned().collect(),
+            StatusGroup::Host => {
+                let mut hosts: Vec<String> = self
+                    .repo
+                    .remotes
+                    .iter()
+                    .flatten()
+                    .filter_map(|remote| repo::remote_host(&remote.url))
+                    .collect();
+                hosts.sort();
+                hosts.dedup();
+                hosts
+            }
+            StatusGroup::Tag => self.repo.tags.clone(),
+        };
+
+        if groups.is_empty() {
+            vec![None]
+        } else {
+            groups.into_iter().map(Some).collect()
+        }
+    }
+}
+
+fn sort_entries(entries: &mut [RepoStatusEntry], sort: StatusSort) {
+    match sort {
+        StatusSort::Name => entries.sort_by_key(|entry| entry.repo.fullname()),
+        StatusSort::Dirty => entries.sort_by_key(|entry| std::cmp::Reverse(entry.dirty())),
+        StatusSort::Ahead => entries.sort_by_key(|entry| std::cmp::Reverse(entry.ahead())),
+        // Repositories without any commits go last
+        StatusSort::Age => entries.sort_by_key(|entry| entry.last_commit_time.unwrap_or(i64::MAX)),
+    }
+}
+
+fn group_title(group_by: StatusGroup, group: Option<&str>) -> String {
+    match (group_by, group) {
+        (StatusGroup::Namespace, Some(namespace)) => format!("Namespace: {namespace}"),
+        (StatusGroup::Namespace, None) => String::from("No namespace"),
+        (StatusGroup::Host, Some(host)) => format!("Host: {host}"),
+        (StatusGroup::Host, None) => String::from("No remote host"),
+        (StatusGroup::Tag, Some(tag)) => format!("Tag: {tag}"),
+        (StatusGroup::Tag, None) => String::from("No tags"),
+    }
+}
+
+/// The repositories of the status, see [`get_status_table()`]
+struct StatusEntries {
+    /// The name (or root) of every selected tree
+    tree_labels: Vec<String>,
+    entries: Vec<RepoStatusEntry>,
+    errors: Vec<String>,
+    check_failures: Vec<String>,
+    now: u64,
+}
+
+fn get_status_entries(
+    config: config::Config,
+    tree: Option<&str>,
+    sort: Option<StatusSort>,
+    only_in_progress: bool,
+    submodules: bool,
+    skip_jj: bool,
+    stale_after: Option<Duration>,
+) -> Result<StatusEntries, String> {
     let mut errors = Vec::new();
-    let mut tables = Vec::new();
-    for tree in config.trees()? {
-        let repos = tree.repos.unwrap_or_default();
+    let mut check_failures = Vec::new();
+    let mut entries = Vec::new();
 
+    let now = notify::now()?;
+    let trees = config::select_trees(config.trees()?, tree)?;
+    let tree_labels: Vec<String> = trees.iter().map(|tree| tree.label().to_string()).collect();
+    for (tree_index, tree) in trees.into_iter().enumerate() {
         let root_path = path::expand_path(Path::new(&tree.root));
+        let tree_state = state::read_state(&tree.state_root()).unwrap_or_else(|error| {
+            errors.push(error);
+            state::TreeState::default()
+        });
 
-        let mut table = Table::new();
-        add_table_header(&mut table);
-
-        for repo in &repos {
-            let repo_path = root_path.join(&repo.name);
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            let repo_path = root_path.join(repo.directory());
 
             if !repo_path.exists() {
                 errors.push(format!(
                     "{}: Repository does not exist. Run sync?",
-                    &repo.name
+                    repo.fullname()
                 ));
                 continue;
             }
 
+            let last_update = tree_state.last_update(&repo.fullname());
+            if let (Some(stale_after), Some(last_update)) = (stale_after, last_update) {
+                if now.saturating_sub(last_update) < stale_after.as_secs() {
+                    continue;
+                }
+            }
+
+            if skip_jj && repo::RepoHandle::detect_jj(&repo_path) {
+                continue;
+            }
+
             let repo_handle = repo::RepoHandle::open(&repo_path, repo.worktree_setup);
 
             let repo_handle = match repo_handle {
@@ -177,62 +478,319 @@ pub fn get_status_table(config: config::Config) -> Result<(Vec<Table>, Vec<Strin
                     if error.kind == repo::RepoErrorKind::NotFound {
                         errors.push(format!(
                             "{}: No git repository found. Run sync?",
-                            &repo.name
+                            repo.fullname()
                         ));
                     } else {
                         errors.push(format!(
                             "{}: Opening repository failed: {}",
-                            &repo.name, error
+                            repo.fullname(),
+                            error
                         ));
                     }
                     continue;
                 }
             };
 
-            if let Err(err) =
-                add_repo_status(&mut table, &repo.name, &repo_handle, repo.worktree_setup)
-            {
-                errors.push(format!("{}: Couldn't add repo status: {}", &repo.name, err));
+            let status = timings::measure("status", Some(&repo), || {
+                repo_handle.status(repo.worktree_setup)
+            });
+            let mut status = match status {
+                Ok(status) => status,
+                Err(err) => {
+                    errors.push(format!(
+                        "{}: Couldn't add repo status: {}",
+                        repo.fullname(),
+                        err
+                    ));
+                    continue;
+                }
+            };
+
+            if submodules && !repo.worktree_setup {
+                match repo_handle.unpushed_submodules() {
+                    Ok(unpushed) => status.unpushed_submodules = unpushed,
+                    Err(err) => errors.push(format!(
+                        "{}: Couldn't check submodules: {}",
+                        repo.fullname(),
+                        err
+                    )),
+                }
+            }
+
+            if !repo.worktree_setup {
+                prompt::record(&repo_path, &status, None);
             }
+
+            if only_in_progress && status.operation.is_none() {
+                continue;
+            }
+
+            let last_commit_time = if sort == Some(StatusSort::Age) {
+                match repo_handle.last_commit_time() {
+                    Ok(time) => time,
+                    Err(err) => {
+                        errors.push(format!(
+                            "{}: Failed getting last commit: {}",
+                            repo.fullname(),
+                            err
+                        ));
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            check_failures.extend(
+                status
+                    .check_failures()
+                    .into_iter()
+                    .map(|failure| format!("{}: {}", repo.fullname(), failure)),
+            );
+
+            entries.push(RepoStatusEntry {
+                tree: tree_index,
+                repo,
+                path: repo_path,
+                status,
+                last_commit_time,
+                last_update,
+            });
         }
+    }
 
-        tables.push(table);
+    if let Some(sort) = sort {
+        sort_entries(&mut entries, sort);
     }
 
-    Ok((tables, errors))
+    Ok(StatusEntries {
+        tree_labels,
+        entries,
+        errors,
+        check_failures,
+        now,
+    })
 }
 
-fn add_worktree_table_header(table: &mut Table) {
+/// Returns the status tables, errors and all check failures of the
+/// repositories (see [`repo::RepoStatus::check_failures`])
+///
+/// With `tree`, only the trees with that name or root are included. Without
+/// `group_by`, there is one table per tree, titled with the name (or root) of
+/// the tree if there are several. Otherwise, there is one table per group,
+/// together with a title for it. Without `sort`, the repositories are
+/// listed in the order of the configuration.
+///
+/// With `only_in_progress`, only repositories with an unfinished operation (e.g.
+/// a rebase) are included, and empty tables are left out. With `submodules`,
+/// submodules whose commit was not pushed are reported. With `skip_jj`,
+/// repositories colocated with jj are left out. With `stale_after`, only
+/// repositories that were not synced or fetched for that long (or never) are
+/// included.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn get_status_table(
+    config: config::Config,
+    tree: Option<&str>,
+    sort: Option<StatusSort>,
+    group_by: Option<StatusGroup>,
+    only_in_progress: bool,
+    submodules: bool,
+    skip_jj: bool,
+    stale_after: Option<Duration>,
+) -> Result<(Vec<(Option<String>, Table)>, Vec<String>, Vec<String>), String> {
+    let StatusEntries {
+        tree_labels,
+        entries,
+        errors,
+        check_failures,
+        now,
+    } = get_status_entries(
+        config,
+        tree,
+        sort,
+        only_in_progress,
+        submodules,
+        skip_jj,
+        stale_after,
+    )?;
+
+    // Groups are keyed by (is the "no group" group, group name, tree index), so
+    // the "no group" group goes last and trees keep their order
+    type GroupKey = (bool, Option<String>, usize);
+    let mut groups: BTreeMap<GroupKey, Vec<&RepoStatusEntry>> = BTreeMap::new();
+    match group_by {
+        Some(group_by) => {
+            for entry in &entries {
+                for group in entry.groups(group_by) {
+                    groups
+                        .entry((group.is_none(), group, 0))
+                        .or_default()
+                        .push(entry);
+                }
+            }
+        }
+        None => {
+            // Every tree gets a table, even if it is empty
+            for tree_index in 0..tree_labels.len() {
+                groups.insert((false, None, tree_index), Vec::new());
+            }
+            for entry in &entries {
+                groups
+                    .entry((false, None, entry.tree))
+                    .or_default()
+                    .push(entry);
+            }
+        }
+    }
+
+    if only_in_progress || stale_after.is_some() {
+        groups.retain(|_, entries| !entries.is_empty());
+    }
+
+    let mut tables = Vec::new();
+    for ((_, group, tree_index), entries) in groups {
+        let mut table = Table::new();
+        add_table_header(&mut table, true);
+        for entry in entries {
+            add_repo_status_row(
+                &mut table,
+                &entry.repo.fullname(),
+                &entry.status,
+                entry.repo.worktree_setup,
+                Some(format_last_update(entry.last_update, now)),
+            );
+        }
+        let title = match group_by {
+            Some(group_by) => Some(group_title(group_by, group.as_deref())),
+            // A single tree does not need to be told apart
+            None if tree_labels.len() > 1 => Some(format!("Tree: {}", tree_labels[tree_index])),
+            None => None,
+        };
+        tables.push((title, table));
+    }
+
+    Ok((tables, errors, check_failures))
+}
+
+/// A repository in the JSON status, see [`get_status_json()`]
+#[derive(Serialize)]
+struct RepoStatusJson<'a> {
+    /// The name (or root) of the tree, if the repository is from a configuration
+    tree: Option<&'a str>,
+    name: &'a str,
+    path: &'a Path,
+    worktree_setup: bool,
+    last_update: Option<u64>,
+    status: &'a repo::RepoStatus,
+}
+
+/// Like [`get_status_table()`], but returns all repositories as a single JSON
+/// array instead of tables, together with the errors and check failures
+pub fn get_status_json(
+    config: config::Config,
+    tree: Option<&str>,
+    sort: Option<StatusSort>,
+    only_in_progress: bool,
+    submodules: bool,
+    skip_jj: bool,
+    stale_after: Option<Duration>,
+) -> Result<(String, Vec<String>, Vec<String>), String> {
+    let StatusEntries {
+        tree_labels,
+        entries,
+        errors,
+        check_failures,
+        now: _,
+    } = get_status_entries(
+        config,
+        tree,
+        sort,
+        only_in_progress,
+        submodules,
+        skip_jj,
+        stale_after,
+    )?;
+
+    let names: Vec<String> = entries.iter().map(|entry| entry.repo.fullname()).collect();
+    let json = to_json(
+        &entries
+            .iter()
+            .zip(&names)
+            .map(|(entry, name)| RepoStatusJson {
+                tree: Some(&tree_labels[entry.tree]),
+                name,
+                path: &entry.path,
+                worktree_setup: entry.repo.worktree_setup,
+                last_update: entry.last_update,
+                status: &entry.status,
+            })
+            .collect::<Vec<_>>(),
+    )?;
+
+    Ok((json, errors, check_failures))
+}
+
+fn add_worktree_table_header(table: &mut Table, disk_usage: bool) {
+    let mut header = vec![
+        Cell::new("Worktree"),
+        Cell::new("Status"),
+        Cell::new("Branch"),
+        Cell::new("Remote branch"),
+    ];
+    if disk_usage {
+        header.push(Cell::new("Disk usage"));
+    }
     table
         .load_preset(comfy_table::presets::UTF8_FULL)
         .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
-        .set_header([
-            Cell::new("Worktree"),
-            Cell::new("Status"),
-            Cell::new("Branch"),
-            Cell::new("Remote branch"),
-        ]);
+        .set_header(header);
 }
 
-fn add_worktree_status(
-    table: &mut Table,
-    worktree: &repo::Worktree,
-    repo: &repo::RepoHandle,
-) -> Result<(), String> {
-    let repo_status = repo.status(false)?;
+fn add_worktree_status(table: &mut Table, entry: &WorktreeStatusEntry) -> Result<(), String> {
+    let repo = &entry.repo;
+    let (branch_output, upstream_output) = match &entry.status.detached_head {
+        Some(commit) => (format!("Detached at {commit}"), String::new()),
+        None => {
+            let local_branch = repo
+                .head_branch()
+                .map_err(|error| format!("Failed getting head branch: {error}"))?;
+            (
+                local_branch
+                    .name()
+                    .map_err(|error| format!("Failed getting name of branch: {error}"))?,
+                format_upstream(repo, &local_branch)?,
+            )
+        }
+    };
+
+    let mut row = vec![
+        if entry.is_current {
+            format!("{} (current)", entry.name)
+        } else {
+            entry.name.clone()
+        },
+        format_changes(&entry.status),
+        branch_output,
+        upstream_output,
+    ];
+    if let Some(size) = entry.size {
+        row.push(stats::format_size(size));
+    }
+    table.add_row(row);
 
-    let local_branch = repo
-        .head_branch()
-        .map_err(|error| format!("Failed getting head branch: {error}"))?;
+    Ok(())
+}
 
-    let upstream_output = match local_branch.upstream() {
+/// The remote branch of `local_branch` and how far they are apart
+fn format_upstream(repo: &repo::RepoHandle, local_branch: &repo::Branch) -> Result<String, String> {
+    Ok(match local_branch.upstream() {
         Ok(remote_branch) => {
             let remote_branch_name = remote_branch
                 .name()
                 .map_err(|error| format!("Failed getting name of remote branch: {error}"))?;
 
             let (ahead, behind) = repo
-                .graph_ahead_behind(&local_branch, &remote_branch)
+                .graph_ahead_behind(local_branch, &remote_branch)
                 .map_err(|error| format!("Failed computing branch deviation: {error}"))?;
 
             format!(
@@ -246,54 +804,233 @@ fn add_worktree_status(
                 },
             )
         }
-        Err(_) => String::new(),
-    };
+        Err(_) => match repo.gone_upstream(local_branch)? {
+            Some(upstream) => format!("{upstream} [gone]\n"),
+            None => String::new(),
+        },
+    })
+}
+
+fn format_last_update(last_update: Option<u64>, now: u64) -> String {
+    match last_update {
+        Some(timestamp) => format_age((now.saturating_sub(timestamp) / 86400) as i64),
+        None => String::from("never"),
+    }
+}
+
+fn format_age(days: i64) -> String {
+    match days {
+        0 => String::from("today"),
+        1 => String::from("1 day ago"),
+        days => format!("{days} days ago"),
+    }
+}
+
+pub fn get_cleanup_suggestion_table(suggestions: &[worktree::CleanupSuggestion]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header([
+            Cell::new("Worktree"),
+            Cell::new("Disk usage"),
+            Cell::new("Last commit"),
+            Cell::new("Removable"),
+        ]);
+
+    for suggestion in suggestions {
+        table.add_row([
+            suggestion.name.clone(),
+            stats::format_size(suggestion.size),
+            format_age(suggestion.age_days),
+            match &suggestion.blocker {
+                None => String::from("\u{2714}"),
+                Some(reason) => reason.clone(),
+            },
+        ]);
+    }
+
+    table
+}
 
+fn get_total_timings_table(kind: &str, totals: &[timings::TotalTiming]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header([
+            Cell::new(kind),
+            Cell::new("Duration"),
+            Cell::new("Repositories"),
+        ]);
+
+    for total in totals {
+        table.add_row([
+            total.name.clone(),
+            timings::format_duration(total.duration),
+            if total.repos == 0 {
+                String::new()
+            } else {
+                total.repos.to_string()
+            },
+        ]);
+    }
+
+    table
+}
+
+/// Returns the tables for `--timings`: The phases, the hosts (if any) and the
+/// repositories, each with the most expensive entry first
+pub fn get_timings_tables(summary: &timings::Summary) -> Vec<Table> {
+    let mut tables = vec![get_total_timings_table("Phase", &summary.phases)];
+
+    if !summary.hosts.is_empty() {
+        tables.push(get_total_timings_table("Host", &summary.hosts));
+    }
+
+    if !summary.repos.is_empty() {
+        let mut table = Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_header([
+                Cell::new("Repository"),
+                Cell::new("Phase"),
+                Cell::new("Duration"),
+            ]);
+        for repo in &summary.repos {
+            table.add_row([
+                repo.repo.clone(),
+                repo.phase.clone(),
+                timings::format_duration(repo.duration),
+            ]);
+        }
+        tables.push(table);
+    }
+
+    tables
+}
+
+pub fn get_location_table(location: &tree::Location) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS);
+
+    table.add_row(["Config", &location.config]);
     table.add_row([
-        worktree.name(),
-        &match repo_status.changes {
-            Some(changes) => {
-                let mut out = Vec::new();
-                if changes.files_new > 0 {
-                    out.push(format!("New: {}\n", changes.files_new));
-                }
-                if changes.files_modified > 0 {
-                    out.push(format!("Modified: {}\n", changes.files_modified));
-                }
-                if changes.files_deleted > 0 {
-                    out.push(format!("Deleted: {}\n", changes.files_deleted));
-                }
-                out.into_iter().collect::<String>().trim().to_string()
-            }
-            None => String::from("\u{2714}"),
+        "Tree",
+        &match &location.tree_name {
+            Some(name) => format!("{} ({})", location.tree, name),
+            None => location.tree.clone(),
         },
-        &local_branch
-            .name()
-            .map_err(|error| format!("Failed getting name of branch: {error}"))?,
-        &upstream_output,
     ]);
+    table.add_row(["Repository", location.repo.as_deref().unwrap_or("-")]);
+    table.add_row(["Namespace", location.namespace.as_deref().unwrap_or("-")]);
+    table.add_row(["Path", location.repo_path.as_deref().unwrap_or("-")]);
+    table.add_row(["Worktree", location.worktree.as_deref().unwrap_or("-")]);
 
-    Ok(())
+    table
 }
 
-pub fn show_single_repo_status(
-    path: &Path,
-) -> Result<(impl std::fmt::Display, Vec<String>), String> {
+/// Returns a table per remote, together with the name of the remote. Branches
+/// with the same prefix are listed together, with the prefix only shown once.
+pub fn get_remote_branch_tables(
+    candidates: &[worktree::RemoteBranchCandidate],
+) -> Vec<(String, Table)> {
+    let mut tables: Vec<(String, Table)> = Vec::new();
+    let mut last_prefix = None;
+
+    for candidate in candidates {
+        if tables.last().map(|(remote, _)| remote) != Some(&candidate.remote) {
+            let mut table = Table::new();
+            table
+                .load_preset(comfy_table::presets::UTF8_FULL)
+                .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+                .set_header([
+                    Cell::new("Prefix"),
+                    Cell::new("Branch"),
+                    Cell::new("Last commit"),
+                ]);
+            tables.push((candidate.remote.clone(), table));
+            last_prefix = None;
+        }
+
+        let prefix = candidate.prefix();
+        let show_prefix = last_prefix != Some(prefix);
+        last_prefix = Some(prefix);
+
+        if let Some((_, table)) = tables.last_mut() {
+            table.add_row([
+                if show_prefix {
+                    prefix.unwrap_or_default().to_string()
+                } else {
+                    String::new()
+                },
+                candidate.branch.clone(),
+                format_age(candidate.age_days),
+            ]);
+        }
+    }
+
+    tables
+}
+
+pub fn get_rebase_plan_table(plans: &[(String, repo::RebasePlan)], stash: bool) -> Table {
     let mut table = Table::new();
-    let mut warnings = Vec::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header([
+            Cell::new("Worktree"),
+            Cell::new("Onto"),
+            Cell::new("Current base"),
+            Cell::new("Target"),
+            Cell::new("Ahead"),
+            Cell::new("Behind"),
+            Cell::new("Note"),
+        ]);
 
-    let is_worktree = repo::RepoHandle::detect_worktree(path);
-    add_table_header(&mut table);
+    for (name, plan) in plans {
+        table.add_row([
+            name.clone(),
+            plan.base_branch.clone(),
+            plan.current_base.clone(),
+            plan.target.clone(),
+            plan.ahead.to_string(),
+            plan.behind.to_string(),
+            String::from(if plan.changes && !stash {
+                "Changes, will be skipped"
+            } else if plan.behind == 0 {
+                "Up to date"
+            } else if plan.changes {
+                "Changes will be stashed"
+            } else {
+                ""
+            }),
+        ]);
+    }
 
-    let repo_handle = repo::RepoHandle::open(path, is_worktree);
+    table
+}
 
-    if let Err(error) = repo_handle {
-        return if error.kind == repo::RepoErrorKind::NotFound {
-            Err(String::from("Directory is not a git directory"))
+/// Opens the repository that contains `path`, returning it together with its
+/// name
+fn open_single_repo(
+    path: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<(repo::DiscoveredRepo, repo::RepoHandle, String), String> {
+    let discovered = repo::RepoHandle::discover(path).map_err(|error| {
+        if error.kind == repo::RepoErrorKind::NotFound {
+            String::from("Directory is not a git directory")
         } else {
-            Err(format!("Opening repository failed: {error}"))
-        };
-    };
+            format!("Opening repository failed: {error}")
+        }
+    })?;
+    let path = discovered.path.as_path();
+
+    let repo_handle = repo::RepoHandle::open(path, discovered.is_worktree)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
 
     let repo_name = match path.file_name() {
         None => {
@@ -315,7 +1052,68 @@ pub fn show_single_repo_status(
         },
     };
 
-    add_repo_status(&mut table, &repo_name, &repo_handle.unwrap(), is_worktree)?;
+    Ok((discovered, repo_handle, repo_name))
+}
+
+/// Returns the status of the repository that `path` belongs to
+///
+/// Inside a linked worktree, this is the status of the main repository. For a
+/// worktree setup, a table of all worktrees follows, with the worktree that
+/// `path` is in marked.
+#[allow(clippy::type_complexity)]
+pub fn show_single_repo_status(
+    path: &Path,
+    submodules: bool,
+) -> Result<(Vec<Table>, Vec<String>, Vec<String>), String> {
+    let mut table = Table::new();
+    let mut warnings = Vec::new();
+
+    add_table_header(&mut table, false);
+
+    let (discovered, repo_handle, repo_name) = open_single_repo(path, &mut warnings)?;
+    let path = discovered.path.as_path();
+    let is_worktree = discovered.is_worktree;
+
+    let repo_status = get_repo_status(path, &repo_handle, is_worktree, submodules)?;
+    let check_failures = repo_status.check_failures();
+    add_repo_status_row(&mut table, &repo_name, &repo_status, is_worktree, None);
+    let mut tables = vec![table];
+
+    if is_worktree {
+        let (worktree_table, errors) = get_worktree_status_table(
+            &repo_handle,
+            path,
+            false,
+            discovered.current_worktree.as_deref(),
+        )?;
+        tables.push(worktree_table);
+        warnings.extend(errors);
+    }
+
+    Ok((tables, warnings, check_failures))
+}
+
+/// Like [`show_single_repo_status()`], but returns the repository as the only
+/// entry of a JSON array like [`get_status_json()`]. The worktrees of a
+/// worktree setup are left out, see [`get_worktree_status_json()`].
+pub fn get_single_repo_status_json(
+    path: &Path,
+    submodules: bool,
+) -> Result<(String, Vec<String>, Vec<String>), String> {
+    let mut warnings = Vec::new();
+
+    let (discovered, repo_handle, repo_name) = open_single_repo(path, &mut warnings)?;
+    let path = discovered.path.as_path();
+
+    let repo_status = get_repo_status(path, &repo_handle, discovered.is_worktree, submodules)?;
+    let json = to_json(&[RepoStatusJson {
+        tree: None,
+        name: &repo_name,
+        path,
+        worktree_setup: discovered.is_worktree,
+        last_update: None,
+        status: &repo_status,
+    }])?;
 
-    Ok((table, warnings))
+    Ok((json, warnings, repo_status.check_failures()))
 }
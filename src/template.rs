@@ -0,0 +1,287 @@
+//! Creating new repositories from a template repository, see `grm repos new`
+//!
+//! The template is cloned, its history is dropped and placeholders in all files
+//! are replaced:
+//!
+//! * `{{project_name}}` with the name of the new repository
+//! * `{{author}}` with the author, by default `GIT_AUTHOR_NAME` or `user.name`
+//!   from the git configuration
+//!
+//! Optionally, the project is created on a forge as well. In the end, the new
+//! repository is added to the configuration.
+
+use std::path::{Path, PathBuf};
+
+use super::auth;
+use super::config;
+use super::output::*;
+use super::path;
+use super::provider;
+use super::repo;
+
+const PLACEHOLDER_PROJECT_NAME: &str = "{{project_name}}";
+const PLACEHOLDER_AUTHOR: &str = "{{author}}";
+
+/// Where to create the project on a forge
+pub struct ForgeProject {
+    pub provider: provider::RemoteProvider,
+    pub token_command: String,
+    pub api_url: Option<String>,
+    /// The group (or organization) of the project, the requesting user if unset
+    pub group: Option<String>,
+    pub private: bool,
+    pub force_ssh: bool,
+    pub remote_name: Option<String>,
+}
+
+fn template_remote(template: &str) -> Result<repo::Remote, String> {
+    let local_path = Path::new(template);
+    if local_path.exists() {
+        let local_path = local_path
+            .canonicalize()
+            .map_err(|error| format!("Failed to resolve \"{template}\": {error}"))?;
+        return Ok(repo::Remote {
+            name: String::from("origin"),
+            url: path::path_as_utf8(&local_path)?.to_string(),
+            remote_type: repo::RemoteType::File,
+        });
+    }
+
+    Ok(repo::Remote {
+        name: String::from("origin"),
+        url: template.to_string(),
+        remote_type: repo::detect_remote_type(template).map_err(|error| {
+            format!("Template \"{template}\" is neither a path nor a URL: {error}")
+        })?,
+    })
+}
+
+fn default_author() -> Result<String, String> {
+    if let Ok(author) = std::env::var("GIT_AUTHOR_NAME") {
+        return Ok(author);
+    }
+    git2::Config::open_default()
+        .and_then(|config| config.get_string("user.name"))
+        .map_err(|_| {
+            String::from("Could not determine the author from the git configuration, use --author")
+        })
+}
+
+/// Replaces `placeholders` in all files below `directory`. Files that are not
+/// valid UTF-8 are left alone.
+fn substitute(directory: &Path, placeholders: &[(&str, &str)]) -> Result<(), String> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|error| format!("Failed to read \"{}\": {}", directory.display(), error))?;
+
+    for entry in entries {
+        let path = entry.map_err(|error| error.to_string())?.path();
+        let metadata = std::fs::symlink_metadata(&path)
+            .map_err(|error| format!("Failed to read \"{}\": {}", path.display(), error))?;
+
+        if metadata.is_dir() {
+            substitute(&path, placeholders)?;
+        } else if metadata.is_file() {
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut replaced = content.clone();
+            for (placeholder, value) in placeholders {
+                replaced = replaced.replace(placeholder, value);
+            }
+            if replaced != content {
+                std::fs::write(&path, replaced).map_err(|error| {
+                    format!("Failed to write \"{}\": {}", path.display(), error)
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets up the local repository at `repo_path` from the template
+fn init_from_template(
+    repo_path: &Path,
+    template: &repo::Remote,
+    placeholders: &[(&str, &str)],
+) -> Result<repo::RepoHandle, String> {
+    print_action(&repo::clone_message(template, repo_path));
+    repo::clone_repo(
+        template,
+        repo_path,
+        false,
+        None,
+        None,
+        None,
+        &repo::RetryConfig::default(),
+    )
+    .map_err(|error| format!("Cloning the template failed: {error}"))?;
+
+    // Start with a fresh history
+    std::fs::remove_dir_all(repo_path.join(".git"))
+        .map_err(|error| format!("Failed to remove the history of the template: {error}"))?;
+
+    substitute(repo_path, placeholders)?;
+
+    let repo_handle = repo::RepoHandle::init(repo_path, false)?;
+    repo_handle.commit_all("Initial commit")?;
+
+    Ok(repo_handle)
+}
+
+/// Creates the project on the forge, adds it as a remote and pushes to it
+fn create_on_forge(
+    repo_handle: &repo::RepoHandle,
+    name: &str,
+    forge: &ForgeProject,
+) -> Result<repo::Repo, String> {
+    let token = auth::get_token_from_command(&forge.token_command)
+        .map_err(|error| format!("Getting token from command failed: {error}"))?;
+
+    let project = provider::create_repo(
+        &forge.provider,
+        token,
+        forge.api_url.clone(),
+        name,
+        forge.group.as_deref(),
+        forge.private,
+        forge.force_ssh,
+        forge.remote_name.clone(),
+    )
+    .map_err(|error| format!("Creating the project failed: {error}"))?;
+
+    for remote in project.remotes.iter().flatten() {
+        print_action(&format!("Created project at \"{}\"", remote.url));
+        repo_handle.new_remote(&remote.name, &remote.url)?;
+
+        let branch_name = repo_handle.head_branch()?.name()?;
+        let push = repo_handle
+            .find_remote(&remote.name)?
+            .ok_or_else(|| format!("Remote \"{}\" not found", remote.name))
+            .and_then(|mut remote_handle| {
+                remote_handle
+                    .push(&branch_name, &branch_name, repo_handle)
+                    .map_err(String::from)
+            });
+        match push {
+            Ok(()) => repo_handle
+                .find_local_branch(&branch_name)?
+                .set_upstream(&remote.name, &branch_name)?,
+            // The project exists now, so keep going. Pushing can be retried by hand.
+            Err(error) => print_warning(&format!(
+                "Pushing {branch_name} to {} failed: {error}",
+                remote.name
+            )),
+        }
+    }
+
+    Ok(project)
+}
+
+/// Creates the repository `name` from `template` in the tree with the name or
+/// root `tree` (which can be omitted if there is only a single tree) and adds it
+/// to the configuration at `config_path`
+///
+/// Returns the path of the new repository.
+pub fn new_repo(
+    config_path: &str,
+    name: &str,
+    template: &str,
+    tree: Option<&str>,
+    author: Option<String>,
+    forge: Option<ForgeProject>,
+) -> Result<PathBuf, String> {
+    path::check_subpath(name)?;
+
+    // Fail before anything is created
+    config::check_writable(config_path)?;
+
+    let mut config: config::Config = config::read_config(config_path)?;
+
+    let config::Config::ConfigTrees(ref mut trees) = config else {
+        return Err(String::from(
+            "New repositories can only be added to a configuration with a list of trees",
+        ));
+    };
+
+    let hosts = trees.hosts.clone().unwrap_or_default();
+    let tree = trees.tree_mut(tree)?;
+
+    if tree.repos.iter().flatten().any(|repo| repo.name == name) {
+        return Err(format!(
+            "Repository \"{name}\" already exists in the configuration"
+        ));
+    }
+
+    let repo_path = path::expand_path(Path::new(&tree.root)).join(name);
+    if repo_path.exists() {
+        return Err(format!("\"{}\" already exists", repo_path.display()));
+    }
+    path::check_writable(&repo_path)?;
+
+    let author = match author {
+        Some(author) => author,
+        None => default_author()?,
+    };
+
+    // The last path component, without any namespace
+    let project_name = Path::new(name)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string());
+
+    let template = template_remote(template)?;
+    let repo_handle = init_from_template(
+        &repo_path,
+        &template,
+        &[
+            (PLACEHOLDER_PROJECT_NAME, &project_name),
+            (PLACEHOLDER_AUTHOR, &author),
+        ],
+    )
+    .map_err(|error| {
+        // Do not leave a half-initialized repository behind
+        if repo_path.exists() {
+            if let Err(cleanup_error) = std::fs::remove_dir_all(&repo_path) {
+                print_warning(&format!(
+                    "Failed to remove \"{}\": {}",
+                    repo_path.display(),
+                    cleanup_error
+                ));
+            }
+        }
+        error
+    })?;
+
+    let mut repo_config = config::RepoConfig {
+        name: name.to_string(),
+        worktree_setup: false,
+        remotes: None,
+        provider_id: None,
+        tags: None,
+        filter: None,
+        default_branch: None,
+        dir: None,
+        remote_name: None,
+    };
+
+    if let Some(forge) = forge {
+        let project = create_on_forge(&repo_handle, &project_name, &forge)?;
+        repo_config.remotes = project.remotes.map(|remotes| {
+            remotes
+                .into_iter()
+                .map(|remote| config::RemoteConfig {
+                    url: config::shorten_url(&remote.url, &hosts),
+                    ..config::RemoteConfig::from_remote(remote)
+                })
+                .collect()
+        });
+        repo_config.provider_id = project.provider_id;
+    }
+
+    tree.repos.get_or_insert_with(Vec::new).push(repo_config);
+
+    config::write_config(&config, config_path)?;
+
+    Ok(repo_path)
+}
@@ -0,0 +1,308 @@
+//! Helpers to set up throwaway repositories, remotes and trees in tests
+//!
+//! Only available with the `testing` feature. Everything is created in its own
+//! [`TestDir`] that is removed on drop, so tests using these helpers can run in
+//! parallel.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::config;
+use super::lock;
+use super::path;
+use super::repo;
+use super::tree;
+use super::worktree;
+
+static TEST_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn git_error(error: git2::Error) -> String {
+    error.message().to_string()
+}
+
+/// A temporary directory that is removed when it is dropped
+#[derive(Debug)]
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new() -> Result<Self, String> {
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "grm-test-{}-{}",
+                process::id(),
+                TEST_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(Self { path }),
+                // Left behind by an earlier process with the same PID
+                Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(error) => {
+                    return Err(format!(
+                        "Creating test directory {} failed: {}",
+                        path.display(),
+                        error
+                    ))
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A bare repository to be used as a `file://` remote
+#[derive(Debug)]
+pub struct TestRemote {
+    dir: TestDir,
+}
+
+impl TestRemote {
+    /// Creates a remote without any commits
+    pub fn empty() -> Result<Self, String> {
+        let dir = TestDir::new()?;
+        git2::Repository::init_bare(dir.path()).map_err(git_error)?;
+        Ok(Self { dir })
+    }
+
+    /// Creates a remote with a single commit on `branch`, which is also the
+    /// default branch of the remote
+    pub fn new(branch: &str) -> Result<Self, String> {
+        let remote = Self::empty()?;
+        remote.commit(branch, "README", "initial\n")?;
+        git2::Repository::open_bare(remote.path())
+            .and_then(|repo| repo.set_head(&format!("refs/heads/{branch}")))
+            .map_err(git_error)?;
+        Ok(remote)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn url(&self) -> String {
+        format!("file://{}", path::path_as_string(self.path()))
+    }
+
+    pub fn remote(&self, name: &str) -> repo::Remote {
+        repo::Remote {
+            name: name.to_string(),
+            url: self.url(),
+            remote_type: repo::RemoteType::File,
+        }
+    }
+
+    /// Commits `content` as `file` in the root of the repository on top of
+    /// `branch`, creating the branch if it does not exist yet
+    ///
+    /// Returns the ID of the new commit.
+    pub fn commit(&self, branch: &str, file: &str, content: &str) -> Result<String, String> {
+        let repo = git2::Repository::open_bare(self.path()).map_err(git_error)?;
+        let reference = format!("refs/heads/{branch}");
+
+        let parent = match repo.find_reference(&reference) {
+            Ok(reference) => Some(reference.peel_to_commit().map_err(git_error)?),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => None,
+            Err(error) => return Err(git_error(error)),
+        };
+        let parent_tree = parent
+            .as_ref()
+            .map(|commit| commit.tree())
+            .transpose()
+            .map_err(git_error)?;
+
+        let blob = repo.blob(content.as_bytes()).map_err(git_error)?;
+        let mut builder = repo.treebuilder(parent_tree.as_ref()).map_err(git_error)?;
+        builder.insert(file, blob, 0o100_644).map_err(git_error)?;
+        let tree = repo
+            .find_tree(builder.write().map_err(git_error)?)
+            .map_err(git_error)?;
+
+        let signature = git2::Signature::now("grm", "grm@example.com").map_err(git_error)?;
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        let oid = repo
+            .commit(
+                Some(&reference),
+                &signature,
+                &signature,
+                &format!("Update {file}"),
+                &tree,
+                &parents,
+            )
+            .map_err(git_error)?;
+        Ok(oid.to_string())
+    }
+
+    /// The ID of the commit `branch` points to, if the branch exists
+    pub fn head(&self, branch: &str) -> Result<Option<String>, String> {
+        let repo = git2::Repository::open_bare(self.path()).map_err(git_error)?;
+        let result = match repo.find_reference(&format!("refs/heads/{branch}")) {
+            Ok(reference) => Ok(Some(
+                reference
+                    .peel_to_commit()
+                    .map_err(git_error)?
+                    .id()
+                    .to_string(),
+            )),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(error) => Err(git_error(error)),
+        };
+        result
+    }
+}
+
+/// A tree root with a list of repositories, which can be synced like a tree
+/// from the configuration
+#[derive(Debug)]
+pub struct TestTree {
+    dir: TestDir,
+    repos: Vec<repo::Repo>,
+}
+
+impl TestTree {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {
+            dir: TestDir::new()?,
+            repos: Vec::new(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Adds a repository with the given remotes, in this order
+    pub fn add_repo(
+        &mut self,
+        name: &str,
+        worktree_setup: bool,
+        remotes: &[(&str, &TestRemote)],
+    ) -> &mut Self {
+        self.repos.push(repo::Repo {
+            name: name.to_string(),
+            namespace: None,
+            worktree_setup,
+            remotes: Some(
+                remotes
+                    .iter()
+                    .map(|(name, remote)| remote.remote(name))
+                    .collect(),
+            ),
+            provider_id: None,
+            tags: Vec::new(),
+            filter: None,
+            default_branch: None,
+            dir: None,
+            remote_name: None,
+        });
+        self
+    }
+
+    pub fn repo_path(&self, name: &str) -> PathBuf {
+        self.root().join(name)
+    }
+
+    pub fn config(&self) -> Result<config::Config, String> {
+        Ok(
+            config::ConfigTrees::from_vec(vec![config::ConfigTree::from_repos(
+                path::path_as_utf8(self.root())?.to_string(),
+                self.repos.clone(),
+            )])
+            .to_config(),
+        )
+    }
+
+    /// Syncs the tree the same way `grm repos sync config` does with the
+    /// default options
+    pub fn sync(&self) -> Result<tree::RunResult, String> {
+        tree::sync_trees(
+            self.config()?,
+            &tree::SyncOptions {
+                lock_policy: lock::LockPolicy::NoWait,
+                existing_root: tree::ExistingRoot::Fail,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn open(&self, name: &str) -> Result<repo::RepoHandle, String> {
+        let path = self.repo_path(name);
+        repo::RepoHandle::open(&path, repo::RepoHandle::detect_worktree(&path))
+            .map_err(|error| error.to_string())
+    }
+
+    /// Fetches all remotes of a repository, like `grm repos fetch`
+    pub fn fetch(&self, name: &str) -> Result<(), String> {
+        self.open(name)?
+            .fetchall(&repo::RetryConfig::default(), None)
+    }
+
+    /// Adds a worktree for `branch` to a repository with a worktree setup,
+    /// like `grm worktree add`
+    pub fn add_worktree(&self, name: &str, branch: &str) -> Result<(), String> {
+        worktree::add_worktree(&self.repo_path(name), branch, None, false).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_test_remote() {
+        let remote = TestRemote::new("main").unwrap();
+        assert!(remote.url().starts_with("file:///"));
+
+        let first = remote.head("main").unwrap().unwrap();
+        let second = remote.commit("main", "file", "content").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(remote.head("main").unwrap(), Some(second));
+        assert_eq!(remote.head("other").unwrap(), None);
+
+        let path = remote.path().to_path_buf();
+        drop(remote);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn check_test_tree_sync() {
+        let origin = TestRemote::new("main").unwrap();
+        let upstream = TestRemote::empty().unwrap();
+        let mut tree = TestTree::new().unwrap();
+        tree.add_repo(
+            "plain",
+            false,
+            &[("origin", &origin), ("upstream", &upstream)],
+        )
+        .add_repo("worktrees", true, &[("origin", &origin)]);
+
+        assert!(tree.sync().unwrap().success());
+
+        let repo = tree.open("plain").unwrap();
+        assert!(!repo.is_bare());
+        assert_eq!(repo.remotes().unwrap(), vec!["origin", "upstream"]);
+
+        assert!(tree.open("worktrees").unwrap().is_bare());
+        assert!(tree.repo_path("worktrees").join("main").is_dir());
+
+        origin.commit("feature", "file", "content").unwrap();
+        tree.fetch("worktrees").unwrap();
+        tree.add_worktree("worktrees", "feature").unwrap();
+        assert!(tree
+            .repo_path("worktrees")
+            .join("feature")
+            .join("file")
+            .is_file());
+    }
+}
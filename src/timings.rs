@@ -0,0 +1,209 @@
+//! Measures how long the phases of a run and the single repositories take, to
+//! find out which repositories or hosts make a run slow
+//!
+//! Measuring is opt-in (`--timings`). The durations are only printed at the end
+//! of the run and never leave the machine.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::repo;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDS: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+
+struct Record {
+    phase: String,
+    repo: Option<String>,
+    /// The host of the first remote of the repository
+    host: Option<String>,
+    duration: Duration,
+}
+
+/// Start recording durations
+///
+/// This is a process-wide setting, as phases are measured deep inside syncs and
+/// fetches, possibly in several threads at once.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records that `phase` took `duration`, for `repo` if given
+pub fn record(phase: &str, repo: Option<&repo::Repo>, duration: Duration) {
+    if enabled() {
+        RECORDS
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(Record {
+                phase: phase.to_string(),
+                repo: repo.map(repo::Repo::fullname),
+                host: repo
+                    .and_then(|repo| repo.remotes.as_ref()?.first())
+                    .and_then(|remote| repo::remote_host(&remote.url)),
+                duration,
+            });
+    }
+}
+
+/// Runs `f` and records how long it took as `phase`, for `repo` if given
+pub fn measure<T>(phase: &str, repo: Option<&repo::Repo>, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(phase, repo, start.elapsed());
+    result
+}
+
+/// The added up durations of a phase or host
+pub struct TotalTiming {
+    pub name: String,
+    pub duration: Duration,
+    /// How many repositories the duration is made of, zero for phases of the
+    /// whole run
+    pub repos: usize,
+}
+
+pub struct RepoTiming {
+    pub repo: String,
+    pub phase: String,
+    pub duration: Duration,
+}
+
+/// The recorded durations, most expensive first
+pub struct Summary {
+    pub phases: Vec<TotalTiming>,
+    pub hosts: Vec<TotalTiming>,
+    pub repos: Vec<RepoTiming>,
+}
+
+/// Summarizes everything recorded so far
+///
+/// The durations of repositories are added up per phase as well. As
+/// repositories may be handled in parallel, the sum can be larger than the
+/// whole run.
+pub fn summary() -> Summary {
+    let records = RECORDS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    summarize(&records)
+}
+
+fn add(totals: &mut BTreeMap<String, TotalTiming>, name: &str, record: &Record) {
+    let total = totals
+        .entry(name.to_string())
+        .or_insert_with(|| TotalTiming {
+            name: name.to_string(),
+            duration: Duration::ZERO,
+            repos: 0,
+        });
+    total.duration += record.duration;
+    if record.repo.is_some() {
+        total.repos += 1;
+    }
+}
+
+fn sorted(totals: BTreeMap<String, TotalTiming>) -> Vec<TotalTiming> {
+    let mut totals: Vec<TotalTiming> = totals.into_values().collect();
+    totals.sort_by_key(|total| std::cmp::Reverse(total.duration));
+    totals
+}
+
+fn summarize(records: &[Record]) -> Summary {
+    let mut phases = BTreeMap::new();
+    let mut hosts = BTreeMap::new();
+    let mut repos = Vec::new();
+
+    for record in records {
+        add(&mut phases, &record.phase, record);
+        if let Some(host) = &record.host {
+            add(&mut hosts, host, record);
+        }
+        if let Some(repo) = &record.repo {
+            repos.push(RepoTiming {
+                repo: repo.clone(),
+                phase: record.phase.clone(),
+                duration: record.duration,
+            });
+        }
+    }
+
+    repos.sort_by_key(|repo| std::cmp::Reverse(repo.duration));
+
+    Summary {
+        phases: sorted(phases),
+        hosts: sorted(hosts),
+        repos,
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{:.1} s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(phase: &str, repo: Option<(&str, &str)>, millis: u64) -> Record {
+        Record {
+            phase: phase.to_string(),
+            repo: repo.map(|(name, _host)| name.to_string()),
+            host: repo.map(|(_name, host)| host.to_string()),
+            duration: Duration::from_millis(millis),
+        }
+    }
+
+    fn totals(totals: &[TotalTiming]) -> Vec<(&str, u128, usize)> {
+        totals
+            .iter()
+            .map(|total| (total.name.as_str(), total.duration.as_millis(), total.repos))
+            .collect()
+    }
+
+    #[test]
+    fn check_summarize() {
+        let summary = summarize(&[
+            entry("provider enumeration", None, 300),
+            entry("clone", Some(("a", "github.com")), 200),
+            entry("clone", Some(("b", "gitlab.com")), 500),
+            entry("update", Some(("c", "github.com")), 100),
+        ]);
+
+        assert_eq!(
+            totals(&summary.phases),
+            vec![
+                ("clone", 700, 2),
+                ("provider enumeration", 300, 0),
+                ("update", 100, 1)
+            ]
+        );
+        assert_eq!(
+            totals(&summary.hosts),
+            vec![("gitlab.com", 500, 1), ("github.com", 300, 2)]
+        );
+        assert_eq!(
+            summary
+                .repos
+                .iter()
+                .map(|repo| repo.repo.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "a", "c"]
+        );
+    }
+
+    #[test]
+    fn check_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(42)), "42 ms");
+        assert_eq!(format_duration(Duration::from_millis(1234)), "1.2 s");
+        assert_eq!(format_duration(Duration::from_secs(75)), "75.0 s");
+    }
+}
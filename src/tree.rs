@@ -1,10 +1,18 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
 
+use super::browse;
 use super::config;
+use super::lock;
+use super::notify;
 use super::output::*;
 use super::path;
 use super::repo;
+use super::state;
+use super::stats;
+use super::timings;
 use super::worktree;
 
 pub struct Tree {
@@ -12,87 +20,2442 @@ pub struct Tree {
     pub repos: Vec<repo::Repo>,
 }
 
+/// A tree from the configuration, prepared for syncing
+struct SyncTree {
+    /// The root as configured
+    root: String,
+    root_path: PathBuf,
+    repos: Vec<repo::Repo>,
+    canonical_remote_name: Option<String>,
+    name: Option<String>,
+    serial: bool,
+    depends_on: Vec<String>,
+    /// Only the repository of the tree is managed, not the root around it
+    standalone: bool,
+    state_root: PathBuf,
+    /// The repositories that are kept in the state file, including the ones of
+    /// other trees with the same root
+    state_repos: Vec<String>,
+    history_days: Option<u32>,
+    max_size_mb: Option<u64>,
+    /// Permissions of the directories created for namespaces and repositories
+    dir_mode: Option<u32>,
+}
+
+impl SyncTree {
+    /// How the tree is called in messages
+    fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.root)
+    }
+
+    /// The name of the first remote of `repo`, the one of the repository winning
+    /// over the one of the tree
+    fn remote_name<'a>(&'a self, repo: &'a repo::Repo) -> Option<&'a str> {
+        repo.remote_name
+            .as_deref()
+            .or(self.canonical_remote_name.as_deref())
+    }
+}
+
 pub fn find_unmanaged_repos(
     root_path: &Path,
     managed_repos: &[repo::Repo],
 ) -> Result<Vec<PathBuf>, String> {
     let mut unmanaged_repos = Vec::new();
 
-    for repo_path in find_repo_paths(root_path)? {
+    for repo_path in find_repo_paths(root_path, false)? {
         if !managed_repos
             .iter()
-            .any(|r| Path::new(root_path).join(r.fullname()) == repo_path)
+            .any(|r| Path::new(root_path).join(r.directory()) == repo_path)
         {
             unmanaged_repos.push(repo_path);
         }
     }
-    Ok(unmanaged_repos)
+    Ok(unmanaged_repos)
+}
+
+/// What to do when a directory exists at the path of a repository, but does not
+/// contain that repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Use the existing repository and rewrite its remotes
+    Adopt,
+    /// Move the directory to `<name>.bak` and clone the repository
+    MoveAside,
+    /// Leave the directory alone
+    Skip,
+    /// Ask for every conflict
+    Ask,
+}
+
+pub struct RepoResult {
+    pub name: String,
+    pub error: Option<String>,
+    /// Problems that did not make the operation fail, e.g. a diverged default branch
+    pub warnings: Vec<String>,
+    /// New commits on the remote tracking branches, only set by fetches
+    pub changes: Vec<repo::BranchChanges>,
+}
+
+/// The result of an operation (e.g. sync or fetch) on a whole configuration
+pub struct RunResult {
+    pub repos: Vec<RepoResult>,
+    /// Errors that do not belong to a single repository
+    pub errors: Vec<String>,
+}
+
+impl RunResult {
+    pub fn success(&self) -> bool {
+        self.errors.is_empty() && self.repos.iter().all(|repo| repo.error.is_none())
+    }
+}
+
+/// Finds repositories that do not exist at their configured location, but somewhere
+/// else in the trees, e.g. because they were renamed or transferred on the forge.
+///
+/// Repositories are matched via their forge project ID, which is stored in the git
+/// configuration of each repository during sync. Returns the current paths by ID.
+/// The git directories of all existing repositories by the URLs of their
+/// remotes, to find repositories that new clones can share objects with
+///
+/// Only repositories that exist before the sync starts are considered, as
+/// other ones might still be cloned by another tree. Partial clones are left
+/// out, as they miss objects themselves.
+fn find_object_sources(trees: &[SyncTree]) -> HashMap<String, Vec<PathBuf>> {
+    let mut sources: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for tree in trees {
+        for repo in tree.repos.iter().filter(|repo| repo.filter.is_none()) {
+            let git_dir = tree
+                .root_path
+                .join(repo.directory())
+                .join(if repo.worktree_setup {
+                    worktree::GIT_MAIN_WORKTREE_DIRECTORY
+                } else {
+                    ".git"
+                });
+            if !git_dir.join("objects").is_dir() {
+                continue;
+            }
+            for remote in repo.remotes.iter().flatten() {
+                sources
+                    .entry(remote.url.clone())
+                    .or_default()
+                    .push(git_dir.clone());
+            }
+        }
+    }
+    sources
+}
+
+/// The objects directory of the repository at `repo_path`
+fn objects_directory(repo_path: &Path, worktree_setup: bool) -> PathBuf {
+    if worktree_setup {
+        repo_path
+            .join(worktree::GIT_MAIN_WORKTREE_DIRECTORY)
+            .join("objects")
+    } else {
+        repo_path.join(".git").join("objects")
+    }
+}
+
+/// The objects directories of the repositories of `trees` that other clones
+/// borrow objects from, or may start to borrow from during this sync (see the
+/// `share_objects` option)
+fn find_object_lenders(trees: &[SyncTree], share_objects: bool) -> HashSet<PathBuf> {
+    let repos: Vec<(&repo::Repo, PathBuf)> = trees
+        .iter()
+        .flat_map(|tree| {
+            tree.repos.iter().map(|repo| {
+                (
+                    repo,
+                    objects_directory(&tree.root_path.join(repo.directory()), repo.worktree_setup),
+                )
+            })
+        })
+        .collect();
+
+    let mut lenders = HashSet::new();
+    for (_repo, objects) in &repos {
+        let Ok(alternates) = std::fs::read_to_string(objects.join("info").join("alternates"))
+        else {
+            continue;
+        };
+        // Relative paths are relative to the objects directory
+        lenders.extend(
+            alternates
+                .lines()
+                .filter_map(|line| objects.join(line.trim()).canonicalize().ok()),
+        );
+    }
+
+    if share_objects {
+        // Clones borrow from repositories with their first remote, see
+        // `find_object_sources()`
+        fn first_url(repo: &repo::Repo) -> Option<&str> {
+            repo.remotes
+                .iter()
+                .flatten()
+                .next()
+                .map(|remote| remote.url.as_str())
+        }
+        let mut first_url_counts: HashMap<&str, usize> = HashMap::new();
+        for (repo, _objects) in &repos {
+            if let Some(url) = first_url(repo) {
+                *first_url_counts.entry(url).or_default() += 1;
+            }
+        }
+        for (repo, objects) in &repos {
+            let may_lend = repo.remotes.iter().flatten().any(|remote| {
+                let own = usize::from(first_url(repo) == Some(remote.url.as_str()));
+                first_url_counts
+                    .get(remote.url.as_str())
+                    .is_some_and(|count| *count > own)
+            });
+            if may_lend {
+                if let Ok(objects) = objects.canonicalize() {
+                    lenders.insert(objects);
+                }
+            }
+        }
+    }
+
+    lenders
+}
+
+fn find_moved_repos(trees: &[SyncTree]) -> HashMap<String, PathBuf> {
+    let mut moved_repos = HashMap::new();
+
+    let missing_ids: HashSet<&str> = trees
+        .iter()
+        .flat_map(|tree| {
+            tree.repos
+                .iter()
+                .filter(|repo| !tree.root_path.join(repo.directory()).exists())
+                .filter_map(|repo| repo.provider_id.as_deref())
+        })
+        .collect();
+
+    if missing_ids.is_empty() {
+        return moved_repos;
+    }
+
+    let managed_paths: HashSet<PathBuf> = trees
+        .iter()
+        .flat_map(|tree| {
+            tree.repos
+                .iter()
+                .map(|repo| tree.root_path.join(repo.directory()))
+        })
+        .collect();
+
+    for tree in trees.iter().filter(|tree| !tree.standalone) {
+        // Errors are reported later, when looking for unmanaged repositories
+        let Ok(repo_paths) = find_repo_paths(&tree.root_path, false) else {
+            continue;
+        };
+        for repo_path in repo_paths {
+            if managed_paths.contains(&repo_path) {
+                continue;
+            }
+            let is_worktree = repo::RepoHandle::detect_worktree(&repo_path);
+            if let Ok(repo_handle) = repo::RepoHandle::open(&repo_path, is_worktree) {
+                if let Ok(Some(provider_id)) = repo_handle.provider_id() {
+                    if missing_ids.contains(provider_id.as_str()) {
+                        moved_repos.insert(provider_id, repo_path);
+                    }
+                }
+            }
+        }
+    }
+
+    moved_repos
+}
+
+/// Refuses repositories whose name would lead outside of the tree root
+fn check_repo_path(repo: &repo::Repo) -> Result<(), String> {
+    path::check_subpath(&repo.fullname())
+        .map_err(|error| format!("Invalid repository name, refusing to touch it: {error}"))?;
+    if let Some(dir) = &repo.dir {
+        path::check_subpath(dir)
+            .map_err(|error| format!("Invalid repository dir, refusing to touch it: {error}"))?;
+    }
+    Ok(())
+}
+
+/// Moves a repository that was renamed on the forge to its new location
+fn move_renamed_repo(
+    repo_path: &Path,
+    repo: &repo::Repo,
+    moved_repos: &mut HashMap<String, PathBuf>,
+    move_renamed: bool,
+    report: &mut RepoReport<'_>,
+) -> Result<(), String> {
+    if repo_path.exists() {
+        return Ok(());
+    }
+
+    let old_path = match repo
+        .provider_id
+        .as_ref()
+        .and_then(|provider_id| moved_repos.remove(provider_id))
+    {
+        Some(old_path) => old_path,
+        None => return Ok(()),
+    };
+
+    if !move_renamed {
+        return Err(format!(
+            "Repository was renamed or transferred, it still exists at \"{}\". Use --move-renamed to move it",
+            path::path_as_string(&old_path)
+        ));
+    }
+
+    report.change(&format!(
+        "Repository was renamed or transferred, moving \"{}\" to \"{}\"",
+        path::path_as_string(&old_path),
+        path::path_as_string(repo_path)
+    ));
+    report.action = SyncAction::Moved;
+
+    if let Some(parent) = repo_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|error| format!("Failed creating parent directory: {error}"))?;
+    }
+    fs::rename(&old_path, repo_path).map_err(|error| {
+        format!(
+            "Failed moving \"{}\": {}",
+            path::path_as_string(&old_path),
+            error
+        )
+    })
+}
+
+/// What to do when a root that was never synced before contains things that do
+/// not belong to the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExistingRoot {
+    /// Sync into the root anyway
+    Allow,
+    /// Ask whether to sync into the root
+    Ask,
+    /// Fail without syncing anything
+    Fail,
+}
+
+/// The options of [`sync_trees()`]
+///
+/// The defaults are the ones of `grm repos sync config`, except that nothing is
+/// asked interactively.
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    /// Only sync the trees with this name or root
+    pub tree: Option<String>,
+    /// Check out the default worktree after cloning a worktree setup
+    pub init_worktree: bool,
+    /// Move repositories that were renamed or transferred on the forge
+    pub move_renamed: bool,
+    pub on_conflict: ConflictResolution,
+    /// Use this protocol for all remotes
+    pub rewrite_protocol: Option<repo::RemoteType>,
+    pub lock_policy: lock::LockPolicy,
+    /// Run the hooks of the repositories after they were cloned or updated
+    pub run_hooks: bool,
+    pub existing_root: ExistingRoot,
+    /// Print every step as it happens instead of one aligned line per repository
+    pub plain: bool,
+    /// How many repositories of a tree are synced at the same time
+    pub jobs: usize,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            tree: None,
+            init_worktree: true,
+            move_renamed: false,
+            on_conflict: ConflictResolution::Adopt,
+            rewrite_protocol: None,
+            lock_policy: lock::LockPolicy::Wait,
+            run_hooks: false,
+            existing_root: ExistingRoot::Fail,
+            plain: false,
+            jobs: 1,
+        }
+    }
+}
+
+/// The options of a sync that apply to every tree
+#[derive(Clone, Copy)]
+struct TreeSyncOptions {
+    init_worktree: bool,
+    move_renamed: bool,
+    on_conflict: ConflictResolution,
+    lock_policy: lock::LockPolicy,
+    retry: repo::RetryConfig,
+    share_objects: bool,
+    run_hooks: bool,
+    /// Print every step as it happens instead of one aligned line per repository
+    plain: bool,
+    /// How many repositories of a tree are synced at the same time
+    jobs: usize,
+}
+
+/// What a sync did to a single repository, shown in the action column of the
+/// sync output
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SyncAction {
+    Cloned,
+    Created,
+    Moved,
+    RemotesUpdated,
+    UpToDate,
+    Skipped,
+    Failed,
+}
+
+impl SyncAction {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Cloned => "cloned",
+            Self::Created => "created",
+            Self::Moved => "moved",
+            Self::RemotesUpdated => "remotes-updated",
+            Self::UpToDate => "up-to-date",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn summary_kind(self) -> SummaryKind {
+        match self {
+            Self::UpToDate => SummaryKind::Unchanged,
+            Self::Cloned | Self::Created | Self::Moved | Self::RemotesUpdated | Self::Skipped => {
+                SummaryKind::Changed
+            }
+            Self::Failed => SummaryKind::Failed,
+        }
+    }
+}
+
+/// Collects what happens to a single repository during a sync
+///
+/// With plain output, every step is printed right away. Otherwise, the steps are
+/// printed below the action of the repository once it is done.
+struct RepoReport<'a> {
+    repo: &'a str,
+    plain: bool,
+    action: SyncAction,
+    changes: Vec<String>,
+}
+
+impl<'a> RepoReport<'a> {
+    fn new(repo: &'a str, plain: bool) -> Self {
+        Self {
+            repo,
+            plain,
+            action: SyncAction::UpToDate,
+            changes: Vec::new(),
+        }
+    }
+
+    fn change(&mut self, message: &str) {
+        if self.plain {
+            print_repo_action(self.repo, message);
+        } else {
+            self.changes.push(message.to_string());
+        }
+    }
+
+    /// Steps that are only printed with plain output, as the action of the
+    /// repository already tells about them
+    fn step(&self, message: &str) {
+        if self.plain {
+            print_action(message);
+        }
+    }
+
+    fn success(&self, message: &str) {
+        if self.plain {
+            print_repo_success(self.repo, message);
+        }
+    }
+
+    /// Changing the remotes is only worth mentioning for existing repositories
+    fn remotes_updated(&mut self) {
+        if self.action == SyncAction::UpToDate {
+            self.action = SyncAction::RemotesUpdated;
+        }
+    }
+
+    fn print(&self, result: &Result<Vec<String>, String>) {
+        match result {
+            Ok(warnings) => print_repo_summary(
+                &self.action.summary_kind(),
+                self.action.label(),
+                self.repo,
+                &self.changes,
+                warnings,
+            ),
+            Err(error) => print_repo_summary(
+                &SyncAction::Failed.summary_kind(),
+                SyncAction::Failed.label(),
+                self.repo,
+                &self.changes,
+                std::slice::from_ref(error),
+            ),
+        }
+    }
+}
+
+/// The outcome of syncing a single tree
+struct TreeSyncResult {
+    repos: Vec<RepoResult>,
+    errors: Vec<String>,
+    managed_paths: Vec<PathBuf>,
+    unmanaged_paths: Vec<PathBuf>,
+}
+
+/// Returns the indices of the trees that each tree depends on
+///
+/// Fails for unknown trees and for trees that depend on each other.
+fn tree_dependencies(trees: &[SyncTree]) -> Result<Vec<Vec<usize>>, String> {
+    let mut dependencies = Vec::new();
+    for tree in trees {
+        let mut indices = Vec::new();
+        for dependency in &tree.depends_on {
+            let index = trees
+                .iter()
+                .position(|other| {
+                    config::tree_is_called(other.name.as_deref(), &other.root, dependency)
+                })
+                .ok_or_else(|| {
+                    format!(
+                        "Tree \"{}\" depends on unknown tree \"{}\"",
+                        tree.label(),
+                        dependency
+                    )
+                })?;
+            indices.push(index);
+        }
+        dependencies.push(indices);
+    }
+
+    // Remove trees without (remaining) dependencies until nothing changes. Whatever
+    // is left over is part of a cycle.
+    let mut remaining: Vec<usize> = (0..trees.len()).collect();
+    loop {
+        let before = remaining.len();
+        let snapshot = remaining.clone();
+        remaining.retain(|index| {
+            dependencies[*index]
+                .iter()
+                .any(|dependency| snapshot.contains(dependency))
+        });
+        if remaining.len() == before {
+            break;
+        }
+    }
+    if !remaining.is_empty() {
+        return Err(format!(
+            "Cyclic dependencies between trees: {}",
+            remaining
+                .iter()
+                .map(|index| trees[*index].label())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(dependencies)
+}
+
+fn sync_tree(
+    tree: &SyncTree,
+    options: TreeSyncOptions,
+    moved_repos: &Mutex<HashMap<String, PathBuf>>,
+    object_sources: &HashMap<String, Vec<PathBuf>>,
+    object_lenders: &HashSet<PathBuf>,
+) -> TreeSyncResult {
+    let root_path = &tree.root_path;
+    let repos = &tree.repos;
+
+    let mut result = TreeSyncResult {
+        repos: Vec::new(),
+        errors: Vec::new(),
+        managed_paths: repos
+            .iter()
+            .map(|repo| root_path.join(repo.directory()))
+            .collect(),
+        unmanaged_paths: Vec::new(),
+    };
+
+    let _tree_lock = match path::check_writable(root_path)
+        .and_then(|()| lock::lock_tree(root_path, options.lock_policy))
+    {
+        Ok(lock) => lock,
+        Err(error) => {
+            for repo in repos {
+                print_repo_error(&repo.name, &error);
+                result.repos.push(RepoResult {
+                    name: repo.fullname(),
+                    error: Some(error.clone()),
+                    warnings: Vec::new(),
+                    changes: Vec::new(),
+                });
+            }
+            return result;
+        }
+    };
+
+    // A broken state file only means that local changes are not detected
+    let tree_state = match state::read_state(&tree.state_root) {
+        Ok(tree_state) => tree_state,
+        Err(error) => {
+            print_error(&error);
+            result.errors.push(error);
+            state::TreeState::default()
+        }
+    };
+    let previous_state = tree_state.clone();
+    let tree_state = Mutex::new(tree_state);
+
+    let sync_one = |repo: &repo::Repo| {
+        let repo_path = root_path.join(repo.directory());
+        let new_directories = missing_directories(root_path, &repo_path);
+        let reference = options
+            .share_objects
+            .then(|| {
+                repo.remotes
+                    .iter()
+                    .flatten()
+                    .next()
+                    .and_then(|remote| object_sources.get(&remote.url))
+                    .and_then(|sources| {
+                        sources
+                            .iter()
+                            .find(|source| !source.starts_with(&repo_path))
+                    })
+            })
+            .flatten();
+        let mut report = RepoReport::new(&repo.name, options.plain);
+        let sync_result = check_repo_path(repo)
+            .and_then(|()| lock::lock_repo(&repo_path, options.lock_policy))
+            .and_then(|_repo_lock| {
+                move_renamed_repo(
+                    &repo_path,
+                    repo,
+                    &mut moved_repos
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner),
+                    options.move_renamed,
+                    &mut report,
+                )
+                .and_then(|()| {
+                    let phase = if repo_path.exists() {
+                        "update"
+                    } else {
+                        "clone"
+                    };
+                    timings::measure(phase, Some(repo), || {
+                        sync_repo(
+                            root_path,
+                            repo,
+                            options.init_worktree,
+                            options.on_conflict,
+                            tree.remote_name(repo),
+                            &tree_state,
+                            reference.map(PathBuf::as_path),
+                            tree.history_days,
+                            &options.retry,
+                            options.run_hooks,
+                            &mut report,
+                        )
+                    })
+                })
+                .and_then(|warnings| {
+                    if let Some(dir_mode) = tree.dir_mode {
+                        apply_dir_mode(&new_directories, dir_mode)?;
+                    }
+                    Ok(warnings)
+                })
+            });
+        let sync_result = sync_result.map(|mut warnings| {
+            match check_size_budget(&repo_path, repo, tree, object_lenders) {
+                Ok(budget_warnings) => warnings.extend(budget_warnings),
+                Err(error) => warnings.push(format!("Checking the disk usage failed: {error}")),
+            }
+            if let Ok(now) = notify::now() {
+                tree_state
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .set_last_sync(&repo.fullname(), now);
+            }
+            warnings
+        });
+        if options.plain {
+            match &sync_result {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        print_repo_warning(&repo.name, warning);
+                    }
+                    print_repo_success(&repo.name, "OK");
+                }
+                Err(error) => print_repo_error(&repo.name, error),
+            }
+        } else {
+            report.print(&sync_result);
+        }
+        let (error, warnings) = match sync_result {
+            Ok(warnings) => (None, warnings),
+            Err(error) => (Some(error), Vec::new()),
+        };
+        RepoResult {
+            name: repo.fullname(),
+            error,
+            warnings,
+            changes: Vec::new(),
+        }
+    };
+
+    // Up to `jobs` repositories are synced at the same time, in the order of
+    // the configuration. The results are kept in that order as well.
+    let nested = nested_repos(repos);
+    let next = Mutex::new(0);
+    let done = Mutex::new(vec![false; repos.len()]);
+    let done_changed = Condvar::new();
+    let repo_results: Mutex<Vec<Option<RepoResult>>> =
+        Mutex::new(repos.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..options.jobs.clamp(1, repos.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = {
+                    let mut next = next
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    *next += 1;
+                    *next - 1
+                };
+                let Some(repo) = repos.get(index) else {
+                    break;
+                };
+                let _mark_done = MarkDone {
+                    index,
+                    done: &done,
+                    done_changed: &done_changed,
+                };
+                wait_until_done(&done, &done_changed, &nested[index]);
+                let repo_result = sync_one(repo);
+                repo_results
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)[index] = Some(repo_result);
+            });
+        }
+    });
+    result.repos.extend(
+        repo_results
+            .into_inner()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .into_iter()
+            .flatten(),
+    );
+
+    let mut tree_state = tree_state
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    tree_state.retain(&tree.state_repos);
+    // The git directory of a standalone repository is missing if cloning failed
+    if tree_state != previous_state && (!tree.standalone || tree.state_root.exists()) {
+        if let Err(error) = state::write_state(&tree.state_root, &tree_state) {
+            print_error(&error);
+            result.errors.push(error);
+        }
+    }
+
+    if tree.standalone {
+        return result;
+    }
+
+    match find_unmanaged_repos(root_path, repos) {
+        Ok(paths) => result.unmanaged_paths = paths,
+        Err(error) => {
+            let error = format!("Error getting unmanaged repos: {error}");
+            print_error(&error);
+            result.errors.push(error);
+        }
+    }
+
+    result
+}
+
+/// Checks the disk usage of a repository against the `max_size_mb` of its tree.
+/// Repositories over the budget have their history trimmed to the
+/// `history_days` of the tree first, if set.
+///
+/// Trimming is skipped for repositories with stashes, as the commits they are
+/// based on may be trimmed, and for repositories that other clones borrow
+/// objects from (see `share_objects`), as these clones would lose the trimmed
+/// objects.
+///
+/// Returns warnings if the history was not trimmed or the repository is still
+/// over the budget.
+fn check_size_budget(
+    repo_path: &Path,
+    repo: &repo::Repo,
+    tree: &SyncTree,
+    object_lenders: &HashSet<PathBuf>,
+) -> Result<Vec<String>, String> {
+    let Some(max_size_mb) = tree.max_size_mb else {
+        return Ok(Vec::new());
+    };
+    let budget = max_size_mb.saturating_mul(1024 * 1024);
+
+    let mut warnings = Vec::new();
+    let mut size = stats::disk_usage(repo_path)?;
+    if size > budget {
+        if let Some(history_days) = tree.history_days {
+            let git_dir = get_actual_git_directory(repo_path, repo.worktree_setup);
+            if repo::has_stashes(&git_dir)? {
+                warnings.push(String::from(
+                    "Not trimming the history, the repository has stashes",
+                ));
+            } else if objects_directory(repo_path, repo.worktree_setup)
+                .canonicalize()
+                .is_ok_and(|objects| object_lenders.contains(&objects))
+            {
+                warnings.push(String::from(
+                    "Not trimming the history, other clones borrow objects from the repository",
+                ));
+            } else {
+                print_repo_action(
+                    &repo.name,
+                    &format!(
+                        "Repository uses {}, trimming its history to the last {} days",
+                        stats::format_size(size),
+                        history_days
+                    ),
+                );
+                repo::trim_history(&git_dir, history_days)?;
+                size = stats::disk_usage(repo_path)?;
+            }
+        }
+    }
+
+    if size > budget {
+        warnings.push(format!(
+            "Repository uses {}, more than the budget of {} MiB",
+            stats::format_size(size),
+            max_size_mb
+        ));
+    }
+    Ok(warnings)
+}
+
+/// Marks a tree (or repository) as done when dropped, so the ones that wait for
+/// it are not stuck even if syncing it panics
+struct MarkDone<'a> {
+    index: usize,
+    done: &'a Mutex<Vec<bool>>,
+    done_changed: &'a Condvar,
+}
+
+impl Drop for MarkDone<'_> {
+    fn drop(&mut self) {
+        self.done
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)[self.index] = true;
+        self.done_changed.notify_all();
+    }
+}
+
+/// Blocks until all of `dependencies` are marked as done, see [`MarkDone`]
+fn wait_until_done(done: &Mutex<Vec<bool>>, done_changed: &Condvar, dependencies: &[usize]) {
+    let mut done_guard = done
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    while !dependencies
+        .iter()
+        .all(|dependency| done_guard[*dependency])
+    {
+        done_guard = done_changed
+            .wait(done_guard)
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+    }
+}
+
+/// For every repository, the earlier repositories of the tree that it is nested
+/// in or that are nested in it
+///
+/// These have to be synced one after the other, as a clone cannot go into a
+/// directory that another clone is writing to.
+fn nested_repos(repos: &[repo::Repo]) -> Vec<Vec<usize>> {
+    let directories: Vec<PathBuf> = repos
+        .iter()
+        .map(|repo| PathBuf::from(repo.directory()))
+        .collect();
+    directories
+        .iter()
+        .enumerate()
+        .map(|(index, directory)| {
+            directories[..index]
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| directory.starts_with(other) || other.starts_with(directory))
+                .map(|(other_index, _)| other_index)
+                .collect()
+        })
+        .collect()
+}
+
+/// Prepares all trees of the configuration for syncing
+fn sync_trees_from_config(
+    config: config::Config,
+    rewrite_protocol: Option<&repo::RemoteType>,
+) -> Result<Vec<SyncTree>, String> {
+    let host_mappings = config.host_mappings();
+
+    let mut trees: Vec<SyncTree> = config
+        .trees()?
+        .into_iter()
+        .map(|tree| {
+            Ok(SyncTree {
+                dir_mode: tree.dir_mode.as_deref().map(parse_dir_mode).transpose()?,
+                root_path: path::expand_path(Path::new(&tree.root)),
+                state_root: tree.state_root(),
+                state_repos: Vec::new(),
+                root: tree.root,
+                repos: tree
+                    .repos
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|repo| match rewrite_protocol {
+                        Some(protocol) => {
+                            rewrite_remotes(repo.into_repo(), protocol, &host_mappings)
+                        }
+                        None => repo.into_repo(),
+                    })
+                    .collect(),
+                canonical_remote_name: tree.canonical_remote_name,
+                name: tree.name,
+                serial: tree.serial.unwrap_or(false),
+                depends_on: tree.depends_on.unwrap_or_default(),
+                standalone: tree.standalone,
+                history_days: tree.history_days,
+                max_size_mb: tree.max_size_mb,
+            })
+        })
+        .collect::<Result<Vec<SyncTree>, String>>()?;
+
+    check_overlapping_trees(&trees)?;
+
+    let state_repos: Vec<Vec<String>> = trees
+        .iter()
+        .map(|tree| {
+            trees
+                .iter()
+                .filter(|other| other.state_root == tree.state_root)
+                .flat_map(|other| other.repos.iter().map(repo::Repo::fullname))
+                .collect()
+        })
+        .collect();
+    for (tree, state_repos) in trees.iter_mut().zip(state_repos) {
+        tree.state_repos = state_repos;
+    }
+
+    Ok(trees)
+}
+
+/// The paths of all repositories of `trees`
+fn managed_paths(trees: &[SyncTree]) -> Vec<PathBuf> {
+    trees
+        .iter()
+        .flat_map(|tree| {
+            tree.repos
+                .iter()
+                .map(|repo| tree.root_path.join(repo.directory()))
+        })
+        .collect()
+}
+
+/// Keeps only the trees meant by `name_or_root`, like [`config::select_trees()`]
+///
+/// Dependencies on the other trees are dropped, as those are not synced.
+fn select_sync_trees(
+    trees: Vec<SyncTree>,
+    name_or_root: Option<&str>,
+) -> Result<Vec<SyncTree>, String> {
+    let Some(name_or_root) = name_or_root else {
+        return Ok(trees);
+    };
+    let (mut selected, others): (Vec<SyncTree>, Vec<SyncTree>) = trees
+        .into_iter()
+        .partition(|tree| config::tree_is_called(tree.name.as_deref(), &tree.root, name_or_root));
+    if selected.is_empty() {
+        return Err(format!(
+            "There is no tree with name or root \"{name_or_root}\""
+        ));
+    }
+    for tree in &mut selected {
+        tree.depends_on.retain(|dependency| {
+            !others
+                .iter()
+                .any(|other| config::tree_is_called(other.name.as_deref(), &other.root, dependency))
+        });
+    }
+    Ok(selected)
+}
+
+/// Parses permissions like `0750` (or `2770` with the setgid bit)
+fn parse_dir_mode(mode: &str) -> Result<u32, String> {
+    match u32::from_str_radix(mode, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!(
+            "Invalid dir_mode \"{mode}\", expected octal permissions like \"0750\""
+        )),
+    }
+}
+
+/// The directories between `root_path` (inclusive) and `repo_path` that do not
+/// exist yet
+fn missing_directories(root_path: &Path, repo_path: &Path) -> Vec<PathBuf> {
+    repo_path
+        .ancestors()
+        .take_while(|directory| !directory.exists())
+        .filter(|directory| directory.starts_with(root_path))
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// Sets the permissions of newly created directories, regardless of the umask
+fn apply_dir_mode(directories: &[PathBuf], mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    for directory in directories.iter().filter(|directory| directory.is_dir()) {
+        fs::set_permissions(directory, fs::Permissions::from_mode(mode)).map_err(|error| {
+            format!(
+                "Failed setting permissions of \"{}\": {}",
+                path::path_as_string(directory),
+                error
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Makes sure that no repository is part of more than one tree
+///
+/// Multiple trees can share the same root, as long as their repositories do not
+/// overlap.
+fn check_overlapping_trees(trees: &[SyncTree]) -> Result<(), String> {
+    let mut paths: HashMap<PathBuf, &SyncTree> = HashMap::new();
+    for tree in trees {
+        for repo in &tree.repos {
+            let repo_path = tree.root_path.join(repo.directory());
+            match paths.get(&repo_path) {
+                Some(other) if !std::ptr::eq(*other, tree) => {
+                    return Err(format!(
+                        "Repository \"{}\" is part of both tree \"{}\" and tree \"{}\"",
+                        path::path_as_string(&repo_path),
+                        other.label(),
+                        tree.label()
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    paths.insert(repo_path, tree);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the entries in the root of `tree` that have nothing to do with the
+/// configuration, if the tree was never synced before
+///
+/// Entries are related if they are git repositories or (part of) the path of a
+/// configured repository of any tree with the same root. Hidden entries are
+/// ignored.
+fn unrelated_root_entries(tree: &SyncTree, trees: &[SyncTree]) -> Result<Vec<String>, String> {
+    // Without repositories, nothing is cloned. A missing root (or one that is no
+    // directory at all) is handled by the sync.
+    if tree.standalone
+        || tree.repos.is_empty()
+        || !tree.root_path.is_dir()
+        || tree.state_root.join(state::STATE_FILE_NAME).exists()
+    {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(&tree.root_path).map_err(|error| {
+        format!(
+            "Failed to open \"{}\": {}",
+            path::path_as_string(&tree.root_path),
+            error
+        )
+    })?;
+
+    let configured_dirs: HashSet<String> = trees
+        .iter()
+        .filter(|other| other.root_path == tree.root_path)
+        .flat_map(|other| &other.repos)
+        .filter_map(|repo| {
+            Path::new(&repo.directory())
+                .components()
+                .next()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        })
+        .collect();
+
+    let mut unrelated = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|error| format!("Error accessing directory: {error}"))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let entry_path = entry.path();
+        if name.starts_with('.')
+            || configured_dirs.contains(&name)
+            || entry_path.join(".git").exists()
+            || entry_path
+                .join(worktree::GIT_MAIN_WORKTREE_DIRECTORY)
+                .exists()
+        {
+            continue;
+        }
+        unrelated.push(name);
+    }
+    unrelated.sort();
+    Ok(unrelated)
+}
+
+/// Makes sure that `grm` does not clone into a directory that is used for
+/// something else, e.g. when the root was misconfigured
+///
+/// Depending on `existing_root`, such a root is synced anyway, only after
+/// confirmation or not at all.
+fn confirm_existing_roots(trees: &[SyncTree], existing_root: ExistingRoot) -> Result<(), String> {
+    if existing_root == ExistingRoot::Allow {
+        return Ok(());
+    }
+
+    let mut checked_roots = HashSet::new();
+    for tree in trees {
+        if !checked_roots.insert(&tree.root_path) {
+            continue;
+        }
+        let unrelated = unrelated_root_entries(tree, trees)?;
+        if unrelated.is_empty() {
+            continue;
+        }
+
+        const SHOWN_ENTRIES: usize = 5;
+        let mut entries = unrelated
+            .iter()
+            .take(SHOWN_ENTRIES)
+            .map(|name| format!("\"{name}\""))
+            .collect::<Vec<String>>()
+            .join(", ");
+        if unrelated.len() > SHOWN_ENTRIES {
+            entries.push_str(&format!(" and {} more", unrelated.len() - SHOWN_ENTRIES));
+        }
+        let message = format!(
+            "Root \"{}\" already contains things that do not belong to the configuration: {}",
+            path::path_as_string(&tree.root_path),
+            entries
+        );
+
+        if existing_root == ExistingRoot::Fail {
+            return Err(format!(
+                "{message}. Use --allow-existing-root to sync into it anyway"
+            ));
+        }
+        print_warning(&message);
+        match prompt("Sync into it anyway? [y/N]")?
+            .to_lowercase()
+            .as_str()
+        {
+            "y" | "yes" => {}
+            _ => return Err(String::from("Aborted, nothing was synced")),
+        }
+    }
+    Ok(())
+}
+
+/// Syncs all trees of the configuration
+///
+/// Trees are synced in parallel, except for trees with `serial` set, which are
+/// synced one at a time. A tree with `depends_on` is only synced after the trees
+/// it depends on. Up to `jobs` repositories of a single tree are synced at the
+/// same time, except for repositories that are nested in each other. With
+/// `ConflictResolution::Ask`, everything is synced one after the other.
+///
+/// Trees that were never synced before are only synced into a root that
+/// contains nothing unrelated to the configuration, unless `existing_root` says
+/// otherwise.
+pub fn sync_trees(config: config::Config, options: &SyncOptions) -> Result<RunResult, String> {
+    let retry = config.retry();
+    let share_objects = config::CloneConfig::share_objects(config.clone_config());
+
+    let trees = sync_trees_from_config(config, options.rewrite_protocol.as_ref())?;
+    // The repositories of trees that are not synced are not unmanaged either
+    let mut managed_repos_absolute_paths = managed_paths(&trees);
+    // Repositories of other trees may borrow objects as well
+    let object_lenders = find_object_lenders(&trees, share_objects);
+    let trees = select_sync_trees(trees, options.tree.as_deref())?;
+
+    confirm_existing_roots(&trees, options.existing_root)?;
+
+    let dependencies = tree_dependencies(&trees)?;
+
+    // Conflicts cannot be asked about for several trees or repositories at the
+    // same time
+    let all_serial = options.on_conflict == ConflictResolution::Ask;
+
+    let options = TreeSyncOptions {
+        init_worktree: options.init_worktree,
+        move_renamed: options.move_renamed,
+        on_conflict: options.on_conflict,
+        lock_policy: options.lock_policy,
+        retry,
+        share_objects,
+        run_hooks: options.run_hooks,
+        // JSON messages are meant for machines, they stay one event per step
+        plain: options.plain || log_json(),
+        jobs: if all_serial { 1 } else { options.jobs },
+    };
+
+    let moved_repos = Mutex::new(find_moved_repos(&trees));
+    let object_sources = find_object_sources(&trees);
+    let done = Mutex::new(vec![false; trees.len()]);
+    let done_changed = Condvar::new();
+    let serial_lock = Mutex::new(());
+    // Trees with the same root share the tree lock and the state file, so they
+    // are synced one after the other
+    let root_locks: HashMap<&Path, Mutex<()>> = trees
+        .iter()
+        .map(|tree| (tree.root_path.as_path(), Mutex::new(())))
+        .collect();
+
+    let tree_results: Vec<TreeSyncResult> = std::thread::scope(|scope| {
+        let handles: Vec<_> = trees
+            .iter()
+            .zip(&dependencies)
+            .enumerate()
+            .map(|(index, (tree, dependencies))| {
+                let (moved_repos, object_sources, object_lenders, done, done_changed) = (
+                    &moved_repos,
+                    &object_sources,
+                    &object_lenders,
+                    &done,
+                    &done_changed,
+                );
+                let (serial_lock, root_locks) = (&serial_lock, &root_locks);
+                scope.spawn(move || {
+                    let _mark_done = MarkDone {
+                        index,
+                        done,
+                        done_changed,
+                    };
+
+                    wait_until_done(done, done_changed, dependencies);
+
+                    let _serial_guard = (all_serial || tree.serial).then(|| {
+                        serial_lock
+                            .lock()
+                            .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    });
+                    let _root_guard = root_locks[tree.root_path.as_path()]
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    sync_tree(tree, options, moved_repos, object_sources, object_lenders)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Syncing a tree panicked"))
+            .collect()
+    });
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    let mut unmanaged_repos_absolute_paths: Vec<PathBuf> = vec![];
+
+    for tree_result in tree_results {
+        results.extend(tree_result.repos);
+        errors.extend(tree_result.errors);
+        managed_repos_absolute_paths.extend(tree_result.managed_paths);
+        for path in tree_result.unmanaged_paths {
+            if !unmanaged_repos_absolute_paths.contains(&path) {
+                unmanaged_repos_absolute_paths.push(path);
+            }
+        }
+    }
+
+    for unmanaged_repo_absolute_path in &unmanaged_repos_absolute_paths {
+        if managed_repos_absolute_paths
+            .iter()
+            .any(|managed_repo_absolute_path| {
+                managed_repo_absolute_path == unmanaged_repo_absolute_path
+            })
+        {
+            continue;
+        }
+        print_warning(&format!(
+            "Found unmanaged repository: \"{}\"",
+            path::path_as_string(unmanaged_repo_absolute_path)
+        ));
+    }
+
+    Ok(RunResult {
+        repos: results,
+        errors,
+    })
+}
+
+/// Checks whether the repositories on disk match the configuration, without
+/// changing anything
+///
+/// Everything that a sync would change (or fail on) is reported as an error of
+/// the repository. Unmanaged repositories are only warned about, like during a
+/// sync. With `tree`, only the trees with that name or root are checked.
+pub fn check_trees(
+    config: config::Config,
+    tree: Option<&str>,
+    rewrite_protocol: Option<repo::RemoteType>,
+) -> Result<RunResult, String> {
+    let trees = sync_trees_from_config(config, rewrite_protocol.as_ref())?;
+    tree_dependencies(&trees)?;
+
+    let managed_paths = managed_paths(&trees);
+    let trees = select_sync_trees(trees, tree)?;
+    let moved_repos = find_moved_repos(&trees);
+
+    let mut result = RunResult {
+        repos: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut unmanaged_paths: Vec<PathBuf> = Vec::new();
+
+    for tree in &trees {
+        let tree_state = match state::read_state(&tree.state_root) {
+            Ok(tree_state) => tree_state,
+            Err(error) => {
+                print_error(&error);
+                result.errors.push(error);
+                state::TreeState::default()
+            }
+        };
+
+        for repo in &tree.repos {
+            let error = match check_repo(tree, repo, &tree_state, &moved_repos) {
+                Ok(problems) if problems.is_empty() => {
+                    print_repo_success(&repo.name, "OK");
+                    None
+                }
+                Ok(problems) => {
+                    for problem in &problems {
+                        print_repo_error(&repo.name, problem);
+                    }
+                    Some(problems.join(", "))
+                }
+                Err(error) => {
+                    print_repo_error(&repo.name, &error);
+                    Some(error)
+                }
+            };
+            result.repos.push(RepoResult {
+                name: repo.fullname(),
+                error,
+                warnings: Vec::new(),
+                changes: Vec::new(),
+            });
+        }
+
+        // Missing repositories are reported already
+        if tree.standalone || !tree.root_path.exists() {
+            continue;
+        }
+        match find_unmanaged_repos(&tree.root_path, &tree.repos) {
+            Ok(paths) => {
+                for path in paths {
+                    if !managed_paths.contains(&path) && !unmanaged_paths.contains(&path) {
+                        unmanaged_paths.push(path);
+                    }
+                }
+            }
+            Err(error) => {
+                let error = format!("Error getting unmanaged repos: {error}");
+                print_error(&error);
+                result.errors.push(error);
+            }
+        }
+    }
+
+    for unmanaged_path in &unmanaged_paths {
+        print_warning(&format!(
+            "Found unmanaged repository: \"{}\"",
+            path::path_as_string(unmanaged_path)
+        ));
+    }
+
+    Ok(result)
+}
+
+/// Returns everything about a single repository that does not match the
+/// configuration
+fn check_repo(
+    tree: &SyncTree,
+    repo: &repo::Repo,
+    tree_state: &state::TreeState,
+    moved_repos: &HashMap<String, PathBuf>,
+) -> Result<Vec<String>, String> {
+    check_repo_path(repo)?;
+    let repo = match tree.remote_name(repo) {
+        Some(canonical_remote_name) => with_canonical_remote_name(repo, canonical_remote_name)?.0,
+        None => repo.clone(),
+    };
+    let repo_path = tree.root_path.join(repo.directory());
+
+    if !is_non_empty_directory(&repo_path)? {
+        return Ok(vec![match repo
+            .provider_id
+            .as_ref()
+            .and_then(|provider_id| moved_repos.get(provider_id))
+        {
+            Some(old_path) => format!(
+                "Repository was renamed or transferred, it still exists at \"{}\"",
+                path::path_as_string(old_path)
+            ),
+            None => String::from("Repository does not exist"),
+        }]);
+    }
+
+    let repo_handle = repo::RepoSession::open(&repo_path, repo.worktree_setup);
+    if let Some(conflict) = find_conflict(&repo_path, &repo, repo_handle.as_ref().ok())? {
+        return Ok(vec![conflict.description()]);
+    }
+
+    let repo_handle = match repo_handle {
+        Ok(repo_handle) => repo_handle,
+        Err(error) => {
+            return if repo::RepoHandle::open(&repo_path, !repo.worktree_setup).is_ok() {
+                Ok(vec![String::from(if repo.worktree_setup {
+                    "Repository is not using a worktree setup"
+                } else {
+                    "Repository is using a worktree setup"
+                })])
+            } else {
+                Err(format!("Opening repository failed: {error}"))
+            };
+        }
+    };
+
+    let mut problems = Vec::new();
+
+    if let Some(remotes) = &repo.remotes {
+        let desired: Vec<state::AppliedRemote> = remotes
+            .iter()
+            .map(|remote| state::AppliedRemote {
+                name: remote.name.clone(),
+                url: remote.url.clone(),
+            })
+            .collect();
+
+        let mut actual = Vec::new();
+        for remote_name in repo_handle.remotes()? {
+            if let Some(remote) = repo_handle.find_remote(&remote_name)? {
+                actual.push(state::AppliedRemote {
+                    name: remote_name,
+                    url: remote.url(),
+                });
+            }
+        }
+
+        // Local changes to configured remotes are drift as well, so only the
+        // deletions depend on what was applied before
+        let changes = state::plan_remotes(&desired, None, &actual)
+            .into_iter()
+            .chain(
+                state::plan_remotes(&desired, tree_state.remotes(&repo.fullname()), &actual)
+                    .into_iter()
+                    .filter(|change| matches!(change, state::RemoteChange::Delete(_))),
+            );
+        for change in changes {
+            problems.push(match change {
+                state::RemoteChange::Add(remote) => {
+                    format!("Remote \"{}\" is missing", remote.name)
+                }
+                state::RemoteChange::SetUrl(remote) => format!(
+                    "Remote \"{}\" does not point to \"{}\"",
+                    remote.name, remote.url
+                ),
+                state::RemoteChange::Delete(remote_name) => {
+                    format!("Remote \"{remote_name}\" is not part of the configuration anymore")
+                }
+            });
+        }
+    }
+
+    if repo.worktree_setup {
+        for worktree in repo_handle.broken_worktrees()? {
+            problems.push(format!("Worktree \"{worktree}\" does not exist anymore"));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// How many remotes are verified at the same time
+const VERIFY_REMOTES_PARALLEL: usize = 8;
+
+/// A configured remote that could not be read
+pub struct DeadRemote {
+    /// The name of the repository, including the namespace
+    pub repo: String,
+    pub remote: String,
+    pub url: String,
+    pub reason: String,
+}
+
+/// Checks that all configured remotes exist and can be read, in parallel and
+/// without touching the repositories
+///
+/// Returns the number of checked remotes and the ones that failed, in the order
+/// of the configuration. With `tree`, only the trees with that name or root are
+/// checked.
+pub fn verify_remotes(
+    config: config::Config,
+    tree: Option<&str>,
+    timeout: std::time::Duration,
+) -> Result<(usize, Vec<DeadRemote>), String> {
+    let mut remotes = Vec::new();
+    for tree in config::select_trees(config.trees()?, tree)? {
+        for repo in tree.repos.unwrap_or_default() {
+            let repo = repo.into_repo();
+            for remote in repo.remotes.iter().flatten() {
+                remotes.push((repo.fullname(), remote.name.clone(), remote.url.clone()));
+            }
+        }
+    }
+
+    let next = Mutex::new(0);
+    let results: Mutex<Vec<Option<String>>> = Mutex::new(vec![None; remotes.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..VERIFY_REMOTES_PARALLEL.min(remotes.len()) {
+            scope.spawn(|| loop {
+                let index = {
+                    let mut next = next
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    *next += 1;
+                    *next - 1
+                };
+                let Some((_, _, url)) = remotes.get(index) else {
+                    break;
+                };
+                if let Err(error) = repo::verify_remote(url, timeout) {
+                    results
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner)[index] = Some(error);
+                }
+            });
+        }
+    });
+
+    let results = results
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let count = remotes.len();
+    Ok((
+        count,
+        remotes
+            .into_iter()
+            .zip(results)
+            .filter_map(|((repo, remote, url), reason)| {
+                reason.map(|reason| DeadRemote {
+                    repo,
+                    remote,
+                    url,
+                    reason,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Fetches all remotes of all configured repositories
+///
+/// If `remotes` is not empty, only these remotes are fetched. Otherwise, with
+/// `fetch.only_configured` set in the configuration, remotes that are not part
+/// of the configuration are skipped.
+///
+/// Failures of single repositories are reported and returned, they do not
+/// abort the whole run.
+pub fn fetch_trees(
+    config: config::Config,
+    remotes: &[String],
+    lock_policy: lock::LockPolicy,
+) -> Result<RunResult, String> {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+
+    let only_configured = config::FetchConfig::only_configured(config.fetch());
+    let prune = config::FetchConfig::prune(config.fetch());
+    let retry = config.retry();
+
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        let state_root = tree.state_root();
+        let mut fetched = Vec::new();
+
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            let remotes = if !remotes.is_empty() {
+                Some(remotes.to_vec())
+            } else if only_configured {
+                Some(
+                    repo.remotes
+                        .iter()
+                        .flatten()
+                        .map(|remote| remote.name.clone())
+                        .collect(),
+                )
+            } else {
+                None
+            };
+
+            let result = timings::measure("fetch", Some(&repo), || {
+                fetch_repo(
+                    &root_path,
+                    &repo,
+                    remotes.as_deref(),
+                    lock_policy,
+                    &retry,
+                    prune,
+                )
+            });
+            let (error, warnings, changes) = match result {
+                Ok((changes, warnings)) => {
+                    print_repo_success(&repo.name, &fetch_message(remotes.as_deref()));
+                    for change in &changes {
+                        print_repo_action(&repo.name, &change.to_string());
+                    }
+                    for warning in &warnings {
+                        print_repo_warning(&repo.name, warning);
+                    }
+                    fetched.push(repo.fullname());
+                    (None, warnings, changes)
+                }
+                Err(error) => {
+                    print_repo_error(&repo.name, &error);
+                    (Some(error), Vec::new(), Vec::new())
+                }
+            };
+            results.push(RepoResult {
+                name: repo.fullname(),
+                error,
+                warnings,
+                changes,
+            });
+        }
+
+        if let Err(error) = record_fetches(&root_path, &state_root, &fetched, lock_policy) {
+            print_error(&error);
+            errors.push(error);
+        }
+    }
+
+    Ok(RunResult {
+        repos: results,
+        errors,
+    })
+}
+
+/// Remembers in the state of the tree that the repositories in `fetched` were
+/// just fetched, so `repos status --stale-after` does not count them as stale
+fn record_fetches(
+    root_path: &Path,
+    state_root: &Path,
+    fetched: &[String],
+    lock_policy: lock::LockPolicy,
+) -> Result<(), String> {
+    // The git directory of a standalone repository is missing if it was never
+    // cloned
+    if fetched.is_empty() || !state_root.exists() {
+        return Ok(());
+    }
+
+    let now = notify::now()?;
+    let _tree_lock = lock::lock_tree(root_path, lock_policy)?;
+    let mut tree_state = state::read_state(state_root)?;
+    for name in fetched {
+        tree_state.set_last_fetch(name, now);
+    }
+    state::write_state(state_root, &tree_state)
+}
+
+/// Switches the remotes of a repository to `protocol` where the host is known,
+/// so that sync updates the remotes of existing repositories as well
+fn rewrite_remotes(
+    mut repo: repo::Repo,
+    protocol: &repo::RemoteType,
+    host_mappings: &[repo::HostMapping],
+) -> repo::Repo {
+    for remote in repo.remotes.iter_mut().flatten() {
+        if let Some(url) = repo::rewrite_remote_url(&remote.url, protocol, host_mappings) {
+            remote.url = url;
+            remote.remote_type = protocol.clone();
+        }
+    }
+    repo
+}
+
+/// Describes which remotes were fetched, `None` meaning all of them
+pub fn fetch_message(remotes: Option<&[String]>) -> String {
+    match remotes {
+        None => String::from("Fetched from all remotes"),
+        Some([]) => String::from("No remotes to fetch from"),
+        Some(remotes) => format!("Fetched from {}", remotes.join(", ")),
+    }
+}
+
+fn fetch_repo(
+    root_path: &Path,
+    repo: &repo::Repo,
+    remotes: Option<&[String]>,
+    lock_policy: lock::LockPolicy,
+    retry: &repo::RetryConfig,
+    prune: Option<bool>,
+) -> Result<(Vec<repo::BranchChanges>, Vec<String>), String> {
+    check_repo_path(repo)?;
+
+    let repo_path = root_path.join(repo.directory());
+
+    if !repo_path.exists() {
+        return Err(String::from("Repository does not exist. Run sync?"));
+    }
+    path::check_writable(&repo_path)?;
+
+    let _lock = lock::lock_repo(&repo_path, lock_policy)?;
+
+    let repo_handle = repo::RepoHandle::open(&repo_path, repo.worktree_setup)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+
+    // Failing to summarize the new commits must not fail the fetch itself
+    let heads = repo_handle.remote_heads();
+
+    match remotes {
+        None => repo_handle.fetchall(retry, prune),
+        Some(remotes) => repo_handle.fetch_only(remotes, retry, prune),
+    }
+    .map_err(|error| format!("Error fetching remotes: {error}"))?;
+
+    match heads.and_then(|heads| repo_handle.remote_changes(&heads)) {
+        Ok(changes) => Ok((changes, Vec::new())),
+        Err(error) => Ok((
+            Vec::new(),
+            vec![format!("Summarizing the new commits failed: {error}")],
+        )),
+    }
+}
+
+/// Returns the paths of all repositories with a worktree setup that have at least
+/// one of `tags`. Without any tags, all repositories with a worktree setup match.
+fn worktree_repos(
+    config: config::Config,
+    tags: &[String],
+) -> Result<Vec<(PathBuf, repo::Repo)>, String> {
+    let mut repos = Vec::new();
+
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            if !repo.worktree_setup {
+                continue;
+            }
+            if !tags.is_empty() && !repo.tags.iter().any(|tag| tags.contains(tag)) {
+                continue;
+            }
+            check_repo_path(&repo)?;
+            repos.push((root_path.join(repo.directory()), repo));
+        }
+    }
+
+    if repos.is_empty() {
+        return Err(String::from("No repository with a worktree setup matches"));
+    }
+
+    Ok(repos)
+}
+
+/// Runs `action` on every repository from [`worktree_repos()`], collecting the
+/// results. `action` returns a success message and warnings.
+fn for_worktree_repos<F>(
+    config: config::Config,
+    tags: &[String],
+    lock_policy: lock::LockPolicy,
+    action: F,
+) -> Result<RunResult, String>
+where
+    F: Fn(&Path) -> Result<(String, Vec<String>), String>,
+{
+    let mut results = Vec::new();
+
+    for (repo_path, repo) in worktree_repos(config, tags)? {
+        let result = if repo_path.exists() {
+            path::check_writable(&repo_path)
+                .and_then(|()| lock::lock_repo(&repo_path, lock_policy))
+                .and_then(|_lock| action(&repo_path))
+        } else {
+            Err(String::from("Repository does not exist. Run sync?"))
+        };
+
+        let (error, warnings) = match result {
+            Ok((message, warnings)) => {
+                print_repo_success(&repo.name, &message);
+                for warning in &warnings {
+                    print_repo_warning(&repo.name, warning);
+                }
+                (None, warnings)
+            }
+            Err(error) => {
+                print_repo_error(&repo.name, &error);
+                (Some(error), Vec::new())
+            }
+        };
+
+        results.push(RepoResult {
+            name: repo.fullname(),
+            error,
+            warnings,
+            changes: Vec::new(),
+        });
+    }
+
+    Ok(RunResult {
+        repos: results,
+        errors: Vec::new(),
+    })
+}
+
+/// Creates a worktree `name` in all repositories with a worktree setup that have
+/// one of `tags`, for changes that span several repositories
+pub fn add_worktrees(
+    config: config::Config,
+    name: &str,
+    tags: &[String],
+    lock_policy: lock::LockPolicy,
+) -> Result<RunResult, String> {
+    for_worktree_repos(config, tags, lock_policy, |repo_path| {
+        let repo_handle = repo::RepoHandle::open(repo_path, true)
+            .map_err(|error| format!("Opening repository failed: {error}"))?;
+        if repo_handle.find_worktree(name).is_ok() {
+            return Ok((format!("Worktree {name} already exists"), Vec::new()));
+        }
+
+        let warnings = worktree::add_worktree(repo_path, name, None, false)?;
+        Ok((
+            format!("Worktree {name} created"),
+            warnings.unwrap_or_default(),
+        ))
+    })
+}
+
+/// Deletes the worktree `name` from all repositories with a worktree setup that
+/// have one of `tags`. Worktrees with changes are kept, unless `overrides` say
+/// otherwise.
+pub fn delete_worktrees(
+    config: config::Config,
+    name: &str,
+    tags: &[String],
+    overrides: repo::WorktreeRemoveOverrides,
+    lock_policy: lock::LockPolicy,
+) -> Result<RunResult, String> {
+    for_worktree_repos(config, tags, lock_policy, |repo_path| {
+        let repo_handle = repo::RepoHandle::open(repo_path, true)
+            .map_err(|error| format!("Opening repository failed: {error}"))?;
+        if repo_handle.find_worktree(name).is_err() {
+            return Ok((format!("Worktree {name} does not exist"), Vec::new()));
+        }
+
+        let worktree_config = repo::read_worktree_root_config(repo_path)?;
+
+        match repo_handle.remove_worktree(
+            repo_path,
+            name,
+            Path::new(name),
+            overrides,
+            &worktree_config,
+        ) {
+            Ok(archive) => Ok((
+                format!(
+                    "Worktree {name} deleted{}",
+                    repo::archived_to(archive.as_deref())
+                ),
+                Vec::new(),
+            )),
+            Err(repo::WorktreeRemoveFailureReason::Error(error)) => Err(error),
+            Err(repo::WorktreeRemoveFailureReason::Changes(changes)) => Err(format!(
+                "Changes in worktree {name}: {changes}. Refusing to delete"
+            )),
+            Err(repo::WorktreeRemoveFailureReason::Submodules(problems)) => Err(format!(
+                "Submodules of worktree {name} would lose work: {problems}. Refusing to delete"
+            )),
+            Err(repo::WorktreeRemoveFailureReason::NotMerged(message)) => Err(message),
+        }
+    })
+}
+
+/// Makes remote URLs comparable, e.g. `https://host/repo.git/` and `https://host/repo`
+fn normalize_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url)
+}
+
+/// Takes an existing repository that is not yet managed by grm, e.g. one that was
+/// cloned manually, and moves it to where the configuration expects it
+///
+/// The repository is matched to the configuration via its remote URLs or its forge
+/// project ID. Remotes that are configured with a different name are renamed, and
+/// then everything is synced like for any other repository. Returns the new path.
+pub fn adopt_repo(
+    config: config::Config,
+    path: &Path,
+    lock_policy: lock::LockPolicy,
+) -> Result<PathBuf, String> {
+    let source_path = path.canonicalize().map_err(|error| {
+        format!(
+            "Failed to canonicalize \"{}\": {}",
+            path::path_as_string(path),
+            error
+        )
+    })?;
+
+    let is_worktree = repo::RepoHandle::detect_worktree(&source_path);
+    let repo_handle = repo::RepoHandle::open(&source_path, is_worktree)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+
+    let provider_id = repo_handle.provider_id()?;
+    let mut current_remotes = Vec::new();
+    for remote_name in repo_handle.remotes()? {
+        if let Some(remote) = repo_handle.find_remote(&remote_name)? {
+            current_remotes.push((remote_name, remote.url()));
+        }
+    }
+
+    let retry = config.retry();
+
+    let mut candidates = Vec::new();
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        for repo in tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+        {
+            let same_provider_id =
+                provider_id.is_some() && repo.provider_id.as_ref() == provider_id.as_ref();
+            let same_remote = repo.remotes.iter().flatten().any(|remote| {
+                current_remotes
+                    .iter()
+                    .any(|(_name, url)| normalize_url(url) == normalize_url(&remote.url))
+            });
+            if same_provider_id || same_remote {
+                let remote_name = repo
+                    .remote_name
+                    .clone()
+                    .or_else(|| tree.canonical_remote_name.clone());
+                candidates.push((root_path.clone(), repo, remote_name));
+            }
+        }
+    }
+
+    let (root_path, repo, canonical_remote_name) = match candidates.len() {
+        0 => {
+            return Err(String::from(
+                "No repository in the configuration has any of the remotes of this repository",
+            ))
+        }
+        1 => candidates.remove(0),
+        _ => {
+            return Err(format!(
+                "Multiple repositories in the configuration match: {}",
+                candidates
+                    .iter()
+                    .map(|(_root_path, repo, _canonical_remote_name)| repo.fullname())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))
+        }
+    };
+
+    check_repo_path(&repo)?;
+
+    if repo.worktree_setup != is_worktree {
+        return Err(if repo.worktree_setup {
+            String::from("The repository is configured with a worktree setup, but does not use one. Convert it with \"grm wt convert\" first")
+        } else {
+            String::from("The repository uses a worktree setup, but is not configured with one")
+        });
+    }
+
+    let target_path = root_path.join(repo.directory());
+
+    path::check_writable(&source_path)?;
+    path::check_writable(&target_path)?;
+
+    let _tree_lock = lock::lock_tree(&root_path, lock_policy)?;
+    let _repo_lock = lock::lock_repo(&target_path, lock_policy)?;
+
+    if target_path.canonicalize().ok().as_ref() != Some(&source_path) {
+        if target_path.exists() {
+            return Err(format!(
+                "\"{}\" already exists",
+                path::path_as_string(&target_path)
+            ));
+        }
+
+        // Worktrees contain the absolute path of the repository, so they would
+        // break when moving it
+        if is_worktree && !repo_handle.get_worktrees()?.is_empty() {
+            return Err(String::from(
+                "Cannot move a repository that has worktrees, remove them first",
+            ));
+        }
+
+        print_repo_action(
+            &repo.name,
+            &format!(
+                "Moving \"{}\" to \"{}\"",
+                path::path_as_string(&source_path),
+                path::path_as_string(&target_path)
+            ),
+        );
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| format!("Failed creating parent directory: {error}"))?;
+        }
+        fs::rename(&source_path, &target_path).map_err(|error| {
+            format!(
+                "Failed moving \"{}\": {}",
+                path::path_as_string(&source_path),
+                error
+            )
+        })?;
+    }
+
+    // Rename remotes that only differ in their name, so their remote tracking
+    // branches are kept. Everything else is handled by the sync.
+    let configured_repo = match &canonical_remote_name {
+        Some(canonical_remote_name) => with_canonical_remote_name(&repo, canonical_remote_name)?.0,
+        None => repo.clone(),
+    };
+    let repo_handle = repo::RepoHandle::open(&target_path, is_worktree)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+    for remote in configured_repo.remotes.iter().flatten() {
+        if repo_handle.find_remote(&remote.name)?.is_some() {
+            continue;
+        }
+        let Some((current_name, _url)) = current_remotes.iter().find(|(name, url)| {
+            normalize_url(url) == normalize_url(&remote.url)
+                && !configured_repo
+                    .remotes
+                    .iter()
+                    .flatten()
+                    .any(|remote| &remote.name == name)
+        }) else {
+            continue;
+        };
+        if let Some(current_remote) = repo_handle.find_remote(current_name)? {
+            print_repo_action(
+                &repo.name,
+                &format!(
+                    "Renaming remote \"{}\" to \"{}\"",
+                    current_name, remote.name
+                ),
+            );
+            repo_handle
+                .rename_remote(&current_remote, &remote.name)
+                .map_err(|error| format!("Renaming the remote failed: {error}"))?;
+        }
+    }
+
+    // Everything that the adopted repository brings along is treated as if it was
+    // applied by an earlier sync, so it is brought in line with the configuration
+    let mut tree_state = state::read_state(&root_path)?;
+    let mut current_remotes = Vec::new();
+    for remote_name in repo_handle.remotes()? {
+        if let Some(remote) = repo_handle.find_remote(&remote_name)? {
+            current_remotes.push(state::AppliedRemote {
+                name: remote_name,
+                url: remote.url(),
+            });
+        }
+    }
+    tree_state.set_remotes(&repo.fullname(), current_remotes);
+    let tree_state = Mutex::new(tree_state);
+
+    let warnings = sync_repo(
+        &root_path,
+        &repo,
+        false,
+        ConflictResolution::Adopt,
+        canonical_remote_name.as_deref(),
+        &tree_state,
+        None,
+        None,
+        &retry,
+        false,
+        // Adopting is about a single repository, so every step is printed right away
+        &mut RepoReport::new(&repo.name, true),
+    )?;
+    let mut tree_state = tree_state
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    tree_state.set_last_sync(&repo.fullname(), notify::now()?);
+    state::write_state(&root_path, &tree_state)?;
+    for warning in &warnings {
+        print_repo_warning(&repo.name, warning);
+    }
+
+    Ok(target_path)
+}
+
+/// Returns the path of the configured repository `name`, which has to exist and
+/// be writable
+fn configured_repo(config: config::Config, name: &str) -> Result<(PathBuf, repo::Repo), String> {
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        if let Some(repo) = tree
+            .repos
+            .unwrap_or_default()
+            .into_iter()
+            .map(|repo| repo.into_repo())
+            .find(|repo| repo.fullname() == name)
+        {
+            let repo_path = root_path.join(repo.directory());
+            if !repo_path.exists() {
+                return Err(String::from("Repository does not exist. Run sync?"));
+            }
+            path::check_writable(&repo_path)?;
+            return Ok((repo_path, repo));
+        }
+    }
+
+    Err(format!(
+        "Repository \"{name}\" is not part of the configuration"
+    ))
+}
+
+/// Where a path belongs in the configuration, see [`find_location()`]
+#[derive(Debug, serde::Serialize)]
+pub struct Location {
+    /// The configuration file
+    pub config: String,
+    /// The root of the tree as configured
+    pub tree: String,
+    pub tree_name: Option<String>,
+    /// The name of the repository, including the namespace. Not set for paths
+    /// in a tree that are not part of any repository.
+    pub repo: Option<String>,
+    pub namespace: Option<String>,
+    pub repo_path: Option<String>,
+    pub worktree: Option<String>,
+}
+
+/// Finds the configured repository (or at least the tree) that `path` is part
+/// of. With nested trees and repositories, the innermost one wins.
+pub fn find_location(
+    config: config::Config,
+    config_path: &str,
+    path: &Path,
+) -> Result<Option<Location>, String> {
+    let path = path
+        .canonicalize()
+        .map_err(|error| format!("Cannot access \"{}\": {}", path.display(), error))?;
+    let config_path = Path::new(config_path)
+        .canonicalize()
+        .map(|config_path| path::path_as_string(&config_path))
+        .unwrap_or_else(|_| config_path.to_string());
+
+    // The depth of the matching directory, to find the innermost match
+    let mut best: Option<(usize, Location)> = None;
+    let mut consider = |depth: usize, location: Location| {
+        if best
+            .as_ref()
+            .map_or(true, |(best_depth, _)| depth > *best_depth)
+        {
+            best = Some((depth, location));
+        }
+    };
+
+    for mut tree in config.trees()? {
+        let Ok(root_path) = path::expand_path(Path::new(&tree.root)).canonicalize() else {
+            continue;
+        };
+        let repos: Vec<repo::Repo> = tree
+            .repos
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(config::RepoConfig::into_repo)
+            .collect();
+        let location = |repo: Option<&repo::Repo>, repo_path: Option<&Path>| Location {
+            config: config_path.clone(),
+            tree: tree.root.clone(),
+            tree_name: tree.name.clone(),
+            repo: repo.map(repo::Repo::fullname),
+            namespace: repo.and_then(|repo| repo.namespace.clone()),
+            repo_path: repo_path.map(path::path_as_string),
+            worktree: None,
+        };
+
+        for repo in &repos {
+            let Ok(repo_path) = root_path.join(repo.directory()).canonicalize() else {
+                continue;
+            };
+            if path.starts_with(&repo_path) {
+                let mut location = location(Some(repo), Some(&repo_path));
+                if repo.worktree_setup {
+                    location.worktree = find_worktree_name(&repo_path, &path);
+                }
+                consider(repo_path.components().count(), location);
+            }
+        }
+
+        // A standalone tree only consists of its repository
+        if !tree.standalone && path.starts_with(&root_path) {
+            consider(root_path.components().count(), location(None, None));
+        }
+    }
+
+    Ok(best.map(|(_, location)| location))
 }
 
-pub fn sync_trees(config: config::Config, init_worktree: bool) -> Result<bool, String> {
-    let mut failures = false;
+/// The name of the worktree below `repo_path` that `path` is in, if any
+///
+/// Worktrees are recognized by their `.git` file, as their names may contain
+/// slashes.
+fn find_worktree_name(repo_path: &Path, path: &Path) -> Option<String> {
+    path.ancestors()
+        .take_while(|ancestor| *ancestor != repo_path)
+        .find(|ancestor| ancestor.join(".git").is_file())
+        .and_then(|worktree_path| worktree_path.strip_prefix(repo_path).ok())
+        .map(path::path_as_string)
+}
 
-    let mut unmanaged_repos_absolute_paths = vec![];
-    let mut managed_repos_absolute_paths = vec![];
+/// Returns the web URL of a configured repository, given by its name (including
+/// the namespace) or by a path inside of it, see [`browse`]
+///
+/// Without `target`, the repository the current directory belongs to is used.
+/// The URL is derived from the remote `remote_name`, or from the first remote.
+pub fn browse_url(
+    config: config::Config,
+    target: Option<&str>,
+    remote_name: Option<&str>,
+) -> Result<String, String> {
+    let web_urls = config.web_urls().to_vec();
 
-    let trees = config.trees()?;
+    let path = match target {
+        Some(target) if !Path::new(target).exists() => None,
+        Some(target) => Some(PathBuf::from(target)),
+        None => Some(
+            std::env::current_dir()
+                .map_err(|error| format!("Could not open current directory: {error}"))?,
+        ),
+    };
+    let path = path
+        .map(|path| {
+            path.canonicalize()
+                .map_err(|error| format!("Cannot access \"{}\": {}", path.display(), error))
+        })
+        .transpose()?;
 
-    for tree in trees {
-        let repos: Vec<repo::Repo> = tree
+    // With nested repositories, the innermost one wins
+    let mut best: Option<(usize, repo::Repo)> = None;
+    for tree in config.trees()? {
+        let root_path = path::expand_path(Path::new(&tree.root));
+        for repo in tree
             .repos
             .unwrap_or_default()
             .into_iter()
-            .map(|repo| repo.into_repo())
-            .collect();
-
-        let root_path = path::expand_path(Path::new(&tree.root));
-
-        for repo in &repos {
-            managed_repos_absolute_paths.push(root_path.join(repo.fullname()));
-            match sync_repo(&root_path, repo, init_worktree) {
-                Ok(()) => print_repo_success(&repo.name, "OK"),
-                Err(error) => {
-                    print_repo_error(&repo.name, &error);
-                    failures = true;
-                }
-            }
-        }
-
-        match find_unmanaged_repos(&root_path, &repos) {
-            Ok(repos) => {
-                for path in repos {
-                    if !unmanaged_repos_absolute_paths.contains(&path) {
-                        unmanaged_repos_absolute_paths.push(path);
-                    }
-                }
-            }
-            Err(error) => {
-                print_error(&format!("Error getting unmanaged repos: {error}"));
-                failures = true;
+            .map(config::RepoConfig::into_repo)
+        {
+            let depth = match &path {
+                None if Some(repo.fullname().as_str()) == target => 0,
+                None => continue,
+                Some(path) => match root_path.join(repo.directory()).canonicalize() {
+                    Ok(repo_path) if path.starts_with(&repo_path) => repo_path.components().count(),
+                    _ => continue,
+                },
+            };
+            if best
+                .as_ref()
+                .map_or(true, |(best_depth, _)| depth > *best_depth)
+            {
+                best = Some((depth, repo));
             }
         }
     }
 
-    for unmanaged_repo_absolute_path in &unmanaged_repos_absolute_paths {
-        if managed_repos_absolute_paths
+    let Some((_, repo)) = best else {
+        return Err(match (target, path) {
+            (_, Some(path)) => format!(
+                "\"{}\" is not part of any configured repository",
+                path.display()
+            ),
+            (target, None) => format!(
+                "Repository \"{}\" is not part of the configuration",
+                target.unwrap_or_default()
+            ),
+        });
+    };
+
+    let remotes = repo.remotes.as_deref().unwrap_or_default();
+    let remote = match remote_name {
+        Some(remote_name) => remotes
             .iter()
-            .any(|managed_repo_absolute_path| {
-                managed_repo_absolute_path == unmanaged_repo_absolute_path
-            })
-        {
-            continue;
-        }
-        print_warning(&format!(
-            "Found unmanaged repository: \"{}\"",
-            path::path_as_string(unmanaged_repo_absolute_path)
-        ));
+            .find(|remote| remote.name == remote_name)
+            .ok_or_else(|| {
+                format!(
+                    "Repository \"{}\" does not have a remote \"{remote_name}\"",
+                    repo.fullname()
+                )
+            })?,
+        None => remotes.first().ok_or_else(|| {
+            format!(
+                "Repository \"{}\" does not have any remotes",
+                repo.fullname()
+            )
+        })?,
+    };
+
+    browse::web_url(&remote.url, None, &web_urls)
+}
+
+/// Fetches everything that was left out by the partial clone of the configured
+/// repository `name`
+pub fn unfilter_repo(
+    config: config::Config,
+    name: &str,
+    lock_policy: lock::LockPolicy,
+) -> Result<PathBuf, String> {
+    let (repo_path, _repo) = configured_repo(config, name)?;
+
+    let _repo_lock = lock::lock_repo(&repo_path, lock_policy)?;
+    repo::unfilter_repo(&repo_path)?;
+
+    Ok(repo_path)
+}
+
+/// Makes the configured repository `name` stop sharing objects with another
+/// repository, see the `share_objects` option
+pub fn dissociate_repo(
+    config: config::Config,
+    name: &str,
+    lock_policy: lock::LockPolicy,
+) -> Result<PathBuf, String> {
+    let (repo_path, repo) = configured_repo(config, name)?;
+
+    let _repo_lock = lock::lock_repo(&repo_path, lock_policy)?;
+    repo::dissociate_repo(&if repo.worktree_setup {
+        repo_path.join(worktree::GIT_MAIN_WORKTREE_DIRECTORY)
+    } else {
+        repo_path.clone()
+    })?;
+
+    Ok(repo_path)
+}
+
+/// Creates an empty first commit in the configured repository `name`, which must
+/// not contain any commits yet, and pushes it to the first remote
+///
+/// The branch is `branch`, the configured default branch or the branch HEAD
+/// already points to, in this order. With a worktree setup, a worktree for the
+/// branch is added as well. Returns the path of the repository and the branch.
+pub fn init_default_branch(
+    config: config::Config,
+    name: &str,
+    branch: Option<&str>,
+    lock_policy: lock::LockPolicy,
+) -> Result<(PathBuf, String), String> {
+    let (repo_path, repo) = configured_repo(config, name)?;
+
+    let _repo_lock = lock::lock_repo(&repo_path, lock_policy)?;
+    let repo_handle = repo::RepoHandle::open(&repo_path, repo.worktree_setup)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+
+    let branch = match branch.map(ToString::to_string).or(repo.default_branch) {
+        Some(branch) => branch,
+        None => repo_handle.head_branch_name()?,
+    };
+
+    repo_handle.init_branch(&branch, "Initial commit")?;
+    print_repo_action(&repo.name, &format!("Created initial commit on {branch}"));
+
+    let remote = repo.remotes.iter().flatten().next();
+    if let Some(remote) = remote {
+        let mut remote_handle = repo_handle
+            .find_remote(&remote.name)?
+            .ok_or_else(|| format!("Remote \"{}\" not found. Run sync?", remote.name))?;
+        remote_handle
+            .push(&branch, &branch, &repo_handle)
+            .map_err(|error| {
+                format!(
+                    "Pushing {branch} to {} failed, push it by hand: {}",
+                    remote.name,
+                    String::from(error)
+                )
+            })?;
+        print_repo_action(&repo.name, &format!("Pushed {branch} to {}", remote.name));
+        repo_handle.set_remote_head(&remote.name, &branch)?;
+    }
+
+    if repo.worktree_setup {
+        let track = remote.map(|remote| (remote.name.as_str(), branch.as_str()));
+        worktree::add_worktree(&repo_path, &branch, track, false)?;
+    } else if let Some(remote) = remote {
+        repo_handle
+            .find_local_branch(&branch)?
+            .set_upstream(&remote.name, &branch)?;
     }
 
-    Ok(!failures)
+    Ok((repo_path, branch))
 }
 
 /// Finds repositories recursively, returning their path
-pub fn find_repo_paths(path: &Path) -> Result<Vec<PathBuf>, String> {
+///
+/// Symlinks are skipped unless `follow_symlinks` is set. When following symlinks,
+/// links that point back into `path` are still skipped, as their targets are found
+/// through their real location anyway. Every directory is only visited once, so
+/// symlink cycles do not lead to endless recursion.
+pub fn find_repo_paths(path: &Path, follow_symlinks: bool) -> Result<Vec<PathBuf>, String> {
+    // If this fails, opening the directory below will fail as well and report a proper error
+    let canonical_root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    let mut visited = HashSet::new();
+    visited.insert(canonical_root.clone());
+
+    find_repo_paths_inner(path, follow_symlinks, &canonical_root, &mut visited)
+}
+
+fn find_repo_paths_inner(
+    path: &Path,
+    follow_symlinks: bool,
+    canonical_root: &Path,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<Vec<PathBuf>, String> {
     let mut repos = Vec::new();
 
     let git_dir = path.join(".git");
@@ -107,14 +2470,35 @@ pub fn find_repo_paths(path: &Path) -> Result<Vec<PathBuf>, String> {
                     match content {
                         Ok(entry) => {
                             let path = entry.path();
-                            if path.is_symlink() {
+                            if !path.is_dir() {
                                 continue;
                             }
-                            if path.is_dir() {
-                                match find_repo_paths(&path) {
-                                    Ok(ref mut r) => repos.append(r),
-                                    Err(error) => return Err(error),
+                            let canonical_path = match path.canonicalize() {
+                                Ok(canonical_path) => canonical_path,
+                                Err(error) => {
+                                    return Err(format!(
+                                        "Failed to canonicalize \"{}\": {}",
+                                        path.display(),
+                                        error
+                                    ))
                                 }
+                            };
+                            if path.is_symlink()
+                                && (!follow_symlinks || canonical_path.starts_with(canonical_root))
+                            {
+                                continue;
+                            }
+                            if !visited.insert(canonical_path) {
+                                continue;
+                            }
+                            match find_repo_paths_inner(
+                                &path,
+                                follow_symlinks,
+                                canonical_root,
+                                visited,
+                            ) {
+                                Ok(ref mut r) => repos.append(r),
+                                Err(error) => return Err(error),
                             }
                         }
                         Err(error) => {
@@ -139,8 +2523,214 @@ pub fn find_repo_paths(path: &Path) -> Result<Vec<PathBuf>, String> {
     Ok(repos)
 }
 
-fn sync_repo(root_path: &Path, repo: &repo::Repo, init_worktree: bool) -> Result<(), String> {
-    let repo_path = root_path.join(repo.fullname());
+/// Returns a copy of `repo` with the first remote renamed to `canonical_remote_name`,
+/// together with the original name of that remote
+fn with_canonical_remote_name(
+    repo: &repo::Repo,
+    canonical_remote_name: &str,
+) -> Result<(repo::Repo, Option<String>), String> {
+    let mut repo = repo.clone();
+    let Some(remotes) = repo.remotes.as_mut().filter(|remotes| !remotes.is_empty()) else {
+        return Ok((repo, None));
+    };
+
+    if remotes[1..]
+        .iter()
+        .any(|remote| remote.name == canonical_remote_name)
+    {
+        return Err(format!(
+            "Remote \"{canonical_remote_name}\" is configured, but it is not the first remote. Cannot use it as canonical remote name"
+        ));
+    }
+
+    let original_name = std::mem::replace(&mut remotes[0].name, canonical_remote_name.to_string());
+    Ok((repo, Some(original_name)))
+}
+
+/// Returns a warning if the local default branch and its upstream branch have diverged,
+/// i.e. both contain commits the other one does not have
+///
+/// This happens when the upstream branch was force-pushed, or when commits were made
+/// on the local branch by accident. Nothing is changed, it is up to the user to sort
+/// this out.
+fn default_branch_divergence(repo_handle: &repo::RepoSession) -> Result<Option<String>, String> {
+    if repo_handle.is_empty()? {
+        return Ok(None);
+    }
+
+    let Ok(local_branch) = repo_handle.default_branch() else {
+        return Ok(None);
+    };
+    let Ok(remote_branch) = local_branch.upstream() else {
+        return Ok(None);
+    };
+
+    let (ahead, behind) = repo_handle.graph_ahead_behind(&local_branch, &remote_branch)?;
+    if ahead == 0 || behind == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(format!(
+        "Default branch \"{}\" has diverged from \"{}\" ({} local and {} remote commits). Was it force-pushed?",
+        local_branch.name()?,
+        remote_branch.name()?,
+        ahead,
+        behind
+    )))
+}
+
+fn is_non_empty_directory(path: &Path) -> Result<bool, String> {
+    Ok(path.exists()
+        && path
+            .read_dir()
+            .map_err(|error| error.to_string())?
+            .next()
+            .is_some())
+}
+
+/// An existing directory that does not contain the expected repository
+enum Conflict {
+    NoRepository,
+    /// The URLs of the remotes of the existing repository
+    UnrelatedRepository(Vec<String>),
+}
+
+impl Conflict {
+    fn description(&self) -> String {
+        match self {
+            Self::NoRepository => String::from("Directory exists, but is not a git repository"),
+            Self::UnrelatedRepository(urls) => format!(
+                "Existing repository has none of the configured remotes (found {})",
+                urls.iter()
+                    .map(|url| format!("\"{url}\""))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Checks whether an existing directory actually contains the repository that is
+/// supposed to be there
+///
+/// An existing repository that has none of the configured remotes is most likely
+/// something else entirely. Repositories without any remotes are fine, they just
+/// get the configured remotes added.
+///
+/// `repo_handle` is the repository at `repo_path`, if it could be opened.
+fn find_conflict(
+    repo_path: &Path,
+    repo: &repo::Repo,
+    repo_handle: Option<&repo::RepoSession>,
+) -> Result<Option<Conflict>, String> {
+    let Some(repo_handle) = repo_handle else {
+        // A repository with the wrong worktree setup is reported later on
+        if repo::RepoHandle::open(repo_path, !repo.worktree_setup).is_ok() {
+            return Ok(None);
+        }
+        return Ok(Some(Conflict::NoRepository));
+    };
+
+    let configured_urls: Vec<&str> = repo
+        .remotes
+        .iter()
+        .flatten()
+        .map(|remote| remote.url.as_str())
+        .collect();
+    if configured_urls.is_empty() {
+        return Ok(None);
+    }
+
+    let mut current_urls = Vec::new();
+    for remote_name in repo_handle.remotes()? {
+        if let Some(remote) = repo_handle.find_remote(&remote_name)? {
+            current_urls.push(remote.url());
+        }
+    }
+
+    if current_urls.is_empty()
+        || current_urls
+            .iter()
+            .any(|url| configured_urls.contains(&url.as_str()))
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(Conflict::UnrelatedRepository(current_urls)))
+}
+
+fn ask_conflict_resolution(
+    repo: &repo::Repo,
+    conflict: &str,
+) -> Result<ConflictResolution, String> {
+    loop {
+        let answer = prompt(&format!(
+            "{}: {}. [a]dopt, [m]ove aside or [s]kip?",
+            repo.name, conflict
+        ))?;
+        match answer.to_lowercase().as_str() {
+            "a" | "adopt" => return Ok(ConflictResolution::Adopt),
+            "m" | "move aside" => return Ok(ConflictResolution::MoveAside),
+            "s" | "skip" => return Ok(ConflictResolution::Skip),
+            _ => print_warning(&format!("Invalid answer \"{answer}\"")),
+        }
+    }
+}
+
+/// Moves a directory out of the way, to `<name>.bak`
+fn move_aside(repo_path: &Path) -> Result<PathBuf, String> {
+    let mut backup_name = repo_path
+        .file_name()
+        .ok_or_else(|| String::from("Repository path has no file name"))?
+        .to_os_string();
+    backup_name.push(".bak");
+    let backup_path = repo_path.with_file_name(backup_name);
+
+    if backup_path.exists() {
+        return Err(format!(
+            "Cannot move the directory aside, \"{}\" already exists",
+            path::path_as_string(&backup_path)
+        ));
+    }
+
+    fs::rename(repo_path, &backup_path).map_err(|error| {
+        format!(
+            "Failed moving \"{}\": {}",
+            path::path_as_string(repo_path),
+            error
+        )
+    })?;
+
+    Ok(backup_path)
+}
+
+/// Syncs a single repository, returning warnings about things that need attention
+#[allow(clippy::too_many_arguments)]
+fn sync_repo(
+    root_path: &Path,
+    repo: &repo::Repo,
+    init_worktree: bool,
+    on_conflict: ConflictResolution,
+    canonical_remote_name: Option<&str>,
+    tree_state: &Mutex<state::TreeState>,
+    reference: Option<&Path>,
+    history_days: Option<u32>,
+    retry: &repo::RetryConfig,
+    run_hooks: bool,
+    report: &mut RepoReport<'_>,
+) -> Result<Vec<String>, String> {
+    // With a canonical remote name, the first remote is called like that, no matter
+    // its name in the configuration. An existing remote is renamed further below.
+    let (canonical_repo, original_remote_name) = match canonical_remote_name {
+        Some(canonical_remote_name) => {
+            let (repo, original_name) = with_canonical_remote_name(repo, canonical_remote_name)?;
+            (Some(repo), original_name)
+        }
+        None => (None, None),
+    };
+    let repo = canonical_repo.as_ref().unwrap_or(repo);
+
+    let repo_path = root_path.join(repo.directory());
     let actual_git_directory = get_actual_git_directory(&repo_path, repo.worktree_setup);
 
     let mut newly_created = false;
@@ -167,26 +2757,75 @@ fn sync_repo(root_path: &Path, repo: &repo::Repo, init_worktree: bool) -> Result
     //
     // Handling the branches on checkout is a bit magic. For minimum surprises, we just set
     // up local tracking branches for all remote branches.
-    if repo_path.exists()
-        && repo_path
-            .read_dir()
-            .map_err(|error| error.to_string())?
-            .next()
-            .is_some()
+    //
+    // Symlinks:
+    //
+    // If the repository path is a symlink, we only ever sync an existing repository behind
+    // it. We never clone or initialize through a symlink, as a dangling or empty link target
+    // most likely means that something is not mounted or moved away.
+    if repo_path.is_symlink()
+        && !(repo_path.exists()
+            && repo_path
+                .read_dir()
+                .map_err(|error| error.to_string())?
+                .next()
+                .is_some())
     {
+        return Err(format!(
+            "Repo path \"{}\" is a symlink to a missing or empty directory, refusing to clone through it",
+            path::path_as_string(&repo_path)
+        ));
+    }
+
+    // The repository is opened once and then used for every step
+    let mut repo_session = None;
+    if is_non_empty_directory(&repo_path)? {
+        repo_session = repo::RepoSession::open(&repo_path, repo.worktree_setup).ok();
+        if let Some(conflict) = find_conflict(&repo_path, repo, repo_session.as_ref())? {
+            let description = conflict.description();
+            let resolution = match on_conflict {
+                ConflictResolution::Ask => ask_conflict_resolution(repo, &description)?,
+                resolution => resolution,
+            };
+            match resolution {
+                ConflictResolution::Adopt => {
+                    if let Conflict::NoRepository = conflict {
+                        return Err(format!(
+                            "{description}. Use --on-conflict to move it aside or skip it"
+                        ));
+                    }
+                    report.change(&format!("{description}, adopting it"));
+                }
+                ConflictResolution::MoveAside => {
+                    repo_session = None;
+                    let backup_path = move_aside(&repo_path)?;
+                    report.change(&format!(
+                        "{}, moved it to \"{}\"",
+                        description,
+                        path::path_as_string(&backup_path)
+                    ));
+                }
+                ConflictResolution::Skip => {
+                    report.action = SyncAction::Skipped;
+                    return Ok(vec![format!("{description}, skipped it")]);
+                }
+                ConflictResolution::Ask => unreachable!(),
+            }
+        }
+    }
+
+    if is_non_empty_directory(&repo_path)? {
         if repo.worktree_setup && !actual_git_directory.exists() {
             return Err(String::from(
                 "Repo already exists, but is not using a worktree setup",
             ));
         };
     } else if repo.remotes.is_none() || repo.remotes.as_ref().unwrap().is_empty() {
-        print_repo_action(
-            &repo.name,
-            "Repository does not have remotes configured, initializing new",
-        );
+        report.change("Repository does not have remotes configured, initializing new");
         match repo::RepoHandle::init(&repo_path, repo.worktree_setup) {
             Ok(r) => {
-                print_repo_success(&repo.name, "Repository created");
+                report.success("Repository created");
+                report.action = SyncAction::Created;
                 Some(r)
             }
             Err(error) => {
@@ -196,9 +2835,26 @@ fn sync_repo(root_path: &Path, repo: &repo::Repo, init_worktree: bool) -> Result
     } else {
         let first = repo.remotes.as_ref().unwrap().first().unwrap();
 
-        match repo::clone_repo(first, &repo_path, repo.worktree_setup) {
+        if let Some(reference) = reference {
+            report.change(&format!(
+                "Sharing objects with \"{}\"",
+                path::path_as_string(reference)
+            ));
+        }
+
+        report.step(&repo::clone_message(first, &actual_git_directory));
+        match repo::clone_repo(
+            first,
+            &repo_path,
+            repo.worktree_setup,
+            repo.filter.as_deref(),
+            history_days,
+            reference,
+            retry,
+        ) {
             Ok(()) => {
-                print_repo_success(&repo.name, "Repository successfully cloned");
+                report.success("Repository successfully cloned");
+                report.action = SyncAction::Cloned;
             }
             Err(error) => {
                 return Err(format!("Repository failed during clone: {error}"));
@@ -208,7 +2864,10 @@ fn sync_repo(root_path: &Path, repo: &repo::Repo, init_worktree: bool) -> Result
         newly_created = true;
     }
 
-    let repo_handle = match repo::RepoHandle::open(&repo_path, repo.worktree_setup) {
+    let repo_handle = match repo_session.map_or_else(
+        || repo::RepoSession::open(&repo_path, repo.worktree_setup),
+        Ok,
+    ) {
         Ok(repo) => repo,
         Err(error) => {
             return if !repo.worktree_setup && repo::RepoHandle::open(&repo_path, true).is_ok() {
@@ -221,71 +2880,235 @@ fn sync_repo(root_path: &Path, repo: &repo::Repo, init_worktree: bool) -> Result
         }
     };
 
-    if newly_created && repo.worktree_setup && init_worktree {
-        match repo_handle.default_branch() {
+    if let Some(provider_id) = &repo.provider_id {
+        if repo_handle.provider_id()?.as_ref() != Some(provider_id) {
+            repo_handle.set_provider_id(provider_id)?;
+        }
+    }
+
+    let mut warnings = Vec::new();
+
+    let project_config = if newly_created {
+        match repo_handle.project_config() {
+            Ok(project_config) => project_config,
+            Err(error) => {
+                warnings.push(error);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    // Where the commands of the project run, the first worktree for worktree setups
+    let mut hook_directory = repo_path.clone();
+
+    if newly_created && repo.worktree_setup && init_worktree && repo_handle.is_empty()? {
+        warnings.push(format!(
+            "Repository is empty, skipping worktree initialization. Use \"grm repos init-default-branch {}\" to create a first commit",
+            repo.fullname()
+        ));
+    } else if newly_created && repo.worktree_setup && init_worktree {
+        // The default branch reported by the forge (or set by the project) beats
+        // guessing. It may not exist yet though, e.g. when nothing was pushed so far.
+        let default_branch = match repo
+            .default_branch
+            .as_deref()
+            .or(project_config
+                .as_ref()
+                .and_then(|project_config| project_config.default_branch.as_deref()))
+            .filter(|branch| repo_handle.find_local_branch(branch).is_ok())
+        {
+            Some(branch) => Ok(branch.to_string()),
+            None => repo_handle
+                .default_branch()
+                .and_then(|branch| branch.name()),
+        };
+        match default_branch {
             Ok(branch) => {
-                worktree::add_worktree(&repo_path, &branch.name()?, None, false)?;
+                worktree::add_worktree_with(&repo_handle, &repo_path, &branch, None, false)?;
+                hook_directory = repo_path.join(branch);
+                if let Err(error) = worktree::write_state_with(&repo_handle, &repo_path) {
+                    warnings.push(format!("Failed writing worktree state: {error}"));
+                }
             }
             Err(_error) => print_repo_error(
                 &repo.name,
-                "Could not determine default branch, skipping worktree initializtion",
+                "Could not determine default branch, skipping worktree initialization",
             ),
         }
     }
+    if let (Some(canonical_remote_name), Some(original_remote_name)) =
+        (canonical_remote_name, &original_remote_name)
+    {
+        if original_remote_name != canonical_remote_name
+            && repo_handle.find_remote(canonical_remote_name)?.is_none()
+        {
+            if let Some(remote) = repo_handle.find_remote(original_remote_name)? {
+                report.change(&format!(
+                    "Renaming remote \"{original_remote_name}\" to \"{canonical_remote_name}\""
+                ));
+                report.remotes_updated();
+                repo_handle
+                    .rename_remote(&remote, canonical_remote_name)
+                    .map_err(|error| {
+                        format!("Repository failed during renaming of the remote: {error}")
+                    })?;
+            }
+        }
+    }
+
+    // The name of the first remote may have changed since the last sync, e.g. when
+    // `remote_name` was changed. Rename the remote that was set up under the old name
+    // instead of replacing it, to keep its remote tracking branches.
+    if let Some(first_remote) = repo.remotes.iter().flatten().next() {
+        let previous_name = tree_state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remotes(&repo.fullname())
+            .unwrap_or_default()
+            .iter()
+            .find(|applied| {
+                applied.url == first_remote.url
+                    && !repo
+                        .remotes
+                        .iter()
+                        .flatten()
+                        .any(|remote| remote.name == applied.name)
+            })
+            .map(|applied| applied.name.clone());
+        if let Some(previous_name) = previous_name {
+            if repo_handle.find_remote(&first_remote.name)?.is_none() {
+                if let Some(remote) = repo_handle.find_remote(&previous_name)? {
+                    report.change(&format!(
+                        "Renaming remote \"{previous_name}\" to \"{}\"",
+                        first_remote.name
+                    ));
+                    report.remotes_updated();
+                    repo_handle
+                        .rename_remote(&remote, &first_remote.name)
+                        .map_err(|error| {
+                            format!("Repository failed during renaming of the remote: {error}")
+                        })?;
+                }
+            }
+        }
+    }
+
     if let Some(remotes) = &repo.remotes {
-        let current_remotes: Vec<String> = repo_handle
+        let desired: Vec<state::AppliedRemote> = remotes
+            .iter()
+            .map(|remote| state::AppliedRemote {
+                name: remote.name.clone(),
+                url: remote.url.clone(),
+            })
+            .collect();
+
+        let mut actual = Vec::new();
+        for remote_name in repo_handle
             .remotes()
-            .map_err(|error| format!("Repository failed during getting the remotes: {error}"))?;
-
-        for remote in remotes {
-            let current_remote = repo_handle.find_remote(&remote.name)?;
-
-            match current_remote {
-                Some(current_remote) => {
-                    let current_url = current_remote.url();
-
-                    if remote.url != current_url {
-                        print_repo_action(
-                            &repo.name,
-                            &format!("Updating remote {} to \"{}\"", &remote.name, &remote.url),
-                        );
-                        if let Err(e) = repo_handle.remote_set_url(&remote.name, &remote.url) {
-                            return Err(format!("Repository failed during setting of the remote URL for remote \"{}\": {}", &remote.name, e));
-                        };
-                    }
+            .map_err(|error| format!("Repository failed during getting the remotes: {error}"))?
+        {
+            if let Some(remote) = repo_handle.find_remote(&remote_name)? {
+                actual.push(state::AppliedRemote {
+                    name: remote_name,
+                    url: remote.url(),
+                });
+            }
+        }
+
+        // A fresh clone cannot contain any local changes
+        let applied = if newly_created {
+            None
+        } else {
+            tree_state
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .remotes(&repo.fullname())
+                .map(<[state::AppliedRemote]>::to_vec)
+        };
+
+        for change in state::plan_remotes(&desired, applied.as_deref(), &actual) {
+            report.remotes_updated();
+            match change {
+                state::RemoteChange::SetUrl(remote) => {
+                    report.change(&format!(
+                        "Updating remote {} to \"{}\"",
+                        &remote.name, &remote.url
+                    ));
+                    if let Err(e) = repo_handle.remote_set_url(&remote.name, &remote.url) {
+                        return Err(format!("Repository failed during setting of the remote URL for remote \"{}\": {}", &remote.name, e));
+                    };
                 }
-                None => {
-                    print_repo_action(
-                        &repo.name,
-                        &format!(
-                            "Setting up new remote \"{}\" to \"{}\"",
-                            &remote.name, &remote.url
-                        ),
-                    );
+                state::RemoteChange::Add(remote) => {
+                    report.change(&format!(
+                        "Setting up new remote \"{}\" to \"{}\"",
+                        &remote.name, &remote.url
+                    ));
                     if let Err(error) = repo_handle.new_remote(&remote.name, &remote.url) {
                         return Err(format!(
                             "Repository failed during setting the remotes: {error}",
                         ));
                     }
                 }
+                state::RemoteChange::Delete(remote_name) => {
+                    report.change(&format!("Deleting remote \"{remote_name}\""));
+                    if let Err(e) = repo_handle.remote_delete(&remote_name) {
+                        return Err(format!(
+                            "Repository failed during deleting remote \"{remote_name}\": {e}"
+                        ));
+                    }
+                }
             }
         }
 
-        for current_remote in &current_remotes {
-            if !remotes.iter().any(|r| &r.name == current_remote) {
-                print_repo_action(
-                    &repo.name,
-                    &format!("Deleting remote \"{}\"", &current_remote,),
-                );
-                if let Err(e) = repo_handle.remote_delete(current_remote) {
-                    return Err(format!(
-                        "Repository failed during deleting remote \"{}\": {}",
-                        &current_remote, e
-                    ));
+        tree_state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .set_remotes(&repo.fullname(), desired);
+    }
+
+    if let Some(commands) = project_config
+        .and_then(|project_config| project_config.post_clone)
+        .filter(|commands| !commands.is_empty())
+    {
+        if run_hooks {
+            for command in commands {
+                report.change(&format!("Running \"{command}\""));
+                if let Err(error) = run_hook(&hook_directory, &command) {
+                    warnings.push(error);
+                    break;
                 }
             }
+        } else {
+            warnings.push(String::from(
+                "The project defines post_clone commands, use --run-hooks to run them",
+            ));
         }
     }
+
+    if let Some(warning) = default_branch_divergence(&repo_handle)? {
+        warnings.push(warning);
+    }
+    Ok(warnings)
+}
+
+/// Runs a command from the configuration of a project in `directory`
+fn run_hook(directory: &Path, command: &str) -> Result<(), String> {
+    let status = std::process::Command::new("/usr/bin/env")
+        .arg("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(directory)
+        .status()
+        .map_err(|error| format!("Failed to run \"{command}\": {error}"))?;
+
+    if !status.success() {
+        return Err(match status.code() {
+            Some(code) => format!("\"{command}\" failed with exit code {code}"),
+            None => format!("\"{command}\" was terminated by a signal"),
+        });
+    }
+
     Ok(())
 }
 
@@ -296,3 +3119,176 @@ fn get_actual_git_directory(path: &Path, is_worktree: bool) -> PathBuf {
         path.to_path_buf()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sync_tree(root: &str, name: Option<&str>, depends_on: &[&str]) -> SyncTree {
+        SyncTree {
+            root: root.to_string(),
+            root_path: PathBuf::from(root),
+            repos: Vec::new(),
+            canonical_remote_name: None,
+            name: name.map(str::to_string),
+            serial: false,
+            depends_on: depends_on.iter().map(|s| s.to_string()).collect(),
+            standalone: false,
+            state_root: PathBuf::from(root),
+            state_repos: Vec::new(),
+            history_days: None,
+            max_size_mb: None,
+            dir_mode: None,
+        }
+    }
+
+    fn repo(name: &str) -> repo::Repo {
+        config::RepoConfig {
+            name: name.to_string(),
+            worktree_setup: false,
+            remotes: None,
+            provider_id: None,
+            tags: None,
+            filter: None,
+            default_branch: None,
+            dir: None,
+            remote_name: None,
+        }
+        .into_repo()
+    }
+
+    #[test]
+    fn check_overlapping_trees_with_shared_root() {
+        let mut team_a = sync_tree("/work", Some("a"), &[]);
+        team_a.repos = vec![repo("one"), repo("two")];
+        let mut team_b = sync_tree("/work", Some("b"), &[]);
+        team_b.repos = vec![repo("three")];
+        let mut nested = sync_tree("/work/nested", None, &[]);
+        nested.repos = vec![repo("one")];
+
+        assert_eq!(check_overlapping_trees(&[team_a, team_b, nested]), Ok(()));
+
+        let mut team_a = sync_tree("/work", Some("a"), &[]);
+        team_a.repos = vec![repo("one"), repo("two")];
+        let mut team_b = sync_tree("/work", Some("b"), &[]);
+        team_b.repos = vec![repo("two")];
+
+        assert_eq!(
+            check_overlapping_trees(&[team_a, team_b]),
+            Err(String::from(
+                "Repository \"/work/two\" is part of both tree \"a\" and tree \"b\""
+            ))
+        );
+
+        // Nested trees can overlap as well
+        let mut outer = sync_tree("/work", None, &[]);
+        outer.repos = vec![repo("nested/one")];
+        let mut nested = sync_tree("/work/nested", None, &[]);
+        nested.repos = vec![repo("one")];
+
+        assert!(check_overlapping_trees(&[outer, nested]).is_err());
+    }
+
+    #[test]
+    fn check_tree_dependencies() {
+        let trees = [
+            sync_tree("/a", Some("a"), &["/b"]),
+            sync_tree("/b", None, &[]),
+            sync_tree("/c", None, &["a", "/b"]),
+        ];
+        assert_eq!(
+            tree_dependencies(&trees),
+            Ok(vec![vec![1], vec![], vec![0, 1]])
+        );
+
+        let trees = [sync_tree("/a", None, &["x"])];
+        assert_eq!(
+            tree_dependencies(&trees),
+            Err(String::from("Tree \"/a\" depends on unknown tree \"x\""))
+        );
+
+        let trees = [
+            sync_tree("/a", Some("a"), &["b"]),
+            sync_tree("/b", Some("b"), &["a"]),
+            sync_tree("/c", None, &["a"]),
+        ];
+        assert_eq!(
+            tree_dependencies(&trees),
+            Err(String::from("Cyclic dependencies between trees: a, b, /c"))
+        );
+
+        let trees = [sync_tree("/a", None, &["/a"])];
+        assert!(tree_dependencies(&trees).is_err());
+    }
+
+    #[test]
+    fn check_nested_repos() {
+        let repos = [
+            repo("outer"),
+            repo("other"),
+            repo("outer/inner"),
+            repo("outer-sibling"),
+            repo("outer/inner/innermost"),
+        ];
+        assert_eq!(
+            nested_repos(&repos),
+            vec![vec![], vec![], vec![0], vec![], vec![0, 2]]
+        );
+
+        // Nesting works in both directions
+        let repos = [repo("ns/inner"), repo("ns")];
+        assert_eq!(nested_repos(&repos), vec![vec![], vec![0]]);
+    }
+
+    #[test]
+    fn check_select_sync_trees() {
+        let trees = || {
+            vec![
+                sync_tree("/work", Some("a"), &[]),
+                sync_tree("/work", Some("b"), &["a"]),
+                sync_tree("/private", None, &["b"]),
+            ]
+        };
+        let roots = |trees: Vec<SyncTree>| -> Vec<(String, Vec<String>)> {
+            trees
+                .into_iter()
+                .map(|tree| (tree.root, tree.depends_on))
+                .collect()
+        };
+
+        assert_eq!(roots(select_sync_trees(trees(), None).unwrap()).len(), 3);
+        // Dependencies on trees that are not synced are dropped
+        assert_eq!(
+            roots(select_sync_trees(trees(), Some("b")).unwrap()),
+            vec![(String::from("/work"), vec![])]
+        );
+        assert_eq!(
+            roots(select_sync_trees(trees(), Some("/private")).unwrap()),
+            vec![(String::from("/private"), vec![])]
+        );
+        // Trees with the same root are selected together
+        assert_eq!(
+            roots(select_sync_trees(trees(), Some("/work/")).unwrap()),
+            vec![
+                (String::from("/work"), vec![]),
+                (String::from("/work"), vec![String::from("a")])
+            ]
+        );
+        assert_eq!(
+            select_sync_trees(trees(), Some("missing")).err(),
+            Some(String::from(
+                "There is no tree with name or root \"missing\""
+            ))
+        );
+    }
+
+    #[test]
+    fn check_parse_dir_mode() {
+        assert_eq!(parse_dir_mode("0750"), Ok(0o750));
+        assert_eq!(parse_dir_mode("750"), Ok(0o750));
+        assert_eq!(parse_dir_mode("2770"), Ok(0o2770));
+        assert!(parse_dir_mode("0790").is_err());
+        assert!(parse_dir_mode("17777").is_err());
+        assert!(parse_dir_mode("rwxr-x---").is_err());
+    }
+}
@@ -207,12 +207,23 @@
 //! * Does the local branch track the correct remote branch?
 //! * Does that remote branch also exist?
 use std::cell::RefCell;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+
+use super::browse;
+use super::notify;
+use super::output::*;
+use super::path;
 use super::repo;
+use super::stats;
+use super::tree;
 
 pub const GIT_MAIN_WORKTREE_DIRECTORY: &str = ".git-main-working-tree";
 
+/// Lists the worktrees in the root of a worktree setup, see [`write_state()`]
+pub const STATE_FILE_NAME: &str = ".grm-state.json";
+
 struct Init;
 
 struct WithLocalBranchName<'a> {
@@ -345,7 +356,7 @@ impl<'a> Worktree<'a, WithLocalTargetSelected<'a>> {
 }
 
 impl<'a> Worktree<'a, WithRemoteTrackingBranch<'a>> {
-    fn create(self, directory: &Path) -> Result<Option<Vec<String>>, String> {
+    fn create(self, directory: &Path, local_fallback: bool) -> Result<Option<Vec<String>>, String> {
         let mut warnings: Vec<String> = vec![];
 
         let mut branch = if let Some(branch) = self.extra.local_branch {
@@ -410,25 +421,25 @@ impl<'a> Worktree<'a, WithRemoteTrackingBranch<'a>> {
                         ));
                     }
 
-                    if let Some(prefix) = self.extra.prefix {
-                        remote.push(
-                            &self.extra.local_branch_name,
-                            &format!("{prefix}/{remote_branch_name}"),
-                            self.repo,
-                        )?;
-
-                        branch.set_upstream(
-                            &remote_name,
-                            &format!("{prefix}/{remote_branch_name}"),
-                        )?;
+                    let remote_branch_name = if let Some(prefix) = self.extra.prefix {
+                        format!("{prefix}/{remote_branch_name}")
                     } else {
-                        remote.push(
-                            &self.extra.local_branch_name,
-                            &remote_branch_name,
-                            self.repo,
-                        )?;
+                        remote_branch_name
+                    };
 
-                        branch.set_upstream(&remote_name, &remote_branch_name)?;
+                    match remote.push(
+                        &self.extra.local_branch_name,
+                        &remote_branch_name,
+                        self.repo,
+                    ) {
+                        Ok(()) => branch.set_upstream(&remote_name, &remote_branch_name)?,
+                        Err(repo::PushError::Refused(message)) if local_fallback => {
+                            print_warning(&format!(
+                                "{message}. The branch \"{}\" does not track a remote branch",
+                                self.extra.local_branch_name
+                            ));
+                        }
+                        Err(error) => return Err(error.into()),
                     }
                 }
             }
@@ -521,6 +532,15 @@ impl<'a> Worktree<'a, WithRemoteTrackingBranch<'a>> {
     }
 }
 
+/// Finds the root of the worktree setup that contains `path`
+///
+/// This is either `path` itself or one of its parents.
+pub fn find_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|directory| directory.join(GIT_MAIN_WORKTREE_DIRECTORY).is_dir())
+        .map(Path::to_path_buf)
+}
+
 /// A branch name must never start or end with a slash, and it cannot have two
 /// consecutive slashes
 fn validate_worktree_name(name: &str) -> Result<(), String> {
@@ -545,27 +565,473 @@ fn validate_worktree_name(name: &str) -> Result<(), String> {
     Ok(())
 }
 
-// TECHDEBT
-//
-// Instead of opening the repo & reading configuration inside the function, it
-// should be done by the caller and given as a parameter
+/// Quotes `input` so it is passed as a single word to `sh`
+fn shell_quote(input: &str) -> String {
+    format!("'{}'", input.replace('\'', r"'\''"))
+}
+
+/// Builds the command to open `path` with
+///
+/// Without a `{path}` placeholder in `command`, the path is appended.
+fn build_open_command(command: &str, path: &Path) -> String {
+    let quoted_path = shell_quote(&path.display().to_string());
+    if command.contains("{path}") {
+        command.replace("{path}", &quoted_path)
+    } else {
+        format!("{command} {quoted_path}")
+    }
+}
+
+/// Opens the worktree `name` with the `open_command` from `grm.toml`
+///
+/// If there is no `open_command`, `$VISUAL` or `$EDITOR` is used instead. The
+/// command inherits the terminal, so terminal editors work as well.
+pub fn open_worktree(
+    directory: &Path,
+    name: &str,
+    config: &Option<repo::WorktreeRootConfig>,
+) -> Result<(), String> {
+    let worktree_path = directory.join(name);
+    if !worktree_path.is_dir() {
+        return Err(format!("Worktree {name} does not exist"));
+    }
+
+    let command = match config
+        .as_ref()
+        .and_then(|config| config.open_command.clone())
+    {
+        Some(command) => command,
+        None => ["VISUAL", "EDITOR"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+            .ok_or(String::from(
+                "No open_command configured in grm.toml, and neither $VISUAL nor $EDITOR are set",
+            ))?,
+    };
+
+    let status = std::process::Command::new("/usr/bin/env")
+        .arg("sh")
+        .arg("-c")
+        .arg(build_open_command(&command, &worktree_path))
+        .current_dir(&worktree_path)
+        .status()
+        .map_err(|error| format!("Failed to run open command: {error}"))?;
+
+    if !status.success() {
+        return Err(match status.code() {
+            Some(code) => format!("Open command failed with exit code {code}"),
+            None => String::from("Open command was terminated by a signal"),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the web URL of the branch of the worktree `name` in the worktree setup
+/// at `directory`, see [`browse`]
+///
+/// The branch and remote are taken from the upstream of the worktree, if it has
+/// one. Without `name`, the URL of the repository itself is returned.
+pub fn browse_url(
+    directory: &Path,
+    name: Option<&str>,
+    remote_name: Option<&str>,
+    config: &Option<repo::WorktreeRootConfig>,
+) -> Result<String, String> {
+    let repo = repo::RepoHandle::open(directory, true)
+        .map_err(|error| format!("Opening repository failed: {error}"))?;
+    let remotes = repo.remotes()?;
+
+    let mut branch = None;
+    let mut upstream_remote = None;
+    if let Some(name) = name {
+        let worktree_path = directory.join(name);
+        if !worktree_path.is_dir() {
+            return Err(format!("Worktree {name} does not exist"));
+        }
+        let worktree_repo = repo::RepoHandle::open(&worktree_path, false)
+            .map_err(|error| format!("Opening worktree failed: {error}"))?;
+        if let Some(commit) = worktree_repo.detached_head()? {
+            return Err(repo::detached_head_message(&commit));
+        }
+        let local_branch = worktree_repo.head_branch()?;
+        branch = Some(local_branch.name()?);
+
+        let upstream = match local_branch.upstream() {
+            Ok(upstream) => Some(upstream.name()?),
+            Err(_) => None,
+        };
+        if let Some(upstream) = upstream {
+            // Remote names may contain slashes as well, so the longest one wins
+            if let Some(remote) = remotes
+                .iter()
+                .filter(|remote| upstream.starts_with(&format!("{remote}/")))
+                .max_by_key(|remote| remote.len())
+            {
+                branch = Some(upstream[remote.len() + 1..].to_string());
+                upstream_remote = Some(remote.clone());
+            }
+        }
+    }
+
+    let default_remote = config
+        .as_ref()
+        .and_then(|config| config.track.as_ref())
+        .map(|track| track.default_remote.clone());
+    let remote_name = match remote_name
+        .map(str::to_string)
+        .or(upstream_remote)
+        .or(default_remote)
+    {
+        Some(remote_name) => remote_name,
+        None => match remotes.as_slice() {
+            [] => return Err(String::from("Repository does not have any remotes")),
+            [remote] => remote.clone(),
+            remotes if remotes.iter().any(|remote| remote == "origin") => String::from("origin"),
+            _ => {
+                return Err(String::from(
+                    "Repository has multiple remotes, use --remote to choose one",
+                ))
+            }
+        },
+    };
+    let remote = repo
+        .find_remote(&remote_name)?
+        .ok_or_else(|| format!("Remote \"{remote_name}\" does not exist"))?;
+
+    let web_urls = config
+        .as_ref()
+        .and_then(|config| config.web_urls.as_deref())
+        .unwrap_or_default();
+    browse::web_url(&remote.url(), branch.as_deref(), web_urls)
+}
+
+/// Fetches the remotes of a worktree setup, returning a description of what was fetched
+///
+/// If `remotes` is not empty, only these remotes are fetched. Otherwise, with
+/// `fetch.only_configured` set in `grm.toml`, only `track.default_remote` is fetched,
+/// as that is the only remote the configuration knows about.
+pub fn fetch_remotes(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+    remotes: &[String],
+) -> Result<String, String> {
+    let config = repo::read_worktree_root_config(directory)?;
+    let fetch_config = config.as_ref().and_then(|config| config.fetch.as_ref());
+    let prune = repo::FetchConfig::prune(fetch_config);
+
+    let remotes = if !remotes.is_empty() {
+        Some(remotes.to_vec())
+    } else if repo::FetchConfig::only_configured(fetch_config) {
+        match config.as_ref().and_then(|config| config.track.as_ref()) {
+            Some(track) => Some(vec![track.default_remote.clone()]),
+            None => {
+                return Err(String::from(
+                    "\"fetch.only_configured\" requires \"track.default_remote\" to be set",
+                ))
+            }
+        }
+    } else {
+        None
+    };
+
+    match &remotes {
+        None => repo.fetchall(&repo::RetryConfig::default(), prune)?,
+        Some(remotes) => repo.fetch_only(remotes, &repo::RetryConfig::default(), prune)?,
+    }
+
+    Ok(tree::fetch_message(remotes.as_deref()))
+}
+
+/// A worktree that could be removed to reclaim disk space, see `grm wt clean --suggest`
+pub struct CleanupSuggestion {
+    pub name: String,
+    /// Disk usage in bytes
+    pub size: u64,
+    /// Age of the HEAD commit in days
+    pub age_days: i64,
+    /// Why `grm wt clean` would keep the worktree, if it would
+    pub blocker: Option<String>,
+}
+
+impl CleanupSuggestion {
+    /// Whether `grm wt clean` would remove the worktree
+    pub fn removable(&self) -> bool {
+        self.blocker.is_none()
+    }
+}
+
+fn cleanup_suggestion(
+    worktree_dir: &Path,
+    name: &str,
+    config: &Option<repo::WorktreeRootConfig>,
+    now: i64,
+) -> Result<CleanupSuggestion, String> {
+    let worktree_repo = repo::RepoHandle::open(worktree_dir, false)
+        .map_err(|error| format!("Error opening repo: {error}"))?;
+    if let Some(commit) = worktree_repo.detached_head()? {
+        return Err(repo::detached_head_message(&commit));
+    }
+    let branch = worktree_repo.head_branch()?;
+
+    let commit_time = branch.commit()?.time();
+
+    let blocker = match worktree_repo.check_worktree_removable(
+        &branch,
+        name,
+        config,
+        repo::WorktreeRemoveOverrides::default(),
+    ) {
+        Ok(()) => None,
+        Err(
+            repo::WorktreeRemoveFailureReason::Changes(reason)
+            | repo::WorktreeRemoveFailureReason::Submodules(reason)
+            | repo::WorktreeRemoveFailureReason::NotMerged(reason),
+        ) => Some(reason),
+        Err(repo::WorktreeRemoveFailureReason::Error(error)) => return Err(error),
+    };
+
+    Ok(CleanupSuggestion {
+        name: name.to_string(),
+        size: stats::disk_usage(worktree_dir)?,
+        age_days: (now - commit_time).max(0) / (60 * 60 * 24),
+        blocker,
+    })
+}
+
+/// Returns all worktrees that `grm wt clean` would consider, ranked by how worthwhile
+/// removing them is
+///
+/// Removable worktrees come first, then larger ones, then ones with older commits.
+/// Nothing is removed.
+pub fn cleanup_suggestions(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+) -> Result<(Vec<CleanupSuggestion>, Vec<String>), String> {
+    let config = repo::read_worktree_root_config(directory)?;
+    let worktrees = repo.cleanup_candidates(&config)?;
+
+    let now = unix_now()?;
+
+    let mut suggestions = Vec::new();
+    let mut warnings = Vec::new();
+
+    for worktree in &worktrees {
+        let worktree_dir = directory.join(worktree.name());
+        if !worktree_dir.exists() {
+            warnings.push(format!(
+                "Worktree {} does not have a directory",
+                worktree.name()
+            ));
+            continue;
+        }
+
+        match cleanup_suggestion(&worktree_dir, worktree.name(), &config, now) {
+            Ok(suggestion) => suggestions.push(suggestion),
+            Err(error) => warnings.push(format!("{}: {}", worktree.name(), error)),
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.removable()
+            .cmp(&a.removable())
+            .then_with(|| b.size.cmp(&a.size))
+            .then_with(|| b.age_days.cmp(&a.age_days))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    Ok((suggestions, warnings))
+}
+
+fn unix_now() -> Result<i64, String> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|error| format!("System time is before the unix epoch: {error}"))?
+        .as_secs() as i64)
+}
+
+/// A remote branch that does not have a local worktree yet
+#[derive(Debug)]
+pub struct RemoteBranchCandidate {
+    pub remote: String,
+    /// The name of the branch on the remote
+    pub branch: String,
+    /// Age of the newest commit in days
+    pub age_days: i64,
+}
+
+impl RemoteBranchCandidate {
+    /// The part of the branch name up to the first slash, e.g. `feature` for
+    /// `feature/login`
+    pub fn prefix(&self) -> Option<&str> {
+        self.branch.split_once('/').map(|(prefix, _)| prefix)
+    }
+}
+
+/// Returns all remote branches that do not have a worktree in `directory` yet,
+/// sorted by remote, prefix and name
+///
+/// A branch that has `default_remote_prefix` from the track configuration in
+/// front of the name of an existing worktree counts as having a worktree.
+pub fn remote_branch_candidates(
+    repo: &repo::RepoHandle,
+    directory: &Path,
+) -> Result<Vec<RemoteBranchCandidate>, String> {
+    let config = repo::read_worktree_root_config(directory)?;
+    let prefix = config
+        .and_then(|config| config.track)
+        .and_then(|track| track.default_remote_prefix);
+
+    let worktrees = repo.get_worktrees()?;
+    let has_worktree = |name: &str| {
+        worktrees.iter().any(|worktree| worktree.name() == name)
+            || directory.join(name).join(".git").exists()
+    };
+
+    let remotes = repo.remotes()?;
+    let now = unix_now()?;
+
+    let mut candidates = Vec::new();
+    for branch in repo.remote_branches()? {
+        let name = branch.name()?;
+        let Some((remote, branch_name)) = remotes.iter().find_map(|remote| {
+            name.strip_prefix(remote.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+                .map(|branch_name| (remote, branch_name))
+        }) else {
+            continue;
+        };
+
+        // The symbolic HEAD of the remote is not a branch of its own
+        if branch_name == "HEAD" {
+            continue;
+        }
+
+        let worktree_name = prefix
+            .as_ref()
+            .and_then(|prefix| branch_name.strip_prefix(&format!("{prefix}/")))
+            .unwrap_or(branch_name);
+        if has_worktree(branch_name) || has_worktree(worktree_name) {
+            continue;
+        }
+
+        candidates.push(RemoteBranchCandidate {
+            remote: remote.clone(),
+            branch: branch_name.to_string(),
+            age_days: (now - branch.commit()?.time()).max(0) / (60 * 60 * 24),
+        });
+    }
+
+    candidates.sort_by(|a, b| {
+        a.remote
+            .cmp(&b.remote)
+            .then_with(|| a.prefix().cmp(&b.prefix()))
+            .then_with(|| a.branch.cmp(&b.branch))
+    });
+
+    Ok(candidates)
+}
+
+/// Finds the candidate for `name`, which is either `<remote>/<branch>` or just
+/// the branch name if it only exists on a single remote
+pub fn find_remote_branch_candidate<'a>(
+    candidates: &'a [RemoteBranchCandidate],
+    name: &str,
+) -> Result<&'a RemoteBranchCandidate, String> {
+    if let Some(candidate) = candidates
+        .iter()
+        .find(|candidate| format!("{}/{}", candidate.remote, candidate.branch) == name)
+    {
+        return Ok(candidate);
+    }
+
+    let matching: Vec<&RemoteBranchCandidate> = candidates
+        .iter()
+        .filter(|candidate| candidate.branch == name)
+        .collect();
+
+    match matching.as_slice() {
+        [candidate] => Ok(candidate),
+        [] => Err(format!(
+            "No remote branch \"{name}\" without a worktree found. Run grm wt fetch?"
+        )),
+        _ => Err(format!(
+            "Branch \"{name}\" exists on several remotes ({}), use <remote>/{name}",
+            matching
+                .iter()
+                .map(|candidate| candidate.remote.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Parses a list of worktree names, one per line
+///
+/// Empty lines and lines starting with `#` are ignored, as are names that
+/// appear more than once.
+pub fn parse_worktree_names(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || names.iter().any(|name| name == line) {
+            continue;
+        }
+        names.push(line.to_string());
+    }
+    names
+}
+
+/// Reads a list of worktree names from the file at `path`, or from stdin if
+/// `path` is `-`
+pub fn read_worktree_names(path: &str) -> Result<Vec<String>, String> {
+    let content = if path == "-" {
+        std::io::read_to_string(std::io::stdin())
+            .map_err(|error| format!("Error reading worktree names from stdin: {error}"))?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|error| format!("Error reading worktree names from \"{path}\": {error}"))?
+    };
+    let names = parse_worktree_names(&content);
+    if names.is_empty() {
+        return Err(String::from("No worktree names given"));
+    }
+    Ok(names)
+}
+
 pub fn add_worktree(
     directory: &Path,
     name: &str,
     track: Option<(&str, &str)>,
     no_track: bool,
 ) -> Result<Option<Vec<String>>, String> {
-    let mut warnings: Vec<String> = vec![];
-
-    validate_worktree_name(name)?;
-
-    let repo = repo::RepoHandle::open(directory, true).map_err(|error| match error.kind {
+    let repo = repo::RepoSession::open(directory, true).map_err(|error| match error.kind {
         repo::RepoErrorKind::NotFound => {
             String::from("Current directory does not contain a worktree setup")
         }
         repo::RepoErrorKind::Unknown(_) => format!("Error opening repo: {error}"),
     })?;
 
+    add_worktree_with(&repo, directory, name, track, no_track)
+}
+
+/// Like [`add_worktree()`], for the already opened worktree setup `repo` at
+/// `directory`
+//
+// TECHDEBT
+//
+// Instead of reading the configuration inside the function, it should be done
+// by the caller and given as a parameter
+pub fn add_worktree_with(
+    repo: &repo::RepoSession,
+    directory: &Path,
+    name: &str,
+    track: Option<(&str, &str)>,
+    no_track: bool,
+) -> Result<Option<Vec<String>>, String> {
+    let mut warnings: Vec<String> = vec![];
+
+    validate_worktree_name(name)?;
+
     let remotes = &repo.remotes()?;
 
     let config = repo::read_worktree_root_config(directory)?;
@@ -574,11 +1040,15 @@ pub fn add_worktree(
         return Err(format!("Worktree {name} already exists"));
     }
 
+    let sparse = config.as_ref().and_then(|config| config.sparse.clone());
     let track_config = config.and_then(|config| config.track);
     let prefix = track_config
         .as_ref()
         .and_then(|track| track.default_remote_prefix.as_ref());
     let enable_tracking = track_config.as_ref().map_or(false, |track| track.default);
+    let local_fallback = track_config
+        .as_ref()
+        .is_some_and(|track| track.local_fallback);
     let default_remote = track_config
         .as_ref()
         .map(|track| track.default_remote.clone());
@@ -593,9 +1063,15 @@ pub fn add_worktree(
     //
     // So if we define `commit` *after* the respective worktrees, it will be dropped first while
     // still being borrowed by `Worktree`.
+    if repo.is_empty()? {
+        return Err(String::from(
+            "Repository does not contain any commits yet, create a first commit (e.g. with grm repos init-default-branch) before adding worktrees",
+        ));
+    }
+
     let default_branch_head = repo.default_branch()?.commit_owned()?;
 
-    let worktree = Worktree::<Init>::new(&repo).set_local_branch_name(name);
+    let worktree = Worktree::<Init>::new(repo).set_local_branch_name(name);
 
     let get_remote_head = |remote_name: &str,
                            remote_branch_name: &str|
@@ -750,7 +1226,12 @@ pub fn add_worktree(
         }
     };
 
-    worktree.create(directory)?;
+    worktree.create(directory, local_fallback)?;
+
+    if let Some(patterns) = sparse.filter(|patterns| !patterns.is_empty()) {
+        repo::RepoHandle::set_sparse_checkout(&directory.join(name), &patterns)
+            .map_err(|error| format!("Setting up sparse checkout failed: {error}"))?;
+    }
 
     Ok(if warnings.is_empty() {
         None
@@ -759,6 +1240,421 @@ pub fn add_worktree(
     })
 }
 
+/// Changes to carry over into a new worktree
+pub enum WorktreeChanges {
+    Patch(PathBuf),
+    Stash(String),
+}
+
+impl WorktreeChanges {
+    /// Takes `value` as a patch if it is an existing file, otherwise as a stash
+    /// of the repository in `directory`
+    pub fn find(directory: &Path, value: &str) -> Result<Self, String> {
+        let path = Path::new(value);
+        if path.is_file() {
+            return path
+                .canonicalize()
+                .map(Self::Patch)
+                .map_err(|error| format!("Cannot open patch \"{value}\": {error}"));
+        }
+
+        let repo = repo::RepoHandle::open(directory, true).map_err(|error| match error.kind {
+            repo::RepoErrorKind::NotFound => {
+                String::from("Current directory does not contain a worktree setup")
+            }
+            _ => format!("Error opening repo: {error}"),
+        })?;
+        if repo.is_stash(value) {
+            Ok(Self::Stash(value.to_string()))
+        } else {
+            Err(format!("\"{value}\" is neither a patch file nor a stash"))
+        }
+    }
+
+    /// Applies the changes to the worktree `name` in `directory`
+    pub fn apply(&self, directory: &Path, name: &str) -> Result<(), String> {
+        let worktree_path = directory.join(name);
+        match self {
+            Self::Patch(patch) => repo::RepoHandle::apply_patch(&worktree_path, patch),
+            Self::Stash(stash) => repo::RepoHandle::apply_stash(&worktree_path, stash),
+        }
+    }
+}
+
+impl std::fmt::Display for WorktreeChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Patch(patch) => write!(f, "patch {}", patch.display()),
+            Self::Stash(stash) => write!(f, "stash {stash}"),
+        }
+    }
+}
+
+/// The outcome of moving changes between worktrees
+pub enum MovedChanges {
+    /// The changes were applied cleanly and removed from the source worktree
+    Clean,
+    /// The changes were applied with conflicts and are kept in the given stash
+    Conflicts(String),
+}
+
+/// Moves all uncommitted changes, including untracked files, from worktree
+/// `from` to worktree `to` by stashing them in `from` and applying the stash
+/// in `to`
+///
+/// If the stash cannot be applied at all, the changes are restored in `from`.
+pub fn move_changes(directory: &Path, from: &str, to: &str) -> Result<MovedChanges, String> {
+    if from == to {
+        return Err(String::from("Source and target worktree are the same"));
+    }
+
+    let repo = repo::RepoHandle::open(directory, true).map_err(|error| match error.kind {
+        repo::RepoErrorKind::NotFound => {
+            String::from("Current directory does not contain a worktree setup")
+        }
+        repo::RepoErrorKind::Unknown(_) => format!("Error opening repo: {error}"),
+    })?;
+
+    for name in [from, to] {
+        if repo.find_worktree(name).is_err() {
+            return Err(format!("Worktree {name} does not exist"));
+        }
+    }
+
+    let from_path = directory.join(from);
+    let to_path = directory.join(to);
+
+    // All worktrees share the stash, so the entry is visible in the target
+    let stash = "stash@{0}";
+    if !repo::RepoHandle::stash_changes(&from_path, &format!("grm: moving changes to {to}"))? {
+        return Err(format!("Worktree {from} has no changes to move"));
+    }
+
+    if let Err(error) = repo::RepoHandle::apply_stash(&to_path, stash) {
+        if repo::RepoHandle::has_conflicts(&to_path)? {
+            return Ok(MovedChanges::Conflicts(stash.to_string()));
+        }
+        return match repo::RepoHandle::pop_stash(&from_path, stash) {
+            Ok(()) => Err(format!(
+                "{error}. The changes were restored in worktree {from}"
+            )),
+            Err(_) => Err(format!("{error}. The changes are kept in {stash}")),
+        };
+    }
+
+    repo::RepoHandle::drop_stash(&from_path, stash)?;
+    Ok(MovedChanges::Clean)
+}
+
+/// Changes the remote branch that the branch of worktree `name` tracks
+///
+/// The remote branch has to exist already, unless `push` is set. Then it is
+/// created by pushing the local branch. Returns warnings that should be shown
+/// to the user.
+pub fn retrack_worktree(
+    directory: &Path,
+    name: &str,
+    remote_name: &str,
+    remote_branch_name: &str,
+    push: bool,
+) -> Result<Vec<String>, String> {
+    let mut warnings = vec![];
+
+    let repo = repo::RepoHandle::open(directory, true).map_err(|error| match error.kind {
+        repo::RepoErrorKind::NotFound => {
+            String::from("Current directory does not contain a worktree setup")
+        }
+        repo::RepoErrorKind::Unknown(_) => format!("Error opening repo: {error}"),
+    })?;
+
+    if repo.find_worktree(name).is_err() {
+        return Err(format!("Worktree {name} does not exist"));
+    }
+
+    let worktree_repo = repo::RepoHandle::open(&directory.join(name), false)
+        .map_err(|error| format!("Error opening worktree {name}: {error}"))?;
+    let mut branch = worktree_repo
+        .head_branch()
+        .map_err(|error| format!("Worktree {name} has no branch checked out: {error}"))?;
+    let branch_name = branch.name()?;
+
+    let Some(mut remote) = repo.find_remote(remote_name)? else {
+        return Err(format!("Remote \"{remote_name}\" not found"));
+    };
+
+    match repo.find_remote_branch(remote_name, remote_branch_name) {
+        Ok(remote_branch) => {
+            if branch.commit()?.id().hex_string() != remote_branch.commit()?.id().hex_string() {
+                warnings.push(format!("The local branch \"{branch_name}\" and the remote branch \"{remote_name}/{remote_branch_name}\" differ. Make sure to push/pull afterwards!"));
+            }
+        }
+        Err(_) => {
+            if !push {
+                return Err(format!(
+                    "Remote branch \"{remote_name}/{remote_branch_name}\" does not exist. Run grm wt fetch, or use --push to create it"
+                ));
+            }
+            remote.push(&branch_name, remote_branch_name, &repo)?;
+        }
+    }
+
+    branch.set_upstream(remote_name, remote_branch_name)?;
+
+    Ok(warnings)
+}
+
+/// Whether `a` and `b` point to the same file, following symlinks
+fn same_path(a: &Path, b: &Path) -> bool {
+    a == b
+        || match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+}
+
+/// Finds all directories below `directory` that contain a file called `marker`,
+/// returned relative to `directory`
+///
+/// Worktrees (and their administrative directories in the main repository) with
+/// a slash in their name are nested, so this has to descend into directories.
+fn find_marked_dirs(directory: &Path, prefix: &Path, marker: &str) -> Result<Vec<PathBuf>, String> {
+    let mut found = vec![];
+    let entries = std::fs::read_dir(directory.join(prefix))
+        .map_err(|error| format!("Error reading directory: {error}"))?;
+    for entry in entries {
+        let entry = entry.map_err(|error| format!("Error reading directory: {error}"))?;
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+            || entry.file_name() == GIT_MAIN_WORKTREE_DIRECTORY
+        {
+            continue;
+        }
+        let relative = prefix.join(entry.file_name());
+        let marker_path = entry.path().join(marker);
+        if marker_path.is_file() {
+            found.push(relative);
+        } else if !marker_path.exists() {
+            found.extend(find_marked_dirs(directory, &relative, marker)?);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Reads the path that the `.git` file of the worktree in `worktree_dir` points
+/// to, or `None` if the file does not contain a path
+fn read_gitdir(worktree_dir: &Path) -> Result<Option<PathBuf>, std::io::Error> {
+    let content = std::fs::read_to_string(worktree_dir.join(".git"))?;
+    // Relative paths are relative to the worktree
+    Ok(content
+        .trim()
+        .strip_prefix("gitdir:")
+        .map(|gitdir| worktree_dir.join(gitdir.trim())))
+}
+
+/// Gets the name of the administrative directory of a worktree from the path in
+/// its `.git` file
+///
+/// libgit2 names the directory like the worktree, so it may contain slashes,
+/// while git only uses the last part of the name.
+fn admin_id(gitdir: &Path) -> Option<PathBuf> {
+    let components: Vec<_> = gitdir.components().collect();
+    components
+        .windows(2)
+        .rposition(|pair| {
+            pair[0].as_os_str() == GIT_MAIN_WORKTREE_DIRECTORY && pair[1].as_os_str() == "worktrees"
+        })
+        .map(|position| components[position + 2..].iter().collect::<PathBuf>())
+        .filter(|id| !id.as_os_str().is_empty())
+        .or_else(|| gitdir.file_name().map(PathBuf::from))
+}
+
+/// Whether the worktree in `worktree_dir` points to a repository that does not
+/// exist, which happens when the worktree setup was moved
+pub fn has_stale_link(worktree_dir: &Path) -> bool {
+    matches!(read_gitdir(worktree_dir), Ok(Some(gitdir)) if !gitdir.is_dir())
+}
+
+/// Rewrites the links between the worktrees in `directory` and the main
+/// repository, e.g. after the worktree setup was moved or restored from a backup
+///
+/// Mirrors `git worktree repair`: The `.git` file of each worktree points to
+/// its administrative directory in the main repository, which points back to
+/// the worktree in its `gitdir` file. Both contain absolute paths, so they break
+/// when the setup is moved. Returns the names of the repaired worktrees and
+/// warnings that should be shown to the user.
+pub fn repair_worktrees(directory: &Path) -> Result<(Vec<String>, Vec<String>), String> {
+    let common_dir = directory.join(GIT_MAIN_WORKTREE_DIRECTORY);
+    if !common_dir.is_dir() {
+        return Err(String::from(
+            "Current directory does not contain a worktree setup",
+        ));
+    }
+    let admin_root = common_dir.join("worktrees");
+
+    let write = |path: &Path, content: String| {
+        std::fs::write(path, content)
+            .map_err(|error| format!("Error writing \"{}\": {error}", path::path_as_string(path)))
+    };
+
+    let mut repaired = vec![];
+    let mut warnings = vec![];
+    let mut linked = vec![];
+
+    for worktree in find_marked_dirs(directory, Path::new(""), ".git")? {
+        let name = path::path_as_string(&worktree);
+        let worktree_dir = directory.join(&worktree);
+        let git_file = worktree_dir.join(".git");
+
+        let Some(gitdir) = read_gitdir(&worktree_dir)
+            .map_err(|error| format!("Error reading \"{name}/.git\": {error}"))?
+        else {
+            warnings.push(format!("{name}: \".git\" does not point to a repository"));
+            continue;
+        };
+        let Some(admin_dir) = admin_id(&gitdir).map(|id| admin_root.join(id)) else {
+            warnings.push(format!("{name}: \".git\" does not point to a repository"));
+            continue;
+        };
+        if !admin_dir.is_dir() {
+            warnings.push(format!(
+                "{name}: Not a worktree of the repository in \"{}\"",
+                path::path_as_string(&common_dir)
+            ));
+            continue;
+        }
+        linked.push(admin_dir.clone());
+
+        let mut changed = false;
+        if !same_path(&gitdir, &admin_dir) {
+            write(
+                &git_file,
+                format!("gitdir: {}\n", path::path_as_string(&admin_dir)),
+            )?;
+            changed = true;
+        }
+
+        let gitdir_file = admin_dir.join("gitdir");
+        let backlink = std::fs::read_to_string(&gitdir_file).unwrap_or_default();
+        if backlink.trim().is_empty() || !same_path(&admin_dir.join(backlink.trim()), &git_file) {
+            write(
+                &gitdir_file,
+                format!("{}\n", path::path_as_string(&git_file)),
+            )?;
+            changed = true;
+        }
+
+        let commondir_file = admin_dir.join("commondir");
+        if let Ok(commondir) = std::fs::read_to_string(&commondir_file) {
+            if !same_path(&admin_dir.join(commondir.trim()), &common_dir) {
+                write(
+                    &commondir_file,
+                    format!("{}\n", path::path_as_string(&common_dir)),
+                )?;
+                changed = true;
+            }
+        }
+
+        if changed {
+            repaired.push(name);
+        }
+    }
+
+    if admin_root.is_dir() {
+        for id in find_marked_dirs(&admin_root, Path::new(""), "gitdir")? {
+            if !linked.contains(&admin_root.join(&id)) {
+                warnings.push(format!(
+                    "No worktree found for \"{}\", remove it with \"git worktree prune\"",
+                    path::path_as_string(&id)
+                ));
+            }
+        }
+    }
+
+    Ok((repaired, warnings))
+}
+
+/// A worktree as listed in the state file of a worktree setup
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateWorktree {
+    pub name: String,
+    /// Unset if HEAD is detached
+    pub branch: Option<String>,
+    /// The remote tracking branch, e.g. `origin/main`
+    pub upstream: Option<String>,
+    /// The time of the last commit on the branch, as a unix timestamp
+    pub commit_time: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetupState {
+    /// When the file was written, as a unix timestamp
+    pub updated: u64,
+    pub worktrees: Vec<StateWorktree>,
+}
+
+fn state_worktree(directory: &Path, worktree: &repo::Worktree) -> Result<StateWorktree, String> {
+    let repo = repo::RepoHandle::open(&directory.join(worktree.name()), false)
+        .map_err(|error| format!("Error opening worktree {}: {}", worktree.name(), error))?;
+
+    let (branch, upstream, commit_time) = match repo.head_branch() {
+        Ok(branch) => (
+            Some(branch.name()?),
+            match branch.upstream() {
+                Ok(upstream) => Some(upstream.name()?),
+                Err(_) => None,
+            },
+            Some(branch.commit()?.time()),
+        ),
+        Err(_) => (None, None, None),
+    };
+
+    Ok(StateWorktree {
+        name: worktree.name().to_string(),
+        branch,
+        upstream,
+        commit_time,
+    })
+}
+
+/// Writes the worktrees of the worktree setup at `directory` to
+/// [`STATE_FILE_NAME`]
+///
+/// Shell prompts and editor plugins can read the file instead of running grm
+/// or opening the repository themselves. grm rewrites it after each command
+/// that changes the worktrees, so it may be outdated after changes made with
+/// git directly.
+pub fn write_state(directory: &Path) -> Result<(), String> {
+    let repo = repo::RepoHandle::open(directory, true)
+        .map_err(|error| format!("Error opening repository: {error}"))?;
+    write_state_with(&repo, directory)
+}
+
+/// Like [`write_state()`], for the already opened worktree setup `repo` at
+/// `directory`
+pub fn write_state_with(repo: &repo::RepoHandle, directory: &Path) -> Result<(), String> {
+    let worktrees = repo
+        .get_worktrees()?
+        .iter()
+        // Worktrees without a directory are reported by "grm wt status" already
+        .filter(|worktree| directory.join(worktree.name()).is_dir())
+        .map(|worktree| state_worktree(directory, worktree))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let content = serde_json::to_string_pretty(&SetupState {
+        updated: notify::now()?,
+        worktrees,
+    })
+    .map_err(|error| format!("Error serializing worktree state: {error}"))?;
+
+    // Write to a temporary file first, so readers never see half a file
+    let path = directory.join(STATE_FILE_NAME);
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&temporary, content)
+        .map_err(|error| format!("Error writing \"{}\": {}", temporary.display(), error))?;
+    std::fs::rename(&temporary, &path)
+        .map_err(|error| format!("Error writing \"{}\": {}", path.display(), error))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -772,4 +1668,76 @@ mod tests {
         assert!(add_worktree(Path::new("/tmp/"), "test test", None, false).is_err());
         assert!(add_worktree(Path::new("/tmp/"), "test\ttest", None, false).is_err());
     }
+
+    #[test]
+    fn check_parse_worktree_names() {
+        assert_eq!(
+            parse_worktree_names("feature-a\n\n  feature-b  \n# done\nfeature-a\nfix/c\n"),
+            vec!["feature-a", "feature-b", "fix/c"]
+        );
+        assert!(parse_worktree_names("\n# nothing\n").is_empty());
+    }
+
+    #[test]
+    fn check_admin_id() {
+        assert_eq!(
+            admin_id(Path::new("/old/.git-main-working-tree/worktrees/fix/b/")),
+            Some(PathBuf::from("fix/b"))
+        );
+        assert_eq!(
+            admin_id(Path::new("/old/.git-main-working-tree/worktrees/test")),
+            Some(PathBuf::from("test"))
+        );
+        assert_eq!(
+            admin_id(Path::new("/old/.git/worktrees/test")),
+            Some(PathBuf::from("test"))
+        );
+    }
+
+    #[test]
+    fn check_build_open_command() {
+        assert_eq!(
+            build_open_command("code {path}", Path::new("/tmp/repo/feature")),
+            "code '/tmp/repo/feature'"
+        );
+        assert_eq!(
+            build_open_command("nvim", Path::new("/tmp/repo/feature")),
+            "nvim '/tmp/repo/feature'"
+        );
+        assert_eq!(
+            build_open_command("cd {path} && make", Path::new("/tmp/it's")),
+            r"cd '/tmp/it'\''s' && make"
+        );
+    }
+
+    fn candidate(remote: &str, branch: &str) -> RemoteBranchCandidate {
+        RemoteBranchCandidate {
+            remote: remote.to_string(),
+            branch: branch.to_string(),
+            age_days: 0,
+        }
+    }
+
+    #[test]
+    fn check_find_remote_branch_candidate() {
+        let candidates = [
+            candidate("origin", "feature/login"),
+            candidate("origin", "fix"),
+            candidate("upstream", "fix"),
+        ];
+
+        let found = find_remote_branch_candidate(&candidates, "feature/login").unwrap();
+        assert_eq!(
+            (found.remote.as_str(), found.prefix()),
+            ("origin", Some("feature"))
+        );
+
+        let found = find_remote_branch_candidate(&candidates, "upstream/fix").unwrap();
+        assert_eq!(found.remote, "upstream");
+
+        assert!(find_remote_branch_candidate(&candidates, "fix")
+            .unwrap_err()
+            .contains("origin, upstream"));
+        assert!(find_remote_branch_candidate(&candidates, "missing").is_err());
+    }
 }
@@ -41,3 +41,19 @@ fn create_repo_with_worktree() -> Result<(), Box<dyn std::error::Error>> {
     cleanup_tmpdir(tmpdir);
     Ok(())
 }
+
+#[test]
+fn session_keeps_remotes_up_to_date() -> Result<(), Box<dyn std::error::Error>> {
+    let tmpdir = init_tmpdir();
+    RepoHandle::init(tmpdir.path(), false)?;
+    let session = RepoSession::open(tmpdir.path(), false)?;
+    assert!(session.remotes()?.is_empty());
+
+    session.new_remote("origin", "https://example.com/repo.git")?;
+    assert_eq!(session.remotes()?, vec![String::from("origin")]);
+
+    session.remote_delete("origin")?;
+    assert!(session.remotes()?.is_empty());
+    cleanup_tmpdir(tmpdir);
+    Ok(())
+}